@@ -0,0 +1,76 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use chronoturin::{presets, render_frame, Camera, DilationMode, RenderSettings, SimConfig, Simulation};
+
+const SEED: u64 = 42;
+
+fn config(stars: u32) -> SimConfig {
+    SimConfig { stars, seed: Some(SEED), ..SimConfig::default() }
+}
+
+fn generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generation");
+    for stars in [1_000, 10_000, 100_000] {
+        let config = config(stars);
+        group.bench_with_input(BenchmarkId::from_parameter(stars), &config, |b, config| b.iter(|| presets::from_config(config, SEED)));
+    }
+    group.finish();
+}
+
+// A freshly generated galaxy stands in for a readback: the splatter only reads positions and flags.
+fn raster(c: &mut Criterion) {
+    let mut group = c.benchmark_group("raster");
+    for stars in [10_000, 100_000] {
+        let config = config(stars);
+        let readback = presets::from_config(&config, SEED);
+        let (camera, settings) = (Camera::from_config(&config), RenderSettings::from_config(&config));
+        for mode in [DilationMode::Newtonian, DilationMode::Chronoturin] {
+            let id = BenchmarkId::new(mode.file_stem(), stars);
+            group.bench_function(id, |b| b.iter(|| render_frame(&readback, &camera, &settings, mode == DilationMode::Chronoturin)));
+        }
+    }
+    group.finish();
+}
+
+// Each iteration steps from the same initial galaxy, then reads it back once; the reset is
+// not timed.
+fn gpu_steps(c: &mut Criterion) {
+    let Some((device, queue)) = pollster::block_on(async {
+        let instance = wgpu::Instance::default();
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+        adapter.request_device(&wgpu::DeviceDescriptor::default(), None).await.ok()
+    }) else {
+        eprintln!("skipping gpu_steps: no GPU adapter available");
+        return;
+    };
+    let (device, queue) = (Arc::new(device), Arc::new(queue));
+    const STEPS: usize = 10;
+    let mut group = c.benchmark_group("gpu_steps");
+    group.sample_size(10);
+    for stars in [10_000, 50_000] {
+        let mut sim = Simulation::new(device.clone(), queue.clone(), &config(stars)).expect("the default adapter holds the bench galaxy");
+        for mode in [DilationMode::Newtonian, DilationMode::Chronoturin] {
+            let id = BenchmarkId::new(mode.file_stem(), stars);
+            group.bench_function(id, |b| {
+                b.iter_custom(|iters| {
+                    let mut total = Duration::ZERO;
+                    for _ in 0..iters {
+                        sim.reset();
+                        sim.set_mode(mode);
+                        let start = Instant::now();
+                        black_box(sim.step_and_read(STEPS));
+                        total += start.elapsed();
+                    }
+                    total
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, generation, raster, gpu_steps);
+criterion_main!(benches);
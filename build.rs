@@ -0,0 +1,22 @@
+use std::path::Path;
+use std::process::Command;
+
+// Records `git describe` for RunManifest when building from a checkout; a source tarball
+// builds without it.
+fn main() {
+    let describe = Command::new("git").args(["describe", "--tags", "--always", "--dirty"]).output();
+    if let Some(out) = describe.ok().filter(|out| out.status.success()) {
+        let describe = String::from_utf8_lossy(&out.stdout);
+        if !describe.trim().is_empty() {
+            println!("cargo:rustc-env=CHRONOTURIN_GIT_DESCRIBE={}", describe.trim());
+        }
+    }
+    // Described again after a commit, checkout or `git add`; edits that are never staged
+    // keep the last `-dirty` suffix until one of those happens.
+    println!("cargo:rerun-if-changed=build.rs");
+    for path in [".git/HEAD", ".git/index", ".git/refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
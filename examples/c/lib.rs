@@ -0,0 +1,3 @@
+// Links chronoturin's C API (src/capi.rs) into libchronoturin_c, shared and static, for C and
+// C++ programs; include/chronoturin.h declares it and step.c uses it. See README.md.
+pub use chronoturin::capi::*;
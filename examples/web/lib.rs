@@ -0,0 +1,4 @@
+// Links chronoturin's WebSimulation bindings into one wasm module for wasm-bindgen; see
+// index.html for the page and README.md for the build. Empty on native targets.
+#[cfg(target_arch = "wasm32")]
+pub use chronoturin::WebSimulation;
@@ -0,0 +1,133 @@
+/* The Chronoturin C API: a GPU galaxy simulation behind an opaque pointer. */
+
+#ifndef CHRONOTURIN_H
+#define CHRONOTURIN_H
+
+/* Generated by cbindgen from src/capi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The call succeeded.
+#define CHRONO_OK 0
+
+// A pointer that must not be NULL was.
+#define CHRONO_ERROR_NULL 1
+
+// The configuration, a flag in argv or a mode was invalid.
+#define CHRONO_ERROR_CONFIG 2
+
+// No adapter, a device that would not open or was lost, or a failed readback.
+#define CHRONO_ERROR_GPU 3
+
+// The simulation panicked. The ChronoSim involved should be destroyed, not used again.
+#define CHRONO_ERROR_PANIC 4
+
+// Every star is stepped every step.
+#define CHRONO_MODE_NEWTONIAN 0
+
+// Stars under the dilation threshold sleep and catch up later.
+#define CHRONO_MODE_CHRONOTURIN 1
+
+#define CHRONO_TYPE_A 0.0
+
+#define CHRONO_TYPE_B 1.0
+
+#define CHRONO_TYPE_CENTRAL -1.0
+
+#define CHRONO_DEAD_FLAG -1.0
+
+// A simulation on its own GPU device. Opaque; create it with chrono_sim_create and free it
+// with chrono_sim_destroy. It may move between threads, but not be used by two at once.
+typedef struct ChronoSim ChronoSim;
+
+// What chrono_sim_create builds. Start from chrono_config_default().
+typedef struct ChronoConfig {
+  // Number of stars; 0 keeps the default (or --stars in argv).
+  uint32_t stars;
+  // Seed of the initial galaxy; the same seed gives the same galaxy.
+  uint64_t seed;
+  // CHRONO_MODE_NEWTONIAN or CHRONO_MODE_CHRONOTURIN.
+  int mode;
+  // Any further flags of the chronoturin binary, e.g. {"--integrator", "leapfrog"}, as
+  // argc UTF-8 strings. argv may be NULL when argc is 0.
+  size_t argc;
+  const char *const *argv;
+} ChronoConfig;
+
+typedef struct Star {
+  float x;
+  float y;
+  float z;
+  float vx;
+  float vy;
+  float vz;
+  float mass;
+  float data_type;
+  float time_debt;
+  float active_flag;
+} Star;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The binary's default star count, seed 0, Chronoturin mode and no further flags.
+struct ChronoConfig chrono_config_default(void);
+
+// Opens a GPU device and generates the galaxy, blocking until both are ready. Returns NULL
+// on failure; chrono_sim_last_error says why.
+//
+// # Safety
+// `config` is NULL or points to a ChronoConfig whose argv holds argc NUL-terminated strings.
+struct ChronoSim *chrono_sim_create(const struct ChronoConfig *config);
+
+// Runs n_steps steps and waits for the GPU to finish them. Returns a CHRONO_ status.
+//
+// # Safety
+// `sim` is NULL or a live pointer from chrono_sim_create.
+int chrono_sim_step(struct ChronoSim *sim, uint32_t n_steps);
+
+// The number of stars, for sizing the buffer of chrono_sim_read; 0 on failure.
+//
+// # Safety
+// `sim` is NULL or a live pointer from chrono_sim_create.
+size_t chrono_sim_star_count(struct ChronoSim *sim);
+
+// Reads the stars back into `out`, at most `cap` of them, and returns how many it wrote.
+// Returns 0 on failure; chrono_sim_last_error tells that apart from a cap of 0.
+//
+// # Safety
+// `sim` is NULL or a live pointer from chrono_sim_create, and `out` has room for `cap` stars
+// (it may be NULL when `cap` is 0).
+size_t chrono_sim_read(struct ChronoSim *sim, struct Star *out, size_t cap);
+
+// Switches between CHRONO_MODE_NEWTONIAN and CHRONO_MODE_CHRONOTURIN from the next step on,
+// without resetting the galaxy. Returns a CHRONO_ status.
+//
+// # Safety
+// `sim` is NULL or a live pointer from chrono_sim_create.
+int chrono_sim_set_mode(struct ChronoSim *sim, int mode);
+
+// Frees the simulation and its device. NULL is ignored.
+//
+// # Safety
+// `sim` is NULL or a pointer from chrono_sim_create that is not used again.
+void chrono_sim_destroy(struct ChronoSim *sim);
+
+// The CHRONO_ status of this thread's last call to a chrono_ function: CHRONO_OK if it
+// succeeded.
+int chrono_sim_last_error(void);
+
+// What went wrong in this thread's last call, or "" if it succeeded. The string belongs to
+// the library and stays valid until the thread's next call to a chrono_ function.
+const char *chrono_sim_last_error_message(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHRONOTURIN_H */
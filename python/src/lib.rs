@@ -0,0 +1,9 @@
+// Links chronoturin's Python bindings (src/python.rs) into the extension module that
+// `import chronoturin` loads.
+use pyo3::prelude::*;
+
+#[pymodule]
+#[pyo3(name = "chronoturin")]
+fn chronoturin_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    chronoturin::python::register(module)
+}
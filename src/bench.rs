@@ -0,0 +1,362 @@
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{sync::Arc, time::Instant};
+
+use serde::Serialize;
+
+use crate::bindings::UniformPath;
+use crate::simulation::TimingSource;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    config::SimConfig,
+    error::ChronoError,
+    gpu::DeviceErrors,
+    interrupt, logging, presets,
+    simulation::{DilationMode, Simulation},
+};
+
+// Two-sided 95% normal quantile for the speedup interval.
+const Z_95: f64 = 1.96;
+
+// `chronoturin [flags] bench --stars N --steps 500 --warmup 50 [--json bench.json]`.
+#[derive(clap::Args, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", default, deny_unknown_fields)]
+pub struct BenchArgs {
+    /// Number of stars (defaults to the --stars before `bench`)
+    #[arg(long)]
+    pub stars: Option<u32>,
+
+    /// Timed steps per dilation mode
+    #[arg(long, default_value_t = 500)]
+    pub steps: usize,
+
+    /// Untimed steps per mode before measuring (pipeline warmup, clocks ramping up)
+    #[arg(long, default_value_t = 50)]
+    pub warmup: usize,
+
+    /// Also write the report as JSON to this file, for CI tracking
+    #[arg(long)]
+    pub json: Option<PathBuf>,
+
+    /// Also time both modes with --unified-kernel, to see what the per-mode kernels save
+    #[arg(long)]
+    pub compare_kernels: bool,
+
+    /// Force how GalaxyState reaches the kernels (defaults to the --uniform-path before `bench`)
+    #[arg(long, value_enum)]
+    pub uniform_path: Option<UniformPath>,
+}
+
+// The flag defaults, for a --config [command.bench] table that leaves some out.
+impl Default for BenchArgs {
+    fn default() -> Self {
+        use clap::{Args, FromArgMatches};
+        let matches = Self::augment_args(clap::Command::new("bench")).get_matches_from(["bench"]);
+        Self::from_arg_matches(&matches).expect("every bench flag has a default")
+    }
+}
+
+// Distribution of per-step times for one mode, in milliseconds.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct StepStats {
+    #[serde(skip)]
+    pub samples: usize,
+    #[serde(rename = "mean_ms")]
+    pub mean: f64,
+    #[serde(rename = "median_ms")]
+    pub median: f64,
+    #[serde(rename = "p95_ms")]
+    pub p95: f64,
+    #[serde(rename = "std_dev_ms")]
+    pub std_dev: f64,
+}
+
+impl StepStats {
+    pub fn from_samples(samples: &[f32]) -> Self {
+        let mut sorted: Vec<f64> = samples.iter().map(|&v| v as f64).collect();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+        if n == 0 {
+            return Self { samples: 0, mean: 0.0, median: 0.0, p95: 0.0, std_dev: 0.0 };
+        }
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let median = if n % 2 == 1 { sorted[n / 2] } else { 0.5 * (sorted[n / 2 - 1] + sorted[n / 2]) };
+        // Nearest-rank percentile, like the displacement statistics
+        let p95 = sorted[((n as f64 * 0.95).ceil() as usize).clamp(1, n) - 1];
+        // Sample standard deviation
+        let var = if n > 1 { sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64 } else { 0.0 };
+        Self { samples: n, mean, median, p95, std_dev: var.sqrt() }
+    }
+
+    fn relative_standard_error(&self) -> f64 {
+        if self.samples == 0 || self.mean == 0.0 {
+            0.0
+        } else {
+            self.std_dev / (self.samples as f64).sqrt() / self.mean
+        }
+    }
+}
+
+// Newtonian mean over Chronoturin mean, with a 95% interval from the delta method
+// (relative standard errors of the two means added in quadrature).
+pub fn speedup(newton: &StepStats, chrono: &StepStats) -> (f64, f64, f64) {
+    if chrono.mean == 0.0 {
+        return (0.0, 0.0, 0.0);
+    }
+    let ratio = newton.mean / chrono.mean;
+    let rel = newton.relative_standard_error().hypot(chrono.relative_standard_error());
+    (ratio, ratio * (1.0 - Z_95 * rel), ratio * (1.0 + Z_95 * rel))
+}
+
+// The same steps again with the stars Morton-sorted every `every` frames (--sort-every).
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct SortedBench {
+    pub every: usize,
+    #[serde(rename = "newtonian")]
+    pub newton: StepStats,
+    #[serde(rename = "chronoturin")]
+    pub chrono: StepStats,
+    // Wall clock of each sort pass, which the step times leave out
+    pub sort: StepStats,
+}
+
+// The same steps again with the single kernel of --unified-kernel (bench --compare-kernels).
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct UnifiedBench {
+    #[serde(rename = "newtonian")]
+    pub newton: StepStats,
+    #[serde(rename = "chronoturin")]
+    pub chrono: StepStats,
+}
+
+// Serialized as the measurements alone; json() adds the speedups worked out from them.
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchReport {
+    pub stars: u32,
+    pub steps: usize,
+    pub warmup: usize,
+    pub timing: TimingSource,
+    pub uniform_path: UniformPath,
+    #[serde(rename = "newtonian")]
+    pub newton: StepStats,
+    #[serde(rename = "chronoturin")]
+    pub chrono: StepStats,
+    #[serde(skip)]
+    pub sorted: Option<SortedBench>,
+    #[serde(skip)]
+    pub unified: Option<UnifiedBench>,
+}
+
+// A bench run beside another, with how much faster each mode's mean got.
+#[derive(Serialize)]
+struct Compared<T: Serialize> {
+    #[serde(flatten)]
+    bench: T,
+    newtonian_speedup: f64,
+    chronoturin_speedup: f64,
+}
+
+// What bench --json writes.
+#[derive(Serialize)]
+struct BenchJson<'a> {
+    #[serde(flatten)]
+    report: &'a BenchReport,
+    speedup: f64,
+    speedup_ci95: [f64; 2],
+    // Only present when sorting was benchmarked, so existing consumers see the same keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sorted: Option<Compared<SortedBench>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unified: Option<Compared<UnifiedBench>>,
+}
+
+impl BenchReport {
+    pub fn speedup(&self) -> (f64, f64, f64) {
+        speedup(&self.newton, &self.chrono)
+    }
+
+    pub fn table(&self) -> String {
+        let mut table = format!(
+            "Stars: {} | Steps: {} timed after {} warmup per mode | Timing: {} | GalaxyState: {}\n\n\
+             | Mode | Mean ms | Median ms | p95 ms | Std dev ms |\n\
+             |------|---------|-----------|--------|------------|\n",
+            self.stars,
+            self.steps,
+            self.warmup,
+            self.timing.name(),
+            self.uniform_path.name()
+        );
+        for (name, s) in [("NEWTONIAN", &self.newton), ("CHRONOTURIN", &self.chrono)] {
+            table.push_str(&format!("| {} | {:.4} | {:.4} | {:.4} | {:.4} |\n", name, s.mean, s.median, s.p95, s.std_dev));
+        }
+        if let Some(sorted) = &self.sorted {
+            for (name, s) in [("NEWTONIAN (sorted)", &sorted.newton), ("CHRONOTURIN (sorted)", &sorted.chrono)] {
+                table.push_str(&format!("| {} | {:.4} | {:.4} | {:.4} | {:.4} |\n", name, s.mean, s.median, s.p95, s.std_dev));
+            }
+        }
+        if let Some(unified) = &self.unified {
+            for (name, s) in [("NEWTONIAN (unified)", &unified.newton), ("CHRONOTURIN (unified)", &unified.chrono)] {
+                table.push_str(&format!("| {} | {:.4} | {:.4} | {:.4} | {:.4} |\n", name, s.mean, s.median, s.p95, s.std_dev));
+            }
+        }
+        let (ratio, lo, hi) = self.speedup();
+        table.push_str(&format!("\nSpeedup: {:.2}× (95% CI {:.2}× to {:.2}×)\n", ratio, lo, hi));
+        if let Some(sorted) = &self.sorted {
+            let (newton, chrono) = (speedup(&self.newton, &sorted.newton), speedup(&self.chrono, &sorted.chrono));
+            table.push_str(&format!(
+                "Sorting every {} frames: Newtonian {:.2}× (95% CI {:.2}× to {:.2}×), Chronoturin {:.2}× (95% CI {:.2}× to {:.2}×) | Sort pass: {:.4} ms mean\n",
+                sorted.every, newton.0, newton.1, newton.2, chrono.0, chrono.1, chrono.2, sorted.sort.mean
+            ));
+        }
+        if let Some(unified) = &self.unified {
+            let (newton, chrono) = (speedup(&unified.newton, &self.newton), speedup(&unified.chrono, &self.chrono));
+            table.push_str(&format!(
+                "Per-mode kernels over --unified-kernel: Newtonian {:.2}× (95% CI {:.2}× to {:.2}×), Chronoturin {:.2}× (95% CI {:.2}× to {:.2}×)\n",
+                newton.0, newton.1, newton.2, chrono.0, chrono.1, chrono.2
+            ));
+        }
+        table
+    }
+
+    pub fn json(&self) -> String {
+        let (ratio, lo, hi) = self.speedup();
+        let sorted = self.sorted.map(|sorted| Compared {
+            bench: sorted,
+            newtonian_speedup: speedup(&self.newton, &sorted.newton).0,
+            chronoturin_speedup: speedup(&self.chrono, &sorted.chrono).0,
+        });
+        let unified = self.unified.map(|unified| Compared {
+            bench: unified,
+            newtonian_speedup: speedup(&unified.newton, &self.newton).0,
+            chronoturin_speedup: speedup(&unified.chrono, &self.chrono).0,
+        });
+        let json = BenchJson { report: self, speedup: ratio, speedup_ci95: [lo, hi], sorted, unified };
+        serde_json::to_string(&json).expect("BenchReport serializes to JSON")
+    }
+}
+
+// --- BENCH: PER-STEP COMPUTE TIME OF BOTH MODES ---
+// The same galaxy for each mode; warmup steps run exactly like timed ones and are dropped.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run(
+    mut config: SimConfig,
+    args: &BenchArgs,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    errors: DeviceErrors,
+) -> Result<(), ChronoError> {
+    let seed = config.seed.unwrap_or_else(rand::random);
+    config.seed = Some(seed);
+    tracing::info!(
+        "Bench: {} stars | Preset: {:?} | {} warmup + {} timed steps per mode | Seed: {} | Accel: {} | Kernel: {}",
+        config.stars,
+        config.preset,
+        args.warmup,
+        args.steps,
+        seed,
+        config.accel.name(),
+        if config.unified_kernel { "unified" } else { "per mode" }
+    );
+
+    let stars = presets::from_config(&config, seed);
+    let mut sim = Simulation::with_stars(device, queue, &config, stars.clone())?;
+    sim.watch_device(errors);
+    tracing::info!("GalaxyState by {}", sim.uniform_path().describe());
+    // --compare-kernels steps a second simulation with the unified kernel (and without the
+    // bench command, which would refuse --unified-kernel); only that kernel is compiled again.
+    let mut unified_sim = match args.compare_kernels {
+        true => Some(sim.resized(&SimConfig { unified_kernel: true, command: None, ..config.clone() }, stars)?),
+        false => None,
+    };
+    let mut timing = TimingSource::WallClock;
+    let mut stats = Vec::with_capacity(2);
+    // With --sort-every, each mode runs a second time, sorted every that many frames' worth of steps.
+    let mut sorted = Vec::with_capacity(2);
+    let mut unified = Vec::with_capacity(2);
+    let mut sort_samples = Vec::new();
+    for mode in [DilationMode::Newtonian, DilationMode::Chronoturin] {
+        // (sort interval, unified kernel)
+        let mut variants = vec![(None, false)];
+        variants.extend(config.sort_every.map(|every| (Some(every * config.steps_per_frame), false)));
+        if unified_sim.is_some() {
+            variants.push((None, true));
+        }
+        for (sort_interval, unified_kernel) in variants {
+            let label = match (sort_interval, unified_kernel) {
+                (Some(_), _) => format!("[{} SORTED]", mode.name()),
+                (None, true) => format!("[{} UNIFIED]", mode.name()),
+                (None, false) => format!("[{}]", mode.name()),
+            };
+            let sim = if unified_kernel { unified_sim.as_mut().unwrap() } else { &mut sim };
+            sim.reset();
+            sim.set_mode(mode);
+            let sort_if_due = |sim: &mut Simulation, step: usize, samples: &mut Vec<f32>| {
+                if sort_interval.is_some_and(|every| step.is_multiple_of(every)) {
+                    let start = Instant::now();
+                    sim.sort_stars();
+                    sim.device().poll(wgpu::Maintain::Wait);
+                    samples.push(start.elapsed().as_secs_f32() * 1000.0);
+                }
+            };
+            for step in 0..args.warmup {
+                sort_if_due(sim, step, &mut Vec::new());
+                sim.time_step()?;
+            }
+            let mut samples = Vec::with_capacity(args.steps);
+            let bar = logging::progress_bar(args.steps as u64, label.clone());
+            for step in 0..args.steps {
+                if interrupt::requested() {
+                    bar.abandon();
+                    tracing::warn!("Interrupted: {} stopped after {} of {} timed steps; no report written", label, step, args.steps);
+                    return Ok(());
+                }
+                sort_if_due(sim, args.warmup + step, &mut sort_samples);
+                let (ms, source) = sim.time_step()?;
+                samples.push(ms);
+                timing = source;
+                if step % 50 == 0 {
+                    bar.set_message(format!("{:.3} ms/step", ms));
+                }
+                bar.inc(1);
+            }
+            bar.finish();
+            tracing::debug!("{} {} timed steps", label, args.steps);
+            let target = match (sort_interval, unified_kernel) {
+                (Some(_), _) => &mut sorted,
+                (None, true) => &mut unified,
+                (None, false) => &mut stats,
+            };
+            target.push(StepStats::from_samples(&samples));
+        }
+    }
+
+    let sorted = config.sort_every.map(|every| SortedBench {
+        every,
+        newton: sorted[0],
+        chrono: sorted[1],
+        sort: StepStats::from_samples(&sort_samples),
+    });
+    let unified = args.compare_kernels.then(|| UnifiedBench { newton: unified[0], chrono: unified[1] });
+    let report = BenchReport {
+        stars: config.stars,
+        steps: args.steps,
+        warmup: args.warmup,
+        timing,
+        uniform_path: sim.uniform_path(),
+        newton: stats[0],
+        chrono: stats[1],
+        sorted,
+        unified,
+    };
+    for line in report.table().lines() {
+        tracing::info!("{}", line);
+    }
+    if timing == TimingSource::WallClock {
+        tracing::info!("No TIMESTAMP_QUERY: times are submit-to-completion wall clock and include driver overhead.");
+    }
+    if let Some(path) = &args.json {
+        std::fs::write(path, report.json() + "\n")?;
+        tracing::info!("Wrote {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use std::num::NonZeroU64;
+
+use crate::error::ChronoError;
+use crate::galaxy::GalaxyState;
+
+// shader.wgsl's group 0, declared here for the kernels that step the stars instead of derived
+// from each entry point. Every pipeline built on one of these layouts takes the same bind
+// groups, whatever bindings its entry point happens to use, and GalaxyState takes a dynamic
+// offset into the ring of per-step states, or comes as push constants instead.
+pub const STATE: u32 = 1;
+// Each star chunk's: its stars and the other chunks', the state, the active counter and
+// its Chunk
+pub const CHUNK: [u32; 7] = [0, STATE, 2, 3, 4, 5, 6];
+// --accel grid's cells (grid::Grid::entries)
+pub const GRID: [u32; 3] = [7, 8, 9];
+// --compaction's active list and calm flags
+pub const COMPACTION: [u32; 2] = [10, 11];
+pub const INTERACTIONS: u32 = 13;
+
+const STATE_SIZE: u64 = std::mem::size_of::<GalaxyState>() as u64;
+const STATE_DECLARATION: &str = "@group(0) @binding(1) var<uniform> state: GalaxyState;";
+const PUSH_RANGES: [wgpu::PushConstantRange; 1] =
+    [wgpu::PushConstantRange { stages: wgpu::ShaderStages::COMPUTE, range: 0..STATE_SIZE as u32 }];
+
+// How each step's GalaxyState reaches the kernels (--uniform-path).
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UniformPath {
+    // A slot of the StateRing, bound by dynamic offset
+    Buffer,
+    // Push constants set ahead of each pass, with no buffer writes at all
+    Push,
+}
+
+impl UniformPath {
+    pub fn name(self) -> &'static str {
+        match self {
+            UniformPath::Buffer => "buffer",
+            UniformPath::Push => "push",
+        }
+    }
+
+    // For the log and `inspect`.
+    pub fn describe(self) -> &'static str {
+        match self {
+            UniformPath::Buffer => "uniform buffer",
+            UniformPath::Push => "push constants",
+        }
+    }
+
+    // Push constants whenever the device was opened with them and they hold a GalaxyState,
+    // unless `wanted` forces a path; forcing push without them is an error.
+    pub fn select(device: &wgpu::Device, wanted: Option<Self>) -> Result<Self, ChronoError> {
+        let push =
+            device.features().contains(wgpu::Features::PUSH_CONSTANTS) && device.limits().max_push_constant_size as u64 >= STATE_SIZE;
+        match wanted {
+            Some(UniformPath::Push) if !push => Err(ChronoError::InvalidConfig(format!(
+                "--uniform-path push needs an adapter with PUSH_CONSTANTS of at least {} bytes",
+                STATE_SIZE
+            ))),
+            Some(path) => Ok(path),
+            None if push => Ok(UniformPath::Push),
+            None => Ok(UniformPath::Buffer),
+        }
+    }
+
+    // The pipeline layout's push constant ranges on this path.
+    pub fn push_constant_ranges(self) -> &'static [wgpu::PushConstantRange] {
+        match self {
+            UniformPath::Buffer => &[],
+            UniformPath::Push => &PUSH_RANGES,
+        }
+    }
+
+    // `source` (shader.wgsl or --shader) with `state` declared for this path. The rewrite
+    // stays on its line, like the other specializations.
+    pub fn declare_state(self, source: &str) -> Result<String, String> {
+        match self {
+            UniformPath::Buffer => Ok(source.to_string()),
+            UniformPath::Push if source.contains(STATE_DECLARATION) => {
+                Ok(source.replacen(STATE_DECLARATION, "var<push_constant> state: GalaxyState;", 1))
+            }
+            UniformPath::Push => Err(format!("push constants need shader.wgsl's `{}`", STATE_DECLARATION)),
+        }
+    }
+}
+
+// The GalaxyState one pass steps with, as its path hands it over.
+#[derive(Copy, Clone)]
+pub enum StepState {
+    // Into the StateRing
+    Offset(u32),
+    Push(GalaxyState),
+}
+
+impl StepState {
+    // Binds `bind_group` as group 0 for the pipeline just set, with the state.
+    pub fn bind<'a>(&self, cpass: &mut wgpu::ComputePass<'a>, bind_group: &'a wgpu::BindGroup) {
+        match self {
+            StepState::Offset(offset) => cpass.set_bind_group(0, bind_group, &[*offset]),
+            StepState::Push(state) => {
+                cpass.set_bind_group(0, bind_group, &[]);
+                cpass.set_push_constants(0, bytemuck::bytes_of(state));
+            }
+        }
+    }
+}
+
+fn binding_type(binding: u32) -> wgpu::BindingType {
+    let (ty, has_dynamic_offset, min_binding_size) = match binding {
+        STATE => (wgpu::BufferBindingType::Uniform, true, NonZeroU64::new(STATE_SIZE)),
+        3 | 7 | INTERACTIONS => (wgpu::BufferBindingType::Uniform, false, None),
+        8 | 9 => (wgpu::BufferBindingType::Storage { read_only: true }, false, None),
+        _ => (wgpu::BufferBindingType::Storage { read_only: false }, false, None),
+    };
+    wgpu::BindingType::Buffer { ty, has_dynamic_offset, min_binding_size }
+}
+
+// The layout of `bindings` from shader.wgsl, visible to compute; STATE is left out on
+// the push path.
+pub fn layout(device: &wgpu::Device, label: &str, path: UniformPath, bindings: impl IntoIterator<Item = u32>) -> wgpu::BindGroupLayout {
+    let entries: Vec<_> = bindings
+        .into_iter()
+        .filter(|&binding| binding != STATE || path == UniformPath::Buffer)
+        .map(|binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: binding_type(binding),
+            count: None,
+        })
+        .collect();
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label: Some(label), entries: &entries })
+}
+
+// The GalaxyState of every step of a frame, each in its own slot of one uniform buffer, so
+// one encoder can step with a different clock per pass without waiting on the CPU. A frame
+// with more steps than slots loads them a ring's worth at a time.
+pub struct StateRing {
+    ring: wgpu::Buffer,
+    // Every step's state as the ring lays them out, copied into the ring in batches
+    upload: wgpu::Buffer,
+    stride: u64,
+    slots: usize,
+}
+
+impl StateRing {
+    pub fn new(device: &wgpu::Device, slots: usize) -> Self {
+        // Dynamic offsets must be multiples of this, 256 bytes on most devices.
+        let stride = STATE_SIZE.next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+        let slots = slots.max(1);
+        Self {
+            ring: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Galaxy State Ring"),
+                size: slots as u64 * stride,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            upload: create_upload(device, slots as u64 * stride),
+            stride,
+            slots,
+        }
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    // Binding STATE: one state's worth of the ring, placed by the dynamic offset.
+    pub fn entry(&self) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: STATE,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &self.ring,
+                offset: 0,
+                size: NonZeroU64::new(STATE_SIZE),
+            }),
+        }
+    }
+
+    // The dynamic offset step `k` of the staged states reads.
+    pub fn offset(&self, k: usize) -> u32 {
+        ((k % self.slots) as u64 * self.stride) as u32
+    }
+
+    // Queues `states` for the next submission: they land in the upload buffer ahead of it,
+    // and load() moves them into the ring between passes.
+    pub fn stage(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, states: &[GalaxyState]) {
+        let size = states.len() as u64 * self.stride;
+        if self.upload.size() < size {
+            self.upload = create_upload(device, size);
+        }
+        let mut bytes = vec![0u8; size as usize];
+        for (slot, state) in bytes.chunks_exact_mut(self.stride as usize).zip(states) {
+            slot[..STATE_SIZE as usize].copy_from_slice(bytemuck::bytes_of(state));
+        }
+        queue.write_buffer(&self.upload, 0, &bytes);
+    }
+
+    // Before step `k` of `n` staged ones: when it starts a batch, copies that batch into the
+    // ring. The passes before it have read their slots by then.
+    pub fn load(&self, encoder: &mut wgpu::CommandEncoder, k: usize, n: usize) {
+        if k.is_multiple_of(self.slots) {
+            let count = self.slots.min(n - k) as u64;
+            encoder.copy_buffer_to_buffer(&self.upload, k as u64 * self.stride, &self.ring, 0, count * self.stride);
+        }
+    }
+
+    // Writes `state` into slot 0 directly, for one step per submission (--legacy-submit).
+    pub fn write_first(&self, queue: &wgpu::Queue, state: &GalaxyState) {
+        queue.write_buffer(&self.ring, 0, bytemuck::bytes_of(state));
+    }
+
+    // Copies the state step `k` read into `dest`, for the passes that still bind a plain one.
+    pub fn copy_out(&self, encoder: &mut wgpu::CommandEncoder, k: usize, dest: &wgpu::Buffer) {
+        encoder.copy_buffer_to_buffer(&self.ring, self.offset(k) as u64, dest, 0, STATE_SIZE);
+    }
+}
+
+fn create_upload(device: &wgpu::Device, size: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Galaxy State Upload"),
+        size,
+        usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
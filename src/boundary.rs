@@ -0,0 +1,90 @@
+use std::fmt;
+
+use crate::galaxy::Star;
+
+// Where stars may go. Open is the original unbounded space; the other two keep slingshotted
+// stars from blowing out the camera framing and float precision.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    Open,
+    // A sphere of this radius around the origin: a star found outside it heading further out
+    // has its radial velocity reversed, so it overshoots by at most one step's travel
+    Reflect { radius: f32 },
+    // A cube of this side centered on the origin: positions wrap around it, and every
+    // separation is taken to the nearest image
+    Periodic { side: f32 },
+}
+
+// `open`, `reflect:R` or `periodic:L`.
+pub fn parse_boundary(arg: &str) -> Result<Boundary, String> {
+    let (kind, size) = match arg.split_once(':') {
+        Some((kind, size)) => (kind, Some(size)),
+        None => (arg, None),
+    };
+    let parse_size = |name: &str| -> Result<f32, String> {
+        let size = size.ok_or_else(|| format!("{} needs a size, as in {}:400", kind, kind))?;
+        match size.trim().parse::<f32>() {
+            Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+            _ => Err(format!("the {} {} must be a positive number, not `{}`", kind, name, size)),
+        }
+    };
+    match kind {
+        "open" if size.is_none() => Ok(Boundary::Open),
+        "open" => Err("open takes no size".into()),
+        "reflect" => Ok(Boundary::Reflect { radius: parse_size("radius")? }),
+        "periodic" => Ok(Boundary::Periodic { side: parse_size("side")? }),
+        _ => Err(format!("unknown boundary `{}`; expected open, reflect:R or periodic:L", kind)),
+    }
+}
+
+// As parse_boundary reads it, so --print-config round-trips.
+impl fmt::Display for Boundary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Open => write!(f, "open"),
+            Self::Reflect { radius } => write!(f, "reflect:{}", radius),
+            Self::Periodic { side } => write!(f, "periodic:{}", side),
+        }
+    }
+}
+
+// Keeps a camera margin around the boundary when --camera-z is left to its default.
+const FRAMING_MARGIN: f32 = 1.1;
+// The camera distance of open runs, which frames the default sphere preset
+const OPEN_CAMERA_Z: f32 = -1000.0;
+
+impl Boundary {
+    // GalaxyState's boundary_kind and boundary_size.
+    pub fn as_uniform(self) -> [f32; 2] {
+        match self {
+            Self::Open => [0.0, 0.0],
+            Self::Reflect { radius } => [1.0, radius],
+            Self::Periodic { side } => [2.0, side],
+        }
+    }
+
+    pub fn side(self) -> Option<f32> {
+        match self {
+            Self::Periodic { side } => Some(side),
+            _ => None,
+        }
+    }
+
+    // The default --camera-z: on the -Z axis, far enough that the sphere or the cube's front
+    // face fills the shorter image side with a margin at focal length `fov`.
+    pub fn camera_z(self, width: u32, height: u32, fov: f32) -> f32 {
+        let half = 0.5 * width.min(height) as f32;
+        let (near, extent) = match self {
+            Self::Open => return OPEN_CAMERA_Z,
+            Self::Reflect { radius } => (radius, radius),
+            Self::Periodic { side } => (0.5 * side, 0.5 * side),
+        };
+        -(near + FRAMING_MARGIN * extent * fov / half)
+    }
+}
+
+// `star` moved into the cube of `side` around the origin, as shader.wgsl's wrap does.
+pub fn wrap(star: &Star, side: f32) -> Star {
+    let w = |x: f32| x - side * (x / side + 0.5).floor();
+    Star { x: w(star.x), y: w(star.y), z: w(star.z), ..*star }
+}
@@ -0,0 +1,264 @@
+// The C API (capi feature): a simulation behind an opaque pointer, for embedding in C and
+// C++ programs. The `///` comments here are the documentation in include/chronoturin.h,
+// which cbindgen generates from this file (tests/capi.rs checks that it is current).
+//
+// Nothing unwinds into C: every function catches panics and reports them, like any other
+// failure, through chrono_sim_last_error.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use clap::Parser;
+
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+use crate::galaxy::Star;
+use crate::simulation::{DilationMode, Simulation};
+
+/// The call succeeded.
+pub const CHRONO_OK: c_int = 0;
+/// A pointer that must not be NULL was.
+pub const CHRONO_ERROR_NULL: c_int = 1;
+/// The configuration, a flag in argv or a mode was invalid.
+pub const CHRONO_ERROR_CONFIG: c_int = 2;
+/// No adapter, a device that would not open or was lost, or a failed readback.
+pub const CHRONO_ERROR_GPU: c_int = 3;
+/// The simulation panicked. The ChronoSim involved should be destroyed, not used again.
+pub const CHRONO_ERROR_PANIC: c_int = 4;
+
+/// Every star is stepped every step.
+pub const CHRONO_MODE_NEWTONIAN: c_int = 0;
+/// Stars under the dilation threshold sleep and catch up later.
+pub const CHRONO_MODE_CHRONOTURIN: c_int = 1;
+
+/// What chrono_sim_create builds. Start from chrono_config_default().
+#[repr(C)]
+pub struct ChronoConfig {
+    /// Number of stars; 0 keeps the default (or --stars in argv).
+    pub stars: u32,
+    /// Seed of the initial galaxy; the same seed gives the same galaxy.
+    pub seed: u64,
+    /// CHRONO_MODE_NEWTONIAN or CHRONO_MODE_CHRONOTURIN.
+    pub mode: c_int,
+    /// Any further flags of the chronoturin binary, e.g. {"--integrator", "leapfrog"}, as
+    /// argc UTF-8 strings. argv may be NULL when argc is 0.
+    pub argc: usize,
+    pub argv: *const *const c_char,
+}
+
+/// A simulation on its own GPU device. Opaque; create it with chrono_sim_create and free it
+/// with chrono_sim_destroy. It may move between threads, but not be used by two at once.
+pub struct ChronoSim {
+    sim: Simulation,
+}
+
+// One failed call: the code chrono_sim_last_error returns and its message.
+struct Failure {
+    code: c_int,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: c_int, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    fn null(what: &str) -> Self {
+        Self::new(CHRONO_ERROR_NULL, format!("{} is NULL", what))
+    }
+}
+
+impl From<ChronoError> for Failure {
+    fn from(error: ChronoError) -> Self {
+        let code = match error {
+            ChronoError::InvalidConfig(_) => CHRONO_ERROR_CONFIG,
+            _ => CHRONO_ERROR_GPU,
+        };
+        Self::new(code, error.to_string())
+    }
+}
+
+thread_local! {
+    // The failure of this thread's last call, if it failed
+    static LAST_ERROR: RefCell<Option<(c_int, CString)>> = const { RefCell::new(None) };
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => format!("panicked: {}", message),
+        (_, Some(message)) => format!("panicked: {}", message),
+        _ => "panicked".into(),
+    }
+}
+
+// Runs one API call: clears the last error, and turns a failure or a panic into it and None.
+fn guard<T>(call: impl FnOnce() -> Result<T, Failure>) -> Option<T> {
+    LAST_ERROR.with(|last| last.borrow_mut().take());
+    let failure = match panic::catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(value)) => return Some(value),
+        Ok(Err(failure)) => failure,
+        Err(payload) => Failure::new(CHRONO_ERROR_PANIC, panic_message(payload.as_ref())),
+    };
+    // A message with a NUL in it is cut short there rather than lost.
+    let mut bytes = failure.message.into_bytes();
+    bytes.truncate(bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len()));
+    let message = CString::new(bytes).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some((failure.code, message)));
+    None
+}
+
+// A guarded call whose result is only its CHRONO_ status.
+fn status(call: impl FnOnce() -> Result<(), Failure>) -> c_int {
+    guard(call).map_or_else(|| chrono_sim_last_error(), |()| CHRONO_OK)
+}
+
+fn dilation_mode(mode: c_int) -> Result<DilationMode, Failure> {
+    match mode {
+        CHRONO_MODE_NEWTONIAN => Ok(DilationMode::Newtonian),
+        CHRONO_MODE_CHRONOTURIN => Ok(DilationMode::Chronoturin),
+        _ => Err(Failure::new(CHRONO_ERROR_CONFIG, format!("mode {} is neither CHRONO_MODE_NEWTONIAN nor CHRONO_MODE_CHRONOTURIN", mode))),
+    }
+}
+
+unsafe fn sim_config(config: &ChronoConfig) -> Result<SimConfig, Failure> {
+    let mut args = vec!["chronoturin".to_string()];
+    if config.argc > 0 && config.argv.is_null() {
+        return Err(Failure::null("argv"));
+    }
+    for i in 0..config.argc {
+        let arg = *config.argv.add(i);
+        if arg.is_null() {
+            return Err(Failure::null(&format!("argv[{}]", i)));
+        }
+        let arg = CStr::from_ptr(arg).to_str().map_err(|_| Failure::new(CHRONO_ERROR_CONFIG, format!("argv[{}] is not UTF-8", i)))?;
+        args.push(arg.to_string());
+    }
+    let mut sim_config = SimConfig::try_parse_from(args).map_err(|e| Failure::new(CHRONO_ERROR_CONFIG, e.to_string()))?;
+    if config.stars != 0 {
+        sim_config.stars = config.stars;
+    }
+    sim_config.seed = Some(config.seed);
+    Ok(sim_config)
+}
+
+unsafe fn sim_mut<'a>(sim: *mut ChronoSim) -> Result<&'a mut Simulation, Failure> {
+    sim.as_mut().map(|sim| &mut sim.sim).ok_or_else(|| Failure::null("sim"))
+}
+
+// The device's first error or loss since the simulation was created, if any.
+fn check(sim: &Simulation) -> Result<(), Failure> {
+    sim.fault().map_or(Ok(()), |error| Err(error.into()))
+}
+
+/// The binary's default star count, seed 0, Chronoturin mode and no further flags.
+#[no_mangle]
+pub extern "C" fn chrono_config_default() -> ChronoConfig {
+    let defaults = SimConfig::default();
+    ChronoConfig { stars: defaults.stars, seed: 0, mode: CHRONO_MODE_CHRONOTURIN, argc: 0, argv: ptr::null() }
+}
+
+/// Opens a GPU device and generates the galaxy, blocking until both are ready. Returns NULL
+/// on failure; chrono_sim_last_error says why.
+///
+/// # Safety
+/// `config` is NULL or points to a ChronoConfig whose argv holds argc NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn chrono_sim_create(config: *const ChronoConfig) -> *mut ChronoSim {
+    guard(|| {
+        let config = config.as_ref().ok_or_else(|| Failure::null("config"))?;
+        let mode = dilation_mode(config.mode)?;
+        let sim = Simulation::open(&sim_config(config)?, mode)?;
+        Ok(Box::into_raw(Box::new(ChronoSim { sim })))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Runs n_steps steps and waits for the GPU to finish them. Returns a CHRONO_ status.
+///
+/// # Safety
+/// `sim` is NULL or a live pointer from chrono_sim_create.
+#[no_mangle]
+pub unsafe extern "C" fn chrono_sim_step(sim: *mut ChronoSim, n_steps: u32) -> c_int {
+    status(|| {
+        let sim = sim_mut(sim)?;
+        sim.step_and_wait(n_steps as usize);
+        check(sim)
+    })
+}
+
+/// The number of stars, for sizing the buffer of chrono_sim_read; 0 on failure.
+///
+/// # Safety
+/// `sim` is NULL or a live pointer from chrono_sim_create.
+#[no_mangle]
+pub unsafe extern "C" fn chrono_sim_star_count(sim: *mut ChronoSim) -> usize {
+    guard(|| Ok(sim_mut(sim)?.star_count() as usize)).unwrap_or(0)
+}
+
+/// Reads the stars back into `out`, at most `cap` of them, and returns how many it wrote.
+/// Returns 0 on failure; chrono_sim_last_error tells that apart from a cap of 0.
+///
+/// # Safety
+/// `sim` is NULL or a live pointer from chrono_sim_create, and `out` has room for `cap` stars
+/// (it may be NULL when `cap` is 0).
+#[no_mangle]
+pub unsafe extern "C" fn chrono_sim_read(sim: *mut ChronoSim, out: *mut Star, cap: usize) -> usize {
+    guard(|| {
+        let sim = sim_mut(sim)?;
+        if out.is_null() && cap > 0 {
+            return Err(Failure::null("out"));
+        }
+        let stars = sim.read_stars();
+        check(sim)?;
+        let count = stars.len().min(cap);
+        if count > 0 {
+            ptr::copy_nonoverlapping(stars.as_ptr(), out, count);
+        }
+        Ok(count)
+    })
+    .unwrap_or(0)
+}
+
+/// Switches between CHRONO_MODE_NEWTONIAN and CHRONO_MODE_CHRONOTURIN from the next step on,
+/// without resetting the galaxy. Returns a CHRONO_ status.
+///
+/// # Safety
+/// `sim` is NULL or a live pointer from chrono_sim_create.
+#[no_mangle]
+pub unsafe extern "C" fn chrono_sim_set_mode(sim: *mut ChronoSim, mode: c_int) -> c_int {
+    status(|| {
+        sim_mut(sim)?.set_mode(dilation_mode(mode)?);
+        Ok(())
+    })
+}
+
+/// Frees the simulation and its device. NULL is ignored.
+///
+/// # Safety
+/// `sim` is NULL or a pointer from chrono_sim_create that is not used again.
+#[no_mangle]
+pub unsafe extern "C" fn chrono_sim_destroy(sim: *mut ChronoSim) {
+    guard(|| {
+        if !sim.is_null() {
+            drop(Box::from_raw(sim));
+        }
+        Ok(())
+    });
+}
+
+/// The CHRONO_ status of this thread's last call to a chrono_ function: CHRONO_OK if it
+/// succeeded.
+#[no_mangle]
+pub extern "C" fn chrono_sim_last_error() -> c_int {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(CHRONO_OK, |(code, _)| *code))
+}
+
+/// What went wrong in this thread's last call, or "" if it succeeded. The string belongs to
+/// the library and stays valid until the thread's next call to a chrono_ function.
+#[no_mangle]
+pub extern "C" fn chrono_sim_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(c"".as_ptr(), |(_, message)| message.as_ptr()))
+}
@@ -0,0 +1,200 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+use crate::galaxy::{GalaxyState, Star};
+use crate::manifest::RunManifest;
+use crate::simulation::DilationMode;
+
+const MAGIC: &[u8; 8] = b"CHRONOCK";
+// Bump whenever the layout below changes; older files are refused rather than misread.
+pub const CHECKPOINT_VERSION: u32 = 7;
+
+// Layout: magic[8], version:u32 little-endian, then the Checkpoint in bincode's fixed-width
+// little-endian encoding. The manifest goes in as its JSON text, and each GalaxyState and
+// star list as their raw bytes, so a restored run is bit for bit the one saved.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CheckpointRun {
+    pub mode: DilationMode,
+    #[serde(with = "pod_bytes")]
+    pub state: GalaxyState,
+    #[serde(with = "pod_bytes")]
+    pub stars: Vec<Star>,
+}
+
+// Everything needed to continue a run: one entry per simulation being stepped
+// (two side by side, one for the current --sequential pass).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub pass: u32,
+    pub next_frame: u64,
+    // The run that wrote it, for `inspect`; resuming goes by the flags it is given instead
+    #[serde(with = "manifest_json")]
+    pub manifest: Option<RunManifest>,
+    pub runs: Vec<CheckpointRun>,
+}
+
+// bincode as bincode::serialize writes it, refusing bytes left over after the checkpoint.
+fn encoding() -> impl Options {
+    bincode::DefaultOptions::new().with_fixint_encoding().reject_trailing_bytes()
+}
+
+impl Checkpoint {
+    // Written to a temporary file and renamed, so a crash mid-write keeps the previous checkpoint.
+    pub fn save(&self, path: &Path) -> Result<(), ChronoError> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+        encoding()
+            .serialize_into(&mut out, self)
+            .map_err(|e| ChronoError::Checkpoint { path: path.to_path_buf(), reason: e.to_string() })?;
+
+        let tmp = path.with_extension("tmp");
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(&out)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ChronoError> {
+        let mut data = Vec::new();
+        std::fs::File::open(path)?.read_to_end(&mut data)?;
+        let bad = |reason: String| ChronoError::Checkpoint { path: path.to_path_buf(), reason };
+
+        let (magic, rest) = data.split_at(MAGIC.len().min(data.len()));
+        if magic != MAGIC {
+            return Err(bad("missing CHRONOCK header".into()));
+        }
+        let (version, body) = rest.split_at(4.min(rest.len()));
+        let version = u32::from_le_bytes(version.try_into().map_err(|_| bad("truncated header".into()))?);
+        if version != CHECKPOINT_VERSION {
+            return Err(bad(format!("format version {} (this build reads {})", version, CHECKPOINT_VERSION)));
+        }
+        encoding().deserialize(body).map_err(|e| match *e {
+            bincode::ErrorKind::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => bad("truncated data".into()),
+            e => bad(e.to_string()),
+        })
+    }
+
+    // Whether `path` starts like a checkpoint, which also ends in `.bin` like a binary dump.
+    pub fn is_checkpoint(path: &Path) -> Result<bool, ChronoError> {
+        let mut magic = [0u8; 8];
+        let mut file = std::fs::File::open(path)?;
+        Ok(file.read_exact(&mut magic).is_ok() && &magic == MAGIC)
+    }
+
+    pub fn star_count(&self) -> Option<usize> {
+        self.runs.first().map(|run| run.stars.len())
+    }
+
+    // Refuses to continue a run with a different shape than the one that wrote this file.
+    pub fn check_compatible(&self, config: &SimConfig) -> Result<(), ChronoError> {
+        // A --central-mass is stored as one extra star, which --stars does not count.
+        let central = config.central_mass.is_some() as usize;
+        for run in &self.runs {
+            let galaxy_stars = run.stars.len().saturating_sub(central);
+            if run.stars.len() != config.stars as usize + central {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "checkpoint holds {} stars but --stars is {}; pass --stars {} to resume it",
+                    galaxy_stars, config.stars, galaxy_stars
+                )));
+            }
+        }
+        let expected_runs = if config.sequential { 1 } else { 2 };
+        if self.runs.len() != expected_runs {
+            return Err(ChronoError::InvalidConfig(format!(
+                "checkpoint was written by a {} run; resume it {} --sequential",
+                if self.runs.len() == 1 { "sequential" } else { "side-by-side" },
+                if self.runs.len() == 1 { "with" } else { "without" }
+            )));
+        }
+        if self.next_frame > config.frames as u64 {
+            return Err(ChronoError::InvalidConfig(format!(
+                "checkpoint resumes at frame {} but --frames is {}",
+                self.next_frame, config.frames
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn run(&self, mode: DilationMode) -> Option<&CheckpointRun> {
+        self.runs.iter().find(|run| run.mode == mode)
+    }
+}
+
+// Plain-old-data fields as one byte string each.
+mod pod_bytes {
+    use serde::de::{Error, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub trait Pod: Sized {
+        fn bytes(&self) -> &[u8];
+        fn from_bytes(bytes: &[u8]) -> Option<Self>;
+    }
+
+    impl Pod for crate::galaxy::GalaxyState {
+        fn bytes(&self) -> &[u8] {
+            bytemuck::bytes_of(self)
+        }
+
+        fn from_bytes(bytes: &[u8]) -> Option<Self> {
+            (bytes.len() == std::mem::size_of::<Self>()).then(|| bytemuck::pod_read_unaligned(bytes))
+        }
+    }
+
+    impl Pod for Vec<crate::galaxy::Star> {
+        fn bytes(&self) -> &[u8] {
+            bytemuck::cast_slice(self)
+        }
+
+        fn from_bytes(bytes: &[u8]) -> Option<Self> {
+            let size = std::mem::size_of::<crate::galaxy::Star>();
+            bytes.len().is_multiple_of(size).then(|| bytes.chunks_exact(size).map(bytemuck::pod_read_unaligned).collect())
+        }
+    }
+
+    pub fn serialize<T: Pod, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(value.bytes())
+    }
+
+    pub fn deserialize<'de, T: Pod, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        struct Bytes<T>(std::marker::PhantomData<T>);
+        impl<T: Pod> Visitor<'_> for Bytes<T> {
+            type Value = T;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "the bytes of a {}", std::any::type_name::<T>())
+            }
+
+            fn visit_bytes<E: Error>(self, bytes: &[u8]) -> Result<T, E> {
+                T::from_bytes(bytes).ok_or_else(|| E::invalid_length(bytes.len(), &self))
+            }
+        }
+        deserializer.deserialize_bytes(Bytes(std::marker::PhantomData))
+    }
+}
+
+// The manifest as the JSON text RunManifest::to_json writes; "" for none.
+mod manifest_json {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::manifest::RunManifest;
+
+    pub fn serialize<S: Serializer>(manifest: &Option<RunManifest>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&manifest.as_ref().map(RunManifest::to_json).unwrap_or_default())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<RunManifest>, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "" => Ok(None),
+            text => RunManifest::from_json(text).map(Some).map_err(|e| D::Error::custom(format!("unreadable manifest: {}", e))),
+        }
+    }
+}
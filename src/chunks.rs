@@ -0,0 +1,63 @@
+use std::ops::Range;
+
+use crate::error::ChronoError;
+use crate::galaxy::Star;
+
+// Star buffers shader.wgsl binds (stars0..stars3); with the active counter that is five
+// of the eight storage buffers per stage every wgpu device allows.
+pub const MAX_CHUNKS: usize = 4;
+
+const STAR_BYTES: u64 = std::mem::size_of::<Star>() as u64;
+
+// How the stars are split across storage buffers, one dispatch each. Every chunk but the
+// last holds `chunk_stars`, so global index i lives in chunk i / chunk_stars. Partners are
+// sampled from the whole population, so every dispatch binds every chunk.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkLayout {
+    pub stars: u32,
+    pub chunk_stars: u32,
+}
+
+impl ChunkLayout {
+    // The largest chunk that fits one storage binding and one dispatch, in whole workgroups,
+    // or `requested` (--chunk-stars) when that is smaller.
+    pub fn new(stars: u32, workgroup_size: u32, requested: Option<u32>, limits: &wgpu::Limits) -> Result<Self, ChronoError> {
+        Self::for_records(stars, workgroup_size, requested, STAR_BYTES, limits)
+    }
+
+    // new() for stars of `star_bytes` each (see Precision::star_bytes).
+    pub fn for_records(
+        stars: u32,
+        workgroup_size: u32,
+        requested: Option<u32>,
+        star_bytes: u64,
+        limits: &wgpu::Limits,
+    ) -> Result<Self, ChronoError> {
+        let max_bytes = limits.max_buffer_size.min(limits.max_storage_buffer_binding_size as u64);
+        let dispatch = limits.max_compute_workgroups_per_dimension as u64 * workgroup_size as u64;
+        let mut fit = (max_bytes / star_bytes).min(dispatch);
+        if fit > workgroup_size as u64 {
+            fit -= fit % workgroup_size as u64;
+        }
+        let chunk_stars = requested.map_or(fit, |r| fit.min(r as u64)).clamp(1, u32::MAX as u64) as u32;
+        let layout = Self { stars, chunk_stars };
+        if layout.count() > MAX_CHUNKS {
+            return Err(ChronoError::BufferTooLarge {
+                stars,
+                bytes: stars as u128 * star_bytes as u128,
+                max_bytes: MAX_CHUNKS as u64 * chunk_stars as u64 * star_bytes,
+            });
+        }
+        Ok(layout)
+    }
+
+    pub fn count(&self) -> usize {
+        self.stars.div_ceil(self.chunk_stars).max(1) as usize
+    }
+
+    // Global star indices of each chunk, in order.
+    pub fn ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        let (stars, chunk) = (self.stars as usize, self.chunk_stars as usize);
+        (0..self.count()).map(move |k| k * chunk..((k + 1) * chunk).min(stars))
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::config::SimConfig;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::ChronoError;
+use crate::galaxy::{is_central, Star};
+
+// What --fof-every links without --linking-length: this fraction of the mean spacing of
+// --stars spread evenly through a sphere of OUTER_RADII scale radii, the usual choice for
+// halos. The presets cut their outliers off at that radius.
+const LINKING_FRACTION: f32 = 0.2;
+const OUTER_RADII: f32 = 10.0;
+
+// --linking-length and --fof-min-members, resolved.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FofSettings {
+    pub linking_length: f32,
+    pub min_members: usize,
+}
+
+impl FofSettings {
+    pub fn from_config(config: &SimConfig) -> Self {
+        let linking_length = config.linking_length.unwrap_or_else(|| {
+            let radius = OUTER_RADII * config.scale_radius.unwrap_or(config.preset.default_scale_radius());
+            let volume = 4.0 / 3.0 * std::f32::consts::PI * radius.powi(3);
+            LINKING_FRACTION * (volume / config.stars.max(1) as f32).cbrt()
+        });
+        Self { linking_length, min_members: config.fof_min_members }
+    }
+}
+
+// One friends-of-friends group that made --fof-min-members.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clump {
+    pub members: u32,
+    pub mass: f64,
+    // Mass-weighted; plain means if the members are massless
+    pub center: [f64; 3],
+    pub velocity: [f64; 3],
+}
+
+// A frame's groups, largest first, so group 0 is the biggest clump. `labels` has one entry
+// per star given to `find`: its index into `groups`, or None for noise (stars in groups too
+// small to keep) and central masses.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clumps {
+    pub groups: Vec<Clump>,
+    pub labels: Vec<Option<u32>>,
+}
+
+impl Clumps {
+    // Stars closer than the linking length are friends, and friends of friends share a group.
+    // Neighbours are found through a grid of linking-length cells, so each star is only
+    // compared with those in the 27 cells around it. Central masses are fields, not bodies,
+    // and join no group.
+    pub fn find(stars: &[Star], settings: &FofSettings) -> Self {
+        let link = settings.linking_length as f64;
+        let cell = |s: &Star| [s.x, s.y, s.z].map(|c| (c as f64 / link).floor() as i32);
+        let mut grid: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+        for (i, star) in stars.iter().enumerate().filter(|(_, s)| !is_central(s)) {
+            grid.entry(cell(star)).or_default().push(i);
+        }
+        let mut parents: Vec<usize> = (0..stars.len()).collect();
+        for (&[cx, cy, cz], members) in &grid {
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let Some(neighbours) = grid.get(&[cx.saturating_add(dx), cy.saturating_add(dy), cz.saturating_add(dz)]) else {
+                            continue;
+                        };
+                        for &i in members {
+                            for &j in neighbours.iter().filter(|&&j| j > i) {
+                                if distance_squared(&stars[i], &stars[j]) <= link * link {
+                                    union(&mut parents, i, j);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Members per root, then the roots that are big enough, largest first (ties by their
+        // first star, so the numbering is stable).
+        let mut roots: HashMap<usize, Vec<usize>> = HashMap::new();
+        for members in grid.values() {
+            for &i in members {
+                roots.entry(find(&mut parents, i)).or_default().push(i);
+            }
+        }
+        let mut kept: Vec<Vec<usize>> = roots.into_values().filter(|m| m.len() >= settings.min_members).collect();
+        kept.iter_mut().for_each(|m| m.sort_unstable());
+        kept.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+
+        let mut labels = vec![None; stars.len()];
+        let groups = kept
+            .iter()
+            .enumerate()
+            .map(|(id, members)| {
+                members.iter().for_each(|&i| labels[i] = Some(id as u32));
+                clump(members.iter().map(|&i| &stars[i]))
+            })
+            .collect();
+        Self { groups, labels }
+    }
+}
+
+fn clump<'a>(members: impl Iterator<Item = &'a Star> + Clone) -> Clump {
+    let count = members.clone().count();
+    let mass: f64 = members.clone().map(|s| s.mass as f64).sum();
+    let weight = |s: &Star| if mass > 0.0 { s.mass as f64 } else { 1.0 };
+    let total = if mass > 0.0 { mass } else { count as f64 };
+    let mean = |f: fn(&Star) -> [f32; 3]| {
+        let sum = members.clone().fold([0.0; 3], |sum, s| {
+            let (v, w) = (f(s), weight(s));
+            [sum[0] + w * v[0] as f64, sum[1] + w * v[1] as f64, sum[2] + w * v[2] as f64]
+        });
+        sum.map(|c| c / total)
+    };
+    Clump { members: count as u32, mass, center: mean(|s| [s.x, s.y, s.z]), velocity: mean(|s| [s.vx, s.vy, s.vz]) }
+}
+
+fn distance_squared(a: &Star, b: &Star) -> f64 {
+    let d = [a.x as f64 - b.x as f64, a.y as f64 - b.y as f64, a.z as f64 - b.z as f64];
+    d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+}
+
+// Union-find with path halving.
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    // The smaller index roots the group, keeping the result independent of visiting order.
+    parents[a.max(b)] = a.min(b);
+}
+
+// --fof-every catalogs: one `group,members,mass,x,y,z,vx,vy,vz` row per clump, largest first.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_catalog(path: &Path, clumps: &Clumps) -> Result<(), ChronoError> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "group,members,mass,x,y,z,vx,vy,vz")?;
+    for (id, c) in clumps.groups.iter().enumerate() {
+        let [x, y, z] = c.center;
+        let [vx, vy, vz] = c.velocity;
+        writeln!(out, "{},{},{},{:.4},{:.4},{:.4},{:.5},{:.5},{:.5}", id, c.members, c.mass, x, y, z, vx, vy, vz)?;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use crate::bindings::{self, StepState, UniformPath};
+use crate::grid::{Accel, Grid};
+use crate::precision::Precision;
+use crate::simulation::{set_substep_force, shader_source};
+
+// active_list's count and frozen count ahead of the indices
+const LIST_HEADER_BYTES: u64 = 8;
+// dispatch_args' fourth word
+const THREADS_OFFSET: u64 = 12;
+
+// The active list and calm flags Compaction::new allocates for `stars`.
+pub fn list_bytes(stars: u32) -> u64 {
+    LIST_HEADER_BYTES + calm_bytes(stars)
+}
+
+pub fn calm_bytes(stars: u32) -> u64 {
+    stars.max(1) as u64 * std::mem::size_of::<u32>() as u64
+}
+
+// shader.wgsl's accrue, args and the --accel kernel's active variant. Compiled once and
+// shared by forked and resized simulations.
+pub struct CompactionPipelines {
+    accrue: wgpu::ComputePipeline,
+    args: wgpu::ComputePipeline,
+    active: wgpu::ComputePipeline,
+    // accrue's and active's, explicit so they take the step's GalaxyState by dynamic offset
+    // or push constants
+    accrue_layout: wgpu::BindGroupLayout,
+    active_layout: wgpu::BindGroupLayout,
+}
+
+impl CompactionPipelines {
+    // `path` is the force kernels', so both take the step's GalaxyState the same way.
+    pub fn new(device: &wgpu::Device, workgroup_size: u32, accel: Accel, precision: Precision, path: UniformPath) -> Self {
+        let source = set_substep_force(&shader_source(workgroup_size, precision), accel).and_then(|source| path.declare_state(&source));
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader.wgsl (compaction)"),
+            source: wgpu::ShaderSource::Wgsl(source.unwrap_or_else(|e| panic!("{}", e)).into()),
+        });
+        let pipeline = |entry_point, layout: Option<&wgpu::BindGroupLayout>| {
+            let layout = layout.map(|layout| {
+                device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[layout],
+                    push_constant_ranges: path.push_constant_ranges(),
+                })
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: layout.as_ref(),
+                module: &shader,
+                entry_point,
+            })
+        };
+        let active = match accel {
+            Accel::Sampled => "main_active",
+            Accel::Bruteforce => "main_active_bruteforce",
+            Accel::Grid => "main_active_grid",
+        };
+        let accrue_layout = bindings::layout(device, "Accrue", path, bindings::CHUNK.into_iter().chain(bindings::COMPACTION));
+        let grid = if accel == Accel::Grid { &bindings::GRID[..] } else { &[] };
+        let active_layout = bindings::layout(
+            device,
+            "Active Force",
+            path,
+            bindings::CHUNK
+                .into_iter()
+                .filter(|&b| b != 2)
+                .chain(bindings::COMPACTION)
+                .chain([bindings::INTERACTIONS])
+                .chain(grid.iter().copied()),
+        );
+        Self {
+            accrue: pipeline("accrue", Some(&accrue_layout)),
+            args: pipeline("args", None),
+            active: pipeline(active, Some(&active_layout)),
+            accrue_layout,
+            active_layout,
+        }
+    }
+}
+
+// Steps only the stars that work (--compaction): a pass over every star accrues time debt
+// and lists the stars that are due, a one-invocation pass turns the list length into an
+// indirect dispatch, and the force pass then runs over the list alone. Each star's sleep
+// rate comes from the tension it measured when it last worked, since measuring it costs
+// as much as the force.
+pub struct Compaction {
+    pipelines: Arc<CompactionPipelines>,
+    calm: wgpu::Buffer,
+    dispatch: wgpu::Buffer,
+    // One per star chunk, with its workgroup count
+    accrue: Vec<(wgpu::BindGroup, u32)>,
+    args: wgpu::BindGroup,
+    active: wgpu::BindGroup,
+}
+
+impl Compaction {
+    // `chunks` are each chunk's entries for shader.wgsl bindings 0 to 6 and its workgroup
+    // count, `interactions` binds the --interactions matrix for the force pass, and `grid`
+    // is the simulation's --accel grid binning, if any.
+    pub fn new(
+        device: &wgpu::Device,
+        pipelines: Arc<CompactionPipelines>,
+        total: u32,
+        chunks: &[(Vec<wgpu::BindGroupEntry>, u32)],
+        interactions: wgpu::BindGroupEntry,
+        grid: Option<&Grid>,
+    ) -> Self {
+        let list = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Active List"),
+            size: list_bytes(total),
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let calm = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Calm Flags"),
+            size: calm_bytes(total),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let dispatch = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Active Dispatch"),
+            contents: bytemuck::cast_slice(&[0u32; 4]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+        let list_entry = wgpu::BindGroupEntry { binding: 10, resource: list.as_entire_binding() };
+        let calm_entry = wgpu::BindGroupEntry { binding: 11, resource: calm.as_entire_binding() };
+        let bind_group = |layout: &wgpu::BindGroupLayout, entries: Vec<wgpu::BindGroupEntry<'_>>| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor { label: None, layout, entries: &entries })
+        };
+
+        let accrue = chunks
+            .iter()
+            .map(|(entries, workgroups)| {
+                let entries = entries.iter().cloned().chain([list_entry.clone(), calm_entry.clone()]).collect();
+                (bind_group(&pipelines.accrue_layout, entries), *workgroups)
+            })
+            .collect();
+        let args = bind_group(
+            &pipelines.args.get_bind_group_layout(0),
+            vec![list_entry.clone(), wgpu::BindGroupEntry { binding: 12, resource: dispatch.as_entire_binding() }],
+        );
+        // Any chunk's entries serve the force pass: it reads stride and total, not offset
+        // and count. It counts nothing either; accrue already has.
+        let active = chunks[0]
+            .0
+            .iter()
+            .filter(|entry| entry.binding != 2)
+            .cloned()
+            .chain([list_entry, calm_entry, interactions])
+            .chain(grid.into_iter().flat_map(Grid::entries))
+            .collect();
+        let active = bind_group(&pipelines.active_layout, active);
+        Self { pipelines, calm, dispatch, accrue, args, active }
+    }
+
+    pub fn pipelines(&self) -> &Arc<CompactionPipelines> {
+        &self.pipelines
+    }
+
+    // Has every star measure its tension the next time it works, e.g. after new stars
+    // were uploaded or the buffers were reordered.
+    pub fn forget_tension(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.calm, 0, None);
+    }
+
+    // Lands ahead of the next submission, like the active counter's reset.
+    pub fn clear_threads(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.dispatch, THREADS_OFFSET, bytemuck::cast_slice(&[0u32]));
+    }
+
+    // Copies the force-pass invocations launched since clear_threads() into `dest`.
+    pub fn copy_threads(&self, encoder: &mut wgpu::CommandEncoder, dest: &wgpu::Buffer, offset: u64) {
+        encoder.copy_buffer_to_buffer(&self.dispatch, THREADS_OFFSET, dest, offset, 4);
+    }
+
+    // Records one step into `cpass` with the step's `state`, after any grid binning.
+    pub fn encode<'a>(&'a self, cpass: &mut wgpu::ComputePass<'a>, state: StepState) {
+        cpass.set_pipeline(&self.pipelines.accrue);
+        for (bind_group, workgroups) in &self.accrue {
+            state.bind(cpass, bind_group);
+            cpass.dispatch_workgroups(*workgroups, 1, 1);
+        }
+        cpass.set_pipeline(&self.pipelines.args);
+        cpass.set_bind_group(0, &self.args, &[]);
+        cpass.dispatch_workgroups(1, 1, 1);
+        cpass.set_pipeline(&self.pipelines.active);
+        state.bind(cpass, &self.active);
+        cpass.dispatch_workgroups_indirect(&self.dispatch, 0);
+    }
+}
@@ -0,0 +1,1334 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::bench::BenchArgs;
+use crate::bindings::UniformPath;
+use crate::boundary::{parse_boundary, Boundary};
+use crate::chunks::ChunkLayout;
+use crate::conservation::EnergyCheck;
+use crate::dump::DumpFormat;
+use crate::error::ChronoError;
+use crate::external::{parse_external_potential, ExternalPotential};
+use crate::galaxy::{DEFAULT_DILATION_THRESHOLD, DEFAULT_DT, DEFAULT_G, DEFAULT_MAX_DEBT, DEFAULT_SOFTENING, MAX_SUBSTEPS, MAX_TYPES};
+use crate::gpu::{BackendChoice, PowerChoice};
+use crate::grid::Accel;
+use crate::hdr::ImageFormat;
+use crate::interactions::{parse_interactions, InteractionTable, Interactions};
+use crate::metrics::MetricsFormat;
+use crate::packing::ReadbackChoice;
+use crate::palette::{parse_palette, Palette, PaletteChoice};
+use crate::pipe::OnPipeClose;
+use crate::precision::Precision;
+use crate::presets::Preset;
+use crate::raster::RasterChoice;
+use crate::render::{parse_stereo, ColorBy, Projection, Stereo, Tonemap};
+use crate::schedule::{parse_mode_schedule, ChainOrder, ModeSchedule};
+use crate::simulation::{Integrator, OnFrameError};
+use crate::snapshot::{InspectArgs, RenderArgs};
+use crate::sort;
+use crate::sweep::SweepArgs;
+use crate::tune::TuneArgs;
+use crate::watchdog::OnDivergence;
+
+// --- CONFIGURATION ---
+// Defaults reproduce the original hardcoded constants.
+#[derive(Parser, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[command(name = "chronoturin", version, about = "Chronoturin: Comparative Visualizer")]
+#[serde(rename_all = "kebab-case", default, deny_unknown_fields)]
+pub struct SimConfig {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Number of stars in the galaxy
+    #[arg(long, default_value_t = 100_000)]
+    pub stars: u32,
+
+    /// Frames rendered for each mode
+    #[arg(long, default_value_t = 150)]
+    pub frames: usize,
+
+    /// Simulation steps dispatched between rendered frames
+    #[arg(long, default_value_t = 5)]
+    pub steps_per_frame: usize,
+
+    /// Compute workgroup size (must be a power of two)
+    #[arg(long, default_value_t = 256)]
+    pub workgroup_size: u32,
+
+    /// Split the stars into storage buffers of at most N (default: as large as the device allows)
+    #[arg(long)]
+    pub chunk_stars: Option<u32>,
+
+    /// Reorder the star buffer along a Morton curve every K frames, so stars that are close
+    /// in space are close in memory (GPU only)
+    #[arg(long)]
+    pub sort_every: Option<usize>,
+
+    /// RNG seed for the initial galaxy (random if omitted)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Readback buffers rotated per frame (1 = no compute/readback overlap)
+    #[arg(long, default_value_t = 2)]
+    pub readback_buffers: usize,
+
+    /// What each frame reads back: every star field, or 16-byte render records (position,
+    /// type, active flag) for runs that only draw frames
+    #[arg(long, value_enum, default_value_t = ReadbackChoice::Full)]
+    pub readback: ReadbackChoice,
+
+    /// Refuse a GPU run whose planned buffers add up to more than this many MiB (the plan is
+    /// printed before anything is allocated either way)
+    #[arg(long, value_name = "MIB")]
+    pub memory_budget: Option<u64>,
+
+    /// Seconds to wait for a frame's readback to map before polling the device again and then
+    /// giving up on it
+    #[arg(long, default_value_t = 30.0, value_name = "SECS")]
+    pub map_timeout_secs: f32,
+
+    /// What to write for a frame whose readback failed even when mapped again: stop the run,
+    /// a black frame, or the previous frame again
+    #[arg(long, value_enum, default_value_t = OnFrameError::Duplicate)]
+    pub on_frame_error: OnFrameError,
+
+    /// Worker threads encoding PNGs in the background
+    #[arg(long, default_value_t = 2)]
+    pub save_threads: usize,
+
+    /// Frames allowed to wait for encoding before the render loop blocks
+    #[arg(long, default_value_t = 4)]
+    pub save_queue: usize,
+
+    /// Restrict wgpu to one graphics backend
+    #[arg(long, value_enum)]
+    pub backend: Option<BackendChoice>,
+
+    /// Use the first adapter whose name contains this text (case-insensitive)
+    #[arg(long)]
+    pub adapter: Option<String>,
+
+    /// Step Newtonian on the first and Chronoturin on the second of two adapters, by their
+    /// --list-adapters index (e.g. 0,1)
+    #[arg(long, value_delimiter = ',')]
+    pub adapters: Option<Vec<usize>>,
+
+    /// Prefer the discrete (high) or integrated (low) GPU
+    #[arg(long, value_enum)]
+    pub power_preference: Option<PowerChoice>,
+
+    /// Print every available adapter and exit
+    #[arg(long)]
+    pub list_adapters: bool,
+
+    /// Read flags from a TOML file of `long-flag-name = value` keys (see README); flags
+    /// given on the command line override it
+    #[arg(long)]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
+    /// Print the resolved configuration (defaults, then --config, then flags) as TOML and
+    /// exit; feeding it back to --config reproduces the run
+    #[arg(long)]
+    #[serde(skip)]
+    pub print_config: bool,
+
+    /// Directory for frames, nested as <out-dir>/<newton|chrono>/<prefix>_NNN.png
+    #[arg(long, global = true)]
+    pub out_dir: Option<PathBuf>,
+
+    /// File name prefix for frames inside --out-dir
+    #[arg(long, global = true, default_value = "frame")]
+    pub prefix: String,
+
+    /// Allow writing into a non-empty --out-dir
+    #[arg(long, global = true)]
+    pub force: bool,
+
+    /// Encode frames straight to video (one file per mode, e.g. out_newton.mp4) instead of PNGs
+    #[arg(long, global = true)]
+    pub video: Option<PathBuf>,
+
+    /// Frame rate for --video (and the one --frames-to announces)
+    #[arg(long, global = true, default_value_t = 30)]
+    pub fps: u32,
+
+    /// ffmpeg executable used by --video
+    #[arg(long, global = true, default_value = "ffmpeg")]
+    pub ffmpeg: PathBuf,
+
+    /// Also write a small looping GIF of both passes back to back
+    #[arg(long, global = true)]
+    pub gif: Option<PathBuf>,
+
+    /// GIF width in pixels (frames are box-filtered down from the full render)
+    #[arg(long, global = true, default_value_t = 256)]
+    pub gif_scale: u32,
+
+    /// Delay between GIF frames in milliseconds
+    #[arg(long, global = true, default_value_t = 50)]
+    pub gif_delay: u32,
+
+    /// Write every frame as raw rgb24 bytes, one after another, to this file or FIFO, or to
+    /// stdout for `-` (logs then go to stderr), e.g. `--frames-to - | ffmpeg -f rawvideo ...`
+    #[arg(long, global = true, value_name = "PATH|-")]
+    pub frames_to: Option<PathBuf>,
+
+    /// What to do when the --frames-to reader closes the pipe: stop writing frames and finish
+    /// the run, or stop with an error
+    #[arg(long, global = true, value_enum, default_value_t = OnPipeClose::Continue)]
+    pub on_pipe_close: OnPipeClose,
+
+    /// Skip the PNG sequence (useful with --gif or --frames-to)
+    #[arg(long, global = true)]
+    pub no_png: bool,
+
+    /// Step every frame but draw and save only every Nth (frame 0, N, 2N, ...); files keep
+    /// the frame's own number
+    #[arg(long, global = true, default_value_t = 1, value_name = "N")]
+    pub save_every: usize,
+
+    /// Quick look: a quarter of --width and --height, single-pixel stars, no overlay, and
+    /// JPEG frames instead of PNG
+    #[arg(long, global = true)]
+    pub preview: bool,
+
+    /// Draw no frames at all; the run writes only metrics, dumps and checkpoints
+    #[arg(long, global = true)]
+    pub no_images: bool,
+
+    /// Frame files: png8 as drawn, png16 for 16-bit PNGs, or exr for the linear float
+    /// channels with the glow as its own layer; png16 and exr are drawn on the CPU, one file
+    /// per mode, without overlays
+    #[arg(long, global = true, value_enum, default_value_t = ImageFormat::Png8)]
+    pub image_format: ImageFormat,
+
+    /// Output image width in pixels
+    #[arg(long, global = true, default_value_t = 1024)]
+    pub width: u32,
+
+    /// Output image height in pixels
+    #[arg(long, global = true, default_value_t = 1024)]
+    pub height: u32,
+
+    /// Projection scale (focal length in pixels)
+    #[arg(long, global = true, default_value_t = 800.0)]
+    pub fov: f32,
+
+    /// Camera position on the Z axis (default: -1000, or far enough to frame a reflecting
+    /// or periodic --boundary)
+    #[arg(long, global = true, allow_hyphen_values = true)]
+    pub camera_z: Option<f32>,
+
+    /// Orbit the camera around the Y axis by this many degrees per frame
+    #[arg(long, global = true, default_value_t = 0.0, allow_hyphen_values = true)]
+    pub orbit: f32,
+
+    /// planar, the pinhole view, or equirect: every direction around the camera on a 2:1
+    /// canvas for 360° video (needs --width twice --height; draws on the CPU)
+    #[arg(long, global = true, value_enum, default_value_t = Projection::Planar)]
+    pub projection: Projection,
+
+    /// Red/cyan 3D: anaglyph, or anaglyph:separation=S for eyes S world units apart
+    /// (default 10); each frame is drawn once per eye, on the CPU
+    #[arg(long, global = true, value_parser = parse_stereo)]
+    pub stereo: Option<Stereo>,
+
+    /// Tone curve applied to accumulated star brightness
+    #[arg(long, global = true, value_enum, default_value_t = Tonemap::Linear)]
+    pub tonemap: Tonemap,
+
+    /// Brightness multiplier applied before tonemapping
+    #[arg(long, global = true, default_value_t = 1.0)]
+    pub exposure: f32,
+
+    /// Depth falloff exponent: each star is weighted by (distance to target / depth)^falloff
+    #[arg(long, global = true, default_value_t = 0.0)]
+    pub falloff: f32,
+
+    /// Multiplier on the Gaussian splat radius (1-6 px, shrinking with depth)
+    #[arg(long, global = true, default_value_t = 1.0)]
+    pub splat_size: f32,
+
+    /// Plot each star as a single pixel instead of a splat
+    #[arg(long, global = true)]
+    pub point: bool,
+
+    /// Motion trails: also read back every sub-step's positions and draw them under the
+    /// frame, fading with age, so fast stars streak in their own colour (draws on the CPU)
+    #[arg(long, global = true)]
+    pub trails: bool,
+
+    /// Brightness of each trail sub-step relative to the one after it, from 0 (exclusive) to 1
+    #[arg(long, global = true, default_value_t = 0.5)]
+    pub trail_decay: f32,
+
+    /// Star colour per data type: classic (red and blue), viridis, or a CSV of type,r,g,b
+    /// rows with 0-255 channels (default: classic for up to two types, else viridis)
+    #[arg(long, global = true, value_parser = parse_palette)]
+    pub palette: Option<PaletteChoice>,
+
+    /// What sets each star's colour in frames: its data type, its speed, its time debt, or
+    /// whether it is active; all but type render on the CPU
+    #[arg(long, global = true, value_enum, default_value_t = ColorBy::Type)]
+    pub color_by: ColorBy,
+
+    /// Value at the low end of the --color-by speed/debt ramp (default: the frame's lowest)
+    #[arg(long, global = true, allow_negative_numbers = true)]
+    pub color_min: Option<f32>,
+
+    /// Value at the high end of the ramp (default: the frame's highest); pin both to keep
+    /// colours comparable across frames
+    #[arg(long, global = true, allow_negative_numbers = true)]
+    pub color_max: Option<f32>,
+
+    /// Where frames are rasterized (default: gpu, or cpu with --cpu); cpu is the original splatter
+    #[arg(long, value_enum)]
+    pub raster: Option<RasterChoice>,
+
+    /// Don't burn the mode/frame/time/active status line into each frame
+    #[arg(long, global = true)]
+    pub no_overlay: bool,
+
+    /// Run the two modes one after the other instead of side by side (half the GPU memory)
+    #[arg(long)]
+    pub sequential: bool,
+
+    /// Run one continuous simulation instead, switching its mode at the given frames, e.g.
+    /// "0:newton,75:chrono" (starts at frame 0; frames in order)
+    #[arg(long, value_parser = parse_mode_schedule, value_name = "FRAME:MODE,...")]
+    pub mode_schedule: Option<ModeSchedule>,
+
+    /// Run the passes one after the other, the second starting from where the first finished
+    /// instead of from the initial galaxy (newton-first unless given)
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "newton-first", value_name = "ORDER")]
+    pub chain: Option<ChainOrder>,
+
+    /// Start --chain's second pass from a dump or checkpoint instead of running the first
+    #[arg(long, requires = "chain", value_name = "PATH")]
+    pub chain_from: Option<PathBuf>,
+
+    /// Instead of the run, step each mode N times from the same galaxy and check every frame matches bit for bit
+    #[arg(long, value_name = "N")]
+    pub verify_determinism: Option<usize>,
+
+    /// Also write per-frame error images coloring each star by its drift from the Newtonian run
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Displacement (world units) shown as full red in --diff images
+    #[arg(long, default_value_t = 5.0)]
+    pub diff_scale: f32,
+
+    /// Write a checkpoint (checkpoint.bin in the output directory) every N frames
+    #[arg(long)]
+    pub checkpoint_every: Option<usize>,
+
+    /// Continue a run from a checkpoint file written by --checkpoint-every
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+
+    /// Dump every star's full state every N frames (into dumps/ under the output directory)
+    #[arg(long)]
+    pub dump_every: Option<usize>,
+
+    /// Snapshot format for --dump-every: CSV with a header, or little-endian f32 with a JSON sidecar
+    #[arg(long, value_enum, default_value_t = DumpFormat::Csv)]
+    pub dump_format: DumpFormat,
+
+    /// Write the radial profile and time_debt histogram of every frame to profiles_<mode>_<frame>.csv
+    /// (into profiles/ under the output directory); the metrics rows carry the counts either way
+    #[arg(long)]
+    pub profiles: bool,
+
+    /// Equal-width radial shells around the center of mass, out to --profile-radius
+    #[arg(long, default_value_t = 32)]
+    pub profile_shells: usize,
+
+    /// Outer edge of the last shell; stars past it count in one overflow bin
+    /// (default: four times the preset scale radius)
+    #[arg(long)]
+    pub profile_radius: Option<f32>,
+
+    /// Equal-width time_debt bins over [0, 1)
+    #[arg(long, default_value_t = 10)]
+    pub debt_bins: usize,
+
+    /// Write the rotation curve and radial velocity dispersion of every frame to
+    /// rotation_<mode>_<frame>.csv (into rotation/ under the output directory), in
+    /// --profile-shells annuli around the measured angular momentum axis
+    #[arg(long)]
+    pub kinematics: bool,
+
+    /// Find friends-of-friends clumps every N frames (from frame 0) and write each analyzed
+    /// frame's catalog to clumps_<mode>_<frame>.csv (into clumps/ under the output directory)
+    #[arg(long)]
+    pub fof_every: Option<usize>,
+
+    /// Stars closer than this are friends (default: 0.2 times the mean spacing of --stars
+    /// spread through a sphere of ten scale radii)
+    #[arg(long)]
+    pub linking_length: Option<f32>,
+
+    /// Groups with fewer members are noise and left out of the catalog
+    #[arg(long, default_value_t = 10)]
+    pub fof_min_members: usize,
+
+    /// Draw the frames --fof-every analyzes with each star in its group's colour and noise in
+    /// grey (draws on the CPU)
+    #[arg(long)]
+    pub fof_color: bool,
+
+    /// Comma-separated star IDs (creation order, from 0) to follow: each gets a
+    /// trajectory_<id>.csv row per mode and frame, and a magenta cross in frames
+    #[arg(long, value_delimiter = ',')]
+    pub track: Vec<u32>,
+
+    /// Step the galaxy with the CPU reference implementation instead of the GPU
+    /// (orders of magnitude slower, but needs no usable wgpu backend)
+    #[arg(long)]
+    pub cpu: bool,
+
+    /// Watch the simulation live in a window instead of writing frames
+    /// (space pauses, M toggles the mode, R resets, arrows/scroll move the camera)
+    #[arg(long)]
+    pub view: bool,
+
+    /// Integration timestep; each frame covers dt * --steps-per-frame of sim time
+    #[arg(long, default_value_t = DEFAULT_DT)]
+    pub dt: f32,
+
+    /// Chronoturin: stars whose mean sampled tension is below this sleep
+    #[arg(long, default_value_t = DEFAULT_DILATION_THRESHOLD)]
+    pub dilation_threshold: f32,
+
+    /// Chronoturin: a sleeping star accrues 1/N of a step per dispatch, so it still
+    /// does work once every N steps
+    #[arg(long, default_value_t = DEFAULT_MAX_DEBT)]
+    pub max_debt: f32,
+
+    /// Time integration scheme (leapfrog also disables velocity damping)
+    #[arg(long, value_enum, default_value_t = Integrator::Euler)]
+    pub integrator: Integrator,
+
+    /// How the GPU stores positions: f32, or df64 (each coordinate a hi + lo pair of f32s
+    /// with compensated drifts, so long runs far from the origin keep small steps)
+    #[arg(long, value_enum, default_value_t = Precision::F32)]
+    pub precision: Precision,
+
+    /// Clamp every star's speed to this after each kick
+    #[arg(long)]
+    pub max_velocity: Option<f32>,
+
+    /// Let a star under strong acceleration split its step into up to this many substeps
+    /// (a power of two), each under 0.25 * sqrt(softening / |a|), with the force
+    /// re-evaluated before every one
+    #[arg(long, default_value_t = 1)]
+    pub max_substeps: u32,
+
+    /// How the shader finds each star's force: SAMPLES random partners, every other star
+    /// (O(N^2)), or a uniform grid with exact neighbors and far cells as point masses
+    #[arg(long, value_enum, default_value_t = Accel::Sampled)]
+    pub accel: Accel,
+
+    /// Chronoturin: run the force pass over the stars that work this step only, listed by
+    /// a cheap pass over every star and dispatched indirectly (GPU only)
+    #[arg(long)]
+    pub compaction: bool,
+
+    /// Step both modes with one kernel that reads the mode from its uniform, instead of a
+    /// kernel compiled per mode (for comparing the two)
+    #[arg(long)]
+    pub unified_kernel: bool,
+
+    /// Compile the force kernel from this WGSL file instead of the built-in shader.wgsl, and
+    /// recompile it between frames whenever the file changes (frame runs and --view)
+    #[arg(long)]
+    pub shader: Option<PathBuf>,
+
+    /// Read commands on stdin between frames of a frame run: pause, resume, set threshold X,
+    /// set max-debt X, set mode newton|chrono, snapshot (write the checkpoint now), and status
+    /// (one JSON line). Each gets a reply line on stdout, or stderr with `--frames-to -`
+    #[arg(long)]
+    pub control: bool,
+
+    /// Gravitational softening length; pairs closer than this stop pulling harder
+    #[arg(long, default_value_t = DEFAULT_SOFTENING)]
+    pub softening: f32,
+
+    /// Gravitational constant used by the shader
+    #[arg(long, default_value_t = DEFAULT_G)]
+    pub g: f32,
+
+    /// Velocity-proportional drag: a star that moves loses this times its velocity per
+    /// unit of sim time, in both dilation modes
+    #[arg(long, default_value_t = 0.0)]
+    pub drag: f32,
+
+    /// A fixed field centered on the origin that every star feels: `halo:scale=200,v0=1.5`
+    /// (logarithmic, flat rotation curve at v0) or `harmonic:omega=0.05`
+    #[arg(long, value_parser = parse_external_potential)]
+    pub external_potential: Option<ExternalPotential>,
+
+    /// Where stars may go: `open`, `reflect:R` (bounce off a sphere of radius R) or
+    /// `periodic:L` (wrap around a cube of side L centered on the origin)
+    #[arg(long, global = true, value_parser = parse_boundary, default_value = "open")]
+    pub boundary: Boundary,
+
+    /// Cull stars that escape past this distance from the origin: they stop being stepped,
+    /// pulled, drawn and dumped, and the force pass shrinks to the stars left
+    #[arg(long)]
+    pub kill_radius: Option<f32>,
+
+    /// With --kill-radius, look for escaped stars every N frames
+    #[arg(long, default_value_t = 10)]
+    pub cull_every: usize,
+
+    /// Merge two stars that come within this distance, once per frame: the survivor takes
+    /// their summed mass and momentum and renders brighter, the other is culled (0 disables;
+    /// needs --accel grid on the GPU)
+    #[arg(long, default_value_t = 0.0)]
+    pub merge_radius: f32,
+
+    /// After every frame, flag stars whose position or velocity is NaN, infinite, or has a
+    /// component past this magnitude
+    #[arg(long, default_value_t = 1.0e6)]
+    pub divergence_bound: f32,
+
+    /// What a run does when the divergence check fires: stop with an error, or log it and go on
+    #[arg(long, value_enum, default_value_t = OnDivergence::Warn)]
+    pub on_divergence: OnDivergence,
+
+    /// Built-in initial distribution
+    #[arg(long, value_enum, default_value_t = Preset::Sphere)]
+    pub preset: Preset,
+
+    /// Start the sphere preset at rest (the original collapse) instead of rotating
+    #[arg(long)]
+    pub cold_start: bool,
+
+    /// Standard deviation of random isotropic velocity added to sphere stars
+    #[arg(long, default_value_t = 0.0)]
+    pub velocity_dispersion: f32,
+
+    /// Prepend a pinned star of this mass (in units of one star) at the origin
+    #[arg(long)]
+    pub central_mass: Option<f32>,
+
+    /// Size of the preset: sphere radius, disk scale length or Plummer scale radius
+    /// (defaults: sphere 300, disk 100, plummer 80, collision 60 per galaxy)
+    #[arg(long)]
+    pub scale_radius: Option<f32>,
+
+    /// Disk vertical scale height (default: a tenth of the scale radius)
+    #[arg(long)]
+    pub scale_height: Option<f32>,
+
+    /// Distance between the two galaxy centers for --preset collision
+    #[arg(long, default_value_t = 400.0)]
+    pub separation: f32,
+
+    /// Closing speed of the two galaxies for --preset collision
+    #[arg(long, default_value_t = 20.0)]
+    pub relative_velocity: f32,
+
+    /// Number of data types; stars attract their own type and repel every other unless
+    /// --interactions says otherwise
+    #[arg(long, global = true, default_value_t = 2)]
+    pub types: usize,
+
+    /// Share of the stars given each type by the presets, comma-separated, one per type
+    /// (default: even)
+    #[arg(long, value_delimiter = ',')]
+    pub type_fractions: Option<Vec<f64>>,
+
+    /// Force coefficients between data types, `a_b=-0.5,a_a=2` or a TOML file with an
+    /// [interactions] table of such keys: positive attracts, negative repels, 0 ignores.
+    /// `a_b` scales the pull a type a star feels from type b; types are indices or letters
+    /// (default: 1 within a type, -1 across)
+    #[arg(long, value_parser = parse_interactions)]
+    pub interactions: Option<InteractionTable>,
+
+    /// Let --interactions give a_b and b_a different values; otherwise a_b sets both
+    #[arg(long)]
+    pub asymmetric: bool,
+
+    /// Load the starting galaxy from a CSV (x,y,z,vx,vy,vz,mass,data_type) or a binary dump;
+    /// the star count then comes from the file and --stars is ignored
+    #[arg(long)]
+    pub initial_conditions: Option<PathBuf>,
+
+    /// How to compute potential energy for the conservation diagnostics: every pair (O(N^2)),
+    /// --energy-samples random pairs, or not at all
+    #[arg(long, value_enum, default_value_t = EnergyCheck::Sampled)]
+    pub energy_check: EnergyCheck,
+
+    /// Pairs drawn per frame for --energy-check sampled
+    #[arg(long, default_value_t = 100_000)]
+    pub energy_samples: usize,
+
+    /// Reduce center of mass, momentum and kinetic energy on the GPU every frame and print
+    /// the center-of-mass drift (a cheap integrator canary)
+    #[arg(long)]
+    pub gpu_diagnostics: bool,
+
+    /// Write one metrics record per frame to this file
+    #[arg(long)]
+    pub metrics_out: Option<PathBuf>,
+
+    /// Format of --metrics-out
+    #[arg(long, value_enum, default_value_t = MetricsFormat::Csv)]
+    pub metrics_format: MetricsFormat,
+
+    /// Also write the end-of-run comparison report as JSON to this file
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+
+    /// After the run, chart per-frame times, active fraction and energy drift into
+    /// timings.png, active_fraction.png and energy_drift.png in the output directory
+    #[arg(long)]
+    pub plots: bool,
+
+    /// Serve the run over HTTP on this address (e.g. 0.0.0.0:8080): /stream is an MJPEG
+    /// stream of the latest frame, /metrics.json the latest metrics record
+    #[arg(long, value_name = "ADDR")]
+    pub serve: Option<String>,
+
+    /// The `run` label of --serve's Prometheus /metrics series [default: the seed]
+    #[arg(long)]
+    pub run_id: Option<String>,
+
+    /// More log output: -v for debug, -vv for trace spans (RUST_LOG overrides both)
+    #[arg(long, short, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only warnings and errors, and no progress bars
+    #[arg(long, short)]
+    pub quiet: bool,
+
+    /// Also write every log line, with timestamps, to this file
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Submit every sub-step separately instead of batching them per frame
+    #[arg(long)]
+    pub legacy_submit: bool,
+
+    /// Hand each sub-step its GalaxyState by push constants or from a uniform buffer
+    /// (default: push constants when the adapter supports them)
+    #[arg(long, value_enum)]
+    pub uniform_path: Option<UniformPath>,
+}
+
+#[derive(clap::Subcommand, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Command {
+    /// Step each mode in turn for dumps, checkpoints and metrics; frames only with --images
+    Simulate(SimulateArgs),
+    /// Draw frames (or a --video or --gif) from dumps or checkpoints without stepping anything
+    Render(RenderArgs),
+    /// Step both modes side by side and measure Chronoturin's divergence every frame
+    Compare,
+    /// Run every combination of the listed values on one device, without images by default
+    Sweep(SweepArgs),
+    /// Bisect --dilation-threshold with short lockstep probes for the largest one within an error budget
+    Tune(TuneArgs),
+    /// Time compute steps of both modes (no readback, no images) and report statistics
+    Bench(BenchArgs),
+    /// Print the header and summary statistics of a dump or checkpoint
+    Inspect(InspectArgs),
+}
+
+// `chronoturin [flags] simulate [--images]`: a --sequential run whose outputs are files.
+#[derive(clap::Args, Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", default, deny_unknown_fields)]
+pub struct SimulateArgs {
+    /// Also write each mode's PNG sequence
+    #[arg(long)]
+    pub images: bool,
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Simulate(_) => "simulate",
+            Command::Render(_) => "render",
+            Command::Compare => "compare",
+            Command::Sweep(_) => "sweep",
+            Command::Tune(_) => "tune",
+            Command::Bench(_) => "bench",
+            Command::Inspect(_) => "inspect",
+        }
+    }
+
+    // simulate and compare step frames like a run without a subcommand.
+    pub fn is_frame_run(&self) -> bool {
+        matches!(self, Command::Simulate(_) | Command::Compare)
+    }
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self::parse_from(["chronoturin"])
+    }
+}
+
+impl SimConfig {
+    pub fn is_sweep(&self) -> bool {
+        matches!(self.command, Some(Command::Sweep(_)))
+    }
+
+    // The subcommand, unless it is one of the frame runs.
+    pub fn other_command(&self) -> Option<&Command> {
+        self.command.as_ref().filter(|c| !c.is_frame_run())
+    }
+
+    // --diff, or the compare subcommand, which measures divergence without drawing it.
+    pub fn measures_divergence(&self) -> bool {
+        self.diff || matches!(self.command, Some(Command::Compare))
+    }
+
+    // The frame size and focal length: --width, --height and --fov, or a quarter of each
+    // with --preview, which frames the same view smaller.
+    pub fn frame_size(&self) -> (u32, u32, f32) {
+        match self.preview {
+            true => ((self.width / 4).max(1), (self.height / 4).max(1), self.fov / 4.0),
+            false => (self.width, self.height, self.fov),
+        }
+    }
+
+    // Whether frame `frame` is drawn: not with --no-images, only every --save-every, and not
+    // when nothing takes the image (simulate without --images).
+    pub fn draws_frame(&self, frame: usize) -> bool {
+        let taken = !self.no_png || self.video.is_some() || self.gif.is_some() || self.frames_to.is_some() || self.serve.is_some();
+        !self.no_images && taken && frame.is_multiple_of(self.save_every)
+    }
+
+    // What simulate fixes about its frame run: one mode at a time, PNGs only with --images.
+    pub fn apply_command(&mut self) {
+        if let Some(Command::Simulate(args)) = &self.command {
+            self.sequential = true;
+            self.no_png |= !args.images;
+        }
+        // --chain is sequential by nature.
+        self.sequential |= self.chain.is_some();
+    }
+
+    pub fn workgroup_count(&self) -> u32 {
+        self.stars.div_ceil(self.workgroup_size)
+    }
+
+    // Star buffers and dispatches for this star count on a device with `limits`.
+    pub fn chunk_layout(&self, limits: &wgpu::Limits) -> Result<ChunkLayout, ChronoError> {
+        ChunkLayout::for_records(self.stars, self.workgroup_size, self.chunk_stars, self.precision.star_bytes(), limits)
+    }
+
+    // --raster, or where frames are drawn by default: the GPU, unless the simulation runs
+    // on the CPU or --color-by needs the CPU rasterizer. --no-images keeps the GPU's buffers free.
+    pub fn raster_choice(&self) -> RasterChoice {
+        match self.raster {
+            Some(choice) => choice,
+            None if self.no_images
+                || self.trails
+                || self.fof_color
+                || self.image_format.is_hdr()
+                || self.stereo.is_some()
+                || self.projection != Projection::Planar
+                || self.cpu
+                || self.color_by != ColorBy::Type
+                || self.precision == Precision::Df64 =>
+            {
+                RasterChoice::Cpu
+            }
+            None => RasterChoice::Gpu,
+        }
+    }
+
+    // --camera-z, or a distance that frames --boundary.
+    pub fn camera_z(&self) -> f32 {
+        self.camera_z.unwrap_or_else(|| self.boundary.camera_z(self.width, self.height, self.fov))
+    }
+
+    // The radius culling flags escaped stars beyond: --kill-radius, or none at all when only
+    // --merge-radius needs its victims culled. None without either.
+    pub fn cull_radius(&self) -> Option<f32> {
+        self.kill_radius.or((self.merge_radius > 0.0).then_some(f32::INFINITY))
+    }
+
+    // --interactions over the default matrix for --types.
+    pub fn interactions(&self) -> Result<Interactions, ChronoError> {
+        let entries = self.interactions.as_ref().map_or(&[][..], |table| &table.0);
+        Interactions::resolve(self.types, entries, self.asymmetric).map_err(ChronoError::InvalidConfig)
+    }
+
+    // --palette, or the default for --types.
+    pub fn palette(&self) -> Palette {
+        match &self.palette {
+            Some(PaletteChoice::Classic) => Palette::classic(),
+            Some(PaletteChoice::Viridis) => Palette::viridis(self.types),
+            Some(PaletteChoice::Custom { palette, .. }) => **palette,
+            None if self.types <= 2 => Palette::classic(),
+            None => Palette::viridis(self.types),
+        }
+    }
+
+    pub fn star_buffer_size(&self) -> Option<u64> {
+        (self.stars as u64).checked_mul(self.precision.star_bytes())
+    }
+
+    // Rejects impossible combinations before any GPU work is submitted.
+    pub fn validate(&self, limits: &wgpu::Limits) -> Result<(), ChronoError> {
+        if self.stars == 0 {
+            return Err(ChronoError::InvalidConfig("--stars must be at least 1".into()));
+        }
+        if self.frames == 0 {
+            return Err(ChronoError::InvalidConfig("--frames must be at least 1".into()));
+        }
+        if self.steps_per_frame == 0 {
+            return Err(ChronoError::InvalidConfig("--steps-per-frame must be at least 1".into()));
+        }
+        if self.width == 0 || self.height == 0 {
+            return Err(ChronoError::InvalidConfig(format!("image size must be at least 1x1, got {}x{}", self.width, self.height)));
+        }
+        if !self.fov.is_finite() || self.fov <= 0.0 || !self.camera_z().is_finite() || self.camera_z() == 0.0 {
+            return Err(ChronoError::InvalidConfig("--fov must be positive and --camera-z finite and non-zero".into()));
+        }
+        if !self.exposure.is_finite() || self.exposure < 0.0 || !self.falloff.is_finite() {
+            return Err(ChronoError::InvalidConfig("--exposure must be non-negative and --falloff finite".into()));
+        }
+        if !self.splat_size.is_finite() || self.splat_size <= 0.0 {
+            return Err(ChronoError::InvalidConfig("--splat-size must be positive".into()));
+        }
+        let positive = |v: Option<f32>| v.is_none_or(|v| v.is_finite() && v > 0.0);
+        if !positive(self.scale_radius) || !positive(self.scale_height) {
+            return Err(ChronoError::InvalidConfig("--scale-radius and --scale-height must be positive".into()));
+        }
+        if !self.velocity_dispersion.is_finite() || self.velocity_dispersion < 0.0 {
+            return Err(ChronoError::InvalidConfig("--velocity-dispersion must be zero or positive".into()));
+        }
+        if !self.dt.is_finite() || self.dt <= 0.0 {
+            return Err(ChronoError::InvalidConfig("--dt must be positive".into()));
+        }
+        if !self.softening.is_finite() || self.softening <= 0.0 {
+            return Err(ChronoError::InvalidConfig("--softening must be positive".into()));
+        }
+        if !self.dilation_threshold.is_finite() || self.dilation_threshold < 0.0 {
+            return Err(ChronoError::InvalidConfig("--dilation-threshold must be zero or positive".into()));
+        }
+        if !self.max_debt.is_finite() || self.max_debt < 1.0 {
+            return Err(ChronoError::InvalidConfig("--max-debt must be at least 1".into()));
+        }
+        if !self.g.is_finite() {
+            return Err(ChronoError::InvalidConfig("--g must be finite".into()));
+        }
+        if !self.drag.is_finite() || self.drag < 0.0 {
+            return Err(ChronoError::InvalidConfig("--drag must be zero or positive".into()));
+        }
+        if self.drag * self.dt >= 1.0 {
+            return Err(ChronoError::InvalidConfig(format!(
+                "--drag {} would reverse velocities in a single --dt {} step; keep drag * dt below 1",
+                self.drag, self.dt
+            )));
+        }
+        if let Some(mass) = self.central_mass {
+            if !mass.is_finite() || mass <= 0.0 {
+                return Err(ChronoError::InvalidConfig("--central-mass must be positive".into()));
+            }
+            if self.preset == Preset::Collision {
+                return Err(ChronoError::InvalidConfig("--central-mass needs a single galaxy; use another --preset".into()));
+            }
+            if self.initial_conditions.is_some() {
+                return Err(ChronoError::InvalidConfig(
+                    "--central-mass applies to the built-in presets; add a data_type -1 row to the initial conditions instead".into(),
+                ));
+            }
+        }
+        if !(1..=MAX_TYPES).contains(&self.types) {
+            return Err(ChronoError::InvalidConfig(format!("--types must be 1 to {}", MAX_TYPES)));
+        }
+        if let Some(fractions) = &self.type_fractions {
+            if fractions.len() != self.types {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "--type-fractions gives {} fractions for --types {}",
+                    fractions.len(),
+                    self.types
+                )));
+            }
+            if fractions.iter().any(|f| !f.is_finite() || *f < 0.0) || fractions.iter().sum::<f64>() <= 0.0 {
+                return Err(ChronoError::InvalidConfig("--type-fractions must be non-negative with a positive sum".into()));
+            }
+        }
+        if self.asymmetric && self.interactions.is_none() {
+            return Err(ChronoError::InvalidConfig("--asymmetric only applies to --interactions".into()));
+        }
+        self.interactions()?;
+        match &self.palette {
+            Some(PaletteChoice::Classic) if self.types > 2 => {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "--palette classic has two colours, not enough for --types {}; use viridis or a CSV",
+                    self.types
+                )));
+            }
+            Some(PaletteChoice::Custom { palette, .. }) if palette.len() != self.types => {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "the --palette file has {} colours but --types is {}",
+                    palette.len(),
+                    self.types
+                )));
+            }
+            _ => {}
+        }
+        if self.kill_radius.is_some_and(|r| !r.is_finite() || r <= 0.0) {
+            return Err(ChronoError::InvalidConfig("--kill-radius must be positive".into()));
+        }
+        if self.cull_every == 0 {
+            return Err(ChronoError::InvalidConfig("--cull-every must be at least 1".into()));
+        }
+        if !self.merge_radius.is_finite() || self.merge_radius < 0.0 {
+            return Err(ChronoError::InvalidConfig("--merge-radius must be 0 or positive".into()));
+        }
+        if self.max_velocity.is_some_and(|v| !v.is_finite() || v <= 0.0) {
+            return Err(ChronoError::InvalidConfig("--max-velocity must be positive".into()));
+        }
+        if !self.max_substeps.is_power_of_two() || self.max_substeps > MAX_SUBSTEPS {
+            return Err(ChronoError::InvalidConfig(format!("--max-substeps must be a power of two from 1 to {}", MAX_SUBSTEPS)));
+        }
+        if self.divergence_bound.is_nan() || self.divergence_bound <= 0.0 {
+            return Err(ChronoError::InvalidConfig("--divergence-bound must be positive".into()));
+        }
+        if self.merge_radius > 0.0 {
+            if !self.cpu && self.accel != Accel::Grid {
+                return Err(ChronoError::InvalidConfig("--merge-radius finds neighbors on the force grid; add --accel grid".into()));
+            }
+            if self.readback == ReadbackChoice::Packed {
+                return Err(ChronoError::InvalidConfig("--merge-radius draws stars by their mass; use --readback full".into()));
+            }
+        }
+        if self.accel == Accel::Grid && matches!(self.boundary, Boundary::Periodic { .. }) {
+            return Err(ChronoError::InvalidConfig(
+                "--accel grid has no periodic images; use --boundary periodic with sampled or bruteforce".into(),
+            ));
+        }
+        if self.accel == Accel::Grid && self.types > 2 {
+            return Err(ChronoError::InvalidConfig(
+                "--accel grid keeps two per-type centroids per cell; use --types 2 or another --accel".into(),
+            ));
+        }
+        if !self.separation.is_finite() || !self.relative_velocity.is_finite() {
+            return Err(ChronoError::InvalidConfig("--separation and --relative-velocity must be finite".into()));
+        }
+        if self.save_every == 0 {
+            return Err(ChronoError::InvalidConfig("--save-every must be at least 1".into()));
+        }
+        if (self.save_every > 1 || self.preview || self.no_images) && (self.view || self.other_command().is_some()) {
+            return Err(ChronoError::InvalidConfig(
+                "--save-every, --preview and --no-images thin out a frame run; drop --view and subcommands".into(),
+            ));
+        }
+        if self.no_images && (self.video.is_some() || self.gif.is_some() || self.frames_to.is_some()) {
+            return Err(ChronoError::InvalidConfig(
+                "--no-images draws nothing for --video, --gif or --frames-to; drop one or the other".into(),
+            ));
+        }
+        if self.image_format.is_hdr() {
+            let name = self.image_format.name();
+            if self.view || self.other_command().is_some_and(|c| !matches!(c, Command::Render(_))) {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "--image-format {} writes the frames of a frame run or render; drop --view and other subcommands",
+                    name
+                )));
+            }
+            if self.preview || self.no_images || self.no_png || self.video.is_some() {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "--image-format {} needs frame files; drop --preview, --no-images, --no-png and --video",
+                    name
+                )));
+            }
+            if self.raster == Some(RasterChoice::Gpu) {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "--image-format {} keeps the CPU framebuffer's float channels; drop --raster gpu",
+                    name
+                )));
+            }
+            if self.image_format == ImageFormat::Exr && cfg!(not(feature = "exr")) {
+                return Err(ChronoError::InvalidConfig("--image-format exr needs a build with the `exr` feature".into()));
+            }
+        }
+        if self.stereo.is_some() || self.projection != Projection::Planar {
+            if self.view || self.raster == Some(RasterChoice::Gpu) {
+                return Err(ChronoError::InvalidConfig(
+                    "--stereo and --projection equirect draw on the CPU; drop --view and --raster gpu".into(),
+                ));
+            }
+            if self.stereo.is_some() && self.image_format.is_hdr() {
+                return Err(ChronoError::InvalidConfig("--stereo draws an 8-bit anaglyph; use --image-format png8".into()));
+            }
+            let (width, height, _) = self.frame_size();
+            if self.projection == Projection::Equirect && width != 2 * height {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "--projection equirect maps the sphere onto a 2:1 canvas, not {}x{}; use --width twice --height",
+                    width, height
+                )));
+            }
+        }
+        if self.trails {
+            if self.steps_per_frame < 2 || !(self.trail_decay > 0.0 && self.trail_decay <= 1.0) {
+                return Err(ChronoError::InvalidConfig(
+                    "--trails blends a frame's sub-steps; use --steps-per-frame 2 or more and a --trail-decay in (0, 1]".into(),
+                ));
+            }
+            if self.view || self.other_command().is_some() || self.no_images {
+                return Err(ChronoError::InvalidConfig(
+                    "--trails draws the frames of a frame run; drop --view, subcommands and --no-images".into(),
+                ));
+            }
+            if self.raster == Some(RasterChoice::Gpu) || self.precision == Precision::Df64 {
+                return Err(ChronoError::InvalidConfig(
+                    "--trails draws its streaks on the CPU from f32 records; drop --raster gpu and --precision df64".into(),
+                ));
+            }
+            if matches!(self.color_by, ColorBy::Speed | ColorBy::Debt) {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "trail sub-steps carry no {}; use --color-by type or active with --trails",
+                    self.color_by.name()
+                )));
+            }
+        }
+        if self.dump_every == Some(0) {
+            return Err(ChronoError::InvalidConfig("--dump-every must be at least 1".into()));
+        }
+        if self.profile_shells == 0 || self.debt_bins == 0 {
+            return Err(ChronoError::InvalidConfig("--profile-shells and --debt-bins must be at least 1".into()));
+        }
+        if !positive(self.profile_radius) {
+            return Err(ChronoError::InvalidConfig("--profile-radius must be positive".into()));
+        }
+        if self.profiles && (self.view || self.other_command().is_some()) {
+            return Err(ChronoError::InvalidConfig("--profiles writes files per frame of a frame run; drop --view and subcommands".into()));
+        }
+        if self.kinematics {
+            if self.view || self.other_command().is_some() {
+                return Err(ChronoError::InvalidConfig(
+                    "--kinematics writes files per frame of a frame run; drop --view and subcommands".into(),
+                ));
+            }
+            if self.readback == ReadbackChoice::Packed {
+                return Err(ChronoError::InvalidConfig("--kinematics measures velocities; use --readback full".into()));
+            }
+        }
+        if self.fof_every == Some(0) {
+            return Err(ChronoError::InvalidConfig("--fof-every must be at least 1".into()));
+        }
+        if !positive(self.linking_length) || self.fof_min_members == 0 {
+            return Err(ChronoError::InvalidConfig("--linking-length must be positive and --fof-min-members at least 1".into()));
+        }
+        if self.fof_every.is_some() {
+            if self.view || self.other_command().is_some() {
+                return Err(ChronoError::InvalidConfig(
+                    "--fof-every writes catalogs per frame of a frame run; drop --view and subcommands".into(),
+                ));
+            }
+            if self.readback == ReadbackChoice::Packed {
+                return Err(ChronoError::InvalidConfig("--fof-every catalogs masses and velocities; use --readback full".into()));
+            }
+        }
+        if self.fof_color {
+            if self.fof_every.is_none() {
+                return Err(ChronoError::InvalidConfig("--fof-color colours the frames --fof-every analyzes; give --fof-every too".into()));
+            }
+            if self.raster == Some(RasterChoice::Gpu) {
+                return Err(ChronoError::InvalidConfig("--fof-color draws on the CPU; drop --raster gpu".into()));
+            }
+        }
+        if self.plots && cfg!(not(feature = "plots")) {
+            return Err(ChronoError::InvalidConfig("--plots needs a build with the `plots` feature".into()));
+        }
+        if self.plots && (self.view || self.other_command().is_some()) {
+            return Err(ChronoError::InvalidConfig("--plots charts a frame run; drop --view and subcommands".into()));
+        }
+        if self.serve.is_some() && cfg!(not(feature = "serve")) {
+            return Err(ChronoError::InvalidConfig("--serve needs a build with the `serve` feature".into()));
+        }
+        if self.serve.is_some() && (self.view || (self.other_command().is_some() && !self.is_sweep())) {
+            return Err(ChronoError::InvalidConfig("--serve watches a frame run or a sweep; drop --view and other subcommands".into()));
+        }
+        if self.frames_to.is_some() && (self.view || self.other_command().is_some_and(|c| !matches!(c, Command::Render(_)))) {
+            return Err(ChronoError::InvalidConfig("--frames-to pipes a frame run or render; drop --view and other subcommands".into()));
+        }
+        if let Some(schedule) = &self.mode_schedule {
+            if schedule.last_frame() >= self.frames {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "--mode-schedule switches at frame {}, but the run has {} frames",
+                    schedule.last_frame(),
+                    self.frames
+                )));
+            }
+            if self.view || self.command.is_some() || self.sequential || self.adapters.is_some() || self.diff {
+                return Err(ChronoError::InvalidConfig(
+                    "--mode-schedule is one continuous run; drop --view, subcommands, --sequential, --adapters and --diff".into(),
+                ));
+            }
+            if self.resume.is_some() || self.checkpoint_every.is_some() {
+                return Err(ChronoError::InvalidConfig("--mode-schedule runs cannot be checkpointed or resumed yet".into()));
+            }
+        }
+        if self.chain.is_some() {
+            if self.view || self.other_command().is_some() || self.adapters.is_some() || self.mode_schedule.is_some() || self.diff {
+                return Err(ChronoError::InvalidConfig(
+                    "--chain runs one pass after the other; drop --view, subcommands, --adapters, --mode-schedule and --diff".into(),
+                ));
+            }
+            if self.resume.is_some() {
+                return Err(ChronoError::InvalidConfig("--chain cannot --resume; use --chain-from with the checkpoint".into()));
+            }
+            if self.chain_from.is_some() && self.initial_conditions.is_some() {
+                return Err(ChronoError::InvalidConfig("--chain-from sets the starting stars; drop --initial-conditions".into()));
+            }
+            if self.readback == ReadbackChoice::Packed {
+                return Err(ChronoError::InvalidConfig("--chain carries velocities into the second pass; run with --readback full".into()));
+            }
+        }
+        if self.control && (self.view || self.other_command().is_some() || (self.adapters.is_some() && self.sequential)) {
+            return Err(ChronoError::InvalidConfig(
+                "--control steps one frame run at a time; drop --view, subcommands and --sequential --adapters".into(),
+            ));
+        }
+        if self.report.is_some() && (self.view || self.other_command().is_some_and(|c| !matches!(c, Command::Tune(_)))) {
+            return Err(ChronoError::InvalidConfig(
+                "--report summarizes a frame run or tune; drop --view (bench has --json, sweep its own table)".into(),
+            ));
+        }
+        if !self.track.is_empty() {
+            if self.view || self.other_command().is_some() {
+                return Err(ChronoError::InvalidConfig("--track follows stars through a frame run; drop --view and subcommands".into()));
+            }
+            if self.readback == ReadbackChoice::Packed {
+                return Err(ChronoError::InvalidConfig("--track writes every star field; use --readback full".into()));
+            }
+            // A --central-mass star comes first, ahead of the --stars others.
+            let count = self.stars as u64 + self.central_mass.is_some() as u64;
+            if let Some(id) = self.track.iter().find(|&&id| id as u64 >= count) {
+                return Err(ChronoError::InvalidConfig(format!("--track {} is past the last star; IDs run from 0 to {}", id, count - 1)));
+            }
+        }
+        if !(self.map_timeout_secs.is_finite() && self.map_timeout_secs > 0.0) {
+            return Err(ChronoError::InvalidConfig("--map-timeout-secs must be positive".into()));
+        }
+        if self.checkpoint_every == Some(0) {
+            return Err(ChronoError::InvalidConfig("--checkpoint-every must be at least 1".into()));
+        }
+        if let Some(command @ (Command::Sweep(_) | Command::Bench(_))) = &self.command {
+            if self.cpu || self.initial_conditions.is_some() || self.resume.is_some() {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "{} generates its galaxies on one GPU; drop --cpu, --initial-conditions and --resume",
+                    command.name()
+                )));
+            }
+        }
+        if let Some(runs) = self.verify_determinism {
+            if runs < 2 {
+                return Err(ChronoError::InvalidConfig("--verify-determinism needs at least 2 runs to compare".into()));
+            }
+            if self.view
+                || self.command.is_some()
+                || self.adapters.is_some()
+                || self.resume.is_some()
+                || self.mode_schedule.is_some()
+                || self.chain.is_some()
+            {
+                return Err(ChronoError::InvalidConfig(
+                    "--verify-determinism repeats a fresh run on one device; drop --view, subcommands, --adapters, --resume, --mode-schedule and --chain".into(),
+                ));
+            }
+        }
+        if let Some(Command::Tune(args)) = &self.command {
+            args.validate()?;
+            if self.resume.is_some()
+                || self.measures_divergence()
+                || self.sequential
+                || self.mode_schedule.is_some()
+                || self.chain.is_some()
+            {
+                return Err(ChronoError::InvalidConfig(
+                    "tune probes both modes in lockstep from the start; drop --resume, --diff, --sequential, --mode-schedule and --chain"
+                        .into(),
+                ));
+            }
+        }
+        if let Some(Command::Bench(args)) = &self.command {
+            if args.steps == 0 {
+                return Err(ChronoError::InvalidConfig("bench --steps must be at least 1".into()));
+            }
+            if args.compare_kernels && self.unified_kernel {
+                return Err(ChronoError::InvalidConfig("bench --compare-kernels already times --unified-kernel; drop it".into()));
+            }
+        }
+        if self.view && cfg!(not(feature = "viewer")) {
+            return Err(ChronoError::InvalidConfig("--view needs a build with the `viewer` feature".into()));
+        }
+        if self.view && (self.cpu || self.command.is_some() || self.resume.is_some()) {
+            return Err(ChronoError::InvalidConfig("--view draws one GPU simulation live; drop --cpu, --resume and subcommands".into()));
+        }
+        if self.raster == Some(RasterChoice::Gpu) && self.cpu {
+            return Err(ChronoError::InvalidConfig("--raster gpu needs the GPU backend; drop --cpu".into()));
+        }
+        if self.color_by != ColorBy::Type {
+            if self.raster == Some(RasterChoice::Gpu) {
+                return Err(ChronoError::InvalidConfig("raster.wgsl only colours by type; use --raster cpu with --color-by".into()));
+            }
+            if self.view {
+                return Err(ChronoError::InvalidConfig("--view draws its points by type; drop --color-by".into()));
+            }
+            if self.readback == ReadbackChoice::Packed && self.color_by != ColorBy::Active {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "--color-by {} needs every star field; use --readback full",
+                    self.color_by.name().to_lowercase()
+                )));
+            }
+        }
+        let finite = |v: Option<f32>| v.is_none_or(f32::is_finite);
+        if !finite(self.color_min)
+            || !finite(self.color_max)
+            || matches!((self.color_min, self.color_max), (Some(lo), Some(hi)) if lo >= hi)
+        {
+            return Err(ChronoError::InvalidConfig("--color-min and --color-max must be finite, with min below max".into()));
+        }
+        if self.readback == ReadbackChoice::Packed {
+            if self.cpu || self.view || self.other_command().is_some() {
+                return Err(ChronoError::InvalidConfig(
+                    "--readback packed applies to GPU frame runs; drop --cpu, --view and subcommands".into(),
+                ));
+            }
+            if self.dump_every.is_some() || self.checkpoint_every.is_some() {
+                return Err(ChronoError::InvalidConfig(
+                    "--dump-every and --checkpoint-every need every star field; use --readback full".into(),
+                ));
+            }
+            if self.no_png && self.video.is_none() && self.gif.is_none() && self.frames_to.is_none() {
+                return Err(ChronoError::InvalidConfig(
+                    "--readback packed only carries what frame images need, and --no-png writes none".into(),
+                ));
+            }
+        }
+        if self.shader.is_some() && (self.cpu || self.compaction || self.other_command().is_some()) {
+            return Err(ChronoError::InvalidConfig(
+                "--shader reloads the main GPU kernel of a frame run; drop --cpu, --compaction and subcommands".into(),
+            ));
+        }
+        if self.compaction && self.cpu {
+            return Err(ChronoError::InvalidConfig("--compaction lists active stars on the GPU; drop --cpu".into()));
+        }
+        if self.accel != Accel::Sampled && self.cpu {
+            return Err(ChronoError::InvalidConfig("the CPU reference only mirrors --accel sampled; drop --cpu".into()));
+        }
+        if self.uniform_path.is_some() && self.cpu {
+            return Err(ChronoError::InvalidConfig("--uniform-path picks how the GPU kernels get GalaxyState; drop --cpu".into()));
+        }
+        if self.gpu_diagnostics && self.cpu {
+            return Err(ChronoError::InvalidConfig("--gpu-diagnostics reduces on the GPU; drop --cpu".into()));
+        }
+        if self.precision == Precision::Df64 {
+            if self.cpu {
+                return Err(ChronoError::InvalidConfig("--precision df64 widens the GPU star layout; drop --cpu".into()));
+            }
+            if self.cull_radius().is_some() {
+                return Err(ChronoError::InvalidConfig("--precision df64 keeps every star; drop --kill-radius and --merge-radius".into()));
+            }
+            if self.view || self.readback == ReadbackChoice::Packed || self.gpu_diagnostics || self.raster == Some(RasterChoice::Gpu) {
+                return Err(ChronoError::InvalidConfig(
+                    "only the stepping passes read df64 stars; drop --view, --readback packed, --gpu-diagnostics and --raster gpu".into(),
+                ));
+            }
+        }
+        if let Some(adapters) = &self.adapters {
+            if adapters.len() != 2 {
+                return Err(ChronoError::InvalidConfig("--adapters takes two indices: Newtonian's adapter, then Chronoturin's".into()));
+            }
+            if self.adapter.is_some() || self.cpu || self.view || self.other_command().is_some() {
+                return Err(ChronoError::InvalidConfig(
+                    "--adapters picks both frame-run devices; drop --adapter, --cpu, --view and subcommands".into(),
+                ));
+            }
+            if self.sequential
+                && (self.gif.is_some() || self.frames_to.is_some() || self.checkpoint_every.is_some() || self.resume.is_some())
+            {
+                return Err(ChronoError::InvalidConfig(
+                    "--sequential --adapters runs both passes at once; drop --gif, --frames-to, --checkpoint-every and --resume".into(),
+                ));
+            }
+        }
+        match &self.command {
+            Some(Command::Simulate(_)) if self.diff => {
+                return Err(ChronoError::InvalidConfig(
+                    "simulate steps one mode at a time; use compare or drop the subcommand for --diff".into(),
+                ));
+            }
+            Some(Command::Compare) if self.sequential => {
+                return Err(ChronoError::InvalidConfig("compare steps both modes side by side; drop --sequential".into()));
+            }
+            Some(Command::Render(_)) if self.raster == Some(RasterChoice::Gpu) => {
+                return Err(ChronoError::InvalidConfig("render draws on the CPU; drop --raster gpu".into()));
+            }
+            _ => {}
+        }
+        if self.diff && self.sequential {
+            return Err(ChronoError::InvalidConfig("--diff needs both modes in lockstep; drop --sequential".into()));
+        }
+        if !self.diff_scale.is_finite() || self.diff_scale <= 0.0 {
+            return Err(ChronoError::InvalidConfig("--diff-scale must be positive".into()));
+        }
+        if !self.orbit.is_finite() {
+            return Err(ChronoError::InvalidConfig("--orbit must be a finite number of degrees".into()));
+        }
+        if self.gif_scale == 0 {
+            return Err(ChronoError::InvalidConfig("--gif-scale must be at least 1".into()));
+        }
+        if self.fps == 0 {
+            return Err(ChronoError::InvalidConfig("--fps must be at least 1".into()));
+        }
+        if self.readback_buffers == 0 {
+            return Err(ChronoError::InvalidConfig("--readback-buffers must be at least 1".into()));
+        }
+        if self.memory_budget == Some(0) {
+            return Err(ChronoError::InvalidConfig("--memory-budget must be at least 1 MiB".into()));
+        }
+        if !self.workgroup_size.is_power_of_two() {
+            return Err(ChronoError::InvalidConfig(format!("--workgroup-size {} is not a power of two", self.workgroup_size)));
+        }
+        if self.workgroup_size > limits.max_compute_workgroup_size_x || self.workgroup_size > limits.max_compute_invocations_per_workgroup {
+            return Err(ChronoError::InvalidConfig(format!(
+                "--workgroup-size {} exceeds the device maximum of {}",
+                self.workgroup_size,
+                limits.max_compute_workgroup_size_x.min(limits.max_compute_invocations_per_workgroup)
+            )));
+        }
+        if self.chunk_stars == Some(0) {
+            return Err(ChronoError::InvalidConfig("--chunk-stars must be at least 1".into()));
+        }
+        if let Some(every) = self.sort_every {
+            if every == 0 {
+                return Err(ChronoError::InvalidConfig("--sort-every must be at least 1".into()));
+            }
+            if self.cpu {
+                return Err(ChronoError::InvalidConfig("--sort-every reorders the GPU star buffers; drop --cpu".into()));
+            }
+        }
+        // Culling moves the dead behind the living with the same sort.
+        let sorted = match (self.sort_every, self.cull_radius()) {
+            (Some(_), _) => Some("--sort-every"),
+            (None, Some(_)) if !self.cpu => Some(if self.kill_radius.is_some() { "--kill-radius" } else { "--merge-radius" }),
+            _ => None,
+        };
+        if let Some(flag) = sorted {
+            let bytes = sort::key_bytes(self.stars);
+            if bytes > limits.max_storage_buffer_binding_size as u64 || bytes > limits.max_buffer_size {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "{} needs {} bytes of sort keys for {} stars, more than one storage binding holds",
+                    flag, bytes, self.stars
+                )));
+            }
+        }
+        self.chunk_layout(limits).map(|_| ())
+    }
+}
@@ -0,0 +1,171 @@
+use std::path::Path;
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, ValueEnum};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::boundary::{parse_boundary, Boundary};
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+use crate::external::{parse_external_potential, ExternalPotential};
+use crate::interactions::{parse_interactions, InteractionTable};
+use crate::palette::{parse_palette, PaletteChoice};
+use crate::render::{parse_stereo, Stereo};
+use crate::schedule::{parse_mode_schedule, ModeSchedule};
+use crate::snapshot::{parse_frame_range, FrameRange};
+
+// `--config experiment.toml`: the long flag names as keys (`steps-per-frame = 5`), each
+// value as the flag takes it, and the subcommand as a table of its own flags
+// (`[command.sweep]`) or a name (`command = "compare"`). Flags given on the command line
+// override the file key by key, including inside the subcommand's table.
+pub fn resolve(matches: &ArgMatches) -> Result<SimConfig, ChronoError> {
+    let cli = SimConfig::from_arg_matches(matches).map_err(|e| ChronoError::InvalidConfig(e.to_string()))?;
+    let Some(path) = cli.config.clone() else { return Ok(cli) };
+    let text = std::fs::read_to_string(&path).map_err(|e| ChronoError::Parse { path: path.clone(), line: 0, message: e.to_string() })?;
+    let file: SimConfig = toml::from_str(&text).map_err(|e| file_error(&path, &text, &e))?;
+    let mut table = to_table(&file);
+    overlay(&mut table, &to_table(&cli), matches, &SimConfig::command());
+    let mut resolved: SimConfig = toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| ChronoError::InvalidConfig(format!("{}: {}", path.display(), e.message())))?;
+    resolved.config = cli.config;
+    resolved.print_config = cli.print_config;
+    Ok(resolved)
+}
+
+// --print-config: every key, defaults included, that `resolve` reads back to `config`.
+pub fn to_toml(config: &SimConfig) -> String {
+    toml::to_string(&to_table(config)).expect("SimConfig serializes to TOML")
+}
+
+fn to_table<T: Serialize>(value: &T) -> toml::Table {
+    let mut table = toml::Table::try_from(value).expect("configs serialize to a TOML table");
+    table.iter_mut().for_each(|(_, v)| tidy_floats(v));
+    table
+}
+
+// toml writes an f32 by way of f64, as 0.05000000074505806. A float that is exactly an f32
+// is written as the f32's shortest form instead, which reads back to the same f32.
+fn tidy_floats(value: &mut toml::Value) {
+    match value {
+        toml::Value::Float(v) if (*v as f32) as f64 == *v && v.is_finite() => {
+            *v = (*v as f32).to_string().parse().expect("f32 display parses as f64");
+        }
+        toml::Value::Array(values) => values.iter_mut().for_each(tidy_floats),
+        toml::Value::Table(table) => table.iter_mut().for_each(|(_, v)| tidy_floats(v)),
+        _ => {}
+    }
+}
+
+// Replaces the keys of `table` that were given on the command line with their `cli` values.
+fn overlay(table: &mut toml::Table, cli: &toml::Table, matches: &ArgMatches, command: &clap::Command) {
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if matches.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let key = id.replace('_', "-");
+        match cli.get(&key) {
+            Some(value) => table.insert(key, value.clone()),
+            None => table.remove(&key),
+        };
+    }
+    let (Some((name, sub_matches)), Some(given)) = (matches.subcommand(), cli.get("command")) else { return };
+    // The same subcommand in the file keeps the flags the command line leaves alone.
+    let from_file = table.get("command").and_then(|c| c.get(name)).and_then(toml::Value::as_table).cloned();
+    let merged = match (from_file, given.get(name).and_then(toml::Value::as_table), command.find_subcommand(name)) {
+        (Some(mut args), Some(given_args), Some(sub)) => {
+            overlay(&mut args, given_args, sub_matches, sub);
+            toml::Value::Table(toml::Table::from_iter([(name.to_string(), toml::Value::Table(args))]))
+        }
+        _ => given.clone(),
+    };
+    table.insert("command".into(), merged);
+}
+
+// Names the file, line and key, without serde's list of every key it would have accepted.
+fn file_error(path: &Path, text: &str, error: &toml::de::Error) -> ChronoError {
+    let Some(span) = error.span() else {
+        return ChronoError::Parse { path: path.to_path_buf(), line: 0, message: error.message().to_string() };
+    };
+    let line = text[..span.start].matches('\n').count() + 1;
+    let message = error.message().split(", expected one of").next().unwrap_or_default();
+    let key = text.lines().nth(line - 1).and_then(|l| l.split_once('=')).map(|(key, _)| key.trim().trim_matches('"'));
+    let message = match key {
+        Some(key) if !message.contains(&format!("`{}`", key)) => format!("`{}`: {}", key, message),
+        _ => message.to_string(),
+    };
+    ChronoError::Parse { path: path.to_path_buf(), line, message }
+}
+
+// ValueEnum flags read and write the names clap accepts.
+macro_rules! serde_value_enum {
+    ($($ty:ty),* $(,)?) => {$(
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.to_possible_value().expect("no variant is skipped").get_name())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let name = String::deserialize(deserializer)?;
+                <$ty as ValueEnum>::from_str(&name, false).map_err(|_| {
+                    let names: Vec<String> =
+                        <$ty>::value_variants().iter().filter_map(|v| v.to_possible_value()).map(|v| v.get_name().to_string()).collect();
+                    de::Error::custom(format!("unknown value `{}`, expected {}", name, names.join(", ")))
+                })
+            }
+        }
+    )*};
+}
+
+// Flags with their own value parser read the same string, and write it back with Display.
+macro_rules! serde_parsed {
+    ($($ty:ty => $parse:path),* $(,)?) => {$(
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                $parse(&String::deserialize(deserializer)?).map_err(de::Error::custom)
+            }
+        }
+    )*};
+}
+
+serde_value_enum!(
+    crate::bindings::UniformPath,
+    crate::conservation::EnergyCheck,
+    crate::dump::DumpFormat,
+    crate::gpu::BackendChoice,
+    crate::gpu::PowerChoice,
+    crate::grid::Accel,
+    crate::hdr::ImageFormat,
+    crate::metrics::MetricsFormat,
+    crate::packing::ReadbackChoice,
+    crate::pipe::OnPipeClose,
+    crate::precision::Precision,
+    crate::presets::Preset,
+    crate::raster::RasterChoice,
+    crate::render::ColorBy,
+    crate::render::Projection,
+    crate::render::Tonemap,
+    crate::schedule::ChainOrder,
+    crate::simulation::Integrator,
+    crate::simulation::OnFrameError,
+    crate::watchdog::OnDivergence,
+);
+
+serde_parsed!(
+    Boundary => parse_boundary,
+    ExternalPotential => parse_external_potential,
+    FrameRange => parse_frame_range,
+    InteractionTable => parse_interactions,
+    ModeSchedule => parse_mode_schedule,
+    PaletteChoice => parse_palette,
+    Stereo => parse_stereo,
+);
@@ -0,0 +1,159 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::boundary::Boundary;
+use crate::config::SimConfig;
+use crate::external::ExternalPotential;
+use crate::galaxy::{is_central, type_index, Star};
+use crate::interactions::Interactions;
+
+// Must match `SAMPLES` in shader.wgsl
+const SAMPLES: f64 = 32.0;
+// Pairs for --energy-check sampled are drawn from this seed, so every frame uses the same pairs
+// and frame-to-frame changes are not sampling noise.
+const PAIR_SEED: u64 = 0x454e_4552_4759;
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EnergyCheck {
+    // Every pair: O(N^2), fine for a few thousand stars
+    Exact,
+    // --energy-samples random pairs, scaled up to the full pair count
+    Sampled,
+    // Kinetic energy and momenta only
+    Off,
+}
+
+// Totals for one frame. The shader ignores mass for the acceleration, so every star counts
+// as unit mass here too; a pinned central star is an external field, not a body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Conservation {
+    // Serialized as the metrics columns name them
+    #[cfg_attr(feature = "serde", serde(rename = "kinetic_energy"))]
+    pub kinetic: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "potential_energy"))]
+    pub potential: Option<f64>,
+    pub momentum: [f64; 3],
+    pub angular_momentum: [f64; 3],
+}
+
+impl Conservation {
+    pub fn total_energy(&self) -> Option<f64> {
+        self.potential.map(|p| p + self.kinetic)
+    }
+
+    // 2T/|W|: 1 for a galaxy in virial equilibrium, below it while collapsing and above it
+    // while flying apart. None without a measured, nonzero potential.
+    pub fn virial_ratio(&self) -> Option<f64> {
+        self.potential.filter(|&p| p != 0.0).map(|p| 2.0 * self.kinetic / p.abs())
+    }
+}
+
+// The potential whose gradient the shader's sampled force approximates (for distances
+// well above the softening): each pair couples with G * 500 * SAMPLES / (n - 1), times
+// its --interactions coefficient. An --asymmetric matrix has no potential; the symmetric
+// part a_b + b_a over 2 is what is measured then. An --external-potential adds its own
+// energy for every body; --drag only dissipates, so it shows up as drift. A periodic
+// --boundary couples each pair through its nearest image, like the kernels.
+pub struct Potential {
+    pub check: EnergyCheck,
+    pub g: f32,
+    pub softening: f32,
+    pub samples: usize,
+    pub interactions: Interactions,
+    pub external: Option<ExternalPotential>,
+    pub boundary: Boundary,
+}
+
+impl Potential {
+    pub fn from_config(config: &SimConfig) -> Self {
+        Self {
+            check: config.energy_check,
+            g: config.g,
+            softening: config.softening,
+            samples: config.energy_samples,
+            interactions: config.interactions().expect("config is validated before Potential::from_config"),
+            external: config.external_potential,
+            boundary: config.boundary,
+        }
+    }
+}
+
+pub fn measure(stars: &[Star], potential: &Potential) -> Conservation {
+    let central = stars.first().filter(|s| is_central(s));
+    let bodies = if central.is_some() { &stars[1..] } else { stars };
+
+    let mut kinetic = 0.0;
+    let mut momentum = [0.0; 3];
+    let mut angular_momentum = [0.0; 3];
+    for s in bodies {
+        let (r, v) = ([s.x as f64, s.y as f64, s.z as f64], [s.vx as f64, s.vy as f64, s.vz as f64]);
+        kinetic += 0.5 * (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]);
+        for k in 0..3 {
+            momentum[k] += v[k];
+        }
+        angular_momentum[0] += r[1] * v[2] - r[2] * v[1];
+        angular_momentum[1] += r[2] * v[0] - r[0] * v[2];
+        angular_momentum[2] += r[0] * v[1] - r[1] * v[0];
+    }
+
+    let potential = match potential.check {
+        EnergyCheck::Off => None,
+        check => Some(potential_energy(stars, bodies, central, check, potential)),
+    };
+    Conservation { kinetic, potential, momentum, angular_momentum }
+}
+
+fn potential_energy(stars: &[Star], bodies: &[Star], central: Option<&Star>, check: EnergyCheck, p: &Potential) -> f64 {
+    let coupling = p.g as f64 * 500.0 * SAMPLES / (stars.len().max(2) - 1) as f64;
+    let soft_sq = p.softening as f64 * p.softening as f64;
+    let pair = |a: &Star, b: &Star| {
+        let image = |d: f32| match p.boundary.side() {
+            Some(side) => (d - side * (d / side + 0.5).floor()) as f64,
+            None => d as f64,
+        };
+        let (dx, dy, dz) = (image(a.x - b.x), image(a.y - b.y), image(a.z - b.z));
+        let (ta, tb) = (type_index(a), type_index(b));
+        let coefficient = 0.5 * (p.interactions.coefficient(ta, tb) + p.interactions.coefficient(tb, ta)) as f64;
+        -coefficient * coupling / (dx * dx + dy * dy + dz * dz + soft_sq).sqrt()
+    };
+
+    let n = bodies.len();
+    let pairs = n * n.saturating_sub(1) / 2;
+    let mut total = 0.0;
+    if check == EnergyCheck::Exact || p.samples >= pairs {
+        for i in 0..n {
+            for j in i + 1..n {
+                total += pair(&bodies[i], &bodies[j]);
+            }
+        }
+    } else if p.samples > 0 {
+        let mut rng = StdRng::seed_from_u64(PAIR_SEED);
+        let mut sum = 0.0;
+        for _ in 0..p.samples {
+            let i = rng.gen_range(0..n);
+            let j = (i + rng.gen_range(1..n)) % n;
+            sum += pair(&bodies[i], &bodies[j]);
+        }
+        total = sum / p.samples as f64 * pairs as f64;
+    }
+
+    if let Some(external) = p.external {
+        total += bodies.iter().map(|s| external.energy([s.x as f64, s.y as f64, s.z as f64])).sum::<f64>();
+    }
+
+    // Every body sits in the central mass's field.
+    if let Some(c) = central {
+        for s in bodies {
+            let (dx, dy, dz) = ((s.x - c.x) as f64, (s.y - c.y) as f64, (s.z - c.z) as f64);
+            total -= coupling * c.mass as f64 / (dx * dx + dy * dy + dz * dz + soft_sq).sqrt();
+        }
+    }
+    total
+}
+
+// (E_last - E_first) / |E_first|, or None without potential energy or with E_first = 0.
+pub fn relative_drift(first: &Conservation, last: &Conservation) -> Option<f64> {
+    let (e0, e1) = (first.total_energy()?, last.total_energy()?);
+    (e0 != 0.0).then(|| (e1 - e0) / e0.abs())
+}
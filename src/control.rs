@@ -0,0 +1,123 @@
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::simulation::DilationMode;
+
+// How often a paused run checks for an interrupt.
+const PAUSE_POLL: Duration = Duration::from_millis(200);
+
+// One line of --control input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlCommand {
+    Pause,
+    Resume,
+    SetThreshold(f32),
+    SetMaxDebt(f32),
+    // What the controlled simulation steps as from the next submitted frame
+    SetMode(DilationMode),
+    // Write the checkpoint now
+    Snapshot,
+    Status,
+}
+
+impl ControlCommand {
+    // Words separated by whitespace; the error is the reply line's text.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |name: &str, text: &str| match text.parse::<f32>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("set {} takes a number, not `{}`", name, text)),
+        };
+        match words[..] {
+            ["pause"] => Ok(Self::Pause),
+            ["resume"] => Ok(Self::Resume),
+            ["snapshot"] => Ok(Self::Snapshot),
+            ["status"] => Ok(Self::Status),
+            ["set", "threshold", value] => number("threshold", value).map(Self::SetThreshold),
+            ["set", "max-debt", value] => match number("max-debt", value)? {
+                debt if debt >= 1.0 => Ok(Self::SetMaxDebt(debt)),
+                debt => Err(format!("set max-debt must be at least 1, not {}", debt)),
+            },
+            ["set", "mode", mode] => match mode.to_ascii_lowercase().as_str() {
+                "newton" | "newtonian" => Ok(Self::SetMode(DilationMode::Newtonian)),
+                "chrono" | "chronoturin" => Ok(Self::SetMode(DilationMode::Chronoturin)),
+                _ => Err(format!("set mode takes newton or chrono, not `{}`", mode)),
+            },
+            ["set", key, ..] => Err(format!("unknown setting `{}` (threshold, max-debt, mode)", key)),
+            _ => Err(format!("unknown command `{}` (pause, resume, set, snapshot, status)", line.trim())),
+        }
+    }
+}
+
+// `status`'s reply, one JSON line.
+#[derive(Clone, Debug, Serialize)]
+pub struct ControlStatus {
+    pub frame: usize,
+    pub mode: &'static str,
+    pub sim_time: f32,
+    pub frame_ms: f32,
+    pub dilation_threshold: f32,
+    pub max_debt: f32,
+    pub paused: bool,
+}
+
+impl ControlStatus {
+    pub fn json(&self) -> String {
+        serde_json::to_string(self).expect("ControlStatus serializes to JSON")
+    }
+}
+
+// `--control`: commands read on a thread of their own and handed over between frames, so a
+// run with no input never waits on it. Replies are written one line each.
+pub struct Control {
+    lines: Receiver<String>,
+    replies: Box<dyn Write + Send>,
+    pub paused: bool,
+    // Set by `set mode`; None steps as the pass (or side) would
+    pub stepping: Option<DilationMode>,
+}
+
+impl Control {
+    pub fn new<R: BufRead + Send + 'static>(input: R, replies: Box<dyn Write + Send>) -> Self {
+        let (send, lines) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("control".into())
+            .spawn(move || {
+                for line in input.lines().map_while(Result::ok) {
+                    if !line.trim().is_empty() && send.send(line).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn the --control thread");
+        Self { lines, replies, paused: false, stepping: None }
+    }
+
+    // The next command that came in. While paused this waits for one, returning None on an
+    // interrupt or once the input is closed, which also resumes the run.
+    pub fn next(&mut self, interrupted: impl Fn() -> bool) -> Option<Result<ControlCommand, String>> {
+        let line = if self.paused {
+            loop {
+                match self.lines.recv_timeout(PAUSE_POLL) {
+                    Ok(line) => break Some(line),
+                    Err(RecvTimeoutError::Timeout) if !interrupted() => continue,
+                    Err(_) => {
+                        self.paused = false;
+                        break None;
+                    }
+                }
+            }
+        } else {
+            self.lines.try_recv().ok()
+        };
+        line.map(|line| ControlCommand::parse(&line))
+    }
+
+    pub fn reply(&mut self, line: &str) {
+        let _ = writeln!(self.replies, "{}", line);
+        let _ = self.replies.flush();
+    }
+}
@@ -0,0 +1,513 @@
+use crate::boundary;
+use crate::config::SimConfig;
+use crate::galaxy::{is_central, is_dead, type_index, GalaxyState, Star, DEAD_FLAG};
+use crate::interactions::Interactions;
+use crate::packing::RenderRecord;
+use crate::presets;
+use crate::simulation::{DilationMode, FrameData, Stepper};
+use crate::watchdog::WatchdogCounts;
+
+// Must match `SAMPLES` in shader.wgsl
+const SAMPLES: u32 = 32;
+
+// Same integer hash as the shader, so both sample exactly the same partners.
+fn pcg(v: u32) -> u32 {
+    let state = v.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+fn sample_index(index: u32, i: u32, n: u32, time_seed: f32) -> u32 {
+    pcg(index ^ pcg(time_seed.to_bits() ^ pcg(i))) % n
+}
+
+// Must match `SUBSTEP_ETA` in shader.wgsl
+const SUBSTEP_ETA: f32 = 0.25;
+
+// One dispatch of shader.wgsl over `stars`, line for line. Every star reads its partners
+// from the positions before the step, which is what the GPU sees unless another workgroup
+// happens to write first; so do its substeps. Returns the number of stars that were active
+// and the substeps they took.
+pub fn step(stars: &mut [Star], state: &GalaxyState, interactions: &Interactions) -> (u32, u32) {
+    let before = stars.to_vec();
+    let mut active = 0;
+    let mut substeps = 0;
+
+    for (index, star) in stars.iter_mut().enumerate() {
+        let index = index as u32;
+        // A central mass (data_type CENTRAL, always stars[0]) is pinned in place.
+        if is_central(star) {
+            continue;
+        }
+        let (force, mut tension) = sampled_force(index, star, &before, state, interactions);
+
+        // 2. APPLY MODE LOGIC
+        let mut dilation = 1.0;
+        if state.dilation_mode > 0.5 {
+            tension /= SAMPLES as f32;
+            if tension < state.dilation_threshold {
+                dilation = 1.0 / state.max_time_debt;
+            }
+        }
+
+        // 3. PHYSICS UPDATE
+        if state.integrator < 0.5 {
+            star.vx *= state.damping;
+            star.vy *= state.damping;
+            star.vz *= state.damping;
+        }
+        star.time_debt += dilation;
+        star.active_flag = 0.0;
+
+        if star.time_debt >= 1.0 {
+            star.time_debt -= 1.0;
+            star.active_flag = 1.0;
+            active += 1;
+
+            let n = substep_count(star, force, state);
+            substeps += n;
+            kick_drift(star, force, n, state, |s| sampled_force(index, s, &before, state, interactions).0);
+        }
+    }
+    (active, substeps)
+}
+
+// shader.wgsl's sampled_pull plus central_pull at `star`'s position: the force, and the
+// tension summed over the samples.
+fn sampled_force(index: u32, star: &Star, before: &[Star], state: &GalaxyState, interactions: &Interactions) -> ([f32; 3], f32) {
+    let n = before.len() as u32;
+    let has_central = before.first().is_some_and(is_central);
+    let soft_sq = state.softening * state.softening;
+
+    // 1. CALCULATE TENSION (Distance Weighted)
+    let mut tension = 0.0f32;
+    let (mut force_x, mut force_y, mut force_z) = (0.0f32, 0.0f32, 0.0f32);
+    for i in 0..SAMPLES {
+        let target_idx = sample_index(index, i, n, state.time_seed);
+        if target_idx == index || (has_central && target_idx == 0) {
+            continue;
+        }
+        let other = &before[target_idx as usize];
+        let [dx, dy, dz] = nearest_image([other.x - star.x, other.y - star.y, other.z - star.z], state);
+        let dist_sq = dx * dx + dy * dy + dz * dz + soft_sq;
+        let dist = dist_sq.sqrt();
+
+        if (star.data_type - other.data_type).abs() > 0.1 {
+            tension += (1.0 / dist_sq) * 10000.0;
+        }
+
+        let f = (state.g_const * 500.0 * interactions.coefficient(type_index(star), type_index(other))) / dist_sq;
+        force_x += f * (dx / dist);
+        force_y += f * (dy / dist);
+        force_z += f * (dz / dist);
+    }
+
+    if has_central {
+        let center = &before[0];
+        let (dx, dy, dz) = (center.x - star.x, center.y - star.y, center.z - star.z);
+        let dist_sq = dx * dx + dy * dy + dz * dz + soft_sq;
+        let dist = dist_sq.sqrt();
+        let f = (state.g_const * 500.0 * SAMPLES as f32 * center.mass / (n - 1).max(1) as f32) / dist_sq;
+        force_x += f * (dx / dist);
+        force_y += f * (dy / dist);
+        force_z += f * (dz / dist);
+    }
+    ([force_x, force_y, force_z], tension)
+}
+
+// shader.wgsl's substep_count.
+fn substep_count(star: &Star, pull: [f32; 3], state: &GalaxyState) -> u32 {
+    let external = external_pull(star, state);
+    let f = [0, 1, 2].map(|i| pull[i] + external[i]);
+    let a = (f[0] * f[0] + f[1] * f[1] + f[2] * f[2]).sqrt();
+    let limit = SUBSTEP_ETA * SUBSTEP_ETA * state.softening;
+    let cap = (state.max_substeps + 0.5) as u32;
+    let mut n = 1;
+    while n < cap && (state.dt / n as f32) * (state.dt / n as f32) * a > limit {
+        n *= 2;
+    }
+    n
+}
+
+// shader.wgsl's kick_drift, with `force` re-evaluating the pull for each later substep.
+fn kick_drift(star: &mut Star, pull: [f32; 3], substeps: u32, state: &GalaxyState, force: impl Fn(&Star) -> [f32; 3]) {
+    let h = state.dt / substeps as f32;
+    let leapfrog = state.integrator > 0.5;
+    let mut kick = h;
+    if leapfrog {
+        kick = 0.5 * h;
+        if state.first_step < 0.5 {
+            kick += 0.5 * state.dt;
+        }
+    }
+    kick_star(star, pull, kick, state);
+    drift(star, h, state);
+    for _ in 1..substeps {
+        kick_star(star, force(star), h, state);
+        drift(star, h, state);
+    }
+    if leapfrog && substeps > 1 {
+        kick_star(star, force(star), 0.5 * (state.dt - h), state);
+    }
+}
+
+// shader.wgsl's kick_star: the external field joins `pull` here, then --max-velocity clamps.
+fn kick_star(star: &mut Star, pull: [f32; 3], kick: f32, state: &GalaxyState) {
+    let [ex, ey, ez] = external_pull(star, state);
+    star.vx += (pull[0] + ex) * kick;
+    star.vy += (pull[1] + ey) * kick;
+    star.vz += (pull[2] + ez) * kick;
+    let speed = (star.vx * star.vx + star.vy * star.vy + star.vz * star.vz).sqrt();
+    if state.max_velocity > 0.0 && speed > state.max_velocity {
+        let k = state.max_velocity / speed;
+        star.vx *= k;
+        star.vy *= k;
+        star.vz *= k;
+    }
+}
+
+fn drift(star: &mut Star, h: f32, state: &GalaxyState) {
+    star.x += star.vx * h;
+    star.y += star.vy * h;
+    star.z += star.vz * h;
+    bound(star, state);
+}
+
+// shader.wgsl's external_pull: the --external-potential field plus --drag.
+fn external_pull(star: &Star, state: &GalaxyState) -> [f32; 3] {
+    let r = [star.x, star.y, star.z];
+    let mut k = 0.0;
+    if state.external_kind > 1.5 {
+        k = state.external_strength;
+    } else if state.external_kind > 0.5 {
+        k = state.external_strength / (r[0] * r[0] + r[1] * r[1] + r[2] * r[2] + state.external_scale * state.external_scale);
+    }
+    let v = [star.vx, star.vy, star.vz];
+    [0, 1, 2].map(|i| -k * r[i] - state.drag * v[i])
+}
+
+// shader.wgsl's bound: the --boundary after the drift.
+fn bound(star: &mut Star, state: &GalaxyState) {
+    let size = state.boundary_size;
+    if state.boundary_kind > 1.5 {
+        *star = boundary::wrap(star, size);
+    } else if state.boundary_kind > 0.5 {
+        let r_sq = star.x * star.x + star.y * star.y + star.z * star.z;
+        let outward = star.x * star.vx + star.y * star.vy + star.z * star.vz;
+        if r_sq > size * size && outward > 0.0 {
+            let k = 2.0 * outward / r_sq;
+            star.vx -= k * star.x;
+            star.vy -= k * star.y;
+            star.vz -= k * star.z;
+        }
+    }
+}
+
+fn nearest_image(d: [f32; 3], state: &GalaxyState) -> [f32; 3] {
+    let side = state.boundary_size;
+    match state.boundary_kind > 1.5 {
+        true => d.map(|x| x - side * (x / side + 0.5).floor()),
+        false => d,
+    }
+}
+
+// shader.wgsl's cull and the partition sort after it: flags the stars of `stars` past
+// `radius` dead and moves them behind the living, keeping both groups in order, with `ids`
+// following along. Returns how many are alive.
+pub fn cull(stars: &mut [Star], ids: &mut [u32], radius: f32) -> usize {
+    for star in stars.iter_mut().filter(|s| !is_dead(s) && !is_central(s)) {
+        if star.x * star.x + star.y * star.y + star.z * star.z > radius * radius {
+            star.active_flag = DEAD_FLAG;
+        }
+    }
+    let mut order: Vec<usize> = (0..stars.len()).collect();
+    order.sort_by_key(|&i| is_dead(&stars[i]));
+    let (before, before_ids) = (stars.to_vec(), ids.to_vec());
+    for (k, &i) in order.iter().enumerate() {
+        stars[k] = before[i];
+        ids[k] = before_ids[i];
+    }
+    stars.iter().filter(|s| !is_dead(s)).count()
+}
+
+// shader.wgsl's merge over the living `stars`, in index order: each star's nearest living
+// neighbor within `radius` merges into the lower index of the two, unless either star is
+// already taken this pass. The GPU only looks in the neighboring grid cells but otherwise
+// pairs the same way. Returns the number of mergers.
+pub fn merge(stars: &mut [Star], radius: f32) -> u32 {
+    let mut claimed = vec![false; stars.len()];
+    let mut mergers = 0;
+    for index in 0..stars.len() {
+        let star = stars[index];
+        if is_dead(&star) || is_central(&star) {
+            continue;
+        }
+        let mut nearest = (index, radius * radius);
+        for (j, other) in stars.iter().enumerate().filter(|(j, s)| *j != index && !is_dead(s) && !is_central(s)) {
+            let d_sq = (other.x - star.x).powi(2) + (other.y - star.y).powi(2) + (other.z - star.z).powi(2);
+            if d_sq < nearest.1 {
+                nearest = (j, d_sq);
+            }
+        }
+        let j = nearest.0;
+        if j <= index || claimed[index] || claimed[j] {
+            continue;
+        }
+        (claimed[index], claimed[j]) = (true, true);
+        let (a, b) = (stars[index], stars[j]);
+        let mass = a.mass + b.mass;
+        let wa = if mass > 0.0 { a.mass / mass } else { 0.5 };
+        let wb = 1.0 - wa;
+        stars[index] = Star {
+            x: wa * a.x + wb * b.x,
+            y: wa * a.y + wb * b.y,
+            z: wa * a.z + wb * b.z,
+            vx: wa * a.vx + wb * b.vx,
+            vy: wa * a.vy + wb * b.vy,
+            vz: wa * a.vz + wb * b.vz,
+            mass,
+            ..a
+        };
+        stars[j] = Star { mass: 0.0, active_flag: DEAD_FLAG, ..b };
+        mergers += 1;
+    }
+    mergers
+}
+
+// No device limits apply on the CPU; SimConfig::validate() still checks everything else.
+pub fn limits() -> wgpu::Limits {
+    wgpu::Limits {
+        max_compute_workgroup_size_x: u32::MAX,
+        max_compute_invocations_per_workgroup: u32::MAX,
+        max_compute_workgroups_per_dimension: u32::MAX,
+        max_buffer_size: u64::MAX,
+        max_storage_buffer_binding_size: u32::MAX,
+        ..wgpu::Limits::default()
+    }
+}
+
+// Simulation's API on the CPU (--cpu): slow, but needs no GPU and no wgpu backend.
+// Frames are computed on submit, so there is never more than one in flight.
+pub struct CpuSimulation {
+    config: SimConfig,
+    initial_data: Vec<Star>,
+    state: GalaxyState,
+    interactions: Interactions,
+    stars: Vec<Star>,
+    // With --kill-radius: each star's creation index, the living stars leading `stars`, and
+    // the frames until the next cull
+    ids: Vec<u32>,
+    alive: usize,
+    frames_until_cull: usize,
+    // With --merge-radius, since the stars were uploaded
+    mergers: u32,
+    // Taken by the star updates since run_frame began
+    substeps: u32,
+}
+
+impl CpuSimulation {
+    // Generates the initial galaxy for `config.preset` from `config.seed` (0 if unset).
+    pub fn new(config: &SimConfig) -> Self {
+        Self::with_stars(config, presets::from_config(config, config.seed.unwrap_or(0)))
+    }
+
+    pub fn with_stars(config: &SimConfig, initial_data: Vec<Star>) -> Self {
+        let mut config = config.clone();
+        config.stars = initial_data.len() as u32;
+        let state = GalaxyState::new(0.0, DilationMode::Newtonian.as_uniform(), &config);
+        let interactions = config.interactions().expect("config is validated before with_stars");
+        let (ids, alive) = ((0..initial_data.len() as u32).collect(), initial_data.len());
+        Self {
+            config,
+            stars: initial_data.clone(),
+            initial_data,
+            state,
+            interactions,
+            ids,
+            alive,
+            frames_until_cull: 0,
+            mergers: 0,
+            substeps: 0,
+        }
+    }
+
+    pub fn fork(&self) -> Self {
+        Self::with_stars(&self.config, self.initial_data.clone())
+    }
+
+    pub fn config(&self) -> &SimConfig {
+        &self.config
+    }
+
+    pub fn initial_stars(&self) -> &[Star] {
+        &self.initial_data
+    }
+
+    pub fn state(&self) -> GalaxyState {
+        self.state
+    }
+
+    pub fn sim_time(&self) -> f32 {
+        self.state.time_seed
+    }
+
+    pub fn reset(&mut self) {
+        let stars = self.initial_data.clone();
+        self.upload(&stars);
+        self.state.time_seed = 0.0;
+        self.state.first_step = 1.0;
+    }
+
+    // Softening, G and dt stay as configured; only the clock and mode come from `state`.
+    pub fn restore(&mut self, state: GalaxyState, stars: &[Star]) {
+        assert_eq!(stars.len(), self.initial_data.len(), "restore() needs exactly star_count() stars");
+        self.upload(stars);
+        self.state.time_seed = state.time_seed;
+        self.state.dilation_mode = state.dilation_mode;
+        self.state.first_step = 0.0;
+    }
+
+    // Like the GPU upload, culls right away.
+    fn upload(&mut self, stars: &[Star]) {
+        self.stars.copy_from_slice(stars);
+        self.ids.iter_mut().enumerate().for_each(|(k, id)| *id = k as u32);
+        self.alive = stars.len();
+        self.frames_until_cull = self.config.cull_every;
+        self.mergers = 0;
+        if let Some(radius) = self.config.cull_radius() {
+            self.alive = cull(&mut self.stars, &mut self.ids, radius);
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: DilationMode) {
+        self.state.dilation_mode = mode.as_uniform();
+    }
+
+    // Kept in the config too, so reset() starts over with the new values.
+    pub fn set_dilation(&mut self, dilation_threshold: f32, max_debt: f32) {
+        self.config.dilation_threshold = dilation_threshold;
+        self.config.max_debt = max_debt;
+        self.state.dilation_threshold = dilation_threshold;
+        self.state.max_time_debt = max_debt;
+    }
+
+    // Stars not culled past --kill-radius or merged away.
+    pub fn alive(&self) -> usize {
+        self.alive
+    }
+
+    // Runs `n` steps, advancing the clock by dt before each one like the GPU path.
+    // Returns the summed active count.
+    pub fn step(&mut self, n: usize) -> u32 {
+        let mut active = 0;
+        for _ in 0..n {
+            self.state.time_seed += self.state.dt;
+            let (stepped, substeps) = step(&mut self.stars[..self.alive], &self.state, &self.interactions);
+            active += stepped;
+            self.substeps += substeps;
+            self.state.first_step = 0.0;
+        }
+        active
+    }
+
+    pub fn step_and_read(&mut self, n: usize) -> Vec<Star> {
+        self.run_frame(n).stars
+    }
+
+    // Culls first when --cull-every frames have passed, or merges and culls every frame with
+    // --merge-radius, like Simulation::submit_frame.
+    pub fn run_frame(&mut self, steps: usize) -> FrameData {
+        let merging = self.config.merge_radius > 0.0;
+        if merging {
+            self.mergers += merge(&mut self.stars[..self.alive], self.config.merge_radius);
+            self.frames_until_cull = 0;
+        }
+        if let Some(radius) = self.config.cull_radius() {
+            if self.frames_until_cull == 0 {
+                self.alive = cull(&mut self.stars, &mut self.ids, radius);
+                self.frames_until_cull = self.config.cull_every;
+            }
+            self.frames_until_cull -= 1;
+        }
+        self.substeps = 0;
+        // --trails: the positions after each sub-step but the last, packed like the GPU's.
+        let mut trails = Vec::new();
+        let active_updates = if self.config.trails {
+            let mut active = 0;
+            for k in 0..steps {
+                active += self.step(1);
+                if k + 1 < steps {
+                    trails.push(self.creation_order().iter().map(|s| RenderRecord::pack(s).unpack()).collect());
+                }
+            }
+            active
+        } else {
+            self.step(steps)
+        };
+        FrameData {
+            stars: self.creation_order(),
+            sim_time: self.state.time_seed,
+            gpu_ms: None,
+            active_updates,
+            steps,
+            moments: None,
+            image: None,
+            packed: false,
+            readback_bytes: 0,
+            readback_ms: 0.0,
+            force_threads: None,
+            alive: self.alive as u32,
+            mergers: merging.then_some(self.mergers),
+            position_lo: None,
+            substeps: Some(self.substeps),
+            placeholder: false,
+            stats_only: false,
+            trails,
+            watchdog: WatchdogCounts::measure(&self.stars[..self.alive], self.config.divergence_bound),
+        }
+    }
+
+    fn creation_order(&self) -> Vec<Star> {
+        let mut ordered = self.stars.clone();
+        for (star, &id) in self.stars.iter().zip(&self.ids) {
+            ordered[id as usize] = *star;
+        }
+        ordered
+    }
+}
+
+impl Stepper for CpuSimulation {
+    type Ticket = FrameData;
+
+    fn fork(&self) -> Self {
+        CpuSimulation::fork(self)
+    }
+
+    fn reset(&mut self) {
+        CpuSimulation::reset(self)
+    }
+
+    fn restore(&mut self, state: GalaxyState, stars: &[Star]) {
+        CpuSimulation::restore(self, state, stars)
+    }
+
+    fn set_mode(&mut self, mode: DilationMode) {
+        CpuSimulation::set_mode(self, mode)
+    }
+
+    fn set_dilation(&mut self, dilation_threshold: f32, max_debt: f32) {
+        CpuSimulation::set_dilation(self, dilation_threshold, max_debt)
+    }
+
+    fn readback_depth(&self) -> usize {
+        1
+    }
+
+    fn submit_frame(&mut self, steps: usize) -> FrameData {
+        self.run_frame(steps)
+    }
+
+    fn finish_frame(&mut self, ticket: FrameData) -> FrameData {
+        ticket
+    }
+}
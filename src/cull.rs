@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+// Culling's alive count, ahead of the per-chunk params
+const ALIVE_OFFSET: u64 = 8;
+const PARAMS_OFFSET: u64 = 16;
+const DISPATCH_OFFSET: u64 = 80;
+// One `Chunk` or one dispatch entry
+const ENTRY_BYTES: u64 = 16;
+
+// Matches `Culling` in shader.wgsl; only four chunks fit a dispatch's bindings.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullingState {
+    radius: f32,
+    chunks: u32,
+    alive: u32,
+    total: u32,
+    params: [[u32; 4]; 4],
+    dispatch: [[u32; 4]; 4],
+}
+
+// shader.wgsl's cull and cull_args. Compiled once and shared by forked and resized simulations.
+pub struct CullPipelines {
+    cull: wgpu::ComputePipeline,
+    args: wgpu::ComputePipeline,
+}
+
+impl CullPipelines {
+    // `shader` is simulation::shader_module's, which the other passes on shader.wgsl share.
+    pub fn new(device: &wgpu::Device, shader: &wgpu::ShaderModule) -> Self {
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: None,
+                module: shader,
+                entry_point,
+            })
+        };
+        Self { cull: pipeline("cull"), args: pipeline("cull_args") }
+    }
+}
+
+// --kill-radius: stars that escaped past the radius are flagged dead (DEAD_FLAG) and moved
+// behind the living ones by MortonSort::encode_partition, after which every chunk's Chunk
+// and force-pass dispatch cover the living stars only. Stars only ever die, so the living
+// stay a prefix of the buffers between culls.
+pub struct Culling {
+    pipelines: Arc<CullPipelines>,
+    state: wgpu::Buffer,
+    // One per star chunk, with its whole workgroup count
+    cull: Vec<(wgpu::BindGroup, u32)>,
+    args: wgpu::BindGroup,
+}
+
+impl Culling {
+    // `chunks` are each chunk's entries for shader.wgsl bindings 0 and 3 to 6, its `Chunk`
+    // as [offset, count, stride, total] over every star, and its workgroup count.
+    pub fn new(
+        device: &wgpu::Device,
+        pipelines: Arc<CullPipelines>,
+        radius: f32,
+        chunks: &[(Vec<wgpu::BindGroupEntry>, [u32; 4], u32)],
+    ) -> Self {
+        let mut initial = CullingState {
+            radius,
+            chunks: chunks.len() as u32,
+            alive: chunks[0].1[3],
+            total: chunks[0].1[3],
+            ..bytemuck::Zeroable::zeroed()
+        };
+        for (k, (_, params, workgroups)) in chunks.iter().enumerate() {
+            initial.params[k] = *params;
+            initial.dispatch[k] = [*workgroups, 1, 1, 0];
+        }
+        let state = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Culling"),
+            contents: bytemuck::bytes_of(&initial),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+        let state_entry = wgpu::BindGroupEntry { binding: 14, resource: state.as_entire_binding() };
+        let bind_group = |pipeline: &wgpu::ComputePipeline, entries: Vec<wgpu::BindGroupEntry<'_>>| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &entries,
+            })
+        };
+        let cull = chunks
+            .iter()
+            .map(|(entries, _, workgroups)| {
+                let entries = entries.iter().cloned().chain([state_entry.clone()]).collect();
+                (bind_group(&pipelines.cull, entries), *workgroups)
+            })
+            .collect();
+        let args = bind_group(&pipelines.args, vec![state_entry]);
+        Self { pipelines, state, cull, args }
+    }
+
+    pub fn pipelines(&self) -> &Arc<CullPipelines> {
+        &self.pipelines
+    }
+
+    // Records pass 1 over each chunk's living stars, as its Chunk params have them, and
+    // pass 2. The partition sort goes between this and apply().
+    pub fn mark(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.state, ALIVE_OFFSET, Some(4));
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Cull"), timestamp_writes: None });
+        cpass.set_pipeline(&self.pipelines.cull);
+        for (bind_group, workgroups) in &self.cull {
+            cpass.set_bind_group(0, bind_group, &[]);
+            cpass.dispatch_workgroups(*workgroups, 1, 1);
+        }
+        cpass.set_pipeline(&self.pipelines.args);
+        cpass.set_bind_group(0, &self.args, &[]);
+        cpass.dispatch_workgroups(1, 1, 1);
+    }
+
+    // Copies the cut-down Chunk of each chunk into its params buffer, in chunk order.
+    pub fn apply<'a>(&self, encoder: &mut wgpu::CommandEncoder, params: impl Iterator<Item = &'a wgpu::Buffer>) {
+        for (k, buffer) in params.enumerate() {
+            encoder.copy_buffer_to_buffer(&self.state, PARAMS_OFFSET + k as u64 * ENTRY_BYTES, buffer, 0, ENTRY_BYTES);
+        }
+    }
+
+    // The indirect dispatch of chunk `k`'s force pass.
+    pub fn dispatch_args(&self, k: usize) -> (&wgpu::Buffer, u64) {
+        (&self.state, DISPATCH_OFFSET + k as u64 * ENTRY_BYTES)
+    }
+
+    // Copies the living star count into `dest`.
+    pub fn copy_alive(&self, encoder: &mut wgpu::CommandEncoder, dest: &wgpu::Buffer, offset: u64) {
+        encoder.copy_buffer_to_buffer(&self.state, ALIVE_OFFSET, dest, offset, 4);
+    }
+}
@@ -0,0 +1,188 @@
+use serde::{Serialize, Serializer};
+
+use crate::config::SimConfig;
+use crate::diff::displacements;
+use crate::galaxy::Star;
+use crate::simulation::{DilationMode, Stepper};
+
+// Every field of every star as little-endian f32 bits, in buffer order: what two runs must
+// agree on byte for byte.
+pub fn canonical_bytes(stars: &[Star]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(std::mem::size_of_val(stars));
+    for star in stars {
+        for value in bytemuck::cast_ref::<Star, [f32; 10]>(star) {
+            bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+    }
+    bytes
+}
+
+// FNV-1a over canonical_bytes.
+pub fn state_hash(stars: &[Star]) -> u64 {
+    canonical_bytes(stars).iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3))
+}
+
+// Where a repeat first left the reference run.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct FrameMismatch {
+    // Which repeat, counting the reference as run 0
+    pub run: usize,
+    pub frame: usize,
+    // Stars with any field that differs
+    pub stars: usize,
+    pub max_displacement: f32,
+    // The largest difference in any one field
+    pub max_delta: f32,
+}
+
+// None when both slices are bitwise the same.
+pub fn compare_states(run: usize, frame: usize, reference: &[Star], other: &[Star]) -> Option<FrameMismatch> {
+    let fields = |s: &Star| *bytemuck::cast_ref::<Star, [f32; 10]>(s);
+    let differing: Vec<usize> = (0..reference.len())
+        .filter(|&i| fields(&reference[i]).iter().zip(fields(&other[i])).any(|(a, b)| a.to_bits() != b.to_bits()))
+        .collect();
+    if differing.is_empty() && reference.len() == other.len() {
+        return None;
+    }
+    let max_displacement = displacements(reference, other).into_iter().fold(0.0, f32::max);
+    let max_delta = differing
+        .iter()
+        .flat_map(|&i| fields(&reference[i]).into_iter().zip(fields(&other[i])).map(|(a, b)| (a - b).abs()))
+        .fold(0.0, |max: f32, d| if d.is_nan() { f32::INFINITY } else { max.max(d) });
+    Some(FrameMismatch { run, frame, stars: differing.len(), max_displacement, max_delta })
+}
+
+// One mode's repeats.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModeDeterminism {
+    pub mode: DilationMode,
+    // state_hash of each run's final frame, the reference first
+    pub final_hashes: Vec<u64>,
+    // The earliest frame any repeat diverged at
+    pub first_mismatch: Option<FrameMismatch>,
+}
+
+impl ModeDeterminism {
+    pub fn deterministic(&self) -> bool {
+        self.first_mismatch.is_none() && self.final_hashes.windows(2).all(|w| w[0] == w[1])
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeterminismReport {
+    pub adapter: String,
+    pub runs: usize,
+    pub frames: usize,
+    pub modes: Vec<ModeDeterminism>,
+}
+
+impl DeterminismReport {
+    pub fn deterministic(&self) -> bool {
+        self.modes.iter().all(ModeDeterminism::deterministic)
+    }
+
+    pub fn table(&self) -> String {
+        let mut table = format!(
+            "Determinism on {}: {} runs x {} frames\n\n\
+             | Mode | Final hash | Deterministic | First divergence |\n\
+             |------|------------|---------------|------------------|\n",
+            self.adapter, self.runs, self.frames
+        );
+        for m in &self.modes {
+            let divergence = m.first_mismatch.map_or("-".to_string(), |d| {
+                format!(
+                    "run {} frame {}: {} stars, max displacement {:.3e}, max field delta {:.3e}",
+                    d.run, d.frame, d.stars, d.max_displacement, d.max_delta
+                )
+            });
+            table.push_str(&format!(
+                "| {} | {:016x} | {} | {} |\n",
+                m.mode.name(),
+                m.final_hashes[0],
+                if m.deterministic() { "yes" } else { "no" },
+                divergence
+            ));
+        }
+        table
+    }
+}
+
+// The `determinism` object of the report JSON, with each verdict and the hashes in hex.
+impl Serialize for DeterminismReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct ModeJson {
+            mode: DilationMode,
+            deterministic: bool,
+            final_hashes: Vec<String>,
+            first_mismatch: Option<FrameMismatch>,
+        }
+        #[derive(Serialize)]
+        struct ReportJson<'a> {
+            adapter: &'a str,
+            runs: usize,
+            frames: usize,
+            deterministic: bool,
+            modes: Vec<ModeJson>,
+        }
+        let modes = self
+            .modes
+            .iter()
+            .map(|m| ModeJson {
+                mode: m.mode,
+                deterministic: m.deterministic(),
+                final_hashes: m.final_hashes.iter().map(|h| format!("{:016x}", h)).collect(),
+                first_mismatch: m.first_mismatch,
+            })
+            .collect();
+        ReportJson { adapter: &self.adapter, runs: self.runs, frames: self.frames, deterministic: self.deterministic(), modes }
+            .serialize(serializer)
+    }
+}
+
+// `--verify-determinism N`: each mode run N times from the same galaxy, every repeat on
+// `candidate` stepped in lockstep with a fresh run of `reference` so each frame compares
+// directly without keeping any. `on_frame(mode, run, frame)` sees each compared frame.
+pub fn verify<S: Stepper>(
+    reference: &mut S,
+    candidate: &mut S,
+    config: &SimConfig,
+    runs: usize,
+    adapter: &str,
+    mut on_frame: impl FnMut(DilationMode, usize, usize) -> bool,
+) -> DeterminismReport {
+    let mut modes = Vec::new();
+    'modes: for mode in [DilationMode::Newtonian, DilationMode::Chronoturin] {
+        let mut result = ModeDeterminism { mode, final_hashes: Vec::new(), first_mismatch: None };
+        for run in 1..runs {
+            for sim in [&mut *reference, &mut *candidate] {
+                sim.reset();
+                sim.set_mode(mode);
+            }
+            let mut last = None;
+            for frame in 0..config.frames {
+                let ticket = reference.submit_frame(config.steps_per_frame);
+                let expected = reference.finish_frame(ticket);
+                let ticket = candidate.submit_frame(config.steps_per_frame);
+                let actual = candidate.finish_frame(ticket);
+                if let Some(mismatch) = compare_states(run, frame, &expected.stars, &actual.stars) {
+                    if result.first_mismatch.is_none_or(|m| frame < m.frame) {
+                        result.first_mismatch = Some(mismatch);
+                    }
+                }
+                let go_on = on_frame(mode, run, frame);
+                last = Some((expected, actual));
+                if !go_on {
+                    break 'modes;
+                }
+            }
+            let (expected, actual) = last.expect("validate() requires at least one frame");
+            if run == 1 {
+                result.final_hashes.push(state_hash(&expected.stars));
+            }
+            result.final_hashes.push(state_hash(&actual.stars));
+        }
+        modes.push(result);
+    }
+    DeterminismReport { adapter: adapter.to_string(), runs, frames: config.frames, modes }
+}
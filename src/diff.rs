@@ -0,0 +1,55 @@
+use image::{Rgb, RgbImage};
+
+use crate::galaxy::Star;
+use crate::render::{project, Camera};
+
+// 3D displacement between the same star index in two runs.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub struct DisplacementStats {
+    pub mean: f32,
+    pub p99: f32,
+    pub max: f32,
+    // Stars whose displacement is NaN/inf in either run; excluded from the numbers above
+    pub non_finite: usize,
+}
+
+pub fn displacements(reference: &[Star], other: &[Star]) -> Vec<f32> {
+    reference.iter().zip(other).map(|(a, b)| ((a.x - b.x).powi(2) + (a.y - b.y).powi(2) + (a.z - b.z).powi(2)).sqrt()).collect()
+}
+
+pub fn displacement_stats(reference: &[Star], other: &[Star]) -> DisplacementStats {
+    let all = displacements(reference, other);
+    let mut finite: Vec<f32> = all.iter().copied().filter(|d| d.is_finite()).collect();
+    let non_finite = all.len() - finite.len();
+    if finite.is_empty() {
+        return DisplacementStats { mean: 0.0, p99: 0.0, max: 0.0, non_finite };
+    }
+    finite.sort_by(f32::total_cmp);
+    let mean = (finite.iter().map(|&d| d as f64).sum::<f64>() / finite.len() as f64) as f32;
+    // Nearest-rank percentile
+    let rank = ((finite.len() as f64 * 0.99).ceil() as usize).clamp(1, finite.len());
+    DisplacementStats { mean, p99: finite[rank - 1], max: finite[finite.len() - 1], non_finite }
+}
+
+// green (negligible) -> yellow -> red (>= full_scale)
+pub fn error_color(error: f32, full_scale: f32) -> Rgb<u8> {
+    let t = if error.is_finite() { (error / full_scale).clamp(0.0, 1.0) } else { 1.0 };
+    let (r, g) = if t < 0.5 { (t * 2.0, 1.0) } else { (1.0, (1.0 - t) * 2.0) };
+    Rgb([(r * 255.0).round() as u8, (g * 255.0).round() as u8, 0])
+}
+
+// Plots each star at its `other` position colored by how far it drifted from `reference`.
+// Stars that left the view in `other` fall back to their reference position; stars out of
+// view in both runs are not drawn (they still count in displacement_stats).
+pub fn render_diff(reference: &[Star], other: &[Star], cam: &Camera, full_scale: f32) -> RgbImage {
+    let mut img = RgbImage::new(cam.width, cam.height);
+    // Draw small errors first so large ones stay visible where stars overlap.
+    let errors = displacements(reference, other);
+    let mut order: Vec<usize> = (0..errors.len()).collect();
+    order.sort_by(|&a, &b| errors[a].total_cmp(&errors[b]));
+    for i in order {
+        let Some((x, y)) = project(&other[i], cam).or_else(|| project(&reference[i], cam)) else { continue };
+        img.put_pixel(x, y, error_color(errors[i], full_scale));
+    }
+    img
+}
@@ -0,0 +1,262 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::ChronoError;
+use crate::galaxy::Star;
+use crate::manifest::RunManifest;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::precision::DfStar;
+use crate::precision::Precision;
+use crate::simulation::DilationMode;
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DumpFormat {
+    Csv,
+    Bin,
+}
+
+impl DumpFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            DumpFormat::Csv => "csv",
+            DumpFormat::Bin => "bin",
+        }
+    }
+}
+
+// Column order of both formats; matches the field order of `Star`.
+pub const STAR_FIELDS: [&str; 10] = ["x", "y", "z", "vx", "vy", "vz", "mass", "data_type", "time_debt", "active_flag"];
+// Appended to both with --precision df64; matches `DfStar::lo`.
+pub const LO_FIELDS: [&str; 3] = ["x_lo", "y_lo", "z_lo"];
+
+// What a dump was taken from, recorded in the binary sidecar.
+#[derive(Clone, Copy, Debug)]
+pub struct DumpInfo {
+    pub mode: &'static str,
+    pub frame: usize,
+    pub sim_time: f32,
+}
+
+// The `<path>.json` beside a binary dump: how to read it back, and what it was taken from.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(serde::Serialize)]
+struct Sidecar<'a> {
+    format: &'static str,
+    stars: usize,
+    stride_bytes: u64,
+    fields: &'a [&'a str],
+    precision: Precision,
+    mode: &'static str,
+    frame: usize,
+    sim_time: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manifest: Option<&'a RunManifest>,
+}
+
+// Writes `stars` to `path` (CSV with a header row, or raw little-endian f32 plus a
+// `<path>.json` sidecar). f32 values print in shortest round-trip form, so parsing a
+// CSV dump gives back the exact readback. With `position_lo` (FrameData::position_lo)
+// every record gains LO_FIELDS, and the sidecar says df64. `manifest` goes in a `# `
+// comment line above the CSV header, or under the sidecar's "manifest" key.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_dump(
+    path: &Path,
+    stars: &[Star],
+    position_lo: Option<&[[f32; 3]]>,
+    format: DumpFormat,
+    info: &DumpInfo,
+    manifest: Option<&RunManifest>,
+) -> Result<(), ChronoError> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let fields: Vec<&str> = STAR_FIELDS.iter().chain(LO_FIELDS.iter().filter(|_| position_lo.is_some())).copied().collect();
+    match format {
+        DumpFormat::Csv => {
+            if let Some(manifest) = manifest {
+                writeln!(out, "# {}", manifest.to_json())?;
+            }
+            writeln!(out, "{}", fields.join(","))?;
+            for (i, s) in stars.iter().enumerate() {
+                let lo = position_lo.map_or(&[][..], |lo| &lo[i][..]);
+                let values: Vec<String> = bytemuck::cast_ref::<Star, [f32; 10]>(s).iter().chain(lo).map(f32::to_string).collect();
+                writeln!(out, "{}", values.join(","))?;
+            }
+        }
+        DumpFormat::Bin => {
+            let (precision, values): (Precision, Vec<f32>) = match position_lo {
+                None => (Precision::F32, bytemuck::cast_slice(stars).to_vec()),
+                Some(lo) => {
+                    let wide: Vec<DfStar> = stars.iter().zip(lo).map(|(&star, &lo)| DfStar { star, lo }).collect();
+                    (Precision::Df64, bytemuck::cast_slice(&wide).to_vec())
+                }
+            };
+            for v in values {
+                out.write_all(&v.to_le_bytes())?;
+            }
+            let sidecar = Sidecar {
+                format: "f32le",
+                stars: stars.len(),
+                stride_bytes: precision.star_bytes(),
+                fields: &fields,
+                precision,
+                mode: info.mode,
+                frame: info.frame,
+                sim_time: info.sim_time,
+                manifest,
+            };
+            std::fs::write(sidecar_path(path), serde_json::to_string(&sidecar).expect("Sidecar serializes to JSON") + "\n")?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".json");
+    PathBuf::from(name)
+}
+
+// What a binary dump's sidecar records, and its star count; None without a readable sidecar.
+pub fn read_sidecar(path: &Path) -> Option<(DumpInfo, usize)> {
+    let sidecar = sidecar_json(path)?;
+    let mode = [DilationMode::Newtonian, DilationMode::Chronoturin].into_iter().find(|m| sidecar["mode"] == m.name())?;
+    let info = DumpInfo { mode: mode.name(), frame: sidecar["frame"].as_u64()? as usize, sim_time: sidecar["sim_time"].as_f64()? as f32 };
+    Some((info, sidecar["stars"].as_u64()? as usize))
+}
+
+// The manifest write_dump recorded: the CSV comment line, or the sidecar's. None for dumps
+// written without one, or binary dumps without a sidecar.
+pub fn read_dump_manifest(path: &Path) -> Result<Option<RunManifest>, ChronoError> {
+    let parse_error = |line: usize, message: String| ChronoError::Parse { path: path.to_path_buf(), line, message };
+    if path.extension().is_some_and(|e| e == "bin") {
+        let Some(manifest) = sidecar_json(path).and_then(|sidecar| sidecar.get("manifest").cloned()) else { return Ok(None) };
+        let manifest = serde_json::from_value(manifest).map_err(|e| parse_error(0, format!("unreadable sidecar manifest: {}", e)))?;
+        return Ok(Some(manifest));
+    }
+    let mut first = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut first)?;
+    match first.trim().strip_prefix("# ") {
+        Some(json) => RunManifest::from_json(json).map(Some).map_err(|e| parse_error(1, format!("unreadable manifest: {}", e))),
+        None => Ok(None),
+    }
+}
+
+fn sidecar_json(path: &Path) -> Option<serde_json::Value> {
+    serde_json::from_str(&std::fs::read_to_string(sidecar_path(path)).ok()?).ok()
+}
+
+// Parses a CSV dump. The header row is required and must list STAR_FIELDS in order,
+// then LO_FIELDS for a df64 dump; those residuals are dropped. `#` lines are comments.
+pub fn read_csv_dump(path: &Path) -> Result<Vec<Star>, ChronoError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut stars = Vec::new();
+    let mut columns = STAR_FIELDS.len();
+    let mut header = true;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let parse_error = |message: String| ChronoError::Parse { path: path.to_path_buf(), line: i + 1, message };
+        if line.starts_with('#') {
+            continue;
+        }
+        if std::mem::take(&mut header) {
+            let wide = [&STAR_FIELDS[..], &LO_FIELDS[..]].concat().join(",");
+            if line.trim() == wide {
+                columns = STAR_FIELDS.len() + LO_FIELDS.len();
+            } else if line.trim() != STAR_FIELDS.join(",") {
+                return Err(parse_error(format!("expected header \"{}\"", STAR_FIELDS.join(","))));
+            }
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let values = parse_row(&line).map_err(parse_error)?;
+        if values.len() != columns {
+            return Err(parse_error(format!("expected {} columns, found {}", columns, values.len())));
+        }
+        stars.push(bytemuck::pod_read_unaligned(bytemuck::cast_slice(&values[..STAR_FIELDS.len()])));
+    }
+    Ok(stars)
+}
+
+// Parses a binary dump: a whole number of little-endian 40-byte star records, or 52-byte
+// DfStar ones when the sidecar says df64 (their residuals are dropped).
+pub fn read_bin_dump(path: &Path) -> Result<Vec<Star>, ChronoError> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    let df64 = sidecar_json(path).is_some_and(|sidecar| sidecar["precision"] == Precision::Df64.name());
+    let stride = if df64 { Precision::Df64 } else { Precision::F32 }.star_bytes() as usize;
+    if data.len() % stride != 0 {
+        return Err(ChronoError::Parse {
+            path: path.to_path_buf(),
+            line: 0,
+            message: format!("{} bytes is not a whole number of {}-byte star records", data.len(), stride),
+        });
+    }
+    let values: Vec<f32> = data.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+    Ok(values.chunks_exact(stride / 4).map(|v| bytemuck::pod_read_unaligned(bytemuck::cast_slice(&v[..STAR_FIELDS.len()]))).collect())
+}
+
+// Initial conditions from a file: a binary dump (`.bin`), or CSV rows of
+// `x,y,z,vx,vy,vz,mass,data_type` (time_debt and active_flag start at 0) or full
+// 10-column dump rows. A leading header row is optional, and `#` lines are comments. Every
+// value must be finite.
+pub fn read_initial_conditions(path: &Path) -> Result<Vec<Star>, ChronoError> {
+    let parse_error = |line: usize, message: String| ChronoError::Parse { path: path.to_path_buf(), line, message };
+    let stars = if path.extension().is_some_and(|e| e == "bin") {
+        let stars = read_bin_dump(path)?;
+        if let Some(i) = stars.iter().position(|s| !bytemuck::cast_ref::<Star, [f32; 10]>(s).iter().all(|v| v.is_finite())) {
+            return Err(parse_error(0, format!("star {} has a non-finite value", i)));
+        }
+        stars
+    } else {
+        let reader = BufReader::new(File::open(path)?);
+        let mut stars = Vec::new();
+        let mut header = true;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let first = std::mem::take(&mut header);
+            let values = match parse_row(&line) {
+                Ok(values) => values,
+                // Anything non-numeric on the first line is taken as a header.
+                Err(_) if first => continue,
+                Err(message) => return Err(parse_error(i + 1, message)),
+            };
+            let star = match values.len() {
+                8 => Star {
+                    x: values[0],
+                    y: values[1],
+                    z: values[2],
+                    vx: values[3],
+                    vy: values[4],
+                    vz: values[5],
+                    mass: values[6],
+                    data_type: values[7],
+                    time_debt: 0.0,
+                    active_flag: 0.0,
+                },
+                10 => bytemuck::pod_read_unaligned(bytemuck::cast_slice(&values)),
+                n => return Err(parse_error(i + 1, format!("expected 8 or 10 columns, found {}", n))),
+            };
+            if let Some(col) = values.iter().position(|v| !v.is_finite()) {
+                return Err(parse_error(i + 1, format!("{} is not finite", STAR_FIELDS[col])));
+            }
+            stars.push(star);
+        }
+        stars
+    };
+    if stars.is_empty() {
+        return Err(parse_error(0, "no stars found".into()));
+    }
+    Ok(stars)
+}
+
+fn parse_row(line: &str) -> Result<Vec<f32>, String> {
+    line.split(',').map(|v| v.trim().parse::<f32>().map_err(|e| format!("\"{}\": {}", v.trim(), e))).collect()
+}
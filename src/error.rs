@@ -0,0 +1,140 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ChronoError {
+    AdapterNotFound,
+    // --adapter did not match any adapter name
+    AdapterNotMatched { wanted: String, available: Vec<String> },
+    // An --adapters index past the adapters wgpu can see
+    AdapterIndex { index: usize, available: Vec<String> },
+    DeviceRequestFailed(wgpu::RequestDeviceError),
+    // A device of an --adapters run reported an error or was lost; `adapter` names it
+    Device { adapter: String, message: String },
+    // The stars would not fit in chunks::MAX_CHUNKS storage bindings on this device
+    BufferTooLarge { stars: u32, bytes: u128, max_bytes: u64 },
+    InvalidConfig(String),
+    // An embedded shader's Star or GalaxyState disagrees with the Rust struct
+    ShaderLayout(String),
+    // A --resume or --chain-from file is not a checkpoint this build can read
+    Checkpoint { path: std::path::PathBuf, reason: String },
+    // A data file could not be parsed; `line` is 1-based (0 when not line-oriented)
+    Parse { path: std::path::PathBuf, line: usize, message: String },
+    // The external video encoder failed; carries its stderr
+    Encoder(String),
+    // The --view window or web canvas could not be opened or presented to
+    Window(String),
+    // plotters failed to draw a --plots chart
+    Plot(String),
+    // The divergence watchdog fired with --on-divergence abort; `conditions` is WatchdogCounts::describe
+    Diverged { mode: &'static str, frame: usize, conditions: String },
+    // The --frames-to reader closed the pipe with --on-pipe-close abort
+    PipeClosed { target: String, frames: u64 },
+    // The run's device was lost (a driver reset or hang) and could not be recovered
+    DeviceLost(String),
+    // A readback buffer could not be mapped, so the frame never came back
+    MapFailed(String),
+    // A readback never mapped, even after --map-timeout-secs and a few more polls
+    MapTimeout { secs: f32 },
+    // An uncaptured wgpu validation or out-of-memory error
+    Validation(String),
+    // tune: the most conservative threshold already missed the error budget
+    OverBudget { threshold: f32, error: f32, budget: f32 },
+    // The memory plan does not fit the device's limits or --memory-budget; `mitigations` are
+    // the flags that would shrink it
+    OverMemory { reason: String, mitigations: Vec<String> },
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ChronoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChronoError::AdapterNotFound => write!(f, "no compatible GPU adapter was found"),
+            ChronoError::AdapterNotMatched { wanted, available } => {
+                write!(f, "no adapter name contains \"{}\"; available adapters:", wanted)?;
+                if available.is_empty() {
+                    write!(f, " none")?;
+                }
+                for name in available {
+                    write!(f, "\n  - {}", name)?;
+                }
+                Ok(())
+            }
+            ChronoError::AdapterIndex { index, available } => {
+                write!(f, "there is no adapter [{}]; available adapters:", index)?;
+                if available.is_empty() {
+                    write!(f, " none")?;
+                }
+                for (i, name) in available.iter().enumerate() {
+                    write!(f, "\n  [{}] {}", i, name)?;
+                }
+                Ok(())
+            }
+            ChronoError::Device { adapter, message } => write!(f, "GPU error on {}: {}", adapter, message),
+            ChronoError::DeviceRequestFailed(e) => write!(f, "the GPU adapter refused to create a device: {}", e),
+            ChronoError::BufferTooLarge { stars, bytes, max_bytes } => write!(
+                f,
+                "{} stars need {} bytes of storage, but the device allows at most {} bytes across {} star buffers",
+                stars,
+                bytes,
+                max_bytes,
+                crate::chunks::MAX_CHUNKS
+            ),
+            ChronoError::InvalidConfig(msg) => write!(f, "{}", msg),
+            ChronoError::ShaderLayout(msg) => write!(f, "shader struct layout does not match the Rust side: {}", msg),
+            ChronoError::Checkpoint { path, reason } => write!(f, "{} is not a usable checkpoint: {}", path.display(), reason),
+            ChronoError::Parse { path, line: 0, message } => write!(f, "{}: {}", path.display(), message),
+            ChronoError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+            ChronoError::Encoder(msg) => write!(f, "video encoding failed: {}", msg),
+            ChronoError::Window(msg) => write!(f, "viewer window: {}", msg),
+            ChronoError::Plot(msg) => write!(f, "plotting failed: {}", msg),
+            ChronoError::Diverged { mode, frame, conditions } => write!(f, "{} diverged at frame {}: {}", mode, frame, conditions),
+            ChronoError::PipeClosed { target, frames } => write!(f, "the --frames-to reader closed {} after {} frames", target, frames),
+            ChronoError::DeviceLost(msg) => write!(f, "the GPU device was lost: {}", msg),
+            ChronoError::MapFailed(msg) => write!(f, "a frame's readback could not be mapped: {}", msg),
+            ChronoError::MapTimeout { secs } => write!(
+                f,
+                "a frame's readback did not map within --map-timeout-secs {} or the polls after it; the driver stopped answering",
+                secs
+            ),
+            ChronoError::Validation(msg) => write!(f, "GPU validation error: {}", msg),
+            ChronoError::OverBudget { threshold, error, budget } => write!(
+                f,
+                "even --dilation-threshold {} diverges by {:.4} of the galaxy's radius, over the {} error budget",
+                threshold, error, budget
+            ),
+            ChronoError::OverMemory { reason, mitigations } => {
+                write!(f, "{}", reason)?;
+                if !mitigations.is_empty() {
+                    write!(f, "; to fit, try:")?;
+                }
+                for mitigation in mitigations {
+                    write!(f, "\n  - {}", mitigation)?;
+                }
+                Ok(())
+            }
+            ChronoError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ChronoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChronoError::DeviceRequestFailed(e) => Some(e),
+            ChronoError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for ChronoError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        ChronoError::DeviceRequestFailed(e)
+    }
+}
+
+impl From<std::io::Error> for ChronoError {
+    fn from(e: std::io::Error) -> Self {
+        ChronoError::Io(e)
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+
+// --external-potential: a fixed field centered on the origin that every star feels on top
+// of the others' pull, per unit mass like the rest of the model.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExternalPotential {
+    // Logarithmic halo, phi = v0^2 / 2 * ln(r^2 + scale^2): a rotation curve that rises
+    // inside `scale` and flattens at v0 outside it
+    Halo { scale: f32, v0: f32 },
+    // Harmonic trap, phi = omega^2 r^2 / 2: every star oscillates with angular frequency omega
+    Harmonic { omega: f32 },
+}
+
+// `kind:key=value,...`, e.g. `halo:scale=200,v0=1.5` or `harmonic:omega=0.05`. Every key of
+// the kind is required.
+pub fn parse_external_potential(arg: &str) -> Result<ExternalPotential, String> {
+    let (kind, params) = arg.split_once(':').unwrap_or((arg, ""));
+    let mut values: Vec<(&str, f32)> = Vec::new();
+    for pair in params.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let Some((key, value)) = pair.split_once('=') else { return Err(format!("`{}` is not key=value", pair)) };
+        let value: f32 = value.trim().parse().map_err(|_| format!("`{}` is not a number", value.trim()))?;
+        if !value.is_finite() || value <= 0.0 {
+            return Err(format!("{} must be positive", key.trim()));
+        }
+        values.push((key.trim(), value));
+    }
+    let keys: &[&str] = match kind {
+        "halo" => &["scale", "v0"],
+        "harmonic" => &["omega"],
+        _ => return Err(format!("unknown potential `{}`; expected halo or harmonic", kind)),
+    };
+    if let Some((key, _)) = values.iter().find(|(key, _)| !keys.contains(key)) {
+        return Err(format!("{} takes {}, not {}", kind, keys.join(" and "), key));
+    }
+    let get = |name: &str| values.iter().find(|(key, _)| *key == name).map(|&(_, v)| v).ok_or_else(|| format!("{} needs {}=", kind, name));
+    Ok(match kind {
+        "halo" => ExternalPotential::Halo { scale: get("scale")?, v0: get("v0")? },
+        _ => ExternalPotential::Harmonic { omega: get("omega")? },
+    })
+}
+
+// As parse_external_potential reads it.
+impl fmt::Display for ExternalPotential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Halo { scale, v0 } => write!(f, "halo:scale={},v0={}", scale, v0),
+            Self::Harmonic { omega } => write!(f, "harmonic:omega={}", omega),
+        }
+    }
+}
+
+impl ExternalPotential {
+    // GalaxyState's external_kind, external_scale and external_strength.
+    pub fn as_uniform(potential: Option<Self>) -> [f32; 3] {
+        match potential {
+            None => [0.0; 3],
+            Some(Self::Halo { scale, v0 }) => [1.0, scale, v0 * v0],
+            Some(Self::Harmonic { omega }) => [2.0, 0.0, omega * omega],
+        }
+    }
+
+    // phi at `r`, for the conservation diagnostics.
+    pub fn energy(&self, r: [f64; 3]) -> f64 {
+        let r_sq = r[0] * r[0] + r[1] * r[1] + r[2] * r[2];
+        match *self {
+            Self::Halo { scale, v0 } => 0.5 * (v0 as f64).powi(2) * (r_sq + (scale as f64).powi(2)).ln(),
+            Self::Harmonic { omega } => 0.5 * (omega as f64).powi(2) * r_sq,
+        }
+    }
+}
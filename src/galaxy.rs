@@ -0,0 +1,211 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::error::ChronoError;
+use crate::external::ExternalPotential;
+use crate::presets::TypeMix;
+
+// DATA TYPES: 0 to --types - 1. Same types attract, different ones repel, unless
+// --interactions says otherwise (see interactions.rs).
+pub const TYPE_A: f32 = 0.0;
+pub const TYPE_B: f32 = 1.0;
+// A pinned central mass (--central-mass); the shader never moves it. Negative so every
+// non-negative value stays free for --types.
+pub const TYPE_CENTRAL: f32 = -1.0;
+// active_flag of a star culled past --kill-radius (see cull.rs). It stays in the buffers,
+// behind the living ones, but nothing steps, pulls with, draws or dumps it again.
+pub const DEAD_FLAG: f32 = -1.0;
+// --types and palette entries are capped here; raster.wgsl and points.wgsl size their
+// palette uniforms to match.
+pub const MAX_TYPES: usize = 16;
+
+// With the serde feature, a Star serializes with the dump columns (dump::STAR_FIELDS) as keys.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Star {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub vx: f32,
+    pub vy: f32,
+    pub vz: f32,
+    pub mass: f32,
+    pub data_type: f32,
+    pub time_debt: f32,
+    pub active_flag: f32, // Replaces 'padding' to visualize work
+}
+
+// The same test as the shaders' `data_type < -0.5`.
+pub fn is_central(star: &Star) -> bool {
+    star.data_type < TYPE_CENTRAL + 0.5
+}
+
+pub fn is_dead(star: &Star) -> bool {
+    star.active_flag < DEAD_FLAG + 0.5
+}
+
+// Positions and velocities as they are, but no time debt or stepped flag carried over, as
+// a fresh galaxy starts; culled stars stay dead.
+pub fn clear_dilation(stars: &mut [Star]) {
+    for star in stars.iter_mut() {
+        star.time_debt = 0.0;
+        if !is_dead(star) {
+            star.active_flag = 0.0;
+        }
+    }
+}
+
+// The palette entry that draws `star`, the shaders' `u32(floor(data_type + 0.5))`.
+pub fn type_index(star: &Star) -> usize {
+    (star.data_type + 0.5).floor().max(0.0) as usize
+}
+
+// Rejects stars that are neither a central mass nor one of `types` data types, such as
+// initial conditions written when central masses were data_type 2.
+pub fn check_types(stars: &[Star], types: usize) -> Result<(), ChronoError> {
+    let valid = |s: &Star| is_central(s) || (s.data_type == s.data_type.round() && (s.data_type as usize) < types);
+    match stars.iter().position(|s| !valid(s)) {
+        Some(i) => Err(ChronoError::InvalidConfig(format!(
+            "star {} has data_type {}; with --types {} it must be 0 to {}, or {} for a central mass",
+            i,
+            stars[i].data_type,
+            types,
+            types - 1,
+            TYPE_CENTRAL
+        ))),
+        None => Ok(()),
+    }
+}
+
+// active_flag counts per data type, as of the last step; central masses are left out.
+pub fn active_by_type(stars: &[Star], types: usize) -> Vec<u32> {
+    let mut counts = vec![0; types];
+    for star in stars.iter().filter(|s| s.active_flag > 0.5 && !is_central(s)) {
+        counts[type_index(star).min(types - 1)] += 1;
+    }
+    counts
+}
+
+// shader_layout.rs checks the WGSL side of both structs at startup.
+const _: () = assert!(std::mem::size_of::<Star>() == 40 && std::mem::align_of::<Star>() == 4);
+
+// Uniform buffer shared with shader.wgsl. WGSL rounds uniform structs up to 16 bytes,
+// so any new field has to keep the size a multiple of 16 (pad with unused floats).
+// With the serde feature its keys are a file format; g_const and max_time_debt go by their
+// flags' names, g and max_debt.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GalaxyState {
+    pub time_seed: f32,
+    pub dilation_mode: f32, // 0.0 = Newton, 1.0 = Chronoturin
+    pub softening: f32,     // Plummer softening length, added in quadrature to every distance
+    #[cfg_attr(feature = "serde", serde(rename = "g"))]
+    pub g_const: f32,
+    pub dt: f32,
+    pub damping: f32,            // velocity factor per step, constant decay per unit of sim time
+    pub integrator: f32,         // 0.0 = Euler, 1.0 = leapfrog
+    pub first_step: f32,         // 1.0 on a pass's first dispatch, where leapfrog kicks half a step
+    pub dilation_threshold: f32, // Chronoturin: stars below this mean tension sleep
+    #[cfg_attr(feature = "serde", serde(rename = "max_debt"))]
+    pub max_time_debt: f32, // a sleeping star accrues 1/max_time_debt of a step per dispatch
+    pub drag: f32,               // --drag: deceleration per unit of velocity
+    pub external_kind: f32,      // --external-potential: 0.0 = none, 1.0 = halo, 2.0 = harmonic
+    pub external_scale: f32,     // halo core radius
+    pub external_strength: f32,  // v0^2 for the halo, omega^2 for the trap
+    pub boundary_kind: f32,      // --boundary: 0.0 = open, 1.0 = reflect, 2.0 = periodic
+    pub boundary_size: f32,      // the reflecting radius or the periodic side
+    pub max_velocity: f32,       // --max-velocity: speeds are clamped to this after every kick, 0.0 = no clamp
+    pub max_substeps: f32,       // --max-substeps: the most substeps a star may split a step into
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub _pad: [f32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<GalaxyState>() == 80 && std::mem::align_of::<GalaxyState>() == 4);
+
+// Defaults reproduce the constants the shader used to hard-code (G = 0.5, +10 on d^2,
+// DT = 0.05 and a 0.90 velocity factor every step).
+pub const DEFAULT_G: f32 = 0.5;
+pub const DEFAULT_SOFTENING: f32 = 3.1622777;
+pub const DEFAULT_DT: f32 = 0.05;
+// ...and Chronoturin's "tension < 0.5 sleeps at 0.02 of a step per dispatch".
+pub const DEFAULT_DILATION_THRESHOLD: f32 = 0.5;
+pub const DEFAULT_MAX_DEBT: f32 = 50.0;
+// The largest --max-substeps, dt / 64 for the hardest-pressed stars
+pub const MAX_SUBSTEPS: u32 = 64;
+const DAMPING_PER_DEFAULT_STEP: f32 = 0.90;
+
+impl GalaxyState {
+    pub fn new(time_seed: f32, dilation_mode: f32, config: &crate::config::SimConfig) -> Self {
+        let [external_kind, external_scale, external_strength] = ExternalPotential::as_uniform(config.external_potential);
+        let [boundary_kind, boundary_size] = config.boundary.as_uniform();
+        Self {
+            time_seed,
+            dilation_mode,
+            softening: config.softening,
+            g_const: config.g,
+            dt: config.dt,
+            damping: damping_for(config.dt),
+            integrator: config.integrator.as_uniform(),
+            first_step: 1.0,
+            dilation_threshold: config.dilation_threshold,
+            max_time_debt: config.max_debt,
+            drag: config.drag,
+            external_kind,
+            external_scale,
+            external_strength,
+            boundary_kind,
+            boundary_size,
+            max_velocity: config.max_velocity.unwrap_or(0.0),
+            max_substeps: config.max_substeps as f32,
+            _pad: [0.0; 2],
+        }
+    }
+}
+
+// Per-step damping that removes the same fraction of velocity per unit of sim time at any dt.
+pub fn damping_for(dt: f32) -> f32 {
+    DAMPING_PER_DEFAULT_STEP.powf(dt / DEFAULT_DT)
+}
+
+// The bounding box of the finite positions, made cubic and scaled by `scale` around its
+// center, as [lowest corner x, y, z, side]. The side is at least `scale`.
+pub fn bounding_cube(stars: &[Star], scale: f32) -> [f32; 4] {
+    let finite = stars.iter().filter(|s| s.x.is_finite() && s.y.is_finite() && s.z.is_finite());
+    let (lo, hi) = finite.fold(([f32::MAX; 3], [f32::MIN; 3]), |(lo, hi), s| {
+        let p = [s.x, s.y, s.z];
+        ([0, 1, 2].map(|k| lo[k].min(p[k])), [0, 1, 2].map(|k| hi[k].max(p[k])))
+    });
+    if lo[0] > hi[0] {
+        return [-scale / 2.0, -scale / 2.0, -scale / 2.0, scale];
+    }
+    let side = [0, 1, 2].map(|k| hi[k] - lo[k]).into_iter().fold(1.0f32, f32::max) * scale;
+    let center = [0, 1, 2].map(|k| 0.5 * (lo[k] + hi[k]));
+    [center[0] - side / 2.0, center[1] - side / 2.0, center[2] - side / 2.0, side]
+}
+
+// Builds the initial sphere from a seed, so every pass (and every rerun with
+// the same --seed) starts from the exact same galaxy. Stars start on roughly
+// circular orbits; see presets::sphere for the knobs.
+pub fn generate_galaxy(num_stars: u32, seed: u64) -> Vec<Star> {
+    crate::presets::sphere(num_stars, seed, &crate::presets::PresetParams::default())
+}
+
+// The original distribution at rest, with a configurable radius.
+pub fn generate_sphere(num_stars: u32, seed: u64, radius: f32, mix: &TypeMix) -> Vec<Star> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut initial_data = Vec::with_capacity(num_stars as usize);
+    for _ in 0..num_stars {
+        let r = radius * rng.gen::<f32>().sqrt();
+        let theta = rng.gen_range(0.0..std::f32::consts::TAU);
+        let phi = rng.gen_range(0.0..std::f32::consts::PI);
+        let x = r * phi.sin() * theta.cos();
+        let y = r * phi.sin() * theta.sin();
+        let z = r * phi.cos();
+        let data_type = mix.pick(&mut rng);
+
+        initial_data.push(Star { x, y, z, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type, time_debt: 0.0, active_flag: 0.0 });
+    }
+    initial_data
+}
@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, Rgb, RgbImage, RgbaImage};
+
+use crate::error::ChronoError;
+use crate::font;
+
+// Looping GIF written frame by frame, so memory stays flat however long the run is.
+// Both passes go into the same file back to back.
+pub struct GifWriter {
+    encoder: GifEncoder<BufWriter<File>>,
+    width: u32,
+    delay_ms: u32,
+    frames: usize,
+    path: PathBuf,
+}
+
+impl GifWriter {
+    // `width` is the output width in pixels; height follows the source aspect ratio.
+    pub fn create(path: &Path, width: u32, delay_ms: u32) -> Result<Self, ChronoError> {
+        let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+        encoder.set_repeat(Repeat::Infinite).map_err(image_error)?;
+        Ok(Self { encoder, width, delay_ms, frames: 0, path: path.to_path_buf() })
+    }
+
+    pub fn add_frame(&mut self, img: &RgbImage) -> Result<(), ChronoError> {
+        self.add_labeled_frame(img, None)
+    }
+
+    // Downsamples `img` and appends it, optionally stamping `label` across the top.
+    pub fn add_labeled_frame(&mut self, img: &RgbImage, label: Option<(&str, Rgb<u8>)>) -> Result<(), ChronoError> {
+        let height = (img.height() as u64 * self.width as u64 / img.width().max(1) as u64).max(1) as u32;
+        let mut small = box_downsample(img, self.width, height);
+        if let Some((text, color)) = label {
+            let scale = (self.width / 128).max(1);
+            let x = self.width.saturating_sub(font::text_width(text, scale)) / 2;
+            font::draw_text(&mut small, x, 2 * scale, text, scale, color);
+        }
+        let rgba = RgbaImage::from_fn(small.width(), small.height(), |x, y| {
+            let p = small.get_pixel(x, y);
+            image::Rgba([p[0], p[1], p[2], 255])
+        });
+        let delay = Delay::from_numer_denom_ms(self.delay_ms, 1);
+        self.encoder.encode_frame(Frame::from_parts(rgba, 0, 0, delay)).map_err(image_error)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    // Dropping the encoder writes the GIF trailer.
+    pub fn finish(self) -> PathBuf {
+        self.path
+    }
+}
+
+// Averages every source pixel that falls inside each destination pixel.
+pub fn box_downsample(img: &RgbImage, width: u32, height: u32) -> RgbImage {
+    let (sw, sh) = (img.width() as u64, img.height() as u64);
+    RgbImage::from_fn(width, height, |x, y| {
+        let x0 = x as u64 * sw / width as u64;
+        let x1 = ((x as u64 + 1) * sw / width as u64).max(x0 + 1).min(sw);
+        let y0 = y as u64 * sh / height as u64;
+        let y1 = ((y as u64 + 1) * sh / height as u64).max(y0 + 1).min(sh);
+        let mut sum = [0u64; 3];
+        for sy in y0..y1 {
+            for sx in x0..x1 {
+                let p = img.get_pixel(sx as u32, sy as u32);
+                for c in 0..3 {
+                    sum[c] += p[c] as u64;
+                }
+            }
+        }
+        let n = ((x1 - x0) * (y1 - y0)).max(1);
+        Rgb([(sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8])
+    })
+}
+
+fn image_error(e: image::ImageError) -> ChronoError {
+    ChronoError::Io(std::io::Error::other(e))
+}
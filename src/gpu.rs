@@ -0,0 +1,393 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+use crate::simulation::DilationMode;
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BackendChoice {
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+}
+
+impl BackendChoice {
+    pub fn backends(self) -> wgpu::Backends {
+        match self {
+            BackendChoice::Vulkan => wgpu::Backends::VULKAN,
+            BackendChoice::Metal => wgpu::Backends::METAL,
+            BackendChoice::Dx12 => wgpu::Backends::DX12,
+            BackendChoice::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PowerChoice {
+    High,
+    Low,
+}
+
+pub fn backends(config: &SimConfig) -> wgpu::Backends {
+    config.backend.map_or(wgpu::Backends::all(), BackendChoice::backends)
+}
+
+pub fn create_instance(config: &SimConfig) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor { backends: backends(config), ..Default::default() })
+}
+
+// One instance per set of backends for the whole process, never dropped, for hosts that open
+// and close simulations as they go (Simulation::open). On GL, dropping an instance terminates
+// the EGL display that every other instance's devices share, and their next call panics.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn shared_instance(config: &SimConfig) -> Arc<wgpu::Instance> {
+    static INSTANCES: Mutex<Vec<(wgpu::Backends, Arc<wgpu::Instance>)>> = Mutex::new(Vec::new());
+    let mut instances = INSTANCES.lock().unwrap_or_else(PoisonError::into_inner);
+    let backends = backends(config);
+    if let Some((_, instance)) = instances.iter().find(|(b, _)| *b == backends) {
+        return instance.clone();
+    }
+    let instance = Arc::new(create_instance(config));
+    instances.push((backends, instance.clone()));
+    instance
+}
+
+// Honors --adapter/--power-preference and opens a device, enabling
+// TIMESTAMP_QUERY and PUSH_CONSTANTS when the adapter has them. With a surface (--view), only adapters
+// that can present to it qualify.
+pub async fn request_device(
+    instance: &wgpu::Instance,
+    config: &SimConfig,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), ChronoError> {
+    let adapter = match &config.adapter {
+        Some(wanted) => {
+            let needle = wanted.to_lowercase();
+            enumerate_adapters(instance, backends(config))
+                .into_iter()
+                .filter(|a| compatible_surface.is_none_or(|s| a.is_surface_supported(s)))
+                .find(|a| a.get_info().name.to_lowercase().contains(&needle))
+                .ok_or_else(|| ChronoError::AdapterNotMatched {
+                    wanted: wanted.clone(),
+                    available: describe_adapters(instance, backends(config)),
+                })?
+        }
+        None => {
+            let power_preference = match config.power_preference {
+                Some(PowerChoice::High) => wgpu::PowerPreference::HighPerformance,
+                Some(PowerChoice::Low) => wgpu::PowerPreference::LowPower,
+                None => wgpu::PowerPreference::default(),
+            };
+            instance
+                .request_adapter(&wgpu::RequestAdapterOptions { power_preference, compatible_surface, ..Default::default() })
+                .await
+                .ok_or(ChronoError::AdapterNotFound)?
+        }
+    };
+    open_device(adapter).await
+}
+
+// The adapter at `index` in --list-adapters order, for --adapters.
+pub async fn request_device_at(
+    instance: &wgpu::Instance,
+    config: &SimConfig,
+    index: usize,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), ChronoError> {
+    let adapter = enumerate_adapters(instance, backends(config))
+        .into_iter()
+        .nth(index)
+        .ok_or_else(|| ChronoError::AdapterIndex { index, available: describe_adapters(instance, backends(config)) })?;
+    open_device(adapter).await
+}
+
+async fn open_device(adapter: wgpu::Adapter) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), ChronoError> {
+    // Timestamps are optional: without them we only report wall-clock times. Without push
+    // constants GalaxyState goes through a uniform buffer (bindings::UniformPath).
+    let optional_features = adapter.features() & (wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::PUSH_CONSTANTS);
+    // The adapter's own limits rather than wgpu's defaults, so big runs get the largest buffers it has.
+    let required_limits = adapter.limits();
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor { required_features: optional_features, required_limits, ..Default::default() }, None)
+        .await?;
+    Ok((adapter, device, queue))
+}
+
+// "name | backend | device type" for every adapter wgpu can see.
+pub fn describe_adapters(instance: &wgpu::Instance, backends: wgpu::Backends) -> Vec<String> {
+    enumerate_adapters(instance, backends)
+        .iter()
+        .map(|a| {
+            let info = a.get_info();
+            format!("{} | {:?} | {:?}", info.name, info.backend, info.device_type)
+        })
+        .collect()
+}
+
+// Browsers hand out one adapter at a time through request_adapter, so on the web there are none to list.
+fn enumerate_adapters(instance: &wgpu::Instance, backends: wgpu::Backends) -> Vec<wgpu::Adapter> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return instance.enumerate_adapters(backends);
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = (instance, backends);
+        Vec::new()
+    }
+}
+
+// The first uncaptured error or loss of a device, kept instead of exiting (--adapters) so
+// the other device's run can finish; the run on this one stops at its next frame. A
+// Simulation given one reports it as its Stepper::fault.
+#[derive(Clone, Default)]
+pub struct DeviceErrors {
+    fault: Arc<Mutex<Option<DeviceFault>>>,
+    // Set by expect_drop: the device is going away on purpose, which wgpu reports as a loss too
+    dropping: Arc<AtomicBool>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceFault {
+    // The driver reset or dropped the device (a TDR, a hung dispatch)
+    Lost(String),
+    // An uncaptured validation or out-of-memory error
+    Error(String),
+}
+
+impl DeviceFault {
+    pub fn to_error(&self) -> ChronoError {
+        match self {
+            DeviceFault::Lost(message) => ChronoError::DeviceLost(message.clone()),
+            DeviceFault::Error(message) => ChronoError::Validation(message.clone()),
+        }
+    }
+}
+
+impl DeviceErrors {
+    pub fn watch(device: &wgpu::Device) -> Self {
+        let errors = Self::watch_loss(device);
+        let sink = errors.clone();
+        device.on_uncaptured_error(Box::new(move |error| sink.record(DeviceFault::Error(error.to_string()))));
+        errors
+    }
+
+    // Only the loss, for a device whose other errors keep their own handler.
+    pub fn watch_loss(device: &wgpu::Device) -> Self {
+        let errors = Self::default();
+        let sink = errors.clone();
+        // Dropping the device also reports it lost, with the same Unknown reason as a driver
+        // loss in wgpu 0.19; only a loss nobody expect_drop()ed is an error.
+        device.set_device_lost_callback(move |reason, message| {
+            if matches!(reason, wgpu::DeviceLostReason::Unknown) && !sink.dropping.load(Ordering::Acquire) {
+                tracing::error!("GPU device lost: {}", message);
+                sink.record(DeviceFault::Lost(message));
+            }
+        });
+        errors
+    }
+
+    // Called just before the device's owner drops it, so the loss that reports is ignored.
+    pub fn expect_drop(&self) {
+        self.dropping.store(true, Ordering::Release);
+    }
+
+    pub fn record(&self, fault: DeviceFault) {
+        self.fault.lock().unwrap_or_else(PoisonError::into_inner).get_or_insert(fault);
+    }
+
+    pub fn fault(&self) -> Option<DeviceFault> {
+        self.fault.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    pub fn lost(&self) -> bool {
+        matches!(self.fault(), Some(DeviceFault::Lost(_)))
+    }
+
+    pub fn first(&self) -> Option<String> {
+        self.fault().map(|fault| match fault {
+            DeviceFault::Lost(message) => format!("device lost: {}", message),
+            DeviceFault::Error(message) => message,
+        })
+    }
+}
+
+// After a readback misses --map-timeout-secs: this many more polls, each waiting this long.
+const MAP_RETRIES: usize = 3;
+const MAP_RETRY_WAIT: Duration = Duration::from_secs(1);
+
+pub type MapReceiver = Receiver<Result<(), wgpu::BufferAsyncError>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapOutcome {
+    Mapped,
+    // The callback came back with an error, which leaves the buffer unmapped
+    Failed(String),
+    // The callback was dropped unanswered, so the buffer's state is unknown
+    Dropped,
+    // Some drivers never answer at all
+    TimedOut,
+}
+
+impl MapOutcome {
+    // Whether the buffer still has to be unmapped before its next map: mapped ones, and
+    // pending ones, which unmapping cancels. Unmapping an unmapped buffer is a validation error.
+    pub fn needs_unmap(&self) -> bool {
+        matches!(self, MapOutcome::Mapped | MapOutcome::TimedOut)
+    }
+}
+
+// Waits up to `timeout` for a map_async callback, then polls `device` a few more times in
+// case the driver only needs a nudge.
+pub fn await_map(device: &wgpu::Device, rx: &MapReceiver, timeout: Duration) -> MapOutcome {
+    let outcome = |result: Result<(), wgpu::BufferAsyncError>| match result {
+        Ok(()) => MapOutcome::Mapped,
+        Err(e) => MapOutcome::Failed(e.to_string()),
+    };
+    match rx.recv_timeout(timeout) {
+        Ok(result) => return outcome(result),
+        Err(RecvTimeoutError::Disconnected) => return MapOutcome::Dropped,
+        Err(RecvTimeoutError::Timeout) => {}
+    }
+    tracing::warn!("A readback has not mapped after {:.1} s; polling the device again", timeout.as_secs_f32());
+    for _ in 0..MAP_RETRIES {
+        device.poll(wgpu::Maintain::Poll);
+        match rx.recv_timeout(MAP_RETRY_WAIT) {
+            Ok(result) => return outcome(result),
+            Err(RecvTimeoutError::Disconnected) => return MapOutcome::Dropped,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+    MapOutcome::TimedOut
+}
+
+// Starts reading `buffer` back. The receiver is gone if await_map gave up waiting.
+pub fn map_read(buffer: &wgpu::Buffer) -> MapReceiver {
+    let (tx, rx) = std::sync::mpsc::channel();
+    buffer.slice(..).map_async(wgpu::MapMode::Read, move |v| {
+        let _ = tx.send(v);
+    });
+    rx
+}
+
+// Settles every map of one frame's readback, `wait` being await_map on its device. A map the
+// driver failed is started again once with `remap(i)`, since the frame's copy is still in
+// buffer i; one that was dropped or timed out is not.
+pub fn settle_maps(
+    pending: Vec<MapReceiver>,
+    mut wait: impl FnMut(&MapReceiver) -> MapOutcome,
+    mut remap: impl FnMut(usize) -> MapReceiver,
+) -> Vec<MapOutcome> {
+    let mut outcomes: Vec<MapOutcome> = pending.iter().map(&mut wait).collect();
+    for (i, outcome) in outcomes.iter_mut().enumerate() {
+        if let MapOutcome::Failed(message) = outcome {
+            tracing::warn!("Readback buffer {} did not map ({}); mapping it again", i, message);
+            *outcome = wait(&remap(i));
+        }
+    }
+    outcomes
+}
+
+// Ok once every buffer mapped; otherwise the first failure, as the frame's fault.
+pub fn map_result(outcomes: &[MapOutcome], timeout_secs: f32) -> Result<(), ChronoError> {
+    match outcomes.iter().find(|outcome| **outcome != MapOutcome::Mapped) {
+        None => Ok(()),
+        Some(MapOutcome::Failed(message)) => Err(ChronoError::MapFailed(message.clone())),
+        Some(MapOutcome::Dropped) => Err(ChronoError::MapFailed("the map callback was dropped".into())),
+        Some(_) => Err(ChronoError::MapTimeout { secs: timeout_secs }),
+    }
+}
+
+// Maps all of `buffer` like a readback slot, waiting on `device`, and hands its bytes to
+// `read`. The buffer is left unmapped whether or not it came back.
+pub fn read_mapped<T>(
+    device: &wgpu::Device,
+    buffer: &wgpu::Buffer,
+    timeout_secs: f32,
+    read: impl FnOnce(&[u8]) -> T,
+) -> Result<T, ChronoError> {
+    let map = || {
+        let rx = map_read(buffer);
+        device.poll(wgpu::Maintain::Wait);
+        rx
+    };
+    let timeout = Duration::from_secs_f32(timeout_secs);
+    let outcomes = settle_maps(vec![map()], |rx| await_map(device, rx, timeout), |_| map());
+    let result = map_result(&outcomes, timeout_secs).map(|()| read(&buffer.slice(..).get_mapped_range()));
+    if outcomes[0].needs_unmap() {
+        buffer.unmap();
+    }
+    result
+}
+
+// `surface`, when given, restricts the choice to adapters that can present to it.
+// The errors catch the device's loss, for a frame run to recover from.
+pub async fn request_gpu(
+    instance: &wgpu::Instance,
+    config: &SimConfig,
+    surface: Option<&wgpu::Surface<'_>>,
+) -> Result<(wgpu::Adapter, Arc<wgpu::Device>, Arc<wgpu::Queue>, DeviceErrors), ChronoError> {
+    let (adapter, device, queue) = request_device(instance, config, surface).await?;
+    log_device("", &adapter, &device);
+
+    // Shader/pipeline validation errors carry the WGSL source location in their message.
+    // Once the device is lost every call fails, and the run deals with the loss instead.
+    let errors = DeviceErrors::watch_loss(&device);
+    let lost = errors.clone();
+    device.on_uncaptured_error(Box::new(move |error| {
+        if lost.lost() {
+            return;
+        }
+        tracing::error!("GPU validation error:\n{}", error);
+        std::process::exit(2);
+    }));
+    Ok((adapter, Arc::new(device), Arc::new(queue), errors))
+}
+
+// One mode's device under --adapters. Its errors are kept rather than exiting, so a failing
+// device stops only the run on it.
+pub async fn request_gpu_at(
+    instance: &wgpu::Instance,
+    config: &SimConfig,
+    index: usize,
+    mode: DilationMode,
+) -> Result<(ModeAdapter, Arc<wgpu::Device>, Arc<wgpu::Queue>), ChronoError> {
+    let (adapter, device, queue) = request_device_at(instance, config, index).await?;
+    log_device(&format!("[{}] [{}] ", mode.name(), index), &adapter, &device);
+    let errors = DeviceErrors::watch(&device);
+    let wgpu::AdapterInfo { name, backend, .. } = adapter.get_info();
+    Ok((ModeAdapter { mode, index, name, backend, errors }, Arc::new(device), Arc::new(queue)))
+}
+
+fn log_device(prefix: &str, adapter: &wgpu::Adapter, device: &wgpu::Device) {
+    let info = adapter.get_info();
+    let limits = device.limits();
+    tracing::info!("{}Adapter: {} | Backend: {:?} | Type: {:?}", prefix, info.name, info.backend, info.device_type);
+    tracing::info!(
+        "{}Limits: max_buffer_size {} | max_storage_binding {} | max_workgroup_size {} | max_workgroups/dim {}",
+        prefix,
+        limits.max_buffer_size,
+        limits.max_storage_buffer_binding_size,
+        limits.max_compute_workgroup_size_x,
+        limits.max_compute_workgroups_per_dimension
+    );
+    if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+        tracing::warn!("{}adapter does not support TIMESTAMP_QUERY; GPU compute time will not be reported.", prefix);
+    }
+}
+
+// The adapter one mode runs on with --adapters, for the metrics column, the summary and its errors.
+pub struct ModeAdapter {
+    pub mode: DilationMode,
+    pub index: usize,
+    pub name: String,
+    pub backend: wgpu::Backend,
+    pub errors: DeviceErrors,
+}
+
+impl ModeAdapter {
+    pub fn label(&self) -> String {
+        format!("adapter [{}] {}", self.index, self.name)
+    }
+}
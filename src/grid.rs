@@ -0,0 +1,234 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use crate::galaxy::{bounding_cube, Star};
+use crate::precision::Precision;
+
+// Must match `WORKGROUP_SIZE` in grid.wgsl
+const GRID_WORKGROUP_SIZE: u32 = 256;
+// Matches `Cell` in grid.wgsl and shader.wgsl
+const CELL_BYTES: u64 = 48;
+// Past this, the far-field loop over every cell outweighs what finer cells save nearby.
+pub const MAX_SIDE: u32 = 32;
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Accel {
+    // The original kernel: SAMPLES random partners per star and step
+    Sampled,
+    // Every other star, exactly, at O(N^2)
+    Bruteforce,
+    // Neighboring cells of a uniform grid exactly, farther cells as point masses
+    Grid,
+}
+
+impl Accel {
+    pub fn name(self) -> &'static str {
+        match self {
+            Accel::Sampled => "sampled",
+            Accel::Bruteforce => "bruteforce",
+            Accel::Grid => "grid",
+        }
+    }
+
+    // The shader.wgsl kernel that steps the stars
+    pub fn entry_point(self) -> &'static str {
+        match self {
+            Accel::Sampled => "main",
+            Accel::Bruteforce => "main_bruteforce",
+            Accel::Grid => "main_grid",
+        }
+    }
+}
+
+// Matches `Grid` in grid.wgsl and shader.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridParams {
+    cube: [f32; 4],
+    side: u32,
+    cells: u32,
+    total: u32,
+    stride: u32,
+}
+
+// Cells per axis for `stars`. A star visits its 27 neighboring cells' stars and every
+// cell once, about 27 N / side^3 + side^3 reads, which is least at side^3 = sqrt(27 N).
+pub fn cells_per_side(stars: u32) -> u32 {
+    ((27.0 * stars as f64).powf(1.0 / 6.0).round() as u32).clamp(2, MAX_SIDE)
+}
+
+// The grid covers the galaxy's bounding box with a quarter of margin; stars that leave
+// it are binned into the faces, which keeps the forces right but the edge cells crowded.
+pub fn grid_cube(stars: &[Star]) -> [f32; 4] {
+    bounding_cube(stars, 1.25)
+}
+
+// The cells and bins buffers Grid::new allocates for `stars`.
+pub fn cell_bytes(stars: u32) -> u64 {
+    cells_per_side(stars).pow(3) as u64 * CELL_BYTES
+}
+
+pub fn bin_bytes(stars: u32) -> u64 {
+    2 * stars.max(1) as u64 * std::mem::size_of::<u32>() as u64
+}
+
+// Compiled once and shared by forked and resized simulations.
+pub struct GridPipelines {
+    clear: wgpu::ComputePipeline,
+    count: wgpu::ComputePipeline,
+    scan: wgpu::ComputePipeline,
+    scatter: wgpu::ComputePipeline,
+    centroids: wgpu::ComputePipeline,
+    precision: Precision,
+}
+
+impl GridPipelines {
+    pub fn new(device: &wgpu::Device, precision: Precision) -> Self {
+        let source = precision.star_struct(include_str!("grid.wgsl")).unwrap_or_else(|e| panic!("grid.wgsl: {}", e));
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("grid.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: None,
+                module: &shader,
+                entry_point,
+            })
+        };
+        Self {
+            clear: pipeline("clear"),
+            count: pipeline("count"),
+            scan: pipeline("scan"),
+            scatter: pipeline("scatter"),
+            centroids: pipeline("centroids"),
+            precision,
+        }
+    }
+}
+
+// A bind group and the workgroup grid it is dispatched with.
+type Pass = (wgpu::BindGroup, [u32; 2]);
+
+// Bins one simulation's stars into a uniform grid before each step (--accel grid): counts
+// per cell, a prefix sum into cell offsets, a scatter of star indices into cell order,
+// and a centroid per cell and star type. shader.wgsl's main_grid reads the result.
+pub struct Grid {
+    pipelines: Arc<GridPipelines>,
+    params: wgpu::Buffer,
+    cells: wgpu::Buffer,
+    bins: wgpu::Buffer,
+    side: u32,
+    // clear, count, scan, scatter and centroids, in dispatch order
+    passes: [Pass; 5],
+}
+
+impl Grid {
+    // `chunks` are the star buffers with their global index ranges, every chunk but the
+    // last holding `stride` stars.
+    pub fn new(
+        device: &wgpu::Device,
+        pipelines: Arc<GridPipelines>,
+        chunks: &[(&wgpu::Buffer, Range<usize>)],
+        stride: u32,
+        cube: [f32; 4],
+    ) -> Self {
+        let total = chunks.last().map_or(0, |(_, range)| range.end as u32);
+        let side = cells_per_side(total);
+        let max_groups = device.limits().max_compute_workgroups_per_dimension;
+        let grid = |threads: u32| {
+            let groups = threads.div_ceil(GRID_WORKGROUP_SIZE);
+            let x = groups.clamp(1, max_groups);
+            [x, groups.div_ceil(x).max(1)]
+        };
+
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Params"),
+            contents: bytemuck::bytes_of(&GridParams { cube, side, cells: side.pow(3), total, stride }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let cells = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Grid Cells"),
+            size: cell_bytes(total),
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let bins = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Grid Bins"),
+            size: bin_bytes(total),
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let placeholder = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Grid Placeholder"),
+            size: pipelines.precision.star_bytes(),
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        // Each pass gets exactly the bindings it uses: stars, bins or neither.
+        let bind_group = |pipeline: &wgpu::ComputePipeline, stars: bool, with_bins: bool| {
+            let mut entries = vec![
+                wgpu::BindGroupEntry { binding: 1, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: cells.as_entire_binding() },
+            ];
+            if stars {
+                entries.extend([0, 3, 4, 5].into_iter().enumerate().map(|(k, binding)| wgpu::BindGroupEntry {
+                    binding,
+                    resource: chunks.get(k).map_or(&placeholder, |(buffer, _)| *buffer).as_entire_binding(),
+                }));
+            }
+            if with_bins {
+                entries.push(wgpu::BindGroupEntry { binding: 6, resource: bins.as_entire_binding() });
+            }
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &entries,
+            })
+        };
+        let passes = [
+            (bind_group(&pipelines.clear, false, false), grid(side.pow(3))),
+            (bind_group(&pipelines.count, true, true), grid(total)),
+            (bind_group(&pipelines.scan, false, false), [1, 1]),
+            (bind_group(&pipelines.scatter, false, true), grid(total)),
+            (bind_group(&pipelines.centroids, true, true), grid(side.pow(3))),
+        ];
+        Self { pipelines, params, cells, bins, side, passes }
+    }
+
+    pub fn pipelines(&self) -> &Arc<GridPipelines> {
+        &self.pipelines
+    }
+
+    pub fn side(&self) -> u32 {
+        self.side
+    }
+
+    // shader.wgsl's bindings 7, 8 and 9 for main_grid.
+    pub fn entries(&self) -> [wgpu::BindGroupEntry<'_>; 3] {
+        [
+            wgpu::BindGroupEntry { binding: 7, resource: self.params.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 8, resource: self.cells.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 9, resource: self.bins.as_entire_binding() },
+        ]
+    }
+
+    // For stars uploaded anew, so the grid covers them again.
+    pub fn set_cube(&self, queue: &wgpu::Queue, cube: [f32; 4]) {
+        queue.write_buffer(&self.params, 0, bytemuck::cast_slice(&cube));
+    }
+
+    // Records the binning into `cpass`, ahead of a step's main_grid dispatches.
+    pub fn encode<'a>(&'a self, cpass: &mut wgpu::ComputePass<'a>) {
+        let p = &self.pipelines;
+        for (pipeline, (bind_group, [x, y])) in [&p.clear, &p.count, &p.scan, &p.scatter, &p.centroids].into_iter().zip(&self.passes) {
+            cpass.set_pipeline(pipeline);
+            cpass.set_bind_group(0, bind_group, &[]);
+            cpass.dispatch_workgroups(*x, *y, 1);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+struct Star {
+    x: f32, y: f32, z: f32,
+    vx: f32, vy: f32, vz: f32,
+    mass: f32,
+    data_type: f32,
+    time_debt: f32,
+    active_flag: f32,
+};
+
+// Matches `Grid` in shader.wgsl.
+struct Grid {
+    cube: vec4<f32>, // xyz: lowest corner of the grid, w: its side
+    side: u32, // cells per axis
+    cells: u32, // side^3
+    total: u32, // stars across all chunks
+    stride: u32, // stars per chunk, as in shader.wgsl
+};
+
+// Matches `Cell` in shader.wgsl, which only reads it.
+struct Cell {
+    a: vec4<f32>, // centroid and count of the cell's TYPE_A stars
+    b: vec4<f32>, // the same for TYPE_B
+    start: u32, // first entry of the cell in bins' second half
+    count: atomic<u32>,
+    fill: atomic<u32>, // entries scattered so far
+    pad0: u32,
+};
+
+@group(0) @binding(0) var<storage, read> stars0: array<Star>;
+@group(0) @binding(1) var<uniform> grid: Grid;
+@group(0) @binding(2) var<storage, read_write> cells: array<Cell>;
+@group(0) @binding(3) var<storage, read> stars1: array<Star>;
+@group(0) @binding(4) var<storage, read> stars2: array<Star>;
+@group(0) @binding(5) var<storage, read> stars3: array<Star>;
+// bins[i] is star i's cell (NO_CELL for a central mass or a culled star); bins[total + k]
+// is the k-th binned star, grouped by cell
+@group(0) @binding(6) var<storage, read_write> bins: array<u32>;
+
+const WORKGROUP_SIZE: u32 = 256u;
+const NO_CELL: u32 = 0xffffffffu;
+
+var<workgroup> partial: array<u32, WORKGROUP_SIZE>;
+
+fn load_star(index: u32) -> Star {
+    let c = index / grid.stride;
+    let i = index - c * grid.stride;
+    switch c {
+        case 0u: { return stars0[i]; }
+        case 1u: { return stars1[i]; }
+        case 2u: { return stars2[i]; }
+        default: { return stars3[i]; }
+    }
+}
+
+// Dispatches are folded into a second dimension past max_compute_workgroups_per_dimension.
+fn flat_index(id: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return id.x + id.y * groups.x * WORKGROUP_SIZE;
+}
+
+// Cell of one coordinate. Stars that left the cube are clamped to its faces; NaN fails
+// both comparisons and lands in the last cell.
+fn axis(p: f32, lo: f32) -> u32 {
+    let t = (p - lo) / grid.cube.w * f32(grid.side);
+    if (t < 0.0) {
+        return 0u;
+    }
+    if (t < f32(grid.side) - 1.0) {
+        return u32(t);
+    }
+    return grid.side - 1u;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn clear(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let c = flat_index(global_id, groups);
+    if (c >= grid.cells) {
+        return;
+    }
+    cells[c].a = vec4<f32>(0.0);
+    cells[c].b = vec4<f32>(0.0);
+    cells[c].start = 0u;
+    atomicStore(&cells[c].count, 0u);
+    atomicStore(&cells[c].fill, 0u);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn count(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let i = flat_index(global_id, groups);
+    if (i >= grid.total) {
+        return;
+    }
+    let s = load_star(i);
+    // shader.wgsl pulls a central mass on its own, and nothing pulls a culled star.
+    if (s.data_type < -0.5 || s.active_flag < -0.5) {
+        bins[i] = NO_CELL;
+        return;
+    }
+    let c = axis(s.x, grid.cube.x) + grid.side * (axis(s.y, grid.cube.y) + grid.side * axis(s.z, grid.cube.z));
+    bins[i] = c;
+    atomicAdd(&cells[c].count, 1u);
+}
+
+// Exclusive prefix sum of the counts, in one workgroup: each invocation sums a run of
+// cells, the run totals are scanned in workgroup memory, and each run is then filled in.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn scan(@builtin(local_invocation_index) lid: u32) {
+    let per = (grid.cells + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE;
+    let begin = min(lid * per, grid.cells);
+    let end = min(begin + per, grid.cells);
+    var run = 0u;
+    for (var c = begin; c < end; c++) {
+        run += atomicLoad(&cells[c].count);
+    }
+    partial[lid] = run;
+    workgroupBarrier();
+    for (var offset = 1u; offset < WORKGROUP_SIZE; offset *= 2u) {
+        var before = 0u;
+        if (lid >= offset) {
+            before = partial[lid - offset];
+        }
+        workgroupBarrier();
+        partial[lid] += before;
+        workgroupBarrier();
+    }
+    var start = partial[lid] - run;
+    for (var c = begin; c < end; c++) {
+        cells[c].start = start;
+        start += atomicLoad(&cells[c].count);
+    }
+}
+
+// Within a cell the order is whichever invocation got there first.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn scatter(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let i = flat_index(global_id, groups);
+    if (i >= grid.total || bins[i] == NO_CELL) {
+        return;
+    }
+    let c = bins[i];
+    bins[grid.total + cells[c].start + atomicAdd(&cells[c].fill, 1u)] = i;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn centroids(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let c = flat_index(global_id, groups);
+    if (c >= grid.cells) {
+        return;
+    }
+    var a = vec4<f32>(0.0);
+    var b = vec4<f32>(0.0);
+    let start = cells[c].start;
+    let end = start + atomicLoad(&cells[c].count);
+    for (var k = start; k < end; k++) {
+        let s = load_star(bins[grid.total + k]);
+        if (s.data_type < 0.5) {
+            a += vec4<f32>(s.x, s.y, s.z, 1.0);
+        } else {
+            b += vec4<f32>(s.x, s.y, s.z, 1.0);
+        }
+    }
+    if (a.w > 0.0) {
+        cells[c].a = vec4<f32>(a.xyz / a.w, a.w);
+    }
+    if (b.w > 0.0) {
+        cells[c].b = vec4<f32>(b.xyz / b.w, b.w);
+    }
+}
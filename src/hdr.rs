@@ -0,0 +1,123 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use image::{ImageBuffer, Rgb};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::ChronoError;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::manifest::RunManifest;
+use crate::render::{for_central_pixels, Tonemap, CENTRAL_COLOR};
+
+// What frame images are written as (--image-format).
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    // 8-bit RGB PNGs, overlays and all
+    Png8,
+    // 16-bit RGB PNGs of the tonemapped frame, without overlays
+    Png16,
+    // OpenEXR: the linear float channels before tonemapping (the `exr` feature)
+    Exr,
+}
+
+impl ImageFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageFormat::Png8 => "png8",
+            ImageFormat::Png16 => "png16",
+            ImageFormat::Exr => "exr",
+        }
+    }
+
+    // Drawn from an HdrImage on the CPU rather than the 8-bit frame.
+    pub fn is_hdr(self) -> bool {
+        self != ImageFormat::Png8
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Exr => "exr",
+            ImageFormat::Png8 | ImageFormat::Png16 => "png",
+        }
+    }
+}
+
+// The channels of an --image-format exr frame, by name. Kept as they are so EXRs from
+// different runs and versions diff channel by channel.
+pub const EXR_CHANNELS: [&str; 4] = ["R", "G", "B", "glow.Y"];
+
+pub type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+
+// One frame's channels as Framebuffer::hdr leaves them, before they are quantized.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    // Palette-weighted star brightness per pixel, without the glow; 1.0 is what a linear
+    // 8-bit frame draws as full white at --exposure 1
+    pub rgb: Vec<[f32; 3]>,
+    // Summed weight of the active stars per pixel
+    pub glow: Vec<f32>,
+    // What one unit of glow adds to `rgb` when the frame is drawn (render::glow_tint)
+    pub glow_tint: [f32; 3],
+    pub exposure: f32,
+    pub tonemap: Tonemap,
+    // Central masses as (x, y, radius) blobs; drawn into 16-bit frames, left out of EXRs
+    pub central: Vec<(f32, f32, f32)>,
+}
+
+impl HdrImage {
+    // The frame as Framebuffer::tonemap draws it, at 16 bits per channel.
+    pub fn to_png16(&self) -> Rgb16Image {
+        let mut display: Vec<[f32; 3]> = self
+            .rgb
+            .iter()
+            .zip(&self.glow)
+            .map(|(rgb, glow)| std::array::from_fn(|c| self.tonemap.apply((rgb[c] + glow * self.glow_tint[c]) * self.exposure)))
+            .collect();
+        for &(cx, cy, radius) in &self.central {
+            for_central_pixels(cx, cy, radius, self.width, self.height, |x, y, weight| {
+                let p = &mut display[y as usize * self.width as usize + x as usize];
+                for c in 0..3 {
+                    p[c] += (CENTRAL_COLOR[c] as f32 / 255.0 - p[c]) * weight;
+                }
+            });
+        }
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let p = display[y as usize * self.width as usize + x as usize];
+            Rgb(p.map(|v| (v * u16::MAX as f32).round() as u16))
+        })
+    }
+}
+
+// Writes `img` as a single-part EXR with 32-bit float channels named as in EXR_CHANNELS,
+// carrying `manifest` as the layer's comments.
+#[cfg(all(feature = "exr", not(target_arch = "wasm32")))]
+pub fn save_exr(path: &Path, img: &HdrImage, manifest: Option<&RunManifest>) -> Result<(), ChronoError> {
+    use exr::prelude::*;
+    let channel = |name: &str, values: Vec<f32>| AnyChannel::new(name, FlatSamples::F32(values));
+    let [r, g, b, glow] = EXR_CHANNELS;
+    let channels = vec![
+        channel(r, img.rgb.iter().map(|p| p[0]).collect()),
+        channel(g, img.rgb.iter().map(|p| p[1]).collect()),
+        channel(b, img.rgb.iter().map(|p| p[2]).collect()),
+        channel(glow, img.glow.clone()),
+    ];
+    let attributes = LayerAttributes {
+        software_name: Some(Text::from("chronoturin")),
+        comments: manifest.and_then(|m| Text::new_or_none(m.to_json())),
+        ..LayerAttributes::default()
+    };
+    let size = (img.width as usize, img.height as usize);
+    let layer = Layer::new(size, attributes, Encoding::FAST_LOSSLESS, AnyChannels::sort(channels.into()));
+    Image::from_layer(layer).write().to_file(path).map_err(|e| ChronoError::Parse {
+        path: path.to_path_buf(),
+        line: 0,
+        message: e.to_string(),
+    })
+}
+
+#[cfg(all(not(feature = "exr"), not(target_arch = "wasm32")))]
+pub fn save_exr(path: &Path, _: &HdrImage, _: Option<&RunManifest>) -> Result<(), ChronoError> {
+    Err(ChronoError::Parse { path: path.to_path_buf(), line: 0, message: "this build has no `exr` feature".into() })
+}
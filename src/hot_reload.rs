@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::error::ChronoError;
+use crate::simulation::Stepper;
+
+// The --shader file the force kernels are compiled from. Between frames its modification
+// time and size are compared with the last look, which is cheap enough to do every frame.
+pub struct ShaderWatcher {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    source: String,
+    // Counts changes, so steppers polled from separate passes each catch up once
+    generation: usize,
+    // The generation that last failed to compile, so it is reported once, not every frame
+    failed: Option<usize>,
+}
+
+impl ShaderWatcher {
+    // Compiles `path` into every stepper in `sims` and starts watching it. A file that does
+    // not compile is an error here, since there are no kernels of its own to fall back to.
+    pub fn start<S: Stepper>(path: &Path, sims: &mut [&mut S]) -> Result<Self, ChronoError> {
+        let stamp = stamp(path);
+        let source = std::fs::read_to_string(path)?;
+        install(&source, sims).map_err(|e| ChronoError::InvalidConfig(format!("--shader {} does not compile:\n{}", path.display(), e)))?;
+        tracing::info!("Force kernels compiled from {}; edits are picked up between frames", path.display());
+        Ok(Self { path: path.to_path_buf(), stamp, source, generation: 0, failed: None })
+    }
+
+    // Recompiles `sims`, last brought up to generation `*loaded`, if the file has changed.
+    // Either every stepper switches or none does, so lockstep modes never run different
+    // kernels. A file that fails to read or compile is reported and the previous kernels keep
+    // running until the next change.
+    pub fn poll<S: Stepper>(&mut self, loaded: &mut usize, sims: &mut [&mut S]) {
+        let stamp = stamp(&self.path);
+        if stamp != self.stamp {
+            self.stamp = stamp;
+            match std::fs::read_to_string(&self.path) {
+                Ok(source) if source != self.source => {
+                    self.source = source;
+                    self.generation += 1;
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("--shader {}: {}; keeping the current kernels", self.path.display(), e),
+            }
+        }
+        if *loaded == self.generation || self.failed == Some(self.generation) {
+            return;
+        }
+        match install(&self.source, sims) {
+            Ok(()) => {
+                *loaded = self.generation;
+                tracing::info!("Reloaded the force kernels from {}", self.path.display());
+            }
+            Err(e) => {
+                self.failed = Some(self.generation);
+                tracing::error!("--shader {} does not compile; keeping the previous kernels:\n{}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+// Compiles `source` for every stepper first and switches them over only once all have.
+fn install<S: Stepper>(source: &str, sims: &mut [&mut S]) -> Result<(), String> {
+    let compiled = sims.iter().map(|sim| sim.compile_kernels(source)).collect::<Result<Vec<_>, _>>()?;
+    for (sim, kernels) in sims.iter_mut().zip(compiled) {
+        if let Some(kernels) = kernels {
+            sim.install_kernels(kernels);
+        }
+    }
+    Ok(())
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
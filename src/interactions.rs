@@ -0,0 +1,174 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::galaxy::MAX_TYPES;
+
+// How strongly each data type pulls on every other: positive attracts, negative repels and
+// 0 ignores. Row `on`, column `by` scales the force a type `on` star feels from a type `by`
+// one. Without --interactions a type attracts its own kind with 1 and repels the rest with
+// -1, the rule the kernels always had.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interactions {
+    coefficients: [[f32; MAX_TYPES]; MAX_TYPES],
+    types: usize,
+}
+
+// One `a_b = value` entry of --interactions, before it is checked against --types.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InteractionEntry {
+    pub on: usize,
+    pub by: usize,
+    pub value: f32,
+}
+
+// --interactions as given: `a_b=-0.5,a_a=2` inline, or a TOML file with an [interactions]
+// table of the same keys. A type is its index or a letter, a for 0 up to p for 15.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InteractionTable(pub Vec<InteractionEntry>);
+
+// The inline form, whether the table came inline or from a file.
+impl fmt::Display for InteractionTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<String> = self.0.iter().map(|e| format!("{}={}", key(e.on, e.by), e.value)).collect();
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+pub fn parse_interactions(arg: &str) -> Result<InteractionTable, String> {
+    if arg.contains('=') {
+        return arg
+            .split(',')
+            .map(|pair| parse_entry(pair).map_err(|e| format!("`{}`: {}", pair.trim(), e)))
+            .collect::<Result<_, _>>()
+            .map(InteractionTable);
+    }
+    let path = Path::new(arg);
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_toml(&text).map_err(|e| format!("{}{}", path.display(), e))
+}
+
+// The subset of TOML a flat table of numbers needs: `[table]` headers, `key = value` lines
+// and `#` comments. Tables other than [interactions] are skipped.
+fn parse_toml(text: &str) -> Result<InteractionTable, String> {
+    let mut table = None;
+    let mut entries = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let at = |what: String| format!(" line {}: {}", line_no + 1, what);
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            table = Some(name.trim().to_string());
+            continue;
+        }
+        match table.as_deref() {
+            Some("interactions") => entries.push(parse_entry(line).map_err(at)?),
+            Some(_) => {}
+            None => return Err(at("keys go in the [interactions] table".into())),
+        }
+    }
+    if entries.is_empty() {
+        return Err(": no [interactions] entries".into());
+    }
+    Ok(InteractionTable(entries))
+}
+
+fn parse_entry(pair: &str) -> Result<InteractionEntry, String> {
+    let Some((key, value)) = pair.split_once('=') else { return Err("expected a_b = value".into()) };
+    let key = key.trim().trim_matches('"');
+    let Some((on, by)) = key.split_once('_') else { return Err(format!("key `{}` is not a_b", key)) };
+    let value = value.trim();
+    let value: f32 = value.parse().map_err(|_| format!("`{}` is not a number", value))?;
+    if !value.is_finite() {
+        return Err("coefficients must be finite".into());
+    }
+    Ok(InteractionEntry { on: parse_type(on)?, by: parse_type(by)?, value })
+}
+
+fn parse_type(name: &str) -> Result<usize, String> {
+    let index = match name.as_bytes() {
+        [letter @ b'a'..=b'z'] => (letter - b'a') as usize,
+        _ => name.parse().map_err(|_| format!("type `{}` is neither an index nor a letter", name))?,
+    };
+    if index >= MAX_TYPES {
+        return Err(format!("type `{}` is past the maximum of {}", name, MAX_TYPES - 1));
+    }
+    Ok(index)
+}
+
+// The key an entry was written with, letters for the types.
+fn key(on: usize, by: usize) -> String {
+    let letter = |t: usize| (b'a' + t as u8) as char;
+    format!("{}_{}", letter(on), letter(by))
+}
+
+impl Interactions {
+    pub fn default_for(types: usize) -> Self {
+        let mut coefficients = [[0.0; MAX_TYPES]; MAX_TYPES];
+        for (on, row) in coefficients.iter_mut().enumerate() {
+            for (by, c) in row.iter_mut().enumerate() {
+                *c = if on == by { 1.0 } else { -1.0 };
+            }
+        }
+        Self { coefficients, types }
+    }
+
+    // Applies `entries` over the default. Unless `asymmetric`, `a_b` sets `b_a` as well,
+    // and giving both with different values is an error.
+    pub fn resolve(types: usize, entries: &[InteractionEntry], asymmetric: bool) -> Result<Self, String> {
+        let mut matrix = Self::default_for(types);
+        let mut given: Vec<(usize, usize, f32)> = Vec::new();
+        for &InteractionEntry { on, by, value } in entries {
+            if on >= types || by >= types {
+                return Err(format!("--interactions sets {} but --types {} ends at {}", key(on, by), types, key(types - 1, types - 1)));
+            }
+            if given.iter().any(|&(a, b, _)| (a, b) == (on, by)) {
+                return Err(format!("--interactions sets {} twice", key(on, by)));
+            }
+            if !asymmetric {
+                if let Some(&(_, _, mirror)) = given.iter().find(|&&(a, b, _)| (a, b) == (by, on)).filter(|g| g.2 != value) {
+                    return Err(format!(
+                        "--interactions sets {} = {} but {} = {}; the matrix must be symmetric unless --asymmetric",
+                        key(by, on),
+                        mirror,
+                        key(on, by),
+                        value
+                    ));
+                }
+                matrix.coefficients[by][on] = value;
+            }
+            matrix.coefficients[on][by] = value;
+            given.push((on, by, value));
+        }
+        Ok(matrix)
+    }
+
+    pub fn types(&self) -> usize {
+        self.types
+    }
+
+    // Types past --types clamp to the last one, like the palette.
+    pub fn coefficient(&self, on: usize, by: usize) -> f32 {
+        self.coefficients[on.min(self.types - 1)][by.min(self.types - 1)]
+    }
+
+    // The --types by --types matrix, row-major, as the metrics log records it.
+    pub fn values(&self) -> Vec<f32> {
+        self.coefficients[..self.types].iter().flat_map(|row| row[..self.types].iter().copied()).collect()
+    }
+
+    pub fn uniform(&self) -> InteractionsUniform {
+        let mut coefficients = [[0.0; 4]; MAX_TYPES * MAX_TYPES / 4];
+        bytemuck::cast_slice_mut::<_, f32>(&mut coefficients).copy_from_slice(bytemuck::cast_slice(&self.coefficients));
+        InteractionsUniform { coefficients }
+    }
+}
+
+// Matches `Interactions` in shader.wgsl: the full MAX_TYPES square, row-major, four to a
+// vec4 since uniform arrays have a 16-byte stride.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InteractionsUniform {
+    pub coefficients: [[f32; 4]; MAX_TYPES * MAX_TYPES / 4],
+}
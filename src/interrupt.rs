@@ -0,0 +1,25 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Exit status of a run stopped by Ctrl-C (128 + SIGINT, as a shell reports it).
+pub const EXIT_CODE: i32 = 130;
+
+// Set by the first Ctrl-C; frame loops stop after the current frame and clean up as usual.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// A second Ctrl-C falls through to the default handler and kills the process.
+#[cfg(unix)]
+pub fn install_handler() {
+    extern "C" fn on_sigint(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+    }
+    unsafe { libc::signal(libc::SIGINT, on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t) };
+}
+
+// Elsewhere Ctrl-C still ends the process immediately.
+#[cfg(not(unix))]
+pub fn install_handler() {}
+
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,169 @@
+// wgpu handles are not Send on the web, where everything runs on the page's one thread anyway.
+#![cfg_attr(target_arch = "wasm32", allow(clippy::arc_with_non_send_sync))]
+
+pub mod bench;
+pub mod bindings;
+pub mod boundary;
+#[cfg(all(feature = "capi", not(target_arch = "wasm32")))]
+pub mod capi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod checkpoint;
+pub mod chunks;
+pub mod clumps;
+pub mod compaction;
+pub mod config;
+pub mod config_file;
+pub mod conservation;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod control;
+pub mod cpu_reference;
+pub mod cull;
+pub mod determinism;
+pub mod diff;
+pub mod dump;
+pub mod error;
+pub mod external;
+pub mod font;
+pub mod galaxy;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gif;
+pub mod gpu;
+pub mod grid;
+pub mod hdr;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hot_reload;
+pub mod interactions;
+pub mod interrupt;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod logging;
+pub mod manifest;
+pub mod memory;
+pub mod merge;
+pub mod metrics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod output;
+pub mod overlay;
+pub mod packing;
+pub mod palette;
+pub mod pipe;
+#[cfg(all(feature = "plots", not(target_arch = "wasm32")))]
+pub mod plots;
+pub mod points;
+pub mod precision;
+pub mod presets;
+pub mod profiles;
+#[cfg(all(feature = "python", not(target_arch = "wasm32")))]
+pub mod python;
+pub mod raster;
+pub mod recovery;
+pub mod reduction;
+pub mod render;
+pub mod report;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod runner;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod saver;
+pub mod schedule;
+#[cfg(all(feature = "serve", not(target_arch = "wasm32")))]
+pub mod serve;
+pub mod shader_layout;
+pub mod simulation;
+pub mod snapshot;
+pub mod sort;
+pub mod sweep;
+pub mod telemetry;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod trajectory;
+pub mod tune;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod video;
+#[cfg(all(feature = "viewer", not(target_arch = "wasm32")))]
+pub mod viewer;
+pub mod watchdog;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+
+pub use bench::{BenchArgs, BenchReport, SortedBench, StepStats, UnifiedBench};
+pub use bindings::UniformPath;
+pub use boundary::Boundary;
+#[cfg(not(target_arch = "wasm32"))]
+pub use checkpoint::{Checkpoint, CheckpointRun};
+pub use chunks::ChunkLayout;
+#[cfg(not(target_arch = "wasm32"))]
+pub use clumps::write_catalog;
+pub use clumps::{Clump, Clumps, FofSettings};
+pub use compaction::Compaction;
+pub use config::SimConfig;
+pub use conservation::{Conservation, EnergyCheck};
+#[cfg(not(target_arch = "wasm32"))]
+pub use control::{Control, ControlCommand, ControlStatus};
+pub use cpu_reference::CpuSimulation;
+pub use cull::Culling;
+pub use determinism::{state_hash, DeterminismReport, FrameMismatch, ModeDeterminism};
+pub use diff::{displacement_stats, render_diff, DisplacementStats};
+#[cfg(not(target_arch = "wasm32"))]
+pub use dump::write_dump;
+pub use dump::{read_bin_dump, read_csv_dump, read_dump_manifest, read_initial_conditions, read_sidecar, DumpFormat, DumpInfo};
+pub use error::ChronoError;
+pub use external::ExternalPotential;
+pub use galaxy::{active_by_type, generate_galaxy, is_dead, GalaxyState, Star, DEAD_FLAG, MAX_TYPES, TYPE_A, TYPE_B, TYPE_CENTRAL};
+#[cfg(not(target_arch = "wasm32"))]
+pub use gif::GifWriter;
+pub use gpu::ModeAdapter;
+pub use grid::{cells_per_side, Accel, Grid};
+#[cfg(not(target_arch = "wasm32"))]
+pub use hdr::save_exr;
+pub use hdr::{HdrImage, ImageFormat, Rgb16Image, EXR_CHANNELS};
+#[cfg(not(target_arch = "wasm32"))]
+pub use hot_reload::ShaderWatcher;
+pub use interactions::{InteractionTable, Interactions, InteractionsUniform};
+pub use manifest::{read_png_text, save_png, save_png16, RunManifest};
+pub use memory::{Allocation, MemoryPlan};
+pub use merge::Merging;
+#[cfg(not(target_arch = "wasm32"))]
+pub use metrics::MetricsWriter;
+pub use metrics::{MetricsFormat, MetricsRecord};
+#[cfg(not(target_arch = "wasm32"))]
+pub use output::OutputLayout;
+pub use overlay::{draw_legend, draw_overlay, draw_tracked, OverlayInfo};
+pub use packing::{Packer, ReadbackChoice, RenderRecord};
+pub use palette::{Palette, PaletteChoice};
+pub use pipe::{OnPipeClose, RawFrameWriter};
+pub use points::{PointRenderer, ViewUniform};
+pub use precision::{DfStar, Precision};
+pub use presets::{Preset, PresetParams, TypeMix};
+#[cfg(not(target_arch = "wasm32"))]
+pub use profiles::{write_profiles, write_rotation_curve};
+pub use profiles::{Annulus, Histogram, LagrangianRadii, ProfileSettings, Profiles, RotationCurve, LAGRANGIAN_FRACTIONS};
+pub use raster::{GpuRaster, RasterChoice};
+pub use recovery::{Recovering, Reopen};
+pub use reduction::{Moments, Quantity, Reduction};
+pub use render::{
+    anaglyph, project, render_frame, render_trails, render_with, side_by_side, Camera, ColorBy, Framebuffer, Projection, RenderSettings,
+    SplatKernels, Stereo, Tonemap,
+};
+pub use report::{determinism_report_json, tune_report_json, FrameSample, ModeReport, ReportConfig, RunReport};
+#[cfg(not(target_arch = "wasm32"))]
+pub use saver::{FrameSaver, SaveStats};
+pub use schedule::{parse_mode_schedule, ChainOrder, ModeSchedule};
+#[cfg(all(feature = "serve", not(target_arch = "wasm32")))]
+pub use serve::{FrameServer, FrameSink};
+pub use simulation::{
+    CompiledKernels, DilationMode, FrameData, FrameTicket, Integrator, OnFrameError, Simulation, Stepper, TimingSource, DT,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use snapshot::{check_star_count, inspect, replay_plan};
+pub use snapshot::{
+    draw_run, draw_run_hdr, FrameRange, InspectArgs, RenderArgs, ReplayFrame, Snapshot, SnapshotKind, SnapshotRun, StarSummary,
+};
+pub use sort::{morton_key, quantization_cube, MortonSort};
+pub use sweep::{SweepArgs, SweepPoint, SweepResult};
+pub use telemetry::Telemetry;
+#[cfg(not(target_arch = "wasm32"))]
+pub use trajectory::TrajectoryWriter;
+pub use tune::{Probe, TuneArgs, TuneTrace};
+#[cfg(not(target_arch = "wasm32"))]
+pub use video::VideoEncoder;
+pub use watchdog::{OnDivergence, WatchdogCounts};
+#[cfg(target_arch = "wasm32")]
+pub use web::WebSimulation;
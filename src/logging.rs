@@ -0,0 +1,162 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::fmt::format::{FmtSpan, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{filter, Layer};
+
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+
+// Per-frame status lines. The console drops them while a progress bar shows the same
+// numbers; --log-file (and a console that is not a terminal) keeps every one.
+pub const FRAME_TARGET: &str = "chronoturin::frame";
+
+static PROGRESS: OnceLock<MultiProgress> = OnceLock::new();
+
+// Console level: --quiet shows warnings only, each --verbose adds a level.
+pub fn level(config: &SimConfig) -> LevelFilter {
+    match (config.quiet, config.verbose) {
+        (true, _) => LevelFilter::WARN,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    }
+}
+
+// RUST_LOG, when set, replaces the level from the flags (e.g. RUST_LOG=chronoturin=trace).
+fn env_filter(level: LevelFilter) -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(format!("chronoturin={}", level)))
+}
+
+// Installs the global subscriber: plain messages on stdout (stderr when `--frames-to -` has
+// stdout), drawn around the progress bars, plus a timestamped copy in --log-file.
+pub fn init(config: &SimConfig) -> Result<(), ChronoError> {
+    let to_stderr = config.frames_to.as_deref().is_some_and(crate::pipe::is_stdout);
+    let bars_visible = !config.quiet && io::stderr().is_terminal();
+    let target = if bars_visible { ProgressDrawTarget::stderr() } else { ProgressDrawTarget::hidden() };
+    PROGRESS.get_or_init(|| MultiProgress::with_draw_target(target));
+
+    let console = tracing_subscriber::fmt::layer()
+        .with_span_events(FmtSpan::CLOSE)
+        .event_format(ConsoleFormat)
+        .with_writer(ConsoleWriter { to_stderr })
+        .with_ansi(if to_stderr { io::stderr().is_terminal() } else { io::stdout().is_terminal() })
+        .with_filter(env_filter(level(config)))
+        .with_filter(filter::filter_fn(move |meta| !(bars_visible && meta.target() == FRAME_TARGET)));
+    let file = match &config.log_file {
+        Some(path) => Some(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(Mutex::new(File::create(path)?))
+                .with_span_events(FmtSpan::CLOSE)
+                .with_filter(env_filter(level(config))),
+        ),
+        None => None,
+    };
+    tracing_subscriber::registry().with(console).with(file).init();
+    Ok(())
+}
+
+fn progress() -> &'static MultiProgress {
+    PROGRESS.get_or_init(|| MultiProgress::with_draw_target(ProgressDrawTarget::hidden()))
+}
+
+// A bar of `len` frames (or steps); set its message to the latest timings.
+pub fn progress_bar(len: u64, prefix: String) -> ProgressBar {
+    let bar = progress().add(ProgressBar::new(len));
+    let style = ProgressStyle::with_template("{prefix} [{bar:30}] {pos}/{len} | ETA {eta} | {msg}")
+        .expect("valid progress template")
+        .progress_chars("=> ");
+    bar.set_style(style);
+    bar.set_prefix(prefix);
+    bar.enable_steady_tick(Duration::from_millis(250));
+    bar
+}
+
+// INFO lines print bare, like the old println! output; other levels and spans are labeled.
+struct ConsoleFormat;
+
+impl<S, N> FormatEvent<S, N> for ConsoleFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let level = *event.metadata().level();
+        if level != Level::INFO {
+            write!(writer, "{}: ", level)?;
+        }
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                match span.extensions().get::<FormattedFields<N>>().filter(|fields| !fields.is_empty()) {
+                    Some(fields) => write!(writer, "{}{{{}}}: ", span.name(), fields)?,
+                    None => write!(writer, "{}: ", span.name())?,
+                }
+            }
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+// Hides the progress bars while a log line is written, so neither garbles the other.
+struct ConsoleWriter {
+    to_stderr: bool,
+}
+
+impl<'a> MakeWriter<'a> for ConsoleWriter {
+    type Writer = ConsoleLine;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ConsoleLine(Vec::new(), self.to_stderr)
+    }
+}
+
+struct ConsoleLine(Vec<u8>, bool);
+
+impl Write for ConsoleLine {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ConsoleLine {
+    fn drop(&mut self) {
+        if !self.0.is_empty() {
+            progress().suspend(|| {
+                let mut out: Box<dyn Write> = if self.1 { Box::new(io::stderr().lock()) } else { Box::new(io::stdout().lock()) };
+                let _ = out.write_all(&self.0);
+                let _ = out.flush();
+            });
+        }
+    }
+}
+
+// 23412 -> "23,412"
+pub fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut groups = Vec::new();
+    let mut end = digits.len();
+    while end > 3 {
+        groups.push(&digits[end - 3..end]);
+        end -= 3;
+    }
+    groups.push(&digits[..end]);
+    groups.reverse();
+    groups.join(",")
+}
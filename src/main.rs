@@ -1,188 +1,205 @@
-use std::time::Instant;
-use wgpu::util::DeviceExt;
-use rand::prelude::*;
-use image::RgbImage;
-use std::io::Write;
+use clap::CommandFactory;
+use tracing::info;
 
-// --- CONFIGURATION ---
-const NUM_STARS: u32 = 100_000; 
-const WORKGROUP_SIZE: u32 = 256;
-const FRAMES_PER_MODE: usize = 150; // 150 frames for each mode
-const SIM_STEPS_PER_FRAME: usize = 5; 
-
-// DATA TYPES
-const TYPE_A: f32 = 0.0; 
-const TYPE_B: f32 = 1.0; 
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Star {
-    x: f32, y: f32, z: f32,
-    vx: f32, vy: f32, vz: f32,
-    mass: f32, 
-    data_type: f32, 
-    time_debt: f32, 
-    active_flag: f32, // Replaces 'padding' to visualize work
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct GalaxyState {
-    time_seed: f32, 
-    dilation_mode: f32, // 0.0 = Newton, 1.0 = Chronoturin
-    padding2: f32, padding3: f32,
-}
+use chronoturin::config::Command;
+use chronoturin::{
+    bench, config_file, cpu_reference, gpu, inspect, interrupt, logging, runner, snapshot, sweep, ChronoError, DilationMode, SimConfig,
+};
+#[cfg(feature = "viewer")]
+use chronoturin::{galaxy::check_types, presets, read_initial_conditions, viewer, Simulation};
 
 fn main() {
-    pollster::block_on(run());
+    let config = match config_file::resolve(&SimConfig::command().get_matches()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if config.print_config {
+        print!("{}", config_file::to_toml(&config));
+        return;
+    }
+    let instance = gpu::create_instance(&config);
+    if config.list_adapters {
+        let adapters = gpu::describe_adapters(&instance, gpu::backends(&config));
+        if adapters.is_empty() {
+            println!("No adapters found.");
+        }
+        for (i, adapter) in adapters.iter().enumerate() {
+            println!("[{}] {}", i, adapter);
+        }
+        return;
+    }
+    if let Err(e) = logging::init(&config) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    let backends = gpu::backends(&config);
+    interrupt::install_handler();
+    if let Err(e) = pollster::block_on(run(config, &instance)) {
+        print_diagnosis(&e, &instance, backends);
+        std::process::exit(1);
+    }
+    if interrupt::requested() {
+        std::process::exit(interrupt::EXIT_CODE);
+    }
 }
 
-async fn run() {
-    println!("--- CHRONOTURIN: COMPARATIVE VISUALIZER ---");
-    
-    let instance = wgpu::Instance::default();
-    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await.expect("No GPU");
-    let (device, queue) = adapter.request_device(
-        &wgpu::DeviceDescriptor::default(),
-        None, 
-    ).await.expect("No Device");
-
-    let mut rng = rand::thread_rng();
-    
-    // --- RUN TWO PASSES (Newtonian vs Chronoturin) ---
-    for pass in 0..2 {
-        let is_chronoturin = pass == 1;
-        let mode_name = if is_chronoturin { "CHRONOTURIN" } else { "NEWTONIAN" };
-        let file_prefix = if is_chronoturin { "chrono" } else { "newton" };
-        let dilation_val = if is_chronoturin { 1.0 } else { 0.0 };
-
-        println!("\n>> STARTING PASS {}: {} MODE", pass + 1, mode_name);
-
-        // 1. RESET DATA (Identical Start for Fairness)
-        let mut initial_data = Vec::with_capacity(NUM_STARS as usize);
-        // We use a deterministic seed-like generation by re-creating RNG if we wanted exactness,
-        // but random chaos is fine as long as density is similar.
-        for _ in 0..NUM_STARS {
-            let r = 300.0 * rng.gen::<f32>().sqrt();
-            let theta = rng.gen_range(0.0..std::f32::consts::TAU);
-            let phi = rng.gen_range(0.0..std::f32::consts::PI);
-            let x = r * phi.sin() * theta.cos();
-            let y = r * phi.sin() * theta.sin();
-            let z = r * phi.cos();
-            let data_type = if rng.gen_bool(0.5) { TYPE_A } else { TYPE_B };
+async fn run(mut config: SimConfig, instance: &wgpu::Instance) -> Result<(), ChronoError> {
+    // inspect prints to stdout and, like render, never opens a device.
+    if let Some(Command::Inspect(args)) = &config.command {
+        print!("{}", inspect(&args.path)?);
+        return Ok(());
+    }
+    info!("--- CHRONOTURIN: COMPARATIVE VISUALIZER ---");
+    if let Some(Command::Render(args)) = config.command.clone() {
+        return snapshot::render(config, &args);
+    }
+    config.apply_command();
 
-            initial_data.push(Star { 
-                x, y, z, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, 
-                data_type, time_debt: 0.0, active_flag: 0.0 
-            });
+    #[cfg(feature = "viewer")]
+    if config.view {
+        return run_view(config, instance).await;
+    }
+    // --cpu never touches wgpu; otherwise device resources are created once and shared by both
+    // passes, or with --adapters one device per mode, Newtonian's first.
+    let mut adapters = Vec::new();
+    let mut adapter_name = String::from("CPU reference");
+    let mut device_errors = None;
+    let gpu = if config.cpu {
+        info!("Backend: CPU reference (--cpu)");
+        None
+    } else if let Some(indices) = config.adapters.clone() {
+        // Flag conflicts are reported before any device opens; with_stars re-checks against each one's limits.
+        config.validate(&cpu_reference::limits())?;
+        let mut devices = Vec::with_capacity(2);
+        for (index, mode) in indices.into_iter().zip([DilationMode::Newtonian, DilationMode::Chronoturin]) {
+            let (adapter, device, queue) = gpu::request_gpu_at(instance, &config, index, mode).await?;
+            adapters.push(adapter);
+            devices.push((device, queue));
         }
+        Some(devices)
+    } else {
+        let (adapter, device, queue, errors) = gpu::request_gpu(instance, &config, None).await?;
+        device_errors = Some(errors);
+        let info = adapter.get_info();
+        adapter_name = format!("{} ({:?})", info.name, info.backend);
+        Some(vec![(device, queue)])
+    };
+    let limits = gpu.as_ref().map_or_else(cpu_reference::limits, |devices| devices[0].0.limits());
+    if let Some(Command::Sweep(args)) = config.command.clone() {
+        config.validate(&limits)?;
+        let (device, queue) = gpu.expect("validate() rejects sweeps with --cpu").swap_remove(0);
+        return sweep::run(config, &args, device, queue, device_errors.expect("one device"));
+    }
+    if let Some(Command::Bench(args)) = config.command.clone() {
+        config.stars = args.stars.unwrap_or(config.stars);
+        config.uniform_path = args.uniform_path.or(config.uniform_path);
+        config.validate(&limits)?;
+        let (device, queue) = gpu.expect("validate() rejects benchmarks with --cpu").swap_remove(0);
+        return bench::run(config, &args, device, queue, device_errors.expect("one device"));
+    }
+    runner::run_frames(config, gpu, adapters, adapter_name, device_errors).await
+}
 
-        let storage_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Star Storage"),
-            contents: bytemuck::cast_slice(&initial_data),
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let galaxy_state = GalaxyState { time_seed: 0.0, dilation_mode: dilation_val, padding2: 0.0, padding3: 0.0 };
-        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Galaxy State"),
-            contents: bytemuck::cast_slice(&[galaxy_state]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Readback Buffer"),
-            size: (initial_data.len() * std::mem::size_of::<Star>()) as u64,
-            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: None, layout: None, module: &shader, entry_point: "main",
-        });
-        
-        let bind_group_layout = compute_pipeline.get_bind_group_layout(0);
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None, layout: &bind_group_layout, entries: &[
-                wgpu::BindGroupEntry { binding: 0, resource: storage_buffer.as_entire_binding() },
-                wgpu::BindGroupEntry { binding: 1, resource: uniform_buffer.as_entire_binding() },
-            ],
-        });
-
-        // RENDER LOOP
-        let fov = 800.0;
-        let camera_z = -1000.0;
-
-        for frame in 0..FRAMES_PER_MODE {
-            let start_time = Instant::now();
-
-            for _ in 0..SIM_STEPS_PER_FRAME {
-                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-                {
-                    let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None, timestamp_writes: None });
-                    cpass.set_pipeline(&compute_pipeline);
-                    cpass.set_bind_group(0, &bind_group, &[]);
-                    cpass.dispatch_workgroups((NUM_STARS + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
-                }
-                queue.submit(Some(encoder.finish()));
-            }
-
-            // Readback
-            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-            encoder.copy_buffer_to_buffer(&storage_buffer, 0, &readback_buffer, 0, (initial_data.len() * std::mem::size_of::<Star>()) as u64);
-            queue.submit(Some(encoder.finish()));
-
-            let buffer_slice = readback_buffer.slice(..);
-            let (tx, rx) = std::sync::mpsc::channel();
-            buffer_slice.map_async(wgpu::MapMode::Read, move |v| tx.send(v).unwrap());
-            
-            device.poll(wgpu::Maintain::Wait);
-            
-            if let Ok(Ok(())) = rx.recv() {
-                let data = buffer_slice.get_mapped_range();
-                let stars: &[Star] = bytemuck::cast_slice(&data);
-                let mut img = RgbImage::new(1024, 1024);
-
-                for star in stars {
-                    let rel_z = star.z - camera_z;
-                    if rel_z > 10.0 {
-                        let factor = fov / rel_z;
-                        let screen_x = star.x * factor + 512.0;
-                        let screen_y = star.y * factor + 512.0;
-
-                        if screen_x >= 0.0 && screen_x < 1024.0 && screen_y >= 0.0 && screen_y < 1024.0 {
-                            let pixel = img.get_pixel_mut(screen_x as u32, screen_y as u32);
-                            
-                            // BASE COLORS
-                            if star.data_type < 0.5 { pixel[0] = pixel[0].saturating_add(200); } // Red
-                            else { pixel[2] = pixel[2].saturating_add(255); } // Blue
+// --- VIEW: ONE LIVE WINDOW INSTEAD OF FRAME FILES ---
+#[cfg(feature = "viewer")]
+async fn run_view(mut config: SimConfig, instance: &wgpu::Instance) -> Result<(), ChronoError> {
+    let seed = config.seed.unwrap_or_else(rand::random);
+    config.seed = Some(seed);
+    let initial_data = match &config.initial_conditions {
+        Some(path) => read_initial_conditions(path)?,
+        None => presets::from_config(&config, seed),
+    };
+    config.stars = initial_data.len() as u32;
+    // Flag conflicts are reported before a window opens; with_stars re-checks against the adapter's limits.
+    config.validate(&cpu_reference::limits())?;
+    check_types(&initial_data, config.types)?;
+
+    let (event_loop, window) = viewer::open_window(&config)?;
+    let surface = instance.create_surface(window.clone()).map_err(|e| ChronoError::Window(e.to_string()))?;
+    let (adapter, device, queue, errors) = gpu::request_gpu(instance, &config, Some(&surface)).await?;
+    if !adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE) {
+        return Err(ChronoError::Window("the adapter cannot read storage buffers in vertex shaders".into()));
+    }
+    let mut sim = Simulation::with_stars(device, queue, &config, initial_data)?;
+    sim.watch_device(errors);
+    info!("Stars: {} | Preset: {:?} | Steps/Frame: {} | Seed: {}", sim.star_count(), config.preset, config.steps_per_frame, seed);
+    info!("Controls: Space pause | M toggle mode | R reset | Left/Right orbit | Up/Down lift | Scroll zoom | Esc/Q quit");
+    viewer::run(event_loop, window, surface, &adapter, sim, &config)
+}
 
-                            // EFFICIENCY VISUALIZER (The Glow)
-                            // If active_flag is 1.0, the particle worked this frame.
-                            // Newtonian Mode: ALL particles work -> Total Whiteout.
-                            // Chronoturin Mode: Only CORE works -> Dark Shell.
-                            if star.active_flag > 0.5 {
-                                pixel[1] = pixel[1].saturating_add(150); // Add Green/White
-                                if is_chronoturin {
-                                    // Make Chronoturin core look "Golden" to distinguish
-                                    pixel[0] = pixel[0].saturating_add(50);
-                                }
-                            }
-                        }
-                    }
+fn print_diagnosis(error: &ChronoError, instance: &wgpu::Instance, backends: wgpu::Backends) {
+    eprintln!("\nError: {}", error);
+    match error {
+        ChronoError::AdapterNotFound | ChronoError::DeviceRequestFailed(_) => {
+            eprintln!("Backends searched: {:?}", backends);
+            let adapters = gpu::describe_adapters(instance, backends);
+            if adapters.is_empty() {
+                eprintln!("Adapters found: none");
+            } else {
+                eprintln!("Adapters found:");
+                for adapter in adapters {
+                    eprintln!("  - {}", adapter);
                 }
-                drop(data); 
-                readback_buffer.unmap();
-                
-                let filename = format!("{}_{:03}.png", file_prefix, frame);
-                img.save(&filename).unwrap();
-                
-                let dur = start_time.elapsed().as_millis();
-                print!("\r[{}] Frame {:03} | Render Time: {} ms", mode_name, frame, dur);
-                std::io::stdout().flush().unwrap();
             }
+            eprintln!("Suggested fixes:");
+            eprintln!("  * Install or update your GPU driver (Vulkan on Windows/Linux, Metal on macOS, DX12 on Windows).");
+            eprintln!("  * On headless Linux, install Mesa (lavapipe/llvmpipe) for a software adapter.");
+            eprintln!("  * Try another backend with --backend vulkan|metal|dx12|gl (see --list-adapters).");
+        }
+        ChronoError::BufferTooLarge { .. } => {
+            eprintln!("Suggested fix: lower --stars, or use an adapter with larger storage buffers (see --list-adapters).");
+        }
+        ChronoError::AdapterNotMatched { .. } | ChronoError::AdapterIndex { .. } => {
+            eprintln!("Run with --list-adapters to see adapter names.")
+        }
+        ChronoError::Device { .. } => {
+            eprintln!("Frames and metrics written before the error are intact; rerun on another adapter or without --adapters.");
+        }
+        ChronoError::DeviceLost(_) | ChronoError::MapFailed(_) => {
+            eprintln!("Frames and metrics written before the fault are intact, and so is the last good state in checkpoint.bin; --resume it once the driver is back.");
+            eprintln!("Long dispatches can trip the driver's watchdog (TDR on Windows): lower --steps-per-frame or --stars.");
+        }
+        ChronoError::MapTimeout { .. } => {
+            eprintln!("The frames before it are written, and the last good state is in checkpoint.bin for --resume.");
+            eprintln!("Update the GPU driver, try another --backend, or raise --map-timeout-secs for very large readbacks.");
+        }
+        ChronoError::Validation(_) => eprintln!("A shader or pipeline was rejected by the driver; the message above names the WGSL line."),
+        ChronoError::Diverged { .. } => {
+            eprintln!("Suggested fixes: lower --dt or raise --softening; --on-divergence warn logs it and keeps the run going.");
+        }
+        ChronoError::InvalidConfig(_) => eprintln!("Run with --help to see valid options."),
+        ChronoError::Parse { .. } => {}
+        ChronoError::Checkpoint { .. } => {
+            eprintln!("A checkpoint from another version of chronoturin is refused; resume it with the build that wrote it, or rerun from the start.");
+        }
+        ChronoError::ShaderLayout(_) => {
+            eprintln!(
+                "A field was added, removed or reordered on one side only; update the Rust struct in galaxy.rs and the WGSL one together."
+            );
+        }
+        ChronoError::Window(_) => {
+            eprintln!("Suggested fix: --view needs a desktop session (DISPLAY or WAYLAND_DISPLAY); drop it to write frames instead.");
+        }
+        ChronoError::Encoder(_) => {
+            eprintln!("Suggested fix: install ffmpeg with libx264, point --ffmpeg at it, or drop --video for PNGs.");
+        }
+        ChronoError::Plot(_) => {
+            eprintln!("Frames, metrics and the report are already written; rerun without --plots, or plot the --metrics-out file.");
+        }
+        ChronoError::PipeClosed { .. } => {
+            eprintln!("Frames up to the close were written; --on-pipe-close continue finishes the run without the pipe.");
+        }
+        ChronoError::OverBudget { .. } => {
+            eprintln!("Lower tune --min-threshold, shorten --probe-frames, or raise --error-budget.");
+        }
+        ChronoError::OverMemory { .. } => {
+            eprintln!(
+                "The planned buffers are logged above; nothing was allocated. The plan leaves out what the driver reserves for itself."
+            );
         }
+        ChronoError::Io(_) => {}
     }
-    println!("\nSimulation Complete.");
 }
@@ -0,0 +1,157 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+
+use crate::bindings::UniformPath;
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+use crate::hdr::Rgb16Image;
+use crate::memory::MemoryPlan;
+
+// The PNG text keyword frames carry their manifest under.
+pub const PNG_KEYWORD: &str = "chronoturin-manifest";
+
+// What wrote an artifact: the build, and every flag of the run as config_file resolves them.
+// run.json, frame PNGs, dumps and checkpoints all carry this one type, so they agree.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RunManifest {
+    // The crate version
+    pub version: String,
+    // `git describe` when built from a checkout (see build.rs)
+    pub git: Option<String>,
+    // The run's SimConfig, under its --config keys
+    pub config: serde_json::Value,
+    // How the GPU kernels got GalaxyState, as the device decided; None on the CPU
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uniform_path: Option<UniformPath>,
+    // The buffers the run planned to allocate (MemoryPlan); None on the CPU
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryPlan>,
+}
+
+impl RunManifest {
+    // After the seed is drawn and --sequential decided, so the config reruns the same galaxy.
+    pub fn new(config: &SimConfig) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git: option_env!("CHRONOTURIN_GIT_DESCRIBE").map(str::to_string),
+            config: serde_json::to_value(config).expect("SimConfig serializes to JSON"),
+            uniform_path: None,
+            memory: None,
+        }
+    }
+
+    // One line, as dumps and PNG text embed it.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("RunManifest serializes to JSON")
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+
+    // What `inspect` prints: the build, then the config as a --config file would hold it.
+    pub fn describe(&self) -> String {
+        let mut text = format!("Written by chronoturin {}", self.version);
+        if let Some(git) = &self.git {
+            text.push_str(&format!(" ({})", git));
+        }
+        text.push('\n');
+        if let Some(path) = self.uniform_path {
+            text.push_str(&format!("GalaxyState by {}\n", path.describe()));
+        }
+        if let Some(memory) = &self.memory {
+            text.push_str(&format!("{:.2} MiB of GPU buffers planned\n", memory.total() as f64 / (1024.0 * 1024.0)));
+        }
+        // A manifest from another version may not read back; its JSON still says everything.
+        match serde_json::from_value::<SimConfig>(self.config.clone()) {
+            Ok(config) => text.push_str(&crate::config_file::to_toml(&config)),
+            Err(_) => text.push_str(&format!("{:#}\n", self.config)),
+        }
+        text
+    }
+
+    // `run.json` in the output directory.
+    pub fn write(&self, path: &Path) -> Result<(), ChronoError> {
+        let json = serde_json::to_string_pretty(self).expect("RunManifest serializes to JSON");
+        std::fs::write(path, json + "\n")?;
+        Ok(())
+    }
+
+    // The manifest in a PNG's text chunk, a run.json, or a binary dump's sidecar.
+    pub fn read(path: &Path) -> Result<Self, ChronoError> {
+        let error = |message: String| ChronoError::Parse { path: path.to_path_buf(), line: 0, message };
+        if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("png")) {
+            return read_png_text(path)?.ok_or_else(|| error(format!("has no {} text chunk", PNG_KEYWORD)));
+        }
+        let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let value: serde_json::Value = serde_json::from_str(&text).map_err(|e| error(e.to_string()))?;
+        let value = value.get("manifest").cloned().unwrap_or(value);
+        serde_json::from_value(value).map_err(|e| error(format!("not a run manifest: {}", e)))
+    }
+}
+
+// Writes `img` as an 8-bit RGB PNG, with `manifest` in a text chunk (tEXt, or iTXt if a path in
+// the config is not Latin-1). Errors name the file.
+pub fn save_png(path: &Path, img: &RgbImage, manifest: Option<&RunManifest>) -> Result<(), ChronoError> {
+    encode_png(path, (img.width(), img.height()), png::BitDepth::Eight, img.as_raw(), manifest).map_err(|message| ChronoError::Parse {
+        path: path.to_path_buf(),
+        line: 0,
+        message,
+    })
+}
+
+// The same, 16 bits per channel (--image-format png16).
+pub fn save_png16(path: &Path, img: &Rgb16Image, manifest: Option<&RunManifest>) -> Result<(), ChronoError> {
+    let bytes: Vec<u8> = img.as_raw().iter().flat_map(|v| v.to_be_bytes()).collect();
+    encode_png(path, (img.width(), img.height()), png::BitDepth::Sixteen, &bytes, manifest).map_err(|message| ChronoError::Parse {
+        path: path.to_path_buf(),
+        line: 0,
+        message,
+    })
+}
+
+fn encode_png(
+    path: &Path,
+    (width, height): (u32, u32),
+    depth: png::BitDepth,
+    data: &[u8],
+    manifest: Option<&RunManifest>,
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(depth);
+    if let Some(manifest) = manifest {
+        let text = manifest.to_json();
+        if text.chars().all(|c| (c as u32) < 256) {
+            encoder.add_text_chunk(PNG_KEYWORD.into(), text).map_err(|e| e.to_string())?;
+        } else {
+            encoder.add_itxt_chunk(PNG_KEYWORD.into(), text).map_err(|e| e.to_string())?;
+        }
+    }
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(data).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())
+}
+
+// The manifest save_png embedded, or None for a PNG without one.
+pub fn read_png_text(path: &Path) -> Result<Option<RunManifest>, ChronoError> {
+    let error = |message: String| ChronoError::Parse { path: path.to_path_buf(), line: 0, message };
+    let reader = png::Decoder::new(BufReader::new(File::open(path).map_err(|e| error(e.to_string()))?))
+        .read_info()
+        .map_err(|e| error(e.to_string()))?;
+    let info = reader.info();
+    let latin1 = info.uncompressed_latin1_text.iter().filter(|c| c.keyword == PNG_KEYWORD).map(|c| Ok(c.text.clone()));
+    let utf8 = info.utf8_text.iter().filter(|c| c.keyword == PNG_KEYWORD).map(|c| c.get_text());
+    match latin1.chain(utf8).next() {
+        Some(text) => {
+            let text = text.map_err(|e| error(e.to_string()))?;
+            RunManifest::from_json(&text).map(Some).map_err(|e| error(format!("unreadable {} text: {}", PNG_KEYWORD, e)))
+        }
+        None => Ok(None),
+    }
+}
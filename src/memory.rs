@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+
+use crate::compaction;
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+use crate::grid::{self, Accel};
+use crate::merge;
+use crate::packing::{self, ReadbackChoice};
+use crate::raster::{self, RasterChoice};
+use crate::reduction;
+use crate::render::{Camera, RenderSettings};
+use crate::sort;
+
+const MIB: u64 = 1024 * 1024;
+
+// One kind of buffer a simulation allocates: how many there are, the largest of them and
+// their bytes together.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Allocation {
+    pub name: String,
+    pub buffers: u32,
+    pub largest: u64,
+    pub bytes: u64,
+    // Bound as storage, so also held to max_storage_buffer_binding_size
+    pub storage: bool,
+}
+
+// Every sizable buffer a run allocates, worked out from its flags before any of them is
+// created. Uniforms, counters and the per-step GalaxyState ring, a few KiB in all, are left out.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MemoryPlan {
+    // Simulations holding these buffers at once on the device: two for a side-by-side run
+    // that steps a fork of the first
+    pub simulations: u32,
+    // One simulation's buffers
+    pub allocations: Vec<Allocation>,
+}
+
+impl MemoryPlan {
+    // What Simulation::with_stars and main's build_simulation allocate for `config` on a
+    // device with `limits`.
+    pub fn new(config: &SimConfig, limits: &wgpu::Limits, simulations: u32) -> Result<Self, ChronoError> {
+        let layout = config.chunk_layout(limits)?;
+        let stars = layout.stars;
+        let chunks: Vec<u32> = layout.ranges().map(|range| range.len() as u32).collect();
+        let slots = config.readback_buffers.max(1);
+        let star_bytes: Vec<u64> = chunks.iter().map(|&n| n as u64 * config.precision.star_bytes()).collect();
+        let mut allocations = Vec::new();
+        let mut add = |name: &str, storage: bool, sizes: Vec<u64>| {
+            allocations.push(Allocation {
+                name: name.to_string(),
+                buffers: sizes.len() as u32,
+                largest: sizes.iter().copied().max().unwrap_or(0),
+                bytes: sizes.iter().sum(),
+                storage,
+            });
+        };
+
+        add("star storage", true, star_bytes.clone());
+        match config.readback {
+            ReadbackChoice::Full => add("star readback", false, star_bytes.repeat(slots)),
+            ReadbackChoice::Packed => {
+                let records: Vec<u64> = chunks.iter().map(|&n| packing::record_bytes(n)).collect();
+                add("render records", true, records.clone());
+                add("record readback", false, records.repeat(slots));
+            }
+        }
+        if config.trails {
+            let records: Vec<u64> = chunks.iter().map(|&n| packing::record_bytes(n)).collect();
+            let steps = config.steps_per_frame.saturating_sub(1).max(1) as u64;
+            add("trail records", true, records.clone());
+            add("trail readback", false, records.iter().map(|&r| steps * r).collect::<Vec<_>>().repeat(slots));
+        }
+        // Culling moves the dead with the sort, as in with_pipeline.
+        if config.sort_every.is_some() || config.cull_radius().is_some() {
+            add("sort keys", true, vec![sort::key_bytes(stars)]);
+            add("star ids", true, vec![sort::id_bytes(stars); 2]);
+            add("sort scratch", true, star_bytes.clone());
+            add("star id readback", false, vec![sort::id_bytes(stars); slots]);
+        }
+        if config.accel == Accel::Grid {
+            add("grid cells", true, vec![grid::cell_bytes(stars)]);
+            add("grid bins", true, vec![grid::bin_bytes(stars)]);
+            if config.merge_radius > 0.0 {
+                add("merge claims", true, vec![merge::claim_bytes(stars)]);
+            }
+        }
+        if config.compaction {
+            add("active list", true, vec![compaction::list_bytes(stars), compaction::calm_bytes(stars)]);
+        }
+        if config.gpu_diagnostics {
+            add("reduction partials", true, chunks.iter().map(|&n| reduction::partials_bytes(n)).collect());
+        }
+        if config.raster_choice() == RasterChoice::Gpu {
+            let camera = Camera::from_config(config);
+            add("raster accumulation", true, vec![raster::accum_bytes(&RenderSettings::from_config(config), &camera)]);
+            add("raster frame", false, vec![camera.width as u64 * camera.height as u64 * 4]);
+            add("raster readback", false, vec![raster::readback_bytes(&camera); slots]);
+        }
+        Ok(Self { simulations, allocations })
+    }
+
+    // Every simulation's buffers together.
+    pub fn total(&self) -> u64 {
+        self.simulations as u64 * self.allocations.iter().map(|a| a.bytes).sum::<u64>()
+    }
+
+    pub fn largest(&self) -> u64 {
+        self.allocations.iter().map(|a| a.largest).max().unwrap_or(0)
+    }
+
+    // Why the plan does not fit `limits` or `budget` (MiB), or None if it does.
+    pub fn overrun(&self, limits: &wgpu::Limits, budget: Option<u64>) -> Option<String> {
+        for a in &self.allocations {
+            if a.largest > limits.max_buffer_size {
+                return Some(format!(
+                    "a {} buffer needs {}, more than the device's {} per buffer",
+                    a.name,
+                    mib(a.largest),
+                    mib(limits.max_buffer_size)
+                ));
+            }
+            let binding = limits.max_storage_buffer_binding_size as u64;
+            if a.storage && a.largest > binding {
+                return Some(format!(
+                    "a {} buffer needs {}, more than the device's {} per storage binding",
+                    a.name,
+                    mib(a.largest),
+                    mib(binding)
+                ));
+            }
+        }
+        budget
+            .filter(|&budget| self.total() > budget * MIB)
+            .map(|budget| format!("the planned GPU buffers add up to {}, over --memory-budget {}", mib(self.total()), budget))
+    }
+
+    // The plan for `config`, or an error naming what would shrink it when it does not fit.
+    pub fn check(&self, config: &SimConfig, limits: &wgpu::Limits) -> Result<(), ChronoError> {
+        match self.overrun(limits, config.memory_budget) {
+            Some(reason) => Err(ChronoError::OverMemory { reason, mitigations: self.mitigations(config, limits) }),
+            None => Ok(()),
+        }
+    }
+
+    // The flags that would make the plan smaller, each with what it saves, ending with the
+    // most stars that would fit as the run stands.
+    pub fn mitigations(&self, config: &SimConfig, limits: &wgpu::Limits) -> Vec<String> {
+        let total = self.total();
+        let saving = |other: &SimConfig, simulations: u32| {
+            other.validate(limits).ok()?;
+            let plan = Self::new(other, limits, simulations).ok()?;
+            (plan.total() < total).then(|| mib(total - plan.total()))
+        };
+        let mut mitigations = Vec::new();
+        if self.simulations > 1 {
+            if let Some(saved) = saving(config, 1) {
+                mitigations.push(format!("--sequential, which holds one simulation's buffers at a time instead of two (saves {})", saved));
+            }
+        }
+        if config.readback == ReadbackChoice::Full {
+            let packed = SimConfig { readback: ReadbackChoice::Packed, ..config.clone() };
+            if let Some(saved) = saving(&packed, self.simulations) {
+                mitigations
+                    .push(format!("--readback packed, which reads back 16-byte render records instead of whole stars (saves {})", saved));
+            }
+        }
+        if config.readback_buffers > 1 {
+            let single = SimConfig { readback_buffers: 1, ..config.clone() };
+            if let Some(saved) = saving(&single, self.simulations) {
+                mitigations.push(format!("--readback-buffers 1, which gives up overlapping compute with readback (saves {})", saved));
+            }
+        }
+        let fits = |stars: u32| {
+            let smaller = SimConfig { stars, ..config.clone() };
+            Self::new(&smaller, limits, self.simulations).is_ok_and(|plan| plan.overrun(limits, config.memory_budget).is_none())
+        };
+        // Bisect for the most stars that fit; every buffer grows with the star count.
+        let (mut fit, mut over) = (0, config.stars);
+        while over - fit > 1 {
+            let mid = fit + (over - fit) / 2;
+            if fits(mid) {
+                fit = mid
+            } else {
+                over = mid
+            }
+        }
+        if fit > 0 {
+            mitigations.push(format!("--stars {} or fewer", fit));
+        }
+        mitigations
+    }
+
+    // One line per kind of buffer, then the total, as the run logs it before allocating.
+    pub fn table(&self) -> Vec<String> {
+        let mut lines = vec![format!("Planned GPU buffers, per simulation ({} at once):", self.simulations)];
+        let width = self.allocations.iter().map(|a| a.name.len()).max().unwrap_or(0);
+        for a in &self.allocations {
+            lines.push(format!("  {:<width$}  {:>2} x {:>10} = {:>10}", a.name, a.buffers, mib(a.largest), mib(a.bytes), width = width));
+        }
+        lines.push(format!("  Total {}, largest buffer {}", mib(self.total()), mib(self.largest())));
+        lines
+    }
+}
+
+fn mib(bytes: u64) -> String {
+    format!("{:.2} MiB", bytes as f64 / MIB as f64)
+}
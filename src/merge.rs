@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use crate::grid::Grid;
+
+// Merging's merger count, after the radius
+const MERGERS_OFFSET: u64 = 4;
+
+// Matches `Merging` in shader.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MergingState {
+    radius: f32,
+    mergers: u32,
+    pad: [u32; 2],
+}
+
+// The claims Merging::new allocates for `stars`, one u32 each.
+pub fn claim_bytes(stars: u32) -> u64 {
+    stars as u64 * 4
+}
+
+// shader.wgsl's merge. Compiled once and shared by forked and resized simulations.
+pub struct MergePipelines {
+    merge: wgpu::ComputePipeline,
+}
+
+impl MergePipelines {
+    // `shader` is simulation::shader_module's, which the other passes on shader.wgsl share.
+    pub fn new(device: &wgpu::Device, shader: &wgpu::ShaderModule) -> Self {
+        let merge = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("merge"),
+            layout: None,
+            module: shader,
+            entry_point: "merge",
+        });
+        Self { merge }
+    }
+}
+
+// --merge-radius: once per frame, stars closer than the radius merge in pairs. The survivor
+// takes the pair's mass, center of mass and momentum; the other is flagged dead and the
+// cull that follows moves it behind the living (see cull.rs).
+pub struct Merging {
+    pipelines: Arc<MergePipelines>,
+    state: wgpu::Buffer,
+    claims: wgpu::Buffer,
+    // One per star chunk, with its whole workgroup count
+    passes: Vec<(wgpu::BindGroup, u32)>,
+}
+
+impl Merging {
+    // `chunks` are each chunk's entries for shader.wgsl bindings 0 and 3 to 9 and its workgroup count.
+    pub fn new(
+        device: &wgpu::Device,
+        pipelines: Arc<MergePipelines>,
+        radius: f32,
+        stars: u32,
+        chunks: &[(Vec<wgpu::BindGroupEntry>, u32)],
+    ) -> Self {
+        let state = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Merging"),
+            contents: bytemuck::bytes_of(&MergingState { radius, mergers: 0, pad: [0; 2] }),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+        let claims = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Merge Claims"),
+            size: claim_bytes(stars),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = pipelines.merge.get_bind_group_layout(0);
+        let passes = chunks
+            .iter()
+            .map(|(entries, workgroups)| {
+                let entries: Vec<_> = entries
+                    .iter()
+                    .cloned()
+                    .chain([
+                        wgpu::BindGroupEntry { binding: 15, resource: state.as_entire_binding() },
+                        wgpu::BindGroupEntry { binding: 16, resource: claims.as_entire_binding() },
+                    ])
+                    .collect();
+                (device.create_bind_group(&wgpu::BindGroupDescriptor { label: None, layout: &layout, entries: &entries }), *workgroups)
+            })
+            .collect();
+        Self { pipelines, state, claims, passes }
+    }
+
+    pub fn pipelines(&self) -> &Arc<MergePipelines> {
+        &self.pipelines
+    }
+
+    // Rebuilds `grid` on the current positions and records the merge pass over each chunk's
+    // living stars. A cull must follow before the next step.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, grid: &Grid) {
+        encoder.clear_buffer(&self.claims, 0, None);
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Merge"), timestamp_writes: None });
+        grid.encode(&mut cpass);
+        cpass.set_pipeline(&self.pipelines.merge);
+        for (bind_group, workgroups) in &self.passes {
+            cpass.set_bind_group(0, bind_group, &[]);
+            cpass.dispatch_workgroups(*workgroups, 1, 1);
+        }
+    }
+
+    // Restarts the merger count, for stars uploaded anew.
+    pub fn reset(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.state, MERGERS_OFFSET, bytemuck::bytes_of(&0u32));
+    }
+
+    // Copies the mergers so far into `dest`.
+    pub fn copy_mergers(&self, encoder: &mut wgpu::CommandEncoder, dest: &wgpu::Buffer, offset: u64) {
+        encoder.copy_buffer_to_buffer(&self.state, MERGERS_OFFSET, dest, offset, 4);
+    }
+}
@@ -0,0 +1,264 @@
+// Nothing is written on the web.
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::conservation::Conservation;
+use crate::simulation::DilationMode;
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MetricsFormat {
+    Csv,
+    Json,
+}
+
+// One row per rendered frame. Column order is part of the file format; append new
+// fields at the end so existing loaders keep working. Serialized, the record is the
+// `--metrics-format json` row, which reads back into it.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct MetricsRecord {
+    pub mode: DilationMode,
+    pub frame: usize,
+    pub wall_ms: f32,
+    pub gpu_ms: Option<f32>,
+    pub active_count: u32,
+    pub stars: u32,
+    pub sim_time: f32,
+    // Displacement from the Newtonian run at the same frame (--diff, Chronoturin rows only)
+    pub error_mean: Option<f32>,
+    pub error_p99: Option<f32>,
+    // Energy and momentum totals of the read-back stars
+    #[serde(flatten, with = "conservation_columns")]
+    pub conservation: Option<Conservation>,
+    // --dilation-threshold and --max-debt, so threshold sweeps describe themselves
+    pub dilation_threshold: f32,
+    pub max_debt: f32,
+    // FrameData::readback_ms and readback_bytes, to compare --readback full and packed
+    pub readback_ms: f32,
+    pub readback_bytes: u64,
+    // FrameData::force_threads, to measure what --compaction saves
+    pub force_threads: Option<u64>,
+    // The --list-adapters index the row's mode ran on (--adapters only)
+    pub adapter: Option<usize>,
+    // Stars of each data type active in the frame's last step (galaxy::active_by_type)
+    pub active_by_type: Vec<u32>,
+    // Profiles::radial and ::debt counts, overflow bin last; no debt for packed readbacks
+    pub radial_profile: Vec<u32>,
+    pub debt_histogram: Vec<u32>,
+    // The --interactions matrix, row-major over --types, so each run records its forces
+    pub interactions: Vec<f32>,
+    // FrameData::alive: the stars --kill-radius has not culled
+    pub alive: u32,
+    // FrameData::mergers, cumulative (--merge-radius only)
+    pub mergers: Option<u32>,
+    // FrameData::substeps, to see what --max-substeps costs
+    pub substeps: Option<u32>,
+    // The frame did not read back and was written per --on-frame-error; nothing else in the
+    // row was measured
+    pub placeholder: bool,
+    // LagrangianRadii at LAGRANGIAN_FRACTIONS of the mass; None for frames without stars. The
+    // row's virial_ratio column is Conservation::virial_ratio
+    pub lagrangian_radii: Option<[f32; 5]>,
+}
+
+// A Conservation as the JSON row's columns: the totals with their vectors split into x, y
+// and z, and the derived total_energy and virial_ratio, which are written but not read. All
+// of them are null without one.
+mod conservation_columns {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::conservation::Conservation;
+
+    #[derive(Default, Serialize, Deserialize)]
+    struct Columns {
+        kinetic_energy: Option<f64>,
+        potential_energy: Option<f64>,
+        #[serde(skip_deserializing)]
+        total_energy: Option<f64>,
+        momentum_x: Option<f64>,
+        momentum_y: Option<f64>,
+        momentum_z: Option<f64>,
+        angular_momentum_x: Option<f64>,
+        angular_momentum_y: Option<f64>,
+        angular_momentum_z: Option<f64>,
+        #[serde(skip_deserializing)]
+        virial_ratio: Option<f64>,
+    }
+
+    pub fn serialize<S: Serializer>(conservation: &Option<Conservation>, serializer: S) -> Result<S::Ok, S::Error> {
+        let columns = conservation.map_or_else(Columns::default, |c| Columns {
+            kinetic_energy: Some(c.kinetic),
+            potential_energy: c.potential,
+            total_energy: c.total_energy(),
+            momentum_x: Some(c.momentum[0]),
+            momentum_y: Some(c.momentum[1]),
+            momentum_z: Some(c.momentum[2]),
+            angular_momentum_x: Some(c.angular_momentum[0]),
+            angular_momentum_y: Some(c.angular_momentum[1]),
+            angular_momentum_z: Some(c.angular_momentum[2]),
+            virial_ratio: c.virial_ratio(),
+        });
+        columns.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Conservation>, D::Error> {
+        let c = Columns::deserialize(deserializer)?;
+        let Some(kinetic) = c.kinetic_energy else { return Ok(None) };
+        let vector = |name: &str, v: [Option<f64>; 3]| match v {
+            [Some(x), Some(y), Some(z)] => Ok([x, y, z]),
+            _ => Err(D::Error::custom(format!("kinetic_energy without all of {0}_x, {0}_y and {0}_z", name))),
+        };
+        Ok(Some(Conservation {
+            kinetic,
+            potential: c.potential_energy,
+            momentum: vector("momentum", [c.momentum_x, c.momentum_y, c.momentum_z])?,
+            angular_momentum: vector("angular_momentum", [c.angular_momentum_x, c.angular_momentum_y, c.angular_momentum_z])?,
+        }))
+    }
+}
+
+// `--metrics-format json` rows, and --serve's /metrics.json.
+#[cfg(not(target_arch = "wasm32"))]
+impl MetricsRecord {
+    // The row of a frame that never read back (--on-frame-error): which frame it was, under
+    // which settings, and nothing measured.
+    pub fn placeholder(mode: DilationMode, frame: usize, wall_ms: f32, sim_time: f32, (dilation_threshold, max_debt): (f32, f32)) -> Self {
+        MetricsRecord {
+            mode,
+            frame,
+            wall_ms,
+            gpu_ms: None,
+            active_count: 0,
+            stars: 0,
+            sim_time,
+            error_mean: None,
+            error_p99: None,
+            conservation: None,
+            dilation_threshold,
+            max_debt,
+            readback_ms: 0.0,
+            readback_bytes: 0,
+            force_threads: None,
+            adapter: None,
+            active_by_type: Vec::new(),
+            radial_profile: Vec::new(),
+            debt_histogram: Vec::new(),
+            interactions: Vec::new(),
+            alive: 0,
+            mergers: None,
+            substeps: None,
+            placeholder: true,
+            lagrangian_radii: None,
+        }
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string(self).expect("a MetricsRecord serializes to JSON")
+    }
+}
+
+// The CSV columns in file order, each with how a record's cell is written, so the header
+// and the rows come from the one list. Counts and other lists are `;`-separated so the row
+// keeps one column per field; energies and momenta are in scientific notation since they
+// span many decades.
+#[cfg(not(target_arch = "wasm32"))]
+const CSV_COLUMNS: &[(&str, Cell)] = &[
+    ("mode", |r| r.mode.name().to_string()),
+    ("frame", |r| r.frame.to_string()),
+    ("wall_ms", |r| format!("{:.3}", r.wall_ms)),
+    ("gpu_ms", |r| cell(r.gpu_ms, |v| format!("{:.4}", v))),
+    ("active_count", |r| r.active_count.to_string()),
+    ("stars", |r| r.stars.to_string()),
+    ("sim_time", |r| format!("{:.4}", r.sim_time)),
+    ("error_mean", |r| cell(r.error_mean, |v| format!("{:.5}", v))),
+    ("error_p99", |r| cell(r.error_p99, |v| format!("{:.5}", v))),
+    ("kinetic_energy", |r| scientific(r.conservation.map(|c| c.kinetic))),
+    ("potential_energy", |r| scientific(r.conservation.and_then(|c| c.potential))),
+    ("total_energy", |r| scientific(r.conservation.and_then(|c| c.total_energy()))),
+    ("momentum_x", |r| scientific(r.conservation.map(|c| c.momentum[0]))),
+    ("momentum_y", |r| scientific(r.conservation.map(|c| c.momentum[1]))),
+    ("momentum_z", |r| scientific(r.conservation.map(|c| c.momentum[2]))),
+    ("angular_momentum_x", |r| scientific(r.conservation.map(|c| c.angular_momentum[0]))),
+    ("angular_momentum_y", |r| scientific(r.conservation.map(|c| c.angular_momentum[1]))),
+    ("angular_momentum_z", |r| scientific(r.conservation.map(|c| c.angular_momentum[2]))),
+    ("dilation_threshold", |r| r.dilation_threshold.to_string()),
+    ("max_debt", |r| r.max_debt.to_string()),
+    ("readback_ms", |r| format!("{:.3}", r.readback_ms)),
+    ("readback_bytes", |r| r.readback_bytes.to_string()),
+    ("force_threads", |r| cell(r.force_threads, |v| v.to_string())),
+    ("adapter", |r| cell(r.adapter, |v| v.to_string())),
+    ("active_by_type", |r| list(&r.active_by_type, u32::to_string)),
+    ("radial_profile", |r| list(&r.radial_profile, u32::to_string)),
+    ("debt_histogram", |r| list(&r.debt_histogram, u32::to_string)),
+    ("interactions", |r| list(&r.interactions, f32::to_string)),
+    ("alive", |r| r.alive.to_string()),
+    ("mergers", |r| cell(r.mergers, |v| v.to_string())),
+    ("substeps", |r| cell(r.substeps, |v| v.to_string())),
+    ("placeholder", |r| r.placeholder.to_string()),
+    ("lagrangian_radii", |r| cell(r.lagrangian_radii, |radii| list(&radii, |v| format!("{:.3}", v)))),
+    ("virial_ratio", |r| cell(r.conservation.and_then(|c| c.virial_ratio()), |v| format!("{:.5}", v))),
+];
+
+#[cfg(not(target_arch = "wasm32"))]
+type Cell = fn(&MetricsRecord) -> String;
+
+// An empty cell for None.
+#[cfg(not(target_arch = "wasm32"))]
+fn cell<T>(value: Option<T>, write: impl Fn(T) -> String) -> String {
+    value.map(write).unwrap_or_default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn scientific(value: Option<f64>) -> String {
+    cell(value, |v| format!("{:.6e}", v))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn list<T>(values: &[T], write: impl Fn(&T) -> String) -> String {
+    values.iter().map(write).collect::<Vec<_>>().join(";")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct MetricsWriter {
+    out: BufWriter<File>,
+    format: MetricsFormat,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MetricsWriter {
+    pub fn create(path: &Path, format: MetricsFormat) -> io::Result<Self> {
+        Self::open(File::create(path)?, format)
+    }
+
+    // Keeps existing rows (e.g. when resuming from a checkpoint); the CSV header is only
+    // written if the file is new or empty.
+    pub fn append(path: &Path, format: MetricsFormat) -> io::Result<Self> {
+        Self::open(OpenOptions::new().create(true).append(true).open(path)?, format)
+    }
+
+    fn open(file: File, format: MetricsFormat) -> io::Result<Self> {
+        let empty = file.metadata()?.len() == 0;
+        let mut out = BufWriter::new(file);
+        if format == MetricsFormat::Csv && empty {
+            let header: Vec<&str> = CSV_COLUMNS.iter().map(|(name, _)| *name).collect();
+            writeln!(out, "{}", header.join(","))?;
+        }
+        out.flush()?;
+        Ok(Self { out, format })
+    }
+
+    // Flushed immediately so a crash mid-run still leaves every completed frame on disk.
+    pub fn write(&mut self, r: &MetricsRecord) -> io::Result<()> {
+        if self.format == MetricsFormat::Json {
+            writeln!(self.out, "{}", r.json())?;
+            return self.out.flush();
+        }
+        let row: Vec<String> = CSV_COLUMNS.iter().map(|(_, write)| write(r)).collect();
+        writeln!(self.out, "{}", row.join(","))?;
+        self.out.flush()
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::{Command, SimConfig};
+use crate::error::ChronoError;
+use crate::simulation::DilationMode;
+
+const CLUMP_STEM: &str = "clumps";
+const COMPARISON_STEM: &str = "compare";
+const DIFF_STEM: &str = "diff";
+const DUMP_STEM: &str = "dumps";
+const PROFILE_STEM: &str = "profiles";
+const RENDER_STEM: &str = "render";
+const ROTATION_STEM: &str = "rotation";
+const SCHEDULE_STEM: &str = "schedule";
+const SCREENSHOT_STEM: &str = "screenshots";
+const SWEEP_STEM: &str = "sweep";
+
+// Where frames go. Without --out-dir we keep the original flat
+// `newton_000.png` / `chrono_000.png` names in the working directory.
+#[derive(Clone, Debug)]
+pub struct OutputLayout {
+    root: Option<PathBuf>,
+    prefix: String,
+    width: usize,
+    // Of frame images: jpg with --preview, else as --image-format says
+    extension: &'static str,
+}
+
+impl OutputLayout {
+    // Creates the output directories up front so a bad path fails before any GPU work.
+    pub fn prepare(config: &SimConfig) -> Result<Self, ChronoError> {
+        let layout = Self {
+            root: config.out_dir.clone(),
+            prefix: config.prefix.clone(),
+            // --chain numbers the second pass on from the first.
+            width: frame_number_width(if config.chain.is_some() { 2 * config.frames } else { config.frames }),
+            extension: if config.preview { "jpg" } else { config.image_format.extension() },
+        };
+        if let Some(root) = &layout.root {
+            // Resuming continues into the directory the checkpoint came from.
+            if dir_has_entries(root)? && !config.force && config.resume.is_none() {
+                return Err(ChronoError::InvalidConfig(format!(
+                    "output directory {} is not empty (pass --force to overwrite)",
+                    root.display()
+                )));
+            }
+            if config.is_sweep() {
+                std::fs::create_dir_all(root.join(SWEEP_STEM))?;
+            } else if matches!(config.command, Some(Command::Render(_))) {
+                std::fs::create_dir_all(root.join(RENDER_STEM))?;
+            } else if config.view {
+                std::fs::create_dir_all(root.join(SCREENSHOT_STEM))?;
+            } else if config.mode_schedule.is_some() {
+                std::fs::create_dir_all(root.join(SCHEDULE_STEM))?;
+            } else if config.sequential || config.image_format.is_hdr() {
+                for mode in [DilationMode::Newtonian, DilationMode::Chronoturin] {
+                    std::fs::create_dir_all(root.join(mode.file_stem()))?;
+                }
+            } else {
+                std::fs::create_dir_all(root.join(COMPARISON_STEM))?;
+            }
+            if config.diff {
+                std::fs::create_dir_all(root.join(DIFF_STEM))?;
+            }
+            if config.dump_every.is_some() {
+                std::fs::create_dir_all(root.join(DUMP_STEM))?;
+            }
+            if config.profiles {
+                std::fs::create_dir_all(root.join(PROFILE_STEM))?;
+            }
+            if config.fof_every.is_some() {
+                std::fs::create_dir_all(root.join(CLUMP_STEM))?;
+            }
+            if config.kinematics {
+                std::fs::create_dir_all(root.join(ROTATION_STEM))?;
+            }
+            check_writable(root)?;
+        }
+        Ok(layout)
+    }
+
+    pub fn root(&self) -> &Path {
+        self.root.as_deref().unwrap_or(Path::new("."))
+    }
+
+    // The RunManifest of whatever wrote the directory.
+    pub fn manifest_path(&self) -> PathBuf {
+        self.root().join("run.json")
+    }
+
+    pub fn checkpoint_path(&self) -> PathBuf {
+        self.root().join("checkpoint.bin")
+    }
+
+    pub fn frame_path(&self, mode: DilationMode, frame: usize) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(mode.file_stem()).join(format!("{}_{:0w$}.{}", self.prefix, frame, self.extension, w = self.width)),
+            None => PathBuf::from(format!("{}_{:0w$}.{}", mode.file_stem(), frame, self.extension, w = self.width)),
+        }
+    }
+
+    // Side-by-side frames: `compare_000.png`, or `<root>/compare/<prefix>_000.png`.
+    pub fn comparison_path(&self, frame: usize) -> PathBuf {
+        self.stem_path(COMPARISON_STEM, frame)
+    }
+
+    // --mode-schedule frames, one sequence whatever the mode, laid out like comparison frames.
+    pub fn schedule_path(&self, frame: usize) -> PathBuf {
+        self.stem_path(SCHEDULE_STEM, frame)
+    }
+
+    // --diff error images, laid out like comparison frames.
+    pub fn diff_path(&self, frame: usize) -> PathBuf {
+        self.stem_path(DIFF_STEM, frame)
+    }
+
+    // --dump-every snapshots: `dump_newton_000.csv`, or `<root>/dumps/newton_000.csv`.
+    pub fn dump_path(&self, mode: DilationMode, frame: usize, extension: &str) -> PathBuf {
+        let name = format!("{}_{:0w$}.{}", mode.file_stem(), frame, extension, w = self.width);
+        match &self.root {
+            Some(root) => root.join(DUMP_STEM).join(name),
+            None => PathBuf::from(format!("dump_{}", name)),
+        }
+    }
+
+    // --profiles tables: `profiles_newton_000.csv`, or `<root>/profiles/newton_000.csv`.
+    pub fn profile_path(&self, mode: DilationMode, frame: usize) -> PathBuf {
+        let name = format!("{}_{:0w$}.csv", mode.file_stem(), frame, w = self.width);
+        match &self.root {
+            Some(root) => root.join(PROFILE_STEM).join(name),
+            None => PathBuf::from(format!("{}_{}", PROFILE_STEM, name)),
+        }
+    }
+
+    // --kinematics tables: `rotation_newton_000.csv`, or `<root>/rotation/newton_000.csv`.
+    pub fn rotation_path(&self, mode: DilationMode, frame: usize) -> PathBuf {
+        let name = format!("{}_{:0w$}.csv", mode.file_stem(), frame, w = self.width);
+        match &self.root {
+            Some(root) => root.join(ROTATION_STEM).join(name),
+            None => PathBuf::from(format!("{}_{}", ROTATION_STEM, name)),
+        }
+    }
+
+    // --fof-every catalogs: `clumps_newton_000.csv`, or `<root>/clumps/newton_000.csv`.
+    pub fn clump_path(&self, mode: DilationMode, frame: usize) -> PathBuf {
+        let name = format!("{}_{:0w$}.csv", mode.file_stem(), frame, w = self.width);
+        match &self.root {
+            Some(root) => root.join(CLUMP_STEM).join(name),
+            None => PathBuf::from(format!("{}_{}", CLUMP_STEM, name)),
+        }
+    }
+
+    // --sweep-save-last-frame: `sweep_<label>.png`, or `<root>/sweep/<prefix>_<label>.png`.
+    pub fn sweep_frame_path(&self, label: &str) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(SWEEP_STEM).join(format!("{}_{}.png", self.prefix, label)),
+            None => PathBuf::from(format!("{}_{}.png", SWEEP_STEM, label)),
+        }
+    }
+
+    // render frames, named after their input: `render_newton_000.png`, or `<root>/render/newton_000.png`.
+    pub fn render_path(&self, name: &str) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(RENDER_STEM).join(format!("{}.{}", name, self.extension)),
+            None => PathBuf::from(format!("{}_{}.{}", RENDER_STEM, name, self.extension)),
+        }
+    }
+
+    // --view screenshot button: `screenshot_000.png`, or `<root>/screenshots/<prefix>_000.png`.
+    pub fn screenshot_path(&self, shot: usize) -> PathBuf {
+        match &self.root {
+            Some(_) => self.stem_path(SCREENSHOT_STEM, shot),
+            None => PathBuf::from(format!("screenshot_{:0w$}.png", shot, w = self.width)),
+        }
+    }
+
+    // --track: `trajectory_<id>.csv`, in the output directory.
+    pub fn trajectory_path(&self, id: u32) -> PathBuf {
+        self.root().join(format!("trajectory_{}.csv", id))
+    }
+
+    // Sweep metrics when --metrics-out is not given.
+    pub fn sweep_metrics_path(&self) -> PathBuf {
+        self.root().join("sweep_metrics.csv")
+    }
+
+    fn stem_path(&self, stem: &str, frame: usize) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(stem).join(format!("{}_{:0w$}.{}", self.prefix, frame, self.extension, w = self.width)),
+            None => PathBuf::from(format!("{}_{:0w$}.{}", stem, frame, self.extension, w = self.width)),
+        }
+    }
+}
+
+// At least three digits, more when the frame count needs them, so names sort correctly.
+pub fn frame_number_width(frames: usize) -> usize {
+    frames.saturating_sub(1).to_string().len().max(3)
+}
+
+fn dir_has_entries(dir: &Path) -> Result<bool, ChronoError> {
+    match std::fs::read_dir(dir) {
+        Ok(mut entries) => Ok(entries.next().is_some()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn check_writable(dir: &Path) -> Result<(), ChronoError> {
+    let probe = dir.join(".chronoturin-write-test");
+    std::fs::write(&probe, b"")
+        .map_err(|e| ChronoError::InvalidConfig(format!("output directory {} is not writable: {}", dir.display(), e)))?;
+    std::fs::remove_file(&probe)?;
+    Ok(())
+}
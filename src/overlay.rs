@@ -0,0 +1,124 @@
+use image::{Rgb, RgbImage};
+
+use crate::font;
+use crate::galaxy::{is_dead, Star};
+use crate::render::{project, Camera, ColorBy, ACTIVE_COLOR, ASLEEP_COLOR};
+use crate::simulation::DilationMode;
+
+// Mode label colors, matching the glow scheme: Newtonian whites out, Chronoturin glows gold.
+pub const NEWTONIAN_LABEL: Rgb<u8> = Rgb([255, 255, 255]);
+pub const CHRONOTURIN_LABEL: Rgb<u8> = Rgb([255, 200, 50]);
+const TEXT: Rgb<u8> = Rgb([200, 200, 200]);
+// --track crosses
+pub const TRACKED_COLOR: Rgb<u8> = Rgb([255, 0, 255]);
+const CROSS_ARM: i64 = 6;
+
+pub fn mode_color(mode: DilationMode) -> Rgb<u8> {
+    match mode {
+        DilationMode::Newtonian => NEWTONIAN_LABEL,
+        DilationMode::Chronoturin => CHRONOTURIN_LABEL,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct OverlayInfo {
+    pub mode: DilationMode,
+    pub frame: usize,
+    pub sim_time: f32,
+    pub active_fraction: f32,
+}
+
+// Translucent backing strip: darken what is underneath instead of painting over it.
+fn darken(img: &mut RgbImage, x0: u32, y0: u32, width: u32, height: u32) {
+    for y in y0..(y0 + height).min(img.height()) {
+        for x in x0..(x0 + width).min(img.width()) {
+            let p = img.get_pixel_mut(x, y);
+            for c in p.0.iter_mut() {
+                *c = (*c as u16 * 2 / 5) as u8;
+            }
+        }
+    }
+}
+
+fn to_rgb(color: [f32; 3]) -> Rgb<u8> {
+    Rgb(color.map(|c| c.round().clamp(0.0, 255.0) as u8))
+}
+
+// Burns a one-line status strip into the top-left corner, away from the galaxy core.
+pub fn draw_overlay(img: &mut RgbImage, info: &OverlayInfo) {
+    let scale = (img.height() / 512).max(1);
+    let label = info.mode.name();
+    let stats = format!(" | FRAME {:03} | T {:.2} | ACTIVE {:.1}%", info.frame, info.sim_time, info.active_fraction * 100.0);
+    let pad = 3 * scale;
+    let width = pad * 2 + font::text_width(label, scale) + font::text_width(&stats, scale) + (font::GLYPH_WIDTH + 1) * scale;
+    let height = pad * 2 + font::GLYPH_HEIGHT * scale;
+
+    darken(img, 0, 0, width, height);
+    font::draw_text(img, pad, pad, label, scale, mode_color(info.mode));
+    let stats_x = pad + font::text_width(label, scale) + (font::GLYPH_WIDTH + 1) * scale;
+    font::draw_text(img, stats_x, pad, stats.trim_start(), scale, TEXT);
+}
+
+// Pixels of ramp between the legend's two values, at scale 1.
+const RAMP_WIDTH: u32 = 64;
+
+// Burns the --color-by key into the bottom-left corner: the ramp between `range`'s two
+// values, or the active and asleep swatches. Nothing for ColorBy::Type.
+pub fn draw_legend(img: &mut RgbImage, color_by: ColorBy, range: Option<(f32, f32)>) {
+    if color_by == ColorBy::Type {
+        return;
+    }
+    let scale = (img.height() / 512).max(1);
+    let (pad, gap, text_height) = (3 * scale, (font::GLYPH_WIDTH + 1) * scale, font::GLYPH_HEIGHT * scale);
+    let y = img.height().saturating_sub(pad + text_height);
+    let label = color_by.name();
+    let (low, high) = match (color_by, range) {
+        (ColorBy::Active, _) => ("ASLEEP".to_string(), "ACTIVE".to_string()),
+        (_, Some((min, max))) => (format!("{:.2}", min), format!("{:.2}", max)),
+        (_, None) => ("-".to_string(), "-".to_string()),
+    };
+    let ramp = RAMP_WIDTH * scale;
+    let width =
+        pad * 2 + font::text_width(label, scale) + gap + font::text_width(&low, scale) + gap + ramp + gap + font::text_width(&high, scale);
+    darken(img, 0, y.saturating_sub(pad), width, text_height + 2 * pad);
+
+    let mut x = pad;
+    font::draw_text(img, x, y, label, scale, TEXT);
+    x += font::text_width(label, scale) + gap;
+    font::draw_text(img, x, y, &low, scale, TEXT);
+    x += font::text_width(&low, scale) + gap;
+    for dx in 0..ramp {
+        let t = dx as f32 / (ramp - 1).max(1) as f32;
+        let color = match color_by {
+            ColorBy::Active => {
+                if t < 0.5 {
+                    ASLEEP_COLOR
+                } else {
+                    ACTIVE_COLOR
+                }
+            }
+            _ => color_by.ramp(t),
+        };
+        for dy in 0..text_height {
+            if x + dx < img.width() && y + dy < img.height() {
+                img.put_pixel(x + dx, y + dy, to_rgb(color));
+            }
+        }
+    }
+    x += ramp + gap;
+    font::draw_text(img, x, y, &high, scale, TEXT);
+}
+
+// A cross over each --track star still alive and in front of the camera, clipped at the image edges.
+pub fn draw_tracked(img: &mut RgbImage, stars: &[Star], ids: &[u32], cam: &Camera) {
+    for star in ids.iter().filter_map(|&id| stars.get(id as usize)).filter(|s| !is_dead(s)) {
+        let Some((x, y)) = project(star, cam) else { continue };
+        for d in -CROSS_ARM..=CROSS_ARM {
+            for (px, py) in [(x as i64 + d, y as i64), (x as i64, y as i64 + d)] {
+                if (0..img.width() as i64).contains(&px) && (0..img.height() as i64).contains(&py) {
+                    img.put_pixel(px as u32, py as u32, TRACKED_COLOR);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use crate::galaxy::{is_central, is_dead, type_index, Star, DEAD_FLAG, TYPE_CENTRAL};
+
+// Must match `WORKGROUP_SIZE` in packing.wgsl
+const PACK_WORKGROUP_SIZE: u32 = 256;
+// Each invocation strides over the buffer, as in reduction.rs.
+const MAX_WORKGROUPS: u32 = 1024;
+// Must match packing.wgsl
+const TYPE_MASK: u32 = 255;
+const CENTRAL_CLASS: u32 = 255;
+const ACTIVE_BIT: u32 = 256;
+const DEAD_BIT: u32 = 512;
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReadbackChoice {
+    // Every Star field, as dumps, checkpoints and the conservation checks need
+    Full,
+    // One RenderRecord per star: what drawing and diffing a frame need
+    Packed,
+}
+
+// The 16 bytes of a star that rendering uses. Matches `Record` in packing.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RenderRecord {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    // Data type in the low byte (CENTRAL_CLASS for a central mass), ACTIVE_BIT for active_flag,
+    // DEAD_BIT for a culled star
+    pub flags: u32,
+}
+
+// The records Packer::new allocates, and a packed readback maps, for `count` stars.
+pub fn record_bytes(count: u32) -> u64 {
+    count.max(1) as u64 * std::mem::size_of::<RenderRecord>() as u64
+}
+
+impl RenderRecord {
+    // What packing.wgsl writes for `star`.
+    pub fn pack(star: &Star) -> Self {
+        let class = if is_central(star) { CENTRAL_CLASS } else { (type_index(star) as u32).min(CENTRAL_CLASS - 1) };
+        let active = if star.active_flag > 0.5 {
+            ACTIVE_BIT
+        } else if is_dead(star) {
+            DEAD_BIT
+        } else {
+            0
+        };
+        Self { x: star.x, y: star.y, z: star.z, flags: class | active }
+    }
+
+    // A star that projects, renders and diffs like the packed one. Velocity, mass and
+    // time debt are not carried and come back as zero.
+    pub fn unpack(&self) -> Star {
+        let data_type = match self.flags & TYPE_MASK {
+            CENTRAL_CLASS => TYPE_CENTRAL,
+            class => class as f32,
+        };
+        let active_flag = match self.flags & (ACTIVE_BIT | DEAD_BIT) {
+            ACTIVE_BIT => 1.0,
+            DEAD_BIT => DEAD_FLAG,
+            _ => 0.0,
+        };
+        Star { x: self.x, y: self.y, z: self.z, vx: 0.0, vy: 0.0, vz: 0.0, mass: 0.0, data_type, time_debt: 0.0, active_flag }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PackParams {
+    count: u32,
+    pad: [u32; 3],
+}
+
+pub fn create_pipeline(device: &wgpu::Device) -> wgpu::ComputePipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("packing.wgsl"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("packing.wgsl").into()),
+    });
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Packing"),
+        layout: None,
+        module: &shader,
+        entry_point: "main",
+    })
+}
+
+// Packs one star buffer into RenderRecords for the caller to copy out, 40% of the bytes
+// of the stars themselves.
+pub struct Packer {
+    pipeline: Arc<wgpu::ComputePipeline>,
+    bind_group: wgpu::BindGroup,
+    records: wgpu::Buffer,
+    workgroups: u32,
+}
+
+impl Packer {
+    pub fn new(device: &wgpu::Device, pipeline: Arc<wgpu::ComputePipeline>, stars: &wgpu::Buffer, count: u32) -> Self {
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Pack Params"),
+            contents: bytemuck::bytes_of(&PackParams { count, pad: [0; 3] }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let records = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Render Records"),
+            size: record_bytes(count),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: stars.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: records.as_entire_binding() },
+            ],
+        });
+        let workgroups = count.div_ceil(PACK_WORKGROUP_SIZE).clamp(1, MAX_WORKGROUPS);
+        Self { pipeline, bind_group, records, workgroups }
+    }
+
+    pub fn pipeline(&self) -> &Arc<wgpu::ComputePipeline> {
+        &self.pipeline
+    }
+
+    pub fn records(&self) -> &wgpu::Buffer {
+        &self.records
+    }
+
+    // Records the packing pass; copy records() out after it.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Packing"), timestamp_writes: None });
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, &self.bind_group, &[]);
+        cpass.dispatch_workgroups(self.workgroups, 1, 1);
+    }
+}
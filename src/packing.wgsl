@@ -0,0 +1,48 @@
+struct Star {
+    x: f32, y: f32, z: f32,
+    vx: f32, vy: f32, vz: f32,
+    mass: f32,
+    data_type: f32,
+    time_debt: f32,
+    active_flag: f32,
+};
+
+// packing::RenderRecord
+struct Record {
+    x: f32, y: f32, z: f32,
+    flags: u32,
+};
+
+struct PackParams {
+    count: u32,
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
+};
+
+@group(0) @binding(0) var<storage, read> stars: array<Star>;
+@group(0) @binding(1) var<uniform> params: PackParams;
+@group(0) @binding(2) var<storage, read_write> records: array<Record>;
+
+const WORKGROUP_SIZE: u32 = 256u;
+// Must match packing.rs
+const CENTRAL_CLASS: u32 = 255u;
+const ACTIVE_BIT: u32 = 256u;
+const DEAD_BIT: u32 = 512u;
+
+// RenderRecord::pack for every star; each invocation strides over the buffer like reduction.wgsl.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let stride = groups.x * WORKGROUP_SIZE;
+    for (var i = global_id.x; i < params.count; i += stride) {
+        let s = stars[i];
+        // The classes render.rs tells apart: the data type, or central
+        var flags = select(min(u32(floor(max(s.data_type, 0.0) + 0.5)), CENTRAL_CLASS - 1u), CENTRAL_CLASS, s.data_type < -0.5);
+        if (s.active_flag > 0.5) {
+            flags |= ACTIVE_BIT;
+        } else if (s.active_flag < -0.5) {
+            flags |= DEAD_BIT;
+        }
+        records[i] = Record(s.x, s.y, s.z, flags);
+    }
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::galaxy::MAX_TYPES;
+
+// The colour of each data type at full strength, in 0-255 per channel. Framebuffer adds
+// it once per star (scaled by splat weight), so overlapping stars brighten as before.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    colors: [[f32; 3]; MAX_TYPES],
+    len: usize,
+}
+
+// --palette as given; SimConfig::palette resolves it against --types.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaletteChoice {
+    // The original red type A and blue type B
+    Classic,
+    // Evenly spaced along matplotlib's viridis
+    Viridis,
+    // A `type,r,g,b` CSV, read and checked when the flag is parsed
+    Custom { path: PathBuf, palette: Box<Palette> },
+}
+
+pub fn parse_palette(arg: &str) -> Result<PaletteChoice, String> {
+    match arg {
+        "classic" => Ok(PaletteChoice::Classic),
+        "viridis" => Ok(PaletteChoice::Viridis),
+        path => Ok(PaletteChoice::Custom { path: path.into(), palette: Box::new(Palette::from_csv(Path::new(path))?) }),
+    }
+}
+
+// As parse_palette reads it; a custom palette is its CSV's path.
+impl fmt::Display for PaletteChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Classic => write!(f, "classic"),
+            Self::Viridis => write!(f, "viridis"),
+            Self::Custom { path, .. } => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+// viridis at 0, 1/4, 1/2, 3/4 and 1; in between is interpolated linearly.
+const VIRIDIS: [[f32; 3]; 5] = [[68.0, 1.0, 84.0], [59.0, 82.0, 139.0], [33.0, 145.0, 140.0], [94.0, 201.0, 98.0], [253.0, 231.0, 37.0]];
+
+impl Palette {
+    pub fn classic() -> Self {
+        Self::from_colors(&[[200.0, 0.0, 0.0], [0.0, 0.0, 255.0]])
+    }
+
+    pub fn viridis(types: usize) -> Self {
+        let colors: Vec<[f32; 3]> = (0..types)
+            .map(|t| {
+                let x = if types > 1 { t as f32 / (types - 1) as f32 } else { 0.0 } * (VIRIDIS.len() - 1) as f32;
+                let i = (x as usize).min(VIRIDIS.len() - 2);
+                let frac = x - i as f32;
+                [0, 1, 2].map(|k| (VIRIDIS[i][k] + (VIRIDIS[i + 1][k] - VIRIDIS[i][k]) * frac).round())
+            })
+            .collect();
+        Self::from_colors(&colors)
+    }
+
+    fn from_colors(colors: &[[f32; 3]]) -> Self {
+        let mut padded = [[0.0; 3]; MAX_TYPES];
+        padded[..colors.len()].copy_from_slice(colors);
+        Self { colors: padded, len: colors.len() }
+    }
+
+    // One `type,r,g,b` row per data type, 0 to N - 1 in any order, with 0-255 channels.
+    // A header line starting with `type` is skipped, as are blank lines.
+    pub fn from_csv(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut colors: Vec<Option<[f32; 3]>> = Vec::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (line_no == 0 && line.starts_with("type")) {
+                continue;
+            }
+            let at = |what: String| format!("{} line {}: {}", path.display(), line_no + 1, what);
+            let values: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [t, r, g, b] = values[..] else {
+                return Err(at(format!("expected type,r,g,b, found {} columns", values.len())));
+            };
+            let t: usize = t.parse().map_err(|_| at(format!("type `{}` is not a non-negative integer", t)))?;
+            if t >= MAX_TYPES {
+                return Err(at(format!("type {} is past the maximum of {}", t, MAX_TYPES - 1)));
+            }
+            let mut rgb = [0.0; 3];
+            for (channel, value) in rgb.iter_mut().zip([r, g, b]) {
+                *channel = value.parse::<u8>().map_err(|_| at(format!("`{}` is not a 0-255 channel", value)))? as f32;
+            }
+            if colors.len() <= t {
+                colors.resize(t + 1, None);
+            }
+            if colors[t].replace(rgb).is_some() {
+                return Err(at(format!("type {} is listed twice", t)));
+            }
+        }
+        if colors.is_empty() {
+            return Err(format!("{} has no colours", path.display()));
+        }
+        let colors = colors
+            .iter()
+            .enumerate()
+            .map(|(t, c)| c.ok_or_else(|| format!("{} has no colour for type {}", path.display(), t)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_colors(&colors))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn colors(&self) -> &[[f32; 3]] {
+        &self.colors[..self.len]
+    }
+
+    // Types past the end share the last colour.
+    pub fn color(&self, index: usize) -> [f32; 3] {
+        self.colors[index.min(self.len - 1)]
+    }
+}
+
+// Matches `Palette` in points.wgsl and raster.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PaletteUniform {
+    pub colors: [[f32; 4]; MAX_TYPES],
+    pub len: u32,
+    pub pad: [u32; 3],
+}
+
+impl PaletteUniform {
+    pub fn new(palette: &Palette) -> Self {
+        let mut colors = [[0.0; 4]; MAX_TYPES];
+        for (out, [r, g, b]) in colors.iter_mut().zip(palette.colors()) {
+            *out = [*r, *g, *b, 0.0];
+        }
+        Self { colors, len: palette.len() as u32, pad: [0; 3] }
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use image::RgbImage;
+
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+
+// What a run does once the --frames-to reader goes away (--on-pipe-close).
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OnPipeClose {
+    // Stop writing frames and finish the simulation
+    Continue,
+    // Stop with ChronoError::PipeClosed
+    Abort,
+}
+
+// `--frames-to PATH|-`: every frame as raw rgb24 bytes, one after another, into a file, a FIFO or
+// stdout, for `ffmpeg -f rawvideo -pix_fmt rgb24 -s WxH -i -`.
+pub struct RawFrameWriter {
+    // None once the reader closed the pipe
+    out: Option<Box<dyn Write + Send>>,
+    target: PathBuf,
+    frames: u64,
+}
+
+impl RawFrameWriter {
+    // `-` is stdout. Opening a FIFO waits for its reader.
+    pub fn open(target: &Path) -> Result<Self, ChronoError> {
+        let out: Box<dyn Write + Send> = if is_stdout(target) {
+            Box::new(BufWriter::new(io::stdout()))
+        } else {
+            let file = OpenOptions::new().write(true).create(true).truncate(true).open(target).map_err(|e| ChronoError::Parse {
+                path: target.to_path_buf(),
+                line: 0,
+                message: e.to_string(),
+            })?;
+            Box::new(BufWriter::new(file))
+        };
+        Ok(Self { out: Some(out), target: target.to_path_buf(), frames: 0 })
+    }
+
+    // Where the frames go, as the startup line names it.
+    pub fn label(&self) -> String {
+        if is_stdout(&self.target) {
+            "stdout".into()
+        } else {
+            self.target.display().to_string()
+        }
+    }
+
+    // false once the reader has gone away; later frames are dropped.
+    pub fn write_frame(&mut self, img: &RgbImage) -> Result<bool, ChronoError> {
+        let Some(out) = self.out.as_mut() else { return Ok(false) };
+        match out.write_all(img.as_raw()) {
+            Ok(()) => {
+                self.frames += 1;
+                Ok(true)
+            }
+            Err(e) => self.closed(e).map(|_| false),
+        }
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.out.is_some()
+    }
+
+    // Flushes what is buffered; a reader gone by now only loses the tail.
+    pub fn finish(mut self) -> Result<u64, ChronoError> {
+        if let Some(mut out) = self.out.take() {
+            if let Err(e) = out.flush() {
+                self.closed(e)?;
+            }
+        }
+        Ok(self.frames)
+    }
+
+    // A closed pipe ends the output; any other failure is an error.
+    fn closed(&mut self, e: io::Error) -> Result<(), ChronoError> {
+        self.out = None;
+        match e.kind() {
+            ErrorKind::BrokenPipe => Ok(()),
+            _ => Err(ChronoError::Parse { path: self.target.clone(), line: 0, message: e.to_string() }),
+        }
+    }
+}
+
+pub fn is_stdout(target: &Path) -> bool {
+    target == Path::new("-")
+}
+
+// --frames-to, opened once the frame size is known. The size goes to stderr first, since
+// opening a FIFO waits for the reader that needs it.
+pub fn open_frames_to(config: &SimConfig, width: u32, height: u32) -> Result<Option<RawFrameWriter>, ChronoError> {
+    let Some(target) = &config.frames_to else { return Ok(None) };
+    eprintln!(
+        "Raw frames: {}x{} rgb24 at {} fps | ffmpeg -f rawvideo -pix_fmt rgb24 -s {}x{} -r {} -i - out.mp4",
+        width, height, config.fps, width, height, config.fps
+    );
+    RawFrameWriter::open(target).map(Some)
+}
+
+// Once the reader is gone, --on-pipe-close decides whether the run goes on without it.
+pub fn pipe_frame(frames_to: &mut Option<RawFrameWriter>, img: &RgbImage, on_close: OnPipeClose) -> Result<(), ChronoError> {
+    let Some(pipe) = frames_to.as_mut().filter(|p| p.is_open()) else { return Ok(()) };
+    if !pipe.write_frame(img)? {
+        if on_close == OnPipeClose::Abort {
+            return Err(ChronoError::PipeClosed { target: pipe.label(), frames: pipe.frames() });
+        }
+        tracing::warn!("The --frames-to reader closed {} after {} frames; the run goes on without it", pipe.label(), pipe.frames());
+    }
+    Ok(())
+}
+
+pub fn finish_frames_to(frames_to: Option<RawFrameWriter>) -> Result<(), ChronoError> {
+    if let Some(pipe) = frames_to {
+        let label = pipe.label();
+        tracing::info!("Wrote {} raw frames to {}", pipe.finish()?, label);
+    }
+    Ok(())
+}
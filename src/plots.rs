@@ -0,0 +1,237 @@
+use std::path::{Path, PathBuf};
+
+use image::{Rgb, RgbImage};
+use plotters::prelude::*;
+
+use crate::error::ChronoError;
+use crate::font;
+use crate::manifest::{save_png, RunManifest};
+use crate::overlay::mode_color;
+use crate::profiles::LAGRANGIAN_FRACTIONS;
+use crate::report::FrameSample;
+use crate::simulation::DilationMode;
+
+// Readable at full size in an issue comment.
+pub const PLOT_WIDTH: u32 = 1280;
+pub const PLOT_HEIGHT: u32 = 720;
+
+const BACKGROUND: Rgb<u8> = Rgb([20, 20, 24]);
+const GRID: Rgb<u8> = Rgb([55, 55, 62]);
+const AXIS: Rgb<u8> = Rgb([150, 150, 150]);
+const TEXT: Rgb<u8> = Rgb([200, 200, 200]);
+// Text is drawn with the frame overlay's bitmap font, so charts need no system fonts.
+const TEXT_SCALE: u32 = 2;
+// Plot area insets: title and axis label above, tick labels left and below.
+const TOP: u32 = 80;
+const BOTTOM: u32 = 70;
+const LEFT: u32 = 130;
+const RIGHT: u32 = 40;
+const TICKS: usize = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Series {
+    pub label: String,
+    pub color: Rgb<u8>,
+    pub points: Vec<(f64, f64)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chart {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub series: Vec<Series>,
+}
+
+// Data range plus 5% either side; data that never goes negative keeps 0 as the floor.
+// A flat series gets 10% of its value (or 1 around zero). None without finite values.
+pub fn padded_range(values: impl IntoIterator<Item = f64>) -> Option<(f64, f64)> {
+    let (min, max) = values
+        .into_iter()
+        .filter(|v| v.is_finite())
+        .fold(None, |range: Option<(f64, f64)>, v| Some(range.map_or((v, v), |(lo, hi)| (lo.min(v), hi.max(v)))))?;
+    let pad = if max > min {
+        (max - min) * 0.05
+    } else if max != 0.0 {
+        max.abs() * 0.1
+    } else {
+        1.0
+    };
+    let lo = if min >= 0.0 { (min - pad).max(0.0) } else { min - pad };
+    Some((lo, max + pad))
+}
+
+// Round-number ticks (1, 2 or 5 times a power of ten) inside [lo, hi], about `target` of them.
+pub fn nice_ticks(lo: f64, hi: f64, target: usize) -> Vec<f64> {
+    let raw = (hi - lo) / target.max(1) as f64;
+    if !(raw > 0.0 && raw.is_finite()) {
+        return vec![lo];
+    }
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].iter().map(|m| m * magnitude).find(|&s| s >= raw).unwrap_or(10.0 * magnitude);
+    let first = (lo / step).ceil() as i64;
+    let last = (hi / step + 1e-9).floor() as i64;
+    // Multiplying out each tick avoids the drift of repeated addition.
+    (first..=last).map(|i| i as f64 * step).collect()
+}
+
+// Enough decimals to tell neighbouring ticks apart; scientific for very small or large values.
+pub fn tick_label(value: f64, step: f64) -> String {
+    if value == 0.0 {
+        return "0".into();
+    }
+    if value.abs() < 1e-3 || value.abs() >= 1e6 {
+        return format!("{:.1e}", value);
+    }
+    let decimals = if step >= 1.0 { 0 } else { (-step.log10()).ceil() as usize };
+    format!("{:.*}", decimals, value)
+}
+
+pub fn render_chart(chart: &Chart) -> Result<RgbImage, ChronoError> {
+    let points = || chart.series.iter().flat_map(|s| s.points.iter());
+    let (x0, x1) = padded_range(points().map(|p| p.0)).unwrap_or((0.0, 1.0));
+    let (y0, y1) = padded_range(points().map(|p| p.1)).unwrap_or((0.0, 1.0));
+    let (x_ticks, y_ticks) = (nice_ticks(x0, x1, TICKS), nice_ticks(y0, y1, TICKS));
+    let color = |c: Rgb<u8>| RGBColor(c[0], c[1], c[2]);
+    let plot_error = |e: DrawingAreaErrorKind<_>| ChronoError::Plot(e.to_string());
+
+    let mut buffer = vec![0u8; (PLOT_WIDTH * PLOT_HEIGHT * 3) as usize];
+    // Tick positions in pixels, for the labels drawn once plotters lets go of the buffer
+    let (x_marks, y_marks);
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (PLOT_WIDTH, PLOT_HEIGHT)).into_drawing_area();
+        root.fill(&color(BACKGROUND)).map_err(plot_error)?;
+        let area = root.margin(TOP, BOTTOM, LEFT, RIGHT);
+        let mut plot = ChartBuilder::on(&area).build_cartesian_2d(x0..x1, y0..y1).map_err(plot_error)?;
+        for &x in &x_ticks {
+            plot.draw_series(LineSeries::new([(x, y0), (x, y1)], color(GRID))).map_err(plot_error)?;
+        }
+        for &y in &y_ticks {
+            plot.draw_series(LineSeries::new([(x0, y), (x1, y)], color(GRID))).map_err(plot_error)?;
+        }
+        let border = [(x0, y0), (x1, y0), (x1, y1), (x0, y1), (x0, y0)];
+        plot.draw_series(LineSeries::new(border, color(AXIS))).map_err(plot_error)?;
+        for series in &chart.series {
+            let style = color(series.color).stroke_width(2);
+            plot.draw_series(LineSeries::new(series.points.iter().copied(), style)).map_err(plot_error)?;
+        }
+        x_marks = x_ticks.iter().map(|&x| (plot.backend_coord(&(x, y0)).0, x)).collect::<Vec<_>>();
+        y_marks = y_ticks.iter().map(|&y| (plot.backend_coord(&(x0, y)).1, y)).collect::<Vec<_>>();
+        root.present().map_err(plot_error)?;
+    }
+    let mut img = RgbImage::from_raw(PLOT_WIDTH, PLOT_HEIGHT, buffer).expect("buffer is width * height * 3");
+
+    let glyph_height = font::GLYPH_HEIGHT * TEXT_SCALE;
+    let step = |ticks: &[f64]| if ticks.len() > 1 { ticks[1] - ticks[0] } else { 1.0 };
+    for (px, x) in x_marks {
+        let label = tick_label(x, step(&x_ticks));
+        let left = (px.max(0) as u32).saturating_sub(font::text_width(&label, TEXT_SCALE) / 2);
+        font::draw_text(&mut img, left, PLOT_HEIGHT - BOTTOM + 10, &label, TEXT_SCALE, TEXT);
+    }
+    for (py, y) in y_marks {
+        let label = tick_label(y, step(&y_ticks));
+        let left = (LEFT - 10).saturating_sub(font::text_width(&label, TEXT_SCALE));
+        font::draw_text(&mut img, left, (py.max(0) as u32).saturating_sub(glyph_height / 2), &label, TEXT_SCALE, TEXT);
+    }
+    let title_left = (PLOT_WIDTH - font::text_width(&chart.title, 3)) / 2;
+    font::draw_text(&mut img, title_left, 16, &chart.title, 3, TEXT);
+    font::draw_text(&mut img, LEFT, TOP - glyph_height - 10, &chart.y_label, TEXT_SCALE, TEXT);
+    let x_label_left = LEFT + (PLOT_WIDTH - LEFT - RIGHT).saturating_sub(font::text_width(&chart.x_label, TEXT_SCALE)) / 2;
+    font::draw_text(&mut img, x_label_left, PLOT_HEIGHT - glyph_height - 14, &chart.x_label, TEXT_SCALE, TEXT);
+    draw_key(&mut img, &chart.series);
+    Ok(img)
+}
+
+// A swatch and label per series in the plot area's top-right corner, on a solid backing.
+fn draw_key(img: &mut RgbImage, series: &[Series]) {
+    const SWATCH: u32 = 28;
+    let (pad, row) = (10, font::GLYPH_HEIGHT * TEXT_SCALE + 8);
+    let text = series.iter().map(|s| font::text_width(&s.label, TEXT_SCALE)).max().unwrap_or(0);
+    let (width, height) = (pad * 3 + SWATCH + text, pad * 2 + row * series.len() as u32 - 8);
+    let (x0, y0) = (PLOT_WIDTH - RIGHT - width - 12, TOP + 12);
+    fill(img, x0, y0, width, height, BACKGROUND);
+    for (i, s) in series.iter().enumerate() {
+        let y = y0 + pad + row * i as u32;
+        fill(img, x0 + pad, y + font::GLYPH_HEIGHT * TEXT_SCALE / 2 - 1, SWATCH, 3, s.color);
+        font::draw_text(img, x0 + pad * 2 + SWATCH, y, &s.label, TEXT_SCALE, TEXT);
+    }
+}
+
+fn fill(img: &mut RgbImage, x0: u32, y0: u32, width: u32, height: u32, color: Rgb<u8>) {
+    for y in y0..(y0 + height).min(img.height()) {
+        for x in x0..(x0 + width).min(img.width()) {
+            img.put_pixel(x, y, color);
+        }
+    }
+}
+
+// GPU time is drawn in a dimmer shade of the mode's wall-time color.
+fn dim(color: Rgb<u8>) -> Rgb<u8> {
+    Rgb([0, 1, 2].map(|c| ((color[c] as u16 + BACKGROUND[c] as u16) / 2) as u8))
+}
+
+// The charts --plots writes for `modes`: timings and active fraction always, energy drift and
+// the virial ratio when the conservation diagnostics measured them, and the Lagrangian radii
+// when stars came back. Frames without a value leave gaps out.
+pub fn charts(modes: &[(DilationMode, &[FrameSample])]) -> Vec<(&'static str, Chart)> {
+    let series = |label: String, color, value: &dyn Fn(&FrameSample) -> Option<f64>, samples: &[FrameSample]| Series {
+        label,
+        color,
+        points: samples.iter().filter_map(|s| Some((s.frame as f64, value(s)?))).collect(),
+    };
+    let mut timings = Vec::new();
+    let mut active = Vec::new();
+    let mut drift = Vec::new();
+    let mut radii = Vec::new();
+    let mut virial = Vec::new();
+    for &(mode, samples) in modes {
+        let c = mode_color(mode);
+        timings.push(series(format!("{} WALL", mode.name()), c, &|s| Some(s.wall_ms as f64), samples));
+        if samples.iter().any(|s| s.gpu_ms.is_some()) {
+            timings.push(series(format!("{} GPU", mode.name()), dim(c), &|s| s.gpu_ms.map(f64::from), samples));
+        }
+        active.push(series(mode.name().into(), c, &|s| Some(s.active_fraction as f64 * 100.0), samples));
+        if samples.iter().any(|s| s.energy_drift.is_some()) {
+            drift.push(series(mode.name().into(), c, &|s| s.energy_drift, samples));
+        }
+        // The half-mass radius in the mode's color, the other fractions dimmer.
+        if samples.iter().any(|s| s.lagrangian_radii.is_some()) {
+            for (i, fraction) in LAGRANGIAN_FRACTIONS.iter().enumerate() {
+                let color = if *fraction == 0.5 { c } else { dim(c) };
+                let label = format!("{} {}%", mode.name(), fraction * 100.0);
+                radii.push(series(label, color, &|s| s.lagrangian_radii.map(|r| r[i] as f64), samples));
+            }
+        }
+        if samples.iter().any(|s| s.virial_ratio.is_some()) {
+            virial.push(series(mode.name().into(), c, &|s| s.virial_ratio, samples));
+        }
+    }
+    let chart =
+        |title: &str, y_label: &str, series| Chart { title: title.into(), x_label: "FRAME".into(), y_label: y_label.into(), series };
+    let mut charts = vec![
+        ("timings.png", chart("TIME PER FRAME", "MS", timings)),
+        ("active_fraction.png", chart("ACTIVE FRACTION", "ACTIVE %", active)),
+    ];
+    if !drift.is_empty() {
+        charts.push(("energy_drift.png", chart("RELATIVE ENERGY DRIFT", "(E - E0) / |E0|", drift)));
+    }
+    if !radii.is_empty() {
+        charts.push(("lagrangian_radii.png", chart("LAGRANGIAN RADII", "RADIUS ENCLOSING MASS FRACTION", radii)));
+    }
+    if !virial.is_empty() {
+        charts.push(("virial_ratio.png", chart("VIRIAL RATIO", "2T / |W|", virial)));
+    }
+    charts
+}
+
+// Renders charts(modes) into `dir`, each with the run's manifest; returns the paths written.
+pub fn write_plots(dir: &Path, modes: &[(DilationMode, &[FrameSample])], manifest: &RunManifest) -> Result<Vec<PathBuf>, ChronoError> {
+    charts(modes)
+        .into_iter()
+        .map(|(name, chart)| {
+            let path = dir.join(name);
+            save_png(&path, &render_chart(&chart)?, Some(manifest)).map_err(|e| ChronoError::Plot(e.to_string()))?;
+            Ok(path)
+        })
+        .collect()
+}
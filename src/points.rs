@@ -0,0 +1,119 @@
+use wgpu::util::DeviceExt;
+
+use crate::palette::{Palette, PaletteUniform};
+use crate::render::Camera;
+use crate::simulation::{DilationMode, Simulation};
+
+// Matches `View` in points.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ViewUniform {
+    pub position: [f32; 4],
+    pub right: [f32; 4],
+    pub down: [f32; 4],
+    pub forward: [f32; 4],
+}
+
+impl ViewUniform {
+    pub fn new(camera: &Camera, mode: DilationMode) -> Self {
+        let (right, down, forward) = camera.axes();
+        let [x, y, z] = camera.position;
+        Self {
+            position: [x, y, z, camera.fov],
+            right: [right[0], right[1], right[2], camera.width as f32],
+            down: [down[0], down[1], down[2], camera.height as f32],
+            forward: [forward[0], forward[1], forward[2], mode.as_uniform()],
+        }
+    }
+}
+
+// Draws a simulation's stars as one-pixel points straight from its storage buffers,
+// one draw per chunk, so nothing is read back for display.
+pub struct PointRenderer {
+    pipeline: wgpu::RenderPipeline,
+    view_buffer: wgpu::Buffer,
+    draws: Vec<(wgpu::BindGroup, u32)>,
+}
+
+impl PointRenderer {
+    pub fn new(sim: &Simulation, format: wgpu::TextureFormat, palette: &Palette) -> Self {
+        let device = sim.device();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("points.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("points.wgsl").into()),
+        });
+        // Additive, like Framebuffer::accumulate: dense regions brighten.
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Star Points"),
+            layout: None,
+            vertex: wgpu::VertexState { module: &shader, entry_point: "vs_main", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState { color: additive, alpha: additive }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::PointList, ..Default::default() },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("View"),
+            size: std::mem::size_of::<ViewUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let palette = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Point Palette"),
+            contents: bytemuck::bytes_of(&PaletteUniform::new(palette)),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let layout = pipeline.get_bind_group_layout(0);
+        let draws = sim
+            .star_buffers()
+            .map(|(stars, count)| {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout: &layout,
+                    entries: &[
+                        wgpu::BindGroupEntry { binding: 0, resource: stars.as_entire_binding() },
+                        wgpu::BindGroupEntry { binding: 1, resource: view_buffer.as_entire_binding() },
+                        wgpu::BindGroupEntry { binding: 2, resource: palette.as_entire_binding() },
+                    ],
+                });
+                (bind_group, count)
+            })
+            .collect();
+        Self { pipeline, view_buffer, draws }
+    }
+
+    // Clears `target` to black and draws every star seen from `view`.
+    pub fn draw(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, view: &ViewUniform) {
+        queue.write_buffer(&self.view_buffer, 0, bytemuck::bytes_of(view));
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Star Points"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        for (bind_group, count) in &self.draws {
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..*count, 0..1);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+struct Star {
+    x: f32, y: f32, z: f32,
+    vx: f32, vy: f32, vz: f32,
+    mass: f32,
+    data_type: f32,
+    time_debt: f32,
+    active_flag: f32,
+};
+
+// render::Camera packed for the GPU; the w components carry the scalars.
+struct View {
+    position: vec4<f32>, // xyz, fov
+    right: vec4<f32>, // xyz, canvas width
+    down: vec4<f32>, // xyz, canvas height
+    forward: vec4<f32>, // xyz, 1.0 in CHRONOTURIN mode
+};
+
+// palette::PaletteUniform, in 0-255 per channel
+struct Palette {
+    colors: array<vec4<f32>, 16>,
+    len: u32,
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
+};
+
+// One chunk of the star storage the compute shader writes, drawn as-is.
+@group(0) @binding(0) var<storage, read> stars: array<Star>;
+@group(0) @binding(1) var<uniform> view: View;
+@group(0) @binding(2) var<uniform> palette: Palette;
+
+// render::NEAR_PLANE; stars closer than this are clipped.
+const NEAR_PLANE: f32 = 10.0;
+// Added per star; overlapping stars brighten like the offline framebuffer.
+const INTENSITY: f32 = 0.35;
+
+struct Point {
+    @builtin(position) clip: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> Point {
+    let star = stars[index];
+    let rel = vec3<f32>(star.x, star.y, star.z) - view.position.xyz;
+    let v = vec3<f32>(dot(rel, view.right.xyz), dot(rel, view.down.xyz), dot(rel, view.forward.xyz));
+    let scale = view.position.w / (0.5 * vec2<f32>(view.right.w, view.down.w));
+
+    // w = depth performs render::project_to_screen's perspective divide; z = depth - NEAR_PLANE
+    // lands in [0, 1) past the near plane and clips everything before it.
+    var out: Point;
+    out.clip = vec4<f32>(v.x * scale.x, -v.y * scale.y, v.z - NEAR_PLANE, v.z);
+    // A culled star (--kill-radius) lands behind the near plane, like one behind the camera.
+    if (star.active_flag < -0.5) {
+        out.clip = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+    }
+
+    // Framebuffer::tonemap's colours: each type from the palette, active stars add green
+    // (tinted gold in CHRONOTURIN mode), and the central mass is yellow.
+    if (star.data_type < -0.5) {
+        out.color = vec3<f32>(1.0, 0.94, 0.24);
+        return out;
+    }
+    let t = min(u32(floor(star.data_type + 0.5)), palette.len - 1u);
+    var color = palette.colors[t].rgb / 255.0;
+    if (star.active_flag > 0.5) {
+        color += vec3<f32>(0.2 * view.forward.w, 0.59, 0.0);
+    }
+    out.color = color * INTENSITY;
+    return out;
+}
+
+@fragment
+fn fs_main(point: Point) -> @location(0) vec4<f32> {
+    return vec4<f32>(point.color, 1.0);
+}
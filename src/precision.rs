@@ -0,0 +1,92 @@
+use crate::galaxy::Star;
+
+// How the GPU stores positions (--precision).
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Precision {
+    // One f32 per coordinate, the 40-byte Star
+    F32,
+    // Each coordinate as an unevaluated sum hi + lo of two f32s (about 48 bits of mantissa),
+    // a DfStar per star. Forces still read hi only.
+    Df64,
+}
+
+impl Precision {
+    pub fn name(self) -> &'static str {
+        match self {
+            Precision::F32 => "f32",
+            Precision::Df64 => "df64",
+        }
+    }
+
+    // Bytes of one star in the GPU buffers.
+    pub fn star_bytes(self) -> u64 {
+        match self {
+            Precision::F32 => std::mem::size_of::<Star>() as u64,
+            Precision::Df64 => std::mem::size_of::<DfStar>() as u64,
+        }
+    }
+
+    // `stars` as the buffers hold them; df64 starts every residual at 0.
+    pub fn encode(self, stars: &[Star]) -> Vec<u8> {
+        match self {
+            Precision::F32 => bytemuck::cast_slice(stars).to_vec(),
+            Precision::Df64 => {
+                let wide: Vec<DfStar> = stars.iter().map(|&star| DfStar { star, lo: [0.0; 3] }).collect();
+                bytemuck::cast_slice(&wide).to_vec()
+            }
+        }
+    }
+
+    // Appends the stars in `bytes` (as encode wrote them) to `stars`, and with df64 their
+    // residuals to `lo`.
+    pub fn decode(self, bytes: &[u8], stars: &mut Vec<Star>, lo: &mut Vec<[f32; 3]>) {
+        match self {
+            Precision::F32 => stars.extend_from_slice(bytemuck::cast_slice(bytes)),
+            Precision::Df64 => {
+                for wide in bytemuck::cast_slice::<u8, DfStar>(bytes) {
+                    stars.push(wide.star);
+                    lo.push(wide.lo);
+                }
+            }
+        }
+    }
+
+    // Rewrites a WGSL source's `struct Star` to this layout. The residuals go on the
+    // active_flag line, so compiler messages keep the file's own line numbers.
+    pub fn star_struct(self, source: &str) -> Result<String, String> {
+        let start = source.find("struct Star {").ok_or("the shader declares no struct Star")?;
+        let field = "active_flag: f32,";
+        let end = start + source[start..].find(field).ok_or("struct Star no longer ends with active_flag: f32")? + field.len();
+        Ok(match self {
+            Precision::F32 => source.to_string(),
+            Precision::Df64 => format!("{} x_lo: f32, y_lo: f32, z_lo: f32,{}", &source[..end], &source[end..]),
+        })
+    }
+
+    // star_struct, and for df64 the additions in drift swapped for compensated_add, for
+    // shader.wgsl and copies of it (--shader).
+    pub fn kernel(self, source: &str) -> Result<String, String> {
+        let mut source = self.star_struct(source)?;
+        if self == Precision::Df64 {
+            for axis in ["x", "y", "z"] {
+                let drift = format!("star.{} += star.v{} * h;", axis, axis);
+                if !source.contains(&drift) {
+                    return Err(format!("shader.wgsl no longer drifts with `{}`", drift));
+                }
+                source = source.replace(&drift, &format!("compensated_add(&star.{0}, &star.{0}_lo, star.v{0} * h);", axis));
+            }
+        }
+        Ok(source)
+    }
+}
+
+// A star as --precision df64 stores it: the position in `star` is the rounded hi part, and
+// `lo` what the rounding left over, so x = star.x + lo[0] and so on.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DfStar {
+    pub star: Star,
+    pub lo: [f32; 3],
+}
+
+const _: () = assert!(std::mem::size_of::<DfStar>() == 52 && std::mem::align_of::<DfStar>() == 4);
@@ -0,0 +1,327 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::config::SimConfig;
+use crate::galaxy::{generate_sphere, Star, DEFAULT_G, DEFAULT_SOFTENING, MAX_TYPES, TYPE_A, TYPE_B, TYPE_CENTRAL};
+
+// Must match `SAMPLES` in shader.wgsl
+const SAMPLES: f32 = 32.0;
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Preset {
+    // The original sphere, rotating about Z unless --cold-start
+    Sphere,
+    // Exponential disk in the X-Y plane on circular orbits
+    Disk,
+    // Plummer sphere with its equilibrium velocity dispersion
+    Plummer,
+    // Two Plummer spheres on approach, TYPE_A vs TYPE_B (whatever --types says)
+    Collision,
+}
+
+impl Preset {
+    // Used when --scale-radius is not given.
+    pub fn default_scale_radius(self) -> f32 {
+        match self {
+            Preset::Sphere => 300.0,
+            Preset::Disk => 100.0,
+            Preset::Plummer => 80.0,
+            Preset::Collision => 60.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PresetParams {
+    pub scale_radius: f32,
+    pub scale_height: f32,
+    pub separation: f32,
+    pub relative_velocity: f32,
+    pub cold_start: bool,
+    pub velocity_dispersion: f32,
+    // Mass of the pinned star at the origin in units of one star; 0 for none.
+    pub central_mass: f32,
+    // The shader's --g and --softening, so orbits match the force law they start in.
+    pub g_const: f32,
+    pub softening: f32,
+    // --types and --type-fractions
+    pub types: TypeMix,
+}
+
+// How the stars are split between data types: weights[t] is the fraction of type t.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TypeMix {
+    weights: [f64; MAX_TYPES],
+    count: usize,
+}
+
+impl TypeMix {
+    pub fn even(count: usize) -> Self {
+        Self::new(&vec![1.0; count])
+    }
+
+    // `fractions` need not sum to 1; they are scaled to.
+    pub fn new(fractions: &[f64]) -> Self {
+        assert!((1..=MAX_TYPES).contains(&fractions.len()), "between 1 and {} types", MAX_TYPES);
+        let total: f64 = fractions.iter().sum();
+        let mut weights = [0.0; MAX_TYPES];
+        for (weight, fraction) in weights.iter_mut().zip(fractions) {
+            *weight = fraction / total;
+        }
+        Self { weights, count: fractions.len() }
+    }
+
+    pub fn from_config(config: &SimConfig) -> Self {
+        match &config.type_fractions {
+            Some(fractions) => Self::new(fractions),
+            None => Self::even(config.types),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    // One gen_bool per type until one hits, with each type's share of what is left. Two
+    // even types make this the original `gen_bool(0.5)`, so seeds keep their galaxies.
+    pub fn pick(&self, rng: &mut StdRng) -> f32 {
+        let mut left = 1.0;
+        for (t, &weight) in self.weights[..self.count - 1].iter().enumerate() {
+            if left <= 0.0 || rng.gen_bool((weight / left).clamp(0.0, 1.0)) {
+                return t as f32;
+            }
+            left -= weight;
+        }
+        (self.count - 1) as f32
+    }
+}
+
+impl Default for TypeMix {
+    fn default() -> Self {
+        Self::even(2)
+    }
+}
+
+impl PresetParams {
+    pub fn from_config(config: &SimConfig) -> Self {
+        let scale_radius = config.scale_radius.unwrap_or(config.preset.default_scale_radius());
+        Self {
+            scale_radius,
+            scale_height: config.scale_height.unwrap_or(scale_radius * 0.1),
+            separation: config.separation,
+            relative_velocity: config.relative_velocity,
+            cold_start: config.cold_start,
+            velocity_dispersion: config.velocity_dispersion,
+            central_mass: config.central_mass.unwrap_or(0.0),
+            g_const: config.g,
+            softening: config.softening,
+            types: TypeMix::from_config(config),
+        }
+    }
+
+    // The shader sums SAMPLES pair forces of G * 500 / d^2 per star, so the whole galaxy
+    // pulls like a point mass with G*M of SAMPLES * G * 500. Circular and dispersion
+    // velocities below are scaled to that.
+    pub fn galaxy_gm(&self) -> f32 {
+        SAMPLES * self.g_const * 500.0
+    }
+
+    // G*M of the central mass as the shader applies it to a galaxy of `num_stars`.
+    fn central_gm(&self, num_stars: u32) -> f32 {
+        self.galaxy_gm() * self.central_mass / num_stars.max(1) as f32
+    }
+
+    fn soft_sq(&self) -> f32 {
+        self.softening * self.softening
+    }
+}
+
+// The default sphere, as generate_galaxy() builds it.
+impl Default for PresetParams {
+    fn default() -> Self {
+        Self {
+            scale_radius: 300.0,
+            scale_height: 30.0,
+            separation: 400.0,
+            relative_velocity: 20.0,
+            cold_start: false,
+            velocity_dispersion: 0.0,
+            central_mass: 0.0,
+            g_const: DEFAULT_G,
+            softening: DEFAULT_SOFTENING,
+            types: TypeMix::default(),
+        }
+    }
+}
+
+// Initial galaxy for --preset, seeded so both passes (and reruns) match. With a
+// central mass the pinned star comes first, followed by `num_stars` others.
+pub fn generate(preset: Preset, params: &PresetParams, num_stars: u32, seed: u64) -> Vec<Star> {
+    let stars = match preset {
+        Preset::Sphere => sphere(num_stars, seed, params),
+        Preset::Disk => disk(num_stars, seed, params),
+        Preset::Plummer => plummer(num_stars, seed, params),
+        Preset::Collision => collision(num_stars, seed, params),
+    };
+    if params.central_mass > 0.0 {
+        std::iter::once(central_star(params.central_mass)).chain(stars).collect()
+    } else {
+        stars
+    }
+}
+
+pub fn central_star(mass: f32) -> Star {
+    Star { x: 0.0, y: 0.0, z: 0.0, vx: 0.0, vy: 0.0, vz: 0.0, mass, data_type: TYPE_CENTRAL, time_debt: 0.0, active_flag: 0.0 }
+}
+
+pub fn from_config(config: &SimConfig, seed: u64) -> Vec<Star> {
+    generate(config.preset, &PresetParams::from_config(config), config.stars, seed)
+}
+
+// The original positions, then a circular speed about the Z axis from the number of stars
+// inside each radius (all masses are 1) plus any central mass, and isotropic Gaussian
+// jitter of `velocity_dispersion`. Velocities come from a separate stream so the
+// positions match the cold start exactly.
+pub fn sphere(num_stars: u32, seed: u64, params: &PresetParams) -> Vec<Star> {
+    let mut stars = generate_sphere(num_stars, seed, params.scale_radius, &params.types);
+    if !params.cold_start {
+        let mut order: Vec<(f32, usize)> = stars.iter().enumerate().map(|(i, s)| ((s.x * s.x + s.y * s.y + s.z * s.z).sqrt(), i)).collect();
+        order.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (enclosed, &(r, i)) in order.iter().enumerate() {
+            // v = omega x r with omega = sqrt(GM(<r) / r^3) along Z, softened like the shader.
+            let gm = params.galaxy_gm() * enclosed as f32 / num_stars as f32 + params.central_gm(num_stars);
+            let omega = (gm / (r * r + params.soft_sq()).powf(1.5)).sqrt();
+            let star = &mut stars[i];
+            star.vx = -omega * star.y;
+            star.vy = omega * star.x;
+        }
+    }
+    let dispersion = params.velocity_dispersion;
+    if dispersion > 0.0 {
+        let mut rng = StdRng::seed_from_u64(seed ^ VELOCITY_STREAM);
+        for star in &mut stars {
+            star.vx += dispersion * normal(&mut rng);
+            star.vy += dispersion * normal(&mut rng);
+            star.vz += dispersion * normal(&mut rng);
+        }
+    }
+    stars
+}
+
+const VELOCITY_STREAM: u64 = 0x5645_4c4f_4349_5459;
+
+// Surface density ~ exp(-R/h), Gaussian thickness, circular speed from the enclosed mass.
+pub fn disk(num_stars: u32, seed: u64, params: &PresetParams) -> Vec<Star> {
+    let (scale_length, scale_height) = (params.scale_radius, params.scale_height);
+    let central_gm = params.central_gm(num_stars);
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..num_stars)
+        .map(|_| {
+            // R * exp(-R/h) is a Gamma(2, h) distribution: sum of two exponentials.
+            let r = -scale_length * (unit_open(&mut rng) * unit_open(&mut rng)).ln();
+            let theta = rng.gen_range(0.0..std::f32::consts::TAU);
+            let (sin, cos) = theta.sin_cos();
+            let z = scale_height * normal(&mut rng);
+            let x_h = r / scale_length;
+            let enclosed = 1.0 - (1.0 + x_h) * (-x_h).exp();
+            // Softened like the shader so the center does not blow up.
+            let v = ((params.galaxy_gm() * enclosed + central_gm) * r / (r * r + params.soft_sq())).sqrt();
+            Star {
+                x: r * cos,
+                y: r * sin,
+                z,
+                vx: -v * sin,
+                vy: v * cos,
+                vz: 0.0,
+                mass: 1.0,
+                data_type: params.types.pick(&mut rng),
+                time_debt: 0.0,
+                active_flag: 0.0,
+            }
+        })
+        .collect()
+}
+
+// Velocities are drawn for the galaxy alone and scaled up to the local escape speed
+// including any central mass, which keeps the Plummer speed distribution.
+pub fn plummer(num_stars: u32, seed: u64, params: &PresetParams) -> Vec<Star> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let central_gm = params.central_gm(num_stars);
+    (0..num_stars)
+        .map(|_| {
+            let data_type = params.types.pick(&mut rng);
+            let mut star = plummer_star(&mut rng, params.scale_radius, params.galaxy_gm(), data_type);
+            if central_gm > 0.0 {
+                let a = params.scale_radius;
+                let r_sq = star.x * star.x + star.y * star.y + star.z * star.z;
+                let galaxy = params.galaxy_gm() / (r_sq + a * a).sqrt();
+                let boost = ((galaxy + central_gm / (r_sq + params.soft_sq()).sqrt()) / galaxy).sqrt();
+                star.vx *= boost;
+                star.vy *= boost;
+                star.vz *= boost;
+            }
+            star
+        })
+        .collect()
+}
+
+// Two galaxies offset along X (plus one scale radius of impact parameter along Y),
+// closing at `relative_velocity`. The first half is TYPE_A, the second TYPE_B, or TYPE_A
+// as well with a single type; --type-fractions does not apply.
+pub fn collision(num_stars: u32, seed: u64, params: &PresetParams) -> Vec<Star> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let first = num_stars / 2;
+    let second_type = if params.types.count() > 1 { TYPE_B } else { TYPE_A };
+    (0..num_stars)
+        .map(|i| {
+            let (side, data_type) = if i < first { (-1.0, TYPE_A) } else { (1.0, second_type) };
+            let gm = params.galaxy_gm() * 0.5;
+            let mut star = plummer_star(&mut rng, params.scale_radius, gm, data_type);
+            star.x += side * params.separation / 2.0;
+            star.y += side * params.scale_radius / 2.0;
+            star.vx -= side * params.relative_velocity / 2.0;
+            star
+        })
+        .collect()
+}
+
+// Aarseth, Henon & Wielen (1974) sampling of a Plummer sphere with scale radius `a`.
+fn plummer_star(rng: &mut StdRng, a: f32, gm: f32, data_type: f32) -> Star {
+    // Radius from the inverted cumulative mass, cut at 10a to drop extreme outliers.
+    let r = loop {
+        let m: f32 = unit_open(rng);
+        let r = a / (m.powf(-2.0 / 3.0) - 1.0).sqrt();
+        if r.is_finite() && r < 10.0 * a {
+            break r;
+        }
+    };
+    // Speed as a fraction q of the local escape speed, from g(q) = q^2 (1 - q^2)^3.5.
+    let q = loop {
+        let (x1, x2): (f32, f32) = (rng.gen(), rng.gen());
+        if 0.1 * x2 < x1 * x1 * (1.0 - x1 * x1).powf(3.5) {
+            break x1;
+        }
+    };
+    let v = q * (2.0 * gm).sqrt() * (r * r + a * a).powf(-0.25);
+    let [x, y, z] = isotropic(rng, r);
+    let [vx, vy, vz] = isotropic(rng, v);
+    Star { x, y, z, vx, vy, vz, mass: 1.0, data_type, time_debt: 0.0, active_flag: 0.0 }
+}
+
+fn isotropic(rng: &mut StdRng, length: f32) -> [f32; 3] {
+    let cos_phi: f32 = rng.gen_range(-1.0..1.0);
+    let sin_phi = (1.0 - cos_phi * cos_phi).sqrt();
+    let theta = rng.gen_range(0.0..std::f32::consts::TAU);
+    [length * sin_phi * theta.cos(), length * sin_phi * theta.sin(), length * cos_phi]
+}
+
+// Uniform in (0, 1], safe to take the log of.
+fn unit_open(rng: &mut StdRng) -> f32 {
+    1.0 - rng.gen::<f32>()
+}
+
+// Box-Muller standard normal.
+fn normal(rng: &mut StdRng) -> f32 {
+    let (u1, u2) = (unit_open(rng), rng.gen::<f32>());
+    (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+}
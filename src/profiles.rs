@@ -0,0 +1,296 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::config::SimConfig;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::ChronoError;
+use crate::galaxy::{is_central, Star};
+
+// Fixed-width bins over [lower, upper). Values at or past `upper` (and NaNs) all land in
+// `overflow`, so the few stars a collapse flings far out cost one counter, not a bin each.
+// Values below `lower` count in the first bin.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub lower: f32,
+    pub upper: f32,
+    pub counts: Vec<u32>,
+    pub overflow: u32,
+}
+
+impl Histogram {
+    pub fn new(lower: f32, upper: f32, bins: usize) -> Self {
+        Self { lower, upper, counts: vec![0; bins], overflow: 0 }
+    }
+
+    pub fn add(&mut self, value: f32) {
+        let bin = (value - self.lower) / self.bin_width();
+        // Compared as a float first: `as` would saturate huge values and send NaN to bin 0.
+        if bin < self.counts.len() as f32 {
+            self.counts[bin.max(0.0) as usize] += 1;
+        } else {
+            self.overflow += 1;
+        }
+    }
+
+    pub fn bin_width(&self) -> f32 {
+        (self.upper - self.lower) / self.counts.len() as f32
+    }
+
+    // Lower and upper edge of bin `i`.
+    pub fn edges(&self, i: usize) -> (f32, f32) {
+        let edge = |i: usize| self.lower + (self.upper - self.lower) * i as f32 / self.counts.len() as f32;
+        (edge(i), edge(i + 1))
+    }
+
+    // Every bin and then the overflow, as the metrics columns list them.
+    pub fn with_overflow(&self) -> Vec<u32> {
+        self.counts.iter().copied().chain([self.overflow]).collect()
+    }
+}
+
+// --profile-shells, --profile-radius and --debt-bins, resolved. Both modes of a run bin
+// with the same settings, so their profiles line up shell for shell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProfileSettings {
+    pub shells: usize,
+    pub radius: f32,
+    pub debt_bins: usize,
+}
+
+impl ProfileSettings {
+    // Without --profile-radius the shells reach four preset scale radii.
+    pub fn from_config(config: &SimConfig) -> Self {
+        let radius = config.profile_radius.unwrap_or_else(|| 4.0 * config.scale_radius.unwrap_or(config.preset.default_scale_radius()));
+        Self { shells: config.profile_shells, radius, debt_bins: config.debt_bins }
+    }
+}
+
+// One frame's profiles. Central masses are left out of both histograms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profiles {
+    pub center: [f64; 3],
+    // Stars by distance from `center`, in equal-width shells out to ProfileSettings::radius
+    pub radial: Histogram,
+    // time_debt over [0, 1); None for packed readbacks, which carry no debt
+    pub debt: Option<Histogram>,
+}
+
+impl Profiles {
+    pub fn measure(stars: &[Star], settings: &ProfileSettings, has_debt: bool) -> Self {
+        let center = center_of_mass(stars);
+        let mut radial = Histogram::new(0.0, settings.radius, settings.shells);
+        let mut debt = has_debt.then(|| Histogram::new(0.0, 1.0, settings.debt_bins));
+        for star in stars.iter().filter(|s| !is_central(s)) {
+            let d = [star.x as f64 - center[0], star.y as f64 - center[1], star.z as f64 - center[2]];
+            radial.add((d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt() as f32);
+            if let Some(debt) = &mut debt {
+                debt.add(star.time_debt);
+            }
+        }
+        Self { center, radial, debt }
+    }
+
+    // Stars per unit volume in each radial shell.
+    pub fn densities(&self) -> Vec<f64> {
+        (0..self.radial.counts.len())
+            .map(|i| {
+                let (inner, outer) = self.radial.edges(i);
+                let volume = 4.0 / 3.0 * std::f64::consts::PI * ((outer as f64).powi(3) - (inner as f64).powi(3));
+                self.radial.counts[i] as f64 / volume
+            })
+            .collect()
+    }
+}
+
+// The mass fractions whose radii the metrics log records; the middle one is the half-mass radius.
+pub const LAGRANGIAN_FRACTIONS: [f64; 5] = [0.1, 0.25, 0.5, 0.75, 0.9];
+
+// Lagrangian radii: the distances from the center of mass within which each of
+// LAGRANGIAN_FRACTIONS of the mass lies, central masses left out. The (distance, mass) list is
+// kept between frames, so sorting a large galaxy every frame does not reallocate it.
+#[derive(Clone, Debug, Default)]
+pub struct LagrangianRadii {
+    scratch: Vec<(f32, f64)>,
+}
+
+impl LagrangianRadii {
+    // The radius of the star that brings the enclosed mass up to each fraction. Packed readbacks
+    // have no masses, so every star weighs the same then. None without bodies.
+    pub fn measure(&mut self, stars: &[Star]) -> Option<[f32; 5]> {
+        let center = center_of_mass(stars);
+        let bodies = || stars.iter().filter(|s| !is_central(s));
+        let weighted = bodies().any(|s| s.mass > 0.0);
+        self.scratch.clear();
+        self.scratch.extend(bodies().map(|s| {
+            let d = [s.x as f64 - center[0], s.y as f64 - center[1], s.z as f64 - center[2]];
+            ((d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt() as f32, if weighted { s.mass as f64 } else { 1.0 })
+        }));
+        self.scratch.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        let total: f64 = self.scratch.iter().map(|&(_, mass)| mass).sum();
+        let &(outermost, _) = self.scratch.last()?;
+        let mut radii = [outermost; 5];
+        let (mut enclosed, mut next) = (0.0, 0);
+        for &(radius, mass) in &self.scratch {
+            enclosed += mass;
+            while next < radii.len() && enclosed >= LAGRANGIAN_FRACTIONS[next] * total {
+                radii[next] = radius;
+                next += 1;
+            }
+        }
+        Some(radii)
+    }
+}
+
+// Mass-weighted, or plain mean position when the masses did not come back (packed readback).
+pub(crate) fn center_of_mass(stars: &[Star]) -> [f64; 3] {
+    mass_weighted(stars, |s| [s.x, s.y, s.z])
+}
+
+// The mean of `f` over `stars`, weighted as center_of_mass weighs them.
+fn mass_weighted(stars: &[Star], f: fn(&Star) -> [f32; 3]) -> [f64; 3] {
+    let mass: f64 = stars.iter().map(|s| s.mass as f64).sum();
+    let weight = |s: &Star| if mass > 0.0 { s.mass as f64 } else { 1.0 };
+    let total: f64 = stars.iter().map(weight).sum();
+    if total == 0.0 {
+        return [0.0; 3];
+    }
+    let sum = |k: usize| stars.iter().map(|s| weight(s) * f(s)[k] as f64).sum::<f64>() / total;
+    [sum(0), sum(1), sum(2)]
+}
+
+// One annulus of a RotationCurve. Both velocities are None for an empty annulus, and a lone
+// star has no spread.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Annulus {
+    pub stars: u32,
+    // Mean velocity around the axis, positive in the sense the galaxy as a whole rotates
+    pub rotation: Option<f64>,
+    // Standard deviation of the velocities toward or away from the axis
+    pub radial_dispersion: Option<f64>,
+}
+
+// --kinematics: stars in equal-width annuli by their distance from the angular momentum axis
+// through the center of mass, out to ProfileSettings::radius; stars past it are left out, as are
+// central masses. The axis is measured every frame, so a tilted remnant is binned in its own plane.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RotationCurve {
+    pub center: [f64; 3],
+    // Unit vector along the total angular momentum about `center`; +Z when there is none
+    pub axis: [f64; 3],
+    pub radius: f32,
+    pub annuli: Vec<Annulus>,
+}
+
+impl RotationCurve {
+    // Needs velocities, so not for packed readbacks. Velocities are taken relative to the
+    // center of mass's own.
+    pub fn measure(stars: &[Star], settings: &ProfileSettings) -> Self {
+        let center = center_of_mass(stars);
+        let drift = mass_weighted(stars, |s| [s.vx, s.vy, s.vz]);
+        let relative = |s: &Star| {
+            let r = [s.x as f64 - center[0], s.y as f64 - center[1], s.z as f64 - center[2]];
+            (r, [s.vx as f64 - drift[0], s.vy as f64 - drift[1], s.vz as f64 - drift[2]])
+        };
+        let bodies = || stars.iter().filter(|s| !is_central(s));
+        let weighted = bodies().any(|s| s.mass > 0.0);
+        let momentum = bodies().fold([0.0; 3], |l, s| {
+            let ((r, v), m) = (relative(s), if weighted { s.mass as f64 } else { 1.0 });
+            let c = cross(r, v);
+            [l[0] + m * c[0], l[1] + m * c[1], l[2] + m * c[2]]
+        });
+        let axis = normalized(momentum).unwrap_or([0.0, 0.0, 1.0]);
+
+        // Welford's running mean and squared spread per annulus, so nearly empty annuli stay exact.
+        let shells = settings.shells;
+        let mut counts = vec![0u32; shells];
+        let mut rotation = vec![0.0; shells];
+        let mut radial = vec![0.0; shells];
+        let mut spread = vec![0.0; shells];
+        for (r, v) in bodies().map(relative) {
+            let height = dot(r, axis);
+            let in_plane = [r[0] - height * axis[0], r[1] - height * axis[1], r[2] - height * axis[2]];
+            let distance = dot(in_plane, in_plane).sqrt();
+            let bin = distance / settings.radius as f64 * shells as f64;
+            // A star on the axis has no direction around it.
+            let Some(outward) = normalized(in_plane).filter(|_| bin < shells as f64) else { continue };
+            let i = bin as usize;
+            let (v_around, v_out) = (dot(v, cross(axis, outward)), dot(v, outward));
+            counts[i] += 1;
+            let n = counts[i] as f64;
+            rotation[i] += (v_around - rotation[i]) / n;
+            let delta = v_out - radial[i];
+            radial[i] += delta / n;
+            spread[i] += delta * (v_out - radial[i]);
+        }
+        let annuli = (0..shells)
+            .map(|i| match counts[i] {
+                0 => Annulus::default(),
+                n => Annulus { stars: n, rotation: Some(rotation[i]), radial_dispersion: Some((spread[i] / n as f64).max(0.0).sqrt()) },
+            })
+            .collect();
+        Self { center, axis, radius: settings.radius, annuli }
+    }
+
+    // Inner and outer distance from the axis of annulus `i`.
+    pub fn edges(&self, i: usize) -> (f32, f32) {
+        let edge = |i: usize| self.radius * i as f32 / self.annuli.len() as f32;
+        (edge(i), edge(i + 1))
+    }
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalized(v: [f64; 3]) -> Option<[f64; 3]> {
+    let length = dot(v, v).sqrt();
+    (length > 0.0 && length.is_finite()).then(|| v.map(|c| c / length))
+}
+
+// --profiles: one `profile,bin,lower,upper,count,density` row per bin, radial shells first
+// and then the debt histogram. Overflow rows have bin `overflow` and an infinite upper edge;
+// density is only given for shells.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_profiles(path: &Path, profiles: &Profiles) -> Result<(), ChronoError> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "profile,bin,lower,upper,count,density")?;
+    for (i, (count, density)) in profiles.radial.counts.iter().zip(profiles.densities()).enumerate() {
+        let (lower, upper) = profiles.radial.edges(i);
+        writeln!(out, "radial,{},{},{},{},{:.6e}", i, lower, upper, count, density)?;
+    }
+    writeln!(out, "radial,overflow,{},inf,{},", profiles.radial.upper, profiles.radial.overflow)?;
+    if let Some(debt) = &profiles.debt {
+        for (i, count) in debt.counts.iter().enumerate() {
+            let (lower, upper) = debt.edges(i);
+            writeln!(out, "debt,{},{},{},{},", i, lower, upper, count)?;
+        }
+        writeln!(out, "debt,overflow,{},inf,{},", debt.upper, debt.overflow)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// --kinematics: the axis on a `# axis x,y,z` line, then one
+// `annulus,inner,outer,stars,rotation,radial_dispersion` row per annulus. Empty annuli leave
+// both velocities blank.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_rotation_curve(path: &Path, curve: &RotationCurve) -> Result<(), ChronoError> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "# axis {:.6},{:.6},{:.6}", curve.axis[0], curve.axis[1], curve.axis[2])?;
+    writeln!(out, "annulus,inner,outer,stars,rotation,radial_dispersion")?;
+    let value = |v: Option<f64>| v.map(|v| format!("{:.6e}", v)).unwrap_or_default();
+    for (i, annulus) in curve.annuli.iter().enumerate() {
+        let (inner, outer) = curve.edges(i);
+        writeln!(out, "{},{},{},{},{},{}", i, inner, outer, annulus.stars, value(annulus.rotation), value(annulus.radial_dispersion))?;
+    }
+    out.flush()?;
+    Ok(())
+}
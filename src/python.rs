@@ -0,0 +1,160 @@
+use clap::Parser;
+use numpy::{PyArray1, PyArray2, PyArray3, PyArrayMethods};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PySlice;
+
+use crate::config::SimConfig;
+use crate::dump::STAR_FIELDS;
+use crate::error::ChronoError;
+use crate::galaxy::Star;
+use crate::render::{render_frame, Camera, RenderSettings};
+use crate::simulation::{DilationMode, Simulation};
+
+pyo3::create_exception!(chronoturin, ChronoturinError, PyRuntimeError, "No adapter, a lost device or a failed readback.");
+
+// Bad flags are the caller's ValueError; everything the GPU does wrong is a ChronoturinError.
+fn py_error(error: ChronoError) -> PyErr {
+    match error {
+        ChronoError::InvalidConfig(message) => PyValueError::new_err(message),
+        error => ChronoturinError::new_err(error.to_string()),
+    }
+}
+
+fn parse_mode(mode: &str) -> PyResult<DilationMode> {
+    match mode.to_lowercase().as_str() {
+        "newtonian" | "newton" => Ok(DilationMode::Newtonian),
+        "chronoturin" | "chrono" => Ok(DilationMode::Chronoturin),
+        _ => Err(PyValueError::new_err(format!("mode must be \"newtonian\" or \"chronoturin\", not {:?}", mode))),
+    }
+}
+
+// chronoturin.Simulation: one galaxy on its own device, stepped and read back from Python.
+// Arrays come back as NumPy arrays over the readback's own allocation. The readback
+// channels are not Sync, so an instance stays on the thread that created it.
+#[pyclass(name = "Simulation", module = "chronoturin", unsendable)]
+pub struct PySimulation {
+    sim: Simulation,
+    mode: DilationMode,
+    seed: u64,
+}
+
+impl PySimulation {
+    fn check(&self) -> PyResult<()> {
+        self.sim.fault().map_or(Ok(()), |error| Err(py_error(error)))
+    }
+
+    fn read(&mut self, py: Python<'_>) -> PyResult<Vec<Star>> {
+        let sim = &mut self.sim;
+        let stars = py.detach(|| sim.read_stars());
+        self.check()?;
+        Ok(stars)
+    }
+}
+
+#[pymethods]
+impl PySimulation {
+    // `args` are any of the binary's flags (["--integrator", "leapfrog", ...]); `stars` and
+    // `seed` override theirs. Without a seed one is drawn at random, and kept in .seed.
+    #[new]
+    #[pyo3(signature = (stars=None, seed=None, mode="chronoturin", args=Vec::new()))]
+    fn new(py: Python<'_>, stars: Option<u32>, seed: Option<u64>, mode: &str, args: Vec<String>) -> PyResult<Self> {
+        let mut config = SimConfig::try_parse_from(std::iter::once("chronoturin".to_string()).chain(args))
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let mode = parse_mode(mode)?;
+        config.stars = stars.unwrap_or(config.stars);
+        let seed = seed.or(config.seed).unwrap_or_else(rand::random);
+        config.seed = Some(seed);
+        let sim = py.detach(|| Simulation::open(&config, mode)).map_err(py_error)?;
+        Ok(Self { sim, mode, seed })
+    }
+
+    // Runs `n` steps and waits for the GPU to finish them.
+    fn step(&mut self, py: Python<'_>, n: usize) -> PyResult<()> {
+        let sim = &mut self.sim;
+        py.detach(|| sim.step_and_wait(n));
+        self.check()
+    }
+
+    // An (N, 3) float32 array of x, y, z: a view of full_state()'s first three columns.
+    fn positions<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let state = self.full_state(py)?;
+        let columns = (PySlice::full(py), PySlice::new(py, 0, 3, 1));
+        Ok(state.as_any().get_item(columns)?.cast_into::<PyArray2<f32>>()?)
+    }
+
+    // An (N, 10) float32 array, one row per star with the columns of chronoturin.STAR_FIELDS.
+    // NumPy takes over the readback's Vec without copying it.
+    fn full_state<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let stars = self.read(py)?;
+        let rows = stars.len();
+        let floats: Vec<f32> = bytemuck::allocation::cast_vec(stars);
+        PyArray1::from_vec(py, floats).reshape([rows, STAR_FIELDS.len()])
+    }
+
+    // --dilation-threshold, from the next step on.
+    fn set_threshold(&mut self, threshold: f32) -> PyResult<()> {
+        if !threshold.is_finite() || threshold < 0.0 {
+            return Err(PyValueError::new_err("the threshold must be zero or positive"));
+        }
+        let max_debt = self.sim.config().max_debt;
+        self.sim.set_dilation(threshold, max_debt);
+        Ok(())
+    }
+
+    // A (height, width, 3) uint8 image of the stars as the binary draws them. `camera` is
+    // (position, target); without it, the binary's framing at this size.
+    #[pyo3(signature = (width, height, camera=None))]
+    fn render<'py>(
+        &mut self,
+        py: Python<'py>,
+        width: u32,
+        height: u32,
+        camera: Option<([f32; 3], [f32; 3])>,
+    ) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        if width == 0 || height == 0 {
+            return Err(PyValueError::new_err("width and height must be positive"));
+        }
+        let stars = self.read(py)?;
+        let config = self.sim.config();
+        let fov = config.fov * width as f32 / config.width as f32;
+        let (position, target) = camera.unwrap_or(([0.0, 0.0, config.camera_z()], [0.0; 3]));
+        let cam = Camera::look_at(position, target, [0.0, 1.0, 0.0], width, height, fov).with_projection(config.projection);
+        let settings = RenderSettings::from_config(config);
+        let is_chronoturin = self.mode == DilationMode::Chronoturin;
+        let img = py.detach(|| render_frame(&stars, &cam, &settings, is_chronoturin));
+        PyArray1::from_vec(py, img.into_raw()).reshape([height as usize, width as usize, 3])
+    }
+
+    #[getter]
+    fn stars(&self) -> u32 {
+        self.sim.star_count()
+    }
+
+    #[getter]
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // "NEWTONIAN" or "CHRONOTURIN"
+    #[getter]
+    fn mode(&self) -> &'static str {
+        self.mode.name()
+    }
+
+    #[getter]
+    fn sim_time(&self) -> f32 {
+        self.sim.sim_time()
+    }
+}
+
+// Adds Simulation, ChronoturinError and STAR_FIELDS to `module`; python/ calls this from its
+// #[pymodule].
+pub fn register(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    // Without NumPy, fail the import rather than the first call that returns an array.
+    module.py().import("numpy")?;
+    module.add_class::<PySimulation>()?;
+    module.add("ChronoturinError", module.py().get_type::<ChronoturinError>())?;
+    module.add("STAR_FIELDS", STAR_FIELDS.to_vec())?;
+    Ok(())
+}
@@ -0,0 +1,290 @@
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::Duration;
+
+use image::RgbImage;
+use wgpu::util::DeviceExt;
+
+use crate::error::ChronoError;
+use crate::galaxy::Star;
+use crate::palette::PaletteUniform;
+use crate::render::{draw_centrals, reference_depth, Camera, RenderSettings, Tonemap};
+use crate::simulation::DilationMode;
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RasterChoice {
+    // Framebuffer::accumulate over the read-back stars
+    Cpu,
+    // raster.wgsl over the star buffers, read back as one image
+    Gpu,
+}
+
+// Matches `Params` in raster.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RasterParams {
+    position: [f32; 4],
+    right: [f32; 4],
+    down: [f32; 4],
+    forward: [f32; 4],
+    exposure: f32,
+    falloff: f32,
+    splat_size: f32,
+    points: u32,
+    tonemap: u32,
+    gold: u32,
+    // RenderSettings::wrap, 0 for none
+    wrap: f32,
+    // RenderSettings::mass_weighted
+    mass_weighted: u32,
+}
+
+// One u32 per palette colour, plus the glow, for every pixel.
+pub fn accum_bytes(settings: &RenderSettings, camera: &Camera) -> u64 {
+    camera.width as u64 * camera.height as u64 * (settings.palette.len() as u64 + 1) * 4
+}
+
+// Each slot's copy of the frame, in padded rows.
+pub fn readback_bytes(camera: &Camera) -> u64 {
+    padded_row_bytes(camera.width) as u64 * camera.height as u64
+}
+const ACCUMULATE_WORKGROUP: u32 = 256;
+const RESOLVE_WORKGROUP: u32 = 16;
+
+// Compiled once and shared by forked simulations.
+pub struct RasterPipelines {
+    accumulate: wgpu::ComputePipeline,
+    resolve: wgpu::ComputePipeline,
+}
+
+impl RasterPipelines {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("raster.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("raster.wgsl").into()),
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: None,
+                module: &shader,
+                entry_point,
+            })
+        };
+        Self { accumulate: pipeline("accumulate"), resolve: pipeline("resolve") }
+    }
+}
+
+struct RasterSlot {
+    buffer: wgpu::Buffer,
+    pending: Option<Receiver<Result<(), wgpu::BufferAsyncError>>>,
+    // The view this slot's image was drawn from, for the central masses painted on top
+    camera: Camera,
+}
+
+// Draws one simulation's frames on the GPU: every star is splatted into a fixed-point
+// accumulation buffer, tonemapped into an RGBA texture, and the texture is copied into a
+// per-readback-slot buffer in the same submission as the stars.
+pub struct GpuRaster {
+    pipelines: Arc<RasterPipelines>,
+    settings: RenderSettings,
+    camera: Camera,
+    params: wgpu::Buffer,
+    accum: wgpu::Buffer,
+    texture: wgpu::Texture,
+    // One per star chunk, with its workgroup grid
+    accumulate: Vec<(wgpu::BindGroup, [u32; 2])>,
+    resolve: wgpu::BindGroup,
+    slots: Vec<RasterSlot>,
+}
+
+impl GpuRaster {
+    // `camera` fixes the image size; later cameras (set_camera) must keep it.
+    pub fn new<'a>(
+        device: &wgpu::Device,
+        pipelines: Arc<RasterPipelines>,
+        star_buffers: impl Iterator<Item = (&'a wgpu::Buffer, u32)>,
+        slot_count: usize,
+        settings: &RenderSettings,
+        camera: &Camera,
+    ) -> Result<Self, ChronoError> {
+        let limits = device.limits();
+        let accum_bytes = accum_bytes(settings, camera);
+        if camera.width.max(camera.height) > limits.max_texture_dimension_2d
+            || accum_bytes > limits.max_storage_buffer_binding_size as u64
+            || accum_bytes > limits.max_buffer_size
+        {
+            return Err(ChronoError::InvalidConfig(format!(
+                "a {}x{} frame is too large to rasterize on this device; use --raster cpu",
+                camera.width, camera.height
+            )));
+        }
+
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Raster Params"),
+            size: std::mem::size_of::<RasterParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let palette = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Raster Palette"),
+            contents: bytemuck::bytes_of(&PaletteUniform::new(&settings.palette)),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let accum = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Raster Accumulation"),
+            size: accum_bytes,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Raster Frame"),
+            size: wgpu::Extent3d { width: camera.width, height: camera.height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let layout = pipelines.accumulate.get_bind_group_layout(0);
+        let max_groups = limits.max_compute_workgroups_per_dimension;
+        let accumulate = star_buffers
+            .map(|(stars, count)| {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout: &layout,
+                    entries: &[
+                        wgpu::BindGroupEntry { binding: 0, resource: stars.as_entire_binding() },
+                        wgpu::BindGroupEntry { binding: 1, resource: params.as_entire_binding() },
+                        wgpu::BindGroupEntry { binding: 2, resource: accum.as_entire_binding() },
+                        wgpu::BindGroupEntry { binding: 4, resource: palette.as_entire_binding() },
+                    ],
+                });
+                // Folded into a second dimension once a chunk needs more workgroups than one allows.
+                let groups = count.div_ceil(ACCUMULATE_WORKGROUP);
+                let x = groups.clamp(1, max_groups);
+                (bind_group, [x, groups.div_ceil(x)])
+            })
+            .collect();
+        let resolve = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipelines.resolve.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 1, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: accum.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&texture_view) },
+                wgpu::BindGroupEntry { binding: 4, resource: palette.as_entire_binding() },
+            ],
+        });
+        let slots = (0..slot_count)
+            .map(|i| RasterSlot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Raster Readback {}", i)),
+                    size: readback_bytes(camera),
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                pending: None,
+                camera: *camera,
+            })
+            .collect();
+        Ok(Self { pipelines, settings: *settings, camera: *camera, params, accum, texture, accumulate, resolve, slots })
+    }
+
+    // The same raster for another simulation's star buffers (Simulation::fork).
+    pub fn fork<'a>(&self, device: &wgpu::Device, star_buffers: impl Iterator<Item = (&'a wgpu::Buffer, u32)>, slot_count: usize) -> Self {
+        Self::new(device, self.pipelines.clone(), star_buffers, slot_count, &self.settings, &self.camera)
+            .expect("the original raster fit the same device")
+    }
+
+    // Frames submitted from now on are drawn from `camera`.
+    pub fn set_camera(&mut self, camera: &Camera) {
+        assert_eq!((camera.width, camera.height), (self.camera.width, self.camera.height), "the raster size is fixed");
+        self.camera = *camera;
+    }
+
+    // Draws the stars as they are at this point of `encoder` into `slot`.
+    pub fn encode(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, slot: usize, mode: DilationMode) {
+        let cam = &self.camera;
+        let (right, down, forward) = cam.axes();
+        let [x, y, z] = cam.position;
+        let params = RasterParams {
+            position: [x, y, z, cam.fov],
+            right: [right[0], right[1], right[2], cam.width as f32],
+            down: [down[0], down[1], down[2], cam.height as f32],
+            forward: [forward[0], forward[1], forward[2], reference_depth(cam)],
+            exposure: self.settings.exposure,
+            falloff: self.settings.falloff,
+            splat_size: self.settings.splat_size.unwrap_or(0.0),
+            points: self.settings.splat_size.is_none() as u32,
+            tonemap: match self.settings.tonemap {
+                Tonemap::Linear => 0,
+                Tonemap::Log => 1,
+                Tonemap::Reinhard => 2,
+            },
+            gold: (mode == DilationMode::Chronoturin) as u32,
+            wrap: self.settings.wrap.unwrap_or(0.0),
+            mass_weighted: self.settings.mass_weighted as u32,
+        };
+        // One write per submission, so frames queued back to back keep their own views.
+        queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+        encoder.clear_buffer(&self.accum, 0, None);
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Raster"), timestamp_writes: None });
+            cpass.set_pipeline(&self.pipelines.accumulate);
+            for (bind_group, [x, y]) in &self.accumulate {
+                cpass.set_bind_group(0, bind_group, &[]);
+                cpass.dispatch_workgroups(*x, *y, 1);
+            }
+            cpass.set_pipeline(&self.pipelines.resolve);
+            cpass.set_bind_group(0, &self.resolve, &[]);
+            cpass.dispatch_workgroups(cam.width.div_ceil(RESOLVE_WORKGROUP), cam.height.div_ceil(RESOLVE_WORKGROUP), 1);
+        }
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.slots[slot].buffer,
+                layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(padded_row_bytes(cam.width)), rows_per_image: None },
+            },
+            self.texture.size(),
+        );
+        self.slots[slot].camera = *cam;
+    }
+
+    // Call after the submission that encode() went into.
+    pub fn map(&mut self, slot: usize) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.slots[slot].buffer.slice(..).map_async(wgpu::MapMode::Read, move |v| {
+            let _ = tx.send(v);
+        });
+        self.slots[slot].pending = Some(rx);
+    }
+
+    // The finished image of `slot`, with the central masses among `stars` painted on top;
+    // None if nothing was drawn into it, or it did not map within `timeout`. The submission
+    // must already be complete.
+    pub fn finish(&mut self, slot: usize, stars: &[Star], timeout: Duration) -> Option<RgbImage> {
+        let slot = &mut self.slots[slot];
+        let Ok(Ok(())) = slot.pending.take()?.recv_timeout(timeout) else { return None };
+        let (width, height) = (slot.camera.width, slot.camera.height);
+        let row_bytes = padded_row_bytes(width) as usize;
+        let mut img = {
+            let data = slot.buffer.slice(..).get_mapped_range();
+            RgbImage::from_fn(width, height, |x, y| {
+                let i = y as usize * row_bytes + x as usize * 4;
+                image::Rgb([data[i], data[i + 1], data[i + 2]])
+            })
+        };
+        slot.buffer.unmap();
+        draw_centrals(&mut img, stars, &slot.camera);
+        Some(img)
+    }
+}
+
+// copy_texture_to_buffer rows start on COPY_BYTES_PER_ROW_ALIGNMENT boundaries.
+fn padded_row_bytes(width: u32) -> u32 {
+    (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
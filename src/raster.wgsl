@@ -0,0 +1,166 @@
+struct Star {
+    x: f32, y: f32, z: f32,
+    vx: f32, vy: f32, vz: f32,
+    mass: f32,
+    data_type: f32,
+    time_debt: f32,
+    active_flag: f32,
+};
+
+// render::Camera and RenderSettings packed for the GPU; the w components carry the scalars.
+struct Params {
+    position: vec4<f32>, // xyz, fov
+    right: vec4<f32>, // xyz, canvas width
+    down: vec4<f32>, // xyz, canvas height
+    forward: vec4<f32>, // xyz, render::reference_depth
+    exposure: f32,
+    falloff: f32,
+    splat_size: f32,
+    // 1 plots single pixels (--point)
+    points: u32,
+    // Tonemap::Linear, Log, Reinhard
+    tonemap: u32,
+    // 1 tints the glow gold (CHRONOTURIN mode)
+    gold: u32,
+    // A periodic --boundary's side, 0 for none
+    wrap: f32,
+    // 1 scales each star by its mass (--merge-radius)
+    mass_weighted: u32,
+};
+
+// palette::PaletteUniform: RenderSettings::palette in 0-255 per channel
+struct Palette {
+    colors: array<vec4<f32>, 16>,
+    len: u32,
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
+};
+
+@group(0) @binding(0) var<storage, read> stars: array<Star>;
+@group(0) @binding(1) var<uniform> params: Params;
+// Framebuffer's per-type channels then its glow, interleaved per pixel in FIXED_SCALE units
+@group(0) @binding(2) var<storage, read_write> accum: array<atomic<u32>>;
+@group(0) @binding(3) var output: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(4) var<uniform> palette: Palette;
+
+// Matches render.rs.
+const NEAR_PLANE: f32 = 10.0;
+const SPLAT_RADIUS: f32 = 2.0;
+const MAX_SPLAT_RADIUS: f32 = 6.0;
+const LOG_WHITE: f32 = 64.0;
+// WGSL has no float atomics. 1/65536 of a star keeps the summed rounding below one 8-bit
+// step in all but a few pixels, and 65536 full-strength stars per pixel (where the u32 wraps)
+// is far past the point where every tone curve is white at sensible exposures.
+const FIXED_SCALE: f32 = 65536.0;
+
+fn add(pixel: u32, star: Star, weight: f32) {
+    let fixed = u32(weight * FIXED_SCALE + 0.5);
+    if (fixed == 0u) {
+        return;
+    }
+    let channels = palette.len + 1u;
+    // Types past the palette share its last colour, so they can share its channel too.
+    let t = min(u32(floor(star.data_type + 0.5)), palette.len - 1u);
+    atomicAdd(&accum[channels * pixel + t], fixed);
+    if (star.active_flag > 0.5) {
+        atomicAdd(&accum[channels * pixel + palette.len], fixed);
+    }
+}
+
+// Framebuffer::accumulate for one star. Central masses are painted on the CPU afterwards.
+@compute @workgroup_size(256)
+fn accumulate(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let index = global_id.x + global_id.y * groups.x * 256u;
+    if (index >= arrayLength(&stars)) {
+        return;
+    }
+    let star = stars[index];
+    // Culled stars (--kill-radius) are not drawn either.
+    if (star.data_type < -0.5 || star.active_flag < -0.5) {
+        return;
+    }
+    var p = vec3<f32>(star.x, star.y, star.z);
+    if (params.wrap > 0.0) {
+        p -= params.wrap * floor(p / params.wrap + 0.5);
+    }
+    let rel = p - params.position.xyz;
+    let depth = dot(rel, params.forward.xyz);
+    // Also false for NaN, like project_to_screen's check.
+    if (!(depth > NEAR_PLANE)) {
+        return;
+    }
+    let width = params.right.w;
+    let height = params.down.w;
+    let factor = params.position.w / depth;
+    let sx = dot(rel, params.right.xyz) * factor + width / 2.0;
+    let sy = dot(rel, params.down.xyz) * factor + height / 2.0;
+    let reference = params.forward.w;
+    let mass = select(1.0, star.mass, params.mass_weighted == 1u);
+    let weight = mass * select(pow(reference / depth, params.falloff), 1.0, params.falloff == 0.0);
+
+    if (params.points == 1u) {
+        if (sx >= 0.0 && sx < width && sy >= 0.0 && sy < height) {
+            add(u32(sy) * u32(width) + u32(sx), star, weight);
+        }
+        return;
+    }
+    // floor(x + 0.5) is Rust's f32::round for the positive radii here.
+    let radius = clamp(floor(params.splat_size * SPLAT_RADIUS * reference / depth + 0.5), 1.0, MAX_SPLAT_RADIUS);
+    let cx = floor(sx);
+    let cy = floor(sy);
+    // In float, so far-off stars are rejected before the integer casts.
+    if (!(cx + radius >= 0.0 && cy + radius >= 0.0 && cx - radius < width && cy - radius < height)) {
+        return;
+    }
+    // SplatKernels: sigma = radius / 2 and a peak of 1.
+    let sigma = max(radius / 2.0, 0.5);
+    let r = i32(radius);
+    for (var ky = -r; ky <= r; ky++) {
+        let py = i32(cy) + ky;
+        if (py < 0 || py >= i32(height)) {
+            continue;
+        }
+        for (var kx = -r; kx <= r; kx++) {
+            let px = i32(cx) + kx;
+            if (px < 0 || px >= i32(width)) {
+                continue;
+            }
+            let d2 = f32(kx * kx + ky * ky);
+            add(u32(py) * u32(width) + u32(px), star, weight * exp(-d2 / (2.0 * sigma * sigma)));
+        }
+    }
+}
+
+// Tonemap::apply and Framebuffer::tonemap's rounding to 8 bits.
+fn quantize(v: f32) -> f32 {
+    let x = max(v * params.exposure / 255.0, 0.0);
+    var mapped = x;
+    if (params.tonemap == 1u) {
+        mapped = log(1.0 + x) / log(1.0 + LOG_WHITE);
+    } else if (params.tonemap == 2u) {
+        mapped = x / (1.0 + x);
+    }
+    return floor(min(mapped, 1.0) * 255.0 + 0.5) / 255.0;
+}
+
+// Framebuffer::tonemap for one pixel: each type in its palette colour, active stars add
+// green (tinted gold in CHRONOTURIN mode).
+@compute @workgroup_size(16, 16)
+fn resolve(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let width = u32(params.right.w);
+    let height = u32(params.down.w);
+    if (global_id.x >= width || global_id.y >= height) {
+        return;
+    }
+    let pixel = global_id.y * width + global_id.x;
+    let channels = palette.len + 1u;
+    var rgb = vec3<f32>(0.0);
+    for (var t = 0u; t < palette.len; t++) {
+        rgb += f32(atomicLoad(&accum[channels * pixel + t])) / FIXED_SCALE * palette.colors[t].rgb;
+    }
+    let glow = f32(atomicLoad(&accum[channels * pixel + palette.len])) / FIXED_SCALE;
+    let gold = select(0.0, 50.0, params.gold == 1u);
+    let color = vec4<f32>(quantize(rgb.r + glow * gold), quantize(rgb.g + glow * 150.0), quantize(rgb.b), 1.0);
+    textureStore(output, vec2<i32>(global_id.xy), color);
+}
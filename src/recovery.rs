@@ -0,0 +1,249 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+use crate::galaxy::{GalaxyState, Star};
+use crate::render::Camera;
+use crate::simulation::{CompiledKernels, DilationMode, FrameData, Stepper};
+
+// Opens a fresh device and builds the stepper on it again, from the same initial galaxy.
+pub type Reopen<S> = Arc<dyn Fn() -> Result<S, ChronoError> + Send + Sync>;
+
+// How a frame in flight was submitted, to submit it again on the new device.
+#[derive(Copy, Clone)]
+struct Submitted {
+    steps: usize,
+    mode: DilationMode,
+    camera: Option<Camera>,
+    // submit_stats(): nothing to restore from comes back
+    stats: bool,
+}
+
+// Once this many frames have finished since the restart point, the next whole one copies its
+// stars as the new one. A recovery steps the frames in between again, without their stars.
+const RESTART_EVERY: usize = 8;
+
+// What a rebuilt stepper starts from.
+enum Start {
+    // Its initial galaxy, as after reset()
+    Initial,
+    // A frame that came back whole, or the last restore()
+    Frame { sim_time: f32, mode: DilationMode, stars: Vec<Star> },
+    // A packed readback, with no velocities to restore from
+    Unrestorable,
+}
+
+pub struct RecoveringTicket<T> {
+    ticket: T,
+    // Which device submitted it; after a recovery the old device's tickets stand for resubmitted frames
+    device: u32,
+}
+
+// A stepper that survives one loss of its device: when a frame comes back with DeviceLost or
+// MapFailed, it reopens the device, restores the last restart point (a whole readback at most
+// RESTART_EVERY frames back), steps again the frames since, and submits again the frames
+// that were lost, so the caller gets its frames in order as though nothing happened.
+// Only the df64 low position bits are lost: the readback does not restore them. Once the
+// recoveries are used up the fault stays on fault() for the caller.
+pub struct Recovering<S: Stepper> {
+    sim: S,
+    reopen: Reopen<S>,
+    config: SimConfig,
+    mode: DilationMode,
+    camera: Option<Camera>,
+    start: Start,
+    // Frames finished since `start`, stepped again on top of it (stats only) before a resubmission
+    since_start: Vec<Submitted>,
+    // The last --shader source reloaded, for the rebuilt stepper's kernels
+    kernels: Option<String>,
+    in_flight: VecDeque<Submitted>,
+    // Resubmitted on the current device for tickets an earlier one handed out, oldest first
+    replayed: VecDeque<S::Ticket>,
+    device: u32,
+    recoveries_left: usize,
+}
+
+impl<S: Stepper> Recovering<S> {
+    pub fn new(sim: S, config: &SimConfig, reopen: Reopen<S>) -> Self {
+        Self {
+            sim,
+            reopen,
+            config: config.clone(),
+            mode: DilationMode::Newtonian,
+            camera: None,
+            start: Start::Initial,
+            since_start: Vec::new(),
+            kernels: None,
+            in_flight: VecDeque::new(),
+            replayed: VecDeque::new(),
+            device: 0,
+            recoveries_left: 1,
+        }
+    }
+
+    // Devices reopened so far.
+    pub fn recoveries(&self) -> u32 {
+        self.device
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.sim
+    }
+
+    fn submit(sim: &mut S, frame: Submitted) -> S::Ticket {
+        sim.set_mode(frame.mode);
+        if let Some(camera) = &frame.camera {
+            sim.set_camera(camera);
+        }
+        match frame.stats {
+            true => sim.submit_stats(frame.steps),
+            false => sim.submit_frame(frame.steps),
+        }
+    }
+
+    // Rebuilds on a fresh device and resubmits `lost` and every frame still in flight, then
+    // finishes `lost` again; None if there is nothing to restore from or the device will not open.
+    fn recover(&mut self, error: &ChronoError, lost: Submitted) -> Option<FrameData> {
+        self.recoveries_left -= 1;
+        tracing::error!("{}; rebuilding the simulation on a fresh device", error);
+        if matches!(self.start, Start::Unrestorable) {
+            tracing::error!("Recovery failed: packed readbacks carry no velocities to restore from");
+            return None;
+        }
+        let mut sim = match (self.reopen)() {
+            Ok(sim) => sim,
+            Err(e) => {
+                tracing::error!("Recovery failed: {}", e);
+                return None;
+            }
+        };
+        if let Some(Err(e)) = self.kernels.as_deref().map(|source| sim.reload_kernels(source)) {
+            tracing::warn!("The rebuilt simulation keeps its built-in kernels: {}", e);
+        }
+        sim.set_dilation(self.config.dilation_threshold, self.config.max_debt);
+        sim.reset();
+        if let Start::Frame { sim_time, mode, stars } = &self.start {
+            sim.restore(GalaxyState::new(*sim_time, mode.as_uniform(), &self.config), stars);
+        }
+        for &frame in &self.since_start {
+            let ticket = Self::submit(&mut sim, frame);
+            sim.finish_frame(ticket);
+        }
+        let ticket = Self::submit(&mut sim, lost);
+        self.replayed = self.in_flight.iter().map(|&frame| Self::submit(&mut sim, frame)).collect();
+        sim.set_mode(self.mode);
+        if let Some(camera) = &self.camera {
+            sim.set_camera(camera);
+        }
+        self.sim = sim;
+        self.device += 1;
+        let frame = self.sim.finish_frame(ticket);
+        match self.sim.fault() {
+            Some(e) => tracing::error!("Recovery failed: {}", e),
+            None => tracing::warn!(
+                "Recovered on a fresh device at sim time {:.2}; {} frames resubmitted",
+                frame.sim_time,
+                1 + self.replayed.len()
+            ),
+        }
+        Some(frame)
+    }
+}
+
+impl<S: Stepper> Stepper for Recovering<S> {
+    type Ticket = RecoveringTicket<S::Ticket>;
+
+    fn fork(&self) -> Self {
+        let mut forked = Self::new(self.sim.fork(), &self.config, self.reopen.clone());
+        forked.mode = self.mode;
+        forked.camera = self.camera;
+        forked.kernels = self.kernels.clone();
+        forked
+    }
+
+    fn reset(&mut self) {
+        self.sim.reset();
+        self.start = Start::Initial;
+        self.since_start.clear();
+    }
+
+    fn restore(&mut self, state: GalaxyState, stars: &[Star]) {
+        self.sim.restore(state, stars);
+        self.start =
+            Start::Frame { sim_time: state.time_seed, mode: DilationMode::from_uniform(state.dilation_mode), stars: stars.to_vec() };
+        self.since_start.clear();
+    }
+
+    fn set_mode(&mut self, mode: DilationMode) {
+        self.mode = mode;
+        self.sim.set_mode(mode);
+    }
+
+    fn set_dilation(&mut self, dilation_threshold: f32, max_debt: f32) {
+        self.config.dilation_threshold = dilation_threshold;
+        self.config.max_debt = max_debt;
+        self.sim.set_dilation(dilation_threshold, max_debt);
+    }
+
+    fn readback_depth(&self) -> usize {
+        self.sim.readback_depth()
+    }
+
+    fn submit_frame(&mut self, steps: usize) -> Self::Ticket {
+        self.in_flight.push_back(Submitted { steps, mode: self.mode, camera: self.camera, stats: false });
+        RecoveringTicket { ticket: self.sim.submit_frame(steps), device: self.device }
+    }
+
+    fn submit_stats(&mut self, steps: usize) -> Self::Ticket {
+        self.in_flight.push_back(Submitted { steps, mode: self.mode, camera: self.camera, stats: true });
+        RecoveringTicket { ticket: self.sim.submit_stats(steps), device: self.device }
+    }
+
+    fn finish_frame(&mut self, ticket: Self::Ticket) -> FrameData {
+        let submitted = self.in_flight.pop_front().expect("finish_frame without a submitted frame");
+        let mut frame = match ticket.device == self.device {
+            true => self.sim.finish_frame(ticket.ticket),
+            false => {
+                let replayed = self.replayed.pop_front().expect("every frame in flight is resubmitted");
+                self.sim.finish_frame(replayed)
+            }
+        };
+        if let Some(error @ (ChronoError::DeviceLost(_) | ChronoError::MapFailed(_))) = self.sim.fault() {
+            if self.recoveries_left > 0 {
+                frame = self.recover(&error, submitted).unwrap_or(frame);
+            }
+        }
+        if self.sim.fault().is_none() {
+            let restarts = !submitted.stats && (frame.packed || self.since_start.len() + 1 >= RESTART_EVERY);
+            if restarts {
+                self.since_start.clear();
+                self.start = match frame.packed {
+                    true => Start::Unrestorable,
+                    false => Start::Frame { sim_time: frame.sim_time, mode: submitted.mode, stars: frame.stars.clone() },
+                };
+            } else {
+                self.since_start.push(Submitted { stats: true, ..submitted });
+            }
+        }
+        frame
+    }
+
+    fn set_camera(&mut self, camera: &Camera) {
+        self.camera = Some(*camera);
+        self.sim.set_camera(camera);
+    }
+
+    fn compile_kernels(&self, source: &str) -> Result<Option<CompiledKernels>, String> {
+        self.sim.compile_kernels(source)
+    }
+
+    fn install_kernels(&mut self, kernels: CompiledKernels) {
+        self.kernels = Some(kernels.source().to_string());
+        self.sim.install_kernels(kernels);
+    }
+
+    fn fault(&self) -> Option<ChronoError> {
+        self.sim.fault()
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+// Must match `WORKGROUP_SIZE` in reduction.wgsl
+pub const REDUCE_WORKGROUP_SIZE: u32 = 256;
+// Each invocation strides over the buffer, so big galaxies need no more workgroups than this.
+const MAX_WORKGROUPS: u32 = 1024;
+// Eight f32 sums per workgroup
+const PARTIAL_BYTES: u64 = 8 * std::mem::size_of::<f32>() as u64;
+
+// What reduction.wgsl sums over the star buffer. Every quantity fills the same eight slots.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Quantity {
+    // mass, mass * (x, y, z), mass * (vx, vy, vz), kinetic energy
+    Moments,
+    // Stars with active_flag set, in slot 0
+    ActiveCount,
+}
+
+impl Quantity {
+    // Value stored in ReduceParams::quantity
+    pub fn as_uniform(self) -> u32 {
+        match self {
+            Quantity::Moments => 0,
+            Quantity::ActiveCount => 1,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ReduceParams {
+    count: u32,
+    quantity: u32,
+    pad: [u32; 2],
+}
+
+// Totals from a Quantity::Moments reduction.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Moments {
+    pub mass: f64,
+    pub center_of_mass: [f64; 3],
+    pub momentum: [f64; 3],
+    pub kinetic: f64,
+}
+
+impl Moments {
+    pub fn from_sums(sums: &[f64; 8]) -> Self {
+        let mass = sums[0];
+        let center = |k: usize| if mass == 0.0 { 0.0 } else { sums[1 + k] / mass };
+        Self { mass, center_of_mass: [center(0), center(1), center(2)], momentum: [sums[4], sums[5], sums[6]], kinetic: sums[7] }
+    }
+
+    // Distance the center of mass has moved since `first`.
+    pub fn center_drift(&self, first: &Moments) -> f64 {
+        let d = [0, 1, 2].map(|k| self.center_of_mass[k] - first.center_of_mass[k]);
+        (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+    }
+}
+
+pub fn create_pipeline(device: &wgpu::Device) -> wgpu::ComputePipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("reduction.wgsl"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("reduction.wgsl").into()),
+    });
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("Reduction"),
+        layout: None,
+        module: &shader,
+        entry_point: "main",
+    })
+}
+
+// The partials buffer Reduction::new allocates for a buffer of `count` stars.
+pub fn partials_bytes(count: u32) -> u64 {
+    count.div_ceil(REDUCE_WORKGROUP_SIZE).clamp(1, MAX_WORKGROUPS) as u64 * PARTIAL_BYTES
+}
+
+// One reduction of one star buffer: the partials land in a small buffer that the caller
+// copies out, and sum() finishes the job on the CPU in f64.
+pub struct Reduction {
+    pipeline: Arc<wgpu::ComputePipeline>,
+    bind_group: wgpu::BindGroup,
+    partials: wgpu::Buffer,
+    workgroups: u32,
+    pub quantity: Quantity,
+}
+
+impl Reduction {
+    pub fn new(device: &wgpu::Device, pipeline: Arc<wgpu::ComputePipeline>, stars: &wgpu::Buffer, count: u32, quantity: Quantity) -> Self {
+        let workgroups = count.div_ceil(REDUCE_WORKGROUP_SIZE).clamp(1, MAX_WORKGROUPS);
+        let params = ReduceParams { count, quantity: quantity.as_uniform(), pad: [0; 2] };
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Reduce Params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let partials = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Reduce Partials"),
+            size: partials_bytes(count),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: stars.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: partials.as_entire_binding() },
+            ],
+        });
+        Self { pipeline, bind_group, partials, workgroups, quantity }
+    }
+
+    pub fn pipeline(&self) -> &Arc<wgpu::ComputePipeline> {
+        &self.pipeline
+    }
+
+    pub fn partials(&self) -> &wgpu::Buffer {
+        &self.partials
+    }
+
+    pub fn partial_bytes(&self) -> u64 {
+        self.partials.size()
+    }
+
+    // Records the reduction pass; copy partials() out after it.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Reduction"), timestamp_writes: None });
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, &self.bind_group, &[]);
+        cpass.dispatch_workgroups(self.workgroups, 1, 1);
+    }
+
+    // Adds up the per-workgroup partials read back from partials().
+    pub fn sum(bytes: &[u8]) -> [f64; 8] {
+        let mut sums = [0.0; 8];
+        for partial in bytes.chunks_exact(PARTIAL_BYTES as usize) {
+            let values: [f32; 8] = bytemuck::pod_read_unaligned(partial);
+            for (sum, v) in sums.iter_mut().zip(values) {
+                *sum += v as f64;
+            }
+        }
+        sums
+    }
+}
@@ -0,0 +1,76 @@
+struct Star {
+    x: f32, y: f32, z: f32,
+    vx: f32, vy: f32, vz: f32,
+    mass: f32,
+    data_type: f32,
+    time_debt: f32,
+    active_flag: f32,
+};
+
+struct ReduceParams {
+    count: u32,
+    quantity: u32, // 0 = MOMENTS, 1 = ACTIVE COUNT
+    pad0: u32,
+    pad1: u32,
+};
+
+@group(0) @binding(0) var<storage, read> stars: array<Star>;
+@group(0) @binding(1) var<uniform> params: ReduceParams;
+// Eight sums per workgroup, added up on the host
+@group(0) @binding(2) var<storage, read_write> partials: array<vec4<f32>>;
+
+const WORKGROUP_SIZE: u32 = 256u;
+
+var<workgroup> lo: array<vec4<f32>, 256>;
+var<workgroup> hi: array<vec4<f32>, 256>;
+
+// What one star adds to the eight sums.
+fn contribution(s: Star) -> array<vec4<f32>, 2> {
+    // A star culled past --kill-radius has left the system.
+    if (s.active_flag < -0.5) {
+        return array<vec4<f32>, 2>(vec4<f32>(0.0), vec4<f32>(0.0));
+    }
+    if (params.quantity == 1u) {
+        return array<vec4<f32>, 2>(vec4<f32>(step(0.5, s.active_flag), 0.0, 0.0, 0.0), vec4<f32>(0.0));
+    }
+    // mass, mass-weighted position | momentum, kinetic energy
+    let v = vec3<f32>(s.vx, s.vy, s.vz);
+    return array<vec4<f32>, 2>(
+        vec4<f32>(s.mass, s.mass * s.x, s.mass * s.y, s.mass * s.z),
+        vec4<f32>(s.mass * v, 0.5 * s.mass * dot(v, v)),
+    );
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn main(
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    // 1. EACH INVOCATION SUMS A GRID-STRIDED SLICE
+    var a = vec4<f32>(0.0);
+    var b = vec4<f32>(0.0);
+    let stride = groups.x * WORKGROUP_SIZE;
+    for (var i = group.x * WORKGROUP_SIZE + local; i < params.count; i += stride) {
+        let c = contribution(stars[i]);
+        a += c[0];
+        b += c[1];
+    }
+    lo[local] = a;
+    hi[local] = b;
+    workgroupBarrier();
+
+    // 2. TREE REDUCTION IN WORKGROUP MEMORY
+    for (var width = WORKGROUP_SIZE / 2u; width > 0u; width /= 2u) {
+        if (local < width) {
+            lo[local] += lo[local + width];
+            hi[local] += hi[local + width];
+        }
+        workgroupBarrier();
+    }
+
+    if (local == 0u) {
+        partials[2u * group.x] = lo[0];
+        partials[2u * group.x + 1u] = hi[0];
+    }
+}
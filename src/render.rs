@@ -0,0 +1,711 @@
+use std::f32::consts::PI;
+use std::fmt;
+
+use image::RgbImage;
+
+use crate::boundary;
+use crate::config::SimConfig;
+use crate::galaxy::{is_central, is_dead, type_index, Star};
+use crate::hdr::HdrImage;
+use crate::palette::Palette;
+
+// Stars closer to the camera than this (in view space) are dropped.
+pub const NEAR_PLANE: f32 = 10.0;
+
+// Pinhole camera looking from `position` at `target`. The view basis is cached so
+// projecting a star is three dot products and a divide.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    pub width: u32,
+    pub height: u32,
+    pub fov: f32,
+    pub projection: Projection,
+    right: [f32; 3],
+    down: [f32; 3],
+    forward: [f32; 3],
+}
+
+impl Camera {
+    pub fn look_at(position: [f32; 3], target: [f32; 3], up: [f32; 3], width: u32, height: u32, fov: f32) -> Self {
+        let forward = normalize(sub(target, position));
+        let right = normalize(cross(up, forward));
+        // Image rows grow along world +Y when looking down +Z, matching the original renderer.
+        let down = cross(forward, right);
+        Self { position, target, up, width, height, fov, projection: Projection::Planar, right, down, forward }
+    }
+
+    // The original fixed framing: on the -Z axis looking at the origin.
+    pub fn from_config(config: &SimConfig) -> Self {
+        let (width, height, fov) = config.frame_size();
+        Self::look_at([0.0, 0.0, config.camera_z()], [0.0; 3], [0.0, 1.0, 0.0], width, height, fov).with_projection(config.projection)
+    }
+
+    pub fn with_projection(self, projection: Projection) -> Self {
+        Self { projection, ..self }
+    }
+
+    // Rotates the position around the Y axis through the target by `degrees`.
+    pub fn orbited(&self, degrees: f32) -> Self {
+        if degrees == 0.0 {
+            return *self;
+        }
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let [x, y, z] = sub(self.position, self.target);
+        let rotated = [x * cos + z * sin, y, z * cos - x * sin];
+        let position = [rotated[0] + self.target[0], rotated[1] + self.target[1], rotated[2] + self.target[2]];
+        self.moved_to(position)
+    }
+
+    // --stereo: the left and right eyes, `separation` apart along the view's right axis,
+    // both still looking at the target so it sits at screen depth.
+    pub fn eyes(&self, separation: f32) -> (Self, Self) {
+        let offset = |sign: f32| std::array::from_fn(|k| self.position[k] + sign * separation / 2.0 * self.right[k]);
+        (self.moved_to(offset(-1.0)), self.moved_to(offset(1.0)))
+    }
+
+    fn moved_to(&self, position: [f32; 3]) -> Self {
+        Self::look_at(position, self.target, self.up, self.width, self.height, self.fov).with_projection(self.projection)
+    }
+
+    // (right, down, forward): the view-space axes to_view() projects onto.
+    pub fn axes(&self) -> ([f32; 3], [f32; 3], [f32; 3]) {
+        (self.right, self.down, self.forward)
+    }
+
+    // World position -> (view x, view y, depth along the view axis).
+    pub fn to_view(&self, p: [f32; 3]) -> [f32; 3] {
+        let rel = sub(p, self.position);
+        [dot(rel, self.right), dot(rel, self.down), dot(rel, self.forward)]
+    }
+}
+
+// View transform followed by the perspective divide onto the canvas.
+// Returns None for stars on or behind the near plane, off-screen, or NaN.
+pub fn project(star: &Star, cam: &Camera) -> Option<(u32, u32)> {
+    project_with_depth(star, cam).map(|(x, y, _)| (x, y))
+}
+
+// Like project(), but also returns the view-space depth for falloff weighting.
+pub fn project_with_depth(star: &Star, cam: &Camera) -> Option<(u32, u32, f32)> {
+    let (screen_x, screen_y, depth) = project_to_screen(star, cam)?;
+    if (0.0..cam.width as f32).contains(&screen_x) && (0.0..cam.height as f32).contains(&screen_y) {
+        Some((screen_x as u32, screen_y as u32, depth))
+    } else {
+        None
+    }
+}
+
+// Unclipped screen position and depth; only the near plane and NaN are rejected.
+// Stays in float so far-off stars become inf instead of wrapping when cast to pixels.
+pub fn project_to_screen(star: &Star, cam: &Camera) -> Option<(f32, f32, f32)> {
+    let [vx, vy, depth] = cam.to_view([star.x, star.y, star.z]);
+    if cam.projection == Projection::Equirect {
+        return equirect(vx, vy, depth, cam);
+    }
+    if depth.is_nan() || depth <= NEAR_PLANE {
+        return None;
+    }
+    let factor = cam.fov / depth;
+    let (x, y) = (vx * factor + cam.width as f32 / 2.0, vy * factor + cam.height as f32 / 2.0);
+    // A view transform that overflowed divides inf by inf, which is no position at all.
+    if x.is_nan() || y.is_nan() {
+        return None;
+    }
+    Some((x, y, depth))
+}
+
+// The direction of view-space (vx, vy, vz) on the whole-sphere canvas: longitude across,
+// 0 straight ahead at the centre and ±180° behind at the edges; latitude down, the zenith
+// on the top row. The "depth" is the distance, so every star nearer than the near plane
+// is dropped whichever way it lies.
+fn equirect(vx: f32, vy: f32, vz: f32, cam: &Camera) -> Option<(f32, f32, f32)> {
+    let distance = (vx * vx + vy * vy + vz * vz).sqrt();
+    if distance.is_nan() || distance <= NEAR_PLANE || !distance.is_finite() {
+        return None;
+    }
+    let (width, height) = (cam.width as f32, cam.height as f32);
+    let longitude = vx.atan2(vz);
+    let latitude = (-vy).atan2((vx * vx + vz * vz).sqrt());
+    // +180° lands on the left edge with -180°, and the nadir on the last row, not past either.
+    let mut x = (longitude / (2.0 * PI) + 0.5) * width;
+    if x >= width {
+        x -= width;
+    }
+    let y = ((0.5 - latitude / PI) * height).min(height * (1.0 - f32::EPSILON));
+    Some((x, y, distance))
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = dot(v, v).sqrt();
+    [v[0] / len, v[1] / len, v[2] / len]
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Projection {
+    // A pinhole image of what lies ahead, --fov pixels per unit of view slope
+    Planar,
+    // Every direction around the camera on a 2:1 canvas, for 360° players
+    Equirect,
+}
+
+// --stereo: how far apart the two eyes of an anaglyph sit, in world units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stereo {
+    pub separation: f32,
+}
+
+pub const DEFAULT_SEPARATION: f32 = 10.0;
+
+pub fn parse_stereo(arg: &str) -> Result<Stereo, String> {
+    let (kind, options) = match arg.split_once(':') {
+        Some((kind, options)) => (kind, Some(options)),
+        None => (arg, None),
+    };
+    if kind != "anaglyph" {
+        return Err(format!("unknown stereo mode `{}`; expected anaglyph or anaglyph:separation=S", kind));
+    }
+    let separation = match options.map(|o| o.split_once('=')) {
+        None => DEFAULT_SEPARATION,
+        Some(Some(("separation", value))) => match value.trim().parse::<f32>() {
+            Ok(v) if v.is_finite() && v > 0.0 => v,
+            _ => return Err(format!("the anaglyph separation must be a positive number, not `{}`", value)),
+        },
+        Some(_) => return Err(format!("unknown anaglyph option `{}`; expected separation=S", options.unwrap_or_default())),
+    };
+    Ok(Stereo { separation })
+}
+
+// As parse_stereo reads it, so --print-config round-trips.
+impl fmt::Display for Stereo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "anaglyph:separation={}", self.separation)
+    }
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tonemap {
+    Linear,
+    Log,
+    Reinhard,
+}
+
+// Linear values above this map to white under the log curve.
+const LOG_WHITE: f32 = 64.0;
+
+impl Tonemap {
+    // `x` is exposure-scaled brightness where 1.0 is one full-strength star.
+    pub fn apply(self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        match self {
+            Tonemap::Linear => x,
+            Tonemap::Log => x.ln_1p() / LOG_WHITE.ln_1p(),
+            Tonemap::Reinhard => x / (1.0 + x),
+        }
+        .min(1.0)
+    }
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorBy {
+    // The palette colour of each data type, with the active glow on top
+    Type,
+    // |v| on a blue-to-red ramp
+    Speed,
+    // time_debt on a black-to-gold ramp
+    Debt,
+    // White for active stars, grey for sleeping ones
+    Active,
+}
+
+const SPEED_RAMP: [[f32; 3]; 2] = [[0.0, 0.0, 255.0], [255.0, 0.0, 0.0]];
+const DEBT_RAMP: [[f32; 3]; 2] = [[0.0, 0.0, 0.0], [255.0, 200.0, 50.0]];
+pub const ACTIVE_COLOR: [f32; 3] = [255.0, 255.0, 255.0];
+pub const ASLEEP_COLOR: [f32; 3] = [90.0, 90.0, 90.0];
+
+impl ColorBy {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorBy::Type => "TYPE",
+            ColorBy::Speed => "SPEED",
+            ColorBy::Debt => "DEBT",
+            ColorBy::Active => "ACTIVE",
+        }
+    }
+
+    // What the ramp maps, for the modes that have one.
+    pub fn value(self, star: &Star) -> Option<f32> {
+        match self {
+            ColorBy::Speed => Some(dot([star.vx, star.vy, star.vz], [star.vx, star.vy, star.vz]).sqrt()),
+            ColorBy::Debt => Some(star.time_debt),
+            ColorBy::Type | ColorBy::Active => None,
+        }
+    }
+
+    // The ramp colour at `t` in [0, 1], in palette units.
+    pub fn ramp(self, t: f32) -> [f32; 3] {
+        let [lo, hi] = if self == ColorBy::Debt { DEBT_RAMP } else { SPEED_RAMP };
+        [0, 1, 2].map(|k| lo[k] + (hi[k] - lo[k]) * t.clamp(0.0, 1.0))
+    }
+}
+
+// --fof-color: hues a golden angle apart, so neighbouring group IDs never look alike;
+// stars in no group are grey.
+pub fn group_color(group: Option<u32>) -> [f32; 3] {
+    let Some(group) = group else { return ASLEEP_COLOR };
+    let hue = (group as f32 * 137.508).rem_euclid(360.0) / 60.0;
+    let x = 1.0 - (hue.rem_euclid(2.0) - 1.0).abs();
+    let [r, g, b] = match hue as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    };
+    [r, g, b].map(|c| c * 255.0)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderSettings {
+    pub tonemap: Tonemap,
+    pub exposure: f32,
+    // Contributions scale with (reference depth / depth)^falloff; 0 disables it.
+    pub falloff: f32,
+    // Gaussian splat radius multiplier; None plots single pixels.
+    pub splat_size: Option<f32>,
+    pub palette: Palette,
+    pub color_by: ColorBy,
+    // --color-min and --color-max; an open end follows the frame's own extreme.
+    pub color_min: Option<f32>,
+    pub color_max: Option<f32>,
+    // A periodic --boundary's side: stars are drawn wrapped into the cube, since one that
+    // has not moved since it left (asleep, or fresh from the initial conditions) is not yet.
+    pub wrap: Option<f32>,
+    // --merge-radius: each star adds in proportion to its mass, so merged stars stand out
+    pub mass_weighted: bool,
+    // --stereo: drawn once per eye and combined into a red/cyan anaglyph
+    pub stereo: Option<Stereo>,
+}
+
+impl RenderSettings {
+    pub fn from_config(config: &SimConfig) -> Self {
+        let splat_size = if config.point || config.preview { None } else { Some(config.splat_size) };
+        Self {
+            tonemap: config.tonemap,
+            exposure: config.exposure,
+            falloff: config.falloff,
+            splat_size,
+            palette: config.palette(),
+            color_by: config.color_by,
+            color_min: config.color_min,
+            color_max: config.color_max,
+            wrap: config.boundary.side(),
+            mass_weighted: config.merge_radius > 0.0,
+            stereo: config.stereo,
+        }
+    }
+
+    // Saturating per-star additions with no depth weighting: the original look.
+    pub fn legacy() -> Self {
+        Self {
+            tonemap: Tonemap::Linear,
+            exposure: 1.0,
+            falloff: 0.0,
+            splat_size: None,
+            palette: Palette::classic(),
+            color_by: ColorBy::Type,
+            color_min: None,
+            color_max: None,
+            wrap: None,
+            mass_weighted: false,
+            stereo: None,
+        }
+    }
+
+    // The (min, max) the ramp spans over `stars`, central masses aside; None without a ramp.
+    pub fn color_range(&self, stars: &[Star]) -> Option<(f32, f32)> {
+        let values = || stars.iter().filter(|s| !is_central(s)).filter_map(|s| self.color_by.value(s)).filter(|v| v.is_finite());
+        values().next()?;
+        let min = self.color_min.unwrap_or_else(|| values().fold(f32::MAX, f32::min));
+        let max = self.color_max.unwrap_or_else(|| values().fold(f32::MIN, f32::max));
+        Some((min, max))
+    }
+
+    // The colour `star` adds in place of its type channel, or None for ColorBy::Type.
+    fn tint(&self, star: &Star, range: Option<(f32, f32)>) -> Option<[f32; 3]> {
+        match (self.color_by, range) {
+            (ColorBy::Type, _) => None,
+            (ColorBy::Active, _) => Some(if star.active_flag > 0.5 { ACTIVE_COLOR } else { ASLEEP_COLOR }),
+            (by, Some((min, max))) => {
+                let value = by.value(star).unwrap_or(min);
+                Some(by.ramp(if max > min { (value - min) / (max - min) } else { 0.0 }))
+            }
+            (by, None) => Some(by.ramp(0.0)),
+        }
+    }
+}
+
+// Floating-point accumulation buffer. Each data type and the active glow are kept as
+// separate channels and only mixed into RGB, through the palette, when tonemapping.
+// Other --color-by modes add their colours straight into `tinted` and skip the glow.
+// Central masses are kept aside as (screen x, screen y, radius) and painted on top.
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    // One channel per data type seen so far
+    pub types: Vec<Vec<f32>>,
+    pub glow: Vec<f32>,
+    // Empty until a tinted star lands
+    pub tinted: Vec<[f32; 3]>,
+    pub central: Vec<(f32, f32, f32)>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = width as usize * height as usize;
+        Self { width, height, types: Vec::new(), glow: vec![0.0; len], tinted: Vec::new(), central: Vec::new() }
+    }
+
+    pub fn accumulate(&mut self, stars: &[Star], cam: &Camera, settings: &RenderSettings) {
+        self.accumulate_layer(stars, cam, settings, None, None);
+    }
+
+    // --fof-color: each star in its group's colour (group_color) instead of as --color-by says.
+    // `labels` is Clumps::labels, one per star.
+    pub fn accumulate_groups(&mut self, stars: &[Star], cam: &Camera, settings: &RenderSettings, labels: &[Option<u32>]) {
+        self.accumulate_layer(stars, cam, settings, None, Some(labels));
+    }
+
+    // An earlier sub-step under the frame (--trails) at `alpha` of full brightness. Its central
+    // masses and culled stars are left out; the frame itself draws the centrals once.
+    pub fn accumulate_trail(&mut self, stars: &[Star], cam: &Camera, settings: &RenderSettings, alpha: f32) {
+        self.accumulate_layer(stars, cam, settings, Some(alpha), None);
+    }
+
+    // --trails: a frame's earlier sub-steps, oldest first, each `decay` times as bright as
+    // the one after it and the last of them `decay` times the frame's own brightness.
+    pub fn accumulate_trails(&mut self, trails: &[Vec<Star>], cam: &Camera, settings: &RenderSettings, decay: f32) {
+        for (k, trail) in trails.iter().enumerate() {
+            self.accumulate_trail(trail, cam, settings, decay.powi((trails.len() - k) as i32));
+        }
+    }
+
+    fn accumulate_layer(
+        &mut self,
+        stars: &[Star],
+        cam: &Camera,
+        settings: &RenderSettings,
+        trail: Option<f32>,
+        groups: Option<&[Option<u32>]>,
+    ) {
+        let reference = reference_depth(cam);
+        let kernels = settings.splat_size.map(|_| SplatKernels::new());
+        let range = settings.color_range(stars);
+        // Splats across the ±180° seam of an equirect canvas carry on at the other edge.
+        let wraps = cam.projection == Projection::Equirect;
+        let width = self.width as i64;
+        for (i, star) in stars.iter().enumerate() {
+            let wrapped = settings.wrap.map(|side| boundary::wrap(star, side));
+            let star = wrapped.as_ref().unwrap_or(star);
+            if trail.is_some() && (is_central(star) || is_dead(star)) {
+                continue;
+            }
+            if is_central(star) {
+                self.central.extend(central_splat(star, cam));
+                continue;
+            }
+            let tint = match groups {
+                Some(labels) => Some(group_color(labels[i])),
+                None => settings.tint(star, range),
+            };
+            let mass = if settings.mass_weighted { star.mass } else { 1.0 } * trail.unwrap_or(1.0);
+            match (&kernels, settings.splat_size) {
+                (Some(kernels), Some(size)) => {
+                    let Some((sx, sy, depth)) = project_to_screen(star, cam) else { continue };
+                    let radius = (size * SPLAT_RADIUS * reference / depth).round().clamp(1.0, MAX_SPLAT_RADIUS as f32) as i64;
+                    // Positions far off the canvas saturate at the ends of i64, hence the saturating
+                    // bounds. Skip splats entirely off the canvas before touching the kernel.
+                    let (cx, cy) = (sx.floor() as i64, sy.floor() as i64);
+                    let off = |c: i64, size: i64| c.saturating_add(radius) < 0 || c.saturating_sub(radius) >= size;
+                    if (!wraps && off(cx, width)) || off(cy, self.height as i64) {
+                        continue;
+                    }
+                    let weight = mass * falloff_weight(settings.falloff, reference, depth);
+                    let kernel = kernels.get(radius as usize);
+                    let side = 2 * radius + 1;
+                    for ky in 0..side {
+                        let py = cy + ky - radius;
+                        if py < 0 || py >= self.height as i64 {
+                            continue;
+                        }
+                        for kx in 0..side {
+                            let px = match cx + kx - radius {
+                                px if wraps => px.rem_euclid(width),
+                                px if px < 0 || px >= width => continue,
+                                px => px,
+                            };
+                            let i = py as usize * self.width as usize + px as usize;
+                            self.add(i, star, tint, weight * kernel[(ky * side + kx) as usize]);
+                        }
+                    }
+                }
+                _ => {
+                    let Some((x, y, depth)) = project_with_depth(star, cam) else { continue };
+                    let i = y as usize * self.width as usize + x as usize;
+                    self.add(i, star, tint, mass * falloff_weight(settings.falloff, reference, depth));
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, i: usize, star: &Star, tint: Option<[f32; 3]>, weight: f32) {
+        if let Some(color) = tint {
+            if self.tinted.is_empty() {
+                self.tinted = vec![[0.0; 3]; self.glow.len()];
+            }
+            for (sum, c) in self.tinted[i].iter_mut().zip(color) {
+                *sum += weight * c;
+            }
+            return;
+        }
+        let t = type_index(star);
+        if t >= self.types.len() {
+            self.types.resize(t + 1, vec![0.0; self.glow.len()]);
+        }
+        self.types[t][i] += weight;
+        if star.active_flag > 0.5 {
+            self.glow[i] += weight;
+        }
+    }
+
+    pub fn tonemap(&self, settings: &RenderSettings, is_chronoturin: bool) -> RgbImage {
+        let quantize = |v: f32| (settings.tonemap.apply(v * settings.exposure / 255.0) * 255.0).round() as u8;
+        let tint = glow_tint(is_chronoturin);
+        let mut img = RgbImage::from_fn(self.width, self.height, |x, y| {
+            let i = y as usize * self.width as usize + x as usize;
+            let rgb = self.star_rgb(settings, i);
+            image::Rgb(std::array::from_fn(|c| quantize(rgb[c] + self.glow[i] * tint[c])))
+        });
+        for &(cx, cy, radius) in &self.central {
+            draw_central(&mut img, cx, cy, radius);
+        }
+        img
+    }
+
+    // The frame's channels before the glow is mixed in and before exposure and tonemapping,
+    // for --image-format png16 and exr. 1.0 is what a linear 8-bit frame draws as full white.
+    pub fn hdr(&self, settings: &RenderSettings, is_chronoturin: bool) -> HdrImage {
+        let pixels = self.width as usize * self.height as usize;
+        HdrImage {
+            width: self.width,
+            height: self.height,
+            rgb: (0..pixels).map(|i| self.star_rgb(settings, i).map(|c| c / 255.0)).collect(),
+            glow: self.glow.clone(),
+            glow_tint: glow_tint(is_chronoturin).map(|c| c / 255.0),
+            exposure: settings.exposure,
+            tonemap: settings.tonemap,
+            central: self.central.clone(),
+        }
+    }
+
+    // BASE COLORS: the palette entry of each type (classic: type A red, type B blue), plus
+    // any --color-by tint, at pixel `i`.
+    fn star_rgb(&self, settings: &RenderSettings, i: usize) -> [f32; 3] {
+        let mut rgb = [0.0f32; 3];
+        for (t, channel) in self.types.iter().enumerate() {
+            for (sum, c) in rgb.iter_mut().zip(settings.palette.color(t)) {
+                *sum += channel[i] * c;
+            }
+        }
+        if let Some(tinted) = self.tinted.get(i) {
+            for (sum, c) in rgb.iter_mut().zip(tinted) {
+                *sum += c;
+            }
+        }
+        rgb
+    }
+}
+
+// EFFICIENCY VISUALIZER (The Glow): every active star adds green, so Newtonian mode whites
+// out while Chronoturin only lights the core, tinted gold.
+pub fn glow_tint(is_chronoturin: bool) -> [f32; 3] {
+    [if is_chronoturin { 50.0 } else { 0.0 }, 150.0, 0.0]
+}
+
+// Screen position and blob radius of a central mass, or None behind the near plane.
+fn central_splat(star: &Star, cam: &Camera) -> Option<(f32, f32, f32)> {
+    let (sx, sy, depth) = project_to_screen(star, cam)?;
+    let radius = (CENTRAL_RADIUS * reference_depth(cam) / depth).clamp(2.0, 2.0 * MAX_SPLAT_RADIUS as f32);
+    Some((sx, sy, radius))
+}
+
+// Paints every central mass in `stars` over `img`, in star order, as Framebuffer::tonemap does.
+pub fn draw_centrals(img: &mut RgbImage, stars: &[Star], cam: &Camera) {
+    for (cx, cy, radius) in stars.iter().filter(|s| is_central(s)).filter_map(|s| central_splat(s, cam)) {
+        draw_central(img, cx, cy, radius);
+    }
+}
+
+// Bright yellow Gaussian blob, blended towards CENTRAL_COLOR so it reads on any background.
+pub const CENTRAL_COLOR: image::Rgb<u8> = image::Rgb([255, 240, 60]);
+const CENTRAL_RADIUS: f32 = 8.0;
+
+fn draw_central(img: &mut RgbImage, cx: f32, cy: f32, radius: f32) {
+    let (width, height) = img.dimensions();
+    for_central_pixels(cx, cy, radius, width, height, |x, y, weight| {
+        let p = img.get_pixel_mut(x, y);
+        for c in 0..3 {
+            p[c] = (p[c] as f32 + (CENTRAL_COLOR[c] as f32 - p[c] as f32) * weight).round() as u8;
+        }
+    });
+}
+
+// Calls `blend` with every pixel of a width x height image under a central blob, and how far
+// to blend it towards CENTRAL_COLOR.
+pub fn for_central_pixels(cx: f32, cy: f32, radius: f32, width: u32, height: u32, mut blend: impl FnMut(u32, u32, f32)) {
+    let sigma = radius / 2.0;
+    let (x0, x1) = ((cx - radius).floor().max(0.0), (cx + radius).ceil().min(width as f32 - 1.0));
+    let (y0, y1) = ((cy - radius).floor().max(0.0), (cy + radius).ceil().min(height as f32 - 1.0));
+    if x0 > x1 || y0 > y1 {
+        return;
+    }
+    for y in y0 as u32..=y1 as u32 {
+        for x in x0 as u32..=x1 as u32 {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            blend(x, y, (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp());
+        }
+    }
+}
+
+// Splat radius in pixels at the reference depth, before --splat-size.
+const SPLAT_RADIUS: f32 = 2.0;
+pub const MAX_SPLAT_RADIUS: usize = 6;
+
+// Distance from the camera to its target; stars at this depth get falloff weight 1
+// and the nominal splat radius.
+pub fn reference_depth(cam: &Camera) -> f32 {
+    dot(sub(cam.target, cam.position), sub(cam.target, cam.position)).sqrt()
+}
+
+fn falloff_weight(falloff: f32, reference: f32, depth: f32) -> f32 {
+    if falloff == 0.0 {
+        1.0
+    } else {
+        (reference / depth).powf(falloff)
+    }
+}
+
+// Gaussian footprints for every integer radius, built once per frame. Each kernel has
+// sigma = radius / 2 and a peak of 1, so a splat's center matches a plotted point.
+pub struct SplatKernels {
+    kernels: Vec<Vec<f32>>,
+}
+
+impl SplatKernels {
+    pub fn new() -> Self {
+        let kernels = (0..=MAX_SPLAT_RADIUS as i32)
+            .map(|r| {
+                let sigma = (r as f32 / 2.0).max(0.5);
+                let side = 2 * r + 1;
+                (0..side * side)
+                    .map(|i| {
+                        let (dx, dy) = ((i % side - r) as f32, (i / side - r) as f32);
+                        (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { kernels }
+    }
+
+    // Row-major (2r+1)^2 weights.
+    pub fn get(&self, radius: usize) -> &[f32] {
+        &self.kernels[radius.min(MAX_SPLAT_RADIUS)]
+    }
+}
+
+impl Default for SplatKernels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn render_frame(stars: &[Star], cam: &Camera, settings: &RenderSettings, is_chronoturin: bool) -> RgbImage {
+    render_with(cam, settings, is_chronoturin, |fb, cam| fb.accumulate(stars, cam, settings))
+}
+
+// What `fill` accumulates as seen from `cam`, tonemapped; with --stereo, seen from each eye
+// and combined into an anaglyph.
+pub fn render_with(cam: &Camera, settings: &RenderSettings, is_chronoturin: bool, fill: impl Fn(&mut Framebuffer, &Camera)) -> RgbImage {
+    let draw = |cam: &Camera| {
+        let mut fb = Framebuffer::new(cam.width, cam.height);
+        fill(&mut fb, cam);
+        fb.tonemap(settings, is_chronoturin)
+    };
+    match settings.stereo {
+        Some(stereo) => {
+            let (left, right) = cam.eyes(stereo.separation);
+            anaglyph(&draw(&left), &draw(&right))
+        }
+        None => draw(cam),
+    }
+}
+
+// Red/cyan anaglyph: the left eye's brightness in red, the right's in green and blue.
+// Brightness rather than colour, so both eyes see stars of every type.
+pub fn anaglyph(left: &RgbImage, right: &RgbImage) -> RgbImage {
+    let luma = |p: &image::Rgb<u8>| (0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32).round() as u8;
+    RgbImage::from_fn(left.width(), left.height(), |x, y| {
+        let (l, r) = (luma(left.get_pixel(x, y)), luma(right.get_pixel(x, y)));
+        image::Rgb([l, r, r])
+    })
+}
+
+// --trails: `trails`, the frame's earlier sub-steps oldest first, drawn under `stars`, each
+// `decay` times as bright as the one after it, so moving stars streak in their own colours.
+pub fn render_trails(
+    stars: &[Star],
+    trails: &[Vec<Star>],
+    cam: &Camera,
+    settings: &RenderSettings,
+    decay: f32,
+    is_chronoturin: bool,
+) -> RgbImage {
+    render_with(cam, settings, is_chronoturin, |fb, cam| {
+        fb.accumulate_trails(trails, cam, settings, decay);
+        fb.accumulate(stars, cam, settings);
+    })
+}
+
+pub const SEPARATOR_WIDTH: u32 = 4;
+pub const SEPARATOR: image::Rgb<u8> = image::Rgb([90, 90, 90]);
+
+// Places two equally sized frames next to each other with a thin divider over the seam.
+pub fn side_by_side(left: &RgbImage, right: &RgbImage) -> RgbImage {
+    let (w, h) = left.dimensions();
+    let mut out = RgbImage::new(w * 2, h);
+    image::imageops::replace(&mut out, left, 0, 0);
+    image::imageops::replace(&mut out, right, w as i64, 0);
+    let start = w.saturating_sub(SEPARATOR_WIDTH / 2);
+    for y in 0..h {
+        for x in start..(start + SEPARATOR_WIDTH).min(w * 2) {
+            out.put_pixel(x, y, SEPARATOR);
+        }
+    }
+    out
+}
@@ -0,0 +1,183 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::bench::StepStats;
+use crate::config::SimConfig;
+use crate::determinism::DeterminismReport;
+use crate::diff::DisplacementStats;
+use crate::tune::TuneTrace;
+
+// Bumped whenever a key of RunReport::json is renamed or removed; new keys may be added
+// without a bump.
+pub const REPORT_SCHEMA: u32 = 1;
+
+// The flags that decide what a run simulated, as the report records them.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ReportConfig {
+    pub stars: u32,
+    pub seed: u64,
+    pub preset: String,
+    pub frames: usize,
+    pub steps_per_frame: usize,
+    pub dt: f32,
+    pub dilation_threshold: f32,
+    pub max_debt: f32,
+    pub types: usize,
+    pub integrator: String,
+    pub accel: &'static str,
+    // `lockstep`, `sequential` or `schedule` (--mode-schedule)
+    pub layout: &'static str,
+    // Every flag, under its --config key; the report's last key, `resolved_config`
+    #[serde(skip)]
+    pub resolved: serde_json::Value,
+}
+
+impl ReportConfig {
+    // After the run, so `seed` and `sequential` are what it actually used.
+    pub fn from_config(config: &SimConfig) -> Self {
+        Self {
+            stars: config.stars,
+            seed: config.seed.unwrap_or_default(),
+            preset: value_name(config.preset),
+            frames: config.frames,
+            steps_per_frame: config.steps_per_frame,
+            dt: config.dt,
+            dilation_threshold: config.dilation_threshold,
+            max_debt: config.max_debt,
+            types: config.types,
+            integrator: value_name(config.integrator),
+            accel: config.accel.name(),
+            layout: match (&config.mode_schedule, config.chain, config.sequential) {
+                (Some(_), _, _) => "schedule",
+                (None, Some(_), _) => "chain",
+                (None, None, true) => "sequential",
+                (None, None, false) => "lockstep",
+            },
+            resolved: serde_json::to_value(config).expect("SimConfig serializes to JSON"),
+        }
+    }
+}
+
+// One mode's numbers for one frame: what ModeReport aggregates and --plots draws.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameSample {
+    pub frame: usize,
+    pub wall_ms: f32,
+    pub gpu_ms: Option<f32>,
+    pub active_fraction: f32,
+    // Relative to the mode's first measured frame
+    pub energy_drift: Option<f64>,
+    // MetricsRecord::lagrangian_radii and Conservation::virial_ratio
+    pub lagrangian_radii: Option<[f32; 5]>,
+    pub virial_ratio: Option<f64>,
+}
+
+// One mode's frames, aggregated.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ModeReport {
+    pub mode: &'static str,
+    // `name (backend)`, or `CPU reference` with --cpu
+    pub adapter: String,
+    pub frames: usize,
+    // Per-frame wall time; lockstep frames step both modes, so they share it
+    pub wall: StepStats,
+    // None without TIMESTAMP_QUERY
+    pub gpu: Option<StepStats>,
+    pub mean_active_fraction: f64,
+    // conservation::relative_drift of the first and last frame; None with packed readback
+    // or --energy-check none
+    pub energy_drift: Option<f64>,
+}
+
+// Printed at the end of every frame run, and written with --report.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RunReport {
+    pub config: ReportConfig,
+    pub modes: Vec<ModeReport>,
+    pub total_wall_ms: f64,
+    // Chronoturin's displacement from Newtonian at the last frame, for lockstep runs
+    pub divergence: Option<DisplacementStats>,
+    // `tune --run`: the probes that picked the run's threshold
+    pub tuning: Option<TuneTrace>,
+}
+
+impl RunReport {
+    pub fn table(&self) -> String {
+        let c = &self.config;
+        let mut table = format!(
+            "Stars: {} | Seed: {} | Preset: {} | Types: {} | Frames: {} x {} steps of dt {} | Integrator: {} | Accel: {} | Threshold: {} | Max debt: {} | Layout: {}\n\n\
+             | Mode | Adapter | Frames | Wall mean ms | Wall median ms | GPU mean ms | GPU median ms | Active % | Energy drift |\n\
+             |------|---------|--------|--------------|----------------|-------------|---------------|----------|--------------|\n",
+            c.stars, c.seed, c.preset, c.types, c.frames, c.steps_per_frame, c.dt, c.integrator, c.accel,
+            c.dilation_threshold, c.max_debt, c.layout
+        );
+        for m in &self.modes {
+            let gpu = |f: fn(&StepStats) -> f64| m.gpu.as_ref().map_or("-".to_string(), |s| format!("{:.3}", f(s)));
+            table.push_str(&format!(
+                "| {} | {} | {} | {:.1} | {:.1} | {} | {} | {:.1} | {} |\n",
+                m.mode,
+                m.adapter,
+                m.frames,
+                m.wall.mean,
+                m.wall.median,
+                gpu(|s| s.mean),
+                gpu(|s| s.median),
+                m.mean_active_fraction * 100.0,
+                m.energy_drift.map_or("-".to_string(), |d| format!("{:+.3e}", d))
+            ));
+        }
+        table.push_str(&format!("\nTotal wall time: {:.1} ms\n", self.total_wall_ms));
+        if let Some(d) = &self.divergence {
+            table.push_str(&format!(
+                "Final divergence from Newtonian: mean {:.4} | p99 {:.4} | max {:.4} | {} non-finite\n",
+                d.mean, d.p99, d.max, d.non_finite
+            ));
+        }
+        table
+    }
+
+    // Every key is always present (null when not measured), so reports diff cleanly.
+    pub fn json(&self) -> String {
+        #[derive(Serialize)]
+        struct Run<'a> {
+            #[serde(flatten)]
+            report: &'a RunReport,
+            resolved_config: &'a serde_json::Value,
+        }
+        versioned(Run { report: self, resolved_config: &self.config.resolved })
+    }
+}
+
+// What --report holds for a `tune` that stops at its recommendation.
+pub fn tune_report_json(trace: &TuneTrace) -> String {
+    #[derive(Serialize)]
+    struct Tune<'a> {
+        tuning: &'a TuneTrace,
+    }
+    versioned(Tune { tuning: trace })
+}
+
+// What --report holds for --verify-determinism.
+pub fn determinism_report_json(report: &DeterminismReport) -> String {
+    #[derive(Serialize)]
+    struct Determinism<'a> {
+        determinism: &'a DeterminismReport,
+    }
+    versioned(Determinism { determinism: report })
+}
+
+// A --report file: REPORT_SCHEMA, then `body`'s keys.
+fn versioned<T: Serialize>(body: T) -> String {
+    #[derive(Serialize)]
+    struct Versioned<T> {
+        schema: u32,
+        #[serde(flatten)]
+        body: T,
+    }
+    serde_json::to_string(&Versioned { schema: REPORT_SCHEMA, body }).expect("reports serialize to JSON")
+}
+
+// The flag value clap accepts for `value`, e.g. `sphere`.
+fn value_name<T: ValueEnum>(value: T) -> String {
+    value.to_possible_value().map_or_else(String::new, |v| v.get_name().to_string())
+}
@@ -0,0 +1,1551 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+use image::RgbImage;
+use tracing::{debug, error, info, trace_span, warn};
+
+use crate::bench::StepStats;
+use crate::checkpoint::{Checkpoint, CheckpointRun};
+use crate::clumps::{write_catalog, Clumps, FofSettings};
+use crate::config::{Command, SimConfig};
+use crate::conservation::{self, Conservation};
+use crate::control::{Control, ControlCommand, ControlStatus};
+use crate::cpu_reference::{self, CpuSimulation};
+use crate::determinism::{self, DeterminismReport};
+use crate::diff::{displacement_stats, render_diff, DisplacementStats};
+use crate::dump::{read_initial_conditions, write_dump, DumpInfo};
+use crate::error::ChronoError;
+use crate::galaxy::{active_by_type, check_types, clear_dilation, GalaxyState, Star};
+use crate::gif::GifWriter;
+use crate::gpu::{self, DeviceErrors, ModeAdapter};
+use crate::hdr::HdrImage;
+use crate::hot_reload::ShaderWatcher;
+use crate::interrupt;
+use crate::logging::{self, thousands, FRAME_TARGET};
+use crate::manifest::RunManifest;
+use crate::memory::MemoryPlan;
+use crate::metrics::{MetricsRecord, MetricsWriter};
+use crate::output::OutputLayout;
+use crate::overlay::{self, draw_legend, draw_overlay, draw_tracked, OverlayInfo};
+use crate::packing::ReadbackChoice;
+use crate::pipe::{self, OnPipeClose, RawFrameWriter};
+#[cfg(feature = "plots")]
+use crate::plots;
+use crate::presets;
+use crate::profiles::{write_profiles, write_rotation_curve, LagrangianRadii, ProfileSettings, Profiles, RotationCurve};
+use crate::raster::RasterChoice;
+use crate::recovery::{Recovering, Reopen};
+use crate::reduction::Moments;
+use crate::render::{render_with, side_by_side, Camera, Framebuffer, RenderSettings};
+use crate::report::{determinism_report_json, tune_report_json, FrameSample, ModeReport, ReportConfig, RunReport};
+use crate::saver::FrameSaver;
+use crate::schedule::ChainOrder;
+#[cfg(feature = "serve")]
+use crate::serve::{self, FrameServer};
+use crate::simulation::{DilationMode, FrameData, OnFrameError, Simulation, Stepper};
+use crate::snapshot::Snapshot;
+use crate::telemetry::Telemetry;
+use crate::trajectory::TrajectoryWriter;
+use crate::tune::{self, TuneArgs, TuneTrace};
+use crate::video::{video_path_for, VideoEncoder};
+use crate::watchdog::OnDivergence;
+
+// --- FRAME RUN: BOTH MODES, FRAME BY FRAME, INTO FRAME FILES AND METRICS ---
+// `gpu` is the one device, or with --adapters one per mode (`adapters` naming them), or None
+// with --cpu. `device_errors` watch the one device, for a lost device to be recovered from.
+pub async fn run_frames(
+    mut config: SimConfig,
+    gpu: Option<Vec<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>>,
+    adapters: Vec<ModeAdapter>,
+    adapter_name: String,
+    device_errors: Option<DeviceErrors>,
+) -> Result<(), ChronoError> {
+    let limits = gpu.as_ref().map_or_else(cpu_reference::limits, |devices| devices[0].0.limits());
+    // --chain-from's second pass is the whole run, from the snapshot's stars.
+    let chain_from = match (&config.chain, &config.chain_from) {
+        (Some(order), Some(path)) => Some(ChainStart::read(path, order.modes()[0])?),
+        _ => None,
+    };
+    // A file of initial conditions decides the star count (and so the dispatch size).
+    let loaded_stars = match (&config.initial_conditions, &chain_from) {
+        (Some(path), _) => {
+            let stars = read_initial_conditions(path)?;
+            info!("Loaded {} stars from {}", stars.len(), path.display());
+            Some(stars)
+        }
+        (None, Some(start)) => Some(start.stars.clone()),
+        (None, None) => None,
+    };
+    if let Some(stars) = &loaded_stars {
+        config.stars = stars.len() as u32;
+    }
+    config.validate(&limits)?;
+    if let Some(stars) = &loaded_stars {
+        check_types(stars, config.types)?;
+    }
+
+    // A checkpoint pins the seed (so reset() regenerates the same galaxy) and the resume point.
+    let resume = match &config.resume {
+        Some(path) => {
+            let checkpoint = Checkpoint::load(path)?;
+            checkpoint.check_compatible(&config)?;
+            info!("Resuming {} at pass {}, frame {}", path.display(), checkpoint.pass + 1, checkpoint.next_frame);
+            Some(checkpoint)
+        }
+        None => None,
+    };
+
+    // 1. GENERATE DATA ONCE (Identical Start for Fairness)
+    let seed = resume.as_ref().map_or_else(|| config.seed.unwrap_or_else(rand::random), |c| c.seed);
+    config.seed = Some(seed);
+    info!("Stars: {} | Preset: {:?} | Frames/Mode: {} | Steps/Frame: {} x dt {} = {} sim time | Seed: {} | Submit: {} | Raster: {} | Readback: {} | Accel: {}",
+        config.stars, config.preset, config.frames, config.steps_per_frame, config.dt,
+        config.steps_per_frame as f32 * config.dt, seed,
+        if config.legacy_submit { "per step (legacy)" } else { "batched" },
+        if config.raster_choice() == RasterChoice::Cpu { "cpu" } else { "gpu" },
+        if config.readback == ReadbackChoice::Packed { "packed" } else { "full" }, config.accel.name());
+    let initial_data = loaded_stars.unwrap_or_else(|| presets::from_config(&config, seed));
+    // Every buffer the run will allocate, checked before any of them is. Side by side on one
+    // device, the fork holds a second copy, so a run over --memory-budget goes sequential
+    // when that fits, as it does when the fork runs out of memory.
+    let memory = match &gpu {
+        Some(devices) => {
+            let side_by_side = devices.len() == 1 && !config.sequential && config.mode_schedule.is_none();
+            let mut plan = MemoryPlan::new(&config, &limits, if side_by_side { 2 } else { 1 })?;
+            let single = MemoryPlan { simulations: 1, ..plan.clone() };
+            if side_by_side
+                && plan.check(&config, &limits).is_err()
+                && single.check(&config, &limits).is_ok()
+                && !config.measures_divergence()
+                && resume.is_none()
+            {
+                warn!("Both modes' buffers exceed --memory-budget; running them one after the other as with --sequential");
+                config.sequential = true;
+                plan = single;
+            }
+            for line in plan.table() {
+                info!("{}", line);
+            }
+            plan.check(&config, &limits)?;
+            Some(plan)
+        }
+        None => None,
+    };
+    if let Some(runs) = config.verify_determinism {
+        let report = match &gpu {
+            Some(devices) => {
+                let (device, queue) = devices[0].clone();
+                run_verify(&config, runs, &adapter_name, Simulation::with_stars(device, queue, &config, initial_data)?)
+            }
+            None => run_verify(&config, runs, &adapter_name, CpuSimulation::with_stars(&config, initial_data)),
+        };
+        if let Some(path) = &config.report {
+            std::fs::write(path, determinism_report_json(&report) + "\n")?;
+            info!("Wrote {}", path.display());
+        }
+        return Ok(());
+    }
+    // tune stops at its recommendation, or goes on into the frame run with it.
+    let mut tuning = None;
+    if let Some(Command::Tune(args)) = config.command.clone() {
+        let trace = match &gpu {
+            Some(devices) => {
+                let (device, queue) = devices[0].clone();
+                run_tune(&config, &args, Simulation::with_stars(device, queue, &config, initial_data.clone())?)
+            }
+            None => run_tune(&config, &args, CpuSimulation::with_stars(&config, initial_data.clone())),
+        };
+        let Some(threshold) = trace.recommended.filter(|_| args.run && !interrupt::requested()) else {
+            if let Some(path) = &config.report {
+                std::fs::write(path, tune_report_json(&trace) + "\n")?;
+                info!("Wrote {}", path.display());
+            }
+            return match trace.recommended {
+                None if !interrupt::requested() => {
+                    Err(ChronoError::OverBudget { threshold: args.min_threshold, error: trace.probes[0].error, budget: args.error_budget })
+                }
+                _ => Ok(()),
+            };
+        };
+        info!("Running {} frames with --dilation-threshold {}", config.frames, threshold);
+        config.dilation_threshold = threshold;
+        config.command = None;
+        tuning = Some(trace);
+    }
+
+    // On one device, a lost device is rebuilt once from a recent readback (Recovering).
+    let reopen = device_errors.is_some().then(|| -> Reopen<Simulation> {
+        let (config, stars) = (config.clone(), initial_data.clone());
+        Arc::new(move || {
+            let instance = gpu::create_instance(&config);
+            let (_, device, queue, errors) = pollster::block_on(gpu::request_gpu(&instance, &config, None))?;
+            let mut sim = build_simulation(&config, (device, queue), stars.clone())?;
+            sim.watch_device(errors);
+            Ok(sim)
+        })
+    });
+    // Built ahead of the outputs: when the GPU cannot hold a second copy of the buffers
+    // for lockstep, the run turns sequential, which lays the outputs out differently.
+    let build = |devices, stars| build_simulation(&config, devices, stars);
+    let mut backend = match gpu {
+        // Newtonian steps on the first device, Chronoturin on the second, in either layout.
+        Some(mut devices) if devices.len() == 2 => {
+            let mut sim = build(devices.pop().unwrap(), initial_data.clone())?;
+            let mut twin = build(devices.pop().unwrap(), initial_data)?;
+            twin.watch_device(adapters[0].errors.clone());
+            sim.watch_device(adapters[1].errors.clone());
+            Backend::Gpu(Box::new(sim), Some(Box::new(twin)))
+        }
+        Some(mut devices) => {
+            let mut sim = build(devices.swap_remove(0), initial_data)?;
+            if let Some(errors) = device_errors {
+                sim.watch_device(errors);
+            }
+            let twin = if config.sequential || config.mode_schedule.is_some() { None } else { sim.try_fork().await };
+            if twin.is_none() && !config.sequential && config.mode_schedule.is_none() {
+                if config.measures_divergence() || resume.is_some() {
+                    return Err(ChronoError::InvalidConfig(
+                        "the GPU cannot hold both modes at once, and compare, --diff and --resume of a side-by-side run need both".into(),
+                    ));
+                }
+                warn!("The GPU cannot hold both modes at once; running them one after the other as with --sequential");
+                config.sequential = true;
+            }
+            Backend::Gpu(Box::new(sim), twin.map(Box::new))
+        }
+        None => Backend::Cpu(Box::new(CpuSimulation::with_stars(&config, initial_data))),
+    };
+    let shader = match (&config.shader, &mut backend) {
+        (Some(path), Backend::Gpu(sim, twin)) => {
+            let mut sims: Vec<&mut Simulation> = std::iter::once(&mut **sim).chain(twin.as_deref_mut()).collect();
+            Some(ShaderWatcher::start(path, &mut sims)?)
+        }
+        _ => None,
+    };
+
+    let uniform_path = match &backend {
+        Backend::Gpu(sim, _) => Some(sim.uniform_path()),
+        Backend::Cpu(_) => None,
+    };
+    if let Some(path) = uniform_path {
+        info!("GalaxyState by {}", path.describe());
+    }
+
+    let layout = OutputLayout::prepare(&config)?;
+    let manifest = RunManifest { uniform_path, memory, ..RunManifest::new(&config) };
+    manifest.write(&layout.manifest_path())?;
+    let metrics = match &config.metrics_out {
+        Some(path) if resume.is_some() => Some(MetricsWriter::append(path, config.metrics_format)?),
+        Some(path) => Some(MetricsWriter::create(path, config.metrics_format)?),
+        None => None,
+    };
+    let gif = match &config.gif {
+        Some(path) => Some(GifWriter::create(path, config.gif_scale, config.gif_delay)?),
+        None => None,
+    };
+    let trajectories = match config.track.is_empty() {
+        false => Some(TrajectoryWriter::open(&config.track, |id| layout.trajectory_path(id), resume.is_some())?),
+        true => None,
+    };
+    let camera = Camera::from_config(&config);
+    // Side by side, unless the passes run one after the other or --mode-schedule runs just one.
+    let panels = if config.sequential || config.mode_schedule.is_some() { 1 } else { 2 };
+    let frames_to = pipe::open_frames_to(&config, camera.width * panels, camera.height)?;
+    let saver = FrameSaver::new(config.save_threads, config.save_queue, Some(manifest.clone()));
+    let telemetry = Telemetry::new();
+    let run_id = config.run_id.clone().unwrap_or_else(|| seed.to_string());
+    telemetry.count_images(&run_id, saver.written());
+    #[cfg(feature = "serve")]
+    let server = serve::start(&config, &telemetry)?;
+    let mut out = FrameOutput {
+        camera,
+        render_settings: RenderSettings::from_config(&config),
+        layout,
+        saver,
+        manifest,
+        telemetry,
+        run_id,
+        #[cfg(feature = "serve")]
+        server,
+        gif,
+        frames_to,
+        on_pipe_close: config.on_pipe_close,
+        metrics,
+        trajectories,
+        stats: Vec::new(),
+        potential: conservation::Potential::from_config(&config),
+        dilation_threshold: config.dilation_threshold,
+        max_debt: config.max_debt,
+        continuous: config.mode_schedule.is_some() || config.chain.is_some(),
+        types: config.types,
+        profile_settings: ProfileSettings::from_config(&config),
+        write_profiles: config.profiles,
+        write_kinematics: config.kinematics,
+        plots: config.plots,
+        lagrangian: LagrangianRadii::default(),
+        seed,
+        wait_ms: 0.0,
+        cpu_ms: 0.0,
+        overlapped_ms: 0.0,
+        readback_ms: 0.0,
+        readback_bytes: 0,
+        readback_frames: 0,
+        adapters,
+        adapter_name,
+        divergence: None,
+        failure: None,
+        shader,
+        control: config.control.then(|| {
+            let replies: Box<dyn std::io::Write + Send> = match config.frames_to.as_deref().is_some_and(pipe::is_stdout) {
+                true => Box::new(std::io::stderr()),
+                false => Box::new(std::io::stdout()),
+            };
+            Control::new(std::io::BufReader::new(std::io::stdin()), replies)
+        }),
+        on_divergence: config.on_divergence,
+    };
+    let passes = match backend {
+        Backend::Gpu(sim, twin) => match reopen {
+            Some(reopen) => {
+                let recovering = |sim: Box<Simulation>| Recovering::new(*sim, &config, reopen.clone());
+                run_passes(&config, recovering(sim), twin.map(recovering), &mut out, resume.as_ref(), chain_from)
+            }
+            None => run_passes(&config, *sim, twin.map(|twin| *twin), &mut out, resume.as_ref(), chain_from),
+        },
+        Backend::Cpu(sim) => run_passes(&config, *sim, None, &mut out, resume.as_ref(), chain_from),
+    };
+
+    if let Some(gif) = out.gif.take() {
+        let frames = gif.frames();
+        info!("Wrote {} ({} frames)", gif.finish().display(), frames);
+    }
+    pipe::finish_frames_to(out.frames_to.take())?;
+    // Every queued frame must be on disk before we report completion, or a failure.
+    let stats = out.saver.finish();
+    info!(
+        "Saved {} frames ({} failed) | Blocked on save queue: {:.1} ms",
+        stats.frames_saved,
+        stats.failures,
+        stats.blocked.as_secs_f64() * 1000.0
+    );
+    let (run_start, readback_depth) = passes?;
+    info!(
+        "Wall Time: {:.1} ms | Readback wait: {:.1} ms | CPU work overlapped with GPU: {:.1} of {:.1} ms ({} readback buffers)",
+        run_start.elapsed().as_secs_f64() * 1000.0,
+        out.wait_ms,
+        out.overlapped_ms,
+        out.cpu_ms,
+        readback_depth
+    );
+    if out.readback_frames > 0 && !config.cpu {
+        let frames = out.readback_frames as f64;
+        info!(
+            "Readback: {:.2} MiB per frame | Map and copy-out: {:.2} ms per frame",
+            out.readback_bytes as f64 / frames / (1024.0 * 1024.0),
+            out.readback_ms as f64 / frames
+        );
+    }
+    if interrupt::requested() {
+        for (pass, mode) in [DilationMode::Newtonian, DilationMode::Chronoturin].into_iter().enumerate() {
+            // A sequential pass skipped by --resume finished in the earlier run.
+            let skipped = config.sequential && resume.as_ref().is_some_and(|c| c.pass as usize > pass);
+            let done = out.stats.iter().find(|s| s.mode == mode).map_or(if skipped { config.frames } else { 0 }, |s| s.frames_done);
+            warn!("Interrupted: [{}] {} of {} frames completed", mode.name(), done, config.frames);
+        }
+    }
+    for mode_stats in &out.stats {
+        let (mode_name, times, active) = (mode_stats.mode.name(), &mode_stats.gpu_times, &mode_stats.active_fractions);
+        let mean_active = active.iter().sum::<f32>() / active.len().max(1) as f32;
+        info!("[{}] Mean active fraction: {:.1}%", mode_name, mean_active * 100.0);
+        let on = out.adapters.iter().find(|a| a.mode == mode_stats.mode).map_or(String::new(), |a| format!(" on {}", a.label()));
+        if !on.is_empty() {
+            info!("[{}] Frame time: mean {:.1} ms{}", mode_name, mode_stats.wall_ms / active.len().max(1) as f64, on);
+        }
+        if let Some(drift) = mode_stats.energy_drift() {
+            info!("[{}] Relative energy drift: {:+.3e}", mode_name, drift);
+        }
+        if config.compaction && mode_stats.star_steps > 0 {
+            info!(
+                "[{}] Force pass: {:.0} threads per frame, {:.1}% of one per star and step",
+                mode_name,
+                mode_stats.force_threads as f64 / active.len().max(1) as f64,
+                mode_stats.force_threads as f64 / mode_stats.star_steps as f64 * 100.0
+            );
+        }
+        if let Some(drift) = mode_stats.center_drift {
+            info!("[{}] Center-of-mass drift: final {:.3e} | max {:.3e}", mode_name, drift, mode_stats.max_center_drift);
+        }
+        if times.is_empty() {
+            continue;
+        }
+        let mean = times.iter().sum::<f32>() / times.len() as f32;
+        let min = times.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = times.iter().cloned().fold(0.0, f32::max);
+        info!("[{}] GPU compute per frame: mean {:.3} ms | min {:.3} ms | max {:.3} ms{}", mode_name, mean, min, max, on);
+    }
+    // How much of the drift the dilation itself adds on top of the integrator's own error.
+    let drift = |mode| out.stats.iter().find(|s| s.mode == mode).and_then(ModeStats::energy_drift);
+    if let (Some(newtonian), Some(chronoturin)) = (drift(DilationMode::Newtonian), drift(DilationMode::Chronoturin)) {
+        info!("Energy drift added by Chronoturin: {:+.3e}", chronoturin - newtonian);
+    }
+    let total_wall_ms = run_start.elapsed().as_secs_f64() * 1000.0;
+    let mut report = run_report(&config, &out.stats, &out.adapters, &out.adapter_name, out.divergence, total_wall_ms);
+    report.tuning = tuning;
+    for line in report.table().lines() {
+        info!("{}", line);
+    }
+    if let Some(path) = &config.report {
+        std::fs::write(path, report.json() + "\n")?;
+        info!("Wrote {}", path.display());
+    }
+    #[cfg(feature = "plots")]
+    if config.plots {
+        let modes: Vec<(DilationMode, &[FrameSample])> = out.stats.iter().map(|s| (s.mode, &s.samples[..])).collect();
+        for path in plots::write_plots(out.layout.root(), &modes, &out.manifest)? {
+            info!("Wrote {}", path.display());
+        }
+    }
+    // A --sequential --adapters pass that failed, now that the other one has been reported.
+    if let Some(error) = out.failure.take() {
+        return Err(error);
+    }
+    info!("{}", if interrupt::requested() { "Simulation Interrupted." } else { "Simulation Complete." });
+    Ok(())
+}
+
+// Aggregates the per-mode totals record() kept into the end-of-run report.
+// Takes FrameOutput's fields, since finishing the saver has moved out of it.
+fn run_report(
+    config: &SimConfig,
+    stats: &[ModeStats],
+    adapters: &[ModeAdapter],
+    adapter_name: &str,
+    divergence: Option<DisplacementStats>,
+    total_wall_ms: f64,
+) -> RunReport {
+    let modes = stats
+        .iter()
+        .map(|s| ModeReport {
+            mode: s.mode.name(),
+            adapter: adapters
+                .iter()
+                .find(|a| a.mode == s.mode)
+                .map_or_else(|| adapter_name.to_string(), |a| format!("{} ({:?})", a.name, a.backend)),
+            frames: s.frames_done,
+            wall: StepStats::from_samples(&s.samples.iter().map(|f| f.wall_ms).collect::<Vec<_>>()),
+            gpu: (!s.gpu_times.is_empty()).then(|| StepStats::from_samples(&s.gpu_times)),
+            mean_active_fraction: s.active_fractions.iter().map(|&f| f as f64).sum::<f64>() / s.active_fractions.len().max(1) as f64,
+            energy_drift: s.energy_drift(),
+        })
+        .collect();
+    RunReport { config: ReportConfig::from_config(config), modes, total_wall_ms, divergence, tuning: None }
+}
+
+// A frame run's simulation on one of its devices, drawing and packing as the flags ask.
+fn build_simulation(
+    config: &SimConfig,
+    (device, queue): (Arc<wgpu::Device>, Arc<wgpu::Queue>),
+    stars: Vec<Star>,
+) -> Result<Simulation, ChronoError> {
+    let mut sim = Simulation::with_stars(device, queue, config, stars)?;
+    if config.raster_choice() == RasterChoice::Gpu {
+        sim.enable_raster(&RenderSettings::from_config(config), &Camera::from_config(config))?;
+    }
+    if config.readback == ReadbackChoice::Packed {
+        sim.enable_packed_readback();
+    }
+    if config.trails {
+        sim.enable_trails();
+    }
+    Ok(sim)
+}
+
+// The simulation a frame run steps, built before its outputs.
+enum Backend {
+    // With the fork lockstep steps Newtonian on, unless the run is sequential
+    Gpu(Box<Simulation>, Option<Box<Simulation>>),
+    Cpu(Box<CpuSimulation>),
+}
+
+// Runs every pass on whichever backend built `sim`; returns when the clock started and the readback depth.
+// `twin`, if given, is a fork() of `sim` for lockstep runs to step Newtonian on.
+fn run_passes<S: Stepper + Send>(
+    config: &SimConfig,
+    sim: S,
+    twin: Option<S>,
+    out: &mut FrameOutput,
+    resume: Option<&Checkpoint>,
+    chain_from: Option<ChainStart>,
+) -> Result<(Instant, usize), ChronoError> {
+    let run_start = Instant::now();
+    let readback_depth = sim.readback_depth();
+    if let Some(schedule) = &config.mode_schedule {
+        let mut sim = sim;
+        run_pass(config, &mut sim, 0, schedule.mode_at(0), &Mutex::new(out), resume, None)?;
+    } else if let Some(order) = config.chain {
+        run_chain(config, sim, order, out, chain_from)?;
+    } else if config.sequential {
+        // Only --adapters gives a sequential run a second simulation.
+        match twin {
+            Some(newton) => run_parallel(config, newton, sim, out),
+            None => run_sequential(config, sim, out, resume)?,
+        }
+    } else {
+        let newton = twin.unwrap_or_else(|| sim.fork());
+        run_lockstep(config, newton, sim, out, resume)?;
+    }
+    Ok((run_start, readback_depth))
+}
+
+// Per-mode numbers for the end-of-run summary.
+struct ModeStats {
+    mode: DilationMode,
+    gpu_times: Vec<f32>,
+    active_fractions: Vec<f32>,
+    first_conservation: Option<Conservation>,
+    last_conservation: Option<Conservation>,
+    // --gpu-diagnostics: distance from the first frame's center of mass
+    first_moments: Option<Moments>,
+    center_drift: Option<f64>,
+    max_center_drift: f64,
+    // Frames finished in this mode, counting any before a --resume (--mode-schedule and --chain
+    // runs, which cannot resume, count the mode's own)
+    frames_done: usize,
+    // FrameData::force_threads totals, against one invocation per star and step
+    force_threads: u64,
+    star_steps: u64,
+    // Summed frame times, attributed to the mode's adapter with --adapters
+    wall_ms: f64,
+    samples: Vec<FrameSample>,
+    // Watchdog conditions already logged with --on-divergence warn
+    divergence_warned: Vec<&'static str>,
+}
+
+impl ModeStats {
+    fn new(mode: DilationMode) -> Self {
+        Self {
+            mode,
+            gpu_times: Vec::new(),
+            active_fractions: Vec::new(),
+            first_conservation: None,
+            last_conservation: None,
+            first_moments: None,
+            center_drift: None,
+            max_center_drift: 0.0,
+            frames_done: 0,
+            force_threads: 0,
+            star_steps: 0,
+            wall_ms: 0.0,
+            samples: Vec::new(),
+            divergence_warned: Vec::new(),
+        }
+    }
+
+    fn energy_drift(&self) -> Option<f64> {
+        conservation::relative_drift(self.first_conservation.as_ref()?, self.last_conservation.as_ref()?)
+    }
+}
+
+// Everything a finished frame flows into: rendering, image sinks, metrics and timing totals.
+struct FrameOutput {
+    camera: Camera,
+    render_settings: RenderSettings,
+    layout: OutputLayout,
+    saver: FrameSaver,
+    // Also in every frame, dump and checkpoint
+    manifest: RunManifest,
+    // --serve's /metrics, labelled with `run_id`
+    telemetry: Telemetry,
+    run_id: String,
+    #[cfg(feature = "serve")]
+    server: Option<FrameServer>,
+    gif: Option<GifWriter>,
+    frames_to: Option<RawFrameWriter>,
+    on_pipe_close: OnPipeClose,
+    metrics: Option<MetricsWriter>,
+    trajectories: Option<TrajectoryWriter>,
+    stats: Vec<ModeStats>,
+    potential: conservation::Potential,
+    dilation_threshold: f32,
+    max_debt: f32,
+    // --mode-schedule and --chain: frame numbers carry on across modes
+    continuous: bool,
+    types: usize,
+    profile_settings: ProfileSettings,
+    write_profiles: bool,
+    write_kinematics: bool,
+    plots: bool,
+    // Scratch for the Lagrangian radii, reused from frame to frame
+    lagrangian: LagrangianRadii,
+    seed: u64,
+    wait_ms: f32,
+    cpu_ms: f32,
+    overlapped_ms: f32,
+    readback_ms: f32,
+    readback_bytes: u64,
+    readback_frames: usize,
+    // One per mode with --adapters, else empty
+    adapters: Vec<ModeAdapter>,
+    // The one device every mode runs on without --adapters, for the report
+    adapter_name: String,
+    // Lockstep runs' displacement at the last frame, for the report
+    divergence: Option<DisplacementStats>,
+    // The first failed pass of a --sequential --adapters run
+    failure: Option<ChronoError>,
+    // --shader, polled between frames
+    shader: Option<ShaderWatcher>,
+    // --control, answered between frames
+    control: Option<Control>,
+    on_divergence: OnDivergence,
+}
+
+impl FrameOutput {
+    fn adapter(&self, mode: DilationMode) -> Option<&ModeAdapter> {
+        self.adapters.iter().find(|a| a.mode == mode)
+    }
+
+    // The frame as drawn, and with --image-format png16 or exr its float channels as well.
+    // `clumps` are the frame's own, when --fof-every analyzed it.
+    fn render(
+        &self,
+        config: &SimConfig,
+        data: &FrameData,
+        mode: DilationMode,
+        frame: usize,
+        clumps: Option<&Clumps>,
+    ) -> (RgbImage, Option<HdrImage>) {
+        let _span = trace_span!("render", mode = mode.name(), frame).entered();
+        // Each mode replays the same orbit so frame N is framed identically in both.
+        let view = self.camera.orbited(config.orbit * frame as f32);
+        let living = data.living();
+        let mut hdr = None;
+        let mut img = match &data.image {
+            Some(img) => img.clone(),
+            None => {
+                let (settings, is_chronoturin) = (&self.render_settings, mode == DilationMode::Chronoturin);
+                let fill = |fb: &mut Framebuffer, cam: &Camera| {
+                    fb.accumulate_trails(&data.trails, cam, settings, config.trail_decay);
+                    match clumps.filter(|_| config.fof_color) {
+                        Some(clumps) => fb.accumulate_groups(&living, cam, settings, &clumps.labels),
+                        None => fb.accumulate(&living, cam, settings),
+                    }
+                };
+                match config.image_format.is_hdr() {
+                    true => {
+                        let mut fb = Framebuffer::new(view.width, view.height);
+                        fill(&mut fb, &view);
+                        hdr = Some(fb.hdr(settings, is_chronoturin));
+                        fb.tonemap(settings, is_chronoturin)
+                    }
+                    false => render_with(&view, settings, is_chronoturin, fill),
+                }
+            }
+        };
+        draw_tracked(&mut img, &data.stars, &config.track, &view);
+        if !config.no_overlay && !config.preview {
+            let info = OverlayInfo { mode, frame, sim_time: data.sim_time, active_fraction: data.active_fraction() };
+            draw_overlay(&mut img, &info);
+            let settings = &self.render_settings;
+            draw_legend(&mut img, settings.color_by, settings.color_range(&living));
+        }
+        (img, hdr)
+    }
+
+    // Queues a frame file: `hdr` in --image-format when there is one, else `img`.
+    fn save(&mut self, config: &SimConfig, path: PathBuf, img: RgbImage, hdr: Option<HdrImage>) {
+        match hdr {
+            Some(hdr) => self.saver.save_hdr(path, hdr, config.image_format),
+            None => self.saver.save(path, img),
+        }
+    }
+
+    fn record(
+        &mut self,
+        data: &FrameData,
+        mode: DilationMode,
+        frame: usize,
+        wall_ms: f32,
+        error: Option<DisplacementStats>,
+    ) -> Result<(), ChronoError> {
+        // A device that reported an error stops its mode here (--adapters).
+        if let Some(adapter) = self.adapter(mode) {
+            if let Some(message) = adapter.errors.first() {
+                return Err(ChronoError::Device { adapter: adapter.label(), message });
+            }
+        }
+        let adapter = self.adapter(mode).map(|a| a.index);
+        let publish = self.metrics.is_some() || self.serving();
+        let stats = match self.stats.iter_mut().position(|s| s.mode == mode) {
+            Some(i) => &mut self.stats[i],
+            None => {
+                self.stats.push(ModeStats::new(mode));
+                self.stats.last_mut().unwrap()
+            }
+        };
+        if data.watchdog.fired() {
+            if self.on_divergence == OnDivergence::Abort {
+                return Err(ChronoError::Diverged { mode: mode.name(), frame, conditions: data.watchdog.describe() });
+            }
+            // Once a star goes bad its neighbours follow, so each condition is logged once per mode.
+            let fresh: Vec<_> = data.watchdog.conditions().filter(|(c, _)| !stats.divergence_warned.contains(c)).collect();
+            if !fresh.is_empty() {
+                warn!("{} frame {}: divergence watchdog fired: {}", mode.name(), frame, data.watchdog.describe());
+                stats.divergence_warned.extend(fresh.into_iter().map(|(c, _)| c));
+            }
+        }
+        stats.gpu_times.extend(data.gpu_ms);
+        stats.frames_done = if self.continuous { stats.frames_done + 1 } else { frame + 1 };
+        stats.wall_ms += wall_ms as f64;
+        // A placeholder measured nothing; its row only keeps the frames contiguous.
+        if data.placeholder {
+            let record = MetricsRecord {
+                adapter,
+                ..MetricsRecord::placeholder(mode, frame, wall_ms, data.sim_time, (self.dilation_threshold, self.max_debt))
+            };
+            return self.publish(&record);
+        }
+        stats.active_fractions.push(data.active_fraction());
+        if let Some(threads) = data.force_threads {
+            stats.force_threads += threads;
+            stats.star_steps += data.steps as u64 * data.alive as u64;
+        }
+        // Culled stars are gone from every diagnostic. Packed readbacks carry no velocities to
+        // measure, and stats-only frames no stars at all.
+        let living = data.living();
+        let conservation = (!data.packed && !data.stats_only).then(|| conservation::measure(&living, &self.potential));
+        if let Some(conservation) = conservation {
+            stats.first_conservation.get_or_insert(conservation);
+            stats.last_conservation = Some(conservation);
+        }
+        // A sort of every living star, so only for the metrics and --plots that take it.
+        let lagrangian_radii = (publish || self.plots).then(|| self.lagrangian.measure(&living)).flatten();
+        self.telemetry.record_frame(&self.run_id, mode.name(), frame, wall_ms, data.gpu_ms, data.active_fraction());
+        stats.samples.push(FrameSample {
+            frame,
+            wall_ms,
+            gpu_ms: data.gpu_ms,
+            active_fraction: data.active_fraction(),
+            energy_drift: stats.energy_drift(),
+            lagrangian_radii,
+            virial_ratio: conservation.and_then(|c| c.virial_ratio()),
+        });
+        self.readback_ms += data.readback_ms;
+        self.readback_bytes += data.readback_bytes;
+        self.readback_frames += 1;
+        if let Some(moments) = data.moments {
+            let drift = moments.center_drift(stats.first_moments.get_or_insert(moments));
+            stats.center_drift = Some(drift);
+            stats.max_center_drift = stats.max_center_drift.max(drift);
+        }
+        // One binning pass serves both the metrics columns and --profiles.
+        let profiles = (publish || self.write_profiles).then(|| Profiles::measure(&living, &self.profile_settings, !data.packed));
+        if let Some(profiles) = profiles.as_ref().filter(|_| self.write_profiles) {
+            write_profiles(&self.layout.profile_path(mode, frame), profiles)?;
+        }
+        if self.write_kinematics {
+            write_rotation_curve(&self.layout.rotation_path(mode, frame), &RotationCurve::measure(&living, &self.profile_settings))?;
+        }
+        if let Some(trajectories) = &mut self.trajectories {
+            trajectories.write(mode.name(), frame, data.sim_time, &data.stars)?;
+        }
+        if publish {
+            let record = MetricsRecord {
+                mode,
+                frame,
+                wall_ms,
+                gpu_ms: data.gpu_ms,
+                active_count: data.active_per_step().round() as u32,
+                stars: data.stars.len() as u32,
+                sim_time: data.sim_time,
+                error_mean: error.map(|e| e.mean),
+                error_p99: error.map(|e| e.p99),
+                conservation,
+                dilation_threshold: self.dilation_threshold,
+                max_debt: self.max_debt,
+                readback_ms: data.readback_ms,
+                readback_bytes: data.readback_bytes,
+                force_threads: data.force_threads,
+                adapter,
+                active_by_type: active_by_type(&living, self.types),
+                radial_profile: profiles.as_ref().map(|p| p.radial.with_overflow()).unwrap_or_default(),
+                debt_histogram: profiles.as_ref().and_then(|p| p.debt.as_ref()).map(|h| h.with_overflow()).unwrap_or_default(),
+                interactions: self.potential.interactions.values(),
+                alive: data.alive,
+                mergers: data.mergers,
+                substeps: data.substeps,
+                placeholder: false,
+                lagrangian_radii,
+            };
+            self.publish(&record)?;
+        }
+        Ok(())
+    }
+
+    // Whether frame `frame` needs its stars read back: to be drawn, dumped, checkpointed or
+    // searched for clumps, for a diagnostic taken every frame, or as the first or last of a pass
+    // (`edge`), which the summary's drifts and --chain start from. The others bring back only
+    // their stats.
+    fn reads_stars(&self, config: &SimConfig, frame: usize, edge: bool) -> bool {
+        let every_frame = self.metrics.is_some()
+            || self.serving()
+            || self.write_profiles
+            || self.write_kinematics
+            || self.trajectories.is_some()
+            || self.control.is_some()
+            || config.measures_divergence()
+            || config.plots;
+        let dumped = config.dump_every.is_some_and(|every| frame.is_multiple_of(every));
+        let analyzed = config.fof_every.is_some_and(|every| frame.is_multiple_of(every));
+        let checkpointed = config.checkpoint_every.is_some_and(|every| (frame + 1).is_multiple_of(every));
+        edge || every_frame || dumped || analyzed || checkpointed || config.draws_frame(frame)
+    }
+
+    // To --metrics-out and --serve's /metrics.json, whichever are on.
+    fn publish(&mut self, record: &MetricsRecord) -> Result<(), ChronoError> {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.write(record)?;
+        }
+        #[cfg(feature = "serve")]
+        if let Some(server) = &self.server {
+            server.publish_metrics(record.json());
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "serve")]
+    fn serving(&self) -> bool {
+        self.server.is_some()
+    }
+
+    #[cfg(not(feature = "serve"))]
+    fn serving(&self) -> bool {
+        false
+    }
+
+    // --serve: the frame just drawn, encoded on the save pool if anyone is watching. Dropped
+    // when the pool is busy, so the run never waits on the stream.
+    #[cfg(feature = "serve")]
+    fn stream(&mut self, img: &RgbImage) {
+        if let Some(server) = self.server.as_ref().filter(|s| s.streaming()) {
+            let sink = server.frame_sink();
+            self.saver.encode_jpeg(img.clone(), serve::STREAM_QUALITY, move |jpeg| sink.publish(jpeg));
+        }
+    }
+
+    #[cfg(not(feature = "serve"))]
+    fn stream(&mut self, _img: &RgbImage) {}
+
+    fn pipe(&mut self, img: &RgbImage) -> Result<(), ChronoError> {
+        pipe::pipe_frame(&mut self.frames_to, img, self.on_pipe_close)
+    }
+
+    // Progress-line suffix with the latest center-of-mass drift, if --gpu-diagnostics measured one.
+    fn drift_label(&self, mode: DilationMode) -> String {
+        match self.stats.iter().find(|s| s.mode == mode).and_then(|s| s.center_drift) {
+            Some(drift) => format!(" | COM drift: {:.3e}", drift),
+            None => String::new(),
+        }
+    }
+
+    // Every --dump-every frames (starting with frame 0), straight from this frame's readback.
+    fn dump(&self, config: &SimConfig, data: &FrameData, mode: DilationMode, frame: usize) -> Result<(), ChronoError> {
+        match config.dump_every {
+            Some(every) if frame.is_multiple_of(every) && !data.placeholder => {
+                let path = self.layout.dump_path(mode, frame, config.dump_format.extension());
+                let info = DumpInfo { mode: mode.name(), frame, sim_time: data.sim_time };
+                write_dump(&path, &data.living(), data.position_lo.as_deref(), config.dump_format, &info, Some(&self.manifest))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Every --fof-every frames (starting with frame 0), the frame's clumps, their catalog
+    // written. Labels follow data.living(), the stars `render` draws.
+    fn clumps(&self, config: &SimConfig, data: &FrameData, mode: DilationMode, frame: usize) -> Result<Option<Clumps>, ChronoError> {
+        match config.fof_every {
+            Some(every) if frame.is_multiple_of(every) && !data.placeholder => {
+                let clumps = Clumps::find(&data.living(), &FofSettings::from_config(config));
+                write_catalog(&self.layout.clump_path(mode, frame), &clumps)?;
+                let largest = clumps.groups.first().map_or(0, |c| c.members);
+                debug!("{} frame {}: {} clumps, the largest {} stars", mode.name(), frame, clumps.groups.len(), largest);
+                Ok(Some(clumps))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Every --checkpoint-every frames, from the readbacks just finished. `frame` is the last completed one.
+    // `last` writes regardless of --checkpoint-every, for a run stopped early.
+    fn checkpoint(
+        &self,
+        config: &SimConfig,
+        pass: usize,
+        frame: usize,
+        runs: &[(DilationMode, &FrameData)],
+        last: bool,
+    ) -> Result<(), ChronoError> {
+        let Some(every) = config.checkpoint_every else { return Ok(()) };
+        if !last && !(frame + 1).is_multiple_of(every) {
+            return Ok(());
+        }
+        if runs.iter().any(|(_, data)| data.placeholder) {
+            warn!("Frame {} did not read back; no checkpoint is written for it", frame);
+            return Ok(());
+        }
+        if runs.iter().any(|(_, data)| data.stats_only) {
+            warn!("Frame {} brought back only its stats; no checkpoint is written for it", frame);
+            return Ok(());
+        }
+        self.write_checkpoint(config, pass, frame, runs)?;
+        if last {
+            info!("Wrote {} (resume at pass {}, frame {})", self.layout.checkpoint_path().display(), pass + 1, frame + 1);
+        }
+        Ok(())
+    }
+
+    fn write_checkpoint(
+        &self,
+        config: &SimConfig,
+        pass: usize,
+        frame: usize,
+        runs: &[(DilationMode, &FrameData)],
+    ) -> Result<(), ChronoError> {
+        let checkpoint = Checkpoint {
+            seed: self.seed,
+            pass: pass as u32,
+            next_frame: frame as u64 + 1,
+            manifest: Some(self.manifest.clone()),
+            runs: runs
+                .iter()
+                .map(|(mode, data)| CheckpointRun {
+                    mode: *mode,
+                    state: GalaxyState::new(data.sim_time, mode.as_uniform(), config),
+                    stars: data.stars.clone(),
+                })
+                .collect(),
+        };
+        checkpoint.save(&self.layout.checkpoint_path())
+    }
+
+    // Frame `lost` never came back, and the device is past recovering: `runs`, the frames
+    // before it, are saved as the checkpoint, and the error returned names the device it was on.
+    fn lost_frame(
+        &self,
+        config: &SimConfig,
+        pass: usize,
+        lost: usize,
+        runs: &[(DilationMode, &FrameData)],
+        mode: DilationMode,
+        error: ChronoError,
+    ) -> ChronoError {
+        error!("[{}] Frame {:03} did not come back: {}", mode.name(), lost, error);
+        match lost
+            .checked_sub(1)
+            .filter(|_| !runs.is_empty() && runs.iter().all(|(_, data)| !data.packed && !data.placeholder && !data.stats_only))
+        {
+            Some(frame) => match self.write_checkpoint(config, pass, frame, runs) {
+                Ok(()) => error!(
+                    "Saved the state after frame {} to {} (resume at pass {}, frame {})",
+                    frame,
+                    self.layout.checkpoint_path().display(),
+                    pass + 1,
+                    frame + 1
+                ),
+                Err(e) => error!("Could not save the last good state: {}", e),
+            },
+            None => error!("No frame of this run came back whole; any earlier checkpoint is left as it was"),
+        }
+        match self.adapter(mode).and_then(|a| Some(a.label()).zip(a.errors.first())) {
+            Some((adapter, message)) => ChronoError::Device { adapter, message },
+            None => error,
+        }
+    }
+
+    // --control: answers the commands that came in during the frame, and holds the run while
+    // paused. `set` changes `sim`: the pass's, or Chronoturin's side by side. Frames already
+    // submitted step with the old settings.
+    fn control<S: Stepper>(
+        &mut self,
+        config: &SimConfig,
+        sim: &mut S,
+        (pass, frame, frame_ms): (usize, usize, f32),
+        runs: &[(DilationMode, &FrameData)],
+    ) {
+        let Some(mut control) = self.control.take() else { return };
+        let (side, data) = runs[runs.len() - 1];
+        while let Some(command) = control.next(interrupt::requested) {
+            let reply = match command {
+                Err(message) => format!("error: {}", message),
+                Ok(ControlCommand::Pause) => {
+                    info!("Paused after frame {} (--control)", frame);
+                    control.paused = true;
+                    "ok".into()
+                }
+                Ok(ControlCommand::Resume) => {
+                    control.paused = false;
+                    "ok".into()
+                }
+                Ok(ControlCommand::SetThreshold(threshold)) => {
+                    self.dilation_threshold = threshold;
+                    sim.set_dilation(self.dilation_threshold, self.max_debt);
+                    "ok".into()
+                }
+                Ok(ControlCommand::SetMaxDebt(debt)) => {
+                    self.max_debt = debt;
+                    sim.set_dilation(self.dilation_threshold, self.max_debt);
+                    "ok".into()
+                }
+                Ok(ControlCommand::SetMode(mode)) => {
+                    control.stepping = Some(mode);
+                    sim.set_mode(mode);
+                    "ok".into()
+                }
+                Ok(ControlCommand::Snapshot) if runs.iter().any(|(_, data)| data.packed) => {
+                    "error: snapshot needs every star field; run with --readback full".into()
+                }
+                Ok(ControlCommand::Snapshot) => match self.write_checkpoint(config, pass, frame, runs) {
+                    Ok(()) => format!("ok {}", self.layout.checkpoint_path().display()),
+                    Err(e) => format!("error: {}", e),
+                },
+                Ok(ControlCommand::Status) => ControlStatus {
+                    frame,
+                    mode: control.stepping.unwrap_or(side).name(),
+                    sim_time: data.sim_time,
+                    frame_ms,
+                    dilation_threshold: self.dilation_threshold,
+                    max_debt: self.max_debt,
+                    paused: control.paused,
+                }
+                .json(),
+            };
+            control.reply(&reply);
+        }
+        self.control = Some(control);
+    }
+
+    fn add_timing(&mut self, wait_ms: f32, cpu_ms: f32, overlapped: bool) {
+        self.wait_ms += wait_ms;
+        self.cpu_ms += cpu_ms;
+        if overlapped {
+            self.overlapped_ms += cpu_ms;
+        }
+    }
+}
+
+pub(crate) fn active_label(data: &FrameData) -> String {
+    let mergers = data.mergers.map(|n| format!(" | mergers: {}", thousands(n as u64))).unwrap_or_default();
+    format!(
+        "active: {} / {} ({:.1}%){}",
+        thousands(data.active_per_step().round() as u64),
+        thousands(data.alive as u64),
+        data.active_fraction() * 100.0,
+        mergers
+    )
+}
+
+// --verify-determinism: `reference` and a fork of it stepped side by side, once per repeat.
+fn run_verify<S: Stepper>(config: &SimConfig, runs: usize, adapter_name: &str, mut reference: S) -> DeterminismReport {
+    info!("Verify determinism: {} runs x {} frames per mode | Steps/Frame: {}", runs, config.frames, config.steps_per_frame);
+    let mut candidate = reference.fork();
+    let bar = logging::progress_bar((2 * (runs - 1) * config.frames) as u64, "[VERIFY]".into());
+    let report = determinism::verify(&mut reference, &mut candidate, config, runs, adapter_name, |mode, run, _| {
+        bar.set_message(format!("{} run {}/{}", mode.name(), run + 1, runs));
+        bar.inc(1);
+        !interrupt::requested()
+    });
+    bar.finish();
+    for line in report.table().lines() {
+        info!("{}", line);
+    }
+    if interrupt::requested() {
+        warn!("Interrupted: only {} of 2 modes were checked in full", report.modes.len());
+    } else if report.deterministic() {
+        info!("Deterministic: every run of both modes matched bit for bit");
+    } else {
+        warn!("Not deterministic on {}: runs of the same seed diverged", adapter_name);
+    }
+    report
+}
+
+// tune's probes, stepping `newton` and a fork of it; the recommendation is logged, not applied.
+fn run_tune<S: Stepper>(config: &SimConfig, args: &TuneArgs, mut newton: S) -> TuneTrace {
+    info!(
+        "Tune: --dilation-threshold {} to {} | budget {} of the RMS radius | {} probes x {} frames | Steps/Frame: {}",
+        args.min_threshold, args.max_threshold, args.error_budget, args.probes, args.probe_frames, config.steps_per_frame
+    );
+    let mut chrono = newton.fork();
+    let bar = logging::progress_bar(args.probes as u64, "[TUNE]".into());
+    let trace = tune::tune(&mut newton, &mut chrono, config, args, |probe| {
+        let status = format!(
+            "threshold {} | error {:.4} | {:.1}× | active {:.1}%",
+            probe.threshold,
+            probe.error,
+            probe.speedup(),
+            probe.active_fraction * 100.0
+        );
+        info!(target: FRAME_TARGET, "Probe | {} | {}", status, if probe.within(args.error_budget) { "within budget" } else { "over budget" });
+        bar.set_message(status);
+        bar.inc(1);
+        !interrupt::requested()
+    });
+    bar.finish();
+    for line in trace.table().lines() {
+        info!("{}", line);
+    }
+    if let Some(threshold) = trace.recommended {
+        info!("Recommended: --dilation-threshold {} ({} probes)", threshold, trace.probes.len());
+    }
+    if interrupt::requested() {
+        warn!("Interrupted: the search stopped after {} of {} probes", trace.probes.len(), args.probes);
+    }
+    trace
+}
+
+// --- RUN TWO PASSES (Newtonian, then Chronoturin) ---
+fn run_sequential<S: Stepper>(
+    config: &SimConfig,
+    mut sim: S,
+    out: &mut FrameOutput,
+    resume: Option<&Checkpoint>,
+) -> Result<(), ChronoError> {
+    let out = Mutex::new(out);
+    for (pass, mode) in [DilationMode::Newtonian, DilationMode::Chronoturin].into_iter().enumerate() {
+        if resume.is_some_and(|c| (c.pass as usize) > pass) {
+            continue;
+        }
+        if interrupt::requested() {
+            break;
+        }
+        run_pass(config, &mut sim, pass, mode, &out, resume.filter(|c| c.pass as usize == pass), None)?;
+    }
+    Ok(())
+}
+
+// Where --chain's second pass starts: the first's final stars, with the time debt and
+// active flags a fresh galaxy has, and the frame and sim time it stopped at.
+struct ChainStart {
+    stars: Vec<Star>,
+    frame: usize,
+    sim_time: f32,
+}
+
+impl ChainStart {
+    fn new(mut stars: Vec<Star>, frame: usize, sim_time: f32) -> Self {
+        clear_dilation(&mut stars);
+        Self { stars, frame, sim_time }
+    }
+
+    // --chain-from: the run stepped as `first`, if the snapshot has it, numbered on from its frame.
+    fn read(path: &Path, first: DilationMode) -> Result<Self, ChronoError> {
+        let mut runs = Snapshot::read(path)?.runs;
+        let run = runs.iter().position(|run| run.mode == Some(first)).unwrap_or(0);
+        let run = runs.swap_remove(run);
+        info!(
+            "Chaining from {} ({} stars{})",
+            path.display(),
+            run.stars.len(),
+            if run.sim_time.is_none() { ", no sim time recorded" } else { "" }
+        );
+        Ok(Self::new(run.stars, run.frame.map_or(0, |f| f + 1), run.sim_time.unwrap_or(0.0)))
+    }
+}
+
+// --chain: the first pass from the initial galaxy, then the second from where it finished,
+// numbered on from it. With --chain-from the snapshot stands in for the first pass.
+fn run_chain<S: Stepper>(
+    config: &SimConfig,
+    mut sim: S,
+    order: ChainOrder,
+    out: &mut FrameOutput,
+    chain_from: Option<ChainStart>,
+) -> Result<(), ChronoError> {
+    let out = Mutex::new(out);
+    let [first, second] = order.modes();
+    let start = match chain_from {
+        Some(start) => start,
+        None => {
+            let last = run_pass(config, &mut sim, 0, first, &out, None, None)?;
+            match last {
+                Some(last) if !interrupt::requested() => ChainStart::new(last.stars, config.frames, last.sim_time),
+                _ => return Ok(()),
+            }
+        }
+    };
+    info!("Chaining {} on from frame {} (sim time {:.2})", second.name(), start.frame, start.sim_time);
+    run_pass(config, &mut sim, 1, second, &out, None, Some(&start))?;
+    Ok(())
+}
+
+// --sequential with --adapters: both passes at once, each on its own thread and device, into
+// the same outputs. A pass that fails is logged and leaves the other to finish; run() reports
+// it after the summary.
+fn run_parallel<S: Stepper + Send>(config: &SimConfig, newton: S, chrono: S, out: &mut FrameOutput) {
+    // wgpu's GL backend binds its context to one thread, so GL passes take turns instead.
+    let threaded = !out.adapters.iter().any(|a| a.backend == wgpu::Backend::Gl);
+    if !threaded {
+        warn!("the GL backend cannot drive two devices from separate threads; running the passes one after the other.");
+    }
+    let shared = &Mutex::new(&mut *out);
+    let passes = [(DilationMode::Newtonian, newton), (DilationMode::Chronoturin, chrono)].into_iter().enumerate();
+    let results: Vec<_> = if threaded {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = passes
+                .map(|(pass, (mode, mut sim))| (mode, scope.spawn(move || run_pass(config, &mut sim, pass, mode, shared, None, None))))
+                .collect();
+            handles.into_iter().map(|(mode, handle)| (mode, handle.join())).collect()
+        })
+    } else {
+        passes
+            .map(|(pass, (mode, mut sim))| {
+                let run = std::panic::AssertUnwindSafe(|| run_pass(config, &mut sim, pass, mode, shared, None, None));
+                (mode, std::panic::catch_unwind(run))
+            })
+            .collect()
+    };
+    for (mode, result) in results {
+        let error = match result {
+            Ok(Ok(_)) => continue,
+            Ok(Err(error)) => error,
+            Err(_) => ChronoError::Device {
+                adapter: out.adapter(mode).map_or_else(|| mode.name().to_string(), ModeAdapter::label),
+                message: "the pass panicked".into(),
+            },
+        };
+        error!("[{}] Pass failed: {}", mode.name(), error);
+        out.failure.get_or_insert(error);
+    }
+}
+
+// One pass of a sequential run, from `resume` if it stopped in this pass, or from `chain`'s
+// stars and numbered on from its frame. `out` is shared with the other pass's thread under
+// --adapters, and held only while a finished frame is written. Returns the last frame finished.
+fn run_pass<S: Stepper>(
+    config: &SimConfig,
+    sim: &mut S,
+    pass: usize,
+    mode: DilationMode,
+    out: &Mutex<&mut FrameOutput>,
+    resume: Option<&Checkpoint>,
+    chain: Option<&ChainStart>,
+) -> Result<Option<FrameData>, ChronoError> {
+    let lock = || out.lock().unwrap_or_else(PoisonError::into_inner);
+    // --mode-schedule: the one pass switches mode as it goes.
+    let schedule = config.mode_schedule.as_ref();
+    match schedule {
+        Some(schedule) => info!(">> STARTING SCHEDULED RUN: {}", schedule),
+        None => info!(">> STARTING PASS {}: {} MODE", pass + 1, mode.name()),
+    }
+
+    // 2. RESET TO THE SAME INITIAL DATA FOR THIS PASS (or pick up where the checkpoint left off)
+    sim.reset();
+    sim.set_mode(mode);
+    if let Some(control) = &mut lock().control {
+        control.stepping = None;
+    }
+    let mut first_frame = 0;
+    if let Some((checkpoint, run)) = resume.and_then(|c| Some((c, c.run(mode)?))) {
+        sim.restore(run.state, &run.stars);
+        first_frame = checkpoint.next_frame as usize;
+    }
+    if let Some(chain) = chain {
+        sim.restore(GalaxyState::new(chain.sim_time, mode.as_uniform(), config), &chain.stars);
+    }
+    // Output frame numbers; the loop counts this pass's own.
+    let base = chain.map_or(0, |c| c.frame);
+    let camera = lock().camera;
+
+    // --video replaces the PNG sequence for this pass
+    let mut video = match &config.video {
+        Some(path) => Some(VideoEncoder::spawn(
+            &config.ffmpeg,
+            &schedule.map_or_else(|| video_path_for(path, mode), |_| path.clone()),
+            camera.width,
+            camera.height,
+            config.fps,
+        )?),
+        None => None,
+    };
+
+    // Keep up to readback_depth() frames in flight so the GPU computes
+    // frame K+1 while frame K is rasterized and queued for saving.
+    let mut pending = VecDeque::new();
+    let mut submitted = first_frame;
+    let bar =
+        logging::progress_bar(config.frames as u64, if schedule.is_some() { "[SCHEDULE]".into() } else { format!("[{}]", mode.name()) });
+    bar.set_position(first_frame as u64);
+
+    let mut shader_loaded = 0;
+    let mut last = None;
+    let mut last_mode = mode;
+    // The next GIF frame carries the mode label (--save-every may skip the frame that switched)
+    let mut label_gif = true;
+    for local in first_frame..config.frames {
+        let start_time = Instant::now();
+        if let Some(shader) = &mut lock().shader {
+            shader.poll(&mut shader_loaded, &mut [&mut *sim]);
+        }
+
+        while submitted < config.frames && pending.len() < sim.readback_depth() {
+            sim.set_camera(&camera.orbited(config.orbit * (base + submitted) as f32));
+            if let Some(next) = schedule.and_then(|s| s.switch_at(submitted)) {
+                sim.set_mode(next);
+            }
+            let edge = submitted == first_frame || submitted + 1 == config.frames;
+            pending.push_back(match lock().reads_stars(config, base + submitted, edge) {
+                true => sim.submit_frame(config.steps_per_frame),
+                false => sim.submit_stats(config.steps_per_frame),
+            });
+            submitted += 1;
+        }
+        let mut frame_data = sim.finish_frame(pending.pop_front().unwrap());
+        if let Some(error) = sim.fault() {
+            frame_data = match stand_in(config, &error, mode, base + local, frame_data, last.as_ref()) {
+                Some(stand_in) => stand_in,
+                None => {
+                    let runs: Vec<_> = last.iter().map(|data| (last_mode, data)).collect();
+                    return Err(lock().lost_frame(config, pass, base + local, &runs, mode, error));
+                }
+            };
+        }
+        let wait_ms_frame = start_time.elapsed().as_secs_f32() * 1000.0;
+        let frame = base + local;
+        let switched = schedule.and_then(|s| s.switch_at(frame)).is_some();
+        let mode = schedule.map_or(mode, |s| s.mode_at(frame));
+        let mode_name = mode.name();
+        if switched {
+            info!("Frame {:03}: switching to {} (--mode-schedule)", frame, mode_name);
+        }
+        label_gif |= switched;
+        let mut guard = lock();
+        let out = &mut **guard;
+        let cpu_start = Instant::now();
+        let clumps = out.clumps(config, &frame_data, mode, frame)?;
+        if config.draws_frame(frame) {
+            let (img, hdr) = out.render(config, &frame_data, mode, frame, clumps.as_ref());
+            out.stream(&img);
+            out.pipe(&img)?;
+
+            // The first GIF frame of each pass, and of each scheduled mode, carries the mode label to mark the transition.
+            if let Some(gif) = &mut out.gif {
+                gif.add_labeled_frame(&img, label_gif.then_some((mode_name, overlay::mode_color(mode))))?;
+                label_gif = false;
+            }
+            match &mut video {
+                Some(video) => video.write_frame(&img)?,
+                None if config.no_png => {}
+                None if schedule.is_some() => out.save(config, out.layout.schedule_path(frame), img, hdr),
+                None => out.save(config, out.layout.frame_path(mode, frame), img, hdr),
+            }
+        }
+        out.add_timing(wait_ms_frame, cpu_start.elapsed().as_secs_f32() * 1000.0, !pending.is_empty());
+
+        let dur = start_time.elapsed().as_secs_f32() * 1000.0;
+        out.record(&frame_data, mode, frame, dur, None)?;
+        out.dump(config, &frame_data, mode, frame)?;
+        let gpu_label = match frame_data.gpu_ms {
+            Some(gpu_ms) => format!("GPU: {:.2} ms | CPU: {:.1} ms", gpu_ms, dur - gpu_ms),
+            None => format!("Readback Wait: {:.1} ms", wait_ms_frame),
+        };
+        info!(target: FRAME_TARGET, "[{}] Frame {:03} | Sim Time: {:.2} | {}{} | {} | Render Time: {:.0} ms",
+            mode_name, frame, frame_data.sim_time, active_label(&frame_data), out.drift_label(mode), gpu_label, dur);
+        bar.set_message(format!("{:.0} ms/frame | {}{} | {}", dur, active_label(&frame_data), out.drift_label(mode), gpu_label));
+        bar.inc(1);
+        out.control(config, sim, (pass, frame, dur), &[(mode, &frame_data)]);
+        let stop = interrupt::requested();
+        out.checkpoint(config, pass, frame, &[(mode, &frame_data)], stop)?;
+        last = Some(frame_data);
+        last_mode = mode;
+        if stop {
+            break;
+        }
+    }
+    // Frames submitted ahead of an interrupt are read back (and dropped) so no buffer stays mapped.
+    for ticket in pending {
+        sim.finish_frame(ticket);
+    }
+    bar.finish();
+    if let Some(video) = video {
+        info!("Wrote {}", video.finish()?.display());
+    }
+    Ok(last)
+}
+
+// Frame `frame` failed to read back with `error`: a placeholder for it when the run goes on
+// (--on-frame-error), or None to stop. Only failed maps go on; a lost device or a map that
+// never answered would fail every frame after it too.
+fn stand_in(
+    config: &SimConfig,
+    error: &ChronoError,
+    mode: DilationMode,
+    frame: usize,
+    data: FrameData,
+    previous: Option<&FrameData>,
+) -> Option<FrameData> {
+    if !matches!(error, ChronoError::MapFailed(_)) || config.on_frame_error == OnFrameError::Stop {
+        return None;
+    }
+    let written = match (config.on_frame_error, previous) {
+        (OnFrameError::Duplicate, Some(_)) => "the previous frame again",
+        _ => "a black frame",
+    };
+    warn!("[{}] Frame {:03} did not come back ({}); writing {} in its place (--on-frame-error)", mode.name(), frame, error, written);
+    Some(data.stand_in(config.on_frame_error, previous))
+}
+
+// --- RUN BOTH MODES IN LOCKSTEP (Newtonian left, Chronoturin right) ---
+fn run_lockstep<S: Stepper>(
+    config: &SimConfig,
+    newton: S,
+    chrono: S,
+    out: &mut FrameOutput,
+    resume: Option<&Checkpoint>,
+) -> Result<(), ChronoError> {
+    info!(">> STARTING SIDE-BY-SIDE RUN: NEWTONIAN | CHRONOTURIN");
+
+    // Same pipeline, separate buffers, identical initial data.
+    let mut sims = [(DilationMode::Newtonian, newton), (DilationMode::Chronoturin, chrono)];
+    for (mode, sim) in &mut sims {
+        sim.reset();
+        sim.set_mode(*mode);
+        if let Some(run) = resume.and_then(|c| c.run(*mode)) {
+            sim.restore(run.state, &run.stars);
+        }
+    }
+    let first_frame = resume.map_or(0, |c| c.next_frame as usize);
+    let mut video = match &config.video {
+        Some(path) => Some(VideoEncoder::spawn(&config.ffmpeg, path, out.camera.width * 2, out.camera.height, config.fps)?),
+        None => None,
+    };
+
+    let mut pending = [VecDeque::new(), VecDeque::new()];
+    let mut submitted = first_frame;
+    let mut last_error = None;
+    let mut last_frames = Vec::new();
+    let bar = logging::progress_bar(config.frames as u64, "[SIDE-BY-SIDE]".to_string());
+    bar.set_position(first_frame as u64);
+    let mut shader_loaded = 0;
+    for frame in first_frame..config.frames {
+        let start_time = Instant::now();
+        if let Some(shader) = &mut out.shader {
+            let [(_, newton), (_, chrono)] = &mut sims;
+            shader.poll(&mut shader_loaded, &mut [newton, chrono]);
+        }
+
+        while submitted < config.frames && pending[0].len() < sims[0].1.readback_depth() {
+            let edge = submitted == first_frame || submitted + 1 == config.frames;
+            let reads_stars = out.reads_stars(config, submitted, edge);
+            for (queue, (_, sim)) in pending.iter_mut().zip(sims.iter_mut()) {
+                sim.set_camera(&out.camera.orbited(config.orbit * submitted as f32));
+                queue.push_back(match reads_stars {
+                    true => sim.submit_frame(config.steps_per_frame),
+                    false => sim.submit_stats(config.steps_per_frame),
+                });
+            }
+            submitted += 1;
+        }
+        let mut frames = Vec::with_capacity(2);
+        for (queue, (mode, sim)) in pending.iter_mut().zip(sims.iter_mut()) {
+            frames.push((*mode, sim.finish_frame(queue.pop_front().unwrap())));
+        }
+        for (side, (mode, sim)) in sims.iter().enumerate() {
+            let Some(error) = sim.fault() else { continue };
+            let previous = last_frames.get(side).map(|(_, data)| data);
+            match stand_in(config, &error, *mode, frame, frames[side].1.clone(), previous) {
+                Some(stand_in) => frames[side].1 = stand_in,
+                None => {
+                    let runs: Vec<_> = last_frames.iter().map(|(mode, data)| (*mode, data)).collect();
+                    return Err(out.lost_frame(config, 0, frame, &runs, *mode, error));
+                }
+            }
+        }
+        let wait_ms_frame = start_time.elapsed().as_secs_f32() * 1000.0;
+        let cpu_start = Instant::now();
+
+        // Chronoturin's drift from the Newtonian reference, star by star.
+        let measured = !frames.iter().any(|(_, data)| data.placeholder);
+        let error = (config.measures_divergence() && measured).then(|| displacement_stats(&frames[0].1.stars, &frames[1].1.stars));
+        if config.diff && measured && !config.no_images && frame % config.save_every == 0 {
+            let view = out.camera.orbited(config.orbit * frame as f32);
+            let diff = render_diff(&frames[0].1.stars, &frames[1].1.stars, &view, config.diff_scale);
+            out.saver.save(out.layout.diff_path(frame), diff);
+        }
+        let clumps = [out.clumps(config, &frames[0].1, frames[0].0, frame)?, out.clumps(config, &frames[1].1, frames[1].0, frame)?];
+        if config.draws_frame(frame) {
+            let (left, left_hdr) = out.render(config, &frames[0].1, frames[0].0, frame, clumps[0].as_ref());
+            let (right, right_hdr) = out.render(config, &frames[1].1, frames[1].0, frame, clumps[1].as_ref());
+            let img = side_by_side(&left, &right);
+            out.stream(&img);
+            out.pipe(&img)?;
+
+            if let Some(gif) = &mut out.gif {
+                gif.add_frame(&img)?;
+            }
+            match &mut video {
+                Some(video) => video.write_frame(&img)?,
+                None if config.no_png => {}
+                // Float frames stay one file per mode, so the two can be compared pixel by pixel.
+                None if config.image_format.is_hdr() => {
+                    for ((mode, _), hdr) in frames.iter().zip([left_hdr, right_hdr]) {
+                        out.save(config, out.layout.frame_path(*mode, frame), img.clone(), hdr);
+                    }
+                }
+                None => out.saver.save(out.layout.comparison_path(frame), img),
+            }
+        }
+        out.add_timing(wait_ms_frame, cpu_start.elapsed().as_secs_f32() * 1000.0, !pending[0].is_empty());
+
+        let dur = start_time.elapsed().as_secs_f32() * 1000.0;
+        for (mode, data) in &frames {
+            out.record(data, *mode, frame, dur, error.filter(|_| *mode == DilationMode::Chronoturin))?;
+            out.dump(config, data, *mode, frame)?;
+        }
+        let error_label = error.map_or(String::new(), |e| format!(" | Error: mean {:.3} p99 {:.3}", e.mean, e.p99));
+        let status = format!(
+            "[{}] {}{} | [{}] {}{}{}",
+            frames[0].0.name(),
+            active_label(&frames[0].1),
+            out.drift_label(frames[0].0),
+            frames[1].0.name(),
+            active_label(&frames[1].1),
+            out.drift_label(frames[1].0),
+            error_label
+        );
+        info!(target: FRAME_TARGET, "Frame {:03} | Sim Time: {:.2} | {} | Render Time: {:.0} ms", frame, frames[0].1.sim_time, status, dur);
+        bar.set_message(format!("{:.0} ms/frame | {}", dur, status));
+        bar.inc(1);
+        let runs = [(frames[0].0, &frames[0].1), (frames[1].0, &frames[1].1)];
+        out.control(config, &mut sims[1].1, (0, frame, dur), &runs);
+        let stop = interrupt::requested();
+        out.checkpoint(config, 0, frame, &runs, stop)?;
+        last_error = error;
+        // The report's final divergence, measured even without --diff.
+        if (stop || frame + 1 == config.frames) && !frames.iter().any(|(_, data)| data.stats_only) {
+            out.divergence = Some(error.unwrap_or_else(|| displacement_stats(&frames[0].1.stars, &frames[1].1.stars)));
+        }
+        last_frames = frames;
+        if stop {
+            break;
+        }
+    }
+    for (queue, (_, sim)) in pending.iter_mut().zip(sims.iter_mut()) {
+        for ticket in queue.drain(..) {
+            sim.finish_frame(ticket);
+        }
+    }
+    bar.finish();
+    if let Some(video) = video {
+        info!("Wrote {}", video.finish()?.display());
+    }
+    if let Some(e) = last_error {
+        info!(
+            "[DIFF] Final frame displacement vs Newtonian: mean {:.4} | p99 {:.4} | max {:.4}{}",
+            e.mean,
+            e.p99,
+            e.max,
+            if e.non_finite > 0 { format!(" | {} non-finite", e.non_finite) } else { String::new() }
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use image::RgbImage;
+
+use crate::error::ChronoError;
+use crate::hdr::{save_exr, HdrImage, ImageFormat};
+use crate::manifest::{save_png, save_png16, RunManifest};
+
+// --preview frames, saved as .jpg
+const PREVIEW_QUALITY: u8 = 85;
+
+enum SaveJob {
+    Png(PathBuf, RgbImage),
+    // --image-format png16 or exr
+    Hdr(PathBuf, Box<HdrImage>, ImageFormat),
+    // Encoded at this quality and handed to the callback
+    Jpeg(RgbImage, u8, Box<dyn FnOnce(Vec<u8>) + Send>),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SaveStats {
+    pub frames_saved: usize,
+    pub failures: usize,
+    // Time the render loop spent waiting for a free queue slot
+    pub blocked: Duration,
+}
+
+// Encodes and writes frames on worker threads. The queue is bounded, so a slow
+// disk makes `save` block (backpressure) instead of piling images up in memory. Every PNG
+// carries `manifest` in a text chunk; a path ending in .jpg is written as a JPEG, without one.
+// Each file's size and encode time are logged at debug level.
+pub struct FrameSaver {
+    sender: Option<SyncSender<SaveJob>>,
+    workers: Vec<JoinHandle<(usize, usize)>>,
+    blocked: Duration,
+    // Frames on disk so far, for Telemetry::count_images
+    written: Arc<AtomicU64>,
+}
+
+impl FrameSaver {
+    pub fn new(threads: usize, queue_depth: usize, manifest: Option<RunManifest>) -> Self {
+        let (sender, receiver) = sync_channel::<SaveJob>(queue_depth);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let manifest = Arc::new(manifest);
+        let written = Arc::new(AtomicU64::new(0));
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let (receiver, manifest, written) = (receiver.clone(), manifest.clone(), written.clone());
+                std::thread::Builder::new()
+                    .name(format!("png-saver-{}", i))
+                    .spawn(move || worker(receiver, &manifest, &written))
+                    .expect("Failed to spawn save worker")
+            })
+            .collect();
+        Self { sender: Some(sender), workers, blocked: Duration::ZERO, written }
+    }
+
+    pub fn written(&self) -> Arc<AtomicU64> {
+        self.written.clone()
+    }
+
+    pub fn save(&mut self, path: PathBuf, img: RgbImage) {
+        let start = Instant::now();
+        if let Some(sender) = &self.sender {
+            // Only fails if every worker died, in which case finish() reports it.
+            let _ = sender.send(SaveJob::Png(path, img));
+        }
+        self.blocked += start.elapsed();
+    }
+
+    // Writes `img` in `format`, png16 or exr, like save().
+    pub fn save_hdr(&mut self, path: PathBuf, img: HdrImage, format: ImageFormat) {
+        let start = Instant::now();
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(SaveJob::Hdr(path, Box::new(img), format));
+        }
+        self.blocked += start.elapsed();
+    }
+
+    // Encodes `img` as a JPEG on a worker for `done`, unless the queue is full: then the
+    // image is dropped, and false returned, rather than making the caller wait (--serve).
+    pub fn encode_jpeg(&mut self, img: RgbImage, quality: u8, done: impl FnOnce(Vec<u8>) + Send + 'static) -> bool {
+        match &self.sender {
+            Some(sender) => !matches!(sender.try_send(SaveJob::Jpeg(img, quality, Box::new(done))), Err(TrySendError::Full(_))),
+            None => false,
+        }
+    }
+
+    // Waits for every queued frame to hit the disk.
+    pub fn finish(mut self) -> SaveStats {
+        drop(self.sender.take());
+        let mut stats = SaveStats { blocked: self.blocked, ..SaveStats::default() };
+        for handle in self.workers.drain(..) {
+            let (saved, failed) = handle.join().unwrap_or((0, 1));
+            stats.frames_saved += saved;
+            stats.failures += failed;
+        }
+        stats
+    }
+}
+
+fn worker(receiver: Arc<Mutex<Receiver<SaveJob>>>, manifest: &Option<RunManifest>, written: &AtomicU64) -> (usize, usize) {
+    let (mut saved, mut failed) = (0, 0);
+    loop {
+        let job = receiver.lock().unwrap().recv();
+        let (path, img) = match job {
+            Ok(SaveJob::Png(path, img)) => (path, Err(img)),
+            Ok(SaveJob::Hdr(path, img, format)) => (path, Ok((img, format))),
+            Ok(SaveJob::Jpeg(img, quality, done)) => {
+                let _span = tracing::trace_span!("encode_jpeg").entered();
+                let mut jpeg = Vec::new();
+                match image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality).encode_image(&img) {
+                    Ok(()) => done(jpeg),
+                    Err(e) => tracing::warn!("Failed to encode a JPEG: {}", e),
+                }
+                continue;
+            }
+            Err(_) => break,
+        };
+        let _span = tracing::trace_span!("save_png", path = %path.display()).entered();
+        let start = Instant::now();
+        let saved_as = match img {
+            Ok((img, ImageFormat::Exr)) => save_exr(&path, &img, manifest.as_ref()),
+            Ok((img, _)) => save_png16(&path, &img.to_png16(), manifest.as_ref()),
+            Err(img) if path.extension().is_some_and(|e| e == "jpg") => save_jpeg(&path, &img),
+            Err(img) => save_png(&path, &img, manifest.as_ref()),
+        };
+        match saved_as {
+            Ok(()) => {
+                let bytes = std::fs::metadata(&path).map_or(0, |m| m.len());
+                tracing::debug!(
+                    "Wrote {} ({:.1} KiB) in {:.1} ms",
+                    path.display(),
+                    bytes as f64 / 1024.0,
+                    start.elapsed().as_secs_f64() * 1000.0
+                );
+                saved += 1;
+                written.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to save {}", e);
+                failed += 1;
+            }
+        }
+    }
+    (saved, failed)
+}
+
+fn save_jpeg(path: &Path, img: &RgbImage) -> Result<(), ChronoError> {
+    let error = |message: String| ChronoError::Parse { path: path.to_path_buf(), line: 0, message };
+    let file = BufWriter::new(File::create(path).map_err(|e| error(e.to_string()))?);
+    image::codecs::jpeg::JpegEncoder::new_with_quality(file, PREVIEW_QUALITY).encode_image(img).map_err(|e| error(e.to_string()))
+}
@@ -0,0 +1,69 @@
+use crate::simulation::DilationMode;
+
+// `--chain`: which pass runs first, the other starting from where it finished.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChainOrder {
+    NewtonFirst,
+    ChronoFirst,
+}
+
+impl ChainOrder {
+    pub fn modes(self) -> [DilationMode; 2] {
+        match self {
+            ChainOrder::NewtonFirst => [DilationMode::Newtonian, DilationMode::Chronoturin],
+            ChainOrder::ChronoFirst => [DilationMode::Chronoturin, DilationMode::Newtonian],
+        }
+    }
+}
+
+// `--mode-schedule 0:newton,75:chrono`: the mode one continuous run steps as from each frame
+// on. Starts at frame 0, frames strictly increasing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModeSchedule(Vec<(usize, DilationMode)>);
+
+impl ModeSchedule {
+    pub fn mode_at(&self, frame: usize) -> DilationMode {
+        self.0.iter().rev().find(|(start, _)| *start <= frame).map_or(self.0[0].1, |(_, mode)| *mode)
+    }
+
+    // The mode `frame` switches to, if it is a scheduled frame after the first.
+    pub fn switch_at(&self, frame: usize) -> Option<DilationMode> {
+        self.0.iter().skip(1).find(|(start, _)| *start == frame).map(|(_, mode)| *mode)
+    }
+
+    pub fn last_frame(&self) -> usize {
+        self.0[self.0.len() - 1].0
+    }
+
+    pub fn entries(&self) -> &[(usize, DilationMode)] {
+        &self.0
+    }
+}
+
+// As parse_mode_schedule reads it.
+impl std::fmt::Display for ModeSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries: Vec<String> = self.0.iter().map(|(frame, mode)| format!("{}:{}", frame, mode.file_stem())).collect();
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+pub fn parse_mode_schedule(arg: &str) -> Result<ModeSchedule, String> {
+    let mut entries: Vec<(usize, DilationMode)> = Vec::new();
+    for entry in arg.split(',').map(str::trim) {
+        let (frame, mode) = entry.split_once(':').ok_or_else(|| format!("\"{}\" is not FRAME:MODE, as in 75:chrono", entry))?;
+        let frame = frame.trim().parse::<usize>().map_err(|e| format!("\"{}\": {}", frame.trim(), e))?;
+        let mode = match mode.trim().to_ascii_lowercase().as_str() {
+            "newton" | "newtonian" => DilationMode::Newtonian,
+            "chrono" | "chronoturin" => DilationMode::Chronoturin,
+            other => return Err(format!("\"{}\" is not a mode (newton or chrono)", other)),
+        };
+        match entries.last() {
+            None if frame != 0 => return Err(format!("the schedule must start at frame 0, not {}", frame)),
+            Some((last, _)) if *last == frame => return Err(format!("frame {} is scheduled twice", frame)),
+            Some((last, _)) if *last > frame => return Err(format!("frame {} comes after frame {}; list frames in order", frame, last)),
+            _ => entries.push((frame, mode)),
+        }
+    }
+    Ok(ModeSchedule(entries))
+}
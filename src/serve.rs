@@ -0,0 +1,177 @@
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+use crate::telemetry::Telemetry;
+
+// JPEG quality of the /stream parts.
+pub const STREAM_QUALITY: u8 = 80;
+const BOUNDARY: &str = "chronoturin-frame";
+// How often a waiting stream checks whether the run has ended.
+const POLL: Duration = Duration::from_millis(200);
+
+const INDEX: &str = "<!doctype html><title>Chronoturin</title>\
+<body style=\"margin:0;background:#000\"><img src=\"/stream\" style=\"width:100%\"></body>\n";
+
+// What the run publishes and the connections read. Only the latest frame is kept, so a slow
+// client skips frames instead of holding the run up.
+#[derive(Default)]
+struct Shared {
+    // A counter bumped per frame, so each stream sends a frame once
+    frame: Mutex<(u64, Option<Arc<Vec<u8>>>)>,
+    fresh: Condvar,
+    metrics: Mutex<Option<String>>,
+    telemetry: Telemetry,
+    streams: AtomicUsize,
+    stopping: AtomicBool,
+}
+
+// `--serve ADDR`: `/stream` is an MJPEG stream (multipart/x-mixed-replace) of the latest frame,
+// `/metrics.json` the latest metrics record, `/metrics` the Telemetry for Prometheus, and `/` a
+// page showing the stream. Requests are
+// answered on threads of their own; dropping the server stops it and ends every stream.
+pub struct FrameServer {
+    server: Arc<Server>,
+    shared: Arc<Shared>,
+    accept: Option<JoinHandle<()>>,
+}
+
+// Hands an encoded frame from the save pool to the server.
+#[derive(Clone)]
+pub struct FrameSink(Arc<Shared>);
+
+impl FrameSink {
+    pub fn publish(&self, jpeg: Vec<u8>) {
+        let mut frame = self.0.frame.lock().unwrap();
+        *frame = (frame.0 + 1, Some(Arc::new(jpeg)));
+        self.0.fresh.notify_all();
+    }
+}
+
+impl FrameServer {
+    pub fn start(addr: &str, telemetry: Telemetry) -> Result<Self, ChronoError> {
+        let server = Server::http(addr).map_err(|e| ChronoError::InvalidConfig(format!("--serve {}: {}", addr, e)))?;
+        let shared = Shared { telemetry, ..Shared::default() };
+        let (server, shared) = (Arc::new(server), Arc::new(shared));
+        let accept = {
+            let (server, shared) = (server.clone(), shared.clone());
+            std::thread::Builder::new()
+                .name("serve".into())
+                .spawn(move || {
+                    for request in server.incoming_requests() {
+                        respond(request, &shared);
+                    }
+                })
+                .expect("Failed to spawn the --serve thread")
+        };
+        Ok(Self { server, shared, accept: Some(accept) })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    // Whether anyone is watching /stream, so frames are only encoded for someone.
+    pub fn streaming(&self) -> bool {
+        self.shared.streams.load(Ordering::Relaxed) > 0
+    }
+
+    pub fn frame_sink(&self) -> FrameSink {
+        FrameSink(self.shared.clone())
+    }
+
+    pub fn publish_metrics(&self, json: String) {
+        *self.shared.metrics.lock().unwrap() = Some(json);
+    }
+}
+
+// --serve ADDR, answering /metrics from `telemetry`.
+pub fn start(config: &SimConfig, telemetry: &Telemetry) -> Result<Option<FrameServer>, ChronoError> {
+    let Some(addr) = &config.serve else { return Ok(None) };
+    let server = FrameServer::start(addr, telemetry.clone())?;
+    let addr = server.local_addr().map_or_else(|| addr.clone(), |a| a.to_string());
+    tracing::info!("Serving http://{}/stream, http://{}/metrics.json and http://{}/metrics", addr, addr, addr);
+    Ok(Some(server))
+}
+
+impl Drop for FrameServer {
+    // Streams end within POLL of this. They are not joined: one blocked writing to a client
+    // that stopped reading would hold the run's exit up.
+    fn drop(&mut self) {
+        self.shared.stopping.store(true, Ordering::Relaxed);
+        self.shared.fresh.notify_all();
+        self.server.unblock();
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+    }
+}
+
+fn respond(request: Request, shared: &Arc<Shared>) {
+    let path = request.url().split('?').next().unwrap_or_default().to_string();
+    let content_type = |value: &str| Header::from_bytes("Content-Type", value).expect("a valid header");
+    let result = match path.as_str() {
+        "/" => request.respond(Response::from_string(INDEX).with_header(content_type("text/html"))),
+        "/metrics.json" => {
+            // null until the first frame is recorded
+            let json = shared.metrics.lock().unwrap().clone().unwrap_or_else(|| "null".into());
+            request.respond(Response::from_string(json).with_header(content_type("application/json")))
+        }
+        "/metrics" => {
+            let text = shared.telemetry.render();
+            request.respond(Response::from_string(text).with_header(content_type("text/plain; version=0.0.4")))
+        }
+        "/stream" => {
+            let shared = shared.clone();
+            let spawned = std::thread::Builder::new().name("serve-stream".into()).spawn(move || {
+                shared.streams.fetch_add(1, Ordering::Relaxed);
+                let _ = stream(request.into_writer(), &shared);
+                shared.streams.fetch_sub(1, Ordering::Relaxed);
+            });
+            spawned.map(drop)
+        }
+        _ => request.respond(Response::from_string("not found\n").with_status_code(404)),
+    };
+    if let Err(e) = result {
+        tracing::debug!("--serve {}: {}", path, e);
+    }
+}
+
+// Writes each new frame as one part until the run ends or the client goes away.
+fn stream(mut out: Box<dyn Write + Send>, shared: &Shared) -> std::io::Result<()> {
+    write!(
+        out,
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        BOUNDARY
+    )?;
+    out.flush()?;
+    let mut sent = 0;
+    loop {
+        let jpeg = {
+            let mut frame = shared.frame.lock().unwrap();
+            while frame.0 == sent && !shared.stopping.load(Ordering::Relaxed) {
+                frame = shared.fresh.wait_timeout(frame, POLL).unwrap().0;
+            }
+            // Stopping; a frame published just before is still sent.
+            if frame.0 == sent {
+                break;
+            }
+            sent = frame.0;
+            frame.1.clone()
+        };
+        let Some(jpeg) = jpeg else { continue };
+        write!(out, "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n", BOUNDARY, jpeg.len())?;
+        out.write_all(&jpeg)?;
+        out.write_all(b"\r\n")?;
+        out.flush()?;
+    }
+    write!(out, "--{}--\r\n", BOUNDARY)?;
+    out.flush()
+}
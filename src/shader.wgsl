@@ -12,26 +12,420 @@ struct Star {
 struct GalaxyState {
     time_seed: f32, 
     dilation_mode: f32, // 0.0 = NEWTONIAN, 1.0 = CHRONOTURIN
-    padding2: f32, padding3: f32,
+    softening: f32, // distances are sqrt(d^2 + softening^2)
+    g_const: f32,
+    dt: f32,
+    damping: f32, // velocity factor per step
+    integrator: f32, // 0.0 = EULER, 1.0 = LEAPFROG
+    first_step: f32,
+    dilation_threshold: f32, // CHRONOTURIN: mean tension below this sleeps
+    max_time_debt: f32, // a sleeping star works once every max_time_debt dispatches
+    drag: f32, // deceleration per unit of velocity
+    external_kind: f32, // 0.0 = none, 1.0 = halo, 2.0 = harmonic (see external.rs)
+    external_scale: f32,
+    external_strength: f32,
+    boundary_kind: f32, // 0.0 = open, 1.0 = reflect, 2.0 = periodic (see boundary.rs)
+    boundary_size: f32,
+    max_velocity: f32, // speeds are clamped to this after every kick; 0.0 = no clamp
+    max_substeps: f32, // a star may split its step into up to this many substeps
+    pad0: f32,
+    pad1: f32,
 };
 
-@group(0) @binding(0) var<storage, read_write> stars: array<Star>;
+// Which slice of the population this dispatch updates (see chunks.rs)
+struct Chunk {
+    offset: u32, // global index of this dispatch's first star
+    count: u32, // stars in this chunk
+    stride: u32, // stars per chunk; global index i lives in chunk i / stride
+    total: u32, // stars across all chunks
+};
+
+// Up to four star buffers; unused ones are bound to a one-star placeholder.
+@group(0) @binding(0) var<storage, read_write> stars0: array<Star>;
 @group(0) @binding(1) var<uniform> state: GalaxyState;
+// Star updates performed since the host last cleared them, and the substeps they took
+struct Counters {
+    updates: atomic<u32>,
+    substeps: atomic<u32>,
+};
+
+@group(0) @binding(2) var<storage, read_write> counters: Counters;
+@group(0) @binding(3) var<uniform> chunk: Chunk;
+@group(0) @binding(4) var<storage, read_write> stars1: array<Star>;
+@group(0) @binding(5) var<storage, read_write> stars2: array<Star>;
+@group(0) @binding(6) var<storage, read_write> stars3: array<Star>;
+
+// --accel grid only (see grid.rs): the cells grid.wgsl binned the stars into this step
+struct Grid {
+    cube: vec4<f32>, // xyz: lowest corner of the grid, w: its side
+    side: u32, // cells per axis
+    cells: u32, // side^3
+    total: u32,
+    stride: u32,
+};
+
+struct Cell {
+    a: vec4<f32>, // centroid and count of the cell's TYPE_A stars
+    b: vec4<f32>, // the same for TYPE_B
+    start: u32, // first entry of the cell in bins' second half
+    count: u32,
+    fill: u32,
+    pad0: u32,
+};
+
+@group(0) @binding(7) var<uniform> grid: Grid;
+@group(0) @binding(8) var<storage, read> cells: array<Cell>;
+// The cell of each star, then every binned star's index grouped by cell
+@group(0) @binding(9) var<storage, read> bins: array<u32>;
+
+// --compaction only (see compaction.rs): the stars that work this step, appended by accrue
+struct ActiveList {
+    count: atomic<u32>, // appended so far this step
+    frozen: u32, // this step's final count, for the force pass
+    indices: array<u32>,
+};
+
+@group(0) @binding(10) var<storage, read_write> active_list: ActiveList;
+// 1 where the star's tension was below the threshold when it last worked
+@group(0) @binding(11) var<storage, read_write> calm: array<u32>;
+// The force pass's indirect dispatch (x, y, z workgroups), then the invocations it
+// launched summed since the host last cleared it
+@group(0) @binding(12) var<storage, read_write> dispatch_args: array<u32, 4>;
+
+// --interactions (see interactions.rs): the coefficient type `on` feels from type `by` is
+// entry on * MAX_TYPES + by, row-major, four to a vec4.
+struct Interactions {
+    coefficients: array<vec4<f32>, 64>,
+};
+
+@group(0) @binding(13) var<uniform> interactions: Interactions;
+
+const MAX_TYPES: u32 = 16u;
+
+// --kill-radius only (see cull.rs): the living stars occupy global indices 0 to alive - 1,
+// and cull_args cuts each chunk's Chunk and force-pass dispatch down to them.
+struct Culling {
+    radius: f32,
+    chunks: u32, // star buffers in use
+    alive: atomic<u32>, // the stars cull kept
+    total: u32, // every star, dead or alive
+    params: array<Chunk, 4>,
+    dispatch: array<vec4<u32>, 4>, // x, y, z workgroups and a pad
+};
+
+@group(0) @binding(14) var<storage, read_write> culling: Culling;
+
+// --merge-radius only (see merge.rs)
+struct Merging {
+    radius: f32,
+    mergers: atomic<u32>, // since the stars were uploaded
+    pad0: u32,
+    pad1: u32,
+};
+
+@group(0) @binding(15) var<storage, read_write> merging: Merging;
+// 1 for each star a merge has taken this pass, survivor or not
+@group(0) @binding(16) var<storage, read_write> claims: array<atomic<u32>>;
+
+// The divergence watchdog (see watchdog.rs)
+struct Watchdog {
+    bound: f32, // --divergence-bound
+    nan_position: atomic<u32>,
+    nan_velocity: atomic<u32>,
+    far_position: atomic<u32>,
+    fast_velocity: atomic<u32>,
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
+};
+
+@group(0) @binding(17) var<storage, read_write> watchdog: Watchdog;
+
+fn coefficient(on: f32, by: f32) -> f32 {
+    let k = u32(on + 0.5) * MAX_TYPES + u32(by + 0.5);
+    return interactions.coefficients[k / 4u][k % 4u];
+}
 
-const G: f32 = 0.5;
-const DT: f32 = 0.05;
 const SAMPLES: u32 = 32; 
 
-fn rand(co: vec2<f32>) -> f32 {
-    return fract(sin(dot(co, vec2<f32>(12.9898, 78.233))) * 43758.5453);
+// Set from --workgroup-size when the pipeline is built (see simulation::shader_source).
+const WORKGROUP_SIZE: u32 = 256u;
+
+// The dilation mode a pipeline is specialized to (see simulation::kernel_source):
+// 0 NEWTONIAN, 1 CHRONOTURIN, or 2 to read state.dilation_mode (--unified-kernel).
+const KERNEL_MODE: u32 = 2u;
+
+fn chronoturin() -> bool {
+    if (KERNEL_MODE == 2u) {
+        return state.dilation_mode > 0.5;
+    }
+    return KERNEL_MODE == 1u;
 }
 
-@compute @workgroup_size(256)
-fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
-    let index = global_id.x;
-    if (index >= arrayLength(&stars)) { return; }
+// PCG integer hash. Unlike fract(sin(x) * 43758.5453) it gives the same bits on every GPU
+// and on the CPU, so cpu_reference.rs can pick exactly the same partners.
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Partner for sample `i` of star `index` at this step's clock.
+fn sample_index(index: u32, i: u32, n: u32) -> u32 {
+    return pcg(index ^ pcg(bitcast<u32>(state.time_seed) ^ pcg(i))) % n;
+}
+
+// Star `index` of the whole population, whichever buffer holds it.
+fn load_star(index: u32) -> Star {
+    let c = index / chunk.stride;
+    let i = index - c * chunk.stride;
+    switch c {
+        case 0u: { return stars0[i]; }
+        case 1u: { return stars1[i]; }
+        case 2u: { return stars2[i]; }
+        default: { return stars3[i]; }
+    }
+}
+
+fn store_star(index: u32, star: Star) {
+    let c = index / chunk.stride;
+    let i = index - c * chunk.stride;
+    switch c {
+        case 0u: { stars0[i] = star; }
+        case 1u: { stars1[i] = star; }
+        case 2u: { stars2[i] = star; }
+        default: { stars3[i] = star; }
+    }
+}
+
+// The central mass is always felt. Each sample of main's loop stands in for (n - 1) / SAMPLES
+// stars, so one star of mass 1 pulls with G * 500 * SAMPLES / (n - 1).
+fn central_pull(star: Star, n: u32) -> vec3<f32> {
+    if (load_star(0u).data_type > -0.5) {
+        return vec3<f32>(0.0);
+    }
+    let soft_sq = state.softening * state.softening;
+    let center = load_star(0u);
+    let dx = center.x - star.x;
+    let dy = center.y - star.y;
+    let dz = center.z - star.z;
+    let dist_sq = dx*dx + dy*dy + dz*dz + soft_sq;
+    let dist = sqrt(dist_sq);
+    let f = (state.g_const * 500.0 * f32(SAMPLES) * center.mass / f32(max(n - 1u, 1u))) / dist_sq;
+    return vec3<f32>(f * (dx / dist), f * (dy / dist), f * (dz / dist));
+}
+
+// One sample of main's loop for `weight` stars of type `other_type` at `other`:
+// the force in xyz and the tension in w, both unscaled.
+fn pull(star: Star, other: vec3<f32>, other_type: f32, weight: f32) -> vec4<f32> {
+    let d = nearest_image(other - vec3<f32>(star.x, star.y, star.z));
+    let dist_sq = dot(d, d) + state.softening * state.softening;
+    var tension = 0.0;
+    if (abs(star.data_type - other_type) > 0.1) {
+        tension = 10000.0 / dist_sq;
+    }
+    let f = state.g_const * 500.0 * coefficient(star.data_type, other_type) / dist_sq;
+    return weight * vec4<f32>(f * d / sqrt(dist_sq), tension);
+}
+
+// Every other star's pull, summed and scaled to what main's SAMPLES random partners
+// average to, so all three --accel kernels step the same model.
+fn expected(sum: vec4<f32>, star: Star, n: u32) -> vec4<f32> {
+    let others = f32(max(n - 1u, 1u));
+    return vec4<f32>(sum.xyz * (f32(SAMPLES) / others) + central_pull(star, n), sum.w / others);
+}
+
+// Steps 2 and 3 of every --accel kernel; `tension` is the mean over partners.
+fn advance(index: u32, before: Star, force: vec3<f32>, tension: f32) {
+    var star = before;
+
+    // A Newtonian kernel steps every star: no debt to keep, and the flag is always set.
+    if (KERNEL_MODE == 0u) {
+        if (state.integrator < 0.5) {
+            star.vx *= state.damping; star.vy *= state.damping; star.vz *= state.damping;
+        }
+        star.active_flag = 1.0;
+        atomicAdd(&counters.updates, 1u);
+        let substeps = substep_count(star, force);
+        atomicAdd(&counters.substeps, substeps);
+        store_star(index, kick_drift(index, star, force, substeps));
+        return;
+    }
+
+    // 2. APPLY MODE LOGIC
+    var dilation = 1.0; 
+
+    if (chronoturin()) {
+        // --- CHRONOTURIN MODE (Smart) ---
+        if (tension < state.dilation_threshold) { 
+            dilation = 1.0 / state.max_time_debt; // Sleep (98% Savings at the default 50)
+        }
+    } else {
+        // --- NEWTONIAN MODE (Dumb) ---
+        // Always 1.0. Never sleep.
+        dilation = 1.0;
+    }
+
+    // 3. PHYSICS UPDATE
+    if (state.integrator < 0.5) {
+        star.vx *= state.damping; star.vy *= state.damping; star.vz *= state.damping;
+    }
+    star.time_debt += dilation;
+
+    // Default to "Sleeping" state for visualization
+    star.active_flag = 0.0; 
+
+    if (star.time_debt >= 1.0) {
+        star.time_debt -= 1.0;
+        
+        // Mark as ACTIVE (Hot!)
+        star.active_flag = 1.0; 
+        atomicAdd(&counters.updates, 1u);
+
+        let substeps = substep_count(star, force);
+        atomicAdd(&counters.substeps, substeps);
+        star = kick_drift(index, star, force, substeps);
+    }
+
+    store_star(index, star);
+}
+
+// --drag and --external-potential: felt by every star that moves, in either dilation mode.
+fn external_pull(star: Star) -> vec3<f32> {
+    let r = vec3<f32>(star.x, star.y, star.z);
+    var k = 0.0;
+    if (state.external_kind > 1.5) {
+        k = state.external_strength;
+    } else if (state.external_kind > 0.5) {
+        k = state.external_strength / (dot(r, r) + state.external_scale * state.external_scale);
+    }
+    return -k * r - state.drag * vec3<f32>(star.vx, star.vy, star.vz);
+}
+
+// --precision df64's drift of one coordinate (see precision.rs): adds `dx` to hi + lo with
+// a two-sum, so what each step's rounding drops is carried in lo instead of lost. Drivers
+// may rewrite (a + b) - a as b, which cancels the sum to nothing, so every rounded sum it
+// measures is multiplied by a 1.0 they cannot see through.
+fn compensated_add(hi: ptr<function, f32>, lo: ptr<function, f32>, dx: f32) {
+    let one = f32(min(chunk.stride, 1u));
+    let s = (*hi + dx) * one;
+    let b = (s - *hi) * one;
+    let e = (*hi - (s - b)) + (dx - b) + *lo;
+    *hi = (s + e) * one;
+    *lo = e - (*hi - s);
+}
+
+// LEAPFROG: velocities live at half steps, so the pass's first kick is half long and
+// consecutive kick-drift-kick halves merge into one full kick per dispatch. No damping.
+// A star given several substeps takes that many kick-drift pairs of dt / substeps, with
+// the force re-evaluated before each kick after the first; leapfrog then closes with the
+// kick that brings its velocity to the half step every other star is at.
+fn kick_drift(index: u32, before: Star, pull: vec3<f32>, substeps: u32) -> Star {
+    let h = state.dt / f32(substeps);
+    var kick = h;
+    if (state.integrator > 0.5) {
+        kick = 0.5 * h;
+        if (state.first_step < 0.5) {
+            kick += 0.5 * state.dt;
+        }
+    }
+    var star = drift(kick_star(before, pull + external_pull(before), kick), h);
+    for (var k = 1u; k < substeps; k++) {
+        star = drift(kick_star(star, substep_force(index, star) + external_pull(star), h), h);
+    }
+    if (state.integrator > 0.5 && substeps > 1u) {
+        star = kick_star(star, substep_force(index, star) + external_pull(star), 0.5 * (state.dt - h));
+    }
+    return star;
+}
+
+// One kick, then the --max-velocity clamp.
+fn kick_star(before: Star, force: vec3<f32>, kick: f32) -> Star {
+    var star = before;
+    star.vx += force.x * kick;
+    star.vy += force.y * kick;
+    star.vz += force.z * kick;
+    let speed = length(vec3<f32>(star.vx, star.vy, star.vz));
+    if (state.max_velocity > 0.0 && speed > state.max_velocity) {
+        let k = state.max_velocity / speed;
+        star.vx *= k; star.vy *= k; star.vz *= k;
+    }
+    return star;
+}
+
+fn drift(before: Star, h: f32) -> Star {
+    var star = before;
+    star.x += star.vx * h;
+    star.y += star.vy * h;
+    star.z += star.vz * h;
+    return bound(star);
+}
+
+// How short a substep must be against the star's acceleration: SUBSTEP_ETA * sqrt(softening / |a|)
+const SUBSTEP_ETA: f32 = 0.25;
+
+// --max-substeps: the fewest halvings of dt, up to the flag's count, that bring a step
+// under the star's limit. 1 for every star unless the flag is raised.
+fn substep_count(star: Star, pull: vec3<f32>) -> u32 {
+    let a = length(pull + external_pull(star));
+    let limit = SUBSTEP_ETA * SUBSTEP_ETA * state.softening;
+    let cap = u32(state.max_substeps + 0.5);
+    var n = 1u;
+    while (n < cap && (state.dt / f32(n)) * (state.dt / f32(n)) * a > limit) {
+        n *= 2u;
+    }
+    return n;
+}
+
+// The force a substep re-evaluates, from the same model as the kernel's first: each
+// kernel's copy calls its --accel's own (see simulation::set_substep_force).
+fn substep_force(index: u32, star: Star) -> vec3<f32> {
+    return sampled_force(index, star);
+}
+
+fn sampled_force(index: u32, star: Star) -> vec3<f32> {
+    return sampled_pull(index, star, chunk.total).xyz + central_pull(star, chunk.total);
+}
+
+fn bruteforce_force(index: u32, star: Star) -> vec3<f32> {
+    return expected(bruteforce_pull(index, star, chunk.total), star, chunk.total).xyz;
+}
+
+fn grid_force(index: u32, star: Star) -> vec3<f32> {
+    return expected(grid_pull(index, star), star, chunk.total).xyz;
+}
+
+// --boundary after the drift: reflect reverses the radial velocity of a star outside the
+// sphere and heading further out, periodic wraps the position into the cube.
+fn bound(before: Star) -> Star {
+    var star = before;
+    let r = vec3<f32>(star.x, star.y, star.z);
+    if (state.boundary_kind > 1.5) {
+        let wrapped = r - state.boundary_size * floor(r / state.boundary_size + 0.5);
+        star.x = wrapped.x; star.y = wrapped.y; star.z = wrapped.z;
+    } else if (state.boundary_kind > 0.5) {
+        let r_sq = dot(r, r);
+        let outward = dot(r, vec3<f32>(star.vx, star.vy, star.vz));
+        if (r_sq > state.boundary_size * state.boundary_size && outward > 0.0) {
+            let k = 2.0 * outward / r_sq;
+            star.vx -= k * r.x; star.vy -= k * r.y; star.vz -= k * r.z;
+        }
+    }
+    return star;
+}
+
+// With a periodic --boundary, the separation to the nearest image of the partner.
+fn nearest_image(d: vec3<f32>) -> vec3<f32> {
+    if (state.boundary_kind > 1.5) {
+        return d - state.boundary_size * floor(d / state.boundary_size + 0.5);
+    }
+    return d;
+}
 
-    var star = stars[index];
+// main's partner loop: the force in xyz and the summed tension in w.
+fn sampled_pull(index: u32, star: Star, n: u32) -> vec4<f32> {
+    let has_central = load_star(0u).data_type < -0.5;
+    let soft_sq = state.softening * state.softening;
 
     // 1. CALCULATE TENSION (Distance Weighted)
     var tension = 0.0;
@@ -40,67 +434,331 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     var force_z = 0.0;
 
     for (var i = 0u; i < SAMPLES; i++) {
-        let r_seed = vec2<f32>(f32(index) * 0.1, state.time_seed + f32(i));
-        let target_idx = u32(rand(r_seed) * f32(arrayLength(&stars)));
+        let target_idx = sample_index(index, i, n);
         
-        if (target_idx != index) {
-            let other = stars[target_idx];
-            let dx = other.x - star.x;
-            let dy = other.y - star.y;
-            let dz = other.z - star.z;
-            let dist_sq = dx*dx + dy*dy + dz*dz + 10.0;
+        if (target_idx != index && !(has_central && target_idx == 0u)) {
+            let other = load_star(target_idx);
+            let d = nearest_image(vec3<f32>(other.x - star.x, other.y - star.y, other.z - star.z));
+            let dx = d.x;
+            let dy = d.y;
+            let dz = d.z;
+            let dist_sq = dx*dx + dy*dy + dz*dz + soft_sq;
             let dist = sqrt(dist_sq);
             
-            var interaction = -1.0; 
-            if (abs(star.data_type - other.data_type) < 0.1) {
-                interaction = 1.0; 
-            } else {
+            if (abs(star.data_type - other.data_type) > 0.1) {
                 // Distance Weighting (The "Mandelbrot Logic")
                 tension += (1.0 / dist_sq) * 10000.0; 
             }
             
-            let f = (G * 500.0 * interaction) / dist_sq;
+            let f = (state.g_const * 500.0 * coefficient(star.data_type, other.data_type)) / dist_sq;
             force_x += f * (dx / dist);
             force_y += f * (dy / dist);
             force_z += f * (dz / dist);
         }
     }
+    return vec4<f32>(force_x, force_y, force_z, tension);
+}
 
-    // 2. APPLY MODE LOGIC
-    var dilation = 1.0; 
+// The 27 cells around the star's grid cell exactly, every farther cell as one point mass
+// per star type at that type's centroid; unscaled, like sampled_pull.
+fn grid_pull(index: u32, star: Star) -> vec4<f32> {
+    let side = grid.side;
+    let home = bins[index];
+    let hx = home % side;
+    let hy = (home / side) % side;
+    let hz = home / (side * side);
 
-    if (state.dilation_mode > 0.5) {
-        // --- CHRONOTURIN MODE (Smart) ---
-        tension = tension / f32(SAMPLES);
-        if (tension < 0.5) { 
-            dilation = 0.02; // Sleep (98% Savings)
+    var sum = vec4<f32>(0.0);
+    for (var z = max(hz, 1u) - 1u; z <= min(hz + 1u, side - 1u); z++) {
+        for (var y = max(hy, 1u) - 1u; y <= min(hy + 1u, side - 1u); y++) {
+            for (var x = max(hx, 1u) - 1u; x <= min(hx + 1u, side - 1u); x++) {
+                let cell = cells[x + side * (y + side * z)];
+                for (var k = cell.start; k < cell.start + cell.count; k++) {
+                    let j = bins[grid.total + k];
+                    if (j != index) {
+                        let other = load_star(j);
+                        sum += pull(star, vec3<f32>(other.x, other.y, other.z), other.data_type, 1.0);
+                    }
+                }
+            }
         }
-    } else {
-        // --- NEWTONIAN MODE (Dumb) ---
-        // Always 1.0. Never sleep.
-        dilation = 1.0;
     }
+    for (var c = 0u; c < grid.cells; c++) {
+        let x = c % side;
+        let y = (c / side) % side;
+        let z = c / (side * side);
+        if (max(x, hx) - min(x, hx) <= 1u && max(y, hy) - min(y, hy) <= 1u && max(z, hz) - min(z, hz) <= 1u) {
+            continue;
+        }
+        let cell = cells[c];
+        if (cell.a.w > 0.0) {
+            sum += pull(star, cell.a.xyz, 0.0, cell.a.w);
+        }
+        if (cell.b.w > 0.0) {
+            sum += pull(star, cell.b.xyz, 1.0, cell.b.w);
+        }
+    }
+    return sum;
+}
 
-    // 3. PHYSICS UPDATE
-    star.vx *= 0.90; star.vy *= 0.90; star.vz *= 0.90;
-    star.time_debt += dilation;
+fn bruteforce_pull(index: u32, star: Star, n: u32) -> vec4<f32> {
+    let has_central = load_star(0u).data_type < -0.5;
+    var sum = vec4<f32>(0.0);
+    for (var j = 0u; j < n; j++) {
+        if (j != index && !(has_central && j == 0u)) {
+            let other = load_star(j);
+            sum += pull(star, vec3<f32>(other.x, other.y, other.z), other.data_type, 1.0);
+        }
+    }
+    return sum;
+}
 
-    // Default to "Sleeping" state for visualization
-    star.active_flag = 0.0; 
+// --accel sampled: SAMPLES random partners per star and step.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    // The last workgroup of a chunk overhangs its stars; those invocations would otherwise
+    // update the next chunk's first stars a second time.
+    if (global_id.x >= chunk.count) { return; }
+    let index = chunk.offset + global_id.x;
+
+    let star = load_star(index);
+
+    // A central mass (data_type CENTRAL, always star 0) is pinned in place.
+    if (star.data_type < -0.5) { return; }
+    let n = chunk.total;
+    let sampled = sampled_pull(index, star, n);
+    let center = central_pull(star, n);
+    advance(index, star, sampled.xyz + center, sampled.w / f32(SAMPLES));
+}
+
+// --accel bruteforce: every other star, exactly. O(N^2); the reference the other two are judged by.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn main_bruteforce(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= chunk.count) { return; }
+    let index = chunk.offset + global_id.x;
+    let star = load_star(index);
+    if (star.data_type < -0.5) { return; }
+    let total = expected(bruteforce_pull(index, star, chunk.total), star, chunk.total);
+    advance(index, star, total.xyz, total.w);
+}
 
+// --accel grid: see grid_pull.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn main_grid(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= chunk.count) { return; }
+    let index = chunk.offset + global_id.x;
+    let star = load_star(index);
+    if (star.data_type < -0.5) { return; }
+    let total = expected(grid_pull(index, star), star, chunk.total);
+    advance(index, star, total.xyz, total.w);
+}
+
+// --compaction, pass 1 of 3, over every star: accrues time debt at the rate the star's own
+// last tension set, damps, and appends the stars due to work to active_list.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn accrue(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= chunk.count) { return; }
+    let index = chunk.offset + global_id.x;
+    var star = load_star(index);
+    if (star.data_type < -0.5) { return; }
+
+    var dilation = 1.0;
+    if (chronoturin() && calm[index] != 0u) {
+        dilation = 1.0 / state.max_time_debt;
+    }
+    if (state.integrator < 0.5) {
+        star.vx *= state.damping; star.vy *= state.damping; star.vz *= state.damping;
+    }
+    star.time_debt += dilation;
+    star.active_flag = 0.0;
     if (star.time_debt >= 1.0) {
         star.time_debt -= 1.0;
-        
-        // Mark as ACTIVE (Hot!)
-        star.active_flag = 1.0; 
+        star.active_flag = 1.0;
+        atomicAdd(&counters.updates, 1u);
+        active_list.indices[atomicAdd(&active_list.count, 1u)] = index;
+    }
+    store_star(index, star);
+}
+
+// Every device allows at least this many workgroups per dimension.
+const MAX_GROUPS: u32 = 65535u;
 
-        star.vx += force_x * DT;
-        star.vy += force_y * DT;
-        star.vz += force_z * DT;
-        star.x += star.vx * DT;
-        star.y += star.vy * DT;
-        star.z += star.vz * DT;
+// Pass 2, one invocation: sizes the force pass to the list and empties it for the next step.
+@compute @workgroup_size(1)
+fn args() {
+    let count = atomicLoad(&active_list.count);
+    let groups = (count + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE;
+    let x = min(groups, MAX_GROUPS);
+    let y = select(0u, (groups + x - 1u) / max(x, 1u), groups > 0u);
+    dispatch_args[0] = x;
+    dispatch_args[1] = y;
+    dispatch_args[2] = 1u;
+    dispatch_args[3] += x * y * WORKGROUP_SIZE;
+    active_list.frozen = count;
+    atomicStore(&active_list.count, 0u);
+}
+
+// Pass 3, dispatched indirectly over active_list only: the star is known to work, so it
+// takes the force, moves, and measures the tension that sets its rate until it next works.
+fn work(index: u32, star: Star, force: vec3<f32>, tension: f32) {
+    calm[index] = select(0u, 1u, chronoturin() && tension < state.dilation_threshold);
+    store_star(index, kick_drift(index, star, force, substep_count(star, force)));
+}
+
+// Lists longer than MAX_GROUPS workgroups fold into a second dimension (see args).
+fn flat_index(id: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return id.x + id.y * groups.x * WORKGROUP_SIZE;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn main_active(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let k = flat_index(global_id, groups);
+    if (k >= active_list.frozen) { return; }
+    let index = active_list.indices[k];
+    let star = load_star(index);
+    let n = chunk.total;
+    let sampled = sampled_pull(index, star, n);
+    work(index, star, sampled.xyz + central_pull(star, n), sampled.w / f32(SAMPLES));
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn main_active_bruteforce(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let k = flat_index(global_id, groups);
+    if (k >= active_list.frozen) { return; }
+    let index = active_list.indices[k];
+    let star = load_star(index);
+    let total = expected(bruteforce_pull(index, star, chunk.total), star, chunk.total);
+    work(index, star, total.xyz, total.w);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn main_active_grid(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let k = flat_index(global_id, groups);
+    if (k >= active_list.frozen) { return; }
+    let index = active_list.indices[k];
+    let star = load_star(index);
+    let total = expected(grid_pull(index, star), star, chunk.total);
+    work(index, star, total.xyz, total.w);
+}
+
+// --kill-radius, pass 1 of 2, over the living stars of a chunk: flags those past the radius
+// dead and counts the rest. The partition sort then moves the dead behind the living.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn cull(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= chunk.count) { return; }
+    let index = chunk.offset + global_id.x;
+    var star = load_star(index);
+    let r = vec3<f32>(star.x, star.y, star.z);
+    // Restored stars may have died already.
+    if (star.active_flag > -0.5 && star.data_type > -0.5 && dot(r, r) > culling.radius * culling.radius) {
+        star.active_flag = -1.0;
+        store_star(index, star);
+    }
+    if (star.active_flag > -0.5) {
+        atomicAdd(&culling.alive, 1u);
+    }
+}
+
+// Pass 2, one invocation: each chunk keeps the living stars it holds, and every partner
+// loop ends at the last of them.
+@compute @workgroup_size(1)
+fn cull_args() {
+    let alive = atomicLoad(&culling.alive);
+    for (var k = 0u; k < culling.chunks; k++) {
+        let offset = culling.params[k].offset;
+        let full = min(culling.params[k].stride, culling.total - offset);
+        let count = min(max(alive, offset) - offset, full);
+        culling.params[k].count = count;
+        culling.params[k].total = alive;
+        culling.dispatch[k] = vec4<u32>((count + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE, 1u, 1u, 0u);
     }
+}
 
-    stars[index] = star;
+// --merge-radius, once per frame over the living stars of a chunk, on a freshly built grid:
+// each star finds its nearest living neighbor within the radius in the 27 cells around its
+// own, and the lower index of the pair merges it into itself. Both stars must be claimed
+// first, so no star takes part in two merges of one pass; a pair that loses a claim waits
+// for the next frame.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn merge(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= chunk.count) { return; }
+    let index = chunk.offset + global_id.x;
+    let home = bins[index];
+    // No cell for a central mass or a culled star
+    if (home == 0xffffffffu) { return; }
+    let star = load_star(index);
+    let side = grid.side;
+    let hx = home % side;
+    let hy = (home / side) % side;
+    let hz = home / (side * side);
+    var nearest = index;
+    var nearest_sq = merging.radius * merging.radius;
+    for (var z = max(hz, 1u) - 1u; z <= min(hz + 1u, side - 1u); z++) {
+        for (var y = max(hy, 1u) - 1u; y <= min(hy + 1u, side - 1u); y++) {
+            for (var x = max(hx, 1u) - 1u; x <= min(hx + 1u, side - 1u); x++) {
+                let cell = cells[x + side * (y + side * z)];
+                for (var k = cell.start; k < cell.start + cell.count; k++) {
+                    let j = bins[grid.total + k];
+                    let other = load_star(j);
+                    let d = vec3<f32>(other.x - star.x, other.y - star.y, other.z - star.z);
+                    if (j != index && other.active_flag > -0.5 && dot(d, d) < nearest_sq) {
+                        nearest = j;
+                        nearest_sq = dot(d, d);
+                    }
+                }
+            }
+        }
+    }
+    if (nearest <= index) { return; }
+    if (atomicExchange(&claims[index], 1u) != 0u) { return; }
+    if (atomicExchange(&claims[nearest], 1u) != 0u) {
+        atomicStore(&claims[index], 0u);
+        return;
+    }
+    // Reloaded now that no other merge can touch either star.
+    let a = load_star(index);
+    var b = load_star(nearest);
+    let mass = a.mass + b.mass;
+    let wa = select(0.5, a.mass / mass, mass > 0.0);
+    let wb = 1.0 - wa;
+    var merged = a;
+    merged.x = wa * a.x + wb * b.x;
+    merged.y = wa * a.y + wb * b.y;
+    merged.z = wa * a.z + wb * b.z;
+    merged.vx = wa * a.vx + wb * b.vx;
+    merged.vy = wa * a.vy + wb * b.vy;
+    merged.vz = wa * a.vz + wb * b.vz;
+    merged.mass = mass;
+    store_star(index, merged);
+    b.mass = 0.0;
+    b.active_flag = -1.0;
+    store_star(nearest, b);
+    atomicAdd(&merging.mergers, 1u);
+}
+
+// All-ones exponent bits, so drivers that assume finite floats cannot fold the test away.
+fn non_finite(v: vec3<f32>) -> bool {
+    return any((bitcast<vec3<u32>>(v) & vec3<u32>(0x7f800000u)) == vec3<u32>(0x7f800000u));
+}
+
+// Once per frame after the last step, over the living stars of a chunk: counts those
+// whose position or velocity is no longer finite or has run past the bound.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn watch(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (global_id.x >= chunk.count) { return; }
+    let star = load_star(chunk.offset + global_id.x);
+    if (star.active_flag < -0.5) { return; }
+    let r = vec3<f32>(star.x, star.y, star.z);
+    let v = vec3<f32>(star.vx, star.vy, star.vz);
+    let bound = vec3<f32>(watchdog.bound);
+    if (non_finite(r)) {
+        atomicAdd(&watchdog.nan_position, 1u);
+    } else if (any(abs(r) > bound)) {
+        atomicAdd(&watchdog.far_position, 1u);
+    }
+    if (non_finite(v)) {
+        atomicAdd(&watchdog.nan_velocity, 1u);
+    } else if (any(abs(v) > bound)) {
+        atomicAdd(&watchdog.fast_velocity, 1u);
+    }
 }
@@ -0,0 +1,122 @@
+use std::sync::OnceLock;
+
+use naga::{Scalar, TypeInner};
+
+use crate::galaxy::{GalaxyState, Star};
+
+// Every embedded shader that declares Star or GalaxyState.
+const SHADERS: [(&str, &str); 7] = [
+    ("shader.wgsl", include_str!("shader.wgsl")),
+    ("grid.wgsl", include_str!("grid.wgsl")),
+    ("packing.wgsl", include_str!("packing.wgsl")),
+    ("points.wgsl", include_str!("points.wgsl")),
+    ("raster.wgsl", include_str!("raster.wgsl")),
+    ("reduction.wgsl", include_str!("reduction.wgsl")),
+    ("sort.wgsl", include_str!("sort.wgsl")),
+];
+
+// (name, byte offset) of the named f32 fields.
+macro_rules! fields {
+    ($ty:ty { $($field:ident),* }) => {
+        vec![$((stringify!($field), std::mem::offset_of!($ty, $field))),*]
+    };
+}
+
+// A Rust struct shared with the shaders. Padding is left out of `fields` and checked
+// through `size` only; on the WGSL side it is any member named pad*.
+struct HostStruct {
+    name: &'static str,
+    size: usize,
+    fields: Vec<(&'static str, usize)>,
+}
+
+fn host_struct(name: &str) -> Option<HostStruct> {
+    match name {
+        "Star" => Some(HostStruct {
+            name: "Star",
+            size: std::mem::size_of::<Star>(),
+            fields: fields!(Star { x, y, z, vx, vy, vz, mass, data_type, time_debt, active_flag }),
+        }),
+        "GalaxyState" => Some(HostStruct {
+            name: "GalaxyState",
+            size: std::mem::size_of::<GalaxyState>(),
+            fields: fields!(GalaxyState {
+                time_seed,
+                dilation_mode,
+                softening,
+                g_const,
+                dt,
+                damping,
+                integrator,
+                first_step,
+                dilation_threshold,
+                max_time_debt,
+                drag,
+                external_kind,
+                external_scale,
+                external_strength,
+                boundary_kind,
+                boundary_size,
+                max_velocity,
+                max_substeps
+            }),
+        }),
+        _ => None,
+    }
+}
+
+// Checks every embedded shader, once per process; with_stars calls this before compiling.
+pub fn check_builtin_shaders() -> Result<(), String> {
+    static RESULT: OnceLock<Result<(), String>> = OnceLock::new();
+    RESULT
+        .get_or_init(|| SHADERS.iter().try_for_each(|(name, source)| check_shader(source).map_err(|e| format!("{}: {}", name, e))))
+        .clone()
+}
+
+// Parses `source` and compares every Star or GalaxyState it binds in group 0 (as the
+// struct, or as the element of a storage array) with the Rust struct: field names, offsets
+// and types, the size, and the array stride. The error names the first field that differs.
+pub fn check_shader(source: &str) -> Result<(), String> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|e| e.emit_to_string(source))?;
+    let mut layouter = naga::proc::Layouter::default();
+    layouter.update(module.to_ctx()).map_err(|e| e.to_string())?;
+    for (_, global) in module.global_variables.iter() {
+        if global.binding.as_ref().is_none_or(|b| b.group != 0) {
+            continue;
+        }
+        let (ty, stride) = match module.types[global.ty].inner {
+            TypeInner::Array { base, stride, .. } => (base, Some(stride)),
+            _ => (global.ty, None),
+        };
+        let Some(host) = module.types[ty].name.as_deref().and_then(host_struct) else { continue };
+        let TypeInner::Struct { members, .. } = &module.types[ty].inner else {
+            return Err(format!("{} is not a struct in WGSL", host.name));
+        };
+
+        for member in members {
+            let name = member.name.as_deref().unwrap_or_default();
+            if !name.starts_with("pad") && !host.fields.iter().any(|(field, _)| *field == name) {
+                return Err(format!("WGSL {} has `{}`, which the Rust {} lacks", host.name, name, host.name));
+            }
+        }
+        for (field, offset) in &host.fields {
+            let Some(member) = members.iter().find(|m| m.name.as_deref() == Some(*field)) else {
+                return Err(format!("{}.{} is missing from the WGSL struct", host.name, field));
+            };
+            if module.types[member.ty].inner != TypeInner::Scalar(Scalar::F32) {
+                return Err(format!("{}.{} is f32 in Rust but not in WGSL", host.name, field));
+            }
+            if member.offset as usize != *offset {
+                return Err(format!("{}.{} is at byte {} in WGSL but byte {} in Rust", host.name, field, member.offset, offset));
+            }
+        }
+        let size = layouter[ty].size as usize;
+        if size != host.size {
+            return Err(format!("WGSL {} is {} bytes, the Rust one {}", host.name, size, host.size));
+        }
+        if let Some(stride) = stride.filter(|&stride| stride as usize != host.size) {
+            return Err(format!("array<{}> has a {}-byte stride in WGSL, but Rust packs them every {}", host.name, stride, host.size));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,1781 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use image::RgbImage;
+use wgpu::util::DeviceExt;
+
+use crate::bindings::{self, StateRing, StepState, UniformPath};
+use crate::compaction::{Compaction, CompactionPipelines};
+use crate::config::SimConfig;
+use crate::cull::{CullPipelines, Culling};
+use crate::error::ChronoError;
+use crate::galaxy::{is_dead, GalaxyState, Star, DEFAULT_DT};
+use crate::gpu::{self, DeviceErrors};
+use crate::grid::{self, Accel, Grid, GridPipelines};
+use crate::merge::{MergePipelines, Merging};
+use crate::packing::{self, Packer, RenderRecord};
+use crate::precision::Precision;
+use crate::presets;
+use crate::raster::{GpuRaster, RasterPipelines};
+use crate::reduction::{self, Moments, Quantity, Reduction};
+use crate::render::{Camera, RenderSettings};
+use crate::shader_layout;
+use crate::sort::{self, MortonSort, SortPipelines};
+use crate::watchdog::{self, Watchdog, WatchdogCounts, WatchdogPipelines};
+
+// Default --dt; the shader reads the actual step from GalaxyState.
+pub const DT: f32 = DEFAULT_DT;
+
+// Steps encoded per command buffer by step_and_wait, so a long run is not one huge submission.
+const STEPS_PER_SUBMIT: usize = 100;
+
+// Serialized by its name.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DilationMode {
+    Newtonian,
+    Chronoturin,
+}
+
+impl DilationMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            DilationMode::Newtonian => "NEWTONIAN",
+            DilationMode::Chronoturin => "CHRONOTURIN",
+        }
+    }
+
+    // Short name used for output files and directories
+    pub fn file_stem(self) -> &'static str {
+        match self {
+            DilationMode::Newtonian => "newton",
+            DilationMode::Chronoturin => "chrono",
+        }
+    }
+
+    // Value stored in GalaxyState::dilation_mode
+    pub fn as_uniform(self) -> f32 {
+        match self {
+            DilationMode::Newtonian => 0.0,
+            DilationMode::Chronoturin => 1.0,
+        }
+    }
+
+    // Inverse of as_uniform, with the shader's threshold
+    pub fn from_uniform(value: f32) -> Self {
+        if value > 0.5 {
+            DilationMode::Chronoturin
+        } else {
+            DilationMode::Newtonian
+        }
+    }
+}
+
+// What the render loop needs from a backend, so it runs the same on Simulation (GPU)
+// and cpu_reference::CpuSimulation. A ticket stands for a submitted frame.
+pub trait Stepper {
+    type Ticket;
+    fn fork(&self) -> Self;
+    fn reset(&mut self);
+    fn restore(&mut self, state: GalaxyState, stars: &[Star]);
+    fn set_mode(&mut self, mode: DilationMode);
+    fn set_dilation(&mut self, dilation_threshold: f32, max_debt: f32);
+    fn readback_depth(&self) -> usize;
+    fn submit_frame(&mut self, steps: usize) -> Self::Ticket;
+    fn finish_frame(&mut self, ticket: Self::Ticket) -> FrameData;
+    // A frame nothing will draw or measure star by star: only its stats need come back.
+    // Steppers that have the stars at hand anyway return them all.
+    fn submit_stats(&mut self, steps: usize) -> Self::Ticket {
+        self.submit_frame(steps)
+    }
+    // The view for the next submitted frame, for steppers that rasterize on their own.
+    fn set_camera(&mut self, _camera: &Camera) {}
+    // Compiles the force kernels from `source` (--shader) without switching to them, for
+    // steppers that have any; None for the rest.
+    fn compile_kernels(&self, _source: &str) -> Result<Option<CompiledKernels>, String> {
+        Ok(None)
+    }
+    // Switches to kernels compile_kernels made.
+    fn install_kernels(&mut self, _kernels: CompiledKernels) {}
+    fn reload_kernels(&mut self, source: &str) -> Result<(), String> {
+        if let Some(kernels) = self.compile_kernels(source)? {
+            self.install_kernels(kernels);
+        }
+        Ok(())
+    }
+    // What went wrong with the last finished frame, for steppers on a device that can fail.
+    fn fault(&self) -> Option<ChronoError> {
+        None
+    }
+}
+
+// A frame whose compute + copy has been submitted but not yet read back.
+#[derive(Debug)]
+pub struct FrameTicket {
+    slot: usize,
+    submission: wgpu::SubmissionIndex,
+    pub sim_time: f32,
+}
+
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Integrator {
+    // The original update: v += a dt, x += v dt, with per-step velocity damping
+    Euler,
+    // Kick-drift-kick without damping; stored velocities are half a step ahead of positions
+    Leapfrog,
+}
+
+impl Integrator {
+    // Value stored in GalaxyState::integrator
+    pub fn as_uniform(self) -> f32 {
+        match self {
+            Integrator::Euler => 0.0,
+            Integrator::Leapfrog => 1.0,
+        }
+    }
+}
+
+// Where a per-step time came from; serialized by name().
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimingSource {
+    // TIMESTAMP_QUERY around the compute pass
+    GpuTimestamps,
+    // Submit to poll completion on the host, which adds driver and queue overhead
+    WallClock,
+}
+
+impl TimingSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            TimingSource::GpuTimestamps => "gpu-timestamps",
+            TimingSource::WallClock => "wall-clock",
+        }
+    }
+}
+
+// One finished readback.
+#[derive(Clone, Debug)]
+pub struct FrameData {
+    pub stars: Vec<Star>,
+    pub sim_time: f32,
+    // Time between the first and last compute pass, when timestamps are supported
+    pub gpu_ms: Option<f32>,
+    // Star updates (active_flag = 1.0) summed over the frame's sub-steps
+    pub active_updates: u32,
+    pub steps: usize,
+    // GPU-reduced totals of the read-back stars (--gpu-diagnostics)
+    pub moments: Option<Moments>,
+    // The frame rasterized on the GPU (Simulation::enable_raster); None means render the stars on the CPU
+    pub image: Option<RgbImage>,
+    // The stars came back as RenderRecords (enable_packed_readback): no velocity, mass or time debt
+    pub packed: bool,
+    // Star and stats bytes mapped for this frame (the GPU raster's image aside), and the time
+    // spent mapping and copying them out once the GPU was done
+    pub readback_bytes: u64,
+    pub readback_ms: f32,
+    // Invocations the force pass launched over the frame's sub-steps: every chunk's whole
+    // dispatch per step, or the active list's with --compaction. None on the CPU.
+    pub force_threads: Option<u64>,
+    // Stars not culled past --kill-radius or merged away, as of the frame's last step; all of
+    // them without either
+    pub alive: u32,
+    // Mergers since the stars were uploaded, with --merge-radius
+    pub mergers: Option<u32>,
+    // What rounding left off each star's x, y and z with --precision df64, in the order of
+    // `stars`; their positions hold the rounded hi parts
+    pub position_lo: Option<Vec<[f32; 3]>>,
+    // Living stars past the divergence watchdog's checks after the frame's last step
+    pub watchdog: WatchdogCounts,
+    // Substeps the frame's star updates took, at least one each (--max-substeps). None with
+    // --compaction, whose force pass has no binding left to count them in.
+    pub substeps: Option<u32>,
+    // Stands in for a frame that did not read back (stand_in)
+    pub placeholder: bool,
+    // Submitted with submit_stats: no stars or image came back, only the counts and timings
+    pub stats_only: bool,
+    // The stars after each of the frame's sub-steps but the last, oldest first, as render
+    // records in the order of `stars` (--trails); empty without
+    pub trails: Vec<Vec<Star>>,
+}
+
+// What a run writes for a frame whose readback failed, mapped again and failed again
+// (--on-frame-error). Either way the frame numbers stay contiguous.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OnFrameError {
+    // Stop with the error, saving the frame before as the checkpoint
+    Stop,
+    // A frame with no stars in it
+    Black,
+    // The previous frame again, or a black one at the start of a pass
+    Duplicate,
+}
+
+impl FrameData {
+    // This frame, which never came back, drawn as `on_error` says from `previous`. It has no
+    // measurements, so its metrics row is a placeholder too.
+    pub fn stand_in(self, on_error: OnFrameError, previous: Option<&FrameData>) -> FrameData {
+        let (stars, image, alive) = match (on_error, previous) {
+            (OnFrameError::Duplicate, Some(previous)) => (previous.stars.clone(), previous.image.clone(), previous.alive),
+            _ => (Vec::new(), None, 0),
+        };
+        FrameData {
+            stars,
+            image,
+            alive,
+            gpu_ms: None,
+            active_updates: 0,
+            moments: None,
+            position_lo: None,
+            placeholder: true,
+            trails: Vec::new(),
+            ..self
+        }
+    }
+
+    // Mean number of stars that did work per sub-step.
+    pub fn active_per_step(&self) -> f32 {
+        if self.steps == 0 {
+            0.0
+        } else {
+            self.active_updates as f32 / self.steps as f32
+        }
+    }
+
+    // Of the stars still alive, which is all of them without --kill-radius.
+    pub fn active_fraction(&self) -> f32 {
+        if self.stars.is_empty() && !self.stats_only {
+            0.0
+        } else {
+            self.active_per_step() / self.alive.max(1) as f32
+        }
+    }
+
+    // The stars that were not culled, in creation order; what frames draw, dumps write and
+    // the diagnostics measure.
+    pub fn living(&self) -> Cow<'_, [Star]> {
+        match self.alive as usize == self.stars.len() {
+            true => Cow::Borrowed(&self.stars),
+            false => Cow::Owned(self.stars.iter().filter(|s| !is_dead(s)).copied().collect()),
+        }
+    }
+}
+
+struct ReadbackSlot {
+    // One per star chunk
+    stars: Vec<wgpu::Buffer>,
+    // The creation index of each read-back star (--sort-every)
+    ids: Option<wgpu::Buffer>,
+    // [active counter + force-pass invocations][timestamps][reduction partials of every chunk]
+    extras: wgpu::Buffer,
+    // One per mapped buffer, stars first
+    pending: Vec<Receiver<Result<(), wgpu::BufferAsyncError>>>,
+    timed: bool,
+    steps: usize,
+    // False for submit_stats(), which copies and maps the extras only
+    with_stars: bool,
+    // One per chunk, with the records of every --trails sub-step one after another (enable_trails)
+    trails: Vec<wgpu::Buffer>,
+}
+
+impl ReadbackSlot {
+    fn buffers(&self) -> impl Iterator<Item = &wgpu::Buffer> {
+        self.stars.iter().chain(&self.ids).chain(&self.trails).filter(|_| self.with_stars).chain([&self.extras])
+    }
+}
+
+// Matches `Chunk` in shader.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ChunkParams {
+    offset: u32,
+    count: u32,
+    stride: u32,
+    total: u32,
+}
+
+// One storage buffer of the population and the bind group that dispatches it.
+struct Chunk {
+    buffer: wgpu::Buffer,
+    range: Range<usize>,
+    // Its `Chunk` uniform, which --kill-radius cuts down to the living stars, and what it
+    // holds for every star
+    params: wgpu::Buffer,
+    full_params: ChunkParams,
+    bind_group: wgpu::BindGroup,
+    workgroups: u32,
+    // Center of mass, momentum and kinetic energy of this chunk, summed on the GPU after each frame
+    reduction: Option<Reduction>,
+    // Writes this chunk's RenderRecords for each frame, when only those are read back
+    packer: Option<Packer>,
+}
+
+// Two timestamps per readback slot, resolved at QUERY_RESOLVE_BUFFER_ALIGNMENT strides
+// and then copied next to the active counter so one map returns both.
+struct Timestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    // Where time_step copies slot 0's pair to map it
+    staging: wgpu::Buffer,
+    period_ns: f32,
+}
+
+// GalaxyState slots in the ring; frames with more steps load it in batches.
+const MAX_STATE_SLOTS: usize = 64;
+
+const TIMESTAMP_BYTES: u64 = 2 * std::mem::size_of::<u64>() as u64;
+// The active counter, the force-pass invocations with --compaction, the living stars with
+// culling, the mergers with --merge-radius, the watchdog's counts, then the substeps and a pad
+const COUNTER_BYTES: u64 = 16 + watchdog::COUNT_BYTES + 16;
+const SUBSTEPS_OFFSET: u64 = 16 + watchdog::COUNT_BYTES;
+
+// The --accel kernel compiled per dilation mode, or once reading GalaxyState::dilation_mode
+// with --unified-kernel, all on one explicit layout so the same bind groups serve each.
+struct Kernels {
+    pipelines: Vec<wgpu::ComputePipeline>,
+    layout: Arc<wgpu::BindGroupLayout>,
+    path: UniformPath,
+}
+
+impl Kernels {
+    // The force pass's bindings: a chunk's, the --interactions matrix and the grid's cells.
+    fn bind_group_layout(device: &wgpu::Device, config: &SimConfig, path: UniformPath) -> wgpu::BindGroupLayout {
+        let grid = if config.accel == Accel::Grid { &bindings::GRID[..] } else { &[] };
+        bindings::layout(
+            device,
+            "Force Kernels",
+            path,
+            bindings::CHUNK.into_iter().chain([bindings::INTERACTIONS]).chain(grid.iter().copied()),
+        )
+    }
+
+    // Compiles `source` (shader.wgsl or --shader) for each dilation mode on `layout` and
+    // `path`, which hot reloads take from the running kernels so existing bind groups still fit.
+    fn new(
+        device: &wgpu::Device,
+        config: &SimConfig,
+        source: &str,
+        layout: Arc<wgpu::BindGroupLayout>,
+        path: UniformPath,
+    ) -> Result<Self, String> {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: path.push_constant_ranges(),
+        });
+        let build = |mode: Option<DilationMode>| -> Result<_, String> {
+            let source = path.declare_state(&specialize(source, config.workgroup_size, mode, config.precision, config.accel)?)?;
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("shader.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            Ok(device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(mode.map_or("unified", DilationMode::name)),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: config.accel.entry_point(),
+            }))
+        };
+        let pipelines = match config.unified_kernel {
+            true => vec![build(None)?],
+            false => vec![build(Some(DilationMode::Newtonian))?, build(Some(DilationMode::Chronoturin))?],
+        };
+        Ok(Self { pipelines, layout, path })
+    }
+
+    fn for_mode(&self, mode: DilationMode) -> &wgpu::ComputePipeline {
+        &self.pipelines[(mode.as_uniform() as usize).min(self.pipelines.len() - 1)]
+    }
+}
+
+// Force kernels compiled from a --shader source but not yet running, so several steppers can
+// all compile before any of them switches.
+pub struct CompiledKernels {
+    source: String,
+    kernels: Arc<Kernels>,
+}
+
+impl CompiledKernels {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+// What fork() and resized() hand on, so nothing is compiled twice.
+struct Pipelines {
+    compute: Arc<Kernels>,
+    reduce: Option<Arc<wgpu::ComputePipeline>>,
+    // Compiled in with_pipeline on first use when --sort-every is set
+    sort: Option<Arc<SortPipelines>>,
+    // Likewise with --accel grid and --compaction
+    grid: Option<Arc<GridPipelines>>,
+    compaction: Option<Arc<CompactionPipelines>>,
+    cull: Option<Arc<CullPipelines>>,
+    merge: Option<Arc<MergePipelines>>,
+    watchdog: Option<Arc<WatchdogPipelines>>,
+}
+
+// What with_pipeline compiled itself and what it took from Pipelines, for the debug log
+// that tells a cold build from a warm one.
+#[derive(Default)]
+struct Compiles {
+    compiled: Vec<&'static str>,
+    reused: Vec<&'static str>,
+    elapsed: Duration,
+}
+
+impl Compiles {
+    fn reuse_or<T>(&mut self, name: &'static str, shared: Option<Arc<T>>, compile: impl FnOnce() -> T) -> Arc<T> {
+        if let Some(shared) = shared {
+            self.reused.push(name);
+            return shared;
+        }
+        let start = Instant::now();
+        let compiled = Arc::new(compile());
+        self.elapsed += start.elapsed();
+        self.compiled.push(name);
+        compiled
+    }
+
+    fn log(&self) {
+        if !self.compiled.is_empty() {
+            tracing::debug!("Compiled the {} pipelines cold in {:.1} ms", self.compiled.join(", "), self.elapsed.as_secs_f64() * 1000.0);
+        }
+        if !self.reused.is_empty() {
+            tracing::debug!("Reused the compiled {} pipelines warm", self.reused.join(", "));
+        }
+    }
+}
+
+// Owns every GPU resource needed to step one galaxy.
+pub struct Simulation {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    config: SimConfig,
+    initial_data: Vec<Star>,
+    state: GalaxyState,
+    // The stars, split across storage buffers when one binding cannot hold them all
+    chunks: Vec<Chunk>,
+    uniform_buffer: wgpu::Buffer,
+    // Where read_state copies uniform_buffer to map it
+    state_readback: wgpu::Buffer,
+    active_counter: wgpu::Buffer,
+    timestamps: Option<Timestamps>,
+    // Rotated per frame so frame K can be mapped while frame K+1 computes
+    readback_slots: Vec<ReadbackSlot>,
+    next_slot: usize,
+    in_flight: usize,
+    // One GalaxyState per batched sub-step, each pass binding its own by dynamic offset;
+    // None on the push path, where each pass sets its own as push constants
+    states: Option<StateRing>,
+    // Shared with fork()ed simulations; each has its own buffers and bind groups
+    compute_pipeline: Arc<Kernels>,
+    // Frames drawn on the GPU alongside each readback (enable_raster)
+    raster: Option<GpuRaster>,
+    // One per chunk: packs every sub-step but a frame's last for --trails (enable_trails)
+    trail_packers: Vec<Packer>,
+    // Reorders the stars every --sort-every frames, counting down to the next sort
+    sort: Option<MortonSort>,
+    frames_until_sort: usize,
+    // Bins the stars ahead of every step (--accel grid)
+    grid: Option<Grid>,
+    // Runs the force pass over the stars that work only (--compaction)
+    compaction: Option<Compaction>,
+    // Culls escaped stars every --cull-every frames (--kill-radius), counting down to the next cull
+    culling: Option<Culling>,
+    frames_until_cull: usize,
+    // Merges close stars every frame (--merge-radius), before a cull
+    merging: Option<Merging>,
+    // Checks the stars for NaNs and runaways after every frame's steps
+    watchdog: Watchdog,
+    // The device's loss and uncaptured errors, when the caller watches for them (watch_device)
+    device_errors: DeviceErrors,
+    // Why the last finish_frame came back without its readback
+    readback_fault: Option<ReadbackFault>,
+    // Readbacks that failed or timed out since the simulation was built
+    map_failures: u32,
+}
+
+enum ReadbackFault {
+    Failed(String),
+    TimedOut,
+}
+
+impl From<ChronoError> for ReadbackFault {
+    fn from(error: ChronoError) -> Self {
+        match error {
+            ChronoError::MapTimeout { .. } => ReadbackFault::TimedOut,
+            ChronoError::MapFailed(message) => ReadbackFault::Failed(message),
+            error => ReadbackFault::Failed(error.to_string()),
+        }
+    }
+}
+
+impl Simulation {
+    // Generates the initial galaxy for `config.preset` from `config.seed` (0 if unset).
+    pub fn new(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>, config: &SimConfig) -> Result<Self, ChronoError> {
+        let initial_data = presets::from_config(config, config.seed.unwrap_or(0));
+        Self::with_stars(device, queue, config, initial_data)
+    }
+
+    // Opens the adapter `config` picks (--adapter, --power-preference, --backend), without a
+    // window, and generates the galaxy as `new` does, in `mode`. For embedding it (the python
+    // and capi features): the device's errors are kept for fault() instead of exiting, so a
+    // missing adapter or a bad device is an error for the host to handle.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(config: &SimConfig, mode: DilationMode) -> Result<Self, ChronoError> {
+        let instance = gpu::shared_instance(config);
+        let (_, device, queue) = pollster::block_on(gpu::request_device(&instance, config, None))?;
+        let errors = DeviceErrors::watch(&device);
+        let mut sim = Self::new(Arc::new(device), Arc::new(queue), config)?;
+        sim.watch_device(errors);
+        sim.set_mode(mode);
+        sim.fault().map_or(Ok(sim), Err)
+    }
+
+    pub fn with_stars(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        config: &SimConfig,
+        initial_data: Vec<Star>,
+    ) -> Result<Self, ChronoError> {
+        let mut config = config.clone();
+        config.stars = initial_data.len() as u32;
+        config.validate(&device.limits())?;
+        let layout = config.chunk_layout(&device.limits())?;
+        if layout.count() > 1 {
+            tracing::info!("Stars split across {} buffers of up to {} stars", layout.count(), layout.chunk_stars);
+        }
+        if config.accel == Accel::Grid {
+            let side = grid::cells_per_side(config.stars);
+            tracing::info!("Force grid of {}x{}x{} cells", side, side, side);
+        }
+
+        shader_layout::check_builtin_shaders().map_err(ChronoError::ShaderLayout)?;
+        let start = Instant::now();
+        let path = UniformPath::select(&device, config.uniform_path)?;
+        let layout = Arc::new(Kernels::bind_group_layout(&device, &config, path));
+        let compute_pipeline =
+            Arc::new(Kernels::new(&device, &config, include_str!("shader.wgsl"), layout, path).map_err(ChronoError::ShaderLayout)?);
+        let reduce_pipeline = config.gpu_diagnostics.then(|| Arc::new(reduction::create_pipeline(&device)));
+        tracing::debug!("Compiled the force kernels cold in {:.1} ms", start.elapsed().as_secs_f64() * 1000.0);
+        let pipelines = Pipelines {
+            compute: compute_pipeline,
+            reduce: reduce_pipeline,
+            sort: None,
+            grid: None,
+            compaction: None,
+            cull: None,
+            merge: None,
+            watchdog: None,
+        };
+        Ok(Self::with_pipeline(device, queue, config, initial_data, pipelines))
+    }
+
+    // A second simulation of the same initial galaxy that reuses this one's pipeline,
+    // so both modes can be stepped side by side.
+    pub fn fork(&self) -> Self {
+        let mut forked =
+            Self::with_pipeline(self.device.clone(), self.queue.clone(), self.config.clone(), self.initial_data.clone(), self.pipelines());
+        let raster = self.raster.as_ref().map(|r| r.fork(&forked.device, forked.star_buffers(), forked.readback_depth()));
+        forked.raster = raster;
+        forked.device_errors = self.device_errors.clone();
+        if let Some(packer) = &self.chunks[0].packer {
+            forked.pack_with(packer.pipeline().clone());
+        }
+        if let Some(packer) = self.trail_packers.first() {
+            forked.trail_with(packer.pipeline().clone());
+        }
+        forked
+    }
+
+    // fork(), or None if the device runs out of memory for the copy's buffers.
+    pub async fn try_fork(&self) -> Option<Self> {
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        let forked = self.fork();
+        match self.device.pop_error_scope().await {
+            Some(error) => {
+                tracing::debug!("fork ran out of memory: {}", error);
+                None
+            }
+            None => Some(forked),
+        }
+    }
+
+    // Reads back 16-byte RenderRecords instead of whole stars from now on (FrameData::packed),
+    // replacing the star readback buffers. Every slot must be idle.
+    pub fn enable_packed_readback(&mut self) {
+        self.pack_with(Arc::new(packing::create_pipeline(&self.device)));
+    }
+
+    // Also reads back the RenderRecords of each sub-step before a frame's last
+    // (FrameData::trails), for --trails to draw under it. Every slot must be idle.
+    pub fn enable_trails(&mut self) {
+        self.trail_with(Arc::new(packing::create_pipeline(&self.device)));
+    }
+
+    fn trail_with(&mut self, pipeline: Arc<wgpu::ComputePipeline>) {
+        assert_eq!(self.in_flight, 0, "enable_trails called with frames in flight");
+        let steps = self.config.steps_per_frame.saturating_sub(1).max(1) as u64;
+        self.trail_packers =
+            self.chunks.iter().map(|chunk| Packer::new(&self.device, pipeline.clone(), &chunk.buffer, chunk.range.len() as u32)).collect();
+        for (i, slot) in self.readback_slots.iter_mut().enumerate() {
+            slot.trails = self
+                .trail_packers
+                .iter()
+                .enumerate()
+                .map(|(k, packer)| {
+                    self.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(&format!("Trail Readback Buffer {}.{}", i, k)),
+                        size: steps * packer.records().size(),
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    })
+                })
+                .collect();
+        }
+    }
+
+    fn pack_with(&mut self, pipeline: Arc<wgpu::ComputePipeline>) {
+        assert_eq!(self.in_flight, 0, "enable_packed_readback called with frames in flight");
+        for chunk in &mut self.chunks {
+            chunk.packer = Some(Packer::new(&self.device, pipeline.clone(), &chunk.buffer, chunk.range.len() as u32));
+        }
+        for (i, slot) in self.readback_slots.iter_mut().enumerate() {
+            slot.stars = self
+                .chunks
+                .iter()
+                .enumerate()
+                .map(|(k, chunk)| {
+                    self.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(&format!("Packed Readback Buffer {}.{}", i, k)),
+                        size: chunk.packer.as_ref().unwrap().records().size(),
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    })
+                })
+                .collect();
+        }
+    }
+
+    // Draws every frame submitted from now on into an image on the GPU (FrameData::image),
+    // from `camera` until set_camera() changes it.
+    pub fn enable_raster(&mut self, settings: &RenderSettings, camera: &Camera) -> Result<(), ChronoError> {
+        let pipelines = Arc::new(RasterPipelines::new(&self.device));
+        self.raster = Some(GpuRaster::new(&self.device, pipelines, self.star_buffers(), self.readback_depth(), settings, camera)?);
+        Ok(())
+    }
+
+    // The view for frames submitted from now on; ignored without enable_raster().
+    pub fn set_camera(&mut self, camera: &Camera) {
+        if let Some(raster) = &mut self.raster {
+            raster.set_camera(camera);
+        }
+    }
+
+    // A simulation of new initial data (any star count) on this one's device and pipelines,
+    // for sweeps that would otherwise recompile the shader per configuration.
+    pub fn resized(&self, config: &SimConfig, initial_data: Vec<Star>) -> Result<Self, ChronoError> {
+        let mut config = config.clone();
+        config.stars = initial_data.len() as u32;
+        config.validate(&self.device.limits())?;
+        if config.workgroup_size != self.config.workgroup_size {
+            return Err(ChronoError::InvalidConfig("resized() keeps the compiled --workgroup-size".into()));
+        }
+        if config.accel != self.config.accel {
+            return Err(ChronoError::InvalidConfig("resized() keeps the compiled --accel kernel".into()));
+        }
+        if config.precision != self.config.precision {
+            return Err(ChronoError::InvalidConfig("resized() keeps the compiled --precision star layout".into()));
+        }
+        let mut pipelines = self.pipelines();
+        // --unified-kernel only changes the force kernels, on the same layout.
+        if config.unified_kernel != self.config.unified_kernel {
+            let start = Instant::now();
+            let (layout, path) = (self.compute_pipeline.layout.clone(), self.compute_pipeline.path);
+            pipelines.compute = Arc::new(
+                Kernels::new(&self.device, &config, include_str!("shader.wgsl"), layout, path).map_err(ChronoError::ShaderLayout)?,
+            );
+            tracing::debug!("Compiled the force kernels cold in {:.1} ms", start.elapsed().as_secs_f64() * 1000.0);
+        }
+        let mut resized = Self::with_pipeline(self.device.clone(), self.queue.clone(), config, initial_data, pipelines);
+        resized.device_errors = self.device_errors.clone();
+        Ok(resized)
+    }
+
+    fn pipelines(&self) -> Pipelines {
+        Pipelines {
+            compute: self.compute_pipeline.clone(),
+            reduce: self.chunks[0].reduction.as_ref().map(|r| r.pipeline().clone()),
+            sort: self.sort.as_ref().map(|s| s.pipelines().clone()),
+            grid: self.grid.as_ref().map(|g| g.pipelines().clone()),
+            compaction: self.compaction.as_ref().map(|c| c.pipelines().clone()),
+            cull: self.culling.as_ref().map(|c| c.pipelines().clone()),
+            merge: self.merging.as_ref().map(|m| m.pipelines().clone()),
+            watchdog: Some(self.watchdog.pipelines().clone()),
+        }
+    }
+
+    fn with_pipeline(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        config: SimConfig,
+        initial_data: Vec<Star>,
+        pipelines: Pipelines,
+    ) -> Self {
+        let Pipelines {
+            compute: compute_pipeline,
+            reduce: reduce_pipeline,
+            sort: sort_pipelines,
+            grid: grid_pipelines,
+            compaction: compaction_pipelines,
+            cull: cull_pipelines,
+            merge: merge_pipelines,
+            watchdog: watchdog_pipelines,
+        } = pipelines;
+        let mut compiles = Compiles::default();
+        // shader.wgsl as cull, merge and the watchdog run it, compiled once for whichever need it
+        let shader = std::cell::OnceCell::new();
+        let shader = || shader.get_or_init(|| shader_module(&device, config.workgroup_size, config.precision));
+        let layout = config.chunk_layout(&device.limits()).expect("config is validated before with_pipeline");
+        let star_buffers: Vec<wgpu::Buffer> = layout
+            .ranges()
+            .enumerate()
+            .map(|(k, range)| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Star Storage {}", k)),
+                    contents: &config.precision.encode(&initial_data[range]),
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                })
+            })
+            .collect();
+
+        let state = GalaxyState::new(0.0, DilationMode::Newtonian.as_uniform(), &config);
+        // The state as of the last step, for read_state(); the kernels read theirs from `states`.
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Galaxy State"),
+            contents: bytemuck::cast_slice(&[state]),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        });
+        let state_readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Galaxy State Readback"),
+            size: std::mem::size_of::<GalaxyState>() as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let interactions = config.interactions().expect("config is validated before with_pipeline");
+        let interactions_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Interactions"),
+            contents: bytemuck::bytes_of(&interactions.uniform()),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let interactions_entry = wgpu::BindGroupEntry { binding: 13, resource: interactions_buffer.as_entire_binding() };
+
+        let active_counter = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Active Counter"),
+            contents: bytemuck::cast_slice(&[0u32; 2]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let slot_count = config.readback_buffers.max(1);
+        let timestamps = device.features().contains(wgpu::Features::TIMESTAMP_QUERY).then(|| Timestamps {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Compute Timestamps"),
+                ty: wgpu::QueryType::Timestamp,
+                count: 2 * slot_count as u32,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp Resolve"),
+                size: slot_count as u64 * wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            staging: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Step Timestamps"),
+                size: TIMESTAMP_BYTES,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            period_ns: queue.get_timestamp_period(),
+        });
+
+        // Every dispatch binds every star buffer, since partners come from the whole population.
+        let placeholder = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Star Placeholder"),
+            size: config.precision.star_bytes(),
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let star_binding = |k: usize| star_buffers.get(k).unwrap_or(&placeholder).as_entire_binding();
+        let grid = (config.accel == Accel::Grid).then(|| {
+            let pipelines = compiles.reuse_or("grid", grid_pipelines, || GridPipelines::new(&device, config.precision));
+            let buffers: Vec<_> = star_buffers.iter().zip(layout.ranges()).collect();
+            Grid::new(&device, pipelines, &buffers, layout.chunk_stars, grid::grid_cube(&initial_data))
+        });
+        let states =
+            (compute_pipeline.path == UniformPath::Buffer).then(|| StateRing::new(&device, config.steps_per_frame.min(MAX_STATE_SLOTS)));
+        let full_params: Vec<ChunkParams> = layout
+            .ranges()
+            .map(|range| ChunkParams {
+                offset: range.start as u32,
+                count: range.len() as u32,
+                stride: layout.chunk_stars,
+                total: layout.stars,
+            })
+            .collect();
+        let chunk_params: Vec<wgpu::Buffer> = full_params
+            .iter()
+            .map(|params| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Chunk Params"),
+                    contents: bytemuck::bytes_of(params),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                })
+            })
+            .collect();
+        // shader.wgsl's bindings 0 to 6 for chunk `k`
+        let chunk_entries = |k: usize| {
+            let mut entries = vec![
+                wgpu::BindGroupEntry { binding: 0, resource: star_binding(0) },
+                wgpu::BindGroupEntry { binding: 2, resource: active_counter.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: chunk_params[k].as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: star_binding(1) },
+                wgpu::BindGroupEntry { binding: 5, resource: star_binding(2) },
+                wgpu::BindGroupEntry { binding: 6, resource: star_binding(3) },
+            ];
+            entries.extend(states.as_ref().map(StateRing::entry));
+            entries
+        };
+        let workgroups: Vec<u32> = layout.ranges().map(|range| (range.len() as u32).div_ceil(config.workgroup_size)).collect();
+        let bind_groups: Vec<wgpu::BindGroup> = (0..layout.count())
+            .map(|k| {
+                let mut entries = chunk_entries(k);
+                entries.push(interactions_entry.clone());
+                entries.extend(grid.iter().flat_map(Grid::entries));
+                device.create_bind_group(&wgpu::BindGroupDescriptor { label: None, layout: &compute_pipeline.layout, entries: &entries })
+            })
+            .collect();
+        let compaction = config.compaction.then(|| {
+            let pipelines = compiles.reuse_or("compaction", compaction_pipelines, || {
+                CompactionPipelines::new(&device, config.workgroup_size, config.accel, config.precision, compute_pipeline.path)
+            });
+            let chunks: Vec<_> = (0..layout.count()).map(|k| (chunk_entries(k), workgroups[k])).collect();
+            Compaction::new(&device, pipelines, layout.stars, &chunks, interactions_entry, grid.as_ref())
+        });
+        let culling = config.cull_radius().map(|radius| {
+            let pipelines = compiles.reuse_or("cull", cull_pipelines, || CullPipelines::new(&device, shader()));
+            let chunks: Vec<_> = (0..layout.count())
+                .map(|k| {
+                    let entries = chunk_entries(k).into_iter().filter(|entry| ![1, 2].contains(&entry.binding)).collect();
+                    (entries, bytemuck::cast(full_params[k]), workgroups[k])
+                })
+                .collect();
+            Culling::new(&device, pipelines, radius, &chunks)
+        });
+        let merging = grid.as_ref().filter(|_| config.merge_radius > 0.0).map(|grid| {
+            let pipelines = compiles.reuse_or("merge", merge_pipelines, || MergePipelines::new(&device, shader()));
+            let chunks: Vec<_> = (0..layout.count())
+                .map(|k| {
+                    let entries =
+                        chunk_entries(k).into_iter().filter(|entry| ![1, 2].contains(&entry.binding)).chain(grid.entries()).collect();
+                    (entries, workgroups[k])
+                })
+                .collect();
+            Merging::new(&device, pipelines, config.merge_radius, layout.stars, &chunks)
+        });
+        let watchdog = {
+            let pipelines = compiles.reuse_or("watchdog", watchdog_pipelines, || WatchdogPipelines::new(&device, shader()));
+            let chunks: Vec<_> = (0..layout.count())
+                .map(|k| (chunk_entries(k).into_iter().filter(|entry| ![1, 2].contains(&entry.binding)).collect(), workgroups[k]))
+                .collect();
+            Watchdog::new(&device, pipelines, config.divergence_bound, &chunks)
+        };
+        let chunks: Vec<Chunk> = star_buffers
+            .into_iter()
+            .zip(bind_groups)
+            .zip(layout.ranges().zip(workgroups))
+            .zip(chunk_params.into_iter().zip(full_params))
+            .map(|(((buffer, bind_group), (range, workgroups)), (params, full_params))| Chunk {
+                reduction: reduce_pipeline
+                    .clone()
+                    .map(|pipeline| Reduction::new(&device, pipeline, &buffer, range.len() as u32, Quantity::Moments)),
+                workgroups,
+                packer: None,
+                buffer,
+                range,
+                params,
+                full_params,
+                bind_group,
+            })
+            .collect();
+
+        // Culling moves the dead with a partition sort, whose ids also put readbacks back in order.
+        let sort = (config.sort_every.is_some() || culling.is_some()).then(|| {
+            let pipelines = compiles.reuse_or("sort", sort_pipelines, || SortPipelines::new(&device, config.precision));
+            let buffers: Vec<_> = chunks.iter().map(|c| (&c.buffer, c.range.clone())).collect();
+            MortonSort::new(&device, pipelines, &buffers, layout.chunk_stars, sort::quantization_cube(&initial_data))
+        });
+
+        let extras_size = COUNTER_BYTES
+            + if timestamps.is_some() { TIMESTAMP_BYTES } else { 0 }
+            + chunks.iter().filter_map(|c| c.reduction.as_ref()).map(Reduction::partial_bytes).sum::<u64>();
+        let readback_slots = (0..slot_count)
+            .map(|i| ReadbackSlot {
+                stars: chunks
+                    .iter()
+                    .enumerate()
+                    .map(|(k, chunk)| {
+                        device.create_buffer(&wgpu::BufferDescriptor {
+                            label: Some(&format!("Readback Buffer {}.{}", i, k)),
+                            size: chunk.buffer.size(),
+                            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                            mapped_at_creation: false,
+                        })
+                    })
+                    .collect(),
+                ids: sort.as_ref().map(|sort| {
+                    device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(&format!("Readback Ids {}", i)),
+                        size: sort.ids().size(),
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    })
+                }),
+                extras: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Readback Extras {}", i)),
+                    size: extras_size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                pending: Vec::new(),
+                timed: false,
+                steps: 0,
+                with_stars: true,
+                trails: Vec::new(),
+            })
+            .collect();
+
+        compiles.log();
+        Self {
+            device,
+            queue,
+            config,
+            initial_data,
+            state,
+            chunks,
+            uniform_buffer,
+            state_readback,
+            active_counter,
+            timestamps,
+            readback_slots,
+            next_slot: 0,
+            in_flight: 0,
+            states,
+            compute_pipeline,
+            raster: None,
+            trail_packers: Vec::new(),
+            sort,
+            frames_until_sort: 0,
+            grid,
+            compaction,
+            culling,
+            frames_until_cull: 0,
+            merging,
+            watchdog,
+            device_errors: DeviceErrors::default(),
+            readback_fault: None,
+            map_failures: 0,
+        }
+    }
+
+    pub fn config(&self) -> &SimConfig {
+        &self.config
+    }
+
+    // How the kernels get each step's GalaxyState, as UniformPath::select chose it.
+    pub fn uniform_path(&self) -> UniformPath {
+        self.compute_pipeline.path
+    }
+
+    // Reports what `errors` (gpu::DeviceErrors::watch or watch_loss on this device) catches
+    // as the Stepper::fault after each frame; forks and resized() copies share it.
+    pub fn watch_device(&mut self, errors: DeviceErrors) {
+        self.device_errors = errors;
+    }
+
+    // A lost device first, since its readbacks fail with it.
+    pub fn fault(&self) -> Option<ChronoError> {
+        match self.device_errors.fault() {
+            Some(fault) => Some(fault.to_error()),
+            None => match &self.readback_fault {
+                Some(ReadbackFault::Failed(message)) => Some(ChronoError::MapFailed(message.clone())),
+                Some(ReadbackFault::TimedOut) => Some(ChronoError::MapTimeout { secs: self.config.map_timeout_secs }),
+                None => None,
+            },
+        }
+    }
+
+    pub fn map_failures(&self) -> u32 {
+        self.map_failures
+    }
+
+    pub fn star_count(&self) -> u32 {
+        self.config.stars
+    }
+
+    pub fn initial_stars(&self) -> &[Star] {
+        &self.initial_data
+    }
+
+    pub fn state(&self) -> GalaxyState {
+        self.state
+    }
+
+    // Simulated time elapsed, i.e. the time_seed the last dispatch saw.
+    pub fn sim_time(&self) -> f32 {
+        self.state.time_seed
+    }
+
+    pub fn has_timestamps(&self) -> bool {
+        self.timestamps.is_some()
+    }
+
+    // Bytes of star storage across every chunk.
+    pub fn star_buffer_size(&self) -> u64 {
+        self.chunks.iter().map(|c| c.buffer.size()).sum()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    // Each chunk's storage buffer and star count, in index order, for drawing straight from the GPU.
+    pub fn star_buffers(&self) -> impl Iterator<Item = (&wgpu::Buffer, u32)> {
+        self.chunks.iter().map(|c| (&c.buffer, c.range.len() as u32))
+    }
+
+    pub fn device(&self) -> &Arc<wgpu::Device> {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &Arc<wgpu::Queue> {
+        &self.queue
+    }
+
+    fn upload(&self, stars: &[Star]) {
+        for chunk in &self.chunks {
+            self.queue.write_buffer(&chunk.buffer, 0, &self.config.precision.encode(&stars[chunk.range.clone()]));
+        }
+        if let Some(sort) = &self.sort {
+            sort.reset_ids(&self.queue);
+        }
+        if let Some(grid) = &self.grid {
+            grid.set_cube(&self.queue, grid::grid_cube(stars));
+        }
+        if let Some(merging) = &self.merging {
+            merging.reset(&self.queue);
+        }
+        if self.compaction.is_none() && self.culling.is_none() {
+            return;
+        }
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        if let Some(compaction) = &self.compaction {
+            compaction.forget_tension(&mut encoder);
+        }
+        // The uploaded stars are all alive until the cull that goes right after them.
+        for chunk in self.chunks.iter().filter(|_| self.culling.is_some()) {
+            self.queue.write_buffer(&chunk.params, 0, bytemuck::bytes_of(&chunk.full_params));
+        }
+        self.encode_cull(&mut encoder);
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    // Re-uploads the initial galaxy and rewinds the clock, keeping every GPU resource.
+    pub fn reset(&mut self) {
+        self.upload(&self.initial_data);
+        self.frames_until_sort = 0;
+        self.frames_until_cull = self.config.cull_every;
+        self.state.time_seed = 0.0;
+        self.state.first_step = 1.0;
+        self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.state]));
+    }
+
+    // Uploads saved stars and clock (e.g. from a checkpoint); reset() still returns to the initial galaxy.
+    // Softening, G and dt stay as configured; only the clock and mode come from `state`.
+    pub fn restore(&mut self, state: GalaxyState, stars: &[Star]) {
+        assert_eq!(stars.len(), self.initial_data.len(), "restore() needs exactly star_count() stars");
+        self.upload(stars);
+        self.frames_until_sort = 0;
+        self.frames_until_cull = self.config.cull_every;
+        self.state.time_seed = state.time_seed;
+        self.state.dilation_mode = state.dilation_mode;
+        // Saved leapfrog velocities are already offset by the half step.
+        self.state.first_step = 0.0;
+        self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.state]));
+    }
+
+    pub fn set_mode(&mut self, mode: DilationMode) {
+        self.state.dilation_mode = mode.as_uniform();
+        self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.state]));
+    }
+
+    // Compiles the force kernels from `source` (--shader) on the current bind group layout,
+    // without switching to them. If the source does not compile, or no longer matches the
+    // Rust structs or the layout, the message, with the offending line or field, comes back.
+    pub async fn compile_kernels(&self, source: &str) -> Result<CompiledKernels, String> {
+        shader_layout::check_shader(source)?;
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let kernels = Kernels::new(&self.device, &self.config, source, self.compute_pipeline.layout.clone(), self.compute_pipeline.path);
+        let error = self.device.pop_error_scope().await;
+        match (kernels, error) {
+            (Ok(kernels), None) => Ok(CompiledKernels { source: source.to_string(), kernels: Arc::new(kernels) }),
+            (Err(message), _) => Err(message),
+            (_, Some(error)) => Err(error.to_string()),
+        }
+    }
+
+    // Switches to kernels compile_kernels made, between frames: the stars, clock and bind
+    // groups carry on.
+    pub fn install_kernels(&mut self, kernels: CompiledKernels) {
+        self.compute_pipeline = kernels.kernels;
+    }
+
+    // compile_kernels then install_kernels; on an error the old kernels stay.
+    pub async fn reload_kernels(&mut self, source: &str) -> Result<(), String> {
+        let kernels = self.compile_kernels(source).await?;
+        self.install_kernels(kernels);
+        Ok(())
+    }
+
+    // Changes --dilation-threshold and --max-debt without touching buffers or the clock.
+    pub fn set_dilation(&mut self, dilation_threshold: f32, max_debt: f32) {
+        self.config.dilation_threshold = dilation_threshold;
+        self.config.max_debt = max_debt;
+        self.state.dilation_threshold = dilation_threshold;
+        self.state.max_time_debt = max_debt;
+        self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.state]));
+    }
+
+    // Reorders the stars now and restarts the --sort-every countdown; readbacks still come
+    // back in creation order. Does nothing without --sort-every.
+    pub fn sort_stars(&mut self) {
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.frames_until_sort = 0;
+        self.encode_due_sort(&mut encoder);
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    // Records a sort when the countdown has run out, and counts one frame down.
+    fn encode_due_sort(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let (Some(sort), Some(every)) = (&self.sort, self.config.sort_every) else { return };
+        if self.frames_until_sort == 0 {
+            sort.encode(encoder, self.chunks.iter().map(|c| &c.buffer));
+            // The calm flags stay where the stars were.
+            if let Some(compaction) = &self.compaction {
+                compaction.forget_tension(encoder);
+            }
+            self.frames_until_sort = every;
+        }
+        self.frames_until_sort -= 1;
+    }
+
+    // Records a cull when its countdown has run out, and counts one frame down. With
+    // --merge-radius, every frame merges and then culls.
+    fn encode_due_cull(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.culling.is_none() {
+            return;
+        }
+        if let (Some(merging), Some(grid)) = (&self.merging, &self.grid) {
+            merging.encode(encoder, grid);
+            self.encode_cull(encoder);
+            return;
+        }
+        if self.frames_until_cull == 0 {
+            self.encode_cull(encoder);
+            self.frames_until_cull = self.config.cull_every;
+        }
+        self.frames_until_cull -= 1;
+    }
+
+    // Flags the stars past --kill-radius, moves them behind the living and cuts every
+    // chunk's params and dispatch down to the living. Does nothing without --kill-radius.
+    fn encode_cull(&self, encoder: &mut wgpu::CommandEncoder) {
+        let (Some(culling), Some(sort)) = (&self.culling, &self.sort) else { return };
+        culling.mark(encoder);
+        sort.encode_partition(encoder, self.chunks.iter().map(|c| &c.buffer));
+        culling.apply(encoder, self.chunks.iter().map(|c| &c.params));
+        if let Some(compaction) = &self.compaction {
+            compaction.forget_tension(encoder);
+        }
+    }
+
+    // Runs `n` simulation steps, advancing the shader clock by dt before each one.
+    pub fn step(&mut self, n: usize) {
+        if self.config.legacy_submit {
+            self.step_legacy(n, None);
+            return;
+        }
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.encode_steps(&mut encoder, n, None);
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    // Runs `n` steps, at most STEPS_PER_SUBMIT to a command buffer, and waits for the GPU to
+    // finish them, for callers that step without reading back (the python and capi features).
+    pub fn step_and_wait(&mut self, n: usize) {
+        for start in (0..n).step_by(STEPS_PER_SUBMIT) {
+            self.step(STEPS_PER_SUBMIT.min(n - start));
+        }
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+
+    // Number of frames that may be submitted before the oldest must be finished.
+    pub fn readback_depth(&self) -> usize {
+        self.readback_slots.len()
+    }
+
+    // Steps and copies the result back using a single submission.
+    pub fn step_and_read(&mut self, n: usize) -> Vec<Star> {
+        let ticket = self.submit_frame(n);
+        self.finish_frame(ticket).stars
+    }
+
+    // Copies the star buffer back to the CPU, blocking until the GPU is done.
+    pub fn read_stars(&mut self) -> Vec<Star> {
+        self.step_and_read(0)
+    }
+
+    // Submits `n` steps plus the copy into the next readback slot without waiting.
+    // At most readback_depth() tickets may be outstanding, finished in FIFO order.
+    pub fn submit_frame(&mut self, n: usize) -> FrameTicket {
+        self.submit(n, true)
+    }
+
+    // Like submit_frame, but only the active counter, timestamps and reduction come back;
+    // the finished FrameData has no stars.
+    pub fn submit_stats(&mut self, n: usize) -> FrameTicket {
+        self.submit(n, false)
+    }
+
+    fn submit(&mut self, n: usize, with_stars: bool) -> FrameTicket {
+        assert!(self.in_flight < self.readback_slots.len(), "submit_frame called with every readback slot in flight");
+        let slot = self.next_slot;
+        self.next_slot = (self.next_slot + 1) % self.readback_slots.len();
+        self.in_flight += 1;
+        let _span = tracing::trace_span!("submit_frame", slot, steps = n).entered();
+
+        // Lands ahead of this frame's first dispatch (and after every earlier submission).
+        self.queue.write_buffer(&self.active_counter, 0, bytemuck::cast_slice(&[0u32; 2]));
+        if let Some(compaction) = &self.compaction {
+            compaction.clear_threads(&self.queue);
+        }
+
+        // Set first: the steps read it for --trails.
+        self.readback_slots[slot].with_stars = with_stars;
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.encode_due_sort(&mut encoder);
+        self.encode_due_cull(&mut encoder);
+        if self.config.legacy_submit {
+            // The sort goes ahead of the per-step submissions.
+            let sorted =
+                std::mem::replace(&mut encoder, self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None }));
+            self.queue.submit(Some(sorted.finish()));
+            self.step_legacy(n, Some(slot));
+        } else {
+            self.encode_steps(&mut encoder, n, Some(slot));
+        }
+        let readback = &self.readback_slots[slot];
+        for (chunk, buffer) in self.chunks.iter().zip(&readback.stars).filter(|_| with_stars) {
+            match &chunk.packer {
+                Some(packer) => {
+                    packer.encode(&mut encoder);
+                    encoder.copy_buffer_to_buffer(packer.records(), 0, buffer, 0, buffer.size());
+                }
+                None => encoder.copy_buffer_to_buffer(&chunk.buffer, 0, buffer, 0, chunk.buffer.size()),
+            }
+        }
+        if let (true, Some(sort), Some(ids)) = (with_stars, &self.sort, &readback.ids) {
+            encoder.copy_buffer_to_buffer(sort.ids(), 0, ids, 0, ids.size());
+        }
+        encoder.copy_buffer_to_buffer(&self.active_counter, 0, &readback.extras, 0, 4);
+        if let Some(compaction) = &self.compaction {
+            compaction.copy_threads(&mut encoder, &readback.extras, 4);
+        }
+        if let Some(culling) = &self.culling {
+            culling.copy_alive(&mut encoder, &readback.extras, 8);
+        }
+        if let Some(merging) = &self.merging {
+            merging.copy_mergers(&mut encoder, &readback.extras, 12);
+        }
+        self.watchdog.encode(&mut encoder, &readback.extras, 16);
+        encoder.copy_buffer_to_buffer(&self.active_counter, 4, &readback.extras, SUBSTEPS_OFFSET, 4);
+        let timed = n > 0 && self.timestamps.is_some();
+        if let (true, Some(ts)) = (timed, &self.timestamps) {
+            let query = 2 * slot as u32;
+            let offset = slot as u64 * wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT;
+            encoder.resolve_query_set(&ts.query_set, query..query + 2, &ts.resolve_buffer, offset);
+            encoder.copy_buffer_to_buffer(&ts.resolve_buffer, offset, &readback.extras, COUNTER_BYTES, TIMESTAMP_BYTES);
+        }
+        let mut offset = self.partials_offset();
+        for reduction in self.chunks.iter().filter_map(|c| c.reduction.as_ref()) {
+            reduction.encode(&mut encoder);
+            encoder.copy_buffer_to_buffer(reduction.partials(), 0, &readback.extras, offset, reduction.partial_bytes());
+            offset += reduction.partial_bytes();
+        }
+        let mode = DilationMode::from_uniform(self.state.dilation_mode);
+        if let (true, Some(raster)) = (with_stars, &mut self.raster) {
+            raster.encode(&self.queue, &mut encoder, slot, mode);
+        }
+        let submission = self.queue.submit(Some(encoder.finish()));
+        if let (true, Some(raster)) = (with_stars, &mut self.raster) {
+            raster.map(slot);
+        }
+
+        let pending = readback.buffers().map(gpu::map_read).collect();
+        self.readback_slots[slot].pending = pending;
+        self.readback_slots[slot].timed = timed;
+        self.readback_slots[slot].steps = n;
+
+        FrameTicket { slot, submission, sim_time: self.state.time_seed }
+    }
+
+    // Waits for that ticket's submission only (later frames keep running) and copies it out.
+    pub fn finish_frame(&mut self, ticket: FrameTicket) -> FrameData {
+        let _span = tracing::trace_span!("readback", slot = ticket.slot).entered();
+        self.device.poll(wgpu::Maintain::WaitForSubmissionIndex(ticket.submission));
+        self.in_flight -= 1;
+        let copy_start = Instant::now();
+
+        let packed = self.chunks[0].packer.is_some();
+        let slot = &self.readback_slots[ticket.slot];
+        let mut frame = FrameData {
+            stars: Vec::new(),
+            sim_time: ticket.sim_time,
+            gpu_ms: None,
+            active_updates: 0,
+            steps: slot.steps,
+            moments: None,
+            image: None,
+            packed: packed && slot.with_stars,
+            readback_bytes: slot.buffers().map(wgpu::Buffer::size).sum(),
+            readback_ms: 0.0,
+            force_threads: None,
+            alive: self.initial_data.len() as u32,
+            mergers: None,
+            position_lo: None,
+            watchdog: WatchdogCounts::default(),
+            substeps: None,
+            placeholder: false,
+            stats_only: !slot.with_stars,
+            trails: Vec::new(),
+        };
+        // A frame that does not come back is reported by fault(), and the run decides what to do.
+        self.readback_fault = match self.read_back(ticket.slot, &mut frame) {
+            Ok(stars) => {
+                frame.stars = stars;
+                None
+            }
+            Err(error) => {
+                self.map_failures += 1;
+                tracing::error!(
+                    "Frame at sim time {:.2} did not read back ({} failed so far): {}",
+                    frame.sim_time,
+                    self.map_failures,
+                    error
+                );
+                Some(ReadbackFault::from(error))
+            }
+        };
+        frame.readback_ms = copy_start.elapsed().as_secs_f32() * 1000.0;
+        if self.readback_fault.is_none() {
+            let timeout = Duration::from_secs_f32(self.config.map_timeout_secs);
+            frame.image = self.raster.as_mut().and_then(|raster| raster.finish(ticket.slot, &frame.stars, timeout));
+        }
+        frame
+    }
+
+    // Settles the maps of readback slot `index` and copies its stars out, filling in the rest
+    // of `frame` from the extras. Every buffer is left unmapped, whether or not it came back,
+    // so the slot's next frame can map it again.
+    fn read_back(&mut self, index: usize, frame: &mut FrameData) -> Result<Vec<Star>, ChronoError> {
+        let period_ns = self.timestamps.as_ref().map_or(0.0, |ts| ts.period_ns);
+        let partials_offset = self.partials_offset() as usize;
+        let reduced = self.chunks[0].reduction.is_some();
+        let packed = self.chunks[0].packer.is_some();
+        let compacted = self.compaction.is_some();
+        let culled = self.culling.is_some();
+        let merged = self.merging.is_some();
+        let precision = self.config.precision;
+        let stars = self.initial_data.len();
+        let (stride, workgroup_size) = (self.chunks[0].full_params.stride, self.config.workgroup_size);
+        let full_threads = self.chunks.iter().map(|c| c.workgroups as u64).sum::<u64>() * workgroup_size as u64;
+        let chunk_lens: Vec<usize> = self.chunks.iter().map(|c| c.range.len()).collect();
+        let trail_steps = if self.trail_packers.is_empty() { 0 } else { self.config.steps_per_frame.saturating_sub(1) };
+        let timeout = Duration::from_secs_f32(self.config.map_timeout_secs);
+
+        let (device, slot) = (&self.device, &mut self.readback_slots[index]);
+        let pending = std::mem::take(&mut slot.pending);
+        let buffers: Vec<&wgpu::Buffer> = slot.buffers().collect();
+        let remap = |i: usize| {
+            let rx = gpu::map_read(buffers[i]);
+            device.poll(wgpu::Maintain::Wait);
+            rx
+        };
+        let outcomes = gpu::settle_maps(pending, |rx| gpu::await_map(device, rx, timeout), remap);
+        let result = gpu::map_result(&outcomes, self.config.map_timeout_secs).map(|()| {
+            let mut stars = Vec::with_capacity(if slot.with_stars { stars } else { 0 });
+            let mut lo = Vec::new();
+            for buffer in slot.stars.iter().filter(|_| slot.with_stars) {
+                let data = buffer.slice(..).get_mapped_range();
+                if packed {
+                    stars.extend(bytemuck::cast_slice::<u8, RenderRecord>(&data).iter().map(RenderRecord::unpack));
+                } else {
+                    precision.decode(&data, &mut stars, &mut lo);
+                }
+            }
+            // Each chunk's trail buffer holds its records for one sub-step after another.
+            let trail_steps = if slot.with_stars { trail_steps.min(slot.steps.saturating_sub(1)) } else { 0 };
+            let mut trails: Vec<Vec<Star>> = vec![Vec::with_capacity(stars.len()); trail_steps];
+            for (buffer, &len) in slot.trails.iter().zip(&chunk_lens).filter(|_| slot.with_stars) {
+                let data = buffer.slice(..).get_mapped_range();
+                let records: &[RenderRecord] = bytemuck::cast_slice(&data);
+                for (trail, step) in trails.iter_mut().zip(records.chunks(len)) {
+                    trail.extend(step.iter().map(RenderRecord::unpack));
+                }
+            }
+            if let Some(ids) = slot.ids.as_ref().filter(|_| slot.with_stars) {
+                let ids = ids.slice(..).get_mapped_range();
+                let ids: &[u32] = bytemuck::cast_slice(&ids);
+                let mut ordered = vec![bytemuck::Zeroable::zeroed(); stars.len()];
+                for (star, &id) in stars.iter().zip(ids) {
+                    ordered[id as usize] = *star;
+                }
+                stars = ordered;
+                let mut ordered = vec![[0.0; 3]; lo.len()];
+                for (lo, &id) in lo.iter().zip(ids) {
+                    ordered[id as usize] = *lo;
+                }
+                lo = ordered;
+                for trail in &mut trails {
+                    let mut ordered = trail.clone();
+                    for (star, &id) in trail.iter().zip(ids) {
+                        ordered[id as usize] = *star;
+                    }
+                    *trail = ordered;
+                }
+            }
+            frame.trails = trails;
+            frame.position_lo = (precision == Precision::Df64 && slot.with_stars).then_some(lo);
+            let data = slot.extras.slice(..).get_mapped_range();
+            frame.active_updates = bytemuck::pod_read_unaligned(&data[..4]);
+            if culled {
+                frame.alive = bytemuck::pod_read_unaligned(&data[8..12]);
+            }
+            if merged {
+                frame.mergers = Some(bytemuck::pod_read_unaligned(&data[12..16]));
+            }
+            frame.watchdog = bytemuck::pod_read_unaligned(&data[16..SUBSTEPS_OFFSET as usize]);
+            if !compacted {
+                let at = SUBSTEPS_OFFSET as usize;
+                frame.substeps = Some(bytemuck::pod_read_unaligned(&data[at..at + 4]));
+            }
+            // Each chunk's dispatch rounds its own living stars up to whole workgroups.
+            let threads_per_step = if culled {
+                let alive = frame.alive;
+                (0..self.chunks.len() as u32)
+                    .map(|k| (alive.saturating_sub(k * stride).min(stride)).div_ceil(workgroup_size) as u64 * workgroup_size as u64)
+                    .sum()
+            } else {
+                full_threads
+            };
+            frame.force_threads = Some(if compacted {
+                bytemuck::pod_read_unaligned::<u32>(&data[4..8]) as u64
+            } else {
+                slot.steps as u64 * threads_per_step
+            });
+            if slot.timed {
+                let ts_start = COUNTER_BYTES as usize;
+                let ticks: [u64; 2] = bytemuck::pod_read_unaligned(&data[ts_start..ts_start + TIMESTAMP_BYTES as usize]);
+                frame.gpu_ms = Some(ticks[1].wrapping_sub(ticks[0]) as f32 * period_ns / 1_000_000.0);
+            }
+            if reduced {
+                frame.moments = Some(Moments::from_sums(&Reduction::sum(&data[partials_offset..])));
+            }
+            stars
+        });
+        for (buffer, outcome) in buffers.iter().zip(&outcomes) {
+            if outcome.needs_unmap() {
+                buffer.unmap();
+            }
+        }
+        result
+    }
+
+    // The timestamps are always reserved in the extras layout when supported, used or not.
+    fn partials_offset(&self) -> u64 {
+        COUNTER_BYTES + if self.timestamps.is_some() { TIMESTAMP_BYTES } else { 0 }
+    }
+
+    // One dispatch per chunk, after the grid's binning passes with --accel grid; each updates
+    // its own stars and reads partners from all of them with the step's `state`.
+    fn dispatch<'a>(&'a self, cpass: &mut wgpu::ComputePass<'a>, state: StepState) {
+        if let Some(grid) = &self.grid {
+            grid.encode(cpass);
+        }
+        if let Some(compaction) = &self.compaction {
+            compaction.encode(cpass, state);
+            return;
+        }
+        cpass.set_pipeline(self.compute_pipeline.for_mode(DilationMode::from_uniform(self.state.dilation_mode)));
+        for (k, chunk) in self.chunks.iter().enumerate() {
+            state.bind(cpass, &chunk.bind_group);
+            match &self.culling {
+                Some(culling) => {
+                    let (buffer, offset) = culling.dispatch_args(k);
+                    cpass.dispatch_workgroups_indirect(buffer, offset);
+                }
+                None => cpass.dispatch_workgroups(chunk.workgroups, 1, 1),
+            }
+        }
+    }
+
+    // Original behavior: one encoder and one submit per sub-step (--legacy-submit).
+    fn step_legacy(&mut self, n: usize, slot: Option<usize>) {
+        for k in 0..n {
+            self.state.time_seed += self.state.dt;
+            self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.state]));
+            let state = match &self.states {
+                Some(states) => {
+                    states.write_first(&self.queue, &self.state);
+                    StepState::Offset(0)
+                }
+                None => StepState::Push(self.state),
+            };
+            self.state.first_step = 0.0;
+
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            {
+                let timestamp_writes = self.timestamp_writes(slot, k, n);
+                let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None, timestamp_writes });
+                self.dispatch(&mut cpass, state);
+            }
+            if k + 1 < n {
+                self.encode_trail(&mut encoder, slot, k);
+            }
+            self.queue.submit(Some(encoder.finish()));
+        }
+    }
+
+    // Records `n` compute passes. queue.write_buffer only lands at the next submit, so every
+    // step's GalaxyState is staged at once and each pass binds its own slot of the ring, or
+    // pushes its own on the push path.
+    fn encode_steps(&mut self, encoder: &mut wgpu::CommandEncoder, n: usize, slot: Option<usize>) {
+        if n == 0 {
+            return;
+        }
+        let states: Vec<GalaxyState> = (0..n)
+            .map(|_| {
+                self.state.time_seed += self.state.dt;
+                let state = self.state;
+                self.state.first_step = 0.0;
+                state
+            })
+            .collect();
+        if let Some(ring) = &mut self.states {
+            ring.stage(&self.device, &self.queue, &states);
+        }
+
+        for (k, &pushed) in states.iter().enumerate() {
+            let state = match &self.states {
+                Some(ring) => {
+                    ring.load(encoder, k, n);
+                    StepState::Offset(ring.offset(k))
+                }
+                None => StepState::Push(pushed),
+            };
+            {
+                let timestamp_writes = self.timestamp_writes(slot, k, n);
+                let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None, timestamp_writes });
+                self.dispatch(&mut cpass, state);
+            }
+            if k + 1 < n {
+                self.encode_trail(encoder, slot, k);
+            }
+        }
+        match &self.states {
+            Some(ring) => ring.copy_out(encoder, n - 1, &self.uniform_buffer),
+            None => self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&states[n - 1])),
+        }
+    }
+
+    // --trails: the records of the stars as sub-step `k` left them, into readback slot
+    // `slot`'s trail buffers, unless the frame reads back its stats only.
+    fn encode_trail(&self, encoder: &mut wgpu::CommandEncoder, slot: Option<usize>, k: usize) {
+        let Some(slot) = slot.map(|i| &self.readback_slots[i]).filter(|slot| slot.with_stars) else { return };
+        if k + 1 >= self.config.steps_per_frame {
+            return;
+        }
+        for (packer, buffer) in self.trail_packers.iter().zip(&slot.trails) {
+            let size = packer.records().size();
+            packer.encode(encoder);
+            encoder.copy_buffer_to_buffer(packer.records(), 0, buffer, k as u64 * size, size);
+        }
+    }
+
+    // Brackets the frame's passes: begin on the first, end on the last.
+    fn timestamp_writes(&self, slot: Option<usize>, k: usize, n: usize) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        let (ts, slot) = (self.timestamps.as_ref()?, slot?);
+        let query = 2 * slot as u32;
+        (k == 0 || k + 1 == n).then_some(wgpu::ComputePassTimestampWrites {
+            query_set: &ts.query_set,
+            beginning_of_pass_write_index: (k == 0).then_some(query),
+            end_of_pass_write_index: (k + 1 == n).then_some(query + 1),
+        })
+    }
+
+    // Runs one step with nothing read back but its duration, waiting for it to finish.
+    // Uses readback slot 0's timestamp queries, so no frame may be in flight.
+    pub fn time_step(&mut self) -> Result<(f32, TimingSource), ChronoError> {
+        assert_eq!(self.in_flight, 0, "time_step called with frames in flight");
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.encode_steps(&mut encoder, 1, Some(0));
+        let Some(ts) = &self.timestamps else {
+            let start = std::time::Instant::now();
+            self.queue.submit(Some(encoder.finish()));
+            self.device.poll(wgpu::Maintain::Wait);
+            return Ok((start.elapsed().as_secs_f32() * 1000.0, TimingSource::WallClock));
+        };
+
+        encoder.resolve_query_set(&ts.query_set, 0..2, &ts.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&ts.resolve_buffer, 0, &ts.staging, 0, TIMESTAMP_BYTES);
+        self.queue.submit(Some(encoder.finish()));
+        let ticks: [u64; 2] = gpu::read_mapped(&self.device, &ts.staging, self.config.map_timeout_secs, bytemuck::pod_read_unaligned)?;
+        Ok((ticks[1].wrapping_sub(ticks[0]) as f32 * ts.period_ns / 1_000_000.0, TimingSource::GpuTimestamps))
+    }
+
+    // Reads the uniform buffer as the GPU currently sees it (debugging aid).
+    pub fn read_state(&self) -> Result<GalaxyState, ChronoError> {
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(&self.uniform_buffer, 0, &self.state_readback, 0, self.state_readback.size());
+        self.queue.submit(Some(encoder.finish()));
+        gpu::read_mapped(&self.device, &self.state_readback, self.config.map_timeout_secs, bytemuck::pod_read_unaligned)
+    }
+}
+
+// The last simulation on a device drops it, which is no loss; forks leave it to the last one.
+impl Drop for Simulation {
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.device) == 1 {
+            self.device_errors.expect_drop();
+        }
+    }
+}
+
+impl Stepper for Simulation {
+    type Ticket = FrameTicket;
+
+    fn fork(&self) -> Self {
+        Simulation::fork(self)
+    }
+
+    fn reset(&mut self) {
+        Simulation::reset(self)
+    }
+
+    fn restore(&mut self, state: GalaxyState, stars: &[Star]) {
+        Simulation::restore(self, state, stars)
+    }
+
+    fn set_mode(&mut self, mode: DilationMode) {
+        Simulation::set_mode(self, mode)
+    }
+
+    fn set_dilation(&mut self, dilation_threshold: f32, max_debt: f32) {
+        Simulation::set_dilation(self, dilation_threshold, max_debt)
+    }
+
+    fn readback_depth(&self) -> usize {
+        Simulation::readback_depth(self)
+    }
+
+    fn submit_frame(&mut self, steps: usize) -> FrameTicket {
+        Simulation::submit_frame(self, steps)
+    }
+
+    fn finish_frame(&mut self, ticket: FrameTicket) -> FrameData {
+        Simulation::finish_frame(self, ticket)
+    }
+
+    fn submit_stats(&mut self, steps: usize) -> FrameTicket {
+        Simulation::submit_stats(self, steps)
+    }
+
+    fn set_camera(&mut self, camera: &Camera) {
+        Simulation::set_camera(self, camera)
+    }
+
+    fn fault(&self) -> Option<ChronoError> {
+        Simulation::fault(self)
+    }
+
+    // Error scopes resolve at once on native backends; the web has no --shader to reload.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn compile_kernels(&self, source: &str) -> Result<Option<CompiledKernels>, String> {
+        pollster::block_on(Simulation::compile_kernels(self, source)).map(Some)
+    }
+
+    fn install_kernels(&mut self, kernels: CompiledKernels) {
+        Simulation::install_kernels(self, kernels)
+    }
+}
+
+// shader.wgsl with WORKGROUP_SIZE set to `workgroup_size`, so the compiled workgroup always
+// matches the dispatch math, and its Star laid out for `precision`. wgpu 0.19 has no pipeline-overridable constants, so the
+// declaration is rewritten instead; the panic catches the shader and this drifting apart.
+pub fn shader_source(workgroup_size: u32, precision: Precision) -> String {
+    let source = set_constant(include_str!("shader.wgsl"), "WORKGROUP_SIZE", 256, workgroup_size);
+    source.and_then(|source| precision.kernel(&source)).unwrap_or_else(|e| panic!("{}", e))
+}
+
+// shader_source compiled, for the passes that run its entry points as they are.
+pub fn shader_module(device: &wgpu::Device, workgroup_size: u32, precision: Precision) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("shader.wgsl"),
+        source: wgpu::ShaderSource::Wgsl(shader_source(workgroup_size, precision).into()),
+    })
+}
+
+// shader_source specialized to `mode`, or reading the uniform's mode when None.
+pub fn kernel_source(workgroup_size: u32, mode: Option<DilationMode>) -> String {
+    specialize(include_str!("shader.wgsl"), workgroup_size, mode, Precision::F32, Accel::Sampled).unwrap_or_else(|e| panic!("{}", e))
+}
+
+// kernel_source for any copy of shader.wgsl, e.g. --shader's. Each rewrite stays on its
+// line, so compiler messages point at the file's own line numbers.
+fn specialize(source: &str, workgroup_size: u32, mode: Option<DilationMode>, precision: Precision, accel: Accel) -> Result<String, String> {
+    let source = set_constant(source, "WORKGROUP_SIZE", 256, workgroup_size)?;
+    let source = set_constant(&source, "KERNEL_MODE", 2, mode.map_or(2, |mode| mode.as_uniform() as u32))?;
+    set_substep_force(&precision.kernel(&source)?, accel)
+}
+
+// Points substep_force at `accel`'s force. A constant can't pick it: every force the
+// function could call would count as used, grid bindings and all.
+pub fn set_substep_force(source: &str, accel: Accel) -> Result<String, String> {
+    let call = "return sampled_force(index, star);";
+    if !source.contains(call) {
+        return Err(format!("shader.wgsl's substep_force no longer does `{}`", call));
+    }
+    Ok(source.replace(call, &format!("return {}_force(index, star);", accel.name())))
+}
+
+fn set_constant(source: &str, name: &str, default: u32, value: u32) -> Result<String, String> {
+    let declaration = format!("const {}: u32 = {}u;", name, default);
+    if !source.contains(&declaration) {
+        return Err(format!("shader.wgsl no longer declares {}", declaration));
+    }
+    Ok(source.replace(&declaration, &format!("const {}: u32 = {}u;", name, value)))
+}
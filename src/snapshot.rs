@@ -0,0 +1,524 @@
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{collections::BTreeMap, path::Path};
+
+use image::RgbImage;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::checkpoint::{Checkpoint, CHECKPOINT_VERSION};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::dump::{read_bin_dump, read_csv_dump, read_dump_manifest, read_sidecar};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::ChronoError;
+use crate::galaxy::{is_central, type_index, Star};
+use crate::hdr::HdrImage;
+use crate::manifest::RunManifest;
+use crate::overlay::{draw_legend, draw_overlay, OverlayInfo};
+use crate::profiles::center_of_mass;
+use crate::render::{render_frame, Camera, Framebuffer, RenderSettings};
+use crate::simulation::DilationMode;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    config::SimConfig,
+    cpu_reference,
+    gif::GifWriter,
+    interrupt, logging,
+    logging::{thousands, FRAME_TARGET},
+    output::{frame_number_width, OutputLayout},
+    pipe::{finish_frames_to, open_frames_to, pipe_frame},
+    render::side_by_side,
+    saver::FrameSaver,
+    video::VideoEncoder,
+};
+
+// `chronoturin [flags] render dumps/chrono_*.bin` or `render --snapshots dumps/`: frames drawn on
+// the CPU with the camera and image flags, which may also follow `render`. Nothing is stepped,
+// so no GPU is opened.
+#[derive(clap::Args, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RenderArgs {
+    /// Dumps (.csv or .bin) or checkpoints to draw, in this order
+    #[arg(required_unless_present = "snapshots", conflicts_with = "snapshots")]
+    #[serde(default)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Replay a --dump-every directory in frame order, side by side where both modes were dumped
+    #[arg(long)]
+    pub snapshots: Option<PathBuf>,
+
+    /// Only frames in this range: 50..100 (end excluded), 50.. or ..100
+    #[arg(long, value_parser = parse_frame_range)]
+    pub frames: Option<FrameRange>,
+}
+
+// render --frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameRange {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+impl FrameRange {
+    pub fn contains(&self, frame: usize) -> bool {
+        frame >= self.start && self.end.is_none_or(|end| frame < end)
+    }
+}
+
+// As parse_frame_range reads it.
+impl std::fmt::Display for FrameRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.end {
+            Some(end) => write!(f, "{}..{}", self.start, end),
+            None => write!(f, "{}..", self.start),
+        }
+    }
+}
+
+pub fn parse_frame_range(arg: &str) -> Result<FrameRange, String> {
+    let (start, end) = arg.split_once("..").ok_or_else(|| format!("\"{}\" is not a range like 50..100", arg))?;
+    let bound = |v: &str| v.trim().parse::<usize>().map_err(|e| format!("\"{}\": {}", v.trim(), e));
+    let start = if start.is_empty() { 0 } else { bound(start)? };
+    let end = if end.is_empty() { None } else { Some(bound(end)?) };
+    if end.is_some_and(|end| end <= start) {
+        return Err(format!("{} holds no frames", arg));
+    }
+    Ok(FrameRange { start, end })
+}
+
+// `chronoturin inspect checkpoint.bin`, or `inspect run.json` for just the manifest.
+#[derive(clap::Args, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InspectArgs {
+    /// A dump (.csv or .bin), checkpoint, frame (.png) or run.json
+    pub path: PathBuf,
+}
+
+// What kind of file a snapshot came from, with what its header records.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnapshotKind {
+    CsvDump,
+    // `sidecar` is false when the `.json` next to it was missing or unreadable
+    BinDump { sidecar: bool },
+    Checkpoint { seed: u64, pass: u32, next_frame: u64 },
+}
+
+// One set of stars in a snapshot file. A checkpoint holds one per mode it was stepping.
+#[derive(Clone, Debug)]
+pub struct SnapshotRun {
+    pub mode: Option<DilationMode>,
+    pub frame: Option<usize>,
+    pub sim_time: Option<f32>,
+    pub stars: Vec<Star>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub kind: SnapshotKind,
+    pub runs: Vec<SnapshotRun>,
+    // The run that wrote the file, when it recorded one
+    pub manifest: Option<RunManifest>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Snapshot {
+    // Checkpoints are told apart by their header, binary dumps by `.bin`, anything else is
+    // read as a CSV dump. Without a sidecar, the mode and frame come from an output-layout
+    // file name like `newton_012.csv`, if it is one.
+    pub fn read(path: &Path) -> Result<Self, ChronoError> {
+        // Reading errors name the file, so a replay can say which snapshot was missing.
+        let named = |e: ChronoError| match e {
+            ChronoError::Io(e) => ChronoError::Parse { path: path.to_path_buf(), line: 0, message: e.to_string() },
+            e => e,
+        };
+        Self::read_unnamed(path).map_err(named)
+    }
+
+    fn read_unnamed(path: &Path) -> Result<Self, ChronoError> {
+        if Checkpoint::is_checkpoint(path)? {
+            let checkpoint = Checkpoint::load(path)?;
+            let runs = checkpoint
+                .runs
+                .into_iter()
+                .map(|run| SnapshotRun {
+                    mode: Some(run.mode),
+                    // The checkpoint is taken after its last completed frame.
+                    frame: checkpoint.next_frame.checked_sub(1).map(|f| f as usize),
+                    sim_time: Some(run.state.time_seed),
+                    stars: run.stars,
+                })
+                .collect();
+            let kind = SnapshotKind::Checkpoint { seed: checkpoint.seed, pass: checkpoint.pass, next_frame: checkpoint.next_frame };
+            return Ok(Self { kind, runs, manifest: checkpoint.manifest });
+        }
+        let (kind, stars, info) = if path.extension().is_some_and(|e| e == "bin") {
+            let sidecar = read_sidecar(path);
+            let stars = read_bin_dump(path)?;
+            // A truncated dump can still be a whole number of records.
+            if let Some((_, count)) = sidecar.filter(|(_, count)| *count != stars.len()) {
+                return Err(ChronoError::Parse {
+                    path: path.to_path_buf(),
+                    line: 0,
+                    message: format!("holds {} stars, but its sidecar records {}", stars.len(), count),
+                });
+            }
+            (SnapshotKind::BinDump { sidecar: sidecar.is_some() }, stars, sidecar.map(|(info, _)| info))
+        } else {
+            (SnapshotKind::CsvDump, read_csv_dump(path)?, None)
+        };
+        let run = match info {
+            Some(info) => SnapshotRun {
+                mode: [DilationMode::Newtonian, DilationMode::Chronoturin].into_iter().find(|m| m.name() == info.mode),
+                frame: Some(info.frame),
+                sim_time: Some(info.sim_time),
+                stars,
+            },
+            None => {
+                let (mode, frame) = name_hints(path);
+                SnapshotRun { mode, frame, sim_time: None, stars }
+            }
+        };
+        Ok(Self { kind, runs: vec![run], manifest: read_dump_manifest(path)? })
+    }
+
+    // What `inspect` prints.
+    pub fn describe(&self, path: &Path) -> String {
+        let header = match self.kind {
+            SnapshotKind::CsvDump => "CSV dump".to_string(),
+            SnapshotKind::BinDump { sidecar: true } => "binary dump".to_string(),
+            SnapshotKind::BinDump { sidecar: false } => "binary dump (no sidecar)".to_string(),
+            SnapshotKind::Checkpoint { seed, pass, next_frame } => format!(
+                "checkpoint v{} | seed {} | pass {} | resumes at frame {} | {} run{}",
+                CHECKPOINT_VERSION,
+                seed,
+                pass + 1,
+                next_frame,
+                self.runs.len(),
+                if self.runs.len() == 1 { "" } else { "s" }
+            ),
+        };
+        let mut text = format!("{}: {}\n", path.display(), header);
+        for run in &self.runs {
+            let known = |v: Option<String>| v.unwrap_or_else(|| "?".into());
+            text.push_str(&format!(
+                "[{}] frame {} | sim time {}\n",
+                known(run.mode.map(|m| m.name().to_string())),
+                known(run.frame.map(|f| f.to_string())),
+                known(run.sim_time.map(|t| format!("{:.3}", t)))
+            ));
+            for line in StarSummary::measure(&run.stars).lines() {
+                text.push_str(&format!("  {}\n", line));
+            }
+        }
+        if let Some(manifest) = &self.manifest {
+            text.push_str(&manifest.describe());
+        }
+        text
+    }
+}
+
+// What `inspect` prints: a snapshot's stars and manifest, or the manifest alone of a frame or
+// run.json (or a binary dump's sidecar).
+#[cfg(not(target_arch = "wasm32"))]
+pub fn inspect(path: &Path) -> Result<String, ChronoError> {
+    if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("png") || e == "json") {
+        return Ok(format!("{}: run manifest\n{}", path.display(), RunManifest::read(path)?.describe()));
+    }
+    Ok(Snapshot::read(path)?.describe(path))
+}
+
+// One image of a --snapshots replay: that frame's dumps, Newtonian's first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayFrame {
+    pub frame: usize,
+    pub files: Vec<(DilationMode, PathBuf)>,
+}
+
+// The dumps in `dir`, by their --dump-every names, in frame order and within `range`. Every
+// frame must have been dumped for the same modes, so the images all have one size.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn replay_plan(dir: &Path, range: Option<FrameRange>) -> Result<Vec<ReplayFrame>, ChronoError> {
+    let mut frames: BTreeMap<usize, Vec<(DilationMode, PathBuf)>> = BTreeMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.extension().is_some_and(|e| e == "csv" || e == "bin") {
+            continue;
+        }
+        let (Some(mode), Some(frame)) = name_hints(&path) else { continue };
+        if range.is_some_and(|r| !r.contains(frame)) {
+            continue;
+        }
+        let files = frames.entry(frame).or_default();
+        if let Some((_, other)) = files.iter().find(|(m, _)| *m == mode) {
+            return Err(ChronoError::InvalidConfig(format!(
+                "{} and {} are both {} frame {}",
+                other.display(),
+                path.display(),
+                mode.name(),
+                frame
+            )));
+        }
+        files.push((mode, path));
+        files.sort_by_key(|(m, _)| *m == DilationMode::Chronoturin);
+    }
+    let plan: Vec<ReplayFrame> = frames.into_iter().map(|(frame, files)| ReplayFrame { frame, files }).collect();
+    let Some(first) = plan.first() else {
+        return Err(ChronoError::InvalidConfig(format!(
+            "{} holds no dumps{}",
+            dir.display(),
+            if range.is_some() { " in the --frames range" } else { "" }
+        )));
+    };
+    let modes = |f: &ReplayFrame| f.files.iter().map(|(m, _)| m.name()).collect::<Vec<_>>().join(" and ");
+    if let Some(odd) = plan.iter().find(|f| modes(f) != modes(first)) {
+        return Err(ChronoError::InvalidConfig(format!(
+            "frame {} was dumped for {} but frame {} for {}",
+            first.frame,
+            modes(first),
+            odd.frame,
+            modes(odd)
+        )));
+    }
+    Ok(plan)
+}
+
+// Replays draw one galaxy throughout, so every snapshot must hold as many stars as the first.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn check_star_count(path: &Path, stars: usize, first: &mut Option<(PathBuf, usize)>) -> Result<(), ChronoError> {
+    match first {
+        Some((first_path, count)) if *count != stars => Err(ChronoError::Parse {
+            path: path.to_path_buf(),
+            line: 0,
+            message: format!("holds {} stars, but {} holds {}", stars, first_path.display(), count),
+        }),
+        Some(_) => Ok(()),
+        None => {
+            *first = Some((path.to_path_buf(), stars));
+            Ok(())
+        }
+    }
+}
+
+// One run as a frame. The status line needs the mode, frame and sim time, which binary dumps
+// and checkpoints record and CSV dumps do not.
+pub fn draw_run(run: &SnapshotRun, view: &Camera, settings: &RenderSettings, overlay: bool) -> RgbImage {
+    let mut img = render_frame(&run.stars, view, settings, run.mode == Some(DilationMode::Chronoturin));
+    if overlay {
+        if let (Some(mode), Some(frame), Some(sim_time)) = (run.mode, run.frame, run.sim_time) {
+            let active_fraction = StarSummary::measure(&run.stars).active_fraction as f32;
+            draw_overlay(&mut img, &OverlayInfo { mode, frame, sim_time, active_fraction });
+        }
+        draw_legend(&mut img, settings.color_by, settings.color_range(&run.stars));
+    }
+    img
+}
+
+// The float channels of the same frame, without overlays, for --image-format png16 and exr.
+pub fn draw_run_hdr(run: &SnapshotRun, view: &Camera, settings: &RenderSettings) -> HdrImage {
+    let mut fb = Framebuffer::new(view.width, view.height);
+    fb.accumulate(&run.stars, view, settings);
+    fb.hdr(settings, run.mode == Some(DilationMode::Chronoturin))
+}
+
+// `<mode stem>_<frame>` at the end of a file stem, as the output layout names dumps.
+#[cfg(not(target_arch = "wasm32"))]
+fn name_hints(path: &Path) -> (Option<DilationMode>, Option<usize>) {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let Some((head, frame)) = stem.rsplit_once('_') else { return (None, None) };
+    let mode = [DilationMode::Newtonian, DilationMode::Chronoturin].into_iter().find(|m| head.ends_with(m.file_stem()));
+    (mode, mode.and(frame.parse().ok()))
+}
+
+// Summary statistics of one set of stars. Central masses are counted but left out of
+// everything else; so are non-finite stars, which are counted on their own.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StarSummary {
+    pub stars: usize,
+    pub centrals: usize,
+    pub non_finite: usize,
+    // Stars per data_type, up to the highest one present
+    pub by_type: Vec<usize>,
+    // Bounding box corners; None when no star is finite
+    pub bounds: Option<([f32; 3], [f32; 3])>,
+    pub center_of_mass: [f64; 3],
+    // About the center of mass
+    pub rms_radius: f64,
+    pub mean_speed: f64,
+    pub mean_debt: f64,
+    pub active_fraction: f64,
+}
+
+impl StarSummary {
+    pub fn measure(stars: &[Star]) -> Self {
+        let finite = |s: &&Star| bytemuck::cast_ref::<Star, [f32; 10]>(s).iter().all(|v| v.is_finite());
+        let centrals = stars.iter().filter(|s| is_central(s)).count();
+        let rest: Vec<Star> = stars.iter().filter(|s| !is_central(s)).filter(finite).copied().collect();
+        let non_finite = stars.len() - centrals - rest.len();
+        let mut by_type = Vec::new();
+        for star in &rest {
+            let t = type_index(star);
+            if by_type.len() <= t {
+                by_type.resize(t + 1, 0);
+            }
+            by_type[t] += 1;
+        }
+        let bounds = rest.iter().fold(None, |b: Option<([f32; 3], [f32; 3])>, s| {
+            let p = [s.x, s.y, s.z];
+            let (lo, hi) = b.unwrap_or((p, p));
+            Some(([0, 1, 2].map(|i| lo[i].min(p[i])), [0, 1, 2].map(|i| hi[i].max(p[i]))))
+        });
+        let center = center_of_mass(&rest);
+        let n = rest.len().max(1) as f64;
+        let mean = |f: &dyn Fn(&Star) -> f64| rest.iter().map(f).sum::<f64>() / n;
+        let squared_radius = mean(&|s| {
+            let d = [s.x as f64 - center[0], s.y as f64 - center[1], s.z as f64 - center[2]];
+            d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+        });
+        Self {
+            stars: stars.len(),
+            centrals,
+            non_finite,
+            by_type,
+            bounds,
+            center_of_mass: center,
+            rms_radius: squared_radius.sqrt(),
+            mean_speed: mean(&|s| ((s.vx as f64).powi(2) + (s.vy as f64).powi(2) + (s.vz as f64).powi(2)).sqrt()),
+            mean_debt: mean(&|s| s.time_debt as f64),
+            active_fraction: mean(&|s| (s.active_flag > 0.5) as u8 as f64),
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let by_type: Vec<String> = self.by_type.iter().enumerate().map(|(t, n)| format!("{}: {}", t, n)).collect();
+        let bounds = match self.bounds {
+            Some((lo, hi)) => format!("x {:.2} to {:.2} | y {:.2} to {:.2} | z {:.2} to {:.2}", lo[0], hi[0], lo[1], hi[1], lo[2], hi[2]),
+            None => "none".to_string(),
+        };
+        let c = self.center_of_mass;
+        vec![
+            format!("Stars: {} ({} central, {} non-finite) | By type: {}", self.stars, self.centrals, self.non_finite, by_type.join(", ")),
+            format!("Bounds: {}", bounds),
+            format!("Center of mass: ({:.3}, {:.3}, {:.3}) | RMS radius: {:.3}", c[0], c[1], c[2], self.rms_radius),
+            format!(
+                "Mean speed: {:.4} | Mean time_debt: {:.4} | Active: {:.1}%",
+                self.mean_speed,
+                self.mean_debt,
+                self.active_fraction * 100.0
+            ),
+        ]
+    }
+}
+
+// --- RENDER: FRAMES FROM SNAPSHOT FILES, WITHOUT STEPPING ---
+// Every run in every input becomes one frame, or with --snapshots every dumped frame one image,
+// drawn on the CPU with the run's camera flags.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render(config: SimConfig, args: &RenderArgs) -> Result<(), ChronoError> {
+    config.validate(&cpu_reference::limits())?;
+    let plan = match &args.snapshots {
+        Some(dir) => Some(replay_plan(dir, args.frames)?),
+        None => None,
+    };
+    let layout = OutputLayout::prepare(&config)?;
+    let manifest = RunManifest::new(&config);
+    manifest.write(&layout.manifest_path())?;
+    let camera = Camera::from_config(&config);
+    let settings = RenderSettings::from_config(&config);
+    let mut saver = FrameSaver::new(config.save_threads, config.save_queue, Some(manifest));
+    let mut gif = match &config.gif {
+        Some(path) => Some(GifWriter::create(path, config.gif_scale, config.gif_delay)?),
+        None => None,
+    };
+    let panels = plan.as_ref().map_or(1, |plan| plan[0].files.len() as u32);
+    let mut video = match &config.video {
+        Some(path) => Some(VideoEncoder::spawn(&config.ffmpeg, path, camera.width * panels, camera.height, config.fps)?),
+        None => None,
+    };
+    let mut frames_to = open_frames_to(&config, camera.width * panels, camera.height)?;
+    let mut first_count = None;
+    let stem = |path: &Path| path.file_stem().map_or_else(|| "snapshot".into(), |s| s.to_string_lossy().into_owned());
+    let mut draw = |name: String, frame: usize, runs: Vec<(&Path, SnapshotRun)>| -> Result<(), ChronoError> {
+        let view = camera.orbited(config.orbit * frame as f32);
+        let mut images = Vec::with_capacity(runs.len());
+        for (path, run) in &runs {
+            check_star_count(path, run.stars.len(), &mut first_count)?;
+            images.push(draw_run(run, &view, &settings, !config.no_overlay));
+            // Float frames are one file per input, named after it when there are two.
+            if config.image_format.is_hdr() {
+                let name = if runs.len() == 1 { name.clone() } else { stem(path) };
+                saver.save_hdr(layout.render_path(&name), draw_run_hdr(run, &view, &settings), config.image_format);
+            }
+        }
+        let img = if images.len() == 2 { side_by_side(&images[0], &images[1]) } else { images.swap_remove(0) };
+        pipe_frame(&mut frames_to, &img, config.on_pipe_close)?;
+        if let Some(gif) = &mut gif {
+            gif.add_frame(&img)?;
+        }
+        match &mut video {
+            Some(video) => video.write_frame(&img)?,
+            None if config.no_png || config.image_format.is_hdr() => {}
+            None => saver.save(layout.render_path(&name), img),
+        }
+        tracing::info!(target: FRAME_TARGET, "Rendered frame {:03} from {} ({} stars)",
+            frame, runs.iter().map(|(p, _)| p.display().to_string()).collect::<Vec<_>>().join(" and "),
+            thousands(runs[0].1.stars.len() as u64));
+        Ok(())
+    };
+    match &plan {
+        Some(plan) => {
+            let bar = logging::progress_bar(plan.len() as u64, "[RENDER]".to_string());
+            let width = frame_number_width(plan.last().map_or(0, |f| f.frame + 1));
+            for step in plan {
+                if interrupt::requested() {
+                    break;
+                }
+                let mut runs = Vec::with_capacity(step.files.len());
+                for (_, path) in &step.files {
+                    runs.push((path.as_path(), Snapshot::read(path)?.runs.swap_remove(0)));
+                }
+                let name = match &runs[..] {
+                    [(path, _)] => stem(path),
+                    _ => format!("compare_{:0w$}", step.frame, w = width),
+                };
+                draw(name, step.frame, runs)?;
+                bar.inc(1);
+            }
+            bar.finish();
+        }
+        None => {
+            let bar = logging::progress_bar(args.inputs.len() as u64, "[RENDER]".to_string());
+            for (i, path) in args.inputs.iter().enumerate() {
+                if interrupt::requested() {
+                    break;
+                }
+                let snapshot = Snapshot::read(path)?;
+                let several = snapshot.runs.len() > 1;
+                for run in snapshot.runs {
+                    // Without a recorded frame, inputs count (and orbit the camera) in the order given.
+                    let frame = run.frame.unwrap_or(i);
+                    if args.frames.is_some_and(|r| !r.contains(frame)) {
+                        continue;
+                    }
+                    // A checkpoint holds both modes of a side-by-side run; each gets its own file.
+                    let name = match run.mode.filter(|_| several) {
+                        Some(mode) => format!("{}_{}", stem(path), mode.file_stem()),
+                        None => stem(path),
+                    };
+                    draw(name, frame, vec![(path.as_path(), run)])?;
+                }
+                bar.inc(1);
+            }
+            bar.finish();
+        }
+    }
+    if let Some(gif) = gif {
+        let frames = gif.frames();
+        tracing::info!("Wrote {} ({} frames)", gif.finish().display(), frames);
+    }
+    if let Some(video) = video {
+        tracing::info!("Wrote {}", video.finish()?.display());
+    }
+    finish_frames_to(frames_to)?;
+    let stats = saver.finish();
+    tracing::info!("Saved {} frames ({} failed)", stats.frames_saved, stats.failures);
+    Ok(())
+}
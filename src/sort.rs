@@ -0,0 +1,347 @@
+use std::num::NonZeroU64;
+use std::ops::Range;
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use crate::galaxy::{bounding_cube, is_central, is_dead, Star};
+use crate::precision::Precision;
+
+// Must match `WORKGROUP_SIZE` in sort.wgsl
+const SORT_WORKGROUP_SIZE: u32 = 256;
+// One (key, index) pair of u32s per padded star
+const PAIR_BYTES: u64 = 8;
+// Cells per axis; must match sort.wgsl
+const CELLS: f32 = 1024.0;
+// A culled star's key, behind every Morton code; must match sort.wgsl
+const DEAD_KEY: u32 = 0xffff_fffe;
+
+// Matches `SortParams` in sort.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct SortParams {
+    cube: [f32; 4],
+    total: u32,
+    padded: u32,
+    stride: u32,
+    j: u32,
+    k: u32,
+    offset: u32,
+    count: u32,
+    culled_only: u32,
+}
+
+// Bytes of the (key, index) buffer for `stars`, which has to fit one storage binding.
+pub fn key_bytes(stars: u32) -> u64 {
+    (stars.max(1) as u64).next_power_of_two() * PAIR_BYTES
+}
+
+// Each of the star id buffers, one u32 per star.
+pub fn id_bytes(stars: u32) -> u64 {
+    stars.max(1) as u64 * std::mem::size_of::<u32>() as u64
+}
+
+// The cube Morton codes are quantized in: the galaxy's bounding box, made cubic and
+// doubled around its center so stars can drift outward for a while before they are
+// clamped to its faces. Stars outside still sort, just less tightly.
+pub fn quantization_cube(stars: &[Star]) -> [f32; 4] {
+    bounding_cube(stars, 2.0)
+}
+
+// What sort.wgsl's keys pass computes for `star`: 0 for a central mass, DEAD_KEY for a
+// culled one, otherwise its 30-bit Morton code in `cube`, shifted up one and tagged with the
+// low bit.
+pub fn morton_key(star: &Star, cube: [f32; 4]) -> u32 {
+    if is_dead(star) {
+        return DEAD_KEY;
+    }
+    if is_central(star) {
+        return 0;
+    }
+    let cell = |p: f32, lo: f32| {
+        let t = (p - lo) / cube[3] * CELLS;
+        if t < 0.0 {
+            0
+        } else if t < CELLS - 1.0 {
+            t as u32
+        } else {
+            CELLS as u32 - 1
+        }
+    };
+    let spread = |v: u32| {
+        let mut x = v & 0x3ff;
+        x = (x | (x << 16)) & 0x030000ff;
+        x = (x | (x << 8)) & 0x0300f00f;
+        x = (x | (x << 4)) & 0x030c30c3;
+        (x | (x << 2)) & 0x09249249
+    };
+    let morton = spread(cell(star.x, cube[0])) | (spread(cell(star.y, cube[1])) << 1) | (spread(cell(star.z, cube[2])) << 2);
+    (morton << 1) | 1
+}
+
+// Compiled once and shared by forked and resized simulations.
+pub struct SortPipelines {
+    keys: wgpu::ComputePipeline,
+    bitonic: wgpu::ComputePipeline,
+    gather: wgpu::ComputePipeline,
+    gather_ids: wgpu::ComputePipeline,
+    precision: Precision,
+}
+
+impl SortPipelines {
+    pub fn new(device: &wgpu::Device, precision: Precision) -> Self {
+        let source = precision.star_struct(include_str!("sort.wgsl")).unwrap_or_else(|e| panic!("sort.wgsl: {}", e));
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("sort.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: None,
+                module: &shader,
+                entry_point,
+            })
+        };
+        Self {
+            keys: pipeline("keys"),
+            bitonic: pipeline("bitonic"),
+            gather: pipeline("gather"),
+            gather_ids: pipeline("gather_ids"),
+            precision,
+        }
+    }
+}
+
+// A bind group and the workgroup grid it is dispatched with.
+type Pass = (wgpu::BindGroup, [u32; 2]);
+
+// Reorders one simulation's stars along a Morton curve on the GPU: every star gets a key
+// from its quantized position, the (key, index) pairs are bitonic-sorted, and each chunk
+// is refilled from a scratch copy in key order. ids() follows the stars, so readbacks can
+// be put back in creation order.
+pub struct MortonSort {
+    pipelines: Arc<SortPipelines>,
+    ids: wgpu::Buffer,
+    ids_scratch: wgpu::Buffer,
+    // One per star chunk, the same size
+    scratch: Vec<wgpu::Buffer>,
+    keys: Pass,
+    // The keys of encode_partition
+    partition: Pass,
+    // log2(padded) * (log2(padded) + 1) / 2 compare-and-swap steps
+    passes: Vec<Pass>,
+    // One per chunk
+    gathers: Vec<Pass>,
+    gather_ids: Pass,
+    total: u32,
+}
+
+impl MortonSort {
+    // `chunks` are the star buffers with their global index ranges, every chunk but the
+    // last holding `stride` stars.
+    pub fn new(
+        device: &wgpu::Device,
+        pipelines: Arc<SortPipelines>,
+        chunks: &[(&wgpu::Buffer, Range<usize>)],
+        stride: u32,
+        cube: [f32; 4],
+    ) -> Self {
+        let total = chunks.last().map_or(0, |(_, range)| range.end as u32);
+        let padded = (total.max(1)).next_power_of_two();
+        let max_groups = device.limits().max_compute_workgroups_per_dimension;
+        let grid = |threads: u32| {
+            let groups = threads.div_ceil(SORT_WORKGROUP_SIZE);
+            let x = groups.clamp(1, max_groups);
+            [x, groups.div_ceil(x).max(1)]
+        };
+
+        // Every pass reads its own entry of one uniform buffer.
+        let base = SortParams { cube, total, padded, stride, ..Default::default() };
+        let mut entries = vec![base];
+        let mut k = 2;
+        while k <= padded {
+            let mut j = k / 2;
+            while j > 0 {
+                entries.push(SortParams { j, k, ..base });
+                j /= 2;
+            }
+            k *= 2;
+        }
+        let gather_start = entries.len();
+        entries.extend(chunks.iter().map(|(_, range)| SortParams { offset: range.start as u32, count: range.len() as u32, ..base }));
+        let partition_entry = entries.len();
+        entries.push(SortParams { culled_only: 1, ..base });
+        let align = std::mem::size_of::<SortParams>().next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as usize);
+        let mut bytes = vec![0u8; entries.len() * align];
+        for (i, entry) in entries.iter().enumerate() {
+            bytes[i * align..][..std::mem::size_of::<SortParams>()].copy_from_slice(bytemuck::bytes_of(entry));
+        }
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sort Params"),
+            contents: &bytes,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let entry = |i: usize| {
+            wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &params,
+                offset: (i * align) as u64,
+                size: NonZeroU64::new(std::mem::size_of::<SortParams>() as u64),
+            })
+        };
+
+        let pairs = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sort Keys"),
+            size: padded as u64 * PAIR_BYTES,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let ids = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Star Ids"),
+            contents: bytemuck::cast_slice(&identity(total)),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+        let ids_scratch = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Star Ids Scratch"),
+            size: ids.size(),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let scratch: Vec<wgpu::Buffer> = chunks
+            .iter()
+            .enumerate()
+            .map(|(k, (buffer, _))| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("Sort Scratch {}", k)),
+                    size: buffer.size(),
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+        let placeholder = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sort Placeholder"),
+            size: pipelines.precision.star_bytes(),
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = |pipeline: &wgpu::ComputePipeline, entries: Vec<wgpu::BindGroupEntry<'_>>| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &entries,
+            })
+        };
+        let live: Vec<&wgpu::Buffer> = chunks.iter().map(|(buffer, _)| *buffer).collect();
+        let copies: Vec<&wgpu::Buffer> = scratch.iter().collect();
+
+        let keys_pass = |params: usize| {
+            let mut entries = star_entries(&live, &placeholder);
+            entries.push(wgpu::BindGroupEntry { binding: 1, resource: entry(params) });
+            entries.push(wgpu::BindGroupEntry { binding: 2, resource: pairs.as_entire_binding() });
+            (bind_group(&pipelines.keys, entries), grid(padded))
+        };
+        let keys = keys_pass(0);
+        let partition = keys_pass(partition_entry);
+        let passes = (1..gather_start)
+            .map(|i| {
+                let entries = vec![
+                    wgpu::BindGroupEntry { binding: 1, resource: entry(i) },
+                    wgpu::BindGroupEntry { binding: 2, resource: pairs.as_entire_binding() },
+                ];
+                (bind_group(&pipelines.bitonic, entries), grid(padded))
+            })
+            .collect();
+        let gathers = chunks
+            .iter()
+            .enumerate()
+            .map(|(c, (buffer, range))| {
+                let mut entries = star_entries(&copies, &placeholder);
+                entries.push(wgpu::BindGroupEntry { binding: 1, resource: entry(gather_start + c) });
+                entries.push(wgpu::BindGroupEntry { binding: 2, resource: pairs.as_entire_binding() });
+                entries.push(wgpu::BindGroupEntry { binding: 6, resource: buffer.as_entire_binding() });
+                (bind_group(&pipelines.gather, entries), grid(range.len() as u32))
+            })
+            .collect();
+        let gather_ids = (
+            bind_group(
+                &pipelines.gather_ids,
+                vec![
+                    wgpu::BindGroupEntry { binding: 1, resource: entry(0) },
+                    wgpu::BindGroupEntry { binding: 2, resource: pairs.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 7, resource: ids_scratch.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 8, resource: ids.as_entire_binding() },
+                ],
+            ),
+            grid(total),
+        );
+        Self { pipelines, ids, ids_scratch, scratch, keys, partition, passes, gathers, gather_ids, total }
+    }
+
+    pub fn pipelines(&self) -> &Arc<SortPipelines> {
+        &self.pipelines
+    }
+
+    // ids()[slot] is the creation index of the star now stored at global index `slot`.
+    pub fn ids(&self) -> &wgpu::Buffer {
+        &self.ids
+    }
+
+    // For stars just uploaded in creation order.
+    pub fn reset_ids(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.ids, 0, bytemuck::cast_slice(&identity(self.total)));
+    }
+
+    // Records the sort. `chunks` are the star buffers it was built for, in order.
+    pub fn encode<'a>(&self, encoder: &mut wgpu::CommandEncoder, chunks: impl Iterator<Item = &'a wgpu::Buffer>) {
+        self.encode_keyed(encoder, chunks, &self.keys);
+    }
+
+    // Records a sort that only moves culled stars behind the rest, every other star keeping
+    // its place in the order (the index breaks the ties).
+    pub fn encode_partition<'a>(&self, encoder: &mut wgpu::CommandEncoder, chunks: impl Iterator<Item = &'a wgpu::Buffer>) {
+        self.encode_keyed(encoder, chunks, &self.partition);
+    }
+
+    fn encode_keyed<'a>(&self, encoder: &mut wgpu::CommandEncoder, chunks: impl Iterator<Item = &'a wgpu::Buffer>, keys: &Pass) {
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Sort Keys"), timestamp_writes: None });
+            cpass.set_pipeline(&self.pipelines.keys);
+            dispatch(&mut cpass, keys);
+            cpass.set_pipeline(&self.pipelines.bitonic);
+            for pass in &self.passes {
+                dispatch(&mut cpass, pass);
+            }
+        }
+        for (chunk, scratch) in chunks.zip(&self.scratch) {
+            encoder.copy_buffer_to_buffer(chunk, 0, scratch, 0, chunk.size());
+        }
+        encoder.copy_buffer_to_buffer(&self.ids, 0, &self.ids_scratch, 0, self.ids.size());
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Sort Gather"), timestamp_writes: None });
+        cpass.set_pipeline(&self.pipelines.gather);
+        for pass in &self.gathers {
+            dispatch(&mut cpass, pass);
+        }
+        cpass.set_pipeline(&self.pipelines.gather_ids);
+        dispatch(&mut cpass, &self.gather_ids);
+    }
+}
+
+fn dispatch<'a>(cpass: &mut wgpu::ComputePass<'a>, (bind_group, [x, y]): &'a Pass) {
+    cpass.set_bind_group(0, bind_group, &[]);
+    cpass.dispatch_workgroups(*x, *y, 1);
+}
+
+// Bindings 0, 3, 4 and 5 are the four star chunks, as in shader.wgsl.
+fn star_entries<'a>(buffers: &[&'a wgpu::Buffer], placeholder: &'a wgpu::Buffer) -> Vec<wgpu::BindGroupEntry<'a>> {
+    [0, 3, 4, 5]
+        .into_iter()
+        .enumerate()
+        .map(|(k, binding)| wgpu::BindGroupEntry { binding, resource: buffers.get(k).copied().unwrap_or(placeholder).as_entire_binding() })
+        .collect()
+}
+
+fn identity(total: u32) -> Vec<u32> {
+    (0..total.max(1)).collect()
+}
@@ -0,0 +1,142 @@
+struct Star {
+    x: f32, y: f32, z: f32,
+    vx: f32, vy: f32, vz: f32,
+    mass: f32,
+    data_type: f32,
+    time_debt: f32,
+    active_flag: f32,
+};
+
+// One entry of sort.rs's params buffer; each pass binds its own.
+struct SortParams {
+    cube: vec4<f32>, // xyz: lowest corner of the quantization cube, w: its side
+    total: u32, // stars across all chunks
+    padded: u32, // total rounded up to a power of two
+    stride: u32, // stars per chunk, as in shader.wgsl
+    j: u32, // bitonic: distance between compared keys
+    k: u32, // bitonic: length of the sequences being merged
+    offset: u32, // gather: global index of the destination chunk's first star
+    count: u32, // gather: stars in the destination chunk
+    culled_only: u32, // keys: 1 to key on culled or not only, keeping the order otherwise
+};
+
+// The live star chunks for `keys`, their scratch copies for `gather`.
+@group(0) @binding(0) var<storage, read> stars0: array<Star>;
+@group(0) @binding(1) var<uniform> params: SortParams;
+// (key, current index) per star, padded with u32::MAX keys
+@group(0) @binding(2) var<storage, read_write> pairs: array<vec2<u32>>;
+@group(0) @binding(3) var<storage, read> stars1: array<Star>;
+@group(0) @binding(4) var<storage, read> stars2: array<Star>;
+@group(0) @binding(5) var<storage, read> stars3: array<Star>;
+@group(0) @binding(6) var<storage, read_write> dest: array<Star>;
+// Creation index of the star in each slot, before and after the sort
+@group(0) @binding(7) var<storage, read> ids_src: array<u32>;
+@group(0) @binding(8) var<storage, read_write> ids: array<u32>;
+
+const WORKGROUP_SIZE: u32 = 256u;
+const CELLS: f32 = 1024.0;
+// Must match sort.rs; the padding keys are 0xffffffff
+const DEAD_KEY: u32 = 0xfffffffeu;
+
+fn load_star(index: u32) -> Star {
+    let c = index / params.stride;
+    let i = index - c * params.stride;
+    switch c {
+        case 0u: { return stars0[i]; }
+        case 1u: { return stars1[i]; }
+        case 2u: { return stars2[i]; }
+        default: { return stars3[i]; }
+    }
+}
+
+// Dispatches are folded into a second dimension past max_compute_workgroups_per_dimension.
+fn flat_index(id: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return id.x + id.y * groups.x * WORKGROUP_SIZE;
+}
+
+// Ten-bit cell of one coordinate. Stars that left the cube are clamped to its faces;
+// NaN fails both comparisons and lands in the last cell.
+fn cell(p: f32, lo: f32) -> u32 {
+    let t = (p - lo) / params.cube.w * CELLS;
+    if (t < 0.0) {
+        return 0u;
+    }
+    if (t < CELLS - 1.0) {
+        return u32(t);
+    }
+    return u32(CELLS) - 1u;
+}
+
+// Spreads ten bits to every third bit.
+fn spread(v: u32) -> u32 {
+    var x = v & 0x3ffu;
+    x = (x | (x << 16u)) & 0x030000ffu;
+    x = (x | (x << 8u)) & 0x0300f00fu;
+    x = (x | (x << 4u)) & 0x030c30c3u;
+    x = (x | (x << 2u)) & 0x09249249u;
+    return x;
+}
+
+// 30-bit Morton code shifted up one, so key 0 is free for central masses, which shader.wgsl
+// expects at index 0. Culled stars (--kill-radius) sort behind it all, ahead of the padding.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn keys(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let index = flat_index(global_id, groups);
+    if (index >= params.padded) {
+        return;
+    }
+    if (index >= params.total) {
+        pairs[index] = vec2<u32>(0xffffffffu, index);
+        return;
+    }
+    let s = load_star(index);
+    let dead = s.active_flag < -0.5;
+    if (params.culled_only == 1u) {
+        pairs[index] = vec2<u32>(select(0u, 1u, dead), index);
+        return;
+    }
+    if (dead) {
+        pairs[index] = vec2<u32>(DEAD_KEY, index);
+        return;
+    }
+    let morton = spread(cell(s.x, params.cube.x)) | (spread(cell(s.y, params.cube.y)) << 1u) | (spread(cell(s.z, params.cube.z)) << 2u);
+    pairs[index] = vec2<u32>(select((morton << 1u) | 1u, 0u, s.data_type < -0.5), index);
+}
+
+// One compare-and-swap step of a bitonic sort over (key, index); the index breaks ties,
+// so the order is the same on every run.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn bitonic(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let i = flat_index(global_id, groups);
+    let l = i ^ params.j;
+    if (i >= params.padded || l <= i) {
+        return;
+    }
+    let a = pairs[i];
+    let b = pairs[l];
+    let greater = a.x > b.x || (a.x == b.x && a.y > b.y);
+    let ascending = (i & params.k) == 0u;
+    if (greater == ascending) {
+        pairs[i] = b;
+        pairs[l] = a;
+    }
+}
+
+// Fills one chunk with the stars the sorted keys name, read from the scratch copies.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn gather(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let i = flat_index(global_id, groups);
+    if (i >= params.count) {
+        return;
+    }
+    dest[i] = load_star(pairs[params.offset + i].y);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn gather_ids(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {
+    let i = flat_index(global_id, groups);
+    if (i >= params.total) {
+        return;
+    }
+    ids[i] = ids_src[pairs[i].y];
+}
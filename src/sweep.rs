@@ -0,0 +1,331 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{sync::Arc, time::Instant};
+
+use crate::config::SimConfig;
+use crate::diff::DisplacementStats;
+#[cfg(all(feature = "serve", not(target_arch = "wasm32")))]
+use crate::serve;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    bindings::UniformPath,
+    diff::displacement_stats,
+    error::ChronoError,
+    galaxy::active_by_type,
+    gpu::DeviceErrors,
+    interrupt, logging,
+    logging::FRAME_TARGET,
+    manifest::RunManifest,
+    metrics::{MetricsRecord, MetricsWriter},
+    output::OutputLayout,
+    overlay::{draw_legend, draw_overlay, OverlayInfo},
+    presets,
+    profiles::{LagrangianRadii, ProfileSettings, Profiles},
+    render::{render_frame, side_by_side, Camera, RenderSettings},
+    runner::active_label,
+    saver::FrameSaver,
+    simulation::{DilationMode, Simulation},
+    telemetry::Telemetry,
+};
+
+// `chronoturin [flags] sweep --stars 10000,100000 --dilation-threshold 0.1,0.5`.
+// Flags before `sweep` apply to every configuration; an omitted list falls back to them.
+#[derive(clap::Args, Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", default, deny_unknown_fields)]
+pub struct SweepArgs {
+    /// Comma-separated star counts to sweep
+    #[arg(long, value_delimiter = ',')]
+    pub stars: Vec<u32>,
+
+    /// Comma-separated Chronoturin dilation thresholds to sweep
+    #[arg(long, value_delimiter = ',')]
+    pub dilation_threshold: Vec<f32>,
+
+    /// Comma-separated time-debt caps to sweep
+    #[arg(long, value_delimiter = ',')]
+    pub max_debt: Vec<f32>,
+
+    /// Save the last side-by-side frame of each configuration (no images otherwise)
+    #[arg(long)]
+    pub sweep_save_last_frame: bool,
+}
+
+// One configuration of the grid.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SweepPoint {
+    pub stars: u32,
+    pub dilation_threshold: f32,
+    pub max_debt: f32,
+}
+
+impl SweepPoint {
+    pub fn apply(&self, base: &SimConfig) -> SimConfig {
+        SimConfig { stars: self.stars, dilation_threshold: self.dilation_threshold, max_debt: self.max_debt, ..base.clone() }
+    }
+
+    // File-name friendly, e.g. `s10000_t0.5_d50`.
+    pub fn label(&self) -> String {
+        format!("s{}_t{}_d{}", self.stars, self.dilation_threshold, self.max_debt)
+    }
+}
+
+// Cartesian product with stars outermost, so buffers are only reallocated when the count changes.
+pub fn grid(args: &SweepArgs, base: &SimConfig) -> Vec<SweepPoint> {
+    let or_base = |values: &[f32], base: f32| if values.is_empty() { vec![base] } else { values.to_vec() };
+    let stars = if args.stars.is_empty() { vec![base.stars] } else { args.stars.clone() };
+    let thresholds = or_base(&args.dilation_threshold, base.dilation_threshold);
+    let debts = or_base(&args.max_debt, base.max_debt);
+    let mut points = Vec::with_capacity(stars.len() * thresholds.len() * debts.len());
+    for &stars in &stars {
+        for &dilation_threshold in &thresholds {
+            for &max_debt in &debts {
+                points.push(SweepPoint { stars, dilation_threshold, max_debt });
+            }
+        }
+    }
+    points
+}
+
+// Per-configuration line of the end-of-sweep table.
+#[derive(Clone, Debug)]
+pub struct SweepResult {
+    pub point: SweepPoint,
+    pub frames: usize,
+    // Mean step + readback wall time per frame
+    pub newton_ms: f32,
+    pub chrono_ms: f32,
+    pub active_fraction: f32,
+    // Chronoturin's displacement from Newtonian at the last frame
+    pub error: DisplacementStats,
+}
+
+impl SweepResult {
+    pub fn speedup(&self) -> f32 {
+        if self.chrono_ms > 0.0 {
+            self.newton_ms / self.chrono_ms
+        } else {
+            0.0
+        }
+    }
+}
+
+pub fn summary_table(results: &[SweepResult]) -> String {
+    let mut table = String::from(
+        "| Stars | Threshold | Max debt | Frames | Newtonian ms | Chronoturin ms | Speedup | Active % | Error mean | Error p99 |\n\
+         |-------|-----------|----------|--------|--------------|----------------|---------|----------|------------|-----------|\n",
+    );
+    for r in results {
+        table.push_str(&format!(
+            "| {} | {} | {} | {} | {:.2} | {:.2} | {:.1}× | {:.1}% | {:.4} | {:.4} |\n",
+            r.point.stars,
+            r.point.dilation_threshold,
+            r.point.max_debt,
+            r.frames,
+            r.newton_ms,
+            r.chrono_ms,
+            r.speedup(),
+            r.active_fraction * 100.0,
+            r.error.mean,
+            r.error.p99
+        ));
+    }
+    table
+}
+
+// --- SWEEP: EVERY CONFIGURATION OF THE GRID ON ONE DEVICE ---
+// Both modes step each configuration side by side; configurations with the same star count
+// reuse the same buffers, and every new count reuses the compiled pipelines.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run(
+    mut config: SimConfig,
+    args: &SweepArgs,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    errors: DeviceErrors,
+) -> Result<(), ChronoError> {
+    let points = grid(args, &config);
+    for point in &points {
+        point.apply(&config).validate(&device.limits())?;
+    }
+    let seed = config.seed.unwrap_or_else(rand::random);
+    config.seed = Some(seed);
+    let uniform_path = UniformPath::select(&device, config.uniform_path)?;
+    tracing::info!("GalaxyState by {}", uniform_path.describe());
+    let layout = OutputLayout::prepare(&config)?;
+    let manifest = RunManifest { uniform_path: Some(uniform_path), ..RunManifest::new(&config) };
+    manifest.write(&layout.manifest_path())?;
+    let metrics_path = config.metrics_out.clone().unwrap_or_else(|| layout.sweep_metrics_path());
+    let mut metrics = MetricsWriter::create(&metrics_path, config.metrics_format)?;
+    tracing::info!(
+        "Sweep: {} configurations x {} frames | Steps/Frame: {} | Preset: {:?} | Seed: {} | Metrics: {}",
+        points.len(),
+        config.frames,
+        config.steps_per_frame,
+        config.preset,
+        seed,
+        metrics_path.display()
+    );
+
+    let camera = Camera::from_config(&config);
+    let render_settings = RenderSettings::from_config(&config);
+    // Every point bins alike, so their profiles compare shell for shell.
+    let profile_settings = ProfileSettings::from_config(&config);
+    let mut lagrangian = LagrangianRadii::default();
+    let interactions = config.interactions()?.values();
+    let mut saver = FrameSaver::new(config.save_threads, config.save_queue, Some(manifest));
+    let telemetry = Telemetry::new();
+    let run_id = config.run_id.clone().unwrap_or_else(|| seed.to_string());
+    telemetry.count_images(&run_id, saver.written());
+    #[cfg(feature = "serve")]
+    let server = serve::start(&config, &telemetry)?;
+    let mut sims: Option<(Simulation, Simulation)> = None;
+    // Handed to the first point's simulations, so the last of them drops the device.
+    let mut gpu = Some((device, queue));
+    let mut results = Vec::new();
+
+    'points: for (i, point) in points.iter().enumerate() {
+        let point_config = point.apply(&config);
+        let initial_data = presets::from_config(&point_config, seed);
+        // The galaxy only depends on the star count, so the same count keeps the same buffers.
+        let (mut newton, mut chrono) = match sims.take() {
+            Some((newton, chrono)) if newton.initial_stars().len() == initial_data.len() => (newton, chrono),
+            Some((previous, _)) => {
+                let newton = previous.resized(&point_config, initial_data)?;
+                let chrono = newton.fork();
+                (newton, chrono)
+            }
+            None => {
+                let (device, queue) = gpu.take().expect("only the first point builds from the device");
+                let mut newton = Simulation::with_stars(device, queue, &point_config, initial_data)?;
+                newton.watch_device(errors.clone());
+                let chrono = newton.fork();
+                (newton, chrono)
+            }
+        };
+        for (mode, sim) in [(DilationMode::Newtonian, &mut newton), (DilationMode::Chronoturin, &mut chrono)] {
+            sim.set_dilation(point.dilation_threshold, point.max_debt);
+            sim.reset();
+            sim.set_mode(mode);
+        }
+
+        let (mut newton_ms, mut chrono_ms, mut active, mut last_error) = (0.0, 0.0, 0.0, None);
+        let bar = logging::progress_bar(config.frames as u64, format!("[{}/{}] {}", i + 1, points.len(), point.label()));
+        // Each point is a run of its own on /metrics.
+        let point_run = format!("{}/{}", run_id, point.label());
+        for frame in 0..config.frames {
+            if interrupt::requested() {
+                bar.abandon();
+                tracing::warn!("Interrupted: dropping the unfinished configuration {}", point.label());
+                break 'points;
+            }
+            let mut frames = Vec::with_capacity(2);
+            for (mode, sim) in [(DilationMode::Newtonian, &mut newton), (DilationMode::Chronoturin, &mut chrono)] {
+                let start = Instant::now();
+                let ticket = sim.submit_frame(config.steps_per_frame);
+                let data = sim.finish_frame(ticket);
+                frames.push((mode, data, start.elapsed().as_secs_f32() * 1000.0));
+            }
+            let error = displacement_stats(&frames[0].1.stars, &frames[1].1.stars);
+            for (mode, data, wall_ms) in &frames {
+                let error = Some(error).filter(|_| *mode == DilationMode::Chronoturin);
+                let living = data.living();
+                let profiles = Profiles::measure(&living, &profile_settings, !data.packed);
+                telemetry.record_frame(&point_run, mode.name(), frame, *wall_ms, data.gpu_ms, data.active_fraction());
+                let record = MetricsRecord {
+                    mode: *mode,
+                    frame,
+                    wall_ms: *wall_ms,
+                    gpu_ms: data.gpu_ms,
+                    active_count: data.active_per_step().round() as u32,
+                    stars: data.stars.len() as u32,
+                    sim_time: data.sim_time,
+                    error_mean: error.map(|e| e.mean),
+                    error_p99: error.map(|e| e.p99),
+                    conservation: None,
+                    dilation_threshold: point.dilation_threshold,
+                    max_debt: point.max_debt,
+                    readback_ms: data.readback_ms,
+                    readback_bytes: data.readback_bytes,
+                    force_threads: data.force_threads,
+                    adapter: None,
+                    active_by_type: active_by_type(&living, config.types),
+                    radial_profile: profiles.radial.with_overflow(),
+                    debt_histogram: profiles.debt.as_ref().map(|h| h.with_overflow()).unwrap_or_default(),
+                    interactions: interactions.clone(),
+                    alive: data.alive,
+                    mergers: data.mergers,
+                    substeps: data.substeps,
+                    placeholder: false,
+                    lagrangian_radii: lagrangian.measure(&living),
+                };
+                metrics.write(&record)?;
+                #[cfg(feature = "serve")]
+                if let Some(server) = &server {
+                    server.publish_metrics(record.json());
+                }
+            }
+            newton_ms += frames[0].2;
+            chrono_ms += frames[1].2;
+            active += frames[1].1.active_fraction();
+            let status = format!(
+                "Newtonian {:.1} ms | Chronoturin {:.1} ms | {} | Error: mean {:.3}",
+                frames[0].2,
+                frames[1].2,
+                active_label(&frames[1].1),
+                error.mean
+            );
+            tracing::info!(target: FRAME_TARGET, "[{}/{}] {} | Frame {:03} | {}", i + 1, points.len(), point.label(), frame, status);
+            bar.set_message(status);
+            bar.inc(1);
+
+            let save = args.sweep_save_last_frame && frame + 1 == config.frames;
+            #[cfg(feature = "serve")]
+            let watched = server.as_ref().filter(|s| s.streaming());
+            #[cfg(not(feature = "serve"))]
+            let watched: Option<()> = None;
+            if save || watched.is_some() {
+                let view = camera.orbited(config.orbit * frame as f32);
+                let [left, right] = [0, 1].map(|k| {
+                    let (mode, data, _) = &frames[k];
+                    let mut img = render_frame(&data.stars, &view, &render_settings, *mode == DilationMode::Chronoturin);
+                    if !config.no_overlay {
+                        let info = OverlayInfo { mode: *mode, frame, sim_time: data.sim_time, active_fraction: data.active_fraction() };
+                        draw_overlay(&mut img, &info);
+                        draw_legend(&mut img, render_settings.color_by, render_settings.color_range(&data.stars));
+                    }
+                    img
+                });
+                let img = side_by_side(&left, &right);
+                #[cfg(feature = "serve")]
+                if let Some(server) = watched {
+                    let sink = server.frame_sink();
+                    saver.encode_jpeg(img.clone(), serve::STREAM_QUALITY, move |jpeg| sink.publish(jpeg));
+                }
+                if save {
+                    saver.save(layout.sweep_frame_path(&point.label()), img);
+                }
+            }
+            last_error = Some(error);
+        }
+        bar.finish();
+        let frames = config.frames as f32;
+        results.push(SweepResult {
+            point: *point,
+            frames: config.frames,
+            newton_ms: newton_ms / frames,
+            chrono_ms: chrono_ms / frames,
+            active_fraction: active / frames,
+            error: last_error.expect("validate() requires at least one frame"),
+        });
+        sims = Some((newton, chrono));
+    }
+
+    let saved = saver.finish();
+    if args.sweep_save_last_frame {
+        tracing::info!("Saved {} frames ({} failed)", saved.frames_saved, saved.failures);
+    }
+    for line in summary_table(&results).lines() {
+        tracing::info!("{}", line);
+    }
+    tracing::info!("{} of {} configurations completed | Metrics: {}", results.len(), points.len(), metrics_path.display());
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// One mode of one run, as of its latest frame.
+#[derive(Clone, Copy, Debug, Default)]
+struct ModeSeries {
+    frame: usize,
+    fps: f64,
+    gpu_ms: Option<f32>,
+    active_fraction: f32,
+    frames_total: u64,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    modes: BTreeMap<(String, String), ModeSeries>,
+    // Each run's FrameSaver::written
+    images: BTreeMap<String, Arc<AtomicU64>>,
+}
+
+// Live run metrics for --serve's /metrics, in the Prometheus text format. Clones share one
+// registry, and the lock is only held to copy numbers in or out, so a scrape is answered
+// while the run waits on the device. Embedders record into it the same way.
+#[derive(Clone, Debug, Default)]
+pub struct Telemetry(Arc<Mutex<Registry>>);
+
+impl Telemetry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A completed frame of `mode` in `run`; `wall_ms` is what it took, for the frame rate.
+    pub fn record_frame(&self, run: &str, mode: &str, frame: usize, wall_ms: f32, gpu_ms: Option<f32>, active_fraction: f32) {
+        let mut registry = self.0.lock().unwrap();
+        let series = registry.modes.entry((run.to_string(), mode.to_string())).or_default();
+        *series = ModeSeries {
+            frame,
+            fps: if wall_ms > 0.0 { 1000.0 / wall_ms as f64 } else { 0.0 },
+            gpu_ms,
+            active_fraction,
+            frames_total: series.frames_total + 1,
+        };
+    }
+
+    // Reports `written` (FrameSaver::written) as the run's images written.
+    pub fn count_images(&self, run: &str, written: Arc<AtomicU64>) {
+        self.0.lock().unwrap().images.insert(run.to_string(), written);
+    }
+
+    // Every series, `# HELP` and `# TYPE` first, labelled by run and mode.
+    pub fn render(&self) -> String {
+        let (modes, images): (Vec<_>, Vec<_>) = {
+            let registry = self.0.lock().unwrap();
+            let images = registry.images.iter().map(|(run, n)| (run.clone(), n.load(Ordering::Relaxed))).collect();
+            (registry.modes.iter().map(|(key, series)| (key.clone(), *series)).collect(), images)
+        };
+        let mut text = String::new();
+        let mut family = |name: &str, kind: &str, help: &str, value: &dyn Fn(&ModeSeries) -> Option<String>| {
+            text.push_str(&format!("# HELP chronoturin_{} {}\n# TYPE chronoturin_{} {}\n", name, help, name, kind));
+            for ((run, mode), series) in &modes {
+                if let Some(value) = value(series) {
+                    text.push_str(&format!("chronoturin_{}{{run=\"{}\",mode=\"{}\"}} {}\n", name, label(run), label(mode), value));
+                }
+            }
+        };
+        family("frame", "gauge", "Index of the latest completed frame.", &|s| Some(s.frame.to_string()));
+        family("frames_per_second", "gauge", "Frame rate of the latest frame.", &|s| Some(format!("{:.3}", s.fps)));
+        family("gpu_step_ms", "gauge", "GPU time of the latest frame's steps, in milliseconds.", &|s| {
+            s.gpu_ms.map(|ms| format!("{:.4}", ms))
+        });
+        family("active_fraction", "gauge", "Fraction of stars active in the latest frame.", &|s| Some(format!("{:.6}", s.active_fraction)));
+        family("frames_total", "counter", "Frames completed.", &|s| Some(s.frames_total.to_string()));
+        text.push_str("# HELP chronoturin_images_written_total Images written to disk.\n# TYPE chronoturin_images_written_total counter\n");
+        for (run, written) in images {
+            text.push_str(&format!("chronoturin_images_written_total{{run=\"{}\"}} {}\n", label(&run), written));
+        }
+        text
+    }
+}
+
+// Label values escape backslashes, quotes and newlines.
+fn label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -0,0 +1,65 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::dump::STAR_FIELDS;
+use crate::galaxy::{is_dead, Star};
+
+// --track: every frame, each tracked star's full state goes to its own CSV. A star's ID is its
+// creation index, the order the preset or --initial-conditions file produced it in (a
+// --central-mass star is ID 0). Readbacks keep that order even when --sort-every or
+// --compaction reorders the work on the GPU, so an ID is simply an index into the stars.
+// A star culled past --kill-radius gets one last row, its active_flag DEAD_FLAG, for the
+// frame it escaped at, and none after it.
+pub struct TrajectoryWriter {
+    files: Vec<(u32, BufWriter<File>)>,
+    // The mode and ID of every star whose escape row is written
+    escaped: Vec<(String, u32)>,
+}
+
+pub fn trajectory_header() -> String {
+    format!("mode,frame,sim_time,{}", STAR_FIELDS.join(","))
+}
+
+impl TrajectoryWriter {
+    // `path` names each ID's file. `append` keeps the rows already there (resuming from a
+    // checkpoint); the header is only written to new or empty files.
+    pub fn open(ids: &[u32], path: impl Fn(u32) -> PathBuf, append: bool) -> io::Result<Self> {
+        let mut files = Vec::with_capacity(ids.len());
+        for &id in ids {
+            let file = match append {
+                true => OpenOptions::new().create(true).append(true).open(path(id))?,
+                false => File::create(path(id))?,
+            };
+            let empty = file.metadata()?.len() == 0;
+            let mut out = BufWriter::new(file);
+            if empty {
+                writeln!(out, "{}", trajectory_header())?;
+            }
+            out.flush()?;
+            files.push((id, out));
+        }
+        Ok(Self { files, escaped: Vec::new() })
+    }
+
+    // One row per tracked star, in dump precision. Flushed immediately, like the metrics log.
+    pub fn write(&mut self, mode: &str, frame: usize, sim_time: f32, stars: &[Star]) -> io::Result<()> {
+        for (id, out) in &mut self.files {
+            let Some(s) = stars.get(*id as usize) else { continue };
+            if is_dead(s) {
+                if self.escaped.iter().any(|(m, e)| m == mode && e == id) {
+                    continue;
+                }
+                tracing::info!("Tracked star {} escaped at frame {} ({})", id, frame, mode);
+                self.escaped.push((mode.to_string(), *id));
+            }
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                mode, frame, sim_time, s.x, s.y, s.z, s.vx, s.vy, s.vz, s.mass, s.data_type, s.time_debt, s.active_flag
+            )?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+use std::time::Instant;
+
+use serde::{Serialize, Serializer};
+
+use crate::config::SimConfig;
+use crate::diff::{displacement_stats, DisplacementStats};
+use crate::error::ChronoError;
+use crate::simulation::{DilationMode, Stepper};
+use crate::snapshot::StarSummary;
+
+// `chronoturin [flags] tune --error-budget 0.01 --probe-frames 30 [--run]`: short lockstep
+// probes that bisect the dilation threshold. Flags before `tune` apply to every probe.
+#[derive(clap::Args, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", default, deny_unknown_fields)]
+pub struct TuneArgs {
+    /// Largest mean divergence from Newtonian to accept, as a fraction of the galaxy's RMS radius
+    #[arg(long, default_value_t = 0.01)]
+    pub error_budget: f32,
+
+    /// Frames each probe steps both modes before measuring the divergence
+    #[arg(long, default_value_t = 30)]
+    pub probe_frames: usize,
+
+    /// The most conservative threshold searched; if it misses the budget, nothing will
+    #[arg(long, default_value_t = 0.05)]
+    pub min_threshold: f32,
+
+    /// The most aggressive threshold searched
+    #[arg(long, default_value_t = 2.0)]
+    pub max_threshold: f32,
+
+    /// Probes to run in all, both ends of the range included
+    #[arg(long, default_value_t = 8)]
+    pub probes: usize,
+
+    /// Then run the full comparison (--frames of it) with the recommended threshold
+    #[arg(long)]
+    pub run: bool,
+}
+
+// The flag defaults, for a --config [command.tune] table that leaves some out.
+impl Default for TuneArgs {
+    fn default() -> Self {
+        use clap::{Args, FromArgMatches};
+        let matches = Self::augment_args(clap::Command::new("tune")).get_matches_from(["tune"]);
+        Self::from_arg_matches(&matches).expect("every tune flag has a default")
+    }
+}
+
+impl TuneArgs {
+    pub fn validate(&self) -> Result<(), ChronoError> {
+        if !self.error_budget.is_finite() || self.error_budget <= 0.0 {
+            return Err(ChronoError::InvalidConfig("tune --error-budget must be positive".into()));
+        }
+        if self.probe_frames == 0 {
+            return Err(ChronoError::InvalidConfig("tune --probe-frames must be at least 1".into()));
+        }
+        if !(self.min_threshold.is_finite()
+            && self.max_threshold.is_finite()
+            && 0.0 <= self.min_threshold
+            && self.min_threshold < self.max_threshold)
+        {
+            return Err(ChronoError::InvalidConfig("tune --min-threshold must be at least 0 and below --max-threshold".into()));
+        }
+        if self.probes < 2 {
+            return Err(ChronoError::InvalidConfig("tune --probes must be at least 2, one for each end of the range".into()));
+        }
+        Ok(())
+    }
+}
+
+// One threshold, stepped for --probe-frames in both modes from the same galaxy.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Probe {
+    pub threshold: f32,
+    // Chronoturin's displacement from Newtonian after the last probe frame
+    pub divergence: DisplacementStats,
+    // divergence.mean over the Newtonian galaxy's RMS radius, what the budget is held to
+    pub error: f32,
+    // Mean step + readback wall time per frame
+    pub newton_ms: f32,
+    pub chrono_ms: f32,
+    pub active_fraction: f32,
+}
+
+impl Probe {
+    pub fn within(&self, budget: f32) -> bool {
+        self.error <= budget && self.divergence.non_finite == 0
+    }
+
+    pub fn speedup(&self) -> f32 {
+        if self.chrono_ms > 0.0 {
+            self.newton_ms / self.chrono_ms
+        } else {
+            0.0
+        }
+    }
+}
+
+// Every probe in the order it ran, and the largest threshold that met the budget.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuneTrace {
+    pub error_budget: f32,
+    pub probe_frames: usize,
+    pub probes: Vec<Probe>,
+    // None when even --min-threshold missed the budget, or the search was interrupted first
+    pub recommended: Option<f32>,
+}
+
+impl TuneTrace {
+    pub fn table(&self) -> String {
+        let mut table = String::from(
+            "| Probe | Threshold | Error | Within budget | Newtonian ms | Chronoturin ms | Speedup | Active % |\n\
+             |-------|-----------|-------|---------------|--------------|----------------|---------|----------|\n",
+        );
+        for (i, p) in self.probes.iter().enumerate() {
+            table.push_str(&format!(
+                "| {} | {} | {:.4} | {} | {:.2} | {:.2} | {:.1}× | {:.1}% |\n",
+                i + 1,
+                p.threshold,
+                p.error,
+                if p.within(self.error_budget) { "yes" } else { "no" },
+                p.newton_ms,
+                p.chrono_ms,
+                p.speedup(),
+                p.active_fraction * 100.0
+            ));
+        }
+        table
+    }
+}
+
+// The `tuning` object of the report JSON: each probe with its divergence spelled out and
+// whether it met the budget.
+impl Serialize for TuneTrace {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct ProbeJson {
+            threshold: f32,
+            error: f32,
+            within_budget: bool,
+            divergence_mean: f32,
+            divergence_p99: f32,
+            non_finite: usize,
+            newton_ms: f32,
+            chrono_ms: f32,
+            active_fraction: f32,
+        }
+        #[derive(Serialize)]
+        struct TraceJson {
+            error_budget: f32,
+            probe_frames: usize,
+            recommended_threshold: Option<f32>,
+            probes: Vec<ProbeJson>,
+        }
+        let probes = self
+            .probes
+            .iter()
+            .map(|p| ProbeJson {
+                threshold: p.threshold,
+                error: p.error,
+                within_budget: p.within(self.error_budget),
+                divergence_mean: p.divergence.mean,
+                divergence_p99: p.divergence.p99,
+                non_finite: p.divergence.non_finite,
+                newton_ms: p.newton_ms,
+                chrono_ms: p.chrono_ms,
+                active_fraction: p.active_fraction,
+            })
+            .collect();
+        TraceJson { error_budget: self.error_budget, probe_frames: self.probe_frames, recommended_threshold: self.recommended, probes }
+            .serialize(serializer)
+    }
+}
+
+// Steps `newton` and `chrono` from their initial galaxy at `threshold`; each probe resets
+// them, so every threshold starts from the same stars in the same buffers.
+pub fn probe<S: Stepper>(newton: &mut S, chrono: &mut S, config: &SimConfig, threshold: f32, frames: usize) -> Probe {
+    for (mode, sim) in [(DilationMode::Newtonian, &mut *newton), (DilationMode::Chronoturin, &mut *chrono)] {
+        sim.set_dilation(threshold, config.max_debt);
+        sim.reset();
+        sim.set_mode(mode);
+    }
+    let (mut newton_ms, mut chrono_ms, mut active) = (0.0, 0.0, 0.0);
+    let mut last = None;
+    for _ in 0..frames {
+        let start = Instant::now();
+        let ticket = newton.submit_frame(config.steps_per_frame);
+        let reference = newton.finish_frame(ticket);
+        newton_ms += start.elapsed().as_secs_f32() * 1000.0;
+        let start = Instant::now();
+        let ticket = chrono.submit_frame(config.steps_per_frame);
+        let stepped = chrono.finish_frame(ticket);
+        chrono_ms += start.elapsed().as_secs_f32() * 1000.0;
+        active += stepped.active_fraction();
+        last = Some((reference, stepped));
+    }
+    let (reference, stepped) = last.expect("validate() requires at least one probe frame");
+    let divergence = displacement_stats(&reference.stars, &stepped.stars);
+    let radius = StarSummary::measure(&reference.living()).rms_radius as f32;
+    let n = frames as f32;
+    Probe {
+        threshold,
+        divergence,
+        error: if radius > 0.0 { divergence.mean / radius } else { divergence.mean },
+        newton_ms: newton_ms / n,
+        chrono_ms: chrono_ms / n,
+        active_fraction: active / n,
+    }
+}
+
+// The largest threshold in range whose probe meets the budget, taking a larger threshold to
+// mean more sleeping stars and more divergence: both ends first, then bisection between the
+// last threshold that met it and the first that did not. `on_probe` sees each probe as it
+// finishes, and stops the search by returning false.
+pub fn tune<S: Stepper>(
+    newton: &mut S,
+    chrono: &mut S,
+    config: &SimConfig,
+    args: &TuneArgs,
+    mut on_probe: impl FnMut(&Probe) -> bool,
+) -> TuneTrace {
+    let mut trace = TuneTrace { error_budget: args.error_budget, probe_frames: args.probe_frames, probes: Vec::new(), recommended: None };
+    let mut run = |trace: &mut TuneTrace, threshold: f32| {
+        let probe = probe(newton, chrono, config, threshold, args.probe_frames);
+        trace.probes.push(probe);
+        let go_on = on_probe(&probe);
+        (probe.within(args.error_budget), go_on)
+    };
+    let (lo, hi) = (args.min_threshold, args.max_threshold);
+    match run(&mut trace, lo) {
+        (false, _) => return trace,
+        (true, go_on) => {
+            trace.recommended = Some(lo);
+            if !go_on {
+                return trace;
+            }
+        }
+    }
+    match run(&mut trace, hi) {
+        (true, _) => {
+            trace.recommended = Some(hi);
+            return trace;
+        }
+        (false, false) => return trace,
+        (false, true) => {}
+    }
+    let (mut passed, mut failed) = (lo, hi);
+    for _ in 2..args.probes {
+        let mid = 0.5 * (passed + failed);
+        let (within, go_on) = run(&mut trace, mid);
+        if within {
+            passed = mid;
+            trace.recommended = Some(mid);
+        } else {
+            failed = mid;
+        }
+        if !go_on {
+            break;
+        }
+    }
+    trace
+}
@@ -0,0 +1,79 @@
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::thread::JoinHandle;
+
+use image::RgbImage;
+
+use crate::error::ChronoError;
+use crate::simulation::DilationMode;
+
+// Streams raw RGB frames into a spawned ffmpeg process.
+pub struct VideoEncoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    // Drained on a thread so a chatty encoder can never fill the pipe and stall us
+    stderr: Option<JoinHandle<String>>,
+    path: PathBuf,
+}
+
+impl VideoEncoder {
+    pub fn spawn(ffmpeg: &Path, path: &Path, width: u32, height: u32, fps: u32) -> Result<Self, ChronoError> {
+        let mut child = Command::new(ffmpeg)
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{}x{}", width, height), "-r", &fps.to_string(), "-i", "-"])
+            .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| ChronoError::Encoder(format!("failed to start {}: {}", ffmpeg.display(), e)))?;
+
+        let stdin = child.stdin.take();
+        let stderr = child.stderr.take().map(|mut pipe| {
+            std::thread::spawn(move || {
+                let mut text = String::new();
+                let _ = pipe.read_to_string(&mut text);
+                text
+            })
+        });
+        Ok(Self { child, stdin, stderr, path: path.to_path_buf() })
+    }
+
+    pub fn write_frame(&mut self, img: &RgbImage) -> Result<(), ChronoError> {
+        let Some(stdin) = self.stdin.as_mut() else {
+            return Err(ChronoError::Encoder("encoder input already closed".into()));
+        };
+        if let Err(e) = stdin.write_all(img.as_raw()) {
+            let stderr = self.close();
+            return Err(ChronoError::Encoder(format!("encoder for {} died ({}): {}", self.path.display(), e, stderr.trim())));
+        }
+        Ok(())
+    }
+
+    // Closes the pipe and waits for the encoder to write the container.
+    pub fn finish(mut self) -> Result<PathBuf, ChronoError> {
+        let stderr = self.close();
+        match self.child.wait() {
+            Ok(status) if status.success() => Ok(self.path.clone()),
+            Ok(status) => {
+                Err(ChronoError::Encoder(format!("encoder for {} exited with {}: {}", self.path.display(), status, stderr.trim())))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn close(&mut self) -> String {
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+        self.stderr.take().and_then(|h| h.join().ok()).unwrap_or_default()
+    }
+}
+
+// out.mp4 -> out_newton.mp4 / out_chrono.mp4
+pub fn video_path_for(path: &Path, mode: DilationMode) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("video");
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("mp4");
+    path.with_file_name(format!("{}_{}.{}", stem, mode.file_stem(), ext))
+}
@@ -0,0 +1,337 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, Event, KeyEvent, MouseScrollDelta, WindowEvent};
+use winit::event_loop::EventLoop;
+use winit::keyboard::{Key, NamedKey};
+use winit::window::{Window, WindowBuilder};
+
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+use crate::galaxy::DEFAULT_DILATION_THRESHOLD;
+use crate::hot_reload::ShaderWatcher;
+use crate::interrupt;
+use crate::manifest::RunManifest;
+use crate::output::OutputLayout;
+use crate::points::{PointRenderer, ViewUniform};
+use crate::render::{render_frame, Camera, RenderSettings};
+use crate::saver::FrameSaver;
+use crate::simulation::{DilationMode, FrameData, Simulation};
+
+// Camera moves per key press / scroll line.
+const ORBIT_STEP: f32 = 5.0;
+const LIFT_STEP: f32 = 0.05;
+const ZOOM_STEP: f32 = 0.9;
+
+pub fn open_window(config: &SimConfig) -> Result<(EventLoop<()>, Arc<Window>), ChronoError> {
+    let event_loop = EventLoop::new().map_err(|e| ChronoError::Window(e.to_string()))?;
+    let window = WindowBuilder::new()
+        .with_title("Chronoturin")
+        .with_inner_size(PhysicalSize::new(config.width, config.height))
+        .build(&event_loop)
+        .map_err(|e| ChronoError::Window(e.to_string()))?;
+    Ok((event_loop, Arc::new(window)))
+}
+
+// Where the keyboard and scroll wheel have moved the camera from Camera::from_config.
+struct Controls {
+    paused: bool,
+    mode: DilationMode,
+    yaw: f32,
+    lift: f32,
+    zoom: f32,
+}
+
+impl Controls {
+    fn camera(&self, config: &SimConfig, size: PhysicalSize<u32>) -> Camera {
+        let position = [0.0, self.lift * config.camera_z().abs(), config.camera_z() * self.zoom];
+        Camera::look_at(position, [0.0; 3], [0.0, 1.0, 0.0], size.width, size.height, config.fov).orbited(self.yaw)
+    }
+
+    fn set_mode(&mut self, mode: DilationMode, sim: &mut Simulation) {
+        self.mode = mode;
+        sim.set_mode(mode);
+    }
+
+    fn key(&mut self, key: &Key, sim: &mut Simulation) {
+        match key {
+            Key::Named(NamedKey::Space) => self.paused = !self.paused,
+            Key::Named(NamedKey::ArrowLeft) => self.yaw -= ORBIT_STEP,
+            Key::Named(NamedKey::ArrowRight) => self.yaw += ORBIT_STEP,
+            Key::Named(NamedKey::ArrowUp) => self.lift += LIFT_STEP,
+            Key::Named(NamedKey::ArrowDown) => self.lift -= LIFT_STEP,
+            Key::Character(c) if c.eq_ignore_ascii_case("m") => {
+                let mode = match self.mode {
+                    DilationMode::Newtonian => DilationMode::Chronoturin,
+                    DilationMode::Chronoturin => DilationMode::Newtonian,
+                };
+                self.set_mode(mode, sim);
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("r") => sim.reset(),
+            _ => {}
+        }
+    }
+}
+
+fn quits(key: &Key) -> bool {
+    matches!(key, Key::Named(NamedKey::Escape)) || matches!(key, Key::Character(c) if c.eq_ignore_ascii_case("q"))
+}
+
+// What the panel shows, updated after every stepped frame.
+#[derive(Default)]
+struct Stats {
+    fps: f32,
+    // Per sub-step; None without TIMESTAMP_QUERY
+    gpu_step_ms: Option<f32>,
+    active_fraction: f32,
+}
+
+impl Stats {
+    fn record(&mut self, frame: &FrameData, stars: u32) {
+        self.gpu_step_ms = frame.gpu_ms.map(|ms| ms / frame.steps.max(1) as f32);
+        self.active_fraction = frame.active_per_step() / stars.max(1) as f32;
+    }
+}
+
+// Everything one redraw needs: the simulation, the surface and both renderers.
+struct Viewer {
+    sim: Simulation,
+    config: SimConfig,
+    surface: wgpu::Surface<'static>,
+    surface_config: wgpu::SurfaceConfiguration,
+    points: PointRenderer,
+    egui: egui::Context,
+    egui_input: egui_winit::State,
+    egui_renderer: egui_wgpu::Renderer,
+    controls: Controls,
+    stats: Stats,
+    last_redraw: Instant,
+    layout: OutputLayout,
+    // Screenshots go through the same PNG workers as frame files; taken on exit to flush them
+    saver: Option<FrameSaver>,
+    next_shot: usize,
+    // --shader, and the change the kernels were last compiled from
+    shader: Option<(ShaderWatcher, usize)>,
+}
+
+impl Viewer {
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.surface_config.width = size.width.max(1);
+        self.surface_config.height = size.height.max(1);
+        self.surface.configure(self.sim.device(), &self.surface_config);
+    }
+
+    fn redraw(&mut self, window: &Window) -> Result<(), wgpu::SurfaceError> {
+        if let Some((shader, loaded)) = &mut self.shader {
+            shader.poll(loaded, &mut [&mut self.sim]);
+        }
+        if !self.controls.paused {
+            let ticket = self.sim.submit_stats(self.config.steps_per_frame);
+            let frame = self.sim.finish_frame(ticket);
+            self.stats.record(&frame, self.sim.star_count());
+        }
+        let elapsed = self.last_redraw.elapsed().as_secs_f32();
+        self.last_redraw = Instant::now();
+        if elapsed > 0.0 {
+            self.stats.fps = if self.stats.fps == 0.0 { 1.0 / elapsed } else { 0.9 * self.stats.fps + 0.1 / elapsed };
+        }
+
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(self.sim.device(), &self.surface_config);
+                return Ok(());
+            }
+            Err(wgpu::SurfaceError::Timeout) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let target = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let size = PhysicalSize::new(self.surface_config.width, self.surface_config.height);
+        let view = ViewUniform::new(&self.controls.camera(&self.config, size), self.controls.mode);
+
+        // The panel runs first so its slider and buttons are applied before this frame is drawn.
+        let egui = self.egui.clone();
+        let raw_input = self.egui_input.take_egui_input(window);
+        let full_output = egui.run(raw_input, |ctx| self.panel(ctx));
+        self.egui_input.handle_platform_output(window, full_output.platform_output);
+        let jobs = egui.tessellate(full_output.shapes, full_output.pixels_per_point);
+        let screen =
+            egui_wgpu::ScreenDescriptor { size_in_pixels: [size.width, size.height], pixels_per_point: full_output.pixels_per_point };
+        let (device, queue) = (self.sim.device(), self.sim.queue());
+        for (id, delta) in &full_output.textures_delta.set {
+            self.egui_renderer.update_texture(device, queue, *id, delta);
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.points.draw(queue, &mut encoder, &target, &view);
+        let uploads = self.egui_renderer.update_buffers(device, queue, &mut encoder, &jobs, &screen);
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Stats Panel"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.egui_renderer.render(&mut pass, &jobs, &screen);
+        }
+        queue.submit(uploads.into_iter().chain([encoder.finish()]));
+        output.present();
+        for id in &full_output.textures_delta.free {
+            self.egui_renderer.free_texture(id);
+        }
+        Ok(())
+    }
+
+    // Slider and buttons write straight into the simulation's uniform, so they take
+    // effect on the next dispatch without touching the pipelines.
+    fn panel(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::left("stats").resizable(false).show(ctx, |ui| {
+            ui.heading("Chronoturin");
+            ui.label(format!("{:.0} fps", self.stats.fps));
+            ui.label(match self.stats.gpu_step_ms {
+                Some(ms) => format!("GPU step: {:.3} ms", ms),
+                None => "GPU step: n/a (no TIMESTAMP_QUERY)".into(),
+            });
+            ui.label(format!("Active: {:.1}%", self.stats.active_fraction * 100.0));
+            ui.label(format!("Sim time: {:.2}", self.sim.sim_time()));
+            ui.separator();
+
+            let mut mode = self.controls.mode;
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut mode, DilationMode::Newtonian, DilationMode::Newtonian.name());
+                ui.selectable_value(&mut mode, DilationMode::Chronoturin, DilationMode::Chronoturin.name());
+            });
+            if mode != self.controls.mode {
+                self.controls.set_mode(mode, &mut self.sim);
+            }
+            let (mut threshold, max_debt) = (self.sim.config().dilation_threshold, self.sim.config().max_debt);
+            let range = 0.0..=4.0 * self.config.dilation_threshold.max(DEFAULT_DILATION_THRESHOLD);
+            if ui.add(egui::Slider::new(&mut threshold, range).text("dilation threshold")).changed() {
+                self.sim.set_dilation(threshold, max_debt);
+            }
+            ui.separator();
+
+            ui.checkbox(&mut self.controls.paused, "Paused");
+            ui.horizontal(|ui| {
+                if ui.button("Reset").clicked() {
+                    self.sim.reset();
+                }
+                if ui.button("Screenshot").clicked() {
+                    self.screenshot();
+                }
+            });
+        });
+    }
+
+    // Renders the current stars offline at --width x --height, like a frame file.
+    fn screenshot(&mut self) {
+        let stars = self.sim.read_stars();
+        let size = PhysicalSize::new(self.config.width, self.config.height);
+        let camera = self.controls.camera(&self.config, size);
+        let is_chronoturin = self.controls.mode == DilationMode::Chronoturin;
+        let img = render_frame(&stars, &camera, &RenderSettings::from_config(&self.config), is_chronoturin);
+        let shot = (self.next_shot..).find(|&n| !self.layout.screenshot_path(n).exists()).expect("unbounded range");
+        let path = self.layout.screenshot_path(shot);
+        self.next_shot = shot + 1;
+        tracing::info!("Saving screenshot {}", path.display());
+        if let Some(saver) = &mut self.saver {
+            saver.save(path, img);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(stats) = self.saver.take().map(FrameSaver::finish) {
+            if stats.frames_saved + stats.failures > 0 {
+                tracing::info!("Saved {} screenshots ({} failed)", stats.frames_saved, stats.failures);
+            }
+        }
+    }
+}
+
+// Steps `sim` by --steps-per-frame before every redraw until the window closes,
+// Esc/Q is pressed or Ctrl-C arrives.
+pub fn run(
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    adapter: &wgpu::Adapter,
+    mut sim: Simulation,
+    config: &SimConfig,
+) -> Result<(), ChronoError> {
+    let size = window.inner_size();
+    let surface_config = surface
+        .get_default_config(adapter, size.width.max(1), size.height.max(1))
+        .ok_or_else(|| ChronoError::Window("the adapter cannot present to this window".into()))?;
+    surface.configure(sim.device(), &surface_config);
+    let layout = OutputLayout::prepare(config)?;
+
+    let egui = egui::Context::default();
+    let max_texture_side = sim.device().limits().max_texture_dimension_2d as usize;
+    let egui_input =
+        egui_winit::State::new(egui.clone(), egui::ViewportId::ROOT, &*window, Some(window.scale_factor() as f32), Some(max_texture_side));
+    let shader = match &config.shader {
+        Some(path) => Some((ShaderWatcher::start(path, &mut [&mut sim])?, 0)),
+        None => None,
+    };
+    let controls = Controls { paused: false, mode: DilationMode::Chronoturin, yaw: 0.0, lift: 0.0, zoom: 1.0 };
+    sim.set_mode(controls.mode);
+    let mut viewer = Viewer {
+        points: PointRenderer::new(&sim, surface_config.format, &config.palette()),
+        egui_renderer: egui_wgpu::Renderer::new(sim.device(), surface_config.format, None, 1),
+        sim,
+        config: config.clone(),
+        surface,
+        surface_config,
+        egui,
+        egui_input,
+        controls,
+        stats: Stats::default(),
+        last_redraw: Instant::now(),
+        layout,
+        saver: Some(FrameSaver::new(1, config.save_queue, Some(RunManifest::new(config)))),
+        next_shot: 0,
+        shader,
+    };
+    event_loop
+        .run(move |event, target| match event {
+            Event::AboutToWait if interrupt::requested() => target.exit(),
+            Event::AboutToWait => window.request_redraw(),
+            Event::LoopExiting => viewer.finish(),
+            Event::WindowEvent { event, .. } => {
+                // Keys typed into the panel and scrolling over it stay there.
+                let consumed = viewer.egui_input.on_window_event(&window, &event).consumed;
+                match event {
+                    WindowEvent::CloseRequested => target.exit(),
+                    WindowEvent::Resized(size) => viewer.resize(size),
+                    WindowEvent::KeyboardInput { event: KeyEvent { logical_key, state: ElementState::Pressed, .. }, .. } if !consumed => {
+                        if quits(&logical_key) {
+                            target.exit();
+                        }
+                        viewer.controls.key(&logical_key, &mut viewer.sim);
+                    }
+                    WindowEvent::MouseWheel { delta, .. } if !consumed => {
+                        let lines = match delta {
+                            MouseScrollDelta::LineDelta(_, y) => y,
+                            MouseScrollDelta::PixelDelta(p) => p.y as f32 / 50.0,
+                        };
+                        viewer.controls.zoom *= ZOOM_STEP.powf(lines);
+                    }
+                    WindowEvent::RedrawRequested => {
+                        if let Err(e) = viewer.redraw(&window) {
+                            tracing::error!("Viewer stopped: {}", e);
+                            target.exit();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        })
+        .map_err(|e| ChronoError::Window(e.to_string()))
+}
@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt;
+
+use crate::galaxy::{is_dead, Star};
+
+// Watchdog's counts, after the bound
+const COUNTS_OFFSET: u64 = 4;
+pub const COUNT_BYTES: u64 = 16;
+
+// What a run does once the watchdog fires (--on-divergence).
+#[derive(clap::ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OnDivergence {
+    // Stop with ChronoError::Diverged
+    Abort,
+    // Log the conditions the first time each fires in a mode, and carry on
+    Warn,
+}
+
+// Living stars that tripped each condition at the end of a frame. A non-finite coordinate
+// counts under its own condition only, not also as past the bound.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct WatchdogCounts {
+    pub nan_position: u32,
+    pub nan_velocity: u32,
+    pub far_position: u32,
+    pub fast_velocity: u32,
+}
+
+impl WatchdogCounts {
+    // The CPU side of shader.wgsl's watch.
+    pub fn measure(stars: &[Star], bound: f32) -> Self {
+        let mut counts = Self::default();
+        for star in stars.iter().filter(|s| !is_dead(s)) {
+            for (v, nan, far) in [
+                ([star.x, star.y, star.z], &mut counts.nan_position, &mut counts.far_position),
+                ([star.vx, star.vy, star.vz], &mut counts.nan_velocity, &mut counts.fast_velocity),
+            ] {
+                if v.iter().any(|c| !c.is_finite()) {
+                    *nan += 1;
+                } else if v.iter().any(|c| c.abs() > bound) {
+                    *far += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    pub fn fired(&self) -> bool {
+        *self != Self::default()
+    }
+
+    // Each condition that fired, as a description and its star count.
+    pub fn conditions(&self) -> impl Iterator<Item = (&'static str, u32)> {
+        [
+            ("non-finite position", self.nan_position),
+            ("non-finite velocity", self.nan_velocity),
+            ("position past --divergence-bound", self.far_position),
+            ("velocity past --divergence-bound", self.fast_velocity),
+        ]
+        .into_iter()
+        .filter(|&(_, count)| count > 0)
+    }
+
+    // "3 stars with a non-finite position, 1 with a velocity past ..."
+    pub fn describe(&self) -> String {
+        self.conditions().map(|(condition, count)| format!("{} with a {}", count, condition)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+// Matches `Watchdog` in shader.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct WatchdogState {
+    bound: f32,
+    counts: WatchdogCounts,
+    pad: [u32; 3],
+}
+
+// shader.wgsl's watch. Compiled once and shared by forked and resized simulations.
+pub struct WatchdogPipelines {
+    watch: wgpu::ComputePipeline,
+}
+
+impl WatchdogPipelines {
+    // `shader` is simulation::shader_module's, which the other passes on shader.wgsl share.
+    pub fn new(device: &wgpu::Device, shader: &wgpu::ShaderModule) -> Self {
+        let watch = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("watch"),
+            layout: None,
+            module: shader,
+            entry_point: "watch",
+        });
+        Self { watch }
+    }
+}
+
+// Checks every living star once per frame, after its last step, for non-finite or
+// out-of-bound positions and velocities. The counts ride along in the frame's readback,
+// so a healthy frame costs one small dispatch and a 16-byte copy.
+pub struct Watchdog {
+    pipelines: Arc<WatchdogPipelines>,
+    state: wgpu::Buffer,
+    // One per star chunk, with its whole workgroup count
+    passes: Vec<(wgpu::BindGroup, u32)>,
+}
+
+impl Watchdog {
+    // `chunks` are each chunk's entries for shader.wgsl bindings 0 and 3 to 6 and its workgroup count.
+    pub fn new(device: &wgpu::Device, pipelines: Arc<WatchdogPipelines>, bound: f32, chunks: &[(Vec<wgpu::BindGroupEntry>, u32)]) -> Self {
+        let state = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Watchdog"),
+            contents: bytemuck::bytes_of(&WatchdogState { bound, counts: WatchdogCounts::default(), pad: [0; 3] }),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+        let layout = pipelines.watch.get_bind_group_layout(0);
+        let passes = chunks
+            .iter()
+            .map(|(entries, workgroups)| {
+                let entries: Vec<_> =
+                    entries.iter().cloned().chain([wgpu::BindGroupEntry { binding: 17, resource: state.as_entire_binding() }]).collect();
+                (device.create_bind_group(&wgpu::BindGroupDescriptor { label: None, layout: &layout, entries: &entries }), *workgroups)
+            })
+            .collect();
+        Self { pipelines, state, passes }
+    }
+
+    pub fn pipelines(&self) -> &Arc<WatchdogPipelines> {
+        &self.pipelines
+    }
+
+    // Records the watch pass over each chunk's living stars and copies the counts into `dest`.
+    pub fn encode(&self, encoder: &mut wgpu::CommandEncoder, dest: &wgpu::Buffer, offset: u64) {
+        encoder.clear_buffer(&self.state, COUNTS_OFFSET, Some(COUNT_BYTES));
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Watch"), timestamp_writes: None });
+            cpass.set_pipeline(&self.pipelines.watch);
+            for (bind_group, workgroups) in &self.passes {
+                cpass.set_bind_group(0, bind_group, &[]);
+                cpass.dispatch_workgroups(*workgroups, 1, 1);
+            }
+        }
+        encoder.copy_buffer_to_buffer(&self.state, COUNTS_OFFSET, dest, offset, COUNT_BYTES);
+    }
+}
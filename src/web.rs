@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use wasm_bindgen::prelude::*;
+use web_sys::HtmlCanvasElement;
+
+use crate::config::SimConfig;
+use crate::error::ChronoError;
+use crate::gpu;
+use crate::points::{PointRenderer, ViewUniform};
+use crate::presets;
+use crate::render::Camera;
+use crate::simulation::{DilationMode, Simulation};
+
+fn js_error(error: ChronoError) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+// The simulation drawn into an HTML canvas (examples/web). JS calls frame() from
+// requestAnimationFrame and changes the star count or mode between frames. Nothing is
+// read back, so no frame waits on the GPU; the browser has no blocking poll to wait with.
+#[wasm_bindgen]
+pub struct WebSimulation {
+    sim: Simulation,
+    config: SimConfig,
+    canvas: HtmlCanvasElement,
+    surface: wgpu::Surface<'static>,
+    surface_config: wgpu::SurfaceConfiguration,
+    points: PointRenderer,
+    mode: DilationMode,
+}
+
+#[wasm_bindgen]
+impl WebSimulation {
+    // Opens a WebGPU device that can draw into `canvas` and seeds `stars` stars from a random
+    // seed, in Chronoturin mode. Resolves once the device is ready; this takes the place of
+    // the native binary's pollster::block_on.
+    pub async fn create(canvas: HtmlCanvasElement, stars: u32) -> Result<WebSimulation, JsValue> {
+        let config = SimConfig { stars, seed: Some(rand::random()), ..SimConfig::default() };
+        let instance = gpu::create_instance(&config);
+        let surface = instance
+            .create_surface(wgpu::SurfaceTarget::Canvas(canvas.clone()))
+            .map_err(|e| js_error(ChronoError::Window(e.to_string())))?;
+        let (adapter, device, queue) = gpu::request_device(&instance, &config, Some(&surface)).await.map_err(js_error)?;
+        let surface_config = surface
+            .get_default_config(&adapter, canvas.width().max(1), canvas.height().max(1))
+            .ok_or_else(|| js_error(ChronoError::Window("the adapter cannot present to this canvas".into())))?;
+        surface.configure(&device, &surface_config);
+
+        let mut sim = Simulation::new(Arc::new(device), Arc::new(queue), &config).map_err(js_error)?;
+        let mode = DilationMode::Chronoturin;
+        sim.set_mode(mode);
+        let points = PointRenderer::new(&sim, surface_config.format, &config.palette());
+        Ok(Self { sim, config, canvas, surface, surface_config, points, mode })
+    }
+
+    // Starts over from a new galaxy of `stars` stars, on the same device and in the same mode.
+    pub fn set_stars(&mut self, stars: u32) -> Result<(), JsValue> {
+        self.config.stars = stars;
+        let initial_data = presets::from_config(&self.config, rand::random());
+        let mut sim =
+            Simulation::with_stars(self.sim.device().clone(), self.sim.queue().clone(), &self.config, initial_data).map_err(js_error)?;
+        sim.set_mode(self.mode);
+        self.points = PointRenderer::new(&sim, self.surface_config.format, &self.config.palette());
+        self.sim = sim;
+        Ok(())
+    }
+
+    pub fn stars(&self) -> u32 {
+        self.sim.star_count()
+    }
+
+    // Switches between Newtonian and Chronoturin without resetting the galaxy.
+    pub fn set_chronoturin(&mut self, chronoturin: bool) {
+        self.mode = if chronoturin { DilationMode::Chronoturin } else { DilationMode::Newtonian };
+        self.sim.set_mode(self.mode);
+    }
+
+    // "NEWTONIAN" or "CHRONOTURIN"
+    pub fn mode(&self) -> String {
+        self.mode.name().to_string()
+    }
+
+    // Back to the initial galaxy.
+    pub fn reset(&mut self) {
+        self.sim.reset();
+    }
+
+    // Steps by --steps-per-frame and draws the stars into the canvas, following its size.
+    pub fn frame(&mut self) -> Result<(), JsValue> {
+        let (width, height) = (self.canvas.width().max(1), self.canvas.height().max(1));
+        if (width, height) != (self.surface_config.width, self.surface_config.height) {
+            (self.surface_config.width, self.surface_config.height) = (width, height);
+            self.surface.configure(self.sim.device(), &self.surface_config);
+        }
+        self.sim.step(self.config.steps_per_frame);
+
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(self.sim.device(), &self.surface_config);
+                return Ok(());
+            }
+            Err(wgpu::SurfaceError::Timeout) => return Ok(()),
+            Err(e) => return Err(js_error(ChronoError::Window(e.to_string()))),
+        };
+        let target = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let camera = Camera::look_at([0.0, 0.0, self.config.camera_z()], [0.0; 3], [0.0, 1.0, 0.0], width, height, self.config.fov);
+        let mut encoder = self.sim.device().create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.points.draw(self.sim.queue(), &mut encoder, &target, &ViewUniform::new(&camera, self.mode));
+        self.sim.queue().submit(Some(encoder.finish()));
+        output.present();
+        Ok(())
+    }
+}
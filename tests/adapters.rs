@@ -0,0 +1,61 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::{cpu_reference, gpu, ChronoError, SimConfig};
+
+#[test]
+fn adapters_take_two_indices_for_frame_runs() {
+    let limits = cpu_reference::limits();
+    let parse = |args: &[&str]| common::parse(args).validate(&limits);
+    assert_eq!(SimConfig::parse_from(["chronoturin", "--adapters", "0,1"]).adapters, Some(vec![0, 1]));
+    assert!(parse(&["--adapters", "0,1"]).is_ok());
+    assert!(parse(&["--adapters", "1,1", "--sequential", "--diff-scale", "2"]).is_ok());
+    assert!(parse(&["--adapters", "0"]).is_err());
+    assert!(parse(&["--adapters", "0,1,2"]).is_err());
+    assert!(parse(&["--adapters", "0,1", "--adapter", "nvidia"]).is_err());
+    assert!(parse(&["--adapters", "0,1", "--cpu"]).is_err());
+    assert!(parse(&["--adapters", "0,1", "bench"]).is_err());
+    // Lockstep composes each frame from both devices, so it can still write GIFs and checkpoints.
+    assert!(parse(&["--adapters", "0,1", "--gif", "run.gif"]).is_ok());
+    assert!(parse(&["--adapters", "0,1", "--sequential", "--gif", "run.gif"]).is_err());
+    assert!(parse(&["--adapters", "0,1", "--sequential", "--checkpoint-every", "10"]).is_err());
+}
+
+#[test]
+fn device_errors_are_kept_instead_of_exiting() {
+    let (device, _queue) = require_gpu!();
+    let errors = gpu::DeviceErrors::watch(&device);
+    assert_eq!(errors.first(), None);
+    // MAP_READ only combines with COPY_DST.
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 16,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let message = errors.first().expect("the invalid buffer was reported");
+    // Only the first error is kept.
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("second"),
+        size: 16,
+        usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    assert_eq!(errors.first(), Some(message));
+}
+
+#[test]
+fn adapter_indices_past_the_list_are_refused() {
+    let config = SimConfig::default();
+    let instance = gpu::create_instance(&config);
+    let available = gpu::describe_adapters(&instance, gpu::backends(&config));
+    match pollster::block_on(gpu::request_device_at(&instance, &config, available.len())) {
+        Err(ChronoError::AdapterIndex { index, available: listed }) => assert_eq!((index, listed), (available.len(), available.clone())),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("opened an adapter past the list"),
+    }
+    if !available.is_empty() {
+        assert!(pollster::block_on(gpu::request_device_at(&instance, &config, 0)).is_ok());
+    }
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::bench::speedup;
+use chronoturin::config::Command;
+use chronoturin::{BenchReport, DilationMode, SimConfig, Simulation, SortedBench, StepStats, TimingSource, UnifiedBench, UniformPath};
+
+#[test]
+fn step_stats_of_known_samples() {
+    let samples: Vec<f32> = (1..=20).map(|v| v as f32).collect();
+    let s = StepStats::from_samples(&samples);
+    assert_eq!((s.samples, s.mean, s.median, s.p95), (20, 10.5, 10.5, 19.0));
+    // Sample standard deviation of 1..=20 is sqrt(35)
+    assert!((s.std_dev - 35f64.sqrt()).abs() < 1e-12, "{}", s.std_dev);
+    assert_eq!(StepStats::from_samples(&[3.0, 1.0, 2.0]).median, 2.0);
+}
+
+#[test]
+fn speedup_interval_brackets_the_ratio_and_collapses_without_noise() {
+    let newton = StepStats::from_samples(&[9.0, 10.0, 11.0, 10.0]);
+    let chrono = StepStats::from_samples(&[4.0, 5.0, 6.0, 5.0]);
+    let (ratio, lo, hi) = speedup(&newton, &chrono);
+    assert_eq!(ratio, 2.0);
+    assert!(lo < ratio && ratio < hi, "{} {} {}", lo, ratio, hi);
+    let steady = StepStats::from_samples(&[4.0; 8]);
+    assert_eq!(speedup(&StepStats::from_samples(&[8.0; 8]), &steady), (2.0, 2.0, 2.0));
+}
+
+#[test]
+fn json_report_is_one_line() {
+    let stats = StepStats::from_samples(&[2.0, 2.0]);
+    let report = BenchReport {
+        stars: 1000,
+        steps: 2,
+        warmup: 0,
+        timing: TimingSource::WallClock,
+        uniform_path: UniformPath::Buffer,
+        newton: stats,
+        chrono: stats,
+        sorted: None,
+        unified: None,
+    };
+    assert_eq!(
+        report.json(),
+        concat!(
+            r#"{"stars":1000,"steps":2,"warmup":0,"timing":"wall-clock","uniform_path":"buffer","#,
+            r#""newtonian":{"mean_ms":2.0,"median_ms":2.0,"p95_ms":2.0,"std_dev_ms":0.0},"#,
+            r#""chronoturin":{"mean_ms":2.0,"median_ms":2.0,"p95_ms":2.0,"std_dev_ms":0.0},"#,
+            r#""speedup":1.0,"speedup_ci95":[1.0,1.0]}"#
+        )
+    );
+    let halved = StepStats::from_samples(&[1.0, 1.0]);
+    let sorted = BenchReport { sorted: Some(SortedBench { every: 10, newton: halved, chrono: stats, sort: halved }), ..report };
+    assert!(sorted.json().ends_with(concat!(
+        r#""speedup_ci95":[1.0,1.0],"sorted":{"every":10,"#,
+        r#""newtonian":{"mean_ms":1.0,"median_ms":1.0,"p95_ms":1.0,"std_dev_ms":0.0},"#,
+        r#""chronoturin":{"mean_ms":2.0,"median_ms":2.0,"p95_ms":2.0,"std_dev_ms":0.0},"#,
+        r#""sort":{"mean_ms":1.0,"median_ms":1.0,"p95_ms":1.0,"std_dev_ms":0.0},"#,
+        r#""newtonian_speedup":2.0,"chronoturin_speedup":1.0}}"#
+    )));
+    let unified = BenchReport { unified: Some(UnifiedBench { newton: stats, chrono: halved }), ..report };
+    assert!(unified.json().ends_with(concat!(
+        r#""speedup_ci95":[1.0,1.0],"unified":{"#,
+        r#""newtonian":{"mean_ms":2.0,"median_ms":2.0,"p95_ms":2.0,"std_dev_ms":0.0},"#,
+        r#""chronoturin":{"mean_ms":1.0,"median_ms":1.0,"p95_ms":1.0,"std_dev_ms":0.0},"#,
+        r#""newtonian_speedup":1.0,"chronoturin_speedup":0.5}}"#
+    )));
+}
+
+#[test]
+fn bench_defaults_and_star_override() {
+    let config = SimConfig::parse_from(["chronoturin", "--stars", "300", "bench"]);
+    let Some(Command::Bench(args)) = &config.command else { panic!("no bench subcommand") };
+    assert_eq!((args.stars, args.steps, args.warmup, args.json.is_none(), args.compare_kernels), (None, 500, 50, true, false));
+    let config = SimConfig::parse_from(["chronoturin", "bench", "--stars", "40000", "--steps", "10"]);
+    let Some(Command::Bench(args)) = &config.command else { panic!("no bench subcommand") };
+    assert_eq!((args.stars, args.steps), (Some(40000), 10));
+    let limits = chronoturin::cpu_reference::limits();
+    assert!(SimConfig::parse_from(["chronoturin", "bench", "--compare-kernels"]).validate(&limits).is_ok());
+    assert!(SimConfig::parse_from(["chronoturin", "--unified-kernel", "bench", "--compare-kernels"]).validate(&limits).is_err());
+}
+
+#[test]
+fn timed_steps_advance_the_clock() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &SimConfig { stars: 512, ..SimConfig::default() }).unwrap();
+    sim.set_mode(DilationMode::Chronoturin);
+    let (ms, source) = sim.time_step().unwrap();
+    assert!(ms > 0.0, "{} ms from {:?}", ms, source);
+    assert_eq!(source == TimingSource::GpuTimestamps, sim.has_timestamps());
+    sim.time_step().unwrap();
+    assert_eq!(sim.sim_time(), 2.0 * sim.state().dt);
+}
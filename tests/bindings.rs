@@ -0,0 +1,94 @@
+mod common;
+
+use chronoturin::config::Command;
+use chronoturin::{cpu_reference, RunManifest, SimConfig, Simulation, UniformPath};
+
+use common::parse;
+
+fn config() -> SimConfig {
+    SimConfig { steps_per_frame: 3, ..common::seeded(1000, 7) }
+}
+
+#[test]
+fn push_constants_are_chosen_only_where_the_device_has_them() {
+    // common::gpu() asks for no optional features. GL adapters share one display, so it
+    // closes before the next device opens.
+    {
+        let (device, _) = require_gpu!();
+        assert_eq!(UniformPath::select(&device, None).unwrap(), UniformPath::Buffer);
+        assert_eq!(UniformPath::select(&device, Some(UniformPath::Buffer)).unwrap(), UniformPath::Buffer);
+        let error = UniformPath::select(&device, Some(UniformPath::Push)).unwrap_err().to_string();
+        assert!(error.contains("PUSH_CONSTANTS"), "{}", error);
+    }
+
+    let Some((device, _)) = common::push_constant_gpu() else { return };
+    assert_eq!(UniformPath::select(&device, None).unwrap(), UniformPath::Push);
+    assert_eq!(UniformPath::select(&device, Some(UniformPath::Buffer)).unwrap(), UniformPath::Buffer);
+}
+
+// The same galaxy stepped with each path, bit for bit, with and without --compaction.
+#[test]
+fn both_paths_step_alike() {
+    let Some((device, queue)) = common::push_constant_gpu() else {
+        eprintln!("skipping: no adapter with push constants");
+        return;
+    };
+    for compaction in [false, true] {
+        let run = |uniform_path| {
+            let config = SimConfig { uniform_path: Some(uniform_path), compaction, ..config() };
+            let mut sim = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+            assert_eq!(sim.uniform_path(), uniform_path);
+            sim.set_mode(chronoturin::DilationMode::Chronoturin);
+            let mut stars = sim.step_and_read(3);
+            stars.extend(sim.step_and_read(2));
+            (stars, sim.read_state().unwrap())
+        };
+        let (buffer, buffer_state) = run(UniformPath::Buffer);
+        let (push, push_state) = run(UniformPath::Push);
+        assert_eq!(bytemuck::cast_slice::<_, u8>(&buffer), bytemuck::cast_slice::<_, u8>(&push), "compaction {}", compaction);
+        assert_eq!(bytemuck::bytes_of(&buffer_state), bytemuck::bytes_of(&push_state));
+    }
+}
+
+// As every_batched_step_binds_its_own_state, for pushed states.
+#[test]
+fn every_pushed_step_sees_its_own_state() {
+    let Some((device, queue)) = common::push_constant_gpu() else { return };
+    let config = SimConfig { uniform_path: Some(UniformPath::Push), ..config() };
+    let mut sim = Simulation::new(device, queue, &config).unwrap();
+    let source = include_str!("../src/shader.wgsl");
+    let header = "fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {";
+    let recorder = format!(
+        "{}\n    if (global_id.x == 0u) {{ stars0[u32(round(state.time_seed / state.dt)) - 1u].time_debt = state.time_seed; }}\n    if (true) {{ return; }}\n",
+        header
+    );
+    pollster::block_on(sim.reload_kernels(&source.replacen(header, &recorder, 1))).unwrap();
+
+    let stars = sim.step_and_read(5);
+    let mut time_seed = 0.0f32;
+    for (k, star) in stars.iter().take(5).enumerate() {
+        time_seed += config.dt;
+        assert_eq!(star.time_debt, time_seed, "step {} saw another step's state", k);
+    }
+}
+
+#[test]
+fn the_path_is_a_flag_bench_can_force() {
+    assert_eq!(parse(&[]).uniform_path, None);
+    assert_eq!(parse(&["--uniform-path", "push"]).uniform_path, Some(UniformPath::Push));
+    let Some(Command::Bench(args)) = parse(&["bench", "--uniform-path", "buffer"]).command else { panic!("not a bench") };
+    assert_eq!(args.uniform_path, Some(UniformPath::Buffer));
+    let error = parse(&["--cpu", "--uniform-path", "buffer"]).validate(&cpu_reference::limits()).unwrap_err().to_string();
+    assert!(error.contains("drop --cpu"), "{}", error);
+}
+
+#[test]
+fn the_manifest_records_the_chosen_path() {
+    let manifest = RunManifest { uniform_path: Some(UniformPath::Push), ..RunManifest::new(&config()) };
+    let json: serde_json::Value = serde_json::from_str(&manifest.to_json()).unwrap();
+    assert_eq!(json["uniform_path"], "push");
+    assert_eq!(RunManifest::from_json(&manifest.to_json()).unwrap(), manifest);
+    assert!(manifest.describe().contains("GalaxyState by push constants"));
+    // CPU runs, and manifests from before the field, leave it out.
+    assert!(!RunManifest::new(&config()).to_json().contains("uniform_path"));
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use chronoturin::boundary::{parse_boundary, wrap};
+use chronoturin::{
+    cpu_reference, render_frame, Boundary, Camera, CpuSimulation, DilationMode, RenderSettings, SimConfig, Simulation, Star, TYPE_A,
+};
+
+use common::parse;
+
+// A hot sphere whose fastest stars reach the wall within a few dozen steps.
+fn boxed() -> SimConfig {
+    parse(&["--stars", "400", "--seed", "5", "--scale-radius", "380", "--velocity-dispersion", "40", "--boundary", "reflect:400"])
+}
+
+fn radius(star: &Star) -> f32 {
+    (star.x * star.x + star.y * star.y + star.z * star.z).sqrt()
+}
+
+fn speed(star: &Star) -> f32 {
+    (star.vx * star.vx + star.vy * star.vy + star.vz * star.vz).sqrt()
+}
+
+// Every readback of a 100-step run stays inside the sphere plus one step's travel, and some
+// star did get there.
+fn assert_contained(mut step: impl FnMut() -> Vec<Star>, dt: f32) {
+    let mut previous = step();
+    let mut bounced = false;
+    for _ in 1..100 {
+        let stars = step();
+        for (before, after) in previous.iter().zip(&stars) {
+            let travel = speed(before).max(speed(after)) * dt;
+            assert!(radius(after) <= 400.0 + travel + 1e-3, "{:?} left the box", after);
+            bounced |= radius(after) > 390.0;
+        }
+        previous = stars;
+    }
+    assert!(bounced, "no star reached the wall");
+}
+
+#[test]
+fn boundaries_parse_with_their_sizes() {
+    assert_eq!(parse_boundary("open"), Ok(Boundary::Open));
+    assert_eq!(parse_boundary("reflect:400"), Ok(Boundary::Reflect { radius: 400.0 }));
+    assert_eq!(parse_boundary("periodic:800"), Ok(Boundary::Periodic { side: 800.0 }));
+    assert!(parse_boundary("reflect").unwrap_err().contains("needs a size"));
+    assert!(parse_boundary("periodic:-1").is_err());
+    assert!(parse_boundary("open:3").is_err());
+    assert!(parse_boundary("torus:3").is_err());
+
+    let limits = cpu_reference::limits();
+    assert!(boxed().validate(&limits).is_ok());
+    assert!(parse(&["--boundary", "periodic:800", "--accel", "grid"]).validate(&limits).is_err());
+    assert!(parse(&["--boundary", "periodic:800", "--accel", "sampled"]).validate(&limits).is_ok());
+    let state = chronoturin::GalaxyState::new(0.0, 0.0, &parse(&["--boundary", "periodic:800"]));
+    assert_eq!((state.boundary_kind, state.boundary_size), (2.0, 800.0));
+}
+
+#[test]
+fn the_default_camera_frames_the_boundary() {
+    assert_eq!(parse(&[]).camera_z(), -1000.0);
+    assert_eq!(parse(&["--boundary", "reflect:400", "--camera-z", "-50"]).camera_z(), -50.0);
+    // 400 to the sphere's front plus 1.1 * 400 at a focal length of 800 over half of 600 rows.
+    let reflect = parse(&["--boundary", "reflect:400", "--width", "800", "--height", "600", "--fov", "800"]);
+    assert!((reflect.camera_z() + (400.0 + 1.1 * 400.0 * 800.0 / 300.0)).abs() < 1e-2);
+    let periodic = parse(&["--boundary", "periodic:400", "--width", "800", "--height", "600", "--fov", "800"]);
+    assert!(periodic.camera_z() > reflect.camera_z() && periodic.camera_z() < -200.0);
+}
+
+#[test]
+fn a_reflecting_sphere_holds_every_star() {
+    let config = boxed();
+    let mut cpu = CpuSimulation::new(&config);
+    cpu.set_mode(DilationMode::Newtonian);
+    assert_contained(|| cpu.step_and_read(1), config.dt);
+}
+
+#[test]
+fn the_gpu_reflects_like_the_cpu() {
+    let (device, queue) = require_gpu!();
+    let config = boxed();
+    let mut gpu = Simulation::new(device, queue, &config).unwrap();
+    gpu.set_mode(DilationMode::Newtonian);
+    assert_contained(|| gpu.step_and_read(1), config.dt);
+}
+
+#[test]
+fn periodic_stars_wrap_into_the_cube() {
+    let config = parse(&["--stars", "300", "--seed", "2", "--velocity-dispersion", "60", "--boundary", "periodic:300"]);
+    let mut cpu = CpuSimulation::new(&config);
+    cpu.set_mode(DilationMode::Newtonian);
+    let stars = cpu.step_and_read(50);
+    assert!(stars.iter().all(|s| [s.x, s.y, s.z].iter().all(|x| x.abs() <= 150.0)));
+
+    let star =
+        Star { x: 170.0, y: -160.0, z: 20.0, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type: TYPE_A, time_debt: 0.0, active_flag: 1.0 };
+    let wrapped = wrap(&star, 300.0);
+    assert_eq!((wrapped.x, wrapped.y, wrapped.z), (-130.0, 140.0, 20.0));
+
+    // The renderer draws a star that has not moved since it left at its wrapped image.
+    let cam = Camera::look_at([0.0, 0.0, -600.0], [0.0; 3], [0.0, 1.0, 0.0], 64, 64, 100.0);
+    let settings = RenderSettings { wrap: Some(300.0), ..RenderSettings::legacy() };
+    assert_eq!(render_frame(&[star], &cam, &settings, false), render_frame(&[wrapped], &cam, &settings, false));
+    assert_ne!(
+        render_frame(&[star], &cam, &RenderSettings::legacy(), false),
+        render_frame(&[wrapped], &cam, &RenderSettings::legacy(), false)
+    );
+}
@@ -0,0 +1,122 @@
+#![cfg(feature = "capi")]
+
+mod common;
+
+use std::ffi::{c_char, CStr, CString};
+use std::path::PathBuf;
+use std::ptr;
+
+use bytemuck::Zeroable;
+
+use chronoturin::capi::*;
+use chronoturin::{DilationMode, SimConfig, Simulation, Star};
+
+// Rewrites include/chronoturin.h instead of comparing, after a change to the API.
+const BLESS: &str = "CHRONOTURIN_BLESS";
+
+fn last_error() -> (i32, String) {
+    let message = unsafe { CStr::from_ptr(chrono_sim_last_error_message()) };
+    (chrono_sim_last_error(), message.to_str().unwrap().to_string())
+}
+
+// argv for a ChronoConfig; the strings live as long as the returned Vec of them.
+fn args(flags: &[&str]) -> (Vec<CString>, Vec<*const c_char>) {
+    let strings: Vec<CString> = flags.iter().map(|f| CString::new(*f).unwrap()).collect();
+    let pointers = strings.iter().map(|s| s.as_ptr()).collect();
+    (strings, pointers)
+}
+
+#[test]
+fn the_header_is_what_cbindgen_makes_of_the_api() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(root.join("cbindgen.toml")).unwrap();
+    let mut header = Vec::new();
+    let builder = cbindgen::Builder::new().with_config(config).with_src(root.join("src/capi.rs")).with_src(root.join("src/galaxy.rs"));
+    builder.generate().unwrap().write(&mut header);
+    let path = root.join("include/chronoturin.h");
+    if std::env::var_os(BLESS).is_some() {
+        std::fs::write(&path, header).unwrap();
+        eprintln!("blessed {}", path.display());
+        return;
+    }
+    let committed = std::fs::read(&path).unwrap_or_default();
+    assert!(committed == header, "include/chronoturin.h is stale; regenerate it with {}=1 cargo test --test capi", BLESS);
+}
+
+#[test]
+fn failures_come_back_as_codes_and_messages() {
+    unsafe {
+        assert!(chrono_sim_create(ptr::null()).is_null());
+        assert_eq!(last_error(), (CHRONO_ERROR_NULL, "config is NULL".into()));
+
+        let (_strings, argv) = args(&["--integrator", "sideways"]);
+        let config = ChronoConfig { argc: argv.len(), argv: argv.as_ptr(), ..chrono_config_default() };
+        assert!(chrono_sim_create(&config).is_null());
+        let (code, message) = last_error();
+        assert_eq!(code, CHRONO_ERROR_CONFIG);
+        assert!(message.contains("sideways"), "{}", message);
+        let config = ChronoConfig { argc: 1, argv: ptr::null(), ..chrono_config_default() };
+        assert!(chrono_sim_create(&config).is_null());
+        assert_eq!(last_error().0, CHRONO_ERROR_NULL);
+        let config = ChronoConfig { mode: 7, ..chrono_config_default() };
+        assert!(chrono_sim_create(&config).is_null());
+        assert_eq!(last_error().0, CHRONO_ERROR_CONFIG);
+
+        assert_eq!(chrono_sim_step(ptr::null_mut(), 1), CHRONO_ERROR_NULL);
+        assert_eq!(chrono_sim_set_mode(ptr::null_mut(), CHRONO_MODE_NEWTONIAN), CHRONO_ERROR_NULL);
+        assert_eq!(chrono_sim_read(ptr::null_mut(), ptr::null_mut(), 0), 0);
+        assert_eq!(last_error().0, CHRONO_ERROR_NULL);
+        assert_eq!(chrono_sim_star_count(ptr::null_mut()), 0);
+        // Every call starts afresh: destroying NULL succeeds and clears the last error.
+        chrono_sim_destroy(ptr::null_mut());
+        assert_eq!(last_error(), (CHRONO_OK, String::new()));
+    }
+    let defaults = chrono_config_default();
+    assert_eq!((defaults.stars, defaults.seed, defaults.mode, defaults.argc), (SimConfig::default().stars, 0, CHRONO_MODE_CHRONOTURIN, 0));
+}
+
+#[test]
+fn a_simulation_steps_reads_and_switches_mode_like_the_library() {
+    let _gpu = require_gpu!();
+    let flags = ["--integrator", "leapfrog"];
+    let (_strings, argv) = args(&flags);
+    let config = ChronoConfig { stars: 500, seed: 3, mode: CHRONO_MODE_NEWTONIAN, argc: argv.len(), argv: argv.as_ptr() };
+    let library_config = SimConfig { stars: 500, seed: Some(3), ..common::parse(&flags) };
+    let mut library = Simulation::open(&library_config, DilationMode::Newtonian).unwrap();
+    let bits = |stars: &[Star]| bytemuck::cast_slice::<Star, u8>(stars).to_vec();
+
+    unsafe {
+        let sim = chrono_sim_create(&config);
+        assert!(!sim.is_null(), "{:?}", last_error());
+        assert_eq!(chrono_sim_star_count(sim), 500);
+
+        // The seed's galaxy, into a larger buffer whose tail is left alone, or the first
+        // stars of it into a smaller one.
+        let mut stars = vec![Star::zeroed(); 600];
+        assert_eq!(chrono_sim_read(sim, stars.as_mut_ptr(), stars.len()), 500);
+        assert_eq!(bits(&stars[..500]), bits(library.initial_stars()));
+        assert!(stars[500..].iter().all(|s| bytemuck::bytes_of(s).iter().all(|&b| b == 0)));
+        let mut first = vec![Star::zeroed(); 10];
+        assert_eq!(chrono_sim_read(sim, first.as_mut_ptr(), first.len()), 10);
+        assert_eq!(bits(&first), bits(&stars[..10]));
+        assert_eq!(chrono_sim_read(sim, ptr::null_mut(), 1), 0);
+        assert_eq!(last_error().0, CHRONO_ERROR_NULL);
+
+        // Stepped, it is the library's simulation of the same flags, to the bit.
+        assert_eq!(chrono_sim_step(sim, 20), CHRONO_OK);
+        library.step(20);
+        assert_eq!(chrono_sim_read(sim, stars.as_mut_ptr(), 500), 500);
+        assert_eq!(bits(&stars[..500]), bits(&library.read_stars()));
+
+        assert_eq!(chrono_sim_set_mode(sim, 2), CHRONO_ERROR_CONFIG);
+        assert!(last_error().1.contains("mode 2"));
+        assert_eq!(chrono_sim_set_mode(sim, CHRONO_MODE_CHRONOTURIN), CHRONO_OK);
+        library.set_mode(DilationMode::Chronoturin);
+        assert_eq!(chrono_sim_step(sim, 20), CHRONO_OK);
+        library.step(20);
+        assert_eq!(last_error(), (CHRONO_OK, String::new()));
+        assert_eq!(chrono_sim_read(sim, stars.as_mut_ptr(), 500), 500);
+        assert_eq!(bits(&stars[..500]), bits(&library.read_stars()));
+        chrono_sim_destroy(sim);
+    }
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::config_file::to_toml;
+use chronoturin::galaxy::clear_dilation;
+use chronoturin::{cpu_reference, CpuSimulation, DilationMode, GalaxyState, SimConfig, Stepper, DEAD_FLAG};
+
+use common::parse_command;
+
+#[test]
+fn chaining_keeps_motion_but_not_dilation() {
+    let mut cpu = CpuSimulation::new(&SimConfig { stars: 64, seed: Some(5), ..SimConfig::default() });
+    Stepper::set_mode(&mut cpu, DilationMode::Chronoturin);
+    let ticket = cpu.submit_frame(4);
+    let first = cpu.finish_frame(ticket);
+    let mut stars = first.stars.clone();
+    stars[1].active_flag = DEAD_FLAG;
+    clear_dilation(&mut stars);
+    assert!(stars.iter().all(|s| s.time_debt == 0.0));
+    assert!(stars.iter().enumerate().all(|(i, s)| s.active_flag == if i == 1 { DEAD_FLAG } else { 0.0 }));
+    assert!(stars.iter().zip(&first.stars).all(|(a, b)| (a.x, a.y, a.z, a.vx, a.vy, a.vz) == (b.x, b.y, b.z, b.vx, b.vy, b.vz)));
+
+    // The second pass's clock carries on from the first's.
+    let config = SimConfig::default();
+    let mut second = CpuSimulation::with_stars(&config, stars.clone());
+    second.restore(GalaxyState::new(first.sim_time, DilationMode::Newtonian.as_uniform(), &config), &stars);
+    let ticket = second.submit_frame(4);
+    assert!((second.finish_frame(ticket).sim_time - 8.0 * chronoturin::DT).abs() < 1e-6);
+}
+
+#[test]
+fn chain_runs_the_passes_in_order() {
+    assert_eq!(parse_command(&["--chain"]).chain.unwrap().modes(), [DilationMode::Newtonian, DilationMode::Chronoturin]);
+    assert_eq!(parse_command(&["--chain", "chrono-first"]).chain.unwrap().modes(), [DilationMode::Chronoturin, DilationMode::Newtonian]);
+    assert!(parse_command(&["--chain"]).sequential);
+    assert!(to_toml(&parse_command(&["--chain"])).contains("\nchain = \"newton-first\"\n"));
+    assert!(SimConfig::try_parse_from(["chronoturin", "--chain-from", "a.csv"]).is_err());
+}
+
+#[test]
+fn chain_conflicts_are_rejected() {
+    let check = |args: &[&str]| parse_command(args).validate(&cpu_reference::limits()).map_err(|e| e.to_string());
+    assert!(check(&["--chain"]).is_ok());
+    assert!(check(&["--chain", "--chain-from", "checkpoint.bin"]).is_ok());
+    assert!(check(&["--chain", "--diff"]).unwrap_err().starts_with("--chain runs one pass after the other"));
+    assert!(check(&["--chain", "--mode-schedule", "0:newton"]).is_err());
+    assert!(check(&["--chain", "--resume", "checkpoint.bin"]).unwrap_err().contains("--chain-from"));
+    assert!(check(&["--chain", "--chain-from", "a.csv", "--initial-conditions", "b.csv"]).is_err());
+    assert!(check(&["--chain", "--readback", "packed"]).unwrap_err().contains("--readback full"));
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::path::PathBuf;
+
+use chronoturin::{generate_galaxy, Checkpoint, CheckpointRun, ChronoError, DilationMode, GalaxyState, RunManifest, SimConfig, Simulation};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chronoturin-{}-{}.bin", name, std::process::id()))
+}
+
+fn checkpoint(stars: u32) -> Checkpoint {
+    let state = GalaxyState::new(1.25, 1.0, &SimConfig::default());
+    Checkpoint {
+        seed: 99,
+        pass: 1,
+        next_frame: 25,
+        manifest: None,
+        runs: vec![CheckpointRun { mode: DilationMode::Chronoturin, state, stars: generate_galaxy(stars, 3) }],
+    }
+}
+
+#[test]
+fn round_trip_preserves_every_field() {
+    let path = temp_path("ckpt-roundtrip");
+    let manifest = RunManifest::new(&SimConfig { stars: 500, ..SimConfig::default() });
+    let original = Checkpoint { manifest: Some(manifest.clone()), ..checkpoint(500) };
+    original.save(&path).unwrap();
+    let loaded = Checkpoint::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!((loaded.seed, loaded.pass, loaded.next_frame), (99, 1, 25));
+    assert_eq!(loaded.manifest, Some(manifest));
+    let (a, b) = (&original.runs[0], &loaded.runs[0]);
+    assert_eq!(b.mode, DilationMode::Chronoturin);
+    assert_eq!(bytemuck::bytes_of(&a.state), bytemuck::bytes_of(&b.state));
+    assert_eq!(bytemuck::cast_slice::<_, u8>(&a.stars), bytemuck::cast_slice::<_, u8>(&b.stars));
+}
+
+#[test]
+fn corrupt_files_are_rejected() {
+    let path = temp_path("ckpt-corrupt");
+    checkpoint(10).save(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+
+    std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+    let err = Checkpoint::load(&path).unwrap_err();
+    assert!(matches!(err, ChronoError::Checkpoint { .. }) && err.to_string().contains("truncated"), "{}", err);
+    std::fs::write(&path, [&bytes[..], &[0]].concat()).unwrap();
+    assert!(matches!(Checkpoint::load(&path), Err(ChronoError::Checkpoint { .. })));
+
+    let mut wrong_version = bytes.clone();
+    wrong_version[8] = 200;
+    std::fs::write(&path, &wrong_version).unwrap();
+    let err = Checkpoint::load(&path).unwrap_err();
+    assert!(matches!(err, ChronoError::Checkpoint { .. }) && err.to_string().contains("version 200"), "{}", err);
+
+    std::fs::write(&path, b"not a checkpoint").unwrap();
+    assert!(Checkpoint::load(&path).unwrap_err().to_string().contains("header"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn star_count_mismatch_is_a_hard_error() {
+    let config = SimConfig { stars: 400, frames: 50, sequential: true, ..SimConfig::default() };
+    let err = checkpoint(500).check_compatible(&config).unwrap_err().to_string();
+    assert!(err.contains("500 stars") && err.contains("--stars is 400"), "{}", err);
+    assert!(checkpoint(500).check_compatible(&SimConfig { stars: 500, ..config.clone() }).is_ok());
+    // The central mass is the checkpoint's extra star, left out of the count it suggests.
+    let err = checkpoint(501).check_compatible(&SimConfig { central_mass: Some(1000.0), ..config }).unwrap_err().to_string();
+    assert!(err.contains("holds 500 stars") && err.contains("--stars 500 to resume"), "{}", err);
+}
+
+#[test]
+fn restored_simulation_continues_exactly() {
+    let (device, queue) = require_gpu!();
+    let config = SimConfig { stars: 1000, seed: Some(7), ..SimConfig::default() };
+    let mut straight = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+    straight.set_mode(DilationMode::Chronoturin);
+    let ticket = straight.submit_frame(5);
+    let halfway = straight.finish_frame(ticket);
+    let expected = straight.step_and_read(5);
+
+    let mut resumed = Simulation::new(device, queue, &config).unwrap();
+    // Only the clock and mode are taken from the checkpoint; the physics come from the config.
+    let state = GalaxyState { time_seed: halfway.sim_time, dilation_mode: 1.0, ..bytemuck::Zeroable::zeroed() };
+    resumed.restore(state, &halfway.stars);
+    assert_eq!(resumed.sim_time(), halfway.sim_time);
+    let actual = resumed.step_and_read(5);
+    assert_eq!(bytemuck::cast_slice::<_, u8>(&actual), bytemuck::cast_slice::<_, u8>(&expected));
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use chronoturin::chunks::MAX_CHUNKS;
+use chronoturin::{ChronoError, ChunkLayout, DilationMode, SimConfig, Simulation, Star};
+
+fn limits(binding_stars: u32, workgroups: u32) -> wgpu::Limits {
+    let bytes = binding_stars * std::mem::size_of::<Star>() as u32;
+    wgpu::Limits {
+        max_storage_buffer_binding_size: bytes,
+        max_buffer_size: bytes as u64,
+        max_compute_workgroups_per_dimension: workgroups,
+        ..wgpu::Limits::default()
+    }
+}
+
+#[test]
+fn chunks_fit_the_binding_and_the_dispatch_in_whole_workgroups() {
+    let layout = ChunkLayout::new(2500, 256, None, &limits(1000, 65535)).unwrap();
+    assert_eq!((layout.chunk_stars, layout.count()), (768, 4));
+    assert_eq!(layout.ranges().collect::<Vec<_>>(), [0..768, 768..1536, 1536..2304, 2304..2500]);
+    // Two workgroups per dispatch cap the chunk below what the binding holds.
+    assert_eq!(ChunkLayout::new(2000, 256, None, &limits(1000, 2)).unwrap().chunk_stars, 512);
+    assert_eq!(ChunkLayout::new(100, 64, Some(30), &limits(1000, 65535)).unwrap().count(), 4);
+    assert_eq!(ChunkLayout::new(100, 64, None, &wgpu::Limits::default()).unwrap().count(), 1);
+}
+
+#[test]
+fn too_many_chunks_is_an_honest_error() {
+    let err = ChunkLayout::new(5000, 256, None, &limits(1000, 65535)).unwrap_err();
+    assert!(matches!(err, ChronoError::BufferTooLarge { stars: 5000, .. }), "{:?}", err);
+    assert!(err.to_string().contains(&format!("across {} star buffers", MAX_CHUNKS)), "{}", err);
+    let config = SimConfig { stars: 5000, ..SimConfig::default() };
+    assert!(config.validate(&limits(1000, 65535)).is_err());
+    assert!(config.validate(&limits(2000, 65535)).is_ok());
+    assert!(SimConfig { chunk_stars: Some(0), ..config }.validate(&wgpu::Limits::default()).is_err());
+}
+
+#[test]
+fn chunked_runs_match_a_single_buffer() {
+    let (device, queue) = require_gpu!();
+    let whole = SimConfig { stars: 1000, seed: Some(3), gpu_diagnostics: true, ..SimConfig::default() };
+    let chunked = SimConfig { chunk_stars: Some(256), ..whole.clone() };
+    let mut a = Simulation::new(device.clone(), queue.clone(), &whole).unwrap();
+    let mut b = Simulation::new(device, queue, &chunked).unwrap();
+    assert_eq!((a.chunk_count(), b.chunk_count()), (1, 4));
+    assert_eq!(b.star_buffer_size(), a.star_buffer_size());
+    let read = b.read_stars();
+    assert_eq!(bytemuck::cast_slice::<Star, u8>(&read), bytemuck::cast_slice::<Star, u8>(b.initial_stars()));
+
+    let run = |sim: &mut Simulation| {
+        sim.set_mode(DilationMode::Chronoturin);
+        let ticket = sim.submit_frame(4);
+        sim.finish_frame(ticket)
+    };
+    let (a, b) = (run(&mut a), run(&mut b));
+    // Partners read mid-step may race, so compare to within rounding rather than bitwise.
+    let err = a.stars.iter().zip(&b.stars).map(|(a, b)| (a.x - b.x).abs().max((a.y - b.y).abs())).fold(0.0, f32::max);
+    assert!(err < 1e-3, "max position error {}", err);
+    assert_eq!(a.active_updates, b.active_updates);
+    let (ma, mb) = (a.moments.unwrap(), b.moments.unwrap());
+    assert!((ma.mass - mb.mass).abs() < 1e-3 && (ma.kinetic - mb.kinetic).abs() < 1e-3 * ma.kinetic.abs().max(1.0));
+}
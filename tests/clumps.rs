@@ -0,0 +1,184 @@
+mod common;
+
+use std::path::PathBuf;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use chronoturin::render::{group_color, ASLEEP_COLOR};
+use chronoturin::{
+    cpu_reference, presets, write_catalog, Camera, Clumps, DilationMode, FofSettings, Framebuffer, OutputLayout, RenderSettings, SimConfig,
+    Star,
+};
+
+use common::parse;
+
+fn star(x: f32, y: f32, z: f32, v: [f32; 3]) -> Star {
+    Star { x, y, z, vx: v[0], vy: v[1], vz: v[2], ..common::star() }
+}
+
+// A side³ lattice of unit spacing with its corner at `corner`, all moving at `v`.
+fn lattice(corner: [f32; 3], side: usize, v: [f32; 3]) -> Vec<Star> {
+    let mut stars = Vec::new();
+    for i in 0..side * side * side {
+        let (x, y, z) = ((i % side) as f32, (i / side % side) as f32, (i / side / side) as f32);
+        stars.push(star(corner[0] + x, corner[1] + y, corner[2] + z, v));
+    }
+    stars
+}
+
+const SETTINGS: FofSettings = FofSettings { linking_length: 1.5, min_members: 10 };
+
+#[test]
+fn clustered_stars_form_one_group_each_and_stragglers_are_noise() {
+    let mut stars = vec![presets::central_star(1000.0)];
+    stars.extend(lattice([-50.0, 20.0, 0.0], 3, [0.0, -2.0, 0.0]));
+    stars.extend(lattice([100.0, 0.0, 0.0], 4, [1.0, 0.0, 0.0]));
+    // Five in a row: friends, but too few to be a clump. Then loners far from everything.
+    stars.extend((0..5).map(|i| star(0.0, -80.0 + i as f32, 0.0, [0.0; 3])));
+    stars.extend((0..4).map(|i| star(300.0 + 10.0 * i as f32, 0.0, 0.0, [0.0; 3])));
+    stars[1 + 27 + 5].mass = 3.0;
+
+    let clumps = Clumps::find(&stars, &SETTINGS);
+    assert_eq!(clumps.groups.len(), 2);
+    // Largest first, with the mass-weighted center and bulk velocity.
+    let big = clumps.groups[0];
+    assert_eq!((big.members, big.mass), (64, 66.0));
+    let expected_x = (64.0 * 101.5 + 2.0 * (100.0 + 1.0)) / 66.0;
+    assert!((big.center[0] - expected_x).abs() < 1e-9, "{:?}", big.center);
+    assert_eq!(big.velocity, [1.0, 0.0, 0.0]);
+    let small = clumps.groups[1];
+    assert_eq!((small.members, small.mass, small.center), (27, 27.0, [-49.0, 21.0, 1.0]));
+    assert_eq!(small.velocity, [0.0, -2.0, 0.0]);
+
+    assert_eq!(clumps.labels.len(), stars.len());
+    assert_eq!(clumps.labels[0], None, "central masses join no group");
+    assert!(clumps.labels[1..28].iter().all(|&l| l == Some(1)));
+    assert!(clumps.labels[28..92].iter().all(|&l| l == Some(0)));
+    assert!(clumps.labels[92..].iter().all(|&l| l.is_none()));
+
+    // With a lower bar the row of five counts too.
+    let loose = Clumps::find(&stars, &FofSettings { min_members: 5, ..SETTINGS });
+    assert_eq!(loose.groups.iter().map(|g| g.members).collect::<Vec<_>>(), [64, 27, 5]);
+}
+
+#[test]
+fn friends_of_friends_chain_beyond_the_linking_length() {
+    // Twelve stars a unit apart: the ends are 11 apart, but every neighbour is a friend.
+    let line: Vec<Star> = (0..12).map(|i| star(i as f32, 0.0, 0.0, [0.0; 3])).collect();
+    assert_eq!(Clumps::find(&line, &SETTINGS).groups.len(), 1);
+    // Pulled apart in the middle, they are two groups of six, too small for the default.
+    let broken: Vec<Star> = line.iter().enumerate().map(|(i, s)| Star { x: s.x + if i >= 6 { 5.0 } else { 0.0 }, ..*s }).collect();
+    let split = Clumps::find(&broken, &FofSettings { min_members: 6, ..SETTINGS });
+    assert_eq!(split.groups.iter().map(|g| g.members).collect::<Vec<_>>(), [6, 6]);
+    assert_eq!((split.labels[0], split.labels[11]), (Some(0), Some(1)));
+    assert!(Clumps::find(&broken, &SETTINGS).groups.is_empty());
+    // Negative coordinates land in their own cells, not folded onto the positive ones.
+    let mirrored: Vec<Star> = line.iter().map(|s| Star { x: -s.x - 0.5, ..*s }).collect();
+    assert_eq!(Clumps::find(&mirrored, &SETTINGS).groups[0].members, 12);
+}
+
+#[test]
+fn the_grid_finds_the_same_groups_as_comparing_every_pair() {
+    let mut rng = StdRng::seed_from_u64(95);
+    let mut stars: Vec<Star> =
+        (0..1500).map(|_| star(rng.gen_range(-60.0..60.0), rng.gen_range(-60.0..60.0), rng.gen_range(-10.0..10.0), [0.0; 3])).collect();
+    // And some dense knots, so there are groups to find.
+    for _ in 0..6 {
+        let c: [f32; 3] = std::array::from_fn(|_| rng.gen_range(-50.0..50.0));
+        stars
+            .extend((0..40).map(|_| {
+                star(c[0] + rng.gen_range(-2.0..2.0), c[1] + rng.gen_range(-2.0..2.0), c[2] + rng.gen_range(-2.0..2.0), [0.0; 3])
+            }));
+    }
+    let settings = FofSettings { linking_length: 2.5, min_members: 3 };
+    let clumps = Clumps::find(&stars, &settings);
+    assert!(clumps.groups.len() >= 6, "{}", clumps.groups.len());
+
+    // Every pair, by brute force: two stars share a label exactly when they share a group.
+    let mut parent: Vec<usize> = (0..stars.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            i = parent[i];
+        }
+        i
+    }
+    let link = settings.linking_length as f64;
+    for i in 0..stars.len() {
+        for j in i + 1..stars.len() {
+            let d: f64 =
+                [stars[i].x - stars[j].x, stars[i].y - stars[j].y, stars[i].z - stars[j].z].iter().map(|&c| (c as f64).powi(2)).sum();
+            if d <= link * link {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+    }
+    let roots: Vec<usize> = (0..stars.len()).map(|i| root(&mut parent, i)).collect();
+    let sizes = |r: usize| roots.iter().filter(|&&x| x == r).count();
+    for i in 0..stars.len() {
+        assert_eq!(clumps.labels[i].is_some(), sizes(roots[i]) >= settings.min_members, "star {}", i);
+        for j in (i + 1..stars.len()).step_by(37) {
+            if clumps.labels[i].is_some() {
+                assert_eq!(clumps.labels[i] == clumps.labels[j], roots[i] == roots[j], "stars {} and {}", i, j);
+            }
+        }
+    }
+}
+
+#[test]
+fn catalogs_are_written_per_analyzed_frame() {
+    let mut stars = lattice([0.0; 3], 3, [0.5, 0.0, -0.25]);
+    stars.extend(lattice([40.0, 0.0, 0.0], 2, [0.0; 3]));
+    let clumps = Clumps::find(&stars, &FofSettings { linking_length: 1.5, min_members: 8 });
+    let path = std::env::temp_dir().join(format!("chronoturin-clumps-{}.csv", std::process::id()));
+    write_catalog(&path, &clumps).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    let rows: Vec<&str> = text.lines().collect();
+    assert_eq!(
+        rows,
+        [
+            "group,members,mass,x,y,z,vx,vy,vz",
+            "0,27,27,1.0000,1.0000,1.0000,0.50000,0.00000,-0.25000",
+            "1,8,8,40.5000,0.5000,0.5000,0.00000,0.00000,0.00000",
+        ]
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    let limits = cpu_reference::limits();
+    assert!(parse(&["--fof-every", "5", "--linking-length", "2"]).validate(&limits).is_ok());
+    assert!(parse(&["--fof-every", "0"]).validate(&limits).is_err());
+    assert!(parse(&["--fof-every", "5", "--linking-length", "0"]).validate(&limits).is_err());
+    assert!(parse(&["--fof-every", "5", "--fof-min-members", "0"]).validate(&limits).is_err());
+    assert!(parse(&["--fof-every", "5", "--readback", "packed"]).validate(&limits).is_err());
+    assert!(parse(&["--fof-every", "5", "bench"]).validate(&limits).is_err());
+    assert!(parse(&["--fof-color"]).validate(&limits).is_err());
+    assert!(parse(&["--fof-every", "5", "--fof-color", "--raster", "gpu"]).validate(&limits).is_err());
+    // Without --linking-length, a fifth of the mean spacing within ten scale radii.
+    let settings = FofSettings::from_config(&parse(&["--stars", "1000", "--scale-radius", "10"]));
+    let spacing = (4.0 / 3.0 * std::f32::consts::PI * 1.0e6 / 1000.0f32).cbrt();
+    assert!((settings.linking_length - 0.2 * spacing).abs() < 1e-4);
+    assert_eq!(settings.min_members, 10);
+
+    let layout = OutputLayout::prepare(&SimConfig::default()).unwrap();
+    assert_eq!(layout.clump_path(DilationMode::Chronoturin, 40), PathBuf::from("clumps_chrono_040.csv"));
+}
+
+#[test]
+fn fof_color_draws_each_group_in_its_own_colour() {
+    assert_eq!(group_color(None), ASLEEP_COLOR);
+    assert_eq!(group_color(Some(0)), [255.0, 0.0, 0.0]);
+    let colors: Vec<[f32; 3]> = (0..8).map(|g| group_color(Some(g))).collect();
+    for (i, a) in colors.iter().enumerate() {
+        assert!(colors[i + 1..].iter().all(|b| b != a), "group {} repeats", i);
+    }
+
+    let cam = Camera::look_at([0.0, 0.0, -100.0], [0.0; 3], [0.0, 1.0, 0.0], 64, 64, 64.0);
+    let settings = RenderSettings::legacy();
+    let stars = [star(0.0, 0.0, 0.0, [0.0; 3]), star(20.0, 0.0, 0.0, [0.0; 3])];
+    let mut fb = Framebuffer::new(64, 64);
+    fb.accumulate_groups(&stars, &cam, &settings, &[Some(0), None]);
+    let img = fb.tonemap(&settings, false);
+    assert_eq!(img.get_pixel(32, 32).0, [255, 0, 0]);
+    assert_eq!(img.get_pixel(44, 32).0, ASLEEP_COLOR.map(|c| c as u8));
+}
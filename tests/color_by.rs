@@ -0,0 +1,72 @@
+mod common;
+
+use image::RgbImage;
+
+use chronoturin::{cpu_reference, draw_legend, project, render_frame, Camera, ColorBy, RasterChoice, RenderSettings, Star};
+
+use common::parse;
+
+fn camera() -> Camera {
+    Camera::look_at([0.0, 0.0, -1000.0], [0.0; 3], [0.0, 1.0, 0.0], 64, 64, 800.0)
+}
+
+fn star(x: f32, time_debt: f32, active_flag: f32) -> Star {
+    Star { x, vx: x / 10.0, time_debt, active_flag, ..common::star() }
+}
+
+fn pixel(img: &RgbImage, star: &Star) -> [u8; 3] {
+    let (x, y) = project(star, &camera()).unwrap();
+    img.get_pixel(x, y).0
+}
+
+#[test]
+fn ramps_span_the_frame_unless_pinned() {
+    let stars = [star(-20.0, 0.0, 0.0), star(0.0, 5.0, 1.0), star(20.0, 10.0, 0.0)];
+    let debt = RenderSettings { color_by: ColorBy::Debt, ..RenderSettings::legacy() };
+    assert_eq!(debt.color_range(&stars), Some((0.0, 10.0)));
+    let img = render_frame(&stars, &camera(), &debt, true);
+    // Black to gold, with no active glow on top.
+    assert_eq!(stars.map(|s| pixel(&img, &s)), [[0, 0, 0], [128, 100, 25], [255, 200, 50]]);
+
+    let pinned = RenderSettings { color_max: Some(20.0), ..debt };
+    assert_eq!(pinned.color_range(&stars), Some((0.0, 20.0)));
+    assert_eq!(pixel(&render_frame(&stars, &camera(), &pinned, true), &stars[2]), [128, 100, 25]);
+
+    let speed = RenderSettings { color_by: ColorBy::Speed, ..RenderSettings::legacy() };
+    let img = render_frame(&stars, &camera(), &speed, false);
+    assert_eq!([0, 2].map(|i| pixel(&img, &stars[i])), [[255, 0, 0], [255, 0, 0]]);
+    assert_eq!(pixel(&img, &stars[1]), [0, 0, 255]);
+
+    let active = RenderSettings { color_by: ColorBy::Active, ..RenderSettings::legacy() };
+    assert_eq!(active.color_range(&stars), None);
+    let img = render_frame(&stars, &camera(), &active, true);
+    assert_eq!([0, 1].map(|i| pixel(&img, &stars[i])), [[90, 90, 90], [255, 255, 255]]);
+}
+
+#[test]
+fn legend_sits_in_the_bottom_left_corner() {
+    let blank = RgbImage::from_pixel(256, 128, image::Rgb([100, 100, 100]));
+    let mut img = blank.clone();
+    draw_legend(&mut img, ColorBy::Type, None);
+    assert_eq!(img, blank);
+
+    draw_legend(&mut img, ColorBy::Debt, Some((0.0, 12.5)));
+    let changed = |x0: u32, y0: u32, x1: u32, y1: u32| (y0..y1).any(|y| (x0..x1).any(|x| img.get_pixel(x, y) != blank.get_pixel(x, y)));
+    assert!(changed(0, 110, 128, 128));
+    assert!(!changed(0, 0, 256, 100));
+    // The ramp runs from black to gold.
+    assert!(img.pixels().any(|p| p.0 == [255, 200, 50]) && img.pixels().any(|p| p.0 == [0, 0, 0]));
+}
+
+#[test]
+fn color_by_renders_on_the_cpu() {
+    let limits = cpu_reference::limits();
+    assert_eq!(parse(&[]).raster_choice(), RasterChoice::Gpu);
+    assert_eq!(parse(&["--color-by", "debt"]).raster_choice(), RasterChoice::Cpu);
+    assert!(parse(&["--color-by", "speed", "--raster", "gpu"]).validate(&limits).is_err());
+    assert!(parse(&["--color-by", "debt", "--readback", "packed"]).validate(&limits).is_err());
+    assert!(parse(&["--color-by", "active", "--readback", "packed"]).validate(&limits).is_ok());
+    assert!(parse(&["--color-by", "speed", "--color-min", "5", "--color-max", "5"]).validate(&limits).is_err());
+    assert!(parse(&["--color-by", "speed", "--color-min", "-1", "--color-max", "5"]).validate(&limits).is_ok());
+    assert_eq!(RenderSettings::from_config(&parse(&["--color-by", "speed", "--color-max", "8"])).color_max, Some(8.0));
+}
@@ -0,0 +1,108 @@
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use clap::Parser;
+
+use chronoturin::{DilationMode, MetricsRecord, SimConfig, Star, TYPE_A};
+
+// The flags after the binary name, as the command line would parse them.
+pub fn parse(args: &[&str]) -> SimConfig {
+    SimConfig::parse_from(["chronoturin"].iter().chain(args))
+}
+
+// parse(), with the subcommand's layout applied as main does before running it.
+pub fn parse_command(args: &[&str]) -> SimConfig {
+    let mut config = parse(args);
+    config.apply_command();
+    config
+}
+
+// The default galaxy, made small and reproducible.
+pub fn seeded(stars: u32, seed: u64) -> SimConfig {
+    SimConfig { stars, seed: Some(seed), ..SimConfig::default() }
+}
+
+// A unit-mass TYPE_A star at rest at the origin, owing no time; tests set what they look at
+// with `Star { x, ..star() }`.
+pub fn star() -> Star {
+    Star { x: 0.0, y: 0.0, z: 0.0, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type: TYPE_A, time_debt: 0.0, active_flag: 0.0 }
+}
+
+// A Chronoturin row at frame 0 with the always-present columns filled and every optional
+// one empty; tests set the rest with `MetricsRecord { frame, ..record() }`.
+pub fn record() -> MetricsRecord {
+    MetricsRecord {
+        mode: DilationMode::Chronoturin,
+        frame: 0,
+        wall_ms: 12.5,
+        gpu_ms: None,
+        active_count: 2341,
+        stars: 10000,
+        sim_time: 0.25,
+        error_mean: None,
+        error_p99: None,
+        conservation: None,
+        dilation_threshold: 0.5,
+        max_debt: 50.0,
+        readback_ms: 0.75,
+        readback_bytes: 160_016,
+        force_threads: None,
+        adapter: None,
+        active_by_type: vec![1200, 1141],
+        radial_profile: vec![9000, 990, 10],
+        debt_histogram: Vec::new(),
+        interactions: vec![1.0, -0.5, -0.5, 1.0],
+        alive: 9990,
+        mergers: None,
+        substeps: None,
+        placeholder: false,
+        lagrangian_radii: None,
+    }
+}
+
+// Returns None (and the caller skips) when the machine has no usable adapter.
+pub fn gpu() -> Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)> {
+    named_gpu().map(|(device, queue, _)| (device, queue))
+}
+
+// gpu(), with the adapter's name.
+pub fn named_gpu() -> Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>, String)> {
+    pollster::block_on(async {
+        let instance = wgpu::Instance::default();
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+        let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor::default(), None).await.ok()?;
+        Some((Arc::new(device), Arc::new(queue), adapter.get_info().name))
+    })
+}
+
+// gpu() opened with PUSH_CONSTANTS, or None when the adapter lacks them.
+pub fn push_constant_gpu() -> Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)> {
+    pollster::block_on(async {
+        let instance = wgpu::Instance::default();
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+        if !adapter.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+            return None;
+        }
+        let descriptor = wgpu::DeviceDescriptor {
+            required_features: wgpu::Features::PUSH_CONSTANTS,
+            required_limits: adapter.limits(),
+            ..Default::default()
+        };
+        let (device, queue) = adapter.request_device(&descriptor, None).await.ok()?;
+        Some((Arc::new(device), Arc::new(queue)))
+    })
+}
+
+#[macro_export]
+macro_rules! require_gpu {
+    () => {
+        match common::gpu() {
+            Some(gpu) => gpu,
+            None => {
+                eprintln!("skipping: no GPU adapter available");
+                return;
+            }
+        }
+    };
+}
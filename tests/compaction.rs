@@ -0,0 +1,57 @@
+mod common;
+
+use chronoturin::{cpu_reference, Accel, DilationMode, SimConfig, Simulation};
+
+#[test]
+fn compaction_steps_every_star_the_same_when_all_work() {
+    let (device, queue) = require_gpu!();
+    for accel in [Accel::Sampled, Accel::Grid] {
+        // Chunked, so the list and the indirect dispatch span buffers.
+        let config =
+            SimConfig { stars: 3000, seed: Some(8), central_mass: Some(500.0), chunk_stars: Some(1024), accel, ..SimConfig::default() };
+        let mut plain = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+        let mut compacted = Simulation::new(device.clone(), queue.clone(), &SimConfig { compaction: true, ..config.clone() }).unwrap();
+        // Newtonian stars never sleep, so the list holds every star.
+        plain.set_mode(DilationMode::Newtonian);
+        compacted.set_mode(DilationMode::Newtonian);
+        let (a, b) = (plain.step_and_read(3), compacted.step_and_read(3));
+        // Not bit for bit: chunk dispatches see the stars earlier chunks already stepped,
+        // while the single indirect dispatch races over all of them.
+        for (s, t) in a.iter().zip(&b) {
+            assert!((s.x - t.x).abs() < 1e-3 && (s.vy - t.vy).abs() < 1e-3, "{:?}: {:?} vs {:?}", accel, s, t);
+            assert_eq!((s.time_debt, s.active_flag), (t.time_debt, t.active_flag));
+        }
+    }
+}
+
+#[test]
+fn compaction_launches_threads_for_the_working_stars_only() {
+    let (device, queue) = require_gpu!();
+    let config = SimConfig { stars: 4000, seed: Some(8), central_mass: Some(500.0), chunk_stars: Some(1024), ..SimConfig::default() };
+    let steps = 6;
+    let mut frames = Vec::new();
+    for compaction in [false, true] {
+        let mut sim = Simulation::new(device.clone(), queue.clone(), &SimConfig { compaction, ..config.clone() }).unwrap();
+        sim.set_mode(DilationMode::Chronoturin);
+        let ticket = sim.submit_frame(steps);
+        frames.push(sim.finish_frame(ticket));
+    }
+    let wg = config.workgroup_size as u64;
+    // Without compaction every chunk's whole dispatch runs each step.
+    let full = frames[0].force_threads.unwrap();
+    assert_eq!(full, steps as u64 * 4 * 1024u64.div_ceil(wg) * wg);
+    // With it, the list rounded up to workgroups, short of one thread per star and step.
+    let (threads, active) = (frames[1].force_threads.unwrap(), frames[1].active_updates as u64);
+    assert!(active > 0 && threads >= active && threads < active + steps as u64 * wg, "{} threads for {} updates", threads, active);
+    assert!(threads < steps as u64 * config.stars as u64, "{} threads", threads);
+}
+
+#[test]
+fn compaction_needs_the_gpu() {
+    let limits = cpu_reference::limits();
+    let parse = |args: &[&str]| common::parse(args).validate(&limits);
+    assert!(!SimConfig::default().compaction);
+    assert!(parse(&["--compaction"]).is_ok());
+    assert!(parse(&["--compaction", "--accel", "bruteforce"]).is_ok());
+    assert!(parse(&["--compaction", "--cpu"]).is_err());
+}
@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use clap::CommandFactory;
+
+use chronoturin::config::Command;
+use chronoturin::config_file::{resolve, to_toml};
+use chronoturin::{Boundary, ChronoError, Precision, SimConfig};
+
+fn resolve_args(args: &[&str]) -> Result<SimConfig, ChronoError> {
+    resolve(&SimConfig::command().get_matches_from(["chronoturin"].iter().chain(args)))
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chronoturin-config-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(dir: &Path, name: &str, text: &str) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, text).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+const EXPERIMENT: &str = r#"
+# A threshold sweep on a bounded sphere
+stars = 2000
+dt = 0.02
+boundary = "reflect:500"
+interactions = "a_b=-0.5"
+precision = "df64"
+width = 300
+
+[command.sweep]
+stars = [1000, 2000]
+dilation-threshold = [0.1, 0.5]
+"#;
+
+#[test]
+fn flags_override_the_file_key_by_key() {
+    let dir = temp_dir("override");
+    let path = write(&dir, "experiment.toml", EXPERIMENT);
+    let config = resolve_args(&["--config", &path, "--dt", "0.03", "sweep", "--max-debt", "4,8", "--height", "200"]).unwrap();
+    assert_eq!((config.stars, config.dt, config.width, config.height), (2000, 0.03, 300, 200));
+    assert_eq!(config.boundary, Boundary::Reflect { radius: 500.0 });
+    assert_eq!(config.precision, Precision::Df64);
+    assert_eq!(config.interactions().unwrap().coefficient(0, 1), -0.5);
+    let Some(Command::Sweep(sweep)) = &config.command else { panic!("{:?}", config.command) };
+    assert_eq!((sweep.stars.as_slice(), sweep.dilation_threshold.as_slice()), ([1000, 2000].as_slice(), [0.1, 0.5].as_slice()));
+    assert_eq!(sweep.max_debt, [4.0, 8.0]);
+    // Another subcommand on the command line replaces the file's.
+    let config = resolve_args(&["--config", &path, "compare"]).unwrap();
+    assert!(matches!(config.command, Some(Command::Compare)));
+    // Without --config, the flags are all there is.
+    assert_eq!(resolve_args(&["--stars", "7"]).unwrap().stars, 7);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn file_errors_name_the_key_and_line() {
+    let dir = temp_dir("errors");
+    for (text, expected) in [
+        ("stars = 10\nwidht = 3\n", ":2: unknown field `widht`"),
+        ("stars = 10\n\n[command.sweep]\nmax-debt = \"x\"\n", ":4: `max-debt`: invalid type: string \"x\", expected a sequence"),
+        ("accel = \"fast\"\n", ":1: `accel`: unknown value `fast`, expected sampled, bruteforce, grid"),
+        ("boundary = \"reflect\"\n", ":1: `boundary`: reflect needs a size, as in reflect:400"),
+    ] {
+        let path = write(&dir, "bad.toml", text);
+        let error = resolve_args(&["--config", &path]).unwrap_err().to_string();
+        assert_eq!(error, format!("{}{}", path, expected));
+    }
+    assert!(resolve_args(&["--config", "/nonexistent/experiment.toml"]).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// --print-config fed back to --config reproduces every flag.
+#[test]
+fn printed_configs_read_back_unchanged() {
+    let dir = temp_dir("round-trip");
+    let experiment = write(&dir, "experiment.toml", EXPERIMENT);
+    let palette = write(&dir, "palette.csv", "type,r,g,b\n0,255,0,0\n1,0,255,0\n");
+    for args in [
+        vec!["--config", experiment.as_str(), "--orbit=-0.5", "--track", "3,4", "--palette", &palette],
+        vec![
+            "--external-potential",
+            "halo:scale=200,v0=1.5",
+            "--type-fractions",
+            "0.3,0.7",
+            "-vv",
+            "--seed",
+            "9",
+            "bench",
+            "--steps",
+            "20",
+        ],
+        vec!["--integrator", "leapfrog", "--max-velocity", "40", "render", "a.bin", "b.bin", "--frames", "2.."],
+    ] {
+        let printed = to_toml(&resolve_args(&args).unwrap());
+        let path = write(&dir, "printed.toml", &printed);
+        let reread = resolve_args(&["--config", &path]).unwrap();
+        assert_eq!(to_toml(&reread), printed);
+        assert_eq!(
+            format!("{:?}", SimConfig { config: None, ..reread }),
+            format!("{:?}", SimConfig { config: None, ..resolve_args(&args).unwrap() })
+        );
+    }
+    let printed = to_toml(&resolve_args(&["--config", &experiment]).unwrap());
+    assert!(printed.contains("\ndt = 0.02\n") && printed.contains("\nsoftening = 3.1622777\n"), "{}", printed);
+    assert!(printed.ends_with(
+        "[command.sweep]\ndilation-threshold = [0.1, 0.5]\nmax-debt = []\nstars = [1000, 2000]\nsweep-save-last-frame = false\n"
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use chronoturin::conservation::{measure, relative_drift, Potential};
+use chronoturin::{generate_galaxy, Boundary, Conservation, EnergyCheck, Interactions, Star, TYPE_A, TYPE_B, TYPE_CENTRAL};
+
+fn star(x: f32, y: f32, vx: f32, vy: f32, data_type: f32) -> Star {
+    Star { x, y, vx, vy, data_type, ..common::star() }
+}
+
+fn potential(check: EnergyCheck, samples: usize) -> Potential {
+    Potential {
+        check,
+        g: 0.5,
+        softening: 3.1622777,
+        samples,
+        interactions: Interactions::default_for(2),
+        external: None,
+        boundary: Boundary::Open,
+    }
+}
+
+#[test]
+fn momentum_and_angular_momentum_of_a_known_pair() {
+    let stars = [star(10.0, 0.0, 0.0, 2.0, TYPE_A), star(-10.0, 0.0, 0.0, -1.0, TYPE_A)];
+    let c = measure(&stars, &potential(EnergyCheck::Off, 0));
+    assert_eq!(c.kinetic, 0.5 * 4.0 + 0.5 * 1.0);
+    assert_eq!(c.momentum, [0.0, 1.0, 0.0]);
+    // L_z = x * vy - y * vx = 10 * 2 + (-10) * (-1)
+    assert_eq!(c.angular_momentum, [0.0, 0.0, 30.0]);
+    assert_eq!(c.potential, None);
+    assert_eq!(c.total_energy(), None);
+}
+
+#[test]
+fn matching_types_attract_and_opposite_types_repel() {
+    let same = [star(0.0, 0.0, 0.0, 0.0, TYPE_A), star(30.0, 0.0, 0.0, 0.0, TYPE_A)];
+    let opposite = [star(0.0, 0.0, 0.0, 0.0, TYPE_A), star(30.0, 0.0, 0.0, 0.0, TYPE_B)];
+    let p = potential(EnergyCheck::Exact, 0);
+    let (bound, unbound) = (measure(&same, &p).potential.unwrap(), measure(&opposite, &p).potential.unwrap());
+    assert!(bound < 0.0, "{}", bound);
+    assert_eq!(unbound, -bound);
+}
+
+#[test]
+fn sampled_potential_tracks_exact() {
+    // One type only: with both, attraction and repulsion nearly cancel and the sampling noise
+    // dwarfs the net sum.
+    let stars: Vec<Star> = generate_galaxy(2000, 7).into_iter().map(|s| Star { data_type: TYPE_A, ..s }).collect();
+    let exact = measure(&stars, &potential(EnergyCheck::Exact, 0)).potential.unwrap();
+    let sampled = measure(&stars, &potential(EnergyCheck::Sampled, 200_000)).potential.unwrap();
+    assert!((sampled - exact).abs() < 0.05 * exact.abs(), "sampled {} vs exact {}", sampled, exact);
+    // More samples than pairs falls back to the exact sum.
+    let tiny = &stars[..100];
+    assert_eq!(
+        measure(tiny, &potential(EnergyCheck::Sampled, 1_000_000)).potential,
+        measure(tiny, &potential(EnergyCheck::Exact, 0)).potential
+    );
+}
+
+#[test]
+fn central_mass_is_a_field_not_a_body() {
+    let mut central = star(0.0, 0.0, 0.0, 0.0, TYPE_CENTRAL);
+    central.mass = 100.0;
+    let orbiting = star(50.0, 0.0, 0.0, 5.0, TYPE_A);
+    let c = measure(&[central, orbiting], &potential(EnergyCheck::Exact, 0));
+    assert_eq!(c.kinetic, 12.5);
+    assert_eq!(c.momentum, [0.0, 5.0, 0.0]);
+    assert!(c.potential.unwrap() < 0.0);
+}
+
+#[test]
+fn drift_is_relative_to_the_first_frame() {
+    let at = |kinetic, potential| Conservation { kinetic, potential, momentum: [0.0; 3], angular_momentum: [0.0; 3] };
+    assert_eq!(relative_drift(&at(1.0, Some(-5.0)), &at(2.0, Some(-5.0))), Some(0.25));
+    assert_eq!(relative_drift(&at(1.0, None), &at(2.0, None)), None);
+}
+
+#[test]
+fn virial_ratio_needs_a_measured_potential() {
+    let at = |kinetic, potential| Conservation { kinetic, potential, momentum: [0.0; 3], angular_momentum: [0.0; 3] };
+    assert_eq!(at(2.5, Some(-5.0)).virial_ratio(), Some(1.0));
+    assert_eq!(at(1.0, Some(-8.0)).virial_ratio(), Some(0.25));
+    assert_eq!(at(1.0, None).virial_ratio(), None);
+    assert_eq!(at(1.0, Some(0.0)).virial_ratio(), None);
+}
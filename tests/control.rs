@@ -0,0 +1,64 @@
+mod common;
+
+use std::io::Cursor;
+
+use chronoturin::{cpu_reference, Control, ControlCommand, ControlStatus, DilationMode};
+
+#[test]
+fn commands_parse_and_unknown_ones_explain_themselves() {
+    assert_eq!(ControlCommand::parse("pause"), Ok(ControlCommand::Pause));
+    assert_eq!(ControlCommand::parse("  status "), Ok(ControlCommand::Status));
+    assert_eq!(ControlCommand::parse("set threshold 0.3"), Ok(ControlCommand::SetThreshold(0.3)));
+    assert_eq!(ControlCommand::parse("set max-debt 8"), Ok(ControlCommand::SetMaxDebt(8.0)));
+    assert_eq!(ControlCommand::parse("set mode newton"), Ok(ControlCommand::SetMode(DilationMode::Newtonian)));
+    assert_eq!(ControlCommand::parse("set mode Chronoturin"), Ok(ControlCommand::SetMode(DilationMode::Chronoturin)));
+    for (line, expected) in [
+        ("jump", "unknown command `jump`"),
+        ("pause now", "unknown command `pause now`"),
+        ("set threshold high", "set threshold takes a number, not `high`"),
+        ("set threshold NaN", "set threshold takes a number, not `NaN`"),
+        ("set max-debt 0.5", "set max-debt must be at least 1, not 0.5"),
+        ("set mode fast", "set mode takes newton or chrono, not `fast`"),
+        ("set dt 0.1", "unknown setting `dt`"),
+    ] {
+        assert!(ControlCommand::parse(line).unwrap_err().starts_with(expected), "{}", line);
+    }
+}
+
+// A paused run waits for the next line, and closed input lets it go on.
+#[test]
+fn paused_runs_wait_for_input_until_it_closes() {
+    let input = Cursor::new("status\n\nset threshold 2\nbogus\nresume\n");
+    let mut control = Control::new(input, Box::new(std::io::sink()));
+    control.paused = true;
+    assert_eq!(control.next(|| false), Some(Ok(ControlCommand::Status)));
+    assert_eq!(control.next(|| false), Some(Ok(ControlCommand::SetThreshold(2.0))));
+    assert!(control.next(|| false).unwrap().is_err());
+    assert_eq!(control.next(|| false), Some(Ok(ControlCommand::Resume)));
+    assert_eq!(control.next(|| false), None);
+    assert!(!control.paused);
+}
+
+#[test]
+fn status_is_one_json_line() {
+    let status = ControlStatus {
+        frame: 12,
+        mode: DilationMode::Chronoturin.name(),
+        sim_time: 3.0,
+        frame_ms: 16.5,
+        dilation_threshold: 0.3,
+        max_debt: 50.0,
+        paused: false,
+    };
+    let json: serde_json::Value = serde_json::from_str(&status.json()).unwrap();
+    assert_eq!((json["frame"].as_u64(), json["mode"].as_str(), json["paused"].as_bool()), (Some(12), Some("CHRONOTURIN"), Some(false)));
+    assert!(!status.json().contains('\n'));
+}
+
+#[test]
+fn control_drives_frame_runs_only() {
+    let check = |args: &[&str]| common::parse(args).validate(&cpu_reference::limits());
+    assert!(check(&["--control"]).is_ok());
+    assert!(check(&["--control", "compare"]).is_ok());
+    assert!(check(&["--control", "bench"]).unwrap_err().to_string().starts_with("--control steps one frame run"));
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use chronoturin::{
+    cpu_reference, is_dead, CpuSimulation, DilationMode, RenderRecord, SimConfig, Simulation, Star, TrajectoryWriter, DEAD_FLAG,
+};
+
+use common::parse;
+
+// A hot sphere wider than the kill radius, so some stars die at the first cull and more
+// escape as it runs.
+fn escaping() -> SimConfig {
+    parse(&[
+        "--stars",
+        "400",
+        "--seed",
+        "5",
+        "--scale-radius",
+        "380",
+        "--velocity-dispersion",
+        "40",
+        "--kill-radius",
+        "300",
+        "--cull-every",
+        "2",
+    ])
+}
+
+fn radius(star: &Star) -> f32 {
+    (star.x * star.x + star.y * star.y + star.z * star.z).sqrt()
+}
+
+fn dead_ids(stars: &[Star]) -> Vec<usize> {
+    stars.iter().enumerate().filter(|(_, s)| is_dead(s)).map(|(id, _)| id).collect()
+}
+
+#[test]
+fn kill_radius_and_cull_every_are_validated() {
+    let limits = cpu_reference::limits();
+    assert!(escaping().validate(&limits).is_ok());
+    assert!(parse(&["--kill-radius", "0"]).validate(&limits).is_err());
+    assert!(parse(&["--kill-radius=-5"]).validate(&limits).is_err());
+    assert!(parse(&["--kill-radius", "300", "--cull-every", "0"]).validate(&limits).is_err());
+}
+
+#[test]
+fn the_cpu_culls_escaped_stars_and_keeps_creation_order() {
+    let config = escaping();
+    let mut cpu = CpuSimulation::new(&config);
+    cpu.set_mode(DilationMode::Newtonian);
+    let initial = cpu.initial_stars().to_vec();
+    let first = cpu.run_frame(1);
+    // The first cull goes ahead of the first step, on the initial positions.
+    let outside: Vec<usize> = initial.iter().enumerate().filter(|(_, s)| radius(s) > 300.0).map(|(id, _)| id).collect();
+    assert!(!outside.is_empty());
+    assert_eq!(dead_ids(&first.stars), outside);
+    assert_eq!(first.alive as usize, initial.len() - outside.len());
+    assert_eq!(first.living().len(), first.alive as usize);
+    for id in outside {
+        assert_eq!(first.stars[id].active_flag, DEAD_FLAG);
+        assert_eq!((first.stars[id].x, first.stars[id].y), (initial[id].x, initial[id].y), "a dead star moved");
+    }
+
+    let mut alive = first.alive;
+    for _ in 0..40 {
+        let frame = cpu.run_frame(1);
+        assert!(frame.alive <= alive);
+        alive = frame.alive;
+    }
+    assert!(alive < first.alive, "no star escaped after the first cull");
+    cpu.reset();
+    assert_eq!(cpu.alive() as u32, first.alive);
+}
+
+#[test]
+fn the_gpu_counts_the_living_and_shrinks_its_dispatch() {
+    let (device, queue) = require_gpu!();
+    let config = escaping();
+    let mut gpu = Simulation::new(device, queue, &config).unwrap();
+    gpu.set_mode(DilationMode::Newtonian);
+    let mut cpu = CpuSimulation::new(&config);
+    cpu.set_mode(DilationMode::Newtonian);
+
+    let ticket = gpu.submit_frame(1);
+    let (gpu_first, cpu_first) = (gpu.finish_frame(ticket), cpu.run_frame(1));
+    assert_eq!(dead_ids(&gpu_first.stars), dead_ids(&cpu_first.stars));
+    assert_eq!(gpu_first.alive, cpu_first.alive);
+    let full = 400u64.div_ceil(config.workgroup_size as u64) * config.workgroup_size as u64;
+    assert!(gpu_first.force_threads.unwrap() < full);
+    assert!(gpu_first.force_threads.unwrap() >= gpu_first.alive as u64);
+    // The living stars step as they do on the CPU.
+    for (g, c) in gpu_first.stars.iter().zip(&cpu_first.stars) {
+        assert!((g.x - c.x).abs() < 1e-2 && (g.vz - c.vz).abs() < 1e-2, "{:?} != {:?}", g, c);
+    }
+
+    let mut alive = gpu_first.alive;
+    for _ in 0..40 {
+        let ticket = gpu.submit_frame(1);
+        let frame = gpu.finish_frame(ticket);
+        assert!(frame.alive <= alive);
+        assert_eq!(frame.stars.iter().filter(|s| !is_dead(s)).count(), frame.alive as usize);
+        alive = frame.alive;
+    }
+    assert!(alive < gpu_first.alive, "no star escaped after the first cull");
+}
+
+#[test]
+fn packed_records_keep_the_dead_flag() {
+    let star =
+        Star { x: 1.0, y: 2.0, z: 3.0, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type: 1.0, time_debt: 0.5, active_flag: DEAD_FLAG };
+    assert!(is_dead(&RenderRecord::pack(&star).unpack()));
+    let asleep = RenderRecord::pack(&Star { active_flag: 0.0, ..star }).unpack();
+    assert!(!is_dead(&asleep) && asleep.active_flag == 0.0);
+}
+
+#[test]
+fn an_escaped_track_gets_one_last_row() {
+    let dir = std::env::temp_dir().join(format!("chronoturin-cull-track-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("trajectory_1.csv");
+    let mut writer = TrajectoryWriter::open(&[1], |_| path.clone(), false).unwrap();
+    let star = Star { x: 1.0, y: 2.0, z: 3.0, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type: 0.0, time_debt: 0.0, active_flag: 1.0 };
+    let dead = Star { x: 400.0, active_flag: DEAD_FLAG, ..star };
+    writer.write("NEWTONIAN", 0, 0.1, &[star, star]).unwrap();
+    writer.write("NEWTONIAN", 1, 0.2, &[star, dead]).unwrap();
+    writer.write("NEWTONIAN", 2, 0.3, &[star, dead]).unwrap();
+    writer.write("CHRONOTURIN", 2, 0.3, &[star, dead]).unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    let rows: Vec<&str> = text.lines().skip(1).collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[1].starts_with("NEWTONIAN,1,0.2,400,") && rows[1].ends_with(",-1"));
+    assert!(rows[2].starts_with("CHRONOTURIN,2,"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use chronoturin::determinism::{canonical_bytes, compare_states, verify};
+use chronoturin::{
+    cpu_reference, determinism_report_json, generate_galaxy, state_hash, CpuSimulation, DilationMode, FrameData, GalaxyState, SimConfig,
+    Star, Stepper,
+};
+
+fn config() -> SimConfig {
+    SimConfig { frames: 4, ..common::seeded(64, 5) }
+}
+
+// The CPU reference, except that forks nudge one star as `frame` finishes, as a
+// nondeterministic adapter would.
+struct Nudged {
+    inner: CpuSimulation,
+    nudge_at: Option<usize>,
+    frame: usize,
+}
+
+impl Stepper for Nudged {
+    type Ticket = <CpuSimulation as Stepper>::Ticket;
+    fn fork(&self) -> Self {
+        Nudged { inner: self.inner.fork(), nudge_at: Some(2), frame: 0 }
+    }
+    fn reset(&mut self) {
+        self.frame = 0;
+        self.inner.reset();
+    }
+    fn restore(&mut self, state: GalaxyState, stars: &[Star]) {
+        self.inner.restore(state, stars);
+    }
+    fn set_mode(&mut self, mode: DilationMode) {
+        Stepper::set_mode(&mut self.inner, mode);
+    }
+    fn set_dilation(&mut self, dilation_threshold: f32, max_debt: f32) {
+        self.inner.set_dilation(dilation_threshold, max_debt);
+    }
+    fn readback_depth(&self) -> usize {
+        self.inner.readback_depth()
+    }
+    fn submit_frame(&mut self, steps: usize) -> Self::Ticket {
+        self.inner.submit_frame(steps)
+    }
+    fn finish_frame(&mut self, ticket: Self::Ticket) -> FrameData {
+        let mut frame = self.inner.finish_frame(ticket);
+        if self.nudge_at == Some(self.frame) {
+            frame.stars[3].x += 0.25;
+        }
+        self.frame += 1;
+        frame
+    }
+}
+
+#[test]
+fn the_cpu_reference_repeats_itself_exactly() {
+    let mut reference = CpuSimulation::new(&config());
+    let mut candidate = Stepper::fork(&reference);
+    let report = verify(&mut reference, &mut candidate, &config(), 3, "CPU reference", |_, _, _| true);
+    assert!(report.deterministic(), "{}", report.table());
+    assert_eq!(report.modes.iter().map(|m| m.mode).collect::<Vec<_>>(), [DilationMode::Newtonian, DilationMode::Chronoturin]);
+    // The reference's hash, then one per repeat.
+    assert!(report.modes.iter().all(|m| m.final_hashes.len() == 3));
+    assert_ne!(report.modes[0].final_hashes[0], report.modes[1].final_hashes[0]);
+}
+
+#[test]
+fn a_divergent_repeat_reports_its_first_frame() {
+    let mut reference = Nudged { inner: CpuSimulation::new(&config()), nudge_at: None, frame: 0 };
+    let mut candidate = reference.fork();
+    let report = verify(&mut reference, &mut candidate, &config(), 2, "nudged", |_, _, _| true);
+    assert!(!report.deterministic());
+    let mismatch = report.modes[0].first_mismatch.unwrap();
+    assert_eq!((mismatch.run, mismatch.frame, mismatch.stars), (1, 2, 1));
+    assert!((mismatch.max_displacement - 0.25).abs() < 1e-3 && (mismatch.max_delta - 0.25).abs() < 1e-3, "{:?}", mismatch);
+
+    let json: serde_json::Value = serde_json::from_str(&determinism_report_json(&report)).unwrap();
+    assert_eq!(json["determinism"]["adapter"], "nudged");
+    assert_eq!(json["determinism"]["deterministic"].as_bool(), Some(false));
+    assert_eq!(json["determinism"]["modes"][0]["first_mismatch"]["frame"].as_u64(), Some(2));
+    assert_eq!(json["determinism"]["modes"][0]["final_hashes"].as_array().map(Vec::len), Some(2));
+}
+
+#[test]
+fn the_hash_covers_every_field_bit() {
+    let stars = generate_galaxy(4, 9);
+    assert_eq!(canonical_bytes(&stars).len(), 4 * std::mem::size_of::<Star>());
+    let mut flagged = stars.clone();
+    flagged[2].time_debt = f32::from_bits(flagged[2].time_debt.to_bits() ^ 1);
+    assert_ne!(state_hash(&stars), state_hash(&flagged));
+    assert_eq!(compare_states(1, 0, &stars, &flagged).map(|m| m.stars), Some(1));
+    assert_eq!(compare_states(1, 0, &stars, &stars.clone()), None);
+    // -0.0 == 0.0, but not bit for bit.
+    let mut zeroed = stars.clone();
+    zeroed[0].vz = 0.0;
+    let mut negative = zeroed.clone();
+    negative[0].vz = -0.0;
+    assert!(compare_states(1, 0, &zeroed, &negative).is_some());
+}
+
+#[test]
+fn verify_flags_are_checked() {
+    let check = |args: &[&str]| common::parse(args).validate(&cpu_reference::limits()).map_err(|e| e.to_string());
+    assert!(check(&["--cpu", "--verify-determinism", "3", "--frames", "5", "--report", "det.json"]).is_ok());
+    assert!(check(&["--verify-determinism", "1"]).unwrap_err().contains("at least 2 runs"));
+    assert!(check(&["--verify-determinism", "2", "--chain"]).unwrap_err().starts_with("--verify-determinism repeats"));
+    assert!(check(&["--verify-determinism", "2", "tune"]).is_err());
+}
@@ -0,0 +1,49 @@
+use chronoturin::diff::error_color;
+use chronoturin::{displacement_stats, render_diff, Camera, Star, TYPE_A};
+use image::Rgb;
+
+fn star_at(x: f32, y: f32, z: f32) -> Star {
+    Star { x, y, z, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type: TYPE_A, time_debt: 0.0, active_flag: 0.0 }
+}
+
+fn camera() -> Camera {
+    Camera::look_at([0.0, 0.0, -1000.0], [0.0; 3], [0.0, 1.0, 0.0], 64, 64, 800.0)
+}
+
+#[test]
+fn stats_cover_mean_and_p99() {
+    let reference: Vec<Star> = (0..100).map(|_| star_at(0.0, 0.0, 0.0)).collect();
+    // 98 stars stay put, two are displaced by 3-4-5 triangles.
+    let mut other = reference.clone();
+    other[10] = star_at(3.0, 4.0, 0.0);
+    other[20] = star_at(0.0, 6.0, 8.0);
+    let stats = displacement_stats(&reference, &other);
+    assert!((stats.mean - 0.15).abs() < 1e-6);
+    assert_eq!((stats.p99, stats.max, stats.non_finite), (5.0, 10.0, 0));
+}
+
+#[test]
+fn non_finite_positions_are_counted_not_averaged() {
+    let reference = vec![star_at(0.0, 0.0, 0.0); 2];
+    let other = vec![star_at(f32::NAN, 0.0, 0.0), star_at(1.0, 0.0, 0.0)];
+    let stats = displacement_stats(&reference, &other);
+    assert_eq!((stats.mean, stats.max, stats.non_finite), (1.0, 1.0, 1));
+}
+
+#[test]
+fn ramp_runs_green_to_red() {
+    assert_eq!(error_color(0.0, 5.0), Rgb([0, 255, 0]));
+    assert_eq!(error_color(2.5, 5.0), Rgb([255, 255, 0]));
+    assert_eq!(error_color(50.0, 5.0), Rgb([255, 0, 0]));
+    assert_eq!(error_color(f32::NAN, 5.0), Rgb([255, 0, 0]));
+}
+
+#[test]
+fn stars_that_leave_the_view_fall_back_to_their_reference_position() {
+    let reference = vec![star_at(0.0, 0.0, 0.0), star_at(5000.0, 0.0, 0.0)];
+    let other = vec![star_at(5000.0, 0.0, 0.0), star_at(6000.0, 0.0, 0.0)];
+    let img = render_diff(&reference, &other, &camera(), 5.0);
+    // First star is drawn where it was in the reference run; the second is off-screen in both.
+    assert_eq!(img.get_pixel(32, 32), &Rgb([255, 0, 0]));
+    assert_eq!(img.pixels().filter(|p| p.0 != [0, 0, 0]).count(), 1);
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::path::PathBuf;
+
+use chronoturin::dump::sidecar_path;
+use chronoturin::{read_bin_dump, read_csv_dump, write_dump, ChronoError, DumpFormat, DumpInfo, SimConfig, Simulation};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chronoturin-{}-{}", name, std::process::id()))
+}
+
+const INFO: DumpInfo = DumpInfo { mode: "CHRONOTURIN", frame: 4, sim_time: 1.25 };
+
+#[test]
+fn dumps_round_trip_a_readback_exactly() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &SimConfig { stars: 1000, seed: Some(7), ..SimConfig::default() }).unwrap();
+    let stars = sim.step_and_read(5);
+
+    for format in [DumpFormat::Csv, DumpFormat::Bin] {
+        let path = temp_path(&format!("dump.{}", format.extension()));
+        write_dump(&path, &stars, None, format, &INFO, None).unwrap();
+        let parsed = match format {
+            DumpFormat::Csv => read_csv_dump(&path).unwrap(),
+            DumpFormat::Bin => read_bin_dump(&path).unwrap(),
+        };
+        assert_eq!(bytemuck::cast_slice::<_, u8>(&parsed), bytemuck::cast_slice::<_, u8>(&stars), "{:?}", format);
+        std::fs::remove_file(&path).unwrap();
+        if format == DumpFormat::Bin {
+            let sidecar = std::fs::read_to_string(sidecar_path(&path)).unwrap();
+            assert!(sidecar.contains("\"stars\":1000") && sidecar.contains("\"stride_bytes\":40"), "{}", sidecar);
+            std::fs::remove_file(sidecar_path(&path)).unwrap();
+        }
+    }
+}
+
+#[test]
+fn csv_has_header_and_one_row_per_star() {
+    let stars = chronoturin::generate_galaxy(3, 1);
+    let path = temp_path("dump-rows.csv");
+    write_dump(&path, &stars, None, DumpFormat::Csv, &INFO, None).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "x,y,z,vx,vy,vz,mass,data_type,time_debt,active_flag");
+    assert_eq!(lines.len(), 4);
+}
+
+#[test]
+fn malformed_rows_report_their_line() {
+    let path = temp_path("dump-bad.csv");
+    std::fs::write(&path, "x,y,z,vx,vy,vz,mass,data_type,time_debt,active_flag\n1,2,3,0,0,0,1,0,0,0\n1,2,3\n").unwrap();
+    let err = read_csv_dump(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(err, ChronoError::Parse { line: 3, .. }), "{}", err);
+    assert!(err.to_string().contains("expected 10 columns, found 3"));
+}
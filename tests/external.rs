@@ -0,0 +1,81 @@
+mod common;
+
+use chronoturin::conservation::{measure, Potential};
+use chronoturin::external::parse_external_potential;
+use chronoturin::{cpu_reference, CpuSimulation, DilationMode, EnergyCheck, ExternalPotential, SimConfig, Simulation, Star, TYPE_A};
+
+use common::parse;
+
+fn lone_star() -> Vec<Star> {
+    vec![Star { x: 100.0, y: 0.0, z: -40.0, vx: 0.0, vy: 6.0, vz: 0.0, mass: 1.0, data_type: TYPE_A, time_debt: 0.0, active_flag: 0.0 }]
+}
+
+// Leapfrog has no damping of its own, so only --drag takes the energy out.
+fn trapped() -> SimConfig {
+    parse(&["--integrator", "leapfrog", "--drag", "0.3", "--external-potential", "harmonic:omega=0.2"])
+}
+
+fn distance(star: &Star) -> f32 {
+    (star.x * star.x + star.y * star.y + star.z * star.z).sqrt()
+}
+
+#[test]
+fn potential_specs_parse_with_every_key() {
+    assert_eq!(parse_external_potential("halo:scale=200,v0=1.5"), Ok(ExternalPotential::Halo { scale: 200.0, v0: 1.5 }));
+    assert_eq!(parse_external_potential("harmonic: omega = 0.05"), Ok(ExternalPotential::Harmonic { omega: 0.05 }));
+    assert!(parse_external_potential("halo:scale=200").unwrap_err().contains("needs v0="));
+    assert!(parse_external_potential("harmonic:omega=0.1,v0=2").unwrap_err().contains("takes omega, not v0"));
+    assert!(parse_external_potential("plummer:scale=1").is_err());
+    assert!(parse_external_potential("harmonic:omega=-1").is_err());
+
+    let limits = cpu_reference::limits();
+    assert!(trapped().validate(&limits).is_ok());
+    assert!(parse(&["--drag=-0.1"]).validate(&limits).is_err());
+    assert!(parse(&["--drag", "20"]).validate(&limits).unwrap_err().to_string().contains("drag * dt below 1"));
+    let state = chronoturin::GalaxyState::new(0.0, 0.0, &parse(&["--external-potential", "halo:scale=200,v0=1.5"]));
+    assert_eq!((state.external_kind, state.external_scale, state.external_strength, state.drag), (1.0, 200.0, 2.25, 0.0));
+}
+
+#[test]
+fn a_dragged_star_settles_at_the_bottom_of_the_trap() {
+    let mut cpu = CpuSimulation::with_stars(&trapped(), lone_star());
+    cpu.set_mode(DilationMode::Newtonian);
+    let stars = cpu.step_and_read(1000);
+    assert!(distance(&stars[0]) < 0.5, "{:?}", stars[0]);
+    assert!(stars[0].vy.abs() < 0.1);
+
+    // Without drag the trap alone keeps it oscillating.
+    let mut free = CpuSimulation::with_stars(&SimConfig { drag: 0.0, ..trapped() }, lone_star());
+    free.set_mode(DilationMode::Newtonian);
+    let amplitude = (0..100).map(|_| distance(&free.step_and_read(10)[0])).fold(0.0, f32::max);
+    assert!(amplitude > 50.0, "{}", amplitude);
+}
+
+#[test]
+fn the_halo_adds_its_energy() {
+    let halo = ExternalPotential::Halo { scale: 3.0, v0: 2.0 };
+    let potential = |external| Potential {
+        check: EnergyCheck::Exact,
+        g: 0.0,
+        softening: 1.0,
+        samples: 0,
+        interactions: chronoturin::Interactions::default_for(2),
+        external,
+        boundary: chronoturin::Boundary::Open,
+    };
+    let stars = [Star { x: 4.0, y: 0.0, z: 0.0, ..lone_star()[0] }];
+    assert_eq!(measure(&stars, &potential(None)).potential, Some(0.0));
+    assert!((measure(&stars, &potential(Some(halo))).potential.unwrap() - 2.0 * 25f64.ln()).abs() < 1e-9);
+}
+
+#[test]
+fn the_gpu_applies_drag_and_the_trap_like_the_cpu() {
+    let (device, queue) = require_gpu!();
+    let mut gpu = Simulation::with_stars(device, queue, &trapped(), lone_star()).unwrap();
+    let mut cpu = CpuSimulation::with_stars(&trapped(), lone_star());
+    gpu.set_mode(DilationMode::Newtonian);
+    cpu.set_mode(DilationMode::Newtonian);
+    let (g, c) = (gpu.step_and_read(200), cpu.step_and_read(200));
+    assert!((g[0].x - c[0].x).abs() < 1e-2 && (g[0].vy - c[0].vy).abs() < 1e-3, "{:?} vs {:?}", g[0], c[0]);
+    assert!(distance(&gpu.step_and_read(800)[0]) < 0.5);
+}
@@ -0,0 +1,35 @@
+use std::fs::File;
+
+use chronoturin::gif::box_downsample;
+use chronoturin::GifWriter;
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, Rgb, RgbImage};
+
+#[test]
+fn box_filter_averages_each_block() {
+    let img = RgbImage::from_fn(4, 4, |x, _| if x < 2 { Rgb([200, 0, 0]) } else { Rgb([0, 0, 100]) });
+    let small = box_downsample(&img, 2, 2);
+    assert_eq!(small.get_pixel(0, 1), &Rgb([200, 0, 0]));
+    assert_eq!(small.get_pixel(1, 0), &Rgb([0, 0, 100]));
+
+    let one = box_downsample(&img, 1, 1);
+    assert_eq!(one.get_pixel(0, 0), &Rgb([100, 0, 50]));
+}
+
+#[test]
+fn frames_are_appended_to_one_file() {
+    let path = std::env::temp_dir().join(format!("chronoturin-gif-{}.gif", std::process::id()));
+    let mut gif = GifWriter::create(&path, 32, 40).unwrap();
+    let img = RgbImage::from_pixel(128, 64, Rgb([10, 20, 30]));
+    gif.add_labeled_frame(&img, Some(("NEWTONIAN", Rgb([255, 255, 255])))).unwrap();
+    gif.add_frame(&img).unwrap();
+    gif.add_labeled_frame(&img, Some(("CHRONOTURIN", Rgb([255, 200, 50])))).unwrap();
+    assert_eq!(gif.frames(), 3);
+    let written = gif.finish();
+
+    let frames = GifDecoder::new(File::open(&written).unwrap()).unwrap().into_frames().collect_frames().unwrap();
+    std::fs::remove_file(&written).unwrap();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].buffer().dimensions(), (32, 16));
+    assert_eq!(frames[1].delay().numer_denom_ms(), (40, 1));
+}
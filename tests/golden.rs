@@ -0,0 +1,123 @@
+mod common;
+
+use std::path::PathBuf;
+
+use chronoturin::{CpuSimulation, DilationMode, Simulation, Star, Stepper};
+
+use common::seeded;
+
+// Golden hashes of star positions after a few deterministic steps, one per (mode, seed).
+// After an intended change to the physics or the shaders, regenerate them with
+//     CHRONOTURIN_BLESS=1 cargo test --test golden
+// and commit the rewritten files under tests/golden/ with the change.
+const BLESS: &str = "CHRONOTURIN_BLESS";
+const STARS: u32 = 256;
+const STEPS: usize = 10;
+const SEEDS: [u64; 2] = [1, 2];
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name)
+}
+
+// FNV-1a over the bits of every x, y, z in order.
+fn position_hash(stars: &[Star]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for star in stars {
+        for value in [star.x, star.y, star.z] {
+            for byte in value.to_bits().to_le_bytes() {
+                hash = (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        }
+    }
+    hash
+}
+
+// One `mode seed hash` line per run, after `header` comment lines.
+fn render(header: &[String], hashes: &[(DilationMode, u64, u64)]) -> String {
+    let mut text: String = header.iter().map(|line| format!("# {}\n", line)).collect();
+    for (mode, seed, hash) in hashes {
+        text.push_str(&format!("{} {} {:016x}\n", mode.file_stem(), seed, hash));
+    }
+    text
+}
+
+fn header(stepper: &str) -> Vec<String> {
+    vec![
+        format!("{} positions after {} steps of --stars {} --seed N, FNV-1a over the x, y, z bits.", stepper, STEPS, STARS),
+        format!("Regenerate with {}=1 cargo test --test golden.", BLESS),
+    ]
+}
+
+// Writes the file instead of comparing when blessing.
+fn check(name: &str, expected_header: &[String], hashes: &[(DilationMode, u64, u64)]) {
+    let path = golden_path(name);
+    let actual = render(expected_header, hashes);
+    if std::env::var_os(BLESS).is_some() {
+        std::fs::write(&path, actual).unwrap();
+        eprintln!("blessed {}", path.display());
+        return;
+    }
+    let golden = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {} (bless with {}=1)", path.display(), e, BLESS));
+    let lines = |text: &str| text.lines().filter(|l| !l.starts_with('#')).map(str::to_string).collect::<Vec<_>>();
+    assert_eq!(
+        lines(&actual),
+        lines(&golden),
+        "star positions no longer match {}; if the change is intended, bless with {}=1",
+        path.display(),
+        BLESS
+    );
+}
+
+#[test]
+fn cpu_steps_match_their_golden_hashes() {
+    let mut hashes = Vec::new();
+    for mode in [DilationMode::Newtonian, DilationMode::Chronoturin] {
+        for seed in SEEDS {
+            let mut cpu = CpuSimulation::new(&seeded(STARS, seed));
+            Stepper::set_mode(&mut cpu, mode);
+            hashes.push((mode, seed, position_hash(&cpu.step_and_read(STEPS))));
+        }
+    }
+    check("cpu_steps.txt", &header("CPU reference"), &hashes);
+}
+
+// Drivers round and contract floats differently, so the GPU hashes only hold on the adapter
+// that blessed them; elsewhere the test skips.
+#[test]
+fn gpu_steps_match_their_golden_hashes() {
+    let Some((device, queue, adapter)) = common::named_gpu() else {
+        eprintln!("skipping: no GPU adapter available");
+        return;
+    };
+    let blessed_on = format!("Blessed on: {}", adapter);
+    if std::env::var_os(BLESS).is_none() {
+        let golden = std::fs::read_to_string(golden_path("gpu_steps.txt")).unwrap_or_default();
+        if !golden.lines().any(|line| line == format!("# {}", blessed_on)) {
+            eprintln!("skipping: the GPU golden hashes were blessed on another adapter than {}", adapter);
+            return;
+        }
+    }
+    let mut hashes = Vec::new();
+    for mode in [DilationMode::Newtonian, DilationMode::Chronoturin] {
+        for seed in SEEDS {
+            let mut sim = Simulation::new(device.clone(), queue.clone(), &seeded(STARS, seed)).unwrap();
+            sim.set_mode(mode);
+            hashes.push((mode, seed, position_hash(&sim.step_and_read(STEPS))));
+        }
+    }
+    let mut header = header("GPU");
+    header.push(blessed_on);
+    check("gpu_steps.txt", &header, &hashes);
+}
+
+#[test]
+fn the_hash_sees_every_position_bit() {
+    let stars = chronoturin::generate_galaxy(8, 3);
+    let mut moved = stars.clone();
+    moved[7].z = f32::from_bits(moved[7].z.to_bits() ^ 1);
+    assert_ne!(position_hash(&stars), position_hash(&moved));
+    // Velocities and flags are left out.
+    let mut flagged = stars.clone();
+    flagged[0].active_flag = 1.0;
+    assert_eq!(position_hash(&stars), position_hash(&flagged));
+}
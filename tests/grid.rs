@@ -0,0 +1,53 @@
+mod common;
+
+use chronoturin::{cells_per_side, cpu_reference, presets, Accel, DilationMode, SimConfig, Simulation, Star};
+
+// One Newtonian Euler step from rest leaves each star with exactly force * dt.
+fn kicks(device: &std::sync::Arc<wgpu::Device>, queue: &std::sync::Arc<wgpu::Queue>, config: &SimConfig, accel: Accel) -> Vec<[f32; 3]> {
+    let config = SimConfig { accel, ..config.clone() };
+    let mut stars = presets::from_config(&config, config.seed.unwrap());
+    for s in &mut stars {
+        (s.vx, s.vy, s.vz) = (0.0, 0.0, 0.0);
+    }
+    let mut sim = Simulation::with_stars(device.clone(), queue.clone(), &config, stars).unwrap();
+    sim.set_mode(DilationMode::Newtonian);
+    sim.step_and_read(1).iter().map(|s: &Star| [s.vx, s.vy, s.vz]).collect()
+}
+
+// RMS of the difference to `exact`, relative to the RMS of `exact`.
+fn relative_error(approx: &[[f32; 3]], exact: &[[f32; 3]]) -> f64 {
+    let sq = |v: [f32; 3]| v.iter().map(|&c| (c as f64).powi(2)).sum::<f64>();
+    let diff: f64 = approx.iter().zip(exact).map(|(a, e)| sq([a[0] - e[0], a[1] - e[1], a[2] - e[2]])).sum();
+    (diff / exact.iter().map(|&e| sq(e)).sum::<f64>()).sqrt()
+}
+
+#[test]
+fn grid_forces_track_the_brute_force_kernel() {
+    let (device, queue) = require_gpu!();
+    for central_mass in [None, Some(500.0)] {
+        // Chunked, so binning and neighbor lookups cross buffer boundaries.
+        let config = SimConfig { stars: 4096, seed: Some(11), central_mass, chunk_stars: Some(1100), ..SimConfig::default() };
+        let exact = kicks(&device, &queue, &config, Accel::Bruteforce);
+        let grid = relative_error(&kicks(&device, &queue, &config, Accel::Grid), &exact);
+        let sampled = relative_error(&kicks(&device, &queue, &config, Accel::Sampled), &exact);
+        // About 0.5% without a central mass on this seed; 32 random partners miss by far more.
+        assert!(grid < 0.02, "central {:?}: grid force error {}", central_mass, grid);
+        assert!(grid * 10.0 < sampled, "central {:?}: grid {} vs sampled {}", central_mass, grid, sampled);
+    }
+}
+
+#[test]
+fn accel_choices_and_grid_size() {
+    let limits = cpu_reference::limits();
+    let parse = |args: &[&str]| common::parse(args).validate(&limits);
+    assert_eq!(SimConfig::default().accel, Accel::Sampled);
+    assert!(parse(&["--accel", "grid"]).is_ok());
+    assert!(parse(&["--accel", "bruteforce", "--cpu"]).is_err());
+    assert!(parse(&["--accel", "grid", "--cpu"]).is_err());
+    assert!(parse(&["--cpu"]).is_ok());
+    // side^3 near sqrt(27 N), within bounds
+    assert_eq!(cells_per_side(4096), 7);
+    assert_eq!(cells_per_side(100_000), 12);
+    assert_eq!(cells_per_side(0), 2);
+    assert_eq!(cells_per_side(u32::MAX), 32);
+}
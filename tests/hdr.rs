@@ -0,0 +1,102 @@
+use clap::Parser;
+
+use chronoturin::{
+    cpu_reference, generate_galaxy, read_png_text, save_png16, Camera, Framebuffer, RenderSettings, RunManifest, SimConfig, Star,
+};
+
+fn framebuffer(config: &SimConfig) -> Framebuffer {
+    let cam = Camera::from_config(config);
+    let mut fb = Framebuffer::new(cam.width, cam.height);
+    // Every other star active, so the glow has something to show.
+    let stars: Vec<Star> =
+        generate_galaxy(4000, 3).into_iter().enumerate().map(|(i, s)| Star { active_flag: (i % 2) as f32, ..s }).collect();
+    fb.accumulate(&stars, &cam, &RenderSettings::from_config(config));
+    fb
+}
+
+#[test]
+fn png16_frames_draw_like_the_8_bit_frame() {
+    let config = SimConfig { width: 160, height: 120, central_mass: Some(500.0), exposure: 3.0, ..SimConfig::default() };
+    let settings = RenderSettings::from_config(&config);
+    let fb = framebuffer(&config);
+    for is_chronoturin in [false, true] {
+        let (png8, png16) = (fb.tonemap(&settings, is_chronoturin), fb.hdr(&settings, is_chronoturin).to_png16());
+        assert_eq!(png8.dimensions(), png16.dimensions());
+        // The same curve, quantized finer: within rounding of the 8-bit frame everywhere.
+        for (a, b) in png8.pixels().zip(png16.pixels()) {
+            for c in 0..3 {
+                assert!((a[c] as f32 - b[c] as f32 / 257.0).abs() <= 1.0, "{:?} vs {:?}", a, b);
+            }
+        }
+        assert!(png16.pixels().any(|p| p.0.iter().any(|&v| v % 257 != 0)), "no level between two 8-bit ones");
+    }
+
+    // The file is a 16-bit RGB PNG carrying the manifest.
+    let path = std::env::temp_dir().join(format!("chronoturin-png16-{}.png", std::process::id()));
+    let manifest = RunManifest::new(&config);
+    let png16 = fb.hdr(&settings, true).to_png16();
+    save_png16(&path, &png16, Some(&manifest)).unwrap();
+    assert_eq!(image::open(&path).unwrap().into_rgb16(), png16);
+    assert_eq!(read_png_text(&path).unwrap(), Some(manifest));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn hdr_channels_keep_what_8_bits_clip() {
+    let config = SimConfig { width: 160, height: 120, ..SimConfig::default() };
+    let settings = RenderSettings::from_config(&config);
+    let hdr = framebuffer(&config).hdr(&settings, false);
+    // The core is far past white, and the glow is only tinted in when drawn.
+    assert!(hdr.rgb.iter().any(|p| p[0] > 2.0));
+    assert!(hdr.glow.iter().any(|&g| g > 0.0));
+    assert_eq!(hdr.glow_tint, [0.0, 150.0 / 255.0, 0.0]);
+    assert_eq!(framebuffer(&config).hdr(&settings, true).glow_tint[0], 50.0 / 255.0);
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn exr_frames_carry_the_float_channels_by_stable_names() {
+    use chronoturin::{FrameSaver, ImageFormat, EXR_CHANNELS};
+    use exr::prelude::*;
+
+    let config = SimConfig { width: 64, height: 48, ..SimConfig::default() };
+    let hdr = framebuffer(&config).hdr(&RenderSettings::from_config(&config), true);
+    let dir = std::env::temp_dir().join(format!("chronoturin-exr-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut saver = FrameSaver::new(1, 1, Some(RunManifest::new(&config)));
+    saver.save_hdr(dir.join("frame_000.exr"), hdr.clone(), ImageFormat::Exr);
+    assert_eq!(saver.finish().frames_saved, 1);
+
+    let image = read_all_flat_layers_from_file(dir.join("frame_000.exr")).unwrap();
+    let layer = &image.layer_data[0];
+    assert_eq!((layer.size.0, layer.size.1), (64, 48));
+    let mut names: Vec<String> = layer.channel_data.list.iter().map(|c| c.name.to_string()).collect();
+    names.sort();
+    let mut expected = EXR_CHANNELS.map(String::from).to_vec();
+    expected.sort();
+    assert_eq!(names, expected);
+    let channel = |name: &str| match &layer.channel_data.list.iter().find(|c| c.name.eq(name)).unwrap().sample_data {
+        FlatSamples::F32(values) => values.clone(),
+        other => panic!("{} is not f32: {:?}", name, other),
+    };
+    assert_eq!(channel("R"), hdr.rgb.iter().map(|p| p[0]).collect::<Vec<_>>());
+    assert_eq!(channel("B"), hdr.rgb.iter().map(|p| p[2]).collect::<Vec<_>>());
+    assert_eq!(channel("glow.Y"), hdr.glow);
+    assert!(layer.attributes.comments.as_ref().is_some_and(|c| c.to_string().contains("\"stars\"")));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn float_formats_need_cpu_drawn_frame_files() {
+    let limits = cpu_reference::limits();
+    let parse = |args: &[&str]| SimConfig::parse_from(["chronoturin", "--image-format", "png16"].iter().chain(args)).validate(&limits);
+    assert!(parse(&[]).is_ok());
+    assert!(parse(&["--gif", "run.gif"]).is_ok());
+    assert!(parse(&["render", "dump.csv"]).is_ok());
+    assert!(parse(&["--raster", "gpu"]).is_err());
+    assert!(parse(&["--preview"]).is_err());
+    assert!(parse(&["--video", "run.mp4"]).is_err());
+    assert!(parse(&["--no-png"]).is_err());
+    assert!(parse(&["bench"]).is_err());
+    assert_eq!(SimConfig::parse_from(["chronoturin", "--image-format", "exr"]).validate(&limits).is_ok(), cfg!(feature = "exr"));
+}
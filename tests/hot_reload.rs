@@ -0,0 +1,97 @@
+mod common;
+
+use chronoturin::{cpu_reference, Precision, ShaderWatcher, SimConfig, Simulation, Star};
+
+use common::seeded;
+
+const SHADER: &str = include_str!("../src/shader.wgsl");
+const DAMPING: &str = "star.vx *= state.damping; star.vy *= state.damping; star.vz *= state.damping;";
+
+fn bits(stars: Vec<Star>) -> Vec<[u32; 4]> {
+    stars.iter().map(|s| [s.x.to_bits(), s.y.to_bits(), s.vz.to_bits(), s.active_flag.to_bits()]).collect()
+}
+
+#[test]
+fn reloaded_kernels_take_over_the_running_stars() {
+    let (device, queue) = require_gpu!();
+    let mut reference = Simulation::new(device.clone(), queue.clone(), &seeded(1000, 7)).unwrap();
+    let mut sim = Simulation::new(device, queue, &seeded(1000, 7)).unwrap();
+    pollster::block_on(sim.reload_kernels(SHADER)).unwrap();
+    assert_eq!(bits(reference.step_and_read(2)), bits(sim.step_and_read(2)));
+
+    // Errors point at the file's own lines, and the old kernels keep stepping.
+    let line = SHADER.lines().position(|l| l.contains("let dx = ")).unwrap() + 1;
+    let message = pollster::block_on(sim.reload_kernels(&SHADER.replacen("let dx = ", "let dx == ", 1))).unwrap_err();
+    assert!(message.contains(&format!("wgsl:{}:", line)), "{}", message);
+    let message = pollster::block_on(sim.reload_kernels(&SHADER.replace("256u;", "64u;"))).unwrap_err();
+    assert!(message.contains("WORKGROUP_SIZE"), "{}", message);
+    assert_eq!(bits(reference.step_and_read(2)), bits(sim.step_and_read(2)));
+
+    // Undamped kernels carry on from the stars the damped ones left.
+    pollster::block_on(sim.reload_kernels(&SHADER.replace(DAMPING, ""))).unwrap();
+    assert_ne!(bits(reference.step_and_read(2)), bits(sim.step_and_read(2)));
+}
+
+#[test]
+fn watcher_recompiles_when_the_file_changes() {
+    let (device, queue) = require_gpu!();
+    let path = std::env::temp_dir().join(format!("chronoturin-hot-reload-{}.wgsl", std::process::id()));
+    std::fs::write(&path, SHADER).unwrap();
+    let mut sim = Simulation::new(device.clone(), queue.clone(), &seeded(1000, 7)).unwrap();
+    let mut undamped = Simulation::new(device, queue, &seeded(1000, 7)).unwrap();
+    pollster::block_on(undamped.reload_kernels(&SHADER.replace(DAMPING, ""))).unwrap();
+
+    let mut watcher = ShaderWatcher::start(&path, &mut [&mut sim]).unwrap();
+    let mut loaded = 0;
+    // A broken file is reported and skipped; the next good one is picked up.
+    std::fs::write(&path, SHADER.replacen("let dx = ", "let dx == ", 1)).unwrap();
+    watcher.poll(&mut loaded, &mut [&mut sim]);
+    std::fs::write(&path, SHADER.replace(DAMPING, "")).unwrap();
+    watcher.poll(&mut loaded, &mut [&mut sim]);
+    assert_eq!(bits(undamped.step_and_read(2)), bits(sim.step_and_read(2)));
+
+    std::fs::write(&path, "not wgsl").unwrap();
+    assert!(ShaderWatcher::start(&path, &mut [&mut sim]).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+// Lockstep reloads both modes' steppers: when one of them cannot take the new source,
+// neither switches, and the next good file reaches both.
+#[test]
+fn watcher_switches_every_stepper_or_none() {
+    let (device, queue) = require_gpu!();
+    let path = std::env::temp_dir().join(format!("chronoturin-hot-reload-pair-{}.wgsl", std::process::id()));
+    std::fs::write(&path, SHADER).unwrap();
+    let df64 = SimConfig { precision: Precision::Df64, ..seeded(1000, 7) };
+    let mut reference = Simulation::new(device.clone(), queue.clone(), &seeded(1000, 7)).unwrap();
+    let mut df64_reference = Simulation::new(device.clone(), queue.clone(), &df64).unwrap();
+    let mut sim = Simulation::new(device.clone(), queue.clone(), &seeded(1000, 7)).unwrap();
+    let mut df64_sim = Simulation::new(device, queue, &df64).unwrap();
+
+    let mut watcher = ShaderWatcher::start(&path, &mut [&mut sim, &mut df64_sim]).unwrap();
+    let mut loaded = 0;
+    // Still fine for f32, but df64 has no `star.x += star.vx * h;` left to compensate.
+    let undamped = SHADER.replace(DAMPING, "");
+    std::fs::write(&path, undamped.replace("star.x += star.vx * h;", "star.x = star.x + star.vx * h;")).unwrap();
+    watcher.poll(&mut loaded, &mut [&mut sim, &mut df64_sim]);
+    assert_eq!(loaded, 0);
+    assert_eq!(bits(reference.step_and_read(2)), bits(sim.step_and_read(2)));
+    assert_eq!(bits(df64_reference.step_and_read(2)), bits(df64_sim.step_and_read(2)));
+
+    std::fs::write(&path, &undamped).unwrap();
+    watcher.poll(&mut loaded, &mut [&mut sim, &mut df64_sim]);
+    assert_eq!(loaded, 2);
+    assert_ne!(bits(reference.step_and_read(2)), bits(sim.step_and_read(2)));
+    assert_ne!(bits(df64_reference.step_and_read(2)), bits(df64_sim.step_and_read(2)));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn shader_applies_to_gpu_frame_runs() {
+    let limits = cpu_reference::limits();
+    let parse = |args: &[&str]| common::parse(args).validate(&limits);
+    assert!(parse(&["--shader", "my.wgsl"]).is_ok());
+    assert!(parse(&["--shader", "my.wgsl", "--cpu"]).is_err());
+    assert!(parse(&["--shader", "my.wgsl", "--compaction"]).is_err());
+    assert!(parse(&["--shader", "my.wgsl", "bench"]).is_err());
+}
@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use chronoturin::{generate_galaxy, read_initial_conditions, write_dump, ChronoError, DumpFormat, DumpInfo};
+
+fn write_temp(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("chronoturin-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn load_err(name: &str, contents: &str) -> ChronoError {
+    let path = write_temp(name, contents);
+    let err = read_initial_conditions(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    err
+}
+
+#[test]
+fn eight_column_rows_with_optional_header() {
+    let path = write_temp("ic.csv", "x,y,z,vx,vy,vz,mass,data_type\n1,2,3,0.5,0,0,2,1\n\n-4,5,6,0,0,-1,1,0\n");
+    let stars = read_initial_conditions(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(stars.len(), 2);
+    assert_eq!((stars[0].x, stars[0].vx, stars[0].mass, stars[0].data_type), (1.0, 0.5, 2.0, 1.0));
+    assert_eq!((stars[1].vz, stars[1].time_debt, stars[1].active_flag), (-1.0, 0.0, 0.0));
+
+    let headerless = write_temp("ic-noheader.csv", "1,2,3,0,0,0,1,0\n");
+    assert_eq!(read_initial_conditions(&headerless).unwrap().len(), 1);
+    std::fs::remove_file(&headerless).unwrap();
+}
+
+#[test]
+fn bad_rows_name_the_offending_line() {
+    let err = load_err("ic-cols.csv", "x,y,z,vx,vy,vz,mass,data_type\n1,2,3,0,0,0,1,0\n1,2,3,0,0,0,1\n");
+    assert!(matches!(err, ChronoError::Parse { line: 3, .. }), "{}", err);
+    assert!(err.to_string().contains("expected 8 or 10 columns, found 7"));
+
+    let err = load_err("ic-nan.csv", "1,2,3,0,0,0,1,0\n1,2,inf,0,0,0,1,0\n");
+    assert!(matches!(err, ChronoError::Parse { line: 2, .. }), "{}", err);
+    assert!(err.to_string().contains("z is not finite"));
+
+    let err = load_err("ic-text.csv", "1,2,3,0,0,0,1,0\n1,2,abc,0,0,0,1,0\n");
+    assert!(err.to_string().contains(":2:") && err.to_string().contains("abc"), "{}", err);
+
+    let err = load_err("ic-empty.csv", "x,y,z,vx,vy,vz,mass,data_type\n");
+    assert!(err.to_string().contains("no stars"));
+}
+
+#[test]
+fn dumps_seed_new_runs() {
+    let stars = generate_galaxy(50, 9);
+    let info = DumpInfo { mode: "NEWTONIAN", frame: 0, sim_time: 0.0 };
+    for format in [DumpFormat::Csv, DumpFormat::Bin] {
+        let path = std::env::temp_dir().join(format!("chronoturin-{}-ic-dump.{}", std::process::id(), format.extension()));
+        write_dump(&path, &stars, None, format, &info, None).unwrap();
+        let loaded = read_initial_conditions(&path).unwrap();
+        assert_eq!(bytemuck::cast_slice::<_, u8>(&loaded), bytemuck::cast_slice::<_, u8>(&stars), "{:?}", format);
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(chronoturin::dump::sidecar_path(&path));
+    }
+}
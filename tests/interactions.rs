@@ -0,0 +1,93 @@
+mod common;
+
+use chronoturin::galaxy::type_index;
+use chronoturin::interactions::{parse_interactions, InteractionEntry};
+use chronoturin::{cpu_reference, CpuSimulation, DilationMode, Interactions, Simulation, Star};
+
+use common::parse;
+
+// The share of each star's 8 nearest neighbours that have its type: about 0.5 for two
+// evenly mixed types, 1 once they have fully separated.
+fn same_type_neighbours(stars: &[Star]) -> f64 {
+    let mut same = 0;
+    for (i, a) in stars.iter().enumerate() {
+        let mut others: Vec<(f32, usize)> = stars
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(j, b)| ((a.x - b.x).powi(2) + (a.y - b.y).powi(2) + (a.z - b.z).powi(2), j))
+            .collect();
+        others.sort_by(|x, y| x.0.total_cmp(&y.0));
+        same += others[..8].iter().filter(|&&(_, j)| type_index(&stars[j]) == type_index(a)).count();
+    }
+    same as f64 / (8 * stars.len()) as f64
+}
+
+#[test]
+fn tables_parse_inline_and_from_toml() {
+    let entry = |on, by, value| InteractionEntry { on, by, value };
+    assert_eq!(parse_interactions("a_b=-0.5, 1_1 = 2").unwrap().0, [entry(0, 1, -0.5), entry(1, 1, 2.0)]);
+    assert!(parse_interactions("a_b").is_err());
+    assert!(parse_interactions("a_q=1").unwrap_err().contains("past the maximum of 15"));
+    assert!(parse_interactions("ab=1").is_err() && parse_interactions("a_b=inf").is_err());
+
+    let path = std::env::temp_dir().join(format!("chronoturin-interactions-{}.toml", std::process::id()));
+    std::fs::write(&path, "# two types\n[render]\nwidth = 3\n\n[interactions]\na_b = -0.5 # repel\n\"b_b\" = 0\n").unwrap();
+    assert_eq!(parse_interactions(path.to_str().unwrap()).unwrap().0, [entry(0, 1, -0.5), entry(1, 1, 0.0)]);
+    std::fs::write(&path, "a_b = 1\n").unwrap();
+    assert!(parse_interactions(path.to_str().unwrap()).unwrap_err().contains("line 1: keys go in the [interactions] table"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn the_matrix_is_symmetric_unless_asked() {
+    let limits = cpu_reference::limits();
+    // The default is the original rule: attract within a type, repel across.
+    assert_eq!(parse(&["--types", "3"]).interactions().unwrap().values(), [1.0, -1.0, -1.0, -1.0, 1.0, -1.0, -1.0, -1.0, 1.0]);
+    assert_eq!(parse(&["--interactions", "a_b=0.25"]).interactions().unwrap().values(), [1.0, 0.25, 0.25, 1.0]);
+
+    let error = parse(&["--interactions", "a_b=0.25,b_a=-1"]).validate(&limits).unwrap_err().to_string();
+    assert!(error.contains("a_b = 0.25 but b_a = -1") && error.contains("--asymmetric"), "{}", error);
+    let asymmetric = parse(&["--interactions", "a_b=0.25,b_a=-1", "--asymmetric"]);
+    assert!(asymmetric.validate(&limits).is_ok());
+    let matrix = asymmetric.interactions().unwrap();
+    assert_eq!((matrix.coefficient(0, 1), matrix.coefficient(1, 0)), (0.25, -1.0));
+    // Each a_b sets only its own entry now; b_b keeps its default.
+    assert_eq!(matrix.values(), [1.0, 0.25, -1.0, 1.0]);
+
+    assert!(parse(&["--interactions", "a_c=1"]).validate(&limits).unwrap_err().to_string().contains("--types 2 ends at b_b"));
+    assert!(parse(&["--interactions", "a_b=1,a_b=2", "--asymmetric"]).validate(&limits).is_err());
+    assert!(parse(&["--asymmetric"]).validate(&limits).is_err());
+    assert_eq!(Interactions::default_for(2).uniform().coefficients[0], [1.0, -1.0, -1.0, -1.0]);
+}
+
+#[test]
+fn mutually_repelling_types_segregate() {
+    // No pull within a type, so only the cross term moves the stars.
+    let run = |interactions: &str| {
+        let config = parse(&["--stars", "300", "--seed", "4", "--cold-start", "--scale-radius", "60", "--interactions", interactions]);
+        let mut cpu = CpuSimulation::new(&config);
+        cpu.set_mode(DilationMode::Newtonian);
+        let start = same_type_neighbours(cpu.initial_stars());
+        let stars = (0..150).map(|_| cpu.step_and_read(config.steps_per_frame)).last().unwrap();
+        (start, same_type_neighbours(&stars))
+    };
+    let (start, repelled) = run("a_a=0,b_b=0,a_b=-4");
+    assert!(start < 0.55 && repelled > 0.65, "{} -> {}", start, repelled);
+    let (_, attracted) = run("a_a=0,b_b=0,a_b=4");
+    assert!(attracted < 0.55, "{}", attracted);
+}
+
+#[test]
+fn the_gpu_reads_the_uploaded_matrix() {
+    let (device, queue) = require_gpu!();
+    let config = parse(&["--stars", "512", "--seed", "21", "--interactions", "a_a=0.5,a_b=2,b_a=-3", "--asymmetric"]);
+    let mut gpu = Simulation::new(device, queue, &config).unwrap();
+    let mut cpu = CpuSimulation::new(&config);
+    let (g, c) = (gpu.step_and_read(1), cpu.step_and_read(1));
+    let err = g.iter().zip(&c).map(|(g, c)| (g.x - c.x).abs().max((g.y - c.y).abs()).max((g.z - c.z).abs())).fold(0.0, f32::max);
+    assert!(err < 1e-3, "max position error {}", err);
+    // And the matrix matters: the default rule lands elsewhere.
+    let default = CpuSimulation::new(&parse(&["--stars", "512", "--seed", "21"])).step_and_read(1);
+    assert!(default.iter().zip(&c).any(|(d, c)| (d.x - c.x).abs() > 1e-3));
+}
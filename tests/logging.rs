@@ -0,0 +1,22 @@
+use clap::Parser;
+use tracing_subscriber::filter::LevelFilter;
+
+use chronoturin::logging::level;
+use chronoturin::SimConfig;
+
+#[test]
+fn verbosity_flags_pick_the_console_level() {
+    let level_of = |args: &[&str]| level(&SimConfig::parse_from(std::iter::once("chronoturin").chain(args.iter().copied())));
+    assert_eq!(level_of(&[]), LevelFilter::INFO);
+    assert_eq!(level_of(&["-v"]), LevelFilter::DEBUG);
+    assert_eq!(level_of(&["-vv"]), LevelFilter::TRACE);
+    assert_eq!(level_of(&["--verbose", "--verbose", "--verbose"]), LevelFilter::TRACE);
+    assert_eq!(level_of(&["--quiet"]), LevelFilter::WARN);
+}
+
+#[test]
+fn quiet_and_verbose_conflict() {
+    assert!(SimConfig::try_parse_from(["chronoturin", "-q", "-v"]).is_err());
+    let config = SimConfig::parse_from(["chronoturin", "--log-file", "run.log"]);
+    assert_eq!(config.log_file.as_deref(), Some(std::path::Path::new("run.log")));
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use std::path::PathBuf;
+
+use clap::CommandFactory;
+use image::RgbImage;
+
+use chronoturin::config_file::{resolve, to_toml};
+use chronoturin::{
+    generate_galaxy, inspect, read_csv_dump, read_dump_manifest, read_initial_conditions, read_png_text, save_png, write_dump, Checkpoint,
+    DilationMode, DumpFormat, DumpInfo, Integrator, RunManifest, SimConfig, Snapshot,
+};
+
+use common::parse;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chronoturin-manifest-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn every_artifact_reads_back_the_same_manifest() {
+    let dir = temp_dir("artifacts");
+    let manifest = RunManifest::new(&parse(&["--stars", "300", "--seed", "7", "--boundary", "reflect:50"]));
+    assert_eq!(manifest.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(manifest.config["seed"], 7);
+
+    let png = dir.join("frame.png");
+    save_png(&png, &RgbImage::new(8, 4), Some(&manifest)).unwrap();
+    assert_eq!(image::open(&png).unwrap().width(), 8);
+    assert_eq!(RunManifest::read(&png).unwrap(), manifest);
+    // A path that is not Latin-1 goes in an iTXt chunk instead.
+    let unicode = RunManifest::new(&parse(&["--out-dir", "выход"]));
+    save_png(&png, &RgbImage::new(8, 4), Some(&unicode)).unwrap();
+    assert_eq!(read_png_text(&png).unwrap(), Some(unicode));
+    save_png(&png, &RgbImage::new(8, 4), None).unwrap();
+    assert_eq!(read_png_text(&png).unwrap(), None);
+    assert!(RunManifest::read(&png).unwrap_err().to_string().contains("has no chronoturin-manifest text chunk"));
+
+    let run_json = dir.join("run.json");
+    manifest.write(&run_json).unwrap();
+    assert_eq!(RunManifest::read(&run_json).unwrap(), manifest);
+
+    let stars = generate_galaxy(20, 3);
+    let info = DumpInfo { mode: DilationMode::Newtonian.name(), frame: 2, sim_time: 0.5 };
+    for format in [DumpFormat::Csv, DumpFormat::Bin] {
+        let path = dir.join(format!("newton_002.{}", format.extension()));
+        write_dump(&path, &stars, None, format, &info, Some(&manifest)).unwrap();
+        assert_eq!(read_dump_manifest(&path).unwrap().as_ref(), Some(&manifest));
+        assert_eq!(Snapshot::read(&path).unwrap().manifest.as_ref(), Some(&manifest));
+        // The manifest line leaves the stars readable as a dump and as initial conditions.
+        assert_eq!(read_initial_conditions(&path).unwrap().len(), 20);
+    }
+    assert_eq!(read_csv_dump(&dir.join("newton_002.csv")).unwrap().len(), 20);
+    let sidecar = dir.join("newton_002.bin.json");
+    assert_eq!(RunManifest::read(&sidecar).unwrap(), manifest);
+
+    let path = dir.join("checkpoint.bin");
+    let checkpoint = Checkpoint { seed: 7, pass: 0, next_frame: 1, manifest: Some(manifest.clone()), runs: Vec::new() };
+    checkpoint.save(&path).unwrap();
+    assert_eq!(Checkpoint::load(&path).unwrap().manifest, Some(manifest.clone()));
+
+    // inspect prints it from any of them, after the stars for snapshots.
+    let printed = inspect(&path).unwrap();
+    assert!(printed.contains(&format!("Written by chronoturin {}", manifest.version)), "{}", printed);
+    assert!(printed.contains("\nseed = 7\n") && printed.contains("\nboundary = \"reflect:50\"\n"), "{}", printed);
+    assert!(inspect(&run_json).unwrap().starts_with(&format!("{}: run manifest\nWritten by chronoturin", run_json.display())));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// The config inspect prints is a --config file for the same run.
+#[test]
+fn described_configs_rerun_the_same_flags() {
+    let dir = temp_dir("describe");
+    let original = parse(&["--stars", "500", "--seed", "3", "--integrator", "leapfrog", "sweep", "--stars", "100,200"]);
+    let described = RunManifest::new(&original).describe();
+    let (_, toml) = described.split_once('\n').unwrap();
+    let path = dir.join("rerun.toml");
+    std::fs::write(&path, toml).unwrap();
+    let matches = SimConfig::command().get_matches_from(["chronoturin", "--config", path.to_str().unwrap()]);
+    assert_eq!(to_toml(&resolve(&matches).unwrap()), to_toml(&original));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// tests/golden/pre_serde/run.json is the manifest of `--stars 5000 --seed 7 --integrator
+// leapfrog --g 0.25 --boundary periodic:500` from a build before the data types were
+// serialized; its config still reads.
+#[test]
+fn an_earlier_manifest_still_reads() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/pre_serde/run.json");
+    let config: SimConfig = serde_json::from_value(RunManifest::read(&path).unwrap().config).unwrap();
+    assert_eq!((config.stars, config.seed, config.integrator, config.g), (5000, Some(7), Integrator::Leapfrog, 0.25));
+    assert_eq!(config.boundary.side(), Some(500.0));
+}
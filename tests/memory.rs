@@ -0,0 +1,92 @@
+mod common;
+
+use chronoturin::{ChronoError, MemoryPlan, RunManifest, SimConfig, Simulation, Star};
+
+use common::parse;
+
+fn bytes(plan: &MemoryPlan, name: &str) -> Option<u64> {
+    plan.allocations.iter().find(|a| a.name == name).map(|a| a.bytes)
+}
+
+#[test]
+fn the_plan_follows_the_flags() {
+    let limits = wgpu::Limits::default();
+    let star = std::mem::size_of::<Star>() as u64;
+    let plan = MemoryPlan::new(&parse(&["--stars", "1000", "--raster", "cpu"]), &limits, 1).unwrap();
+    assert_eq!(bytes(&plan, "star storage"), Some(1000 * star));
+    assert_eq!(bytes(&plan, "star readback"), Some(2 * 1000 * star));
+    assert_eq!(bytes(&plan, "sort keys"), None);
+    assert_eq!(plan.total(), 3 * 1000 * star);
+    assert_eq!(plan.largest(), 1000 * star);
+
+    let side_by_side = MemoryPlan { simulations: 2, ..plan.clone() };
+    assert_eq!(side_by_side.total(), 2 * plan.total());
+
+    let packed = MemoryPlan::new(&parse(&["--stars", "1000", "--raster", "cpu", "--readback", "packed"]), &limits, 1).unwrap();
+    assert_eq!(bytes(&packed, "record readback"), Some(2 * 1000 * 16));
+    assert_eq!(bytes(&packed, "star readback"), None);
+
+    let trails = MemoryPlan::new(&parse(&["--stars", "1000", "--trails", "--steps-per-frame", "5"]), &limits, 1).unwrap();
+    assert_eq!(bytes(&trails, "trail readback"), Some(2 * 4 * 1000 * 16));
+
+    let sorted =
+        MemoryPlan::new(&parse(&["--stars", "1000", "--raster", "cpu", "--sort-every", "5", "--accel", "grid"]), &limits, 1).unwrap();
+    assert_eq!(bytes(&sorted, "sort keys"), Some(1024 * 8));
+    assert_eq!(bytes(&sorted, "sort scratch"), Some(1000 * star));
+    assert!(bytes(&sorted, "grid cells").is_some() && bytes(&sorted, "grid bins") == Some(2 * 1000 * 4));
+    assert!(bytes(&MemoryPlan::new(&parse(&["--stars", "1000"]), &limits, 1).unwrap(), "raster accumulation").is_some());
+}
+
+#[test]
+fn an_overrun_suggests_what_would_fit() {
+    let limits = wgpu::Limits::default();
+    let config = parse(&["--stars", "1000000", "--raster", "cpu", "--memory-budget", "100"]);
+    let plan = MemoryPlan::new(&config, &limits, 2).unwrap();
+    let err = plan.check(&config, &limits).unwrap_err();
+    let ChronoError::OverMemory { reason, mitigations } = &err else { panic!("{:?}", err) };
+    assert!(reason.contains("over --memory-budget 100"), "{}", reason);
+    assert!(mitigations[0].starts_with("--sequential"), "{:?}", mitigations);
+    assert!(mitigations.iter().any(|m| m.starts_with("--readback packed")), "{:?}", mitigations);
+    assert!(err.to_string().contains("to fit, try:\n  - --sequential"), "{}", err);
+
+    // The suggested star count is the most that fits.
+    let stars: u32 = mitigations.last().unwrap().strip_prefix("--stars ").unwrap().split(' ').next().unwrap().parse().unwrap();
+    let with = |stars| MemoryPlan::new(&SimConfig { stars, ..config.clone() }, &limits, 2).unwrap();
+    assert!(with(stars).check(&config, &limits).is_ok());
+    assert!(with(stars + 1).check(&config, &limits).is_err());
+    assert!(MemoryPlan { simulations: 1, ..plan }.check(&SimConfig { memory_budget: Some(200), ..config }, &limits).is_ok());
+}
+
+#[test]
+fn one_buffer_past_the_device_limits_is_an_overrun() {
+    let limits = wgpu::Limits::default();
+    // 4096x4096 pixels of three u32s is 192 MiB of accumulation, past the 128 MiB binding.
+    let config = parse(&["--stars", "1000", "--width", "4096", "--height", "4096"]);
+    let reason = MemoryPlan::new(&config, &limits, 1).unwrap().overrun(&limits, None).unwrap();
+    assert!(reason.contains("raster accumulation") && reason.contains("per storage binding"), "{}", reason);
+    assert!(MemoryPlan::new(&SimConfig { width: 1024, height: 1024, ..config.clone() }, &limits, 1)
+        .unwrap()
+        .overrun(&limits, None)
+        .is_none());
+    assert!(SimConfig { memory_budget: Some(0), ..config }.validate(&limits).is_err());
+}
+
+#[test]
+fn the_manifest_carries_the_plan() {
+    let config = parse(&["--stars", "500"]);
+    let plan = MemoryPlan::new(&config, &wgpu::Limits::default(), 2).unwrap();
+    let manifest = RunManifest { memory: Some(plan), ..RunManifest::new(&config) };
+    assert_eq!(RunManifest::from_json(&manifest.to_json()).unwrap(), manifest);
+    assert!(manifest.describe().contains("MiB of GPU buffers planned"), "{}", manifest.describe());
+    assert_eq!(RunManifest::from_json(&RunManifest::new(&config).to_json()).unwrap().memory, None);
+}
+
+#[test]
+fn the_plan_matches_what_a_simulation_allocates() {
+    let (device, queue) = require_gpu!();
+    let config = parse(&["--stars", "3000", "--chunk-stars", "1024", "--seed", "2"]);
+    let plan = MemoryPlan::new(&config, &device.limits(), 1).unwrap();
+    let sim = Simulation::new(device, queue, &config).unwrap();
+    assert_eq!(bytes(&plan, "star storage"), Some(sim.star_buffer_size()));
+    assert_eq!(plan.allocations[0].buffers as usize, sim.chunk_count());
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use chronoturin::{cpu_reference, is_dead, render_frame, Camera, CpuSimulation, DilationMode, FrameData, RenderSettings, Simulation, Star};
+
+use common::parse;
+
+fn star(x: f32, vx: f32) -> Star {
+    Star { x, vx, ..common::star() }
+}
+
+// Two equal stars flying at each other along x. Run with --g 0 and no damping they coast
+// (sampled pulls need not be equal and opposite), so the pair's momentum is exactly zero.
+fn head_on() -> Vec<Star> {
+    vec![star(-20.0, 5.0), star(20.0, -5.0)]
+}
+
+// Runs frames until one star is left, then checks it carries both masses and no net momentum.
+fn assert_one_survivor(mut frame: impl FnMut() -> FrameData) {
+    let mut last = None;
+    for _ in 0..300 {
+        let data = frame();
+        let done = data.alive == 1;
+        last = Some(data);
+        if done {
+            break;
+        }
+    }
+    let data = last.unwrap();
+    assert_eq!(data.alive, 1, "the stars never merged");
+    assert_eq!(data.mergers, Some(1));
+    let living = data.living();
+    assert_eq!(living.len(), 1);
+    let survivor = living[0];
+    assert_eq!(survivor.mass, 2.0);
+    let momentum = [survivor.vx, survivor.vy, survivor.vz].map(|v| v * survivor.mass);
+    assert!((survivor.x.abs(), survivor.y, survivor.z) == (0.0, 0.0, 0.0), "{:?}", survivor);
+    assert_eq!(momentum, [0.0; 3]);
+    // The lower index survives; the other is culled, its mass handed over.
+    assert!(!is_dead(&data.stars[0]) && is_dead(&data.stars[1]));
+    assert_eq!(data.stars[1].mass, 0.0);
+}
+
+#[test]
+fn merge_radius_is_validated() {
+    let limits = cpu_reference::limits();
+    assert!(parse(&["--merge-radius", "4", "--accel", "grid"]).validate(&limits).is_ok());
+    assert!(parse(&["--merge-radius", "4", "--cpu"]).validate(&limits).is_ok());
+    assert!(parse(&["--merge-radius=-1"]).validate(&limits).is_err());
+    assert!(parse(&["--merge-radius", "4"]).validate(&limits).unwrap_err().to_string().contains("--accel grid"));
+    assert!(parse(&["--merge-radius", "4", "--accel", "grid", "--readback", "packed"]).validate(&limits).is_err());
+    assert_eq!(parse(&["--merge-radius", "4"]).cull_radius(), Some(f32::INFINITY));
+    assert_eq!(parse(&["--merge-radius", "4", "--kill-radius", "900"]).cull_radius(), Some(900.0));
+    assert_eq!(parse(&[]).cull_radius(), None);
+}
+
+#[test]
+fn a_head_on_pair_merges_on_the_cpu() {
+    let config = parse(&["--merge-radius", "4", "--g", "0", "--integrator", "leapfrog", "--cpu"]);
+    let mut cpu = CpuSimulation::with_stars(&config, head_on());
+    cpu.set_mode(DilationMode::Newtonian);
+    assert_one_survivor(|| cpu.run_frame(1));
+}
+
+#[test]
+fn a_head_on_pair_merges_on_the_gpu() {
+    let (device, queue) = require_gpu!();
+    let config = parse(&["--merge-radius", "4", "--g", "0", "--integrator", "leapfrog", "--accel", "grid"]);
+    let mut gpu = Simulation::with_stars(device, queue, &config, head_on()).unwrap();
+    gpu.set_mode(DilationMode::Newtonian);
+    assert_one_survivor(|| {
+        let ticket = gpu.submit_frame(1);
+        gpu.finish_frame(ticket)
+    });
+}
+
+#[test]
+fn distant_stars_do_not_merge() {
+    let config = parse(&["--stars", "300", "--seed", "3", "--merge-radius", "0.001", "--cpu"]);
+    let mut cpu = CpuSimulation::new(&config);
+    let data = cpu.run_frame(1);
+    assert_eq!((data.alive, data.mergers), (300, Some(0)));
+    assert_eq!(CpuSimulation::new(&parse(&["--stars", "300", "--cpu"])).run_frame(1).mergers, None);
+}
+
+#[test]
+fn merged_stars_render_brighter() {
+    let cam = Camera::look_at([0.0, 0.0, -100.0], [0.0; 3], [0.0, 1.0, 0.0], 32, 32, 100.0);
+    let settings = RenderSettings { mass_weighted: true, ..RenderSettings::legacy() };
+    let brightness = |mass: f32| {
+        let img = render_frame(&[Star { mass, ..star(0.0, 0.0) }], &cam, &settings, false);
+        img.pixels().map(|p| p.0.iter().map(|&c| c as u32).sum::<u32>()).sum::<u32>()
+    };
+    assert!(brightness(2.0) > brightness(1.0));
+    let plain = render_frame(&[Star { mass: 2.0, ..star(0.0, 0.0) }], &cam, &RenderSettings::legacy(), false);
+    assert_eq!(plain, render_frame(&[star(0.0, 0.0)], &cam, &RenderSettings::legacy(), false));
+}
+
+// A dense cluster merges many pairs in one pass; the claims keep every unit of mass in
+// exactly one living star.
+#[test]
+fn gpu_mergers_conserve_mass_in_a_crowd() {
+    let (device, queue) = require_gpu!();
+    let config = parse(&["--stars", "500", "--seed", "4", "--scale-radius", "20", "--accel", "grid", "--merge-radius", "1.5"]);
+    let mut gpu = Simulation::new(device, queue, &config).unwrap();
+    for _ in 0..5 {
+        let ticket = gpu.submit_frame(1);
+        let data = gpu.finish_frame(ticket);
+        let mass: f32 = data.living().iter().map(|s| s.mass).sum();
+        assert_eq!(mass, 500.0);
+        assert_eq!(data.mergers, Some(500 - data.alive));
+    }
+    let ticket = gpu.submit_frame(1);
+    assert!(gpu.finish_frame(ticket).alive < 500, "nothing merged");
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use chronoturin::{Conservation, DilationMode, MetricsFormat, MetricsRecord, MetricsWriter};
+
+use common::record;
+
+#[test]
+fn csv_rows_are_flushed_per_frame() {
+    let path = std::env::temp_dir().join(format!("chronoturin-metrics-{}.csv", std::process::id()));
+    let mut writer = MetricsWriter::create(&path, MetricsFormat::Csv).unwrap();
+    writer.write(&MetricsRecord { gpu_ms: Some(1.5), ..record() }).unwrap();
+    writer.write(&MetricsRecord { frame: 1, ..record() }).unwrap();
+    writer.write(&MetricsRecord { error_mean: Some(0.25), error_p99: Some(1.5), frame: 2, ..record() }).unwrap();
+    let conservation =
+        Conservation { kinetic: 1250.0, potential: Some(-3000.0), momentum: [0.5, 0.0, -2.0], angular_momentum: [0.0, 0.0, 1.0e6] };
+    writer.write(&MetricsRecord { conservation: Some(conservation), frame: 3, ..record() }).unwrap();
+    writer.write(&MetricsRecord { force_threads: Some(1_310_720), frame: 4, ..record() }).unwrap();
+    writer.write(&MetricsRecord { adapter: Some(1), frame: 5, ..record() }).unwrap();
+    writer.write(&MetricsRecord { debt_histogram: vec![4000, 6000, 0], frame: 6, ..record() }).unwrap();
+    writer.write(&MetricsRecord { alive: 9950, mergers: Some(40), frame: 7, ..record() }).unwrap();
+    writer.write(&MetricsRecord { substeps: Some(2600), frame: 8, ..record() }).unwrap();
+    let radii = Some([12.5, 40.0, 101.25, 300.0, 2000.0]);
+    writer.write(&MetricsRecord { lagrangian_radii: radii, frame: 9, ..record() }).unwrap();
+
+    // Read back while the writer is still alive, as a crash would leave it.
+    let text = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "mode,frame,wall_ms,gpu_ms,active_count,stars,sim_time,error_mean,error_p99,\
+kinetic_energy,potential_energy,total_energy,momentum_x,momentum_y,momentum_z,\
+angular_momentum_x,angular_momentum_y,angular_momentum_z,dilation_threshold,max_debt,readback_ms,readback_bytes,force_threads,adapter,active_by_type,radial_profile,debt_histogram,interactions,alive,mergers,substeps,placeholder,lagrangian_radii,virial_ratio");
+    assert_eq!(lines[1], "CHRONOTURIN,0,12.500,1.5000,2341,10000,0.2500,,,,,,,,,,,,0.5,50,0.750,160016,,,1200;1141,9000;990;10,,1;-0.5;-0.5;1,9990,,,false,,");
+    assert_eq!(
+        lines[2],
+        "CHRONOTURIN,1,12.500,,2341,10000,0.2500,,,,,,,,,,,,0.5,50,0.750,160016,,,1200;1141,9000;990;10,,1;-0.5;-0.5;1,9990,,,false,,"
+    );
+    assert_eq!(lines[3], "CHRONOTURIN,2,12.500,,2341,10000,0.2500,0.25000,1.50000,,,,,,,,,,0.5,50,0.750,160016,,,1200;1141,9000;990;10,,1;-0.5;-0.5;1,9990,,,false,,");
+    assert_eq!(lines[4], "CHRONOTURIN,3,12.500,,2341,10000,0.2500,,,1.250000e3,-3.000000e3,-1.750000e3,\
+5.000000e-1,0.000000e0,-2.000000e0,0.000000e0,0.000000e0,1.000000e6,0.5,50,0.750,160016,,,1200;1141,9000;990;10,,1;-0.5;-0.5;1,9990,,,false,,0.83333");
+    assert_eq!(lines[5], "CHRONOTURIN,4,12.500,,2341,10000,0.2500,,,,,,,,,,,,0.5,50,0.750,160016,1310720,,1200;1141,9000;990;10,,1;-0.5;-0.5;1,9990,,,false,,");
+    assert_eq!(
+        lines[6],
+        "CHRONOTURIN,5,12.500,,2341,10000,0.2500,,,,,,,,,,,,0.5,50,0.750,160016,,1,1200;1141,9000;990;10,,1;-0.5;-0.5;1,9990,,,false,,"
+    );
+    assert_eq!(lines[7], "CHRONOTURIN,6,12.500,,2341,10000,0.2500,,,,,,,,,,,,0.5,50,0.750,160016,,,1200;1141,9000;990;10,4000;6000;0,1;-0.5;-0.5;1,9990,,,false,,");
+    assert_eq!(
+        lines[8],
+        "CHRONOTURIN,7,12.500,,2341,10000,0.2500,,,,,,,,,,,,0.5,50,0.750,160016,,,1200;1141,9000;990;10,,1;-0.5;-0.5;1,9950,40,,false,,"
+    );
+    assert_eq!(
+        lines[9],
+        "CHRONOTURIN,8,12.500,,2341,10000,0.2500,,,,,,,,,,,,0.5,50,0.750,160016,,,1200;1141,9000;990;10,,1;-0.5;-0.5;1,9990,,2600,false,,"
+    );
+    assert_eq!(
+        lines[10],
+        "CHRONOTURIN,9,12.500,,2341,10000,0.2500,,,,,,,,,,,,0.5,50,0.750,160016,,,1200;1141,9000;990;10,,1;-0.5;-0.5;1,9990,,,false,\
+12.500;40.000;101.250;300.000;2000.000,"
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn json_lines_use_null_for_missing_gpu_time() {
+    let path = std::env::temp_dir().join(format!("chronoturin-metrics-{}.jsonl", std::process::id()));
+    let mut writer = MetricsWriter::create(&path, MetricsFormat::Json).unwrap();
+    writer.write(&MetricsRecord { frame: 3, ..record() }).unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        text.trim_end(),
+        concat!(
+            r#"{"mode":"CHRONOTURIN","frame":3,"wall_ms":12.5,"gpu_ms":null,"active_count":2341,"stars":10000,"sim_time":0.25,"error_mean":null,"error_p99":null,"#,
+            r#""kinetic_energy":null,"potential_energy":null,"total_energy":null,"momentum_x":null,"momentum_y":null,"momentum_z":null,"#,
+            r#""angular_momentum_x":null,"angular_momentum_y":null,"angular_momentum_z":null,"virial_ratio":null,"dilation_threshold":0.5,"max_debt":50.0,"readback_ms":0.75,"readback_bytes":160016,"force_threads":null,"adapter":null,"active_by_type":[1200,1141],"radial_profile":[9000,990,10],"debt_histogram":[],"interactions":[1.0,-0.5,-0.5,1.0],"alive":9990,"mergers":null,"substeps":null,"placeholder":false,"lagrangian_radii":null}"#
+        )
+    );
+    let radii = MetricsRecord { lagrangian_radii: Some([1.0, 2.0, 3.5, 4.0, 5.0]), frame: 3, ..record() }.json();
+    assert!(radii.ends_with(r#""lagrangian_radii":[1.0,2.0,3.5,4.0,5.0]}"#), "{}", radii);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn placeholder_rows_keep_the_frame_and_measure_nothing() {
+    let path = std::env::temp_dir().join(format!("chronoturin-metrics-placeholder-{}.csv", std::process::id()));
+    let mut writer = MetricsWriter::create(&path, MetricsFormat::Csv).unwrap();
+    writer.write(&MetricsRecord::placeholder(DilationMode::Newtonian, 41, 30.5, 1.25, (0.5, 50.0))).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(text.lines().nth(1), Some("NEWTONIAN,41,30.500,,0,0,1.2500,,,,,,,,,,,,0.5,50,0.000,0,,,,,,,0,,,true,,"));
+    assert!(MetricsRecord::placeholder(DilationMode::Newtonian, 41, 30.5, 1.25, (0.5, 50.0))
+        .json()
+        .ends_with(",\"placeholder\":true,\"lagrangian_radii\":null}"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+// Rows a build from before MetricsRecord was serialized wrote, with its own formatter, read
+// back into records that write the same columns, to the precision it printed.
+#[test]
+fn json_rows_from_before_the_derive_still_read_back() {
+    let text = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/pre_serde/metrics.jsonl")).unwrap();
+    fn close(old: &serde_json::Value, new: &serde_json::Value) -> bool {
+        match (old, new) {
+            (serde_json::Value::Number(a), serde_json::Value::Number(b)) => {
+                let (a, b) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+                (a - b).abs() <= 1e-3 + 1e-5 * a.abs()
+            }
+            (serde_json::Value::Array(a), serde_json::Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| close(a, b)),
+            _ => old == new,
+        }
+    }
+    let mut modes = Vec::new();
+    for line in text.lines() {
+        let record: MetricsRecord = serde_json::from_str(line).unwrap();
+        assert!(record.conservation.is_some());
+        modes.push(record.mode);
+        let old: serde_json::Map<String, serde_json::Value> = serde_json::from_str(line).unwrap();
+        let new: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&record.json()).unwrap();
+        let (mut old_keys, mut new_keys) = (old.keys().collect::<Vec<_>>(), new.keys().collect::<Vec<_>>());
+        old_keys.sort();
+        new_keys.sort();
+        assert_eq!(old_keys, new_keys);
+        for (key, value) in &old {
+            assert!(close(value, &new[key]), "{}: {} was {}", key, new[key], value);
+        }
+    }
+    assert_eq!(modes, [DilationMode::Newtonian, DilationMode::Chronoturin, DilationMode::Newtonian, DilationMode::Chronoturin]);
+}
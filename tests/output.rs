@@ -0,0 +1,102 @@
+mod common;
+
+use std::path::PathBuf;
+
+use chronoturin::output::frame_number_width;
+use chronoturin::{DilationMode, ImageFormat, OutputLayout, SimConfig};
+
+fn temp_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chronoturin-{}-{}", name, std::process::id()))
+}
+
+#[test]
+fn padding_scales_with_frame_count() {
+    assert_eq!(frame_number_width(1), 3);
+    assert_eq!(frame_number_width(150), 3);
+    assert_eq!(frame_number_width(1000), 3);
+    assert_eq!(frame_number_width(1001), 4);
+    assert_eq!(frame_number_width(10_000), 4);
+    assert_eq!(frame_number_width(100_001), 6);
+}
+
+#[test]
+fn default_layout_keeps_flat_names() {
+    let layout = OutputLayout::prepare(&SimConfig::default()).unwrap();
+    assert_eq!(layout.frame_path(DilationMode::Newtonian, 7), PathBuf::from("newton_007.png"));
+    assert_eq!(layout.frame_path(DilationMode::Chronoturin, 42), PathBuf::from("chrono_042.png"));
+    assert_eq!(layout.comparison_path(42), PathBuf::from("compare_042.png"));
+    assert_eq!(layout.screenshot_path(0), PathBuf::from("screenshot_000.png"));
+}
+
+#[test]
+fn out_dir_nests_frames_per_mode() {
+    let root = temp_dir("layout");
+    let config = SimConfig { out_dir: Some(root.clone()), prefix: "img".into(), frames: 10_000, sequential: true, ..SimConfig::default() };
+    let layout = OutputLayout::prepare(&config).unwrap();
+    assert_eq!(layout.frame_path(DilationMode::Newtonian, 3), root.join("newton").join("img_0003.png"));
+    assert!(root.join("chrono").is_dir());
+
+    // Now non-empty: refused unless forced.
+    assert!(OutputLayout::prepare(&config).is_err());
+    assert!(OutputLayout::prepare(&SimConfig { force: true, ..config }).is_ok());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn side_by_side_frames_get_their_own_directory() {
+    let root = temp_dir("compare");
+    let config = SimConfig { out_dir: Some(root.clone()), ..SimConfig::default() };
+    let layout = OutputLayout::prepare(&config).unwrap();
+    assert_eq!(layout.comparison_path(5), root.join("compare").join("frame_005.png"));
+    assert!(root.join("compare").is_dir());
+    assert!(!root.join("newton").exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn float_frames_are_written_per_mode() {
+    let root = temp_dir("exr");
+    let config = SimConfig { out_dir: Some(root.clone()), image_format: ImageFormat::Exr, ..SimConfig::default() };
+    let layout = OutputLayout::prepare(&config).unwrap();
+    assert_eq!(layout.frame_path(DilationMode::Chronoturin, 5), root.join("chrono").join("frame_005.exr"));
+    assert!(root.join("newton").is_dir() && !root.join("compare").exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn view_runs_only_create_the_screenshot_directory() {
+    let root = temp_dir("view");
+    let config = SimConfig { out_dir: Some(root.clone()), view: true, ..SimConfig::default() };
+    let layout = OutputLayout::prepare(&config).unwrap();
+    assert_eq!(layout.screenshot_path(2), root.join("screenshots").join("frame_002.png"));
+    assert!(root.join("screenshots").is_dir());
+    assert!(!root.join("compare").exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn thinned_runs_keep_the_simulation_frame_numbers() {
+    let parse = |args: &[&str]| {
+        let mut config = common::parse(args);
+        config.apply_command();
+        config
+    };
+    let every = parse(&["--save-every", "5"]);
+    assert_eq!((0..12).filter(|&f| every.draws_frame(f)).collect::<Vec<_>>(), [0, 5, 10]);
+    assert!(!(0..12).any(|f| parse(&["--no-images"]).draws_frame(f)));
+    // Nothing takes simulate's frames without --images, unless something streams or encodes them.
+    assert!(!parse(&["simulate"]).draws_frame(0));
+    assert!(parse(&["--gif", "g.gif", "simulate"]).draws_frame(0));
+
+    let preview = parse(&["--preview", "--save-every", "4", "--width", "800", "--height", "600"]);
+    assert_eq!(preview.frame_size(), (200, 150, 200.0));
+    let layout = OutputLayout::prepare(&preview).unwrap();
+    assert_eq!(layout.comparison_path(8), PathBuf::from("compare_008.jpg"));
+    assert_eq!(layout.frame_path(DilationMode::Newtonian, 8), PathBuf::from("newton_008.jpg"));
+
+    let limits = chronoturin::cpu_reference::limits();
+    assert!(preview.validate(&limits).is_ok());
+    assert!(parse(&["--save-every", "0"]).validate(&limits).is_err());
+    assert!(parse(&["--no-images", "--gif", "g.gif"]).validate(&limits).is_err());
+    assert!(parse(&["--preview", "bench"]).validate(&limits).is_err());
+}
@@ -0,0 +1,35 @@
+use chronoturin::overlay::{CHRONOTURIN_LABEL, NEWTONIAN_LABEL};
+use chronoturin::{draw_overlay, DilationMode, OverlayInfo};
+use image::{Rgb, RgbImage};
+
+fn info(mode: DilationMode) -> OverlayInfo {
+    OverlayInfo { mode, frame: 42, sim_time: 10.5, active_fraction: 0.043 }
+}
+
+#[test]
+fn overlay_stays_in_the_top_left_corner() {
+    let mut img = RgbImage::from_pixel(1024, 1024, Rgb([100, 100, 100]));
+    draw_overlay(&mut img, &info(DilationMode::Newtonian));
+    // The strip darkens what is under it rather than replacing it.
+    assert_eq!(img.get_pixel(0, 0), &Rgb([40, 40, 40]));
+    // The galaxy core and the rest of the frame are untouched.
+    assert_eq!(img.get_pixel(512, 512), &Rgb([100, 100, 100]));
+    assert_eq!(img.get_pixel(0, 100), &Rgb([100, 100, 100]));
+}
+
+#[test]
+fn mode_label_uses_mode_color() {
+    for (mode, color) in [(DilationMode::Newtonian, NEWTONIAN_LABEL), (DilationMode::Chronoturin, CHRONOTURIN_LABEL)] {
+        let mut img = RgbImage::new(512, 512);
+        draw_overlay(&mut img, &info(mode));
+        assert!(img.pixels().any(|p| *p == color), "{:?}", mode);
+        let other = if color == NEWTONIAN_LABEL { CHRONOTURIN_LABEL } else { NEWTONIAN_LABEL };
+        assert!(!img.pixels().any(|p| *p == other), "{:?}", mode);
+    }
+}
+
+#[test]
+fn tiny_images_do_not_panic() {
+    let mut img = RgbImage::new(8, 4);
+    draw_overlay(&mut img, &info(DilationMode::Chronoturin));
+}
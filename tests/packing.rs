@@ -0,0 +1,65 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::{cpu_reference, render_frame, Camera, DilationMode, RenderRecord, RenderSettings, SimConfig, Simulation};
+
+#[test]
+fn packed_frames_draw_like_full_frames() {
+    let (device, queue) = require_gpu!();
+    // Split across chunks so every packer and readback buffer is exercised.
+    let config = SimConfig {
+        stars: 3000,
+        seed: Some(6),
+        width: 96,
+        height: 80,
+        central_mass: Some(500.0),
+        chunk_stars: Some(1024),
+        ..SimConfig::default()
+    };
+    let mut full = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+    let mut packed = Simulation::new(device, queue, &config).unwrap();
+    packed.enable_packed_readback();
+    assert!(packed.chunk_count() > 1);
+    let camera = Camera::from_config(&config);
+    let settings = RenderSettings::from_config(&config);
+
+    for sim in [&mut full, &mut packed] {
+        // Chronoturin, so the frame mixes active and sleeping stars.
+        sim.set_mode(DilationMode::Chronoturin);
+    }
+    let (ticket_a, ticket_b) = (full.submit_frame(4), packed.submit_frame(4));
+    let (a, b) = (full.finish_frame(ticket_a), packed.finish_frame(ticket_b));
+    assert!(!a.packed && b.packed);
+    assert_eq!(a.stars.len(), b.stars.len());
+    for (s, p) in a.stars.iter().zip(&b.stars) {
+        assert_eq!((s.x.to_bits(), s.y.to_bits(), s.z.to_bits()), (p.x.to_bits(), p.y.to_bits(), p.z.to_bits()));
+        assert_eq!((s.data_type, s.active_flag), (p.data_type, p.active_flag));
+        assert_eq!(RenderRecord::pack(s), RenderRecord::pack(p));
+    }
+    assert!(b.stars.iter().any(|s| s.active_flag > 0.5) && b.stars.iter().any(|s| s.active_flag < 0.5));
+    assert_eq!(render_frame(&a.stars, &camera, &settings, true), render_frame(&b.stars, &camera, &settings, true));
+    // 16 of 40 bytes per star, plus the same few bytes of stats.
+    assert!(b.readback_bytes * 100 < a.readback_bytes * 41, "{} vs {} bytes", b.readback_bytes, a.readback_bytes);
+
+    // A fork reads back packed too, and stats frames still skip the stars.
+    let mut forked = packed.fork();
+    let ticket = forked.submit_frame(1);
+    assert!(forked.finish_frame(ticket).packed);
+    let ticket = packed.submit_stats(1);
+    let stats = packed.finish_frame(ticket);
+    assert!(stats.stars.is_empty() && !stats.packed);
+}
+
+#[test]
+fn packed_readback_refuses_runs_that_need_every_field() {
+    let limits = cpu_reference::limits();
+    let parse = |args: &[&str]| SimConfig::parse_from(["chronoturin", "--readback", "packed"].iter().chain(args)).validate(&limits);
+    assert!(parse(&[]).is_ok());
+    assert!(parse(&["--no-png", "--gif", "run.gif"]).is_ok());
+    assert!(parse(&["--dump-every", "5"]).is_err());
+    assert!(parse(&["--checkpoint-every", "5"]).is_err());
+    assert!(parse(&["--no-png"]).is_err());
+    assert!(parse(&["--cpu"]).is_err());
+    assert!(parse(&["bench"]).is_err());
+}
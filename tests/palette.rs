@@ -0,0 +1,133 @@
+mod common;
+
+use clap::Parser;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use chronoturin::galaxy::check_types;
+use chronoturin::{
+    active_by_type, cpu_reference, presets, render_frame, Camera, ChronoError, DilationMode, Palette, PresetParams, RenderRecord,
+    RenderSettings, SimConfig, Simulation, Star, TypeMix, TYPE_CENTRAL,
+};
+
+fn write_temp(name: &str, contents: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("chronoturin-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn validate(args: &[&str]) -> Result<(), ChronoError> {
+    common::parse(args).validate(&cpu_reference::limits())
+}
+
+#[test]
+fn palette_files_list_every_type_once() {
+    let path = write_temp("palette.csv", "type,r,g,b\n2,0,255,0\n0,255,0,0\n\n1, 10, 20, 30\n");
+    let palette = Palette::from_csv(&path).unwrap();
+    assert_eq!(palette.colors(), &[[255.0, 0.0, 0.0], [10.0, 20.0, 30.0], [0.0, 255.0, 0.0]]);
+    let arg = path.to_str().unwrap();
+    assert!(validate(&["--types", "3", "--palette", arg]).is_ok());
+    let err = validate(&["--types", "4", "--palette", arg]).unwrap_err().to_string();
+    assert!(err.contains("3 colours but --types is 4"), "{}", err);
+
+    let reject = |name: &str, contents: &str, message: &str| {
+        let path = write_temp(name, contents);
+        let err = Palette::from_csv(&path).unwrap_err();
+        assert!(err.contains(message), "{}: {}", name, err);
+        // Reported by clap while parsing, before anything starts.
+        assert!(SimConfig::try_parse_from(["chronoturin", "--palette", path.to_str().unwrap()]).is_err());
+        std::fs::remove_file(&path).unwrap();
+    };
+    reject("gap.csv", "0,1,2,3\n2,1,2,3\n", "no colour for type 1");
+    reject("twice.csv", "0,1,2,3\n0,4,5,6\n", "line 2: type 0 is listed twice");
+    reject("channel.csv", "0,1,256,3\n", "`256` is not a 0-255 channel");
+    reject("columns.csv", "0,1,2\n", "expected type,r,g,b");
+    reject("empty.csv", "type,r,g,b\n", "no colours");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn types_flags_are_checked() {
+    assert!(validate(&["--types", "5"]).is_ok());
+    assert!(validate(&["--types", "0"]).is_err());
+    assert!(validate(&["--types", "17"]).is_err());
+    assert!(validate(&["--types", "3", "--palette", "classic"]).is_err());
+    assert!(validate(&["--types", "3", "--palette", "viridis"]).is_ok());
+    assert!(validate(&["--types", "3", "--type-fractions", "0.5,0.5"]).is_err());
+    assert!(validate(&["--types", "2", "--type-fractions=-1,2"]).is_err());
+    assert!(validate(&["--types", "3", "--accel", "grid"]).is_err());
+    assert_eq!(SimConfig::parse_from(["chronoturin"]).palette(), Palette::classic());
+    assert_eq!(SimConfig::parse_from(["chronoturin", "--types", "4"]).palette(), Palette::viridis(4));
+
+    // Central masses used to be data_type 2.
+    let old = [Star { data_type: 2.0, ..presets::central_star(100.0) }];
+    let err = check_types(&old, 2).unwrap_err().to_string();
+    assert!(err.contains("star 0 has data_type 2; with --types 2 it must be 0 to 1, or -1 for a central mass"), "{}", err);
+    assert!(check_types(&[presets::central_star(100.0)], 2).is_ok());
+}
+
+#[test]
+fn presets_split_stars_by_the_type_fractions() {
+    // Two even types draw exactly as the original `gen_bool(0.5)`, so seeds keep their galaxies.
+    let (mut mixed, mut original) = (StdRng::seed_from_u64(3), StdRng::seed_from_u64(3));
+    for _ in 0..1000 {
+        assert_eq!(TypeMix::default().pick(&mut mixed), if original.gen_bool(0.5) { 0.0 } else { 1.0 });
+    }
+
+    let params = PresetParams { types: TypeMix::new(&[0.2, 0.0, 0.3, 0.5]), ..PresetParams::default() };
+    let stars = presets::disk(20000, 9, &params);
+    let share = |t: f32| stars.iter().filter(|s| s.data_type == t).count() as f32 / stars.len() as f32;
+    assert_eq!(share(1.0), 0.0);
+    for (t, expected) in [(0.0, 0.2), (2.0, 0.3), (3.0, 0.5)] {
+        assert!((share(t) - expected).abs() < 0.02, "type {}: {}", t, share(t));
+    }
+
+    let mut active: Vec<Star> = stars[..6].to_vec();
+    for star in &mut active[..4] {
+        star.active_flag = 1.0;
+    }
+    active[0].data_type = TYPE_CENTRAL;
+    let expected = (0..4).map(|t| active[1..4].iter().filter(|s| s.data_type == t as f32).count() as u32).collect::<Vec<_>>();
+    assert_eq!(active_by_type(&active, 4), expected);
+}
+
+#[test]
+fn five_types_rasterize_and_pack_like_the_cpu() {
+    let (device, queue) = require_gpu!();
+    let config = SimConfig::parse_from([
+        "chronoturin",
+        "--stars",
+        "3000",
+        "--seed",
+        "5",
+        "--width",
+        "96",
+        "--height",
+        "80",
+        "--types",
+        "5",
+        "--central-mass",
+        "500",
+    ]);
+    let settings = RenderSettings::from_config(&config);
+    assert_eq!(settings.palette.len(), 5);
+    let camera = Camera::from_config(&config);
+    let mut sim = Simulation::new(device, queue, &config).unwrap();
+    sim.enable_raster(&settings, &camera).unwrap();
+    sim.set_mode(DilationMode::Chronoturin);
+    let ticket = sim.submit_frame(3);
+    let frame = sim.finish_frame(ticket);
+    assert!((0..5).all(|t| frame.stars.iter().any(|s| s.data_type == t as f32)));
+
+    let cpu = render_frame(&frame.stars, &camera, &settings, true);
+    let gpu = frame.image.as_ref().expect("rasterized on the GPU");
+    let worst = gpu.pixels().zip(cpu.pixels()).flat_map(|(a, b)| (0..3).map(move |c| a[c].abs_diff(b[c]))).max().unwrap();
+    assert!(worst <= 1, "a channel differs by {}", worst);
+    // Viridis has green in it, which two classic types never draw outside the glow.
+    assert!(cpu.pixels().any(|p| p[1] > p[0] && p[1] > p[2]));
+
+    for star in &frame.stars {
+        let unpacked = RenderRecord::pack(star).unpack();
+        assert_eq!((unpacked.data_type, unpacked.active_flag), (star.data_type, star.active_flag));
+    }
+}
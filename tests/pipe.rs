@@ -0,0 +1,63 @@
+mod common;
+
+use std::path::PathBuf;
+
+use image::{Rgb, RgbImage};
+
+use chronoturin::{cpu_reference, RawFrameWriter};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chronoturin-pipe-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn frames_are_concatenated_rgb24() {
+    let dir = temp_dir("file");
+    let path = dir.join("frames.rgb");
+    let mut pipe = RawFrameWriter::open(&path).unwrap();
+    for shade in [10, 20, 30] {
+        assert!(pipe.write_frame(&RgbImage::from_pixel(4, 2, Rgb([shade, 0, 255]))).unwrap());
+    }
+    assert_eq!(pipe.label(), path.display().to_string());
+    assert_eq!(pipe.finish().unwrap(), 3);
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(bytes.len(), 3 * 4 * 2 * 3);
+    assert_eq!((&bytes[..3], &bytes[24..27], &bytes[48..51]), (&[10, 0, 255][..], &[20, 0, 255][..], &[30, 0, 255][..]));
+    assert_eq!(RawFrameWriter::open(std::path::Path::new("-")).unwrap().label(), "stdout");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// A reader that leaves after one frame stops the output, not the writer.
+#[cfg(unix)]
+#[test]
+fn a_closed_fifo_drops_later_frames() {
+    use std::process::{Command, Stdio};
+
+    let dir = temp_dir("fifo");
+    let fifo = dir.join("frames");
+    assert!(Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+    let img = RgbImage::new(256, 256);
+    let mut reader = Command::new("head").arg("-c").arg(img.as_raw().len().to_string()).arg(&fifo).stdout(Stdio::null()).spawn().unwrap();
+    let mut pipe = RawFrameWriter::open(&fifo).unwrap();
+    assert!(pipe.write_frame(&img).unwrap());
+    assert!(reader.wait().unwrap().success());
+    assert!(!pipe.write_frame(&img).unwrap());
+    assert!(!pipe.is_open());
+    assert!(!pipe.write_frame(&img).unwrap());
+    assert_eq!(pipe.finish().unwrap(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn frames_to_pipes_frame_runs_and_renders() {
+    let limits = cpu_reference::limits();
+    let check = |args: &[&str]| common::parse(args).validate(&limits).map_err(|e| e.to_string());
+    assert!(check(&["--frames-to", "-", "--no-png"]).is_ok());
+    assert!(check(&["--frames-to", "-", "render", "a.bin"]).is_ok());
+    assert!(check(&["--frames-to", "-", "bench"]).unwrap_err().contains("--frames-to pipes a frame run or render"));
+    assert!(check(&["--frames-to", "-", "--view"]).is_err());
+    // Without PNGs, packed readback still has frames to draw.
+    assert!(check(&["--frames-to", "-", "--no-png", "--readback", "packed"]).is_ok());
+}
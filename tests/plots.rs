@@ -0,0 +1,95 @@
+#![cfg(feature = "plots")]
+
+use clap::Parser;
+
+use chronoturin::overlay::mode_color;
+use chronoturin::plots::{charts, nice_ticks, padded_range, render_chart, tick_label, write_plots, PLOT_HEIGHT, PLOT_WIDTH};
+use chronoturin::{cpu_reference, read_png_text, DilationMode, FrameSample, RunManifest, SimConfig};
+
+fn samples(wall: f32, gpu: Option<f32>, drift: Option<f64>) -> Vec<FrameSample> {
+    (0..20)
+        .map(|frame| FrameSample {
+            frame,
+            wall_ms: wall + frame as f32,
+            gpu_ms: gpu,
+            active_fraction: 0.5,
+            energy_drift: drift.map(|d| d * frame as f64),
+            lagrangian_radii: None,
+            virial_ratio: None,
+        })
+        .collect()
+}
+
+#[test]
+fn ranges_pad_the_data_and_ticks_are_round() {
+    assert_eq!(padded_range([10.0, 30.0]), Some((9.0, 31.0)));
+    // Never negative data keeps its floor at 0.
+    assert_eq!(padded_range([0.0, 100.0]), Some((0.0, 105.0)));
+    assert_eq!(padded_range([-2.0, 0.0, f64::NAN]), Some((-2.1, 0.1)));
+    assert_eq!(padded_range([-4.0, -4.0]), Some((-4.4, -3.6)));
+    assert_eq!(padded_range([0.0]), Some((0.0, 1.0)));
+    assert_eq!(padded_range([f64::INFINITY]), None);
+
+    assert_eq!(nice_ticks(0.0, 105.0, 8), [0.0, 20.0, 40.0, 60.0, 80.0, 100.0]);
+    assert_eq!(nice_ticks(-2.1, 0.1, 8), [-2.0, -1.5, -1.0, -0.5, 0.0]);
+    assert_eq!(nice_ticks(9.0, 31.0, 8), [10.0, 15.0, 20.0, 25.0, 30.0]);
+    assert_eq!(tick_label(-1.5, 0.5), "-1.5");
+    assert_eq!(tick_label(20.0, 20.0), "20");
+    assert_eq!(tick_label(2.5e-5, 5e-6), "2.5e-5");
+}
+
+#[test]
+fn charts_cover_both_modes_and_skip_what_was_not_measured() {
+    let newton = samples(10.0, Some(4.0), Some(-1e-4));
+    let chrono = samples(6.0, None, None);
+    let all = charts(&[(DilationMode::Newtonian, &newton), (DilationMode::Chronoturin, &chrono)]);
+    let names: Vec<&str> = all.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, ["timings.png", "active_fraction.png", "energy_drift.png"]);
+    let labels: Vec<&str> = all[0].1.series.iter().map(|s| s.label.as_str()).collect();
+    assert_eq!(labels, ["NEWTONIAN WALL", "NEWTONIAN GPU", "CHRONOTURIN WALL"]);
+    assert_eq!(all[1].1.series[1].points[3], (3.0, 50.0));
+    assert_eq!(all[2].1.series.len(), 1);
+
+    let without_drift = charts(&[(DilationMode::Chronoturin, &chrono)]);
+    assert_eq!(without_drift.len(), 2);
+
+    // Structure: five radii per mode with the half-mass radius in the mode's color, and the
+    // virial ratio only where the potential was measured.
+    let collapsing: Vec<FrameSample> = newton
+        .iter()
+        .map(|s| FrameSample { lagrangian_radii: Some([10.0, 20.0, 40.0 - s.frame as f32, 60.0, 90.0]), virial_ratio: Some(0.8), ..*s })
+        .collect();
+    let structure = charts(&[(DilationMode::Newtonian, &collapsing), (DilationMode::Chronoturin, &chrono)]);
+    let names: Vec<&str> = structure.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, ["timings.png", "active_fraction.png", "energy_drift.png", "lagrangian_radii.png", "virial_ratio.png"]);
+    let radii = &structure[3].1.series;
+    let labels: Vec<&str> = radii.iter().map(|s| s.label.as_str()).collect();
+    assert_eq!(labels, ["NEWTONIAN 10%", "NEWTONIAN 25%", "NEWTONIAN 50%", "NEWTONIAN 75%", "NEWTONIAN 90%"]);
+    assert_eq!(radii[2].color, mode_color(DilationMode::Newtonian));
+    assert_ne!(radii[0].color, radii[2].color);
+    assert_eq!(radii[2].points[5], (5.0, 35.0));
+    assert_eq!(structure[4].1.series[0].points[0], (0.0, 0.8));
+
+    let img = render_chart(&all[0].1).unwrap();
+    assert_eq!(img.dimensions(), (PLOT_WIDTH, PLOT_HEIGHT));
+    for mode in [DilationMode::Newtonian, DilationMode::Chronoturin] {
+        assert!(img.pixels().any(|p| *p == mode_color(mode)), "no {} line", mode.name());
+    }
+}
+
+#[test]
+fn plots_are_written_into_the_output_directory() {
+    let dir = std::env::temp_dir().join(format!("chronoturin-plots-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let newton = samples(10.0, None, None);
+    let manifest = RunManifest::new(&SimConfig::default());
+    let written = write_plots(&dir, &[(DilationMode::Newtonian, &newton)], &manifest).unwrap();
+    assert_eq!(written, [dir.join("timings.png"), dir.join("active_fraction.png")]);
+    assert_eq!(image::open(&written[0]).unwrap().width(), PLOT_WIDTH);
+    assert_eq!(read_png_text(&written[1]).unwrap(), Some(manifest));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let limits = cpu_reference::limits();
+    assert!(SimConfig::parse_from(["chronoturin", "--plots"]).validate(&limits).is_ok());
+    assert!(SimConfig::parse_from(["chronoturin", "--plots", "bench"]).validate(&limits).is_err());
+}
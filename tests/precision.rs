@@ -0,0 +1,140 @@
+mod common;
+
+use chronoturin::dump::sidecar_path;
+use chronoturin::simulation::shader_source;
+use chronoturin::{
+    cpu_reference, read_bin_dump, read_csv_dump, write_dump, DilationMode, DumpFormat, DumpInfo, Precision, RasterChoice, SimConfig,
+    Simulation, Star, TYPE_A,
+};
+
+use common::parse;
+
+const OMEGA: f64 = 3.0e-5;
+const STEPS: usize = 3000;
+
+// Circular orbits a few hundred units out in a weak harmonic trap, slow enough that each
+// step moves a star about 5e-4, some fifteen times the f32 spacing of its coordinates, so
+// f32 rounds a few percent off every drift. Velocities stay f32 with df64 and their own
+// rounding catches up as a star travels, so the run covers a small arc. The orbits start
+// well off the axes, where every velocity component is big enough for its kicks to register.
+fn orbits() -> Vec<Star> {
+    (0..4)
+        .map(|i| {
+            let (r, angle) = (300.0 + 10.0 * i as f32, (30.0 + 15.0 * i as f32).to_radians());
+            let v = OMEGA as f32 * r;
+            Star {
+                x: r * angle.cos(),
+                y: r * angle.sin(),
+                z: 0.0,
+                vx: -v * angle.sin(),
+                vy: v * angle.cos(),
+                vz: 0.0,
+                mass: 1.0,
+                data_type: TYPE_A,
+                time_debt: 0.0,
+                active_flag: 0.0,
+            }
+        })
+        .collect()
+}
+
+fn trap(precision: &str) -> SimConfig {
+    let omega = format!("harmonic:omega={}", OMEGA);
+    parse(&["--g", "0", "--integrator", "leapfrog", "--external-potential", &omega, "--precision", precision])
+}
+
+// The GPU's leapfrog in f64: a half kick, then a kick and a drift per step.
+fn reference(star: &Star, dt: f64) -> [f64; 3] {
+    let k = (OMEGA as f32 * OMEGA as f32) as f64;
+    let mut x = [star.x, star.y, star.z].map(f64::from);
+    let mut v = [star.vx, star.vy, star.vz].map(f64::from);
+    for step in 0..STEPS {
+        let kick = if step == 0 { 0.5 * dt } else { dt };
+        for a in 0..3 {
+            v[a] -= k * x[a] * kick;
+            x[a] += v[a] * dt;
+        }
+    }
+    x
+}
+
+#[test]
+fn precision_is_validated() {
+    let limits = cpu_reference::limits();
+    assert!(parse(&["--precision", "df64"]).validate(&limits).is_ok());
+    assert!(parse(&["--precision", "df64", "--cpu"]).validate(&limits).is_err());
+    assert!(parse(&["--precision", "df64", "--kill-radius", "900"]).validate(&limits).is_err());
+    assert!(parse(&["--precision", "df64", "--view"]).validate(&limits).is_err());
+    assert!(parse(&["--precision", "df64", "--raster", "gpu"]).validate(&limits).is_err());
+    assert_eq!(parse(&["--precision", "df64"]).raster_choice(), RasterChoice::Cpu);
+    assert_eq!(parse(&["--precision", "df64", "--stars", "1000"]).star_buffer_size(), Some(52_000));
+}
+
+#[test]
+fn df64_sources_carry_the_residuals() {
+    for precision in [Precision::F32, Precision::Df64] {
+        let module = naga::front::wgsl::parse_str(&shader_source(64, precision)).unwrap();
+        let span = module.types.iter().find_map(|(_, ty)| match &ty.inner {
+            naga::TypeInner::Struct { span, .. } if ty.name.as_deref() == Some("Star") => Some(*span),
+            _ => None,
+        });
+        assert_eq!(span, Some(precision.star_bytes() as u32));
+    }
+    let kernel = shader_source(64, Precision::Df64);
+    assert!(kernel.contains("compensated_add(&star.z, &star.z_lo, star.vz * h);"));
+    assert!(!kernel.contains("star.x += star.vx * h;"));
+    // The rewrite keeps every line where it was.
+    assert_eq!(kernel.lines().count(), shader_source(64, Precision::F32).lines().count());
+    assert!(Precision::Df64.star_struct("fn main() {}").is_err());
+}
+
+#[test]
+fn df64_positions_track_an_f64_orbit_far_closer_than_f32() {
+    let (device, queue) = require_gpu!();
+    let mut errors = Vec::new();
+    for precision in ["f32", "df64"] {
+        let config = trap(precision);
+        let mut gpu = Simulation::with_stars(device.clone(), queue.clone(), &config, orbits()).unwrap();
+        gpu.set_mode(DilationMode::Newtonian);
+        let ticket = gpu.submit_frame(STEPS);
+        let data = gpu.finish_frame(ticket);
+        assert_eq!(data.position_lo.is_some(), precision == "df64");
+        let lo = data.position_lo.unwrap_or_else(|| vec![[0.0; 3]; data.stars.len()]);
+        let mut worst = 0.0f64;
+        for ((star, lo), initial) in data.stars.iter().zip(&lo).zip(orbits()) {
+            let expected = reference(&initial, config.dt as f64);
+            let got = [star.x, star.y, star.z].map(f64::from);
+            for a in 0..3 {
+                worst = worst.max((got[a] + lo[a] as f64 - expected[a]).abs());
+            }
+        }
+        errors.push(worst);
+    }
+    assert!(errors[0] > 1e-3, "f32 kept up with the reference: {:e}", errors[0]);
+    assert!(errors[1] * 100.0 < errors[0], "df64 {:e} against f32 {:e}", errors[1], errors[0]);
+}
+
+#[test]
+fn df64_dumps_record_the_residuals() {
+    let stars = orbits();
+    let lo: Vec<[f32; 3]> = (0..stars.len()).map(|i| [1e-6 * i as f32, -2e-6, 0.0]).collect();
+    let info = DumpInfo { mode: "NEWTONIAN", frame: 3, sim_time: 0.15 };
+    let dir = std::env::temp_dir().join(format!("chronoturin-precision-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let csv = dir.join("df64.csv");
+    write_dump(&csv, &stars, Some(&lo), DumpFormat::Csv, &info, None).unwrap();
+    let text = std::fs::read_to_string(&csv).unwrap();
+    assert!(text.starts_with("x,y,z,vx,vy,vz,mass,data_type,time_debt,active_flag,x_lo,y_lo,z_lo\n"));
+    assert!(text.lines().nth(2).unwrap().ends_with(",0.000001,-0.000002,0"), "{}", text);
+    let bin = dir.join("df64.bin");
+    write_dump(&bin, &stars, Some(&lo), DumpFormat::Bin, &info, None).unwrap();
+    assert_eq!(std::fs::metadata(&bin).unwrap().len(), 52 * stars.len() as u64);
+    let sidecar = std::fs::read_to_string(sidecar_path(&bin)).unwrap();
+    assert!(sidecar.contains("\"stride_bytes\":52") && sidecar.contains("\"precision\":\"df64\""), "{}", sidecar);
+    // Readers keep the hi parts.
+    for parsed in [read_csv_dump(&csv).unwrap(), read_bin_dump(&bin).unwrap()] {
+        assert_eq!(bytemuck::cast_slice::<_, u8>(&parsed), bytemuck::cast_slice::<_, u8>(&stars));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,120 @@
+use chronoturin::presets::{self, Preset, PresetParams};
+use chronoturin::{generate_galaxy, SimConfig, Star, TYPE_A, TYPE_B, TYPE_CENTRAL};
+
+fn params(preset: Preset) -> PresetParams {
+    PresetParams::from_config(&SimConfig { preset, ..SimConfig::default() })
+}
+
+fn sphere(cold_start: bool, velocity_dispersion: f32) -> PresetParams {
+    PresetParams { cold_start, velocity_dispersion, ..PresetParams::default() }
+}
+
+fn radius(s: &Star) -> f32 {
+    (s.x * s.x + s.y * s.y + s.z * s.z).sqrt()
+}
+
+#[test]
+fn sphere_preset_is_the_original_galaxy() {
+    let preset = presets::generate(Preset::Sphere, &params(Preset::Sphere), 2048, 7);
+    let original = generate_galaxy(2048, 7);
+    assert_eq!(bytemuck::cast_slice::<_, u8>(&preset), bytemuck::cast_slice::<_, u8>(&original));
+}
+
+#[test]
+fn default_sphere_rotates_and_cold_start_rests() {
+    let spinning = presets::sphere(4096, 2, &sphere(false, 0.0));
+    let cold = presets::sphere(4096, 2, &sphere(true, 0.0));
+    for (s, c) in spinning.iter().zip(&cold) {
+        assert_eq!((s.x, s.y, s.z), (c.x, c.y, c.z));
+        assert_eq!((c.vx, c.vy, c.vz), (0.0, 0.0, 0.0));
+        // Tangential about Z and prograde.
+        assert!((s.x * s.vx + s.y * s.vy).abs() < 1e-2);
+        assert!(s.x * s.vy - s.y * s.vx >= 0.0);
+        assert_eq!(s.vz, 0.0);
+    }
+    assert!(spinning.iter().filter(|s| radius(s) > 10.0).all(|s| s.vx.hypot(s.vy) > 0.0));
+}
+
+#[test]
+fn velocity_dispersion_adds_isotropic_jitter() {
+    let cold = presets::sphere(20000, 8, &sphere(true, 0.0));
+    let warm = presets::sphere(20000, 8, &sphere(true, 4.0));
+    assert!(cold.iter().zip(&warm).all(|(c, w)| (c.x, c.y, c.z) == (w.x, w.y, w.z)));
+    for axis in [|s: &Star| s.vx, |s: &Star| s.vy, |s: &Star| s.vz] {
+        let var = warm.iter().map(|s| axis(s) * axis(s)).sum::<f32>() / warm.len() as f32;
+        assert!((var.sqrt() - 4.0).abs() < 0.2, "sigma {}", var.sqrt());
+    }
+}
+
+#[test]
+fn presets_are_deterministic_per_seed() {
+    for preset in [Preset::Disk, Preset::Plummer, Preset::Collision] {
+        let a = presets::generate(preset, &params(preset), 512, 3);
+        let b = presets::generate(preset, &params(preset), 512, 3);
+        let c = presets::generate(preset, &params(preset), 512, 4);
+        assert_eq!(bytemuck::cast_slice::<_, u8>(&a), bytemuck::cast_slice::<_, u8>(&b), "{:?}", preset);
+        assert_ne!(bytemuck::cast_slice::<_, u8>(&a), bytemuck::cast_slice::<_, u8>(&c), "{:?}", preset);
+        assert!(a.iter().all(|s| [s.x, s.y, s.z, s.vx, s.vy, s.vz].iter().all(|v| v.is_finite())), "{:?}", preset);
+    }
+}
+
+#[test]
+fn plummer_half_mass_radius_matches_theory() {
+    let p = params(Preset::Plummer);
+    let mut radii: Vec<f32> = presets::generate(Preset::Plummer, &p, 20000, 11).iter().map(radius).collect();
+    radii.sort_by(f32::total_cmp);
+    // Half the mass of a Plummer sphere lies within ~1.305 a (slightly less after the 10a cut).
+    let half = radii[radii.len() / 2] / p.scale_radius;
+    assert!((1.2..1.4).contains(&half), "half-mass radius {} a", half);
+}
+
+#[test]
+fn disk_is_thin_and_rotating() {
+    let p = params(Preset::Disk);
+    let stars = presets::generate(Preset::Disk, &p, 10000, 5);
+    let mean_abs_z = stars.iter().map(|s| s.z.abs()).sum::<f32>() / stars.len() as f32;
+    let mean_r = stars.iter().map(|s| s.x.hypot(s.y)).sum::<f32>() / stars.len() as f32;
+    assert!(mean_abs_z * 10.0 < mean_r, "z {} vs R {}", mean_abs_z, mean_r);
+    // Every star moves tangentially, all in the same sense.
+    for s in &stars {
+        let radial = s.x * s.vx + s.y * s.vy;
+        let angular = s.x * s.vy - s.y * s.vx;
+        assert!(radial.abs() <= 1e-2 * angular.abs().max(1.0));
+        assert!(angular >= 0.0);
+        assert_eq!(s.vz, 0.0);
+    }
+}
+
+#[test]
+fn collision_splits_types_between_two_separated_galaxies() {
+    let p = params(Preset::Collision);
+    let stars = presets::generate(Preset::Collision, &p, 4000, 9);
+    let (a, b): (Vec<&Star>, Vec<&Star>) = stars.iter().partition(|s| s.data_type == TYPE_A);
+    assert_eq!(a.len(), 2000);
+    assert!(b.iter().all(|s| s.data_type == TYPE_B));
+
+    let mean = |group: &[&Star], f: fn(&Star) -> f32| group.iter().map(|s| f(s)).sum::<f32>() / group.len() as f32;
+    let gap = mean(&b, |s| s.x) - mean(&a, |s| s.x);
+    assert!((gap - p.separation).abs() < 0.1 * p.separation, "centers {} apart", gap);
+    // Closing in: A moves toward +X, B toward -X.
+    let closing = mean(&a, |s| s.vx) - mean(&b, |s| s.vx);
+    assert!((closing - p.relative_velocity).abs() < 0.25 * p.relative_velocity, "closing speed {}", closing);
+}
+
+#[test]
+fn central_mass_is_prepended_and_speeds_up_orbits() {
+    for preset in [Preset::Sphere, Preset::Disk, Preset::Plummer] {
+        let plain = params(preset);
+        let heavy = PresetParams { central_mass: 10000.0, ..plain };
+        let without = presets::generate(preset, &plain, 1000, 6);
+        let with = presets::generate(preset, &heavy, 1000, 6);
+        assert_eq!(with.len(), 1001);
+        let center = &with[0];
+        assert_eq!((center.x, center.y, center.z, center.vx, center.mass), (0.0, 0.0, 0.0, 0.0, 10000.0));
+        assert_eq!(center.data_type, TYPE_CENTRAL);
+        let speed = |s: &Star| (s.vx * s.vx + s.vy * s.vy + s.vz * s.vz).sqrt();
+        let total = |stars: &[Star]| stars.iter().map(speed).sum::<f32>();
+        assert!(total(&with[1..]) > 1.5 * total(&without), "{:?}", preset);
+        assert!(with[1..].iter().zip(&without).all(|(a, b)| (a.x, a.y, a.z) == (b.x, b.y, b.z)));
+    }
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use std::path::PathBuf;
+
+use chronoturin::{
+    cpu_reference, presets, write_profiles, write_rotation_curve, Annulus, DilationMode, Histogram, LagrangianRadii, OutputLayout,
+    ProfileSettings, Profiles, RotationCurve, SimConfig, Star, TYPE_A, TYPE_B,
+};
+
+use common::parse;
+
+fn star(x: f32, y: f32, time_debt: f32) -> Star {
+    Star { x, y, time_debt, ..common::star() }
+}
+
+#[test]
+fn outliers_fall_into_one_overflow_bin() {
+    let mut histogram = Histogram::new(0.0, 10.0, 4);
+    for value in [0.0, 2.4, 2.5, -3.0, 9.99, 10.0, 1.0e30, f32::INFINITY, f32::NAN] {
+        histogram.add(value);
+    }
+    assert_eq!(histogram.counts, [3, 1, 0, 1]);
+    assert_eq!(histogram.overflow, 4);
+    assert_eq!(histogram.edges(2), (5.0, 7.5));
+    assert_eq!(histogram.with_overflow(), [3, 1, 0, 1, 4]);
+}
+
+#[test]
+fn shells_are_centered_on_the_center_of_mass() {
+    // Four stars 5 out from (100, 0), two 15 out, two flung away and a central mass that stays unbinned.
+    let mut stars = vec![
+        star(105.0, 0.0, 0.05),
+        star(95.0, 0.0, 0.55),
+        star(100.0, 5.0, 0.15),
+        star(100.0, -5.0, 0.95),
+        star(115.0, 0.0, 0.0),
+        star(85.0, 0.0, 0.0),
+        star(100.0, -1.0e9, 0.0),
+        star(100.0, 1.0e9, 0.0),
+        Star { x: 100.0, mass: 10.0, ..presets::central_star(10.0) },
+    ];
+    let settings = ProfileSettings { shells: 2, radius: 20.0, debt_bins: 2 };
+    let profiles = Profiles::measure(&stars, &settings, true);
+    assert_eq!(profiles.center, [100.0, 0.0, 0.0]);
+    assert_eq!(profiles.radial.with_overflow(), [4, 2, 2]);
+    assert_eq!(profiles.debt.as_ref().unwrap().with_overflow(), [6, 2, 0]);
+    let inner = 4.0 / (4.0 / 3.0 * std::f64::consts::PI * 1000.0);
+    assert!((profiles.densities()[0] - inner).abs() < 1e-12);
+
+    // Packed readbacks have no mass or debt: the center is the plain mean and the debt histogram is gone.
+    for star in &mut stars {
+        star.mass = 0.0;
+    }
+    stars.retain(|s| s.data_type == TYPE_A);
+    let packed = Profiles::measure(&stars, &settings, false);
+    assert_eq!(packed.center[0], 100.0);
+    assert_eq!(packed.debt, None);
+}
+
+#[test]
+fn lagrangian_radii_enclose_each_mass_fraction() {
+    // Ten unit stars at 1 to 10 from the center, mirrored so the center of mass stays at the origin.
+    let mut stars: Vec<Star> = (1..=10).flat_map(|r| [star(r as f32, 0.0, 0.0), star(-(r as f32), 0.0, 0.0)]).collect();
+    stars.push(Star { mass: 1000.0, ..presets::central_star(1000.0) });
+    let mut radii = LagrangianRadii::default();
+    // 2 of 20 stars reach 10%, 5 reach 25%, 10 the half mass, 15 and 18 the rest.
+    assert_eq!(radii.measure(&stars), Some([1.0, 3.0, 5.0, 8.0, 9.0]));
+
+    // Mass-weighted: a heavy star at 2 holds half the mass on its own.
+    let weighted = [star(1.0, 0.0, 0.0), Star { mass: 3.0, ..star(-2.0, 0.0, 0.0) }, star(7.0, 0.0, 0.0), star(-3.0, 0.0, 0.0)];
+    let center = (1.0 - 6.0 + 7.0 - 3.0) / 6.0;
+    let expected = [1.0 - center, -2.0 - center, -2.0 - center, -3.0 - center, 7.0 - center].map(|d: f64| d.abs() as f32);
+    assert_eq!(radii.measure(&weighted), Some(expected));
+
+    // Packed readbacks carry no masses, so every star counts once, about the plain mean at 0.75.
+    let packed: Vec<Star> = weighted.iter().map(|s| Star { mass: 0.0, ..*s }).collect();
+    assert_eq!(radii.measure(&packed).map(|r| r[2]), Some(2.75));
+    assert_eq!(radii.measure(&[presets::central_star(10.0)]), None);
+}
+
+#[test]
+fn both_modes_bin_alike_and_write_tables() {
+    let limits = cpu_reference::limits();
+    assert_eq!(ProfileSettings::from_config(&parse(&[])), ProfileSettings { shells: 32, radius: 1200.0, debt_bins: 10 });
+    let settings = ProfileSettings::from_config(&parse(&["--profile-shells", "8", "--profile-radius", "50", "--debt-bins", "4"]));
+    assert_eq!(settings, ProfileSettings { shells: 8, radius: 50.0, debt_bins: 4 });
+    assert!(parse(&["--profile-shells", "0"]).validate(&limits).is_err());
+    assert!(parse(&["--profile-radius", "0"]).validate(&limits).is_err());
+    assert!(parse(&["--profiles", "--view"]).validate(&limits).is_err());
+    assert!(parse(&["--profiles", "--readback", "packed"]).validate(&limits).is_ok());
+
+    let layout = OutputLayout::prepare(&SimConfig::default()).unwrap();
+    assert_eq!(layout.profile_path(DilationMode::Chronoturin, 7), PathBuf::from("profiles_chrono_007.csv"));
+
+    let stars = [star(1.0, 0.0, 0.3), Star { data_type: TYPE_B, ..star(-1.0, 0.0, 0.6) }, star(0.0, 500.0, 0.0)];
+    let profiles = Profiles::measure(&stars, &ProfileSettings { shells: 2, radius: 4.0, debt_bins: 2 }, true);
+    let path = std::env::temp_dir().join(format!("chronoturin-profiles-{}.csv", std::process::id()));
+    write_profiles(&path, &profiles).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    let rows: Vec<&str> = text.lines().collect();
+    assert_eq!(rows[0], "profile,bin,lower,upper,count,density");
+    assert!(rows[1].starts_with("radial,0,0,2,0,"), "{}", rows[1]);
+    assert_eq!(&rows[3..], ["radial,overflow,4,inf,3,", "debt,0,0,0.5,2,", "debt,1,0.5,1,1,", "debt,overflow,1,inf,0,"]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn rotation_curves_bin_around_the_measured_axis() {
+    // A disk tilted 45° about X, spinning at 2: eight stars at 7 whose radial velocities
+    // alternate ±1, and a pair at 12 moving only around. Opposite stars cancel, so the center
+    // of mass stays at rest at the origin.
+    let (e1, e2) = ([1.0, 0.0, 0.0], [0.0, 0.5f64.sqrt(), 0.5f64.sqrt()]);
+    let at = |radius: f64, angle: f64, out: f64| {
+        let (sin, cos) = angle.sin_cos();
+        let p: [f64; 3] = std::array::from_fn(|k| radius * (cos * e1[k] + sin * e2[k]));
+        let v: [f64; 3] = std::array::from_fn(|k| 2.0 * (cos * e2[k] - sin * e1[k]) + out * (cos * e1[k] + sin * e2[k]));
+        Star { vx: v[0] as f32, vy: v[1] as f32, vz: v[2] as f32, ..star_at(p) }
+    };
+    let quarter = std::f64::consts::FRAC_PI_4;
+    let mut stars: Vec<Star> = (0..8).map(|k| at(7.0, k as f64 * quarter, if k % 2 == 0 { 1.0 } else { -1.0 })).collect();
+    stars.extend([at(12.0, 0.0, 0.0), at(12.0, 4.0 * quarter, 0.0), presets::central_star(1000.0)]);
+
+    let curve = RotationCurve::measure(&stars, &ProfileSettings { shells: 4, radius: 20.0, debt_bins: 1 });
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-5;
+    assert!(curve.axis.iter().zip([0.0, -(0.5f64.sqrt()), 0.5f64.sqrt()]).all(|(&a, b)| close(a, b)), "{:?}", curve.axis);
+    let counts: Vec<u32> = curve.annuli.iter().map(|a| a.stars).collect();
+    assert_eq!(counts, [0, 8, 2, 0]);
+    assert!(close(curve.annuli[1].rotation.unwrap(), 2.0) && close(curve.annuli[1].radial_dispersion.unwrap(), 1.0));
+    assert!(close(curve.annuli[2].rotation.unwrap(), 2.0) && close(curve.annuli[2].radial_dispersion.unwrap(), 0.0));
+    // Empty annuli have no velocities rather than NaNs.
+    assert_eq!(curve.annuli[3], Annulus { stars: 0, rotation: None, radial_dispersion: None });
+    assert_eq!(curve.edges(2), (10.0, 15.0));
+
+    // Spinning the other way flips the axis, so the rotation stays positive.
+    let reversed: Vec<Star> = stars.iter().map(|s| Star { vx: -s.vx, vy: -s.vy, vz: -s.vz, ..*s }).collect();
+    let curve_back = RotationCurve::measure(&reversed, &ProfileSettings { shells: 4, radius: 20.0, debt_bins: 1 });
+    assert!(close(curve_back.axis[1], 0.5f64.sqrt()) && close(curve_back.annuli[2].rotation.unwrap(), 2.0));
+
+    let path = std::env::temp_dir().join(format!("chronoturin-rotation-{}.csv", std::process::id()));
+    write_rotation_curve(&path, &curve).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    let rows: Vec<&str> = text.lines().collect();
+    assert_eq!(rows[..3], ["# axis 0.000000,-0.707107,0.707107", "annulus,inner,outer,stars,rotation,radial_dispersion", "0,0,5,0,,"]);
+    assert!(rows[2..].iter().all(|row| !row.contains("NaN")));
+    assert!(rows[3].starts_with("1,5,10,8,2.0000"), "{}", rows[3]);
+    std::fs::remove_file(&path).unwrap();
+
+    let parse = |args: &[&str]| common::parse(args).validate(&cpu_reference::limits());
+    assert!(parse(&["--kinematics"]).is_ok());
+    assert!(parse(&["--kinematics", "--readback", "packed"]).is_err());
+    assert!(parse(&["--kinematics", "bench"]).is_err());
+    let layout = OutputLayout::prepare(&SimConfig::default()).unwrap();
+    assert_eq!(layout.rotation_path(DilationMode::Newtonian, 3), PathBuf::from("rotation_newton_003.csv"));
+}
+
+fn star_at(p: [f64; 3]) -> Star {
+    Star { x: p[0] as f32, y: p[1] as f32, z: p[2] as f32, ..star(0.0, 0.0, 0.0) }
+}
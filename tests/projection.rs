@@ -0,0 +1,122 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::render::{parse_stereo, project_to_screen};
+use chronoturin::{
+    anaglyph, cpu_reference, project, render_frame, Camera, Projection, RenderSettings, SimConfig, Star, Stereo, TYPE_A, TYPE_B,
+};
+
+// At the origin looking down +Z: world +X is right and +Y down, as in the planar camera.
+fn equirect(width: u32, height: u32) -> Camera {
+    Camera::look_at([0.0; 3], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0], width, height, 800.0).with_projection(Projection::Equirect)
+}
+
+fn star_at(x: f32, y: f32, z: f32) -> Star {
+    Star { x, y, z, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type: TYPE_A, time_debt: 0.0, active_flag: 0.0 }
+}
+
+#[test]
+fn equirect_maps_longitude_across_and_latitude_down() {
+    let cam = equirect(200, 100);
+    assert_eq!(project(&star_at(0.0, 0.0, 100.0), &cam), Some((100, 50)));
+    assert_eq!(project(&star_at(100.0, 0.0, 0.0), &cam), Some((150, 50)));
+    assert_eq!(project(&star_at(-100.0, 0.0, 0.0), &cam), Some((50, 50)));
+    // 45° up and ahead: a quarter of the way from the equator to the zenith.
+    assert_eq!(project(&star_at(0.0, -100.0, 100.0), &cam), Some((100, 25)));
+    // Every direction lands on the canvas, however far behind or around.
+    for (x, y, z) in [(3.0, 5.0, -100.0), (-70.0, 70.0, -1.0), (0.5, -90.0, 0.0)] {
+        assert!(project(&star_at(x, y, z), &cam).is_some(), "{} {} {}", x, y, z);
+    }
+    // Distance rather than depth decides the near plane.
+    assert_eq!(project(&star_at(5.0, 0.0, -5.0), &cam), None);
+    assert!(project(&star_at(50.0, 0.0, -5.0), &cam).is_some());
+}
+
+#[test]
+fn equirect_poles_stay_on_the_canvas() {
+    let cam = equirect(200, 100);
+    // Straight up is the top row and straight down the last, not one row past it.
+    assert_eq!(project(&star_at(0.0, -100.0, 0.0), &cam).map(|p| p.1), Some(0));
+    assert_eq!(project(&star_at(0.0, 100.0, 0.0), &cam).map(|p| p.1), Some(99));
+    let (_, y, distance) = project_to_screen(&star_at(0.0, 100.0, 0.0), &cam).unwrap();
+    assert!(y < 100.0 && distance == 100.0);
+    // Next to a pole any longitude is a whole row, so neighbours there stay on the edge rows.
+    for x in [-1.0, 1.0] {
+        assert_eq!(project(&star_at(x, 100.0, 0.0), &cam).map(|p| p.1), Some(99));
+        assert_eq!(project(&star_at(x, -100.0, 0.0), &cam).map(|p| p.1), Some(0));
+    }
+}
+
+#[test]
+fn equirect_seam_wraps_at_180_degrees() {
+    let cam = equirect(200, 100);
+    // Directly behind is ±180°: both signs of zero land on the left edge, not at x = width.
+    assert_eq!(project(&star_at(0.0, 0.0, -100.0), &cam), Some((0, 50)));
+    assert_eq!(project(&star_at(-0.0, 0.0, -100.0), &cam), Some((0, 50)));
+    // Just to either side of it, the two edges.
+    assert_eq!(project(&star_at(1.0, 0.0, -100.0), &cam).map(|p| p.0), Some(199));
+    assert_eq!(project(&star_at(-1.0, 0.0, -100.0), &cam).map(|p| p.0), Some(0));
+
+    // A splat across the seam is drawn on both edges.
+    let settings = RenderSettings { splat_size: Some(1.0), ..RenderSettings::legacy() };
+    let img = render_frame(&[star_at(0.0, 0.0, -100.0)], &cam, &settings, false);
+    assert!(img.get_pixel(0, 50)[0] > 0 && img.get_pixel(199, 50)[0] > 0);
+    let planar = Camera::look_at([0.0; 3], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0], 200, 100, 80.0);
+    let img = render_frame(&[star_at(101.0, 0.0, 100.0)], &planar, &settings, false);
+    assert_eq!(img.get_pixel(0, 50)[0], 0);
+}
+
+#[test]
+fn orbits_and_eyes_keep_the_projection() {
+    let cam = equirect(200, 100);
+    assert_eq!(cam.orbited(30.0).projection, Projection::Equirect);
+    let (left, right) = cam.eyes(10.0);
+    assert_eq!((left.projection, right.projection), (Projection::Equirect, Projection::Equirect));
+    // The eyes sit either side of the camera along its right axis, looking at the same target.
+    assert_eq!((left.position, right.position), ([-5.0, 0.0, 0.0], [5.0, 0.0, 0.0]));
+    assert_eq!(left.target, cam.target);
+}
+
+#[test]
+fn anaglyphs_put_the_left_eye_in_red_and_the_right_in_cyan() {
+    let cam = Camera::look_at([0.0, 0.0, -1000.0], [0.0; 3], [0.0, 1.0, 0.0], 200, 100, 800.0);
+    let settings = RenderSettings { stereo: Some(Stereo { separation: 100.0 }), ..RenderSettings::legacy() };
+    // Nearer than the target, so each eye sees it on a different side.
+    let star = Star { data_type: TYPE_B, ..star_at(0.0, 0.0, -500.0) };
+    let img = render_frame(&[star], &cam, &settings, false);
+    let lit: Vec<(u32, image::Rgb<u8>)> = (0..200).map(|x| (x, *img.get_pixel(x, 50))).filter(|(_, p)| p.0 != [0, 0, 0]).collect();
+    assert_eq!(lit.len(), 2, "{:?}", lit);
+    let (red, cyan) = (lit.iter().find(|(_, p)| p[0] > 0).unwrap(), lit.iter().find(|(_, p)| p[1] > 0).unwrap());
+    assert!(red.1[1] == 0 && red.1[2] == 0 && cyan.1[0] == 0 && cyan.1[1] == cyan.1[2]);
+    // Crossed disparity: the left eye sees a near star to the right of where the right eye does.
+    assert!(red.0 > cyan.0, "{:?}", lit);
+    // A star on the target is where both eyes agree.
+    let img = render_frame(&[star_at(0.0, 0.0, 0.0)], &cam, &settings, false);
+    let p = img.get_pixel(100, 50);
+    assert!(p[0] > 0 && p[1] == p[0] && p[2] == p[0], "{:?}", p);
+    let white = image::RgbImage::from_pixel(2, 1, image::Rgb([255, 255, 255]));
+    assert_eq!(anaglyph(&white, &image::RgbImage::new(2, 1)).get_pixel(0, 0).0, [255, 0, 0]);
+}
+
+#[test]
+fn stereo_and_equirect_flags_parse_and_validate() {
+    assert_eq!(parse_stereo("anaglyph"), Ok(Stereo { separation: 10.0 }));
+    assert_eq!(parse_stereo("anaglyph:separation=2.5"), Ok(Stereo { separation: 2.5 }));
+    assert_eq!(parse_stereo(&Stereo { separation: 2.5 }.to_string()), Ok(Stereo { separation: 2.5 }));
+    assert!(parse_stereo("anaglyph:separation=-1").is_err());
+    assert!(parse_stereo("anaglyph:eyes=2").is_err());
+    assert!(parse_stereo("sidebyside").is_err());
+
+    let limits = cpu_reference::limits();
+    let parse = |args: &[&str]| common::parse(args).validate(&limits);
+    assert!(parse(&["--projection", "equirect", "--width", "2048", "--height", "1024"]).is_ok());
+    assert!(parse(&["--projection", "equirect"]).is_err());
+    assert!(parse(&["--projection", "equirect", "--width", "2048", "--height", "1024", "--raster", "gpu"]).is_err());
+    assert!(parse(&["--stereo", "anaglyph:separation=20", "--orbit", "2"]).is_ok());
+    assert!(parse(&["--stereo", "anaglyph", "--image-format", "png16"]).is_err());
+    let config =
+        SimConfig::parse_from(["chronoturin", "--stereo", "anaglyph", "--projection", "equirect", "--width", "400", "--height", "200"]);
+    assert_eq!(Camera::from_config(&config).projection, Projection::Equirect);
+    assert_eq!(RenderSettings::from_config(&config).stereo, Some(Stereo { separation: 10.0 }));
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use clap::Parser;
+use image::RgbImage;
+
+use chronoturin::{cpu_reference, render_frame, Camera, DilationMode, RenderSettings, SimConfig, Simulation, Tonemap};
+
+fn scene() -> SimConfig {
+    SimConfig { stars: 3000, seed: Some(4), width: 96, height: 80, central_mass: Some(500.0), ..SimConfig::default() }
+}
+
+// Fixed-point accumulation may round a channel by one step; anything more is a real mismatch.
+fn assert_close(gpu: &RgbImage, cpu: &RgbImage, what: &str) {
+    assert_eq!(gpu.dimensions(), cpu.dimensions());
+    let pairs = || gpu.pixels().zip(cpu.pixels());
+    let worst = pairs().flat_map(|(a, b)| (0..3).map(move |c| a[c].abs_diff(b[c]))).max().unwrap();
+    let differing = pairs().filter(|(a, b)| a != b).count();
+    assert!(worst <= 1, "{}: a channel differs by {}", what, worst);
+    assert!(differing * 100 <= gpu.len() / 3, "{}: {} pixels differ", what, differing);
+    assert!(cpu.pixels().filter(|p| p[0] > 0 || p[2] > 0).count() > 100, "{}: scene is empty", what);
+}
+
+#[test]
+fn gpu_frames_match_the_cpu_rasterizer() {
+    let (device, queue) = require_gpu!();
+    let config = scene();
+    let camera = Camera::from_config(&config);
+    let tilted = Camera::look_at([300.0, -400.0, -700.0], [0.0; 3], [0.0, 1.0, 0.0], config.width, config.height, 200.0);
+    let settings = [
+        ("splats", RenderSettings::from_config(&config)),
+        ("points", RenderSettings::legacy()),
+        (
+            "log + falloff",
+            RenderSettings { tonemap: Tonemap::Log, exposure: 3.0, falloff: 1.5, splat_size: Some(1.5), ..RenderSettings::legacy() },
+        ),
+        (
+            "reinhard",
+            RenderSettings { tonemap: Tonemap::Reinhard, exposure: 0.5, falloff: 0.0, splat_size: Some(0.5), ..RenderSettings::legacy() },
+        ),
+    ];
+    for (name, settings) in settings {
+        for (mode, cam) in [(DilationMode::Newtonian, camera), (DilationMode::Chronoturin, tilted)] {
+            let mut sim = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+            sim.enable_raster(&settings, &cam).unwrap();
+            sim.set_mode(mode);
+            // A few steps so Chronoturin has a mix of active and sleeping stars.
+            let ticket = sim.submit_frame(4);
+            let frame = sim.finish_frame(ticket);
+            let cpu = render_frame(&frame.stars, &cam, &settings, mode == DilationMode::Chronoturin);
+            assert_close(frame.image.as_ref().expect("rasterized on the GPU"), &cpu, &format!("{} {}", name, mode.name()));
+        }
+    }
+}
+
+#[test]
+fn queued_frames_keep_their_own_camera() {
+    let (device, queue) = require_gpu!();
+    let config = scene();
+    let settings = RenderSettings::from_config(&config);
+    let base = Camera::from_config(&config);
+    let mut newton = Simulation::new(device, queue, &config).unwrap();
+    newton.enable_raster(&settings, &base).unwrap();
+    let mut chrono = newton.fork();
+    chrono.set_mode(DilationMode::Chronoturin);
+
+    // Both readback slots of both simulations in flight before any is finished, as in a lockstep run.
+    let mut tickets = Vec::new();
+    for k in 0..2 {
+        for sim in [&mut newton, &mut chrono] {
+            sim.set_camera(&base.orbited(40.0 * k as f32));
+            tickets.push(sim.submit_frame(2));
+        }
+    }
+    for (i, ticket) in tickets.into_iter().enumerate() {
+        let (sim, mode) = if i % 2 == 0 { (&mut newton, DilationMode::Newtonian) } else { (&mut chrono, DilationMode::Chronoturin) };
+        let frame = sim.finish_frame(ticket);
+        let cam = base.orbited(40.0 * (i / 2) as f32);
+        let cpu = render_frame(&frame.stars, &cam, &settings, mode == DilationMode::Chronoturin);
+        assert_close(frame.image.as_ref().unwrap(), &cpu, &format!("frame {}", i));
+    }
+}
+
+#[test]
+fn stats_frames_and_unrastered_simulations_carry_no_image() {
+    let (device, queue) = require_gpu!();
+    let config = scene();
+    let mut sim = Simulation::new(device, queue, &config).unwrap();
+    let ticket = sim.submit_frame(1);
+    assert!(sim.finish_frame(ticket).image.is_none());
+
+    sim.enable_raster(&RenderSettings::from_config(&config), &Camera::from_config(&config)).unwrap();
+    let ticket = sim.submit_stats(1);
+    assert!(sim.finish_frame(ticket).image.is_none());
+    let ticket = sim.submit_frame(1);
+    assert!(sim.finish_frame(ticket).image.is_some());
+}
+
+#[test]
+fn gpu_raster_needs_the_gpu_backend() {
+    let limits = cpu_reference::limits();
+    assert!(SimConfig::parse_from(["chronoturin", "--cpu", "--raster", "gpu"]).validate(&limits).is_err());
+    assert!(SimConfig::parse_from(["chronoturin", "--cpu", "--raster", "cpu"]).validate(&limits).is_ok());
+    assert!(SimConfig::parse_from(["chronoturin", "--cpu"]).validate(&limits).is_ok());
+}
@@ -0,0 +1,308 @@
+mod common;
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::TryRecvError;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+
+use chronoturin::gpu::{await_map, map_result, settle_maps, DeviceErrors, DeviceFault, MapOutcome, MapReceiver};
+use chronoturin::{
+    cpu_reference, state_hash, ChronoError, CpuSimulation, DilationMode, FrameData, GalaxyState, OnFrameError, Recovering, Reopen,
+    SimConfig, Simulation, Star, Stepper,
+};
+
+use common::seeded;
+
+const FRAMES: usize = 10;
+const DEPTH: usize = 3;
+// The frame the schedule switches to Chronoturin at, while the loss's frames are in flight
+const SWITCH: usize = 4;
+
+// The CPU reference on a pretend device that is lost as frame `lose_at` is read back: that
+// frame and every later one come back empty, as failed maps do.
+struct Flaky {
+    inner: CpuSimulation,
+    lose_at: Option<usize>,
+    finished: usize,
+    errors: DeviceErrors,
+}
+
+impl Flaky {
+    fn new(lose_at: Option<usize>) -> Self {
+        Flaky { inner: CpuSimulation::new(&seeded(48, 21)), lose_at, finished: 0, errors: DeviceErrors::default() }
+    }
+}
+
+impl Stepper for Flaky {
+    type Ticket = FrameData;
+    fn fork(&self) -> Self {
+        Flaky { inner: self.inner.fork(), lose_at: self.lose_at, finished: 0, errors: self.errors.clone() }
+    }
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+    fn restore(&mut self, state: GalaxyState, stars: &[Star]) {
+        self.inner.restore(state, stars);
+    }
+    fn set_mode(&mut self, mode: DilationMode) {
+        Stepper::set_mode(&mut self.inner, mode);
+    }
+    fn set_dilation(&mut self, dilation_threshold: f32, max_debt: f32) {
+        self.inner.set_dilation(dilation_threshold, max_debt);
+    }
+    fn readback_depth(&self) -> usize {
+        DEPTH
+    }
+    fn submit_frame(&mut self, steps: usize) -> FrameData {
+        self.inner.submit_frame(steps)
+    }
+    // Like a GPU's, a stats-only frame comes back without its stars.
+    fn submit_stats(&mut self, steps: usize) -> FrameData {
+        FrameData { stars: Vec::new(), stats_only: true, ..self.inner.submit_frame(steps) }
+    }
+    fn finish_frame(&mut self, mut frame: FrameData) -> FrameData {
+        if self.lose_at == Some(self.finished) {
+            self.errors.record(DeviceFault::Lost("injected".into()));
+        }
+        self.finished += 1;
+        if self.errors.lost() {
+            frame.stars.clear();
+        }
+        frame
+    }
+    fn fault(&self) -> Option<ChronoError> {
+        self.errors.fault().map(|fault| fault.to_error())
+    }
+}
+
+// Steps like a frame run: DEPTH frames in flight, switching mode at SWITCH as it submits,
+// and stopping at the first fault it sees.
+fn run<S: Stepper>(sim: &mut S) -> Vec<FrameData> {
+    run_reading(sim, |_| true)
+}
+
+// Like run, but frames `reads` turns down bring back only their stats.
+fn run_reading<S: Stepper>(sim: &mut S, reads: fn(usize) -> bool) -> Vec<FrameData> {
+    sim.reset();
+    sim.set_mode(DilationMode::Newtonian);
+    let (mut pending, mut submitted, mut frames) = (VecDeque::new(), 0, Vec::new());
+    while frames.len() < FRAMES {
+        while submitted < FRAMES && pending.len() < sim.readback_depth() {
+            if submitted == SWITCH {
+                sim.set_mode(DilationMode::Chronoturin);
+            }
+            pending.push_back(match reads(submitted) {
+                true => sim.submit_frame(seeded(48, 21).steps_per_frame),
+                false => sim.submit_stats(seeded(48, 21).steps_per_frame),
+            });
+            submitted += 1;
+        }
+        frames.push(sim.finish_frame(pending.pop_front().unwrap()));
+        if sim.fault().is_some() {
+            break;
+        }
+    }
+    frames
+}
+
+fn reopen(opens: &Arc<AtomicUsize>, lose_at: Option<usize>) -> Reopen<Flaky> {
+    let opens = opens.clone();
+    Arc::new(move || {
+        opens.fetch_add(1, Ordering::SeqCst);
+        Ok(Flaky::new(lose_at))
+    })
+}
+
+fn hashes(frames: &[FrameData]) -> Vec<u64> {
+    frames.iter().map(|f| state_hash(&f.stars)).collect()
+}
+
+// Losing frame 3 rebuilds from the initial galaxy; losing frame 9, from the restart point
+// frame 7 copied, stepping frame 8 again.
+#[test]
+fn a_lost_device_is_rebuilt_from_the_last_restart_point() {
+    let expected = run(&mut Flaky::new(None));
+    for lose_at in [3, 9] {
+        let opens = Arc::new(AtomicUsize::new(0));
+        let mut sim = Recovering::new(Flaky::new(Some(lose_at)), &seeded(48, 21), reopen(&opens, None));
+        let frames = run(&mut sim);
+        assert!(sim.fault().is_none());
+        assert_eq!((opens.load(Ordering::SeqCst), sim.recoveries()), (1, 1));
+        // The resubmitted frames, the mode switch among them, step exactly as they would have.
+        assert_eq!(hashes(&frames), hashes(&expected), "lost at {}", lose_at);
+        assert_eq!(frames.iter().map(|f| f.sim_time).collect::<Vec<_>>(), expected.iter().map(|f| f.sim_time).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn stats_only_frames_since_the_restart_point_are_stepped_again() {
+    let expected = run(&mut Flaky::new(None));
+    let opens = Arc::new(AtomicUsize::new(0));
+    // Frames 1 to 5 bring back no stars, so losing 4 rebuilds from the initial galaxy and
+    // steps 0 to 3 again.
+    let mut sim = Recovering::new(Flaky::new(Some(4)), &seeded(48, 21), reopen(&opens, None));
+    let frames = run_reading(&mut sim, |frame| frame == 0 || frame > 5);
+    assert!(sim.fault().is_none() && sim.recoveries() == 1);
+    assert!(frames[1..6].iter().all(|f| f.stats_only && f.stars.is_empty()));
+    assert_eq!(hashes(&frames[6..]), hashes(&expected[6..]));
+    assert_eq!(frames.iter().map(|f| f.sim_time).collect::<Vec<_>>(), expected.iter().map(|f| f.sim_time).collect::<Vec<_>>());
+}
+
+#[test]
+fn a_loss_before_any_readback_restarts_from_the_initial_galaxy() {
+    let expected = run(&mut Flaky::new(None));
+    let opens = Arc::new(AtomicUsize::new(0));
+    let mut sim = Recovering::new(Flaky::new(Some(0)), &seeded(48, 21), reopen(&opens, None));
+    assert_eq!(hashes(&run(&mut sim)), hashes(&expected));
+}
+
+#[test]
+fn a_second_loss_is_left_to_the_caller() {
+    let opens = Arc::new(AtomicUsize::new(0));
+    let mut sim = Recovering::new(Flaky::new(Some(2)), &seeded(48, 21), reopen(&opens, Some(4)));
+    let frames = run(&mut sim);
+    assert!(matches!(sim.fault(), Some(ChronoError::DeviceLost(message)) if message == "injected"));
+    assert_eq!(opens.load(Ordering::SeqCst), 1);
+    // Frames 0 and 1, then, stepping those again first, 2 and 3 on the new device; 4, the
+    // fifth it finishes, is lost for good.
+    assert_eq!(frames.len(), 5);
+    assert!(frames[..4].iter().all(|f| f.stars.len() == 48) && frames[4].stars.is_empty());
+}
+
+#[test]
+fn a_device_that_will_not_reopen_keeps_the_fault() {
+    let reopen: Reopen<Flaky> = Arc::new(|| Err(ChronoError::AdapterNotFound));
+    let mut sim = Recovering::new(Flaky::new(Some(1)), &seeded(48, 21), reopen);
+    let frames = run(&mut sim);
+    assert_eq!((frames.len(), sim.recoveries()), (2, 0));
+    assert!(frames[1].stars.is_empty());
+    assert!(matches!(sim.fault(), Some(ChronoError::DeviceLost(_))));
+}
+
+#[test]
+fn device_faults_keep_the_first_and_name_it() {
+    let errors = DeviceErrors::default();
+    assert!(errors.first().is_none() && !errors.lost());
+    errors.record(DeviceFault::Lost("reset".into()));
+    errors.record(DeviceFault::Error("later".into()));
+    assert!(errors.lost());
+    assert_eq!(errors.first().as_deref(), Some("device lost: reset"));
+    assert_eq!(DeviceFault::Error("bad binding".into()).to_error().to_string(), "GPU validation error: bad binding");
+}
+
+// A run's device drops with its last simulation at exit, which is no loss.
+#[test]
+fn dropping_the_device_is_not_a_loss() {
+    let (device, queue) = require_gpu!();
+    let errors = DeviceErrors::watch_loss(&device);
+    let mut sim = Simulation::new(device, queue, &seeded(48, 21)).unwrap();
+    sim.watch_device(errors.clone());
+    let fork = sim.fork();
+    drop(sim);
+    drop(fork);
+    assert!(errors.fault().is_none());
+}
+
+// A driver that never answers is given the timeout and three more one-second polls.
+#[test]
+fn a_map_that_never_answers_times_out() {
+    let (device, _) = require_gpu!();
+    let (tx, rx) = std::sync::mpsc::channel();
+    tx.send(Ok(())).unwrap();
+    assert_eq!(await_map(&device, &rx, Duration::from_millis(10)), MapOutcome::Mapped);
+    tx.send(Err(wgpu::BufferAsyncError)).unwrap();
+    assert!(matches!(await_map(&device, &rx, Duration::from_millis(10)), MapOutcome::Failed(_)));
+    let start = Instant::now();
+    assert_eq!(await_map(&device, &rx, Duration::from_millis(10)), MapOutcome::TimedOut);
+    assert!(start.elapsed() >= Duration::from_secs(3));
+    drop(tx);
+    assert_eq!(await_map(&device, &rx, Duration::from_millis(10)), MapOutcome::Dropped);
+}
+
+// Stands in for await_map: whatever the fake callback sent, or nothing yet.
+fn poll_fake(rx: &MapReceiver) -> MapOutcome {
+    match rx.try_recv() {
+        Ok(Ok(())) => MapOutcome::Mapped,
+        Ok(Err(e)) => MapOutcome::Failed(e.to_string()),
+        Err(TryRecvError::Disconnected) => MapOutcome::Dropped,
+        Err(TryRecvError::Empty) => MapOutcome::TimedOut,
+    }
+}
+
+fn answered(result: Result<(), wgpu::BufferAsyncError>) -> MapReceiver {
+    let (tx, rx) = std::sync::mpsc::channel();
+    tx.send(result).unwrap();
+    rx
+}
+
+#[test]
+fn a_failed_map_is_mapped_once_more() {
+    let mut remapped = Vec::new();
+    let outcomes = settle_maps(vec![answered(Ok(())), answered(Err(wgpu::BufferAsyncError))], poll_fake, |i| {
+        remapped.push(i);
+        answered(Ok(()))
+    });
+    assert_eq!((outcomes, remapped), (vec![MapOutcome::Mapped, MapOutcome::Mapped], vec![1]));
+
+    // Failing again is the frame's fault, and the driver already unmapped the buffer.
+    let outcomes =
+        settle_maps(vec![answered(Err(wgpu::BufferAsyncError)), answered(Ok(()))], poll_fake, |_| answered(Err(wgpu::BufferAsyncError)));
+    assert!(matches!(&outcomes[0], MapOutcome::Failed(_)) && !outcomes[0].needs_unmap() && outcomes[1].needs_unmap());
+    assert!(matches!(map_result(&outcomes, 30.0), Err(ChronoError::MapFailed(_))));
+    assert!(map_result(&[MapOutcome::Mapped, MapOutcome::Mapped], 30.0).is_ok());
+}
+
+#[test]
+fn dropped_and_stalled_maps_are_not_mapped_again() {
+    let (stalled, pending) = std::sync::mpsc::channel();
+    let dropped = std::sync::mpsc::channel().1;
+    let outcomes = settle_maps(vec![dropped, pending], poll_fake, |_| panic!("mapped again"));
+    assert_eq!(outcomes, [MapOutcome::Dropped, MapOutcome::TimedOut]);
+    // A pending map is cancelled by unmapping; a dropped one's buffer is left alone.
+    assert!(!outcomes[0].needs_unmap() && outcomes[1].needs_unmap());
+    assert_eq!(
+        map_result(&outcomes, 30.0).unwrap_err().to_string(),
+        ChronoError::MapFailed("the map callback was dropped".into()).to_string()
+    );
+    assert!(matches!(map_result(&outcomes[1..], 2.5), Err(ChronoError::MapTimeout { secs }) if secs == 2.5));
+    drop(stalled);
+}
+
+#[test]
+fn a_failed_frame_stands_in_as_a_duplicate_or_a_black_frame() {
+    let mut sim = CpuSimulation::new(&seeded(48, 21));
+    let previous = sim.run_frame(2);
+    let lost = sim.run_frame(2);
+    let duplicate = lost.clone().stand_in(OnFrameError::Duplicate, Some(&previous));
+    assert!(duplicate.placeholder && duplicate.moments.is_none());
+    assert_eq!((state_hash(&duplicate.stars), duplicate.sim_time), (state_hash(&previous.stars), lost.sim_time));
+    let black = lost.clone().stand_in(OnFrameError::Black, Some(&previous));
+    assert!(black.placeholder && black.stars.is_empty() && black.image.is_none());
+    // Nothing to duplicate at the start of a pass.
+    assert!(lost.stand_in(OnFrameError::Duplicate, None).stars.is_empty());
+}
+
+#[test]
+fn healthy_readbacks_report_no_fault() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &SimConfig { map_timeout_secs: 5.0, ..seeded(48, 21) }).unwrap();
+    for _ in 0..3 {
+        let ticket = sim.submit_frame(2);
+        assert_eq!(sim.finish_frame(ticket).stars.len(), 48);
+    }
+    assert!(Stepper::fault(&sim).is_none());
+    assert_eq!(sim.map_failures(), 0);
+}
+
+#[test]
+fn the_map_timeout_must_be_positive() {
+    let check = |secs: &str| {
+        SimConfig::parse_from(["chronoturin", "--map-timeout-secs", secs]).validate(&cpu_reference::limits()).map_err(|e| e.to_string())
+    };
+    assert!(check("0.5").is_ok());
+    assert_eq!(check("0").unwrap_err(), "--map-timeout-secs must be positive");
+    assert!(SimConfig::parse_from(["chronoturin"]).map_timeout_secs == 30.0);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::sync::Arc;
+
+use chronoturin::reduction::create_pipeline;
+use chronoturin::{DilationMode, Quantity, Reduction, SimConfig, Simulation, Star, TYPE_A};
+use wgpu::util::DeviceExt;
+
+#[test]
+fn moments_match_the_read_back_stars() {
+    let (device, queue) = require_gpu!();
+    let config = SimConfig { stars: 5000, seed: Some(4), gpu_diagnostics: true, ..SimConfig::default() };
+    let mut sim = Simulation::new(device, queue, &config).unwrap();
+    sim.set_mode(DilationMode::Newtonian);
+    let ticket = sim.submit_frame(3);
+    let frame = sim.finish_frame(ticket);
+    let moments = frame.moments.expect("--gpu-diagnostics reports moments");
+
+    let mass: f64 = frame.stars.iter().map(|s| s.mass as f64).sum();
+    let kinetic: f64 = frame.stars.iter().map(|s| 0.5 * s.mass as f64 * (s.vx * s.vx + s.vy * s.vy + s.vz * s.vz) as f64).sum();
+    let com_x = frame.stars.iter().map(|s| (s.mass * s.x) as f64).sum::<f64>() / mass;
+    let p_y: f64 = frame.stars.iter().map(|s| (s.mass * s.vy) as f64).sum();
+    assert_eq!(moments.mass, mass);
+    assert!((moments.kinetic - kinetic).abs() < 1e-4 * kinetic, "{} vs {}", moments.kinetic, kinetic);
+    assert!((moments.center_of_mass[0] - com_x).abs() < 1e-2, "{} vs {}", moments.center_of_mass[0], com_x);
+    assert!((moments.momentum[1] - p_y).abs() < 1e-2 * p_y.abs().max(1.0), "{} vs {}", moments.momentum[1], p_y);
+}
+
+#[test]
+fn moments_are_off_without_the_flag() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &SimConfig { stars: 256, ..SimConfig::default() }).unwrap();
+    let ticket = sim.submit_frame(1);
+    assert_eq!(sim.finish_frame(ticket).moments, None);
+}
+
+#[test]
+fn active_count_strides_past_the_workgroup_cap() {
+    let (device, queue) = require_gpu!();
+    // More stars than 1024 workgroups of 256 cover in one sweep.
+    let count = 300_001u32;
+    let stars: Vec<Star> = (0..count)
+        .map(|i| Star {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            vx: 0.0,
+            vy: 0.0,
+            vz: 0.0,
+            mass: 1.0,
+            data_type: TYPE_A,
+            time_debt: 0.0,
+            active_flag: if i % 3 == 0 { 1.0 } else { 0.0 },
+        })
+        .collect();
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&stars),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let reduction = Reduction::new(&device, Arc::new(create_pipeline(&device)), &buffer, count, Quantity::ActiveCount);
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: reduction.partial_bytes(),
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    reduction.encode(&mut encoder);
+    encoder.copy_buffer_to_buffer(reduction.partials(), 0, &readback, 0, reduction.partial_bytes());
+    queue.submit(Some(encoder.finish()));
+    readback.slice(..).map_async(wgpu::MapMode::Read, |r| r.unwrap());
+    device.poll(wgpu::Maintain::Wait);
+
+    let sums = Reduction::sum(&readback.slice(..).get_mapped_range());
+    assert_eq!(sums[0], count.div_ceil(3) as f64);
+    assert!(sums[1..].iter().all(|&v| v == 0.0));
+}
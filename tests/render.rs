@@ -0,0 +1,194 @@
+use chronoturin::render::NEAR_PLANE;
+use chronoturin::{
+    generate_galaxy, project, render_frame, side_by_side, Camera, RenderSettings, SplatKernels, Star, Tonemap, TYPE_A, TYPE_B,
+};
+
+fn camera(width: u32, height: u32) -> Camera {
+    Camera::look_at([0.0, 0.0, -1000.0], [0.0; 3], [0.0, 1.0, 0.0], width, height, 800.0)
+}
+
+fn star_at(x: f32, y: f32, z: f32) -> Star {
+    Star { x, y, z, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type: TYPE_A, time_debt: 0.0, active_flag: 0.0 }
+}
+
+#[test]
+fn origin_lands_on_canvas_center() {
+    assert_eq!(project(&star_at(0.0, 0.0, 0.0), &camera(1024, 1024)), Some((512, 512)));
+    assert_eq!(project(&star_at(0.0, 0.0, 0.0), &camera(1920, 1080)), Some((960, 540)));
+}
+
+#[test]
+fn near_plane_is_exclusive() {
+    let cam = camera(1024, 1024);
+    assert_eq!(project(&star_at(0.0, 0.0, cam.position[2] + NEAR_PLANE), &cam), None);
+    assert!(project(&star_at(0.0, 0.0, cam.position[2] + NEAR_PLANE + 0.5), &cam).is_some());
+    assert_eq!(project(&star_at(0.0, 0.0, cam.position[2] - 50.0), &cam), None);
+}
+
+#[test]
+fn right_and_bottom_edges_are_outside() {
+    // At z = 0 the scale factor is 0.8, so x = 640 maps to exactly 512 + 512 = 1024.
+    let cam = camera(1024, 1024);
+    assert_eq!(project(&star_at(640.0, 0.0, 0.0), &cam), None);
+    assert_eq!(project(&star_at(0.0, 640.0, 0.0), &cam), None);
+    assert_eq!(project(&star_at(639.0, 639.0, 0.0), &cam), Some((1023, 1023)));
+    assert_eq!(project(&star_at(-640.0, -640.0, 0.0), &cam), Some((0, 0)));
+}
+
+#[test]
+fn nan_and_huge_positions_are_rejected() {
+    let cam = camera(1024, 1024);
+    assert_eq!(project(&star_at(f32::NAN, 0.0, 0.0), &cam), None);
+    assert_eq!(project(&star_at(0.0, f32::NAN, 0.0), &cam), None);
+    assert_eq!(project(&star_at(0.0, 0.0, f32::NAN), &cam), None);
+    assert_eq!(project(&star_at(f32::MAX, 0.0, 0.0), &cam), None);
+    assert_eq!(project(&star_at(f32::INFINITY, 0.0, 0.0), &cam), None);
+}
+
+#[test]
+fn large_canvases_render_without_overflow() {
+    let cam = camera(4096, 4096);
+    let stars = [star_at(0.0, 0.0, 0.0), star_at(2559.0, 2559.0, 0.0)];
+    let img = render_frame(&stars, &cam, &RenderSettings::legacy(), false);
+    assert_eq!(img.dimensions(), (4096, 4096));
+    assert_eq!(img.get_pixel(2048, 2048)[0], 200);
+    assert_eq!(img.get_pixel(4095, 4095)[0], 200);
+}
+
+// The pre-camera renderer, kept verbatim to prove the default framing is unchanged.
+fn legacy_project(star: &Star) -> Option<(u32, u32)> {
+    let rel_z = star.z - -1000.0;
+    if rel_z > 10.0 {
+        let factor = 800.0 / rel_z;
+        let screen_x = star.x * factor + 512.0;
+        let screen_y = star.y * factor + 512.0;
+        if (0.0..1024.0).contains(&screen_x) && (0.0..1024.0).contains(&screen_y) {
+            return Some((screen_x as u32, screen_y as u32));
+        }
+    }
+    None
+}
+
+#[test]
+fn default_camera_matches_legacy_projection() {
+    let cam = camera(1024, 1024);
+    for star in generate_galaxy(5000, 11) {
+        assert_eq!(project(&star, &cam), legacy_project(&star));
+    }
+    assert_eq!(cam.orbited(0.0), cam);
+}
+
+#[test]
+fn orbit_rotates_around_y_and_culls_behind_camera() {
+    let cam = camera(1024, 1024).orbited(90.0);
+    assert!((cam.position[0].abs() - 1000.0).abs() < 1e-2 && cam.position[2].abs() < 1e-2);
+    // The origin stays centered while the camera circles it.
+    assert_eq!(project(&star_at(0.0, 0.0, 0.0), &cam), Some((512, 512)));
+    // A star just behind the camera, on its side of the orbit, is culled.
+    let behind = [cam.position[0] * 1.01, 0.0, cam.position[2] * 1.01];
+    assert_eq!(project(&star_at(behind[0], behind[1], behind[2]), &cam), None);
+    // Half an orbit later the camera sits on +Z.
+    let opposite = camera(1024, 1024).orbited(180.0);
+    assert!((opposite.position[2] - 1000.0).abs() < 1e-2);
+}
+
+#[test]
+fn linear_without_falloff_reproduces_saturating_output() {
+    let cam = camera(64, 64);
+    let mut stars = vec![star_at(-40.0, 0.0, 0.0); 2];
+    stars.push(Star { active_flag: 1.0, data_type: TYPE_B, ..star_at(0.0, 0.0, 0.0) });
+    let img = render_frame(&stars, &cam, &RenderSettings::legacy(), true);
+    assert_eq!(img.get_pixel(0, 32).0, [255, 0, 0]); // 2 x 200 clips
+    assert_eq!(img.get_pixel(32, 32).0, [50, 150, 255]); // gold-tinted glow on a type B star
+}
+
+#[test]
+fn tonemapping_keeps_dense_regions_below_white() {
+    let cam = camera(64, 64);
+    let dense = vec![star_at(0.0, 0.0, 0.0); 40];
+    let sparse = vec![star_at(0.0, 0.0, 0.0); 4];
+    for tonemap in [Tonemap::Log, Tonemap::Reinhard] {
+        let settings = RenderSettings { tonemap, exposure: 1.0, falloff: 0.0, splat_size: None, ..RenderSettings::legacy() };
+        let hot = render_frame(&dense, &cam, &settings, false).get_pixel(32, 32)[0];
+        let warm = render_frame(&sparse, &cam, &settings, false).get_pixel(32, 32)[0];
+        assert!(hot < 255 && warm < hot, "{:?}: {} vs {}", tonemap, warm, hot);
+    }
+}
+
+#[test]
+fn falloff_dims_distant_stars() {
+    let cam = camera(64, 64);
+    let settings = RenderSettings { tonemap: Tonemap::Linear, exposure: 1.0, falloff: 2.0, splat_size: None, ..RenderSettings::legacy() };
+    // Depth 1000 is the reference distance; depth 2000 gets a quarter of the weight.
+    let near = render_frame(&[star_at(0.0, 0.0, 0.0)], &cam, &settings, false).get_pixel(32, 32)[0];
+    let far = render_frame(&[star_at(0.0, 0.0, 1000.0)], &cam, &settings, false).get_pixel(32, 32)[0];
+    assert_eq!((near, far), (200, 50));
+}
+
+#[test]
+fn splats_spread_light_to_neighbours() {
+    let cam = camera(64, 64);
+    let settings = RenderSettings { splat_size: Some(1.0), ..RenderSettings::legacy() };
+    let img = render_frame(&[star_at(0.0, 0.0, 0.0)], &cam, &settings, false);
+    // Peak matches a plotted point; neighbours fade with distance and nothing is lit far away.
+    assert_eq!(img.get_pixel(32, 32)[0], 200);
+    let (near, far) = (img.get_pixel(33, 32)[0], img.get_pixel(34, 32)[0]);
+    assert!(near < 200 && far < near && far > 0, "{} {}", near, far);
+    assert_eq!(img.get_pixel(40, 32)[0], 0);
+}
+
+#[test]
+fn splat_radius_shrinks_with_depth_and_clamps() {
+    let cam = camera(256, 256);
+    let settings = RenderSettings { splat_size: Some(1.0), ..RenderSettings::legacy() };
+    let lit = |z: f32| render_frame(&[star_at(0.0, 0.0, z)], &cam, &settings, false).pixels().filter(|p| p[0] > 0).count();
+    // Far stars fall back to the 1 px minimum radius; close ones stop at 6 px.
+    assert!(lit(0.0) > lit(9000.0));
+    assert_eq!(lit(9000.0), 9);
+    assert_eq!(lit(-980.0), 13 * 13);
+}
+
+#[test]
+fn off_canvas_splats_are_clipped_not_wrapped() {
+    let cam = camera(64, 64);
+    let settings = RenderSettings { splat_size: Some(3.0), ..RenderSettings::legacy() };
+    // Center just past the right edge: only the left half of the splat lands.
+    let img = render_frame(&[star_at(41.0, 0.0, 0.0)], &cam, &settings, false);
+    assert!(img.get_pixel(63, 32)[0] > 0);
+    assert_eq!(img.get_pixel(0, 32)[0], 0);
+    let gone = render_frame(&[star_at(400.0, 0.0, 0.0)], &cam, &settings, false);
+    assert!(gone.pixels().all(|p| p.0 == [0, 0, 0]));
+}
+
+#[test]
+fn kernels_peak_at_one() {
+    let kernels = SplatKernels::new();
+    for r in 0..=6 {
+        let k = kernels.get(r);
+        assert_eq!(k.len(), (2 * r + 1) * (2 * r + 1));
+        assert_eq!(k[k.len() / 2], 1.0);
+    }
+}
+
+#[test]
+fn side_by_side_doubles_width_with_a_divider() {
+    let left = image::RgbImage::from_pixel(32, 16, image::Rgb([255, 0, 0]));
+    let right = image::RgbImage::from_pixel(32, 16, image::Rgb([0, 0, 255]));
+    let img = side_by_side(&left, &right);
+    assert_eq!(img.dimensions(), (64, 16));
+    assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0]);
+    assert_eq!(img.get_pixel(63, 15).0, [0, 0, 255]);
+    let seam = img.get_pixel(32, 8).0;
+    assert!(seam != [255, 0, 0] && seam != [0, 0, 255]);
+}
+
+#[test]
+fn central_mass_is_drawn_as_a_yellow_blob() {
+    let central = chronoturin::presets::central_star(10000.0);
+    let img = render_frame(&[central], &camera(64, 64), &RenderSettings::legacy(), false);
+    let color = chronoturin::render::CENTRAL_COLOR;
+    let center = img.get_pixel(32, 32);
+    assert!((0..3).all(|c| center[c].abs_diff(color[c]) <= 5), "{:?}", center);
+    // Wider than a single point.
+    assert!(img.get_pixel(36, 32)[0] > 100 && img.get_pixel(36, 32)[1] > 100);
+}
@@ -0,0 +1,127 @@
+use proptest::prelude::*;
+
+use chronoturin::render::{project_to_screen, NEAR_PLANE};
+use chronoturin::{presets, project, render_frame, Camera, Projection, RenderSettings, Star, TYPE_A, TYPE_B};
+
+fn star_at(p: [f32; 3]) -> Star {
+    Star { x: p[0], y: p[1], z: p[2], vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type: TYPE_A, time_debt: 0.0, active_flag: 0.0 }
+}
+
+// Any finite coordinate, weighted towards the scales a galaxy and its camera use, with
+// values around 1e30 and the ends of the f32 range, where the view transform overflows.
+fn coordinate() -> impl Strategy<Value = f32> {
+    prop_oneof![
+        4 => -2000.0f32..2000.0,
+        2 => prop::num::f32::NORMAL | prop::num::f32::ZERO | prop::num::f32::SUBNORMAL,
+        1 => prop_oneof![Just(1e30f32), Just(-1e30), Just(f32::MAX), Just(f32::MIN), Just(f32::MIN_POSITIVE)],
+    ]
+}
+
+fn position() -> impl Strategy<Value = [f32; 3]> {
+    [coordinate(), coordinate(), coordinate()]
+}
+
+fn star() -> impl Strategy<Value = Star> {
+    (position(), prop_oneof![Just(TYPE_A), Just(TYPE_B)], any::<bool>(), 0.0f32..1e6).prop_map(|(p, data_type, active, mass)| Star {
+        data_type,
+        active_flag: if active { 1.0 } else { 0.0 },
+        mass,
+        ..star_at(p)
+    })
+}
+
+// A small canvas seen from anywhere near the galaxy, looking at a target at least a unit
+// ahead along +Z, so never straight along `up`, in either projection.
+fn camera() -> impl Strategy<Value = Camera> {
+    let offset = [-500.0f32..500.0, -500.0f32..500.0, 1.0f32..500.0];
+    let place = [-1000.0f32..1000.0, -1000.0f32..1000.0, -1000.0f32..1000.0];
+    (place, offset, 1u32..64, 1u32..48, 1.0f32..2000.0, any::<bool>()).prop_map(|(position, [dx, dy, dz], width, height, fov, equirect)| {
+        let target = [position[0] + dx, position[1] + dy, position[2] + dz];
+        let projection = if equirect { Projection::Equirect } else { Projection::Planar };
+        Camera::look_at(position, target, [0.0, 1.0, 0.0], width, height, fov).with_projection(projection)
+    })
+}
+
+fn settings() -> impl Strategy<Value = RenderSettings> {
+    (prop::option::of(0.1f32..8.0), 0.0f32..3.0, any::<bool>()).prop_map(|(splat_size, falloff, mass_weighted)| RenderSettings {
+        splat_size,
+        falloff,
+        mass_weighted,
+        ..RenderSettings::legacy()
+    })
+}
+
+// `star` moved to where its coordinates are (right, down, forward) in `cam`'s view.
+fn seen_at(star: Star, cam: &Camera) -> Star {
+    let (right, down, forward) = cam.axes();
+    let at = |k: usize| cam.position[k] + star.x * right[k] + star.y * down[k] + star.z * forward[k];
+    Star { x: at(0), y: at(1), z: at(2), ..star }
+}
+
+proptest! {
+    #[test]
+    fn projection_lands_on_the_canvas_or_nowhere(p in position(), cam in camera()) {
+        if let Some((x, y)) = project(&star_at(p), &cam) {
+            prop_assert!(x < cam.width && y < cam.height, "({}, {}) on a {}x{} canvas", x, y, cam.width, cam.height);
+        }
+        // Unclipped, the position may be anywhere or infinite, but never NaN, and never
+        // for a star on the wrong side of the near plane.
+        if let Some((x, y, depth)) = project_to_screen(&star_at(p), &cam) {
+            prop_assert!(!x.is_nan() && !y.is_nan());
+            prop_assert!(depth > NEAR_PLANE, "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn planar_projection_drops_everything_behind_the_near_plane(p in position(), depth in -1e30f32..=NEAR_PLANE) {
+        // Looking down +Z from the origin, view depth is the star's z.
+        let cam = Camera::look_at([0.0; 3], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0], 64, 48, 800.0);
+        prop_assert_eq!(project_to_screen(&star_at([p[0], p[1], depth]), &cam), None);
+    }
+
+    #[test]
+    fn rasterizing_stays_inside_the_image_and_repeats_exactly(
+        stars in prop::collection::vec(star(), 0..40),
+        central in prop::option::of(position()),
+        cam in camera(),
+        settings in settings(),
+        in_view in prop::collection::vec(any::<bool>(), 40),
+    ) {
+        // About half the stars are placed by their view coordinates, so plenty land just past
+        // the near plane and far to the side, where splats reach the ends of the pixel range.
+        let mut stars: Vec<Star> = stars.into_iter().zip(in_view).map(|(s, in_view)| if in_view { seen_at(s, &cam) } else { s }).collect();
+        if let Some(p) = central {
+            stars.insert(0, Star { x: p[0], y: p[1], z: p[2], ..presets::central_star(1000.0) });
+        }
+        let img = render_frame(&stars, &cam, &settings, true);
+        prop_assert_eq!(img.dimensions(), (cam.width, cam.height));
+        prop_assert_eq!(img, render_frame(&stars, &cam, &settings, true));
+    }
+}
+
+#[test]
+fn near_plane_and_extreme_stars_are_handled() {
+    let cam = Camera::look_at([0.0; 3], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0], 64, 48, 800.0);
+    // Exactly on the near plane is dropped; just past it, straight ahead, is the centre.
+    assert_eq!(project(&star_at([0.0, 0.0, NEAR_PLANE]), &cam), None);
+    assert_eq!(project(&star_at([0.0, 0.0, NEAR_PLANE * 1.001]), &cam), Some((32, 24)));
+    assert_eq!(project(&star_at([0.0, 0.0, -50.0]), &cam), None);
+    // Far ahead, a star 1e30 away is a point at the centre; off to the side of a near one,
+    // it is off the canvas.
+    assert_eq!(project(&star_at([0.0, 0.0, 1e30]), &cam), Some((32, 24)));
+    assert_eq!(project(&star_at([1e30, 0.0, 20.0]), &cam), None);
+    // Turned so that both the depth and the view x of a star at f32::MAX overflow, the
+    // perspective divide is inf * 0: no position at all rather than NaN.
+    let turned = Camera::look_at([0.0; 3], [90.825, -9.175, 40.825], [1.0, 1.0, -2.0], 64, 48, 800.0);
+    assert_eq!(project_to_screen(&star_at([f32::MAX; 3]), &turned), None);
+
+    // Splats of those stars, and a central mass among them, draw nothing and do not panic.
+    let settings = RenderSettings { splat_size: Some(8.0), ..RenderSettings::legacy() };
+    let far = [[1e30, 0.0, 20.0], [-1e30, -1e30, 20.0], [f32::MAX, f32::MAX, f32::MAX], [0.0, f32::MIN, 11.0]];
+    let mut stars: Vec<Star> = far.iter().map(|&p| star_at(p)).collect();
+    stars.push(Star { x: f32::MAX, y: f32::MAX, z: f32::MAX, ..presets::central_star(1000.0) });
+    for cam in [cam, turned] {
+        let img = render_frame(&stars, &cam, &settings, false);
+        assert!(img.pixels().all(|p| p.0 == [0, 0, 0]));
+    }
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::{cpu_reference, DisplacementStats, ModeReport, ReportConfig, RunReport, SimConfig, StepStats};
+
+use common::parse;
+
+fn report() -> RunReport {
+    let config = SimConfig::parse_from(["chronoturin", "--stars", "500", "--seed", "7", "--frames", "4", "--preset", "plummer"]);
+    let mode = |mode, adapter: &str, gpu: Option<StepStats>, energy_drift| ModeReport {
+        mode,
+        adapter: adapter.into(),
+        frames: 4,
+        wall: StepStats::from_samples(&[10.0, 12.0, 14.0, 40.0]),
+        gpu,
+        mean_active_fraction: 0.25,
+        energy_drift,
+    };
+    RunReport {
+        config: ReportConfig::from_config(&config),
+        modes: vec![
+            mode("NEWTONIAN", "GPU \"A\"", Some(StepStats::from_samples(&[2.0, 4.0])), Some(-0.5)),
+            mode("CHRONOTURIN", "CPU reference", None, None),
+        ],
+        total_wall_ms: 200.0,
+        divergence: Some(DisplacementStats { mean: 0.5, p99: 2.0, max: 3.0, non_finite: 1 }),
+        tuning: None,
+    }
+}
+
+#[test]
+fn json_keeps_every_key_in_order() {
+    let json = report().json();
+    let (head, resolved) = json.split_once(r#","resolved_config":"#).unwrap();
+    assert_eq!(
+        head,
+        concat!(
+            r#"{"schema":1,"config":{"stars":500,"seed":7,"preset":"plummer","frames":4,"steps_per_frame":5,"dt":0.05,"#,
+            r#""dilation_threshold":0.5,"max_debt":50.0,"types":2,"integrator":"euler","accel":"sampled","layout":"lockstep"},"#,
+            r#""modes":[{"mode":"NEWTONIAN","adapter":"GPU \"A\"","frames":4,"#,
+            r#""wall":{"mean_ms":19.0,"median_ms":13.0,"p95_ms":40.0,"std_dev_ms":14.094916341243982},"#,
+            r#""gpu":{"mean_ms":3.0,"median_ms":3.0,"p95_ms":4.0,"std_dev_ms":1.4142135623730951},"mean_active_fraction":0.25,"energy_drift":-0.5},"#,
+            r#"{"mode":"CHRONOTURIN","adapter":"CPU reference","frames":4,"#,
+            r#""wall":{"mean_ms":19.0,"median_ms":13.0,"p95_ms":40.0,"std_dev_ms":14.094916341243982},"#,
+            r#""gpu":null,"mean_active_fraction":0.25,"energy_drift":null}],"#,
+            r#""total_wall_ms":200.0,"divergence":{"mean":0.5,"p99":2.0,"max":3.0,"non_finite":1},"tuning":null"#
+        )
+    );
+    // Every flag follows, under its --config key.
+    let resolved: serde_json::Value = serde_json::from_str(resolved.strip_suffix('}').unwrap()).unwrap();
+    assert_eq!((&resolved["stars"], &resolved["frames"], &resolved["seed"]), (&500.into(), &4.into(), &7.into()));
+    assert_eq!(
+        (&resolved["preset"], &resolved["steps-per-frame"], &resolved["command"]),
+        (&"plummer".into(), &5.into(), &serde_json::Value::Null)
+    );
+    // Sequential runs have no divergence but keep the key.
+    let sequential = RunReport { divergence: None, ..report() };
+    assert!(sequential.json().contains(r#""divergence":null,"tuning":null,"resolved_config":{"#));
+}
+
+#[test]
+fn table_lists_each_mode() {
+    let table = report().table();
+    assert!(table.starts_with("Stars: 500 | Seed: 7 | Preset: plummer |"), "{}", table);
+    assert!(table.contains("| NEWTONIAN | GPU \"A\" | 4 | 19.0 | 13.0 | 3.000 | 3.000 | 25.0 | -5.000e-1 |"), "{}", table);
+    assert!(table.contains("| CHRONOTURIN | CPU reference | 4 | 19.0 | 13.0 | - | - | 25.0 | - |"), "{}", table);
+    assert!(table.contains("Final divergence from Newtonian: mean 0.5000 | p99 2.0000 | max 3.0000 | 1 non-finite"), "{}", table);
+
+    let limits = cpu_reference::limits();
+    assert!(parse(&["--report", "report.json"]).validate(&limits).is_ok());
+    assert!(parse(&["--report", "report.json", "bench"]).validate(&limits).is_err());
+    assert_eq!(ReportConfig::from_config(&parse(&["--sequential"])).layout, "sequential");
+}
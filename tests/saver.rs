@@ -0,0 +1,36 @@
+use chronoturin::FrameSaver;
+use image::RgbImage;
+
+#[test]
+fn finish_flushes_every_queued_frame() {
+    let dir = std::env::temp_dir().join(format!("chronoturin-saver-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut saver = FrameSaver::new(2, 1, None);
+    for frame in 0..6 {
+        saver.save(dir.join(format!("frame_{:03}.png", frame)), RgbImage::new(16, 16));
+    }
+    let stats = saver.finish();
+
+    assert_eq!(stats.frames_saved, 6);
+    assert_eq!(stats.failures, 0);
+    for frame in 0..6 {
+        assert!(dir.join(format!("frame_{:03}.png", frame)).exists());
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn jpg_paths_are_written_as_jpegs() {
+    let dir = std::env::temp_dir().join(format!("chronoturin-saver-jpeg-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut saver = FrameSaver::new(1, 1, None);
+    saver.save(dir.join("frame_000.jpg"), RgbImage::from_pixel(16, 8, image::Rgb([200, 40, 40])));
+    assert_eq!(saver.finish().frames_saved, 1);
+
+    let jpeg = image::open(dir.join("frame_000.jpg")).unwrap();
+    assert_eq!((jpeg.width(), jpeg.height()), (16, 8));
+    assert_eq!(&std::fs::read(dir.join("frame_000.jpg")).unwrap()[..2], &[0xFF, 0xD8]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::config_file::to_toml;
+use chronoturin::{cpu_reference, parse_mode_schedule, DilationMode, SimConfig};
+
+use DilationMode::{Chronoturin, Newtonian};
+
+#[test]
+fn schedules_give_each_frame_its_mode() {
+    let schedule = parse_mode_schedule("0:newton, 75:chrono,100:Newtonian").unwrap();
+    assert_eq!(schedule.entries(), [(0, Newtonian), (75, Chronoturin), (100, Newtonian)]);
+    assert_eq!(
+        [0, 74, 75, 99, 100, 500].map(|f| schedule.mode_at(f)),
+        [Newtonian, Newtonian, Chronoturin, Chronoturin, Newtonian, Newtonian]
+    );
+    assert_eq!([0, 74, 75, 100].map(|f| schedule.switch_at(f)), [None, None, Some(Chronoturin), Some(Newtonian)]);
+    assert_eq!(schedule.last_frame(), 100);
+    assert_eq!(schedule.to_string(), "0:newton,75:chrono,100:newton");
+    assert_eq!(parse_mode_schedule(&schedule.to_string()).unwrap(), schedule);
+}
+
+#[test]
+fn malformed_schedules_are_rejected_when_parsed() {
+    for (arg, expected) in [
+        ("10:newton,75:chrono", "the schedule must start at frame 0, not 10"),
+        ("0:newton,75:chrono,75:newton", "frame 75 is scheduled twice"),
+        ("0:newton,75:chrono,50:newton", "frame 50 comes after frame 75"),
+        ("0:newton,75", "\"75\" is not FRAME:MODE"),
+        ("0:newton,x:chrono", "\"x\": invalid digit"),
+        ("0:relativistic", "\"relativistic\" is not a mode"),
+    ] {
+        assert!(parse_mode_schedule(arg).unwrap_err().starts_with(expected), "{}", arg);
+    }
+    // Past the run's last frame is only known once --frames is.
+    let check = |args: &[&str]| common::parse(args).validate(&cpu_reference::limits());
+    assert!(check(&["--frames", "100", "--mode-schedule", "0:newton,75:chrono"]).is_ok());
+    let error = check(&["--frames", "50", "--mode-schedule", "0:newton,75:chrono"]).unwrap_err().to_string();
+    assert_eq!(error, "--mode-schedule switches at frame 75, but the run has 50 frames");
+    assert!(check(&["--frames", "100", "--mode-schedule", "0:newton,75:chrono", "--sequential"]).is_err());
+    assert!(check(&["--frames", "100", "--mode-schedule", "0:newton,75:chrono", "compare"]).is_err());
+}
+
+#[test]
+fn schedules_read_back_from_config_files() {
+    let config = SimConfig::parse_from(["chronoturin", "--frames", "100", "--mode-schedule", "0:chrono,40:newton"]);
+    assert!(to_toml(&config).contains("\nmode-schedule = \"0:chrono,40:newton\"\n"));
+}
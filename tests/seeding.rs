@@ -0,0 +1,17 @@
+use chronoturin::generate_galaxy;
+
+#[test]
+fn same_seed_produces_identical_passes() {
+    let newton_pass = generate_galaxy(4096, 1234);
+    let chrono_pass = generate_galaxy(4096, 1234);
+    let a: &[u8] = bytemuck::cast_slice(&newton_pass);
+    let b: &[u8] = bytemuck::cast_slice(&chrono_pass);
+    assert_eq!(a, b);
+}
+
+#[test]
+fn different_seeds_produce_different_galaxies() {
+    let a = generate_galaxy(64, 1);
+    let b = generate_galaxy(64, 2);
+    assert_ne!(bytemuck::cast_slice::<_, u8>(&a), bytemuck::cast_slice::<_, u8>(&b));
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use chronoturin::dump::STAR_FIELDS;
+use chronoturin::{Conservation, DilationMode, GalaxyState, MetricsRecord, SimConfig, Star, TYPE_B};
+
+// The star tests/golden/serde_v1.json holds, every field set.
+fn star() -> Star {
+    Star { x: 1.5, y: -2.25, z: 0.125, vx: 0.5, vy: -0.75, vz: 3.0, mass: 2.0, data_type: TYPE_B, time_debt: 0.25, active_flag: 1.0 }
+}
+
+fn config() -> SimConfig {
+    common::parse(&[
+        "--stars",
+        "5000",
+        "--seed",
+        "7",
+        "--integrator",
+        "leapfrog",
+        "--g",
+        "0.25",
+        "--boundary",
+        "periodic:500",
+        "--metrics-format",
+        "json",
+    ])
+}
+
+// common::record() with every optional column filled.
+fn record() -> MetricsRecord {
+    MetricsRecord {
+        frame: 40,
+        gpu_ms: Some(3.25),
+        stars: 5000,
+        sim_time: 10.0,
+        error_mean: Some(0.5),
+        conservation: Some(Conservation {
+            kinetic: 1250.0,
+            potential: Some(-3000.0),
+            momentum: [0.5, 0.0, -2.0],
+            angular_momentum: [0.0, 0.0, 1.0e6],
+        }),
+        readback_bytes: 200_016,
+        force_threads: Some(1_310_720),
+        radial_profile: vec![4000, 990, 10],
+        debt_histogram: vec![2000, 3000, 0],
+        interactions: vec![1.0, -1.0, -1.0, 1.0],
+        alive: 5000,
+        substeps: Some(2341),
+        lagrangian_radii: Some([12.5, 40.0, 101.25, 300.0, 2000.0]),
+        ..common::record()
+    }
+}
+
+// JSON gives back what went in, compared by `same`.
+fn json_round_trips<T: Serialize + DeserializeOwned>(value: &T, same: &impl Fn(&T, &T) -> bool) {
+    let json: T = serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap();
+    assert!(same(value, &json), "JSON round trip changed the value");
+}
+
+// So do JSON and bincode.
+fn round_trips<T: Serialize + DeserializeOwned>(value: &T, same: impl Fn(&T, &T) -> bool) {
+    json_round_trips(value, &same);
+    let bincode: T = bincode::deserialize(&bincode::serialize(value).unwrap()).unwrap();
+    assert!(same(value, &bincode), "bincode round trip changed the value");
+}
+
+fn keys<T: Serialize>(value: &T) -> Vec<String> {
+    serde_json::to_value(value).unwrap().as_object().unwrap().keys().cloned().collect()
+}
+
+#[test]
+fn stars_states_records_and_configs_round_trip() {
+    let bits = |a: &Star, b: &Star| bytemuck::bytes_of(a) == bytemuck::bytes_of(b);
+    round_trips(&star(), bits);
+    round_trips(&Star { x: f32::MIN_POSITIVE, vz: -0.0, ..star() }, bits);
+    let state = GalaxyState::new(12.5, 1.0, &config());
+    round_trips(&state, |a, b| bytemuck::bytes_of(a) == bytemuck::bytes_of(b));
+    round_trips(&record().conservation.unwrap(), |a, b| a == b);
+    // A record flattens its conservation columns into itself, which bincode cannot read back.
+    let debug = |a: &MetricsRecord, b: &MetricsRecord| format!("{:?}", a) == format!("{:?}", b);
+    json_round_trips(&record(), &debug);
+    json_round_trips(&MetricsRecord::placeholder(DilationMode::Newtonian, 41, 30.5, 1.25, (0.5, 50.0)), &debug);
+    round_trips(&config(), |a, b| format!("{:?}", a) == format!("{:?}", b));
+    let unknown = serde_json::to_string(&record()).unwrap().replace("CHRONOTURIN", "FAST");
+    assert!(serde_json::from_str::<MetricsRecord>(&unknown).is_err());
+}
+
+// The serialized names are file formats: the hand-written dump writer must agree with them.
+#[test]
+fn serialized_names_match_the_file_formats() {
+    let mut star_keys = keys(&star());
+    let mut columns: Vec<String> = STAR_FIELDS.iter().map(|f| f.to_string()).collect();
+    star_keys.sort();
+    columns.sort();
+    assert_eq!(star_keys, columns, "Star and the dump columns have drifted apart");
+
+    let state_keys = keys(&GalaxyState::new(0.0, 0.0, &config()));
+    assert!(state_keys.contains(&"g".to_string()) && state_keys.contains(&"max_debt".to_string()), "{:?}", state_keys);
+    assert!(!state_keys.iter().any(|k| k == "g_const" || k == "max_time_debt" || k == "_pad"), "{:?}", state_keys);
+}
+
+// tests/golden/serde_v1.json holds a Star and a GalaxyState as these derives first wrote
+// them; neither had a serialized form before. Renaming or removing a field breaks reading
+// it; adding one with a default does not.
+#[test]
+fn the_first_layout_still_deserializes() {
+    let text = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/serde_v1.json")).unwrap();
+    let fixture: serde_json::Value = serde_json::from_str(&text).unwrap();
+
+    let star: Star = serde_json::from_value(fixture["star"].clone()).unwrap();
+    assert_eq!(bytemuck::bytes_of(&star), bytemuck::bytes_of(&self::star()));
+    let state: GalaxyState = serde_json::from_value(fixture["galaxy_state"].clone()).unwrap();
+    assert_eq!(bytemuck::bytes_of(&state), bytemuck::bytes_of(&GalaxyState::new(12.5, 1.0, &config())));
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "serve")]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use image::{Rgb, RgbImage};
+
+use chronoturin::serve::STREAM_QUALITY;
+use chronoturin::{FrameSaver, FrameServer, Telemetry};
+
+fn request(addr: SocketAddr, path: &str) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    stream
+}
+
+fn get(addr: SocketAddr, path: &str) -> String {
+    let mut response = String::new();
+    request(addr, path).read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn clients_see_the_latest_metrics_and_frames_until_the_run_ends() {
+    let telemetry = Telemetry::new();
+    let server = FrameServer::start("127.0.0.1:0", telemetry.clone()).unwrap();
+    let addr = server.local_addr().unwrap();
+    // /metrics answers from the registry the run records into.
+    telemetry.record_frame("7", "chronoturin", 4, 10.0, None, 0.5);
+    let scrape = get(addr, "/metrics");
+    assert!(scrape.starts_with("HTTP/1.1 200") && scrape.contains("text/plain; version=0.0.4"), "{}", scrape);
+    assert!(scrape.contains("\nchronoturin_frame{run=\"7\",mode=\"chronoturin\"} 4\n"), "{}", scrape);
+    assert!(get(addr, "/metrics.json").ends_with("\r\n\r\nnull"));
+    server.publish_metrics(r#"{"frame":3}"#.into());
+    assert!(get(addr, "/metrics.json").ends_with(r#"{"frame":3}"#));
+    assert!(get(addr, "/stream.mjpeg").starts_with("HTTP/1.1 404"));
+    assert!(get(addr, "/").contains("<img src=\"/stream\""));
+
+    let mut stream = request(addr, "/stream");
+    let start = Instant::now();
+    while !server.streaming() {
+        assert!(start.elapsed() < Duration::from_secs(10), "the stream never connected");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    // Encoded on the save pool, as a frame run does it.
+    let mut saver = FrameSaver::new(1, 2, None);
+    let sink = server.frame_sink();
+    assert!(saver.encode_jpeg(RgbImage::from_pixel(16, 8, Rgb([200, 40, 40])), STREAM_QUALITY, move |jpeg| sink.publish(jpeg)));
+    saver.finish();
+    drop(server);
+
+    let mut body = Vec::new();
+    stream.read_to_end(&mut body).unwrap();
+    let text = String::from_utf8_lossy(&body);
+    assert!(text.starts_with("HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary=chronoturin-frame\r\n"), "{}", text);
+    assert!(text.ends_with("\r\n--chronoturin-frame--\r\n"), "{}", text);
+    let part = "--chronoturin-frame\r\nContent-Type: image/jpeg\r\nContent-Length: ";
+    let at = text.find(part).unwrap() + part.len();
+    let (length, _) = text[at..].split_once("\r\n\r\n").unwrap();
+    let jpeg_start = at + length.len() + 4;
+    let jpeg = &body[jpeg_start..jpeg_start + length.parse::<usize>().unwrap()];
+    let frame = image::load_from_memory(jpeg).unwrap().to_rgb8();
+    assert_eq!(frame.dimensions(), (16, 8));
+    assert!(frame.get_pixel(8, 4)[0] > 150);
+}
@@ -0,0 +1,51 @@
+use chronoturin::shader_layout::{check_builtin_shaders, check_shader};
+use chronoturin::Star;
+
+const SHADER: &str = include_str!("../src/shader.wgsl");
+// Reads neither data_type nor time_debt, so dropping or retyping them still parses.
+const REDUCTION: &str = include_str!("../src/reduction.wgsl");
+
+#[test]
+fn embedded_shaders_match_the_rust_structs() {
+    assert_eq!(check_builtin_shaders(), Ok(()));
+}
+
+#[test]
+fn a_field_changed_on_one_side_is_named() {
+    let check = |source: &str, from: &str, to: &str| {
+        assert!(source.contains(from), "{}", from);
+        check_shader(&source.replacen(from, to, 1)).unwrap_err()
+    };
+    assert_eq!(
+        check(SHADER, "    mass: f32, \n", "    mass: f32, \n    charge: f32,\n"),
+        "WGSL Star has `charge`, which the Rust Star lacks"
+    );
+    assert_eq!(check(REDUCTION, "    time_debt: f32,\n", ""), "Star.time_debt is missing from the WGSL struct");
+    assert_eq!(check(REDUCTION, "    data_type: f32,\n", "    data_type: u32,\n"), "Star.data_type is f32 in Rust but not in WGSL");
+    assert_eq!(
+        check(SHADER, "    x: f32, y: f32, z: f32, ", "    y: f32, x: f32, z: f32, "),
+        "Star.x is at byte 4 in WGSL but byte 0 in Rust"
+    );
+    // The kernels read every GalaxyState field, so the dropped one's uses go too.
+    let dropped = SHADER.replacen("    boundary_size: f32,\n", "", 1).replace("state.boundary_size", "state.boundary_kind");
+    assert_eq!(check_shader(&dropped).unwrap_err(), "GalaxyState.boundary_size is missing from the WGSL struct");
+    assert_eq!(check(SHADER, "    pad1: f32,\n", "    pad1: f32,\n    pad2: f32,\n"), "WGSL GalaxyState is 84 bytes, the Rust one 80");
+    // Parse errors come back with the line.
+    assert!(check(SHADER, "let dx = ", "let dx == ").contains("wgsl:"));
+}
+
+#[test]
+fn stars_round_trip_through_bytes() {
+    let star =
+        Star { x: 1.5, y: -2.25, z: 1e-30, vx: f32::MAX, vy: -0.0, vz: 3.0, mass: 0.75, data_type: 1.0, time_debt: 12.5, active_flag: 1.0 };
+    let bytes = bytemuck::bytes_of(&star).to_vec();
+    assert_eq!(bytes.len(), 40);
+    // The shader reads x at byte 0 and active_flag at byte 36.
+    assert_eq!(bytes[0..4], 1.5f32.to_ne_bytes());
+    assert_eq!(bytes[36..40], 1.0f32.to_ne_bytes());
+    let back: Star = bytemuck::pod_read_unaligned(&bytes);
+    let fields = |s: &Star| [s.x, s.y, s.z, s.vx, s.vy, s.vz, s.mass, s.data_type, s.time_debt, s.active_flag].map(f32::to_bits);
+    assert_eq!(fields(&back), fields(&star));
+    let stars: &[Star] = bytemuck::cast_slice(&bytes);
+    assert_eq!(fields(&stars[0]), fields(&star));
+}
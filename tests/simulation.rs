@@ -0,0 +1,369 @@
+mod common;
+
+use chronoturin::{DilationMode, GalaxyState, Integrator, SimConfig, Simulation, Star, DT};
+
+use common::seeded;
+
+#[test]
+fn star_buffer_size_matches_star_count() {
+    let config = seeded(1000, 7);
+    assert_eq!(config.star_buffer_size(), Some(1000 * std::mem::size_of::<Star>() as u64));
+    assert_eq!(config.workgroup_count(), 4);
+}
+
+#[test]
+fn readback_before_stepping_returns_initial_stars() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &seeded(1000, 7)).unwrap();
+    assert_eq!(sim.star_buffer_size(), 1000 * std::mem::size_of::<Star>() as u64);
+
+    let stars = sim.read_stars();
+    assert_eq!(bytemuck::cast_slice::<_, u8>(&stars), bytemuck::cast_slice::<_, u8>(sim.initial_stars()));
+}
+
+#[test]
+fn stepping_moves_stars() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &seeded(1000, 7)).unwrap();
+    sim.set_mode(DilationMode::Chronoturin);
+    sim.step(3);
+
+    let stars = sim.read_stars();
+    assert_eq!(stars.len(), 1000);
+    assert!(stars.iter().zip(sim.initial_stars()).any(|(a, b)| a.x != b.x));
+}
+
+#[test]
+fn time_seed_advances_every_step() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &seeded(1000, 7)).unwrap();
+    sim.step(12);
+
+    let state = sim.read_state().unwrap();
+    assert!((state.time_seed - 12.0 * DT).abs() < 1e-5, "time_seed = {}", state.time_seed);
+    assert_eq!(state.time_seed, sim.sim_time());
+}
+
+// A kernel that only records, from invocation 0, the clock its step saw: step K writes it into
+// star K's time_debt. With two ring slots, five steps also cross batch loads.
+#[test]
+fn every_batched_step_binds_its_own_state() {
+    let (device, queue) = require_gpu!();
+    let config = SimConfig { steps_per_frame: 2, ..seeded(1000, 7) };
+    let mut sim = Simulation::new(device, queue, &config).unwrap();
+    let source = include_str!("../src/shader.wgsl");
+    let header = "fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {";
+    let recorder = format!(
+        "{}\n    if (global_id.x == 0u) {{ stars0[u32(round(state.time_seed / state.dt)) - 1u].time_debt = state.time_seed; }}\n    if (true) {{ return; }}\n",
+        header
+    );
+    pollster::block_on(sim.reload_kernels(&source.replacen(header, &recorder, 1))).unwrap();
+
+    let stars = sim.step_and_read(5);
+    let mut time_seed = 0.0f32;
+    for (k, star) in stars.iter().take(5).enumerate() {
+        time_seed += config.dt;
+        assert_eq!(star.time_debt, time_seed, "step {} saw another step's state", k);
+    }
+    assert_eq!(stars[5].time_debt, sim.initial_stars()[5].time_debt);
+    assert_eq!(sim.read_state().unwrap().time_seed, time_seed);
+}
+
+#[test]
+fn batched_and_legacy_submission_agree() {
+    let (device, queue) = require_gpu!();
+    let legacy_config = SimConfig { legacy_submit: true, ..seeded(1000, 7) };
+    let mut batched = Simulation::new(device.clone(), queue.clone(), &seeded(1000, 7)).unwrap();
+    let mut legacy = Simulation::new(device, queue, &legacy_config).unwrap();
+    batched.set_mode(DilationMode::Chronoturin);
+    legacy.set_mode(DilationMode::Chronoturin);
+
+    let a = batched.step_and_read(5);
+    let b = legacy.step_and_read(5);
+    assert_eq!(batched.read_state().unwrap().time_seed, legacy.read_state().unwrap().time_seed);
+    assert_eq!(bytemuck::cast_slice::<_, u8>(&a), bytemuck::cast_slice::<_, u8>(&b));
+}
+
+#[test]
+fn reset_after_mode_switch_restores_initial_positions() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &seeded(1000, 7)).unwrap();
+    sim.set_mode(DilationMode::Newtonian);
+    let moved = sim.step_and_read(5);
+    assert!(moved.iter().zip(sim.initial_stars()).any(|(a, b)| a.x != b.x));
+
+    sim.reset();
+    sim.set_mode(DilationMode::Chronoturin);
+    let fresh = sim.read_stars();
+    assert_eq!(bytemuck::cast_slice::<_, u8>(&fresh), bytemuck::cast_slice::<_, u8>(sim.initial_stars()));
+    assert_eq!(sim.read_state().unwrap().time_seed, 0.0);
+}
+
+#[test]
+fn pipelined_frames_match_serial_frames() {
+    let (device, queue) = require_gpu!();
+    let serial_config = SimConfig { readback_buffers: 1, ..seeded(1000, 7) };
+    let mut serial = Simulation::new(device.clone(), queue.clone(), &serial_config).unwrap();
+    let mut pipelined = Simulation::new(device, queue, &SimConfig { readback_buffers: 3, ..seeded(1000, 7) }).unwrap();
+
+    let expected: Vec<Vec<Star>> = (0..4).map(|_| serial.step_and_read(2)).collect();
+
+    let mut tickets: std::collections::VecDeque<_> = (0..3).map(|_| pipelined.submit_frame(2)).collect();
+    let mut frames = Vec::new();
+    while let Some(ticket) = tickets.pop_front() {
+        frames.push(pipelined.finish_frame(ticket).stars);
+        if frames.len() == 1 {
+            tickets.push_back(pipelined.submit_frame(2));
+        }
+    }
+
+    for (a, b) in expected.iter().zip(&frames) {
+        assert_eq!(bytemuck::cast_slice::<_, u8>(a), bytemuck::cast_slice::<_, u8>(b));
+    }
+}
+
+#[test]
+fn gpu_time_reported_only_with_timestamp_support() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &seeded(1000, 7)).unwrap();
+    let ticket = sim.submit_frame(3);
+    let frame = sim.finish_frame(ticket);
+    assert_eq!(frame.gpu_ms.is_some(), sim.has_timestamps());
+    assert!(frame.gpu_ms.unwrap_or(0.0) >= 0.0);
+
+    // Plain readbacks run no passes, so there is nothing to time.
+    let ticket = sim.submit_frame(0);
+    assert!(sim.finish_frame(ticket).gpu_ms.is_none());
+}
+
+#[test]
+fn newtonian_mode_counts_every_star_as_active() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &seeded(1000, 7)).unwrap();
+    sim.set_mode(DilationMode::Newtonian);
+    let ticket = sim.submit_frame(4);
+    let frame = sim.finish_frame(ticket);
+    assert_eq!(frame.active_updates, 4 * 1000);
+    assert_eq!(frame.active_fraction(), 1.0);
+
+    // The counter is cleared per frame, and matches the active flags of a single step.
+    sim.set_mode(DilationMode::Chronoturin);
+    let ticket = sim.submit_frame(1);
+    let frame = sim.finish_frame(ticket);
+    let flagged = frame.stars.iter().filter(|s| s.active_flag > 0.5).count() as u32;
+    assert_eq!(frame.active_updates, flagged);
+}
+
+#[test]
+fn forked_simulations_step_independently_from_the_same_start() {
+    let (device, queue) = require_gpu!();
+    let mut newton = Simulation::new(device.clone(), queue.clone(), &seeded(1000, 7)).unwrap();
+    let mut chrono = newton.fork();
+    chrono.set_mode(DilationMode::Chronoturin);
+    let lockstep: Vec<_> = [&mut newton, &mut chrono].into_iter().map(|sim| sim.step_and_read(4)).collect();
+
+    // Each must match a standalone run of its own mode.
+    for (mode, stars) in [DilationMode::Newtonian, DilationMode::Chronoturin].into_iter().zip(&lockstep) {
+        let mut solo = Simulation::new(device.clone(), queue.clone(), &seeded(1000, 7)).unwrap();
+        solo.set_mode(mode);
+        let expected = solo.step_and_read(4);
+        assert_eq!(bytemuck::cast_slice::<_, u8>(stars), bytemuck::cast_slice::<_, u8>(&expected), "{:?}", mode);
+    }
+    assert_ne!(bytemuck::cast_slice::<_, u8>(&lockstep[0]), bytemuck::cast_slice::<_, u8>(&lockstep[1]));
+}
+
+#[test]
+fn central_mass_stays_pinned_and_pulls_stars_in() {
+    let (device, queue) = require_gpu!();
+    let config = SimConfig { central_mass: Some(10000.0), cold_start: true, ..seeded(1000, 7) };
+    let mut sim = Simulation::new(device, queue, &config).unwrap();
+    assert_eq!(sim.initial_stars().len(), 1001);
+    sim.set_mode(DilationMode::Newtonian);
+    let stars = sim.step_and_read(10);
+
+    let center = stars[0];
+    assert_eq!((center.x, center.y, center.z, center.active_flag), (0.0, 0.0, 0.0, 0.0));
+    // Every other star starts at rest, so its velocity now points at the origin.
+    let inward = stars[1..].iter().filter(|s| s.x * s.vx + s.y * s.vy + s.z * s.vz < 0.0).count();
+    assert!(inward > 990, "{} of 1000 stars fell inward", inward);
+}
+
+#[test]
+fn invocations_past_the_last_star_of_a_chunk_do_nothing() {
+    let (device, queue) = require_gpu!();
+    // 300-star chunks in 256-wide workgroups: every dispatch overhangs its chunk by 212 invocations.
+    for config in [seeded(1000, 7), SimConfig { chunk_stars: Some(300), ..seeded(1000, 7) }] {
+        let mut sim = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+        sim.set_mode(DilationMode::Newtonian);
+        let ticket = sim.submit_frame(3);
+        let frame = sim.finish_frame(ticket);
+        assert_eq!(frame.stars.len(), 1000);
+        assert_eq!(frame.active_updates, 3 * 1000, "{} chunks", sim.chunk_count());
+        assert!(frame.stars.iter().all(|s| s.x.is_finite() && s.time_debt == 0.0 && s.active_flag == 1.0));
+    }
+}
+
+#[test]
+fn workgroup_size_flag_reaches_the_compiled_shader() {
+    let source = chronoturin::simulation::shader_source(64, chronoturin::Precision::F32);
+    assert!(source.contains("const WORKGROUP_SIZE: u32 = 64u;") && source.contains("@workgroup_size(WORKGROUP_SIZE)"));
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &SimConfig { workgroup_size: 64, ..seeded(1000, 7) }).unwrap();
+    sim.set_mode(DilationMode::Newtonian);
+    let ticket = sim.submit_frame(2);
+    assert_eq!(sim.finish_frame(ticket).active_updates, 2 * 1000);
+}
+
+#[test]
+fn per_mode_kernels_step_like_the_unified_kernel() {
+    let source = chronoturin::simulation::kernel_source(64, Some(DilationMode::Chronoturin));
+    assert!(source.contains("const KERNEL_MODE: u32 = 1u;") && source.contains("const WORKGROUP_SIZE: u32 = 64u;"));
+    let (device, queue) = require_gpu!();
+    let mut specialized = Simulation::new(device.clone(), queue.clone(), &seeded(1000, 7)).unwrap();
+    let mut unified = Simulation::new(device, queue, &SimConfig { unified_kernel: true, ..seeded(1000, 7) }).unwrap();
+    // Chronoturin second, so the Newtonian kernel also steps stars the other left in debt.
+    for mode in [DilationMode::Chronoturin, DilationMode::Newtonian] {
+        specialized.set_mode(mode);
+        unified.set_mode(mode);
+        let (a, b) = (specialized.step_and_read(4), unified.step_and_read(4));
+        for (s, u) in a.iter().zip(&b) {
+            assert_eq!((s.x.to_bits(), s.vz.to_bits(), s.active_flag), (u.x.to_bits(), u.vz.to_bits(), u.active_flag), "{:?}", mode);
+        }
+    }
+}
+
+#[test]
+fn try_fork_copies_the_simulation_when_it_fits() {
+    let (device, queue) = require_gpu!();
+    let sim = Simulation::new(device, queue, &seeded(1000, 7)).unwrap();
+    let mut twin = pollster::block_on(sim.try_fork()).expect("1000 stars fit twice");
+    let mut forked = sim.fork();
+    twin.set_mode(DilationMode::Chronoturin);
+    forked.set_mode(DilationMode::Chronoturin);
+    let (a, b) = (twin.step_and_read(3), forked.step_and_read(3));
+    assert!(a.iter().zip(&b).all(|(s, t)| (s.x.to_bits(), s.vy.to_bits()) == (t.x.to_bits(), t.vy.to_bits())));
+}
+
+#[test]
+fn galaxy_state_matches_the_wgsl_uniform_layout() {
+    assert_eq!(std::mem::size_of::<GalaxyState>(), 80);
+}
+
+#[test]
+fn dilation_threshold_and_max_debt_set_the_active_count() {
+    let (device, queue) = require_gpu!();
+    let updates = |threshold: f32, max_debt: f32| {
+        let config = SimConfig { dilation_threshold: threshold, max_debt, ..seeded(1000, 7) };
+        let mut sim = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+        sim.set_mode(DilationMode::Chronoturin);
+        let ticket = sim.submit_frame(8);
+        sim.finish_frame(ticket).active_updates
+    };
+    // No tension is below 0, so nothing sleeps.
+    assert_eq!(updates(0.0, 50.0), 1000 * 8);
+    // Everything sleeps, accruing a quarter step per dispatch: two updates each in eight steps.
+    assert_eq!(updates(f32::MAX, 4.0), 1000 * 2);
+    let default = updates(0.5, 50.0);
+    assert!(default > 0 && default < 1000 * 8, "{}", default);
+}
+
+#[test]
+fn set_dilation_applies_to_the_next_stats_frame() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &SimConfig { dilation_threshold: 0.0, max_debt: 4.0, ..seeded(1000, 7) }).unwrap();
+    sim.set_mode(DilationMode::Chronoturin);
+    let ticket = sim.submit_stats(8);
+    let frame = sim.finish_frame(ticket);
+    assert!(frame.stars.is_empty());
+    assert_eq!(frame.active_updates, 1000 * 8);
+
+    // The --view slider: same pipeline and buffers, new uniform.
+    sim.set_dilation(f32::MAX, 4.0);
+    let ticket = sim.submit_stats(8);
+    assert_eq!(sim.finish_frame(ticket).active_updates, 1000 * 2);
+    // Both readback slots have now skipped the stars; full frames still bring them back.
+    assert_eq!(sim.step_and_read(0).len(), 1000);
+    assert_eq!(sim.step_and_read(0).len(), 1000);
+}
+
+#[test]
+fn softening_and_g_reach_the_shader() {
+    let (device, queue) = require_gpu!();
+    let run = |config: SimConfig| {
+        let mut sim = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+        sim.set_mode(DilationMode::Newtonian);
+        let state = sim.read_state().unwrap();
+        (state.softening, state.g_const, sim.step_and_read(5))
+    };
+    let (softening, g, baseline) = run(seeded(1000, 7));
+    assert_eq!((softening, g), (chronoturin::galaxy::DEFAULT_SOFTENING, chronoturin::galaxy::DEFAULT_G));
+
+    let (softening, _, softer) = run(SimConfig { softening: 30.0, ..seeded(1000, 7) });
+    assert_eq!(softening, 30.0);
+    assert_ne!(bytemuck::cast_slice::<_, u8>(&softer), bytemuck::cast_slice::<_, u8>(&baseline));
+    let (_, g, stronger) = run(SimConfig { g: 2.0, ..seeded(1000, 7) });
+    assert_eq!(g, 2.0);
+    assert_ne!(bytemuck::cast_slice::<_, u8>(&stronger), bytemuck::cast_slice::<_, u8>(&baseline));
+}
+
+#[test]
+fn two_body_trajectories_converge_as_dt_shrinks() {
+    let (device, queue) = require_gpu!();
+    // A pinned central mass and one star on a roughly circular orbit. With only two
+    // stars every random sample is skipped, so the central pull is the whole force.
+    let orbiter = Star {
+        x: 100.0,
+        y: 0.0,
+        z: 0.0,
+        vx: 0.0,
+        vy: 40.0,
+        vz: 0.0,
+        mass: 1.0,
+        data_type: chronoturin::TYPE_A,
+        time_debt: 0.0,
+        active_flag: 0.0,
+    };
+    let stars = vec![chronoturin::presets::central_star(1.0), orbiter];
+    let end_position = |dt: f32, steps: usize| {
+        let config = SimConfig { stars: 2, dt, g: 20.0, ..SimConfig::default() };
+        let mut sim = Simulation::with_stars(device.clone(), queue.clone(), &config, stars.clone()).unwrap();
+        sim.set_mode(DilationMode::Newtonian);
+        let end = sim.step_and_read(steps)[1];
+        assert!((sim.sim_time() - dt * steps as f32).abs() < 1e-3);
+        [end.x, end.y, end.z]
+    };
+    let distance = |a: [f32; 3], b: [f32; 3]| ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt();
+
+    // Same simulated time (2.0) at dt, dt/2 and dt/4.
+    let coarse = end_position(0.04, 50);
+    let medium = end_position(0.02, 100);
+    let fine = end_position(0.01, 200);
+    let (coarse_err, medium_err) = (distance(coarse, fine), distance(medium, fine));
+    assert!(coarse_err > 0.0);
+    assert!(medium_err < 0.75 * coarse_err, "dt/2 error {} vs dt error {}", medium_err, coarse_err);
+}
+
+#[test]
+fn leapfrog_keeps_a_circular_orbit_while_euler_spirals_in() {
+    let (device, queue) = require_gpu!();
+    // Circular speed for the shader's central pull G * 500 * SAMPLES * M / (r^2 + softening^2).
+    let g = 20.0;
+    let (r, soft_sq) = (100.0f32, 10.0f32);
+    let v = (g * 500.0 * 32.0 * r / (r * r + soft_sq)).sqrt();
+    let orbiter =
+        Star { x: r, y: 0.0, z: 0.0, vx: 0.0, vy: v, vz: 0.0, mass: 1.0, data_type: chronoturin::TYPE_A, time_debt: 0.0, active_flag: 0.0 };
+    let stars = vec![chronoturin::presets::central_star(1.0), orbiter];
+    // About three orbits in 750 steps, read back every 50.
+    let radii = |integrator: Integrator| {
+        let config = SimConfig { stars: 2, g, integrator, ..SimConfig::default() };
+        let mut sim = Simulation::with_stars(device.clone(), queue.clone(), &config, stars.clone()).unwrap();
+        sim.set_mode(DilationMode::Newtonian);
+        (0..15).map(|_| sim.step_and_read(50)[1]).map(|s| (s.x * s.x + s.y * s.y + s.z * s.z).sqrt()).collect::<Vec<f32>>()
+    };
+
+    let leapfrog = radii(Integrator::Leapfrog);
+    assert!(leapfrog.iter().all(|&rad| (rad - r).abs() < 0.05 * r), "leapfrog radii {:?}", leapfrog);
+    // Euler's damping drains the orbit until the star falls through the center.
+    let euler = radii(Integrator::Euler);
+    assert!(euler[0] < 0.7 * r, "euler radii {:?}", euler);
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::{cpu_reference, morton_key, quantization_cube, DilationMode, SimConfig, Simulation, Star};
+
+fn config(sort_every: usize) -> SimConfig {
+    SimConfig { central_mass: Some(500.0), chunk_stars: Some(1024), sort_every: Some(sort_every), ..common::seeded(3000, 8) }
+}
+
+// The star buffers as the GPU holds them, in storage order.
+fn raw_stars(sim: &Simulation) -> Vec<Star> {
+    let (device, queue) = (sim.device(), sim.queue());
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    let copies: Vec<wgpu::Buffer> = sim
+        .star_buffers()
+        .map(|(buffer, _)| {
+            let copy = device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: buffer.size(),
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            encoder.copy_buffer_to_buffer(buffer, 0, &copy, 0, buffer.size());
+            copy
+        })
+        .collect();
+    queue.submit(Some(encoder.finish()));
+    let mut stars = Vec::new();
+    for copy in &copies {
+        copy.slice(..).map_async(wgpu::MapMode::Read, |r| r.unwrap());
+        device.poll(wgpu::Maintain::Wait);
+        stars.extend_from_slice(bytemuck::cast_slice(&copy.slice(..).get_mapped_range()));
+    }
+    stars
+}
+
+fn ascending(keys: &[u32]) -> bool {
+    keys.windows(2).all(|w| w[0] <= w[1])
+}
+
+#[test]
+fn sorted_buffers_follow_the_morton_curve_and_read_back_in_creation_order() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &config(1)).unwrap();
+    assert!(sim.chunk_count() > 1);
+    let initial = sim.initial_stars().to_vec();
+    let cube = quantization_cube(&initial);
+    let keys = |stars: &[Star]| stars.iter().map(|s| morton_key(s, cube)).collect::<Vec<u32>>();
+    assert!(!ascending(&keys(&raw_stars(&sim))), "the preset already comes in Morton order");
+
+    sim.sort_stars();
+    let raw = raw_stars(&sim);
+    assert!(ascending(&keys(&raw)));
+    assert_eq!(raw[0].data_type, chronoturin::TYPE_CENTRAL, "the central mass stays at index 0");
+    // The readback undoes the permutation exactly.
+    let read = sim.read_stars();
+    assert_eq!(bytemuck::cast_slice::<Star, u32>(&read), bytemuck::cast_slice::<Star, u32>(&initial));
+
+    // Sorting before every frame: each star keeps its own type and mass through the shuffles.
+    sim.set_mode(DilationMode::Chronoturin);
+    for _ in 0..3 {
+        let ticket = sim.submit_frame(2);
+        let frame = sim.finish_frame(ticket);
+        assert!(frame.stars.iter().zip(&initial).all(|(s, i)| (s.data_type, s.mass) == (i.data_type, i.mass)));
+        assert_eq!((frame.stars[0].x, frame.stars[0].y, frame.stars[0].z), (initial[0].x, initial[0].y, initial[0].z));
+    }
+    assert!(keys(&raw_stars(&sim)).windows(2).filter(|w| w[0] > w[1]).count() * 10 < initial.len());
+
+    // reset() uploads creation order again, and the first frame after it sorts.
+    sim.reset();
+    assert_eq!(bytemuck::cast_slice::<Star, u32>(&raw_stars(&sim)), bytemuck::cast_slice::<Star, u32>(&initial));
+    let ticket = sim.submit_frame(0);
+    sim.finish_frame(ticket);
+    assert!(ascending(&keys(&raw_stars(&sim))));
+}
+
+#[test]
+fn sort_every_counts_frames() {
+    let (device, queue) = require_gpu!();
+    let mut sim = Simulation::new(device, queue, &config(3)).unwrap();
+    let cube = quantization_cube(sim.initial_stars());
+    let sorted = |sim: &Simulation| ascending(&raw_stars(sim).iter().map(|s| morton_key(s, cube)).collect::<Vec<u32>>());
+    // Stepping outside a frame scatters the order again; only frames 0 and 3 sort it.
+    for frame in 0..5 {
+        sim.step(5);
+        let ticket = sim.submit_frame(0);
+        sim.finish_frame(ticket);
+        assert_eq!(sorted(&sim), frame % 3 == 0, "frame {}", frame);
+    }
+    sim.reset();
+    assert!(!sorted(&sim));
+}
+
+#[test]
+fn sort_every_needs_the_gpu() {
+    let limits = cpu_reference::limits();
+    assert!(SimConfig::parse_from(["chronoturin", "--sort-every", "10"]).validate(&limits).is_ok());
+    assert!(SimConfig::parse_from(["chronoturin", "--sort-every", "0"]).validate(&limits).is_err());
+    assert!(SimConfig::parse_from(["chronoturin", "--sort-every", "10", "--cpu"]).validate(&limits).is_err());
+}
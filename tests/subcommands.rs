@@ -0,0 +1,164 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::checkpoint::CHECKPOINT_VERSION;
+use chronoturin::config::Command;
+use chronoturin::snapshot::parse_frame_range;
+use chronoturin::{
+    check_star_count, cpu_reference, draw_run, presets, replay_plan, write_dump, Camera, Checkpoint, CheckpointRun, ColorBy, DilationMode,
+    DumpFormat, DumpInfo, FrameRange, GalaxyState, OutputLayout, RenderSettings, SimConfig, Snapshot, SnapshotKind, SnapshotRun, Star,
+    StarSummary, TYPE_A, TYPE_B,
+};
+
+use common::parse_command;
+
+fn star(x: f32, data_type: f32, active_flag: f32) -> Star {
+    Star { x, vx: 3.0, vy: 4.0, data_type, time_debt: 0.5, active_flag, ..common::star() }
+}
+
+#[test]
+fn frame_run_subcommands_fix_their_layout() {
+    let limits = cpu_reference::limits();
+    let simulate = parse_command(&["simulate"]);
+    assert!(simulate.sequential && simulate.no_png);
+    assert!(!parse_command(&["simulate", "--images"]).no_png);
+    assert!(parse_command(&["--diff", "simulate"]).validate(&limits).is_err());
+    // Frame-run flags that other subcommands refuse.
+    assert!(parse_command(&["--report", "r.json", "--profiles", "simulate"]).validate(&limits).is_ok());
+    assert!(parse_command(&["--profiles", "--report", "r.json", "compare"]).validate(&limits).is_ok());
+
+    let compare = parse_command(&["compare"]);
+    assert!(compare.measures_divergence() && !compare.diff && !compare.sequential);
+    assert!(parse_command(&["--sequential", "compare"]).validate(&limits).is_err());
+    assert!(!parse_command(&[]).measures_divergence());
+
+    assert!(matches!(parse_command(&["inspect", "x.bin"]).command, Some(Command::Inspect(_))));
+    assert!(SimConfig::try_parse_from(["chronoturin", "render"]).is_err());
+    assert!(parse_command(&["--cpu", "render", "a.csv"]).validate(&limits).is_ok());
+    assert!(parse_command(&["--raster", "gpu", "render", "a.csv"]).validate(&limits).is_err());
+    assert!(parse_command(&["--profiles", "render", "a.csv"]).validate(&limits).is_err());
+}
+
+#[test]
+fn summaries_skip_centrals_and_non_finite_stars() {
+    let stars =
+        [star(-2.0, TYPE_A, 1.0), star(2.0, TYPE_B, 0.0), star(4.0, 3.0, 0.0), star(f32::NAN, TYPE_A, 1.0), presets::central_star(10.0)];
+    let summary = StarSummary::measure(&stars);
+    assert_eq!((summary.stars, summary.centrals, summary.non_finite), (5, 1, 1));
+    assert_eq!(summary.by_type, [1, 1, 0, 1]);
+    assert_eq!(summary.bounds, Some(([-2.0, 0.0, 0.0], [4.0, 0.0, 0.0])));
+    assert_eq!(summary.center_of_mass, [4.0 / 3.0, 0.0, 0.0]);
+    assert_eq!(summary.mean_speed, 5.0);
+    assert_eq!(summary.mean_debt, 0.5);
+    assert!((summary.active_fraction - 1.0 / 3.0).abs() < 1e-12);
+    assert!(summary.lines()[0].starts_with("Stars: 5 (1 central, 1 non-finite) | By type: 0: 1, 1: 1, 2: 0, 3: 1"));
+}
+
+#[test]
+fn snapshots_read_dumps_and_checkpoints() {
+    let dir = std::env::temp_dir().join(format!("chronoturin-snapshots-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let stars = vec![star(1.0, TYPE_A, 1.0), star(-1.0, TYPE_B, 0.0)];
+    let info = DumpInfo { mode: DilationMode::Chronoturin.name(), frame: 12, sim_time: 3.0 };
+
+    // The sidecar decides a binary dump's mode and frame, wherever the file is.
+    let bin = dir.join("anything.bin");
+    write_dump(&bin, &stars, None, DumpFormat::Bin, &info, None).unwrap();
+    let snapshot = Snapshot::read(&bin).unwrap();
+    assert_eq!(snapshot.kind, SnapshotKind::BinDump { sidecar: true });
+    let run = &snapshot.runs[0];
+    assert_eq!((run.mode, run.frame, run.sim_time), (Some(DilationMode::Chronoturin), Some(12), Some(3.0)));
+    assert!(snapshot.describe(&bin).contains("[CHRONOTURIN] frame 12 | sim time 3.000"));
+
+    // A CSV dump only has its output-layout name to go on.
+    let csv = dir.join("dump_newton_004.csv");
+    write_dump(&csv, &stars, None, DumpFormat::Csv, &info, None).unwrap();
+    let run = &Snapshot::read(&csv).unwrap().runs[0];
+    assert_eq!((run.mode, run.frame, run.sim_time), (Some(DilationMode::Newtonian), Some(4), None));
+    assert_eq!(run.stars.len(), 2);
+    let plain = dir.join("stars.csv");
+    std::fs::rename(&csv, &plain).unwrap();
+    assert_eq!(Snapshot::read(&plain).unwrap().runs[0].mode, None);
+
+    let config = SimConfig::default();
+    let checkpoint = Checkpoint {
+        seed: 9,
+        pass: 0,
+        next_frame: 5,
+        manifest: None,
+        runs: [DilationMode::Newtonian, DilationMode::Chronoturin]
+            .map(|mode| CheckpointRun { mode, state: GalaxyState::new(2.5, mode.as_uniform(), &config), stars: stars.clone() })
+            .into(),
+    };
+    let path = dir.join("checkpoint.bin");
+    checkpoint.save(&path).unwrap();
+    let snapshot = Snapshot::read(&path).unwrap();
+    assert_eq!(snapshot.kind, SnapshotKind::Checkpoint { seed: 9, pass: 0, next_frame: 5 });
+    assert_eq!(snapshot.runs.len(), 2);
+    assert_eq!(
+        (snapshot.runs[1].mode, snapshot.runs[1].frame, snapshot.runs[1].sim_time),
+        (Some(DilationMode::Chronoturin), Some(4), Some(2.5))
+    );
+    let header = format!("checkpoint v{} | seed 9 | pass 1 | resumes at frame 5 | 2 runs", CHECKPOINT_VERSION);
+    assert!(snapshot.describe(&path).contains(&header));
+
+    let layout = OutputLayout::prepare(&SimConfig::default()).unwrap();
+    assert_eq!(layout.render_path("checkpoint_chrono"), std::path::PathBuf::from("render_checkpoint_chrono.png"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn replays_follow_frame_order_and_refuse_mismatches() {
+    // Camera and image flags work after the subcommand too.
+    let config = parse_command(&["--width", "300", "render", "--snapshots", "d", "--fov", "1200", "--color-by", "debt"]);
+    assert_eq!((config.width, config.fov, config.color_by), (300, 1200.0, ColorBy::Debt));
+    let Some(Command::Render(args)) = parse_command(&["render", "--snapshots", "d", "--frames", "50..100"]).command else { panic!() };
+    assert_eq!(args.frames, Some(FrameRange { start: 50, end: Some(100) }));
+    assert!(SimConfig::try_parse_from(["chronoturin", "render", "a.csv", "--snapshots", "d"]).is_err());
+    assert_eq!(parse_frame_range("..7"), Ok(FrameRange { start: 0, end: Some(7) }));
+    assert!(parse_frame_range("5..").unwrap().contains(1000));
+    assert!(parse_frame_range("5..5").is_err() && parse_frame_range("5").is_err());
+
+    let dir = std::env::temp_dir().join(format!("chronoturin-replay-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let stars = vec![star(1.0, TYPE_A, 1.0), star(-1.0, TYPE_B, 0.0)];
+    for frame in 0..4 {
+        for mode in [DilationMode::Chronoturin, DilationMode::Newtonian] {
+            let info = DumpInfo { mode: mode.name(), frame, sim_time: frame as f32 };
+            write_dump(&dir.join(format!("{}_{:03}.bin", mode.file_stem(), frame)), &stars, None, DumpFormat::Bin, &info, None).unwrap();
+        }
+    }
+    std::fs::write(dir.join("notes.csv"), "not a dump").unwrap();
+    let plan = replay_plan(&dir, Some(FrameRange { start: 1, end: Some(3) })).unwrap();
+    assert_eq!(plan.iter().map(|f| f.frame).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(
+        plan[0].files,
+        [(DilationMode::Newtonian, dir.join("newton_001.bin")), (DilationMode::Chronoturin, dir.join("chrono_001.bin"))]
+    );
+    assert!(replay_plan(&dir, Some(FrameRange { start: 9, end: None })).is_err());
+
+    // Frame 3 without Chronoturin no longer lines up.
+    std::fs::remove_file(dir.join("chrono_003.bin")).unwrap();
+    assert!(replay_plan(&dir, None).is_err());
+    assert_eq!(replay_plan(&dir, Some(FrameRange { start: 0, end: Some(3) })).unwrap().len(), 3);
+
+    // A truncated dump is reported by name, against its sidecar or the first snapshot.
+    let truncated = dir.join("newton_002.bin");
+    std::fs::write(&truncated, &std::fs::read(&truncated).unwrap()[..40]).unwrap();
+    assert!(Snapshot::read(&truncated).unwrap_err().to_string().starts_with(&truncated.display().to_string()));
+    let mut first = None;
+    check_star_count(&dir.join("a.bin"), 2, &mut first).unwrap();
+    let error = check_star_count(&dir.join("b.bin"), 1, &mut first).unwrap_err().to_string();
+    assert!(error.contains("b.bin: holds 1 stars") && error.contains("a.bin holds 2"), "{}", error);
+
+    // Only runs that know their mode, frame and sim time get the status line.
+    let config = SimConfig::default();
+    let (camera, settings) = (Camera::from_config(&config), RenderSettings::from_config(&config));
+    let run = Snapshot::read(&dir.join("newton_001.bin")).unwrap().runs.remove(0);
+    let labeled = draw_run(&run, &camera, &settings, true);
+    let bare = draw_run(&SnapshotRun { sim_time: None, ..run.clone() }, &camera, &settings, true);
+    assert_ne!(labeled, bare);
+    assert_eq!(bare, draw_run(&run, &camera, &settings, false));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
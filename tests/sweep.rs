@@ -0,0 +1,107 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::config::Command;
+use chronoturin::sweep::{grid, summary_table};
+use chronoturin::{presets, DilationMode, DisplacementStats, SimConfig, Simulation, SweepPoint, SweepResult};
+
+fn sweep_args(config: &SimConfig) -> &chronoturin::SweepArgs {
+    match &config.command {
+        Some(Command::Sweep(args)) => args,
+        _ => panic!("no sweep subcommand"),
+    }
+}
+
+#[test]
+fn grid_is_the_cartesian_product_with_stars_outermost() {
+    let config = SimConfig::parse_from(["chronoturin", "sweep", "--stars", "100,200", "--dilation-threshold", "0.1,0.5,1"]);
+    let points = grid(sweep_args(&config), &config);
+    let pairs: Vec<(u32, f32)> = points.iter().map(|p| (p.stars, p.dilation_threshold)).collect();
+    assert_eq!(pairs, [(100, 0.1), (100, 0.5), (100, 1.0), (200, 0.1), (200, 0.5), (200, 1.0)]);
+    assert!(points.iter().all(|p| p.max_debt == config.max_debt));
+    assert_eq!(points[1].label(), "s100_t0.5_d50");
+}
+
+#[test]
+fn omitted_lists_fall_back_to_the_shared_flags() {
+    let config = SimConfig::parse_from(["chronoturin", "--stars", "500", "--dilation-threshold", "0.3", "sweep", "--max-debt", "10,20"]);
+    assert!(config.is_sweep());
+    let points = grid(sweep_args(&config), &config);
+    assert_eq!(
+        points,
+        [
+            SweepPoint { stars: 500, dilation_threshold: 0.3, max_debt: 10.0 },
+            SweepPoint { stars: 500, dilation_threshold: 0.3, max_debt: 20.0 },
+        ]
+    );
+    let applied = points[1].apply(&config);
+    assert_eq!((applied.stars, applied.dilation_threshold, applied.max_debt), (500, 0.3, 20.0));
+}
+
+#[test]
+fn summary_has_one_row_per_completed_configuration() {
+    let result = SweepResult {
+        point: SweepPoint { stars: 10000, dilation_threshold: 0.5, max_debt: 50.0 },
+        frames: 20,
+        newton_ms: 30.0,
+        chrono_ms: 10.0,
+        active_fraction: 0.05,
+        error: DisplacementStats { mean: 1.25, p99: 4.5, max: 9.0, non_finite: 0 },
+    };
+    let table = summary_table(&[result]);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[2], "| 10000 | 0.5 | 50 | 20 | 30.00 | 10.00 | 3.0× | 5.0% | 1.2500 | 4.5000 |");
+}
+
+#[test]
+fn resized_and_redilated_simulations_match_fresh_ones() {
+    let (device, queue) = require_gpu!();
+    let small = SimConfig { stars: 256, seed: Some(5), ..SimConfig::default() };
+    let large = SimConfig { stars: 768, dilation_threshold: 0.2, max_debt: 10.0, ..small.clone() };
+    let run = |sim: &mut Simulation| {
+        sim.reset();
+        sim.set_mode(DilationMode::Chronoturin);
+        sim.step_and_read(4)
+    };
+
+    let mut reused = Simulation::new(device.clone(), queue.clone(), &small)
+        .unwrap()
+        .resized(&SimConfig { dilation_threshold: 0.9, ..large.clone() }, presets::from_config(&large, 5))
+        .unwrap();
+    reused.set_dilation(large.dilation_threshold, large.max_debt);
+    let mut fresh = Simulation::new(device, queue, &large).unwrap();
+    assert_eq!(reused.star_count(), 768);
+    // Workgroups may race on partner reads, so compare to within rounding rather than bitwise.
+    let (a, b) = (run(&mut reused), run(&mut fresh));
+    let err = a.iter().zip(&b).map(|(a, b)| (a.x - b.x).abs().max((a.y - b.y).abs()).max((a.z - b.z).abs())).fold(0.0, f32::max);
+    assert!(err < 1e-3, "max position error {}", err);
+    assert_eq!(a.iter().filter(|s| s.active_flag > 0.5).count(), b.iter().filter(|s| s.active_flag > 0.5).count());
+}
+
+// bench --compare-kernels: only the force kernels are compiled again.
+#[test]
+fn resizing_may_switch_to_the_unified_kernel_but_not_the_accel() {
+    let (device, queue) = require_gpu!();
+    let config = SimConfig { stars: 256, seed: Some(5), ..SimConfig::default() };
+    let unified = SimConfig { unified_kernel: true, ..config.clone() };
+    let sim = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+    let run = |mut sim: Simulation| {
+        sim.set_mode(DilationMode::Chronoturin);
+        sim.step_and_read(3)
+    };
+    let reused = run(sim.resized(&unified, presets::from_config(&config, 5)).unwrap());
+    let fresh = run(Simulation::new(device, queue, &unified).unwrap());
+    let err = reused.iter().zip(&fresh).map(|(a, b)| (a.x - b.x).abs().max((a.y - b.y).abs()).max((a.z - b.z).abs())).fold(0.0, f32::max);
+    assert!(err < 1e-3, "max position error {}", err);
+
+    let grid = SimConfig { accel: chronoturin::Accel::Grid, ..config.clone() };
+    assert!(sim.resized(&grid, presets::from_config(&config, 5)).is_err());
+}
+
+#[test]
+fn sweeps_refuse_the_cpu_backend() {
+    let config = SimConfig::parse_from(["chronoturin", "--cpu", "sweep", "--stars", "100"]);
+    assert!(config.validate(&chronoturin::cpu_reference::limits()).is_err());
+}
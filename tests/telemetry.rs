@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use regex_automata::meta::Regex;
+
+use chronoturin::Telemetry;
+
+fn sample(text: &str, pattern: &str) -> Vec<String> {
+    let regex = Regex::new(pattern).unwrap();
+    text.lines()
+        .filter_map(|line| {
+            let mut captures = regex.create_captures();
+            regex.captures(line, &mut captures);
+            captures.get_group(1).map(|value| line[value.range()].to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn scrapes_parse_one_sample_per_series() {
+    let telemetry = Telemetry::new();
+    let written = Arc::new(AtomicU64::new(0));
+    telemetry.count_images("7", written.clone());
+    for frame in 0..3 {
+        telemetry.record_frame("7", "newtonian", frame, 20.0, Some(1.5), 1.0);
+        telemetry.record_frame("7", "chronoturin", frame, 8.0, None, 0.25);
+        written.fetch_add(1, Ordering::Relaxed);
+    }
+    // An embedder's run id goes through label escaping.
+    telemetry.record_frame("a \"b\"", "newtonian", 0, 10.0, None, 0.5);
+    let text = telemetry.render();
+
+    let run = r#"\{run="7",mode="(?:newtonian|chronoturin)"\}"#;
+    for (name, kind, value) in [
+        ("frame", "gauge", r"(2)"),
+        ("frames_per_second", "gauge", r"(50\.000|125\.000)"),
+        ("active_fraction", "gauge", r"(1\.000000|0\.250000)"),
+        ("frames_total", "counter", r"(3)"),
+    ] {
+        assert!(text.contains(&format!("# TYPE chronoturin_{} {}\n", name, kind)), "{}", text);
+        let pattern = format!(r"^chronoturin_{}{} {}$", name, run, value);
+        assert_eq!(sample(&text, &pattern).len(), 2, "{}\n{}", pattern, text);
+    }
+    // Chronoturin's steps have no GPU timing, so only Newtonian has the series.
+    assert_eq!(sample(&text, r#"^chronoturin_gpu_step_ms\{run="7",mode="newtonian"\} (1\.5000)$"#), ["1.5000"]);
+    assert!(!text.contains("chronoturin_gpu_step_ms{run=\"7\",mode=\"chronoturin\"}"));
+    assert_eq!(sample(&text, r#"^chronoturin_images_written_total\{run="7"\} ([0-9]+)$"#), ["3"]);
+    assert_eq!(sample(&text, r#"^chronoturin_frame\{run="a \\"b\\"",mode="newtonian"\} ([0-9]+)$"#), ["0"]);
+
+    // Every line is a comment or a sample.
+    let line = Regex::new(r#"^(?:# (?:HELP|TYPE) chronoturin_[a-z_]+ .+|chronoturin_[a-z_]+\{(?:[a-z]+="(?:[^"\\]|\\.)*",?)+\} [0-9.]+)$"#)
+        .unwrap();
+    for l in text.lines() {
+        assert!(line.is_match(l), "{}", l);
+    }
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::{
+    cpu_reference, render_frame, render_trails, Camera, CpuSimulation, DilationMode, RenderSettings, SimConfig, Simulation, Star, Stepper,
+    TYPE_A, TYPE_B,
+};
+
+fn star_at(x: f32, data_type: f32) -> Star {
+    Star { x, y: 0.0, z: 0.0, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type, time_debt: 0.0, active_flag: 0.0 }
+}
+
+#[test]
+fn trails_fade_behind_the_frame_in_each_stars_colour() {
+    let cam = Camera::look_at([0.0, 0.0, -1000.0], [0.0; 3], [0.0, 1.0, 0.0], 200, 100, 800.0);
+    let settings = RenderSettings::legacy();
+    // One red star moving right and one blue star moving left, one pixel-and-a-bit per sub-step.
+    let at = |k: f32| vec![star_at(-50.0 + 2.0 * k, TYPE_A), star_at(50.0 - 2.0 * k, TYPE_B)];
+    let trails: Vec<Vec<Star>> = (0..3).map(|k| at(k as f32)).collect();
+    let img = render_trails(&at(3.0), &trails, &cam, &settings, 0.5, false);
+    assert_eq!(img, render_trails(&at(3.0), &trails, &cam, &settings, 0.5, false));
+
+    // The frame itself draws as it would without trails.
+    let plain = render_frame(&at(3.0), &cam, &settings, false);
+    let px = |img: &image::RgbImage, x: f32| *img.get_pixel((100.0 + 0.8 * x) as u32, 50);
+    assert_eq!(px(&img, -44.0), px(&plain, -44.0));
+    // Older sub-steps are dimmer, and keep their star's colour.
+    let (newest, oldest) = (px(&img, -46.0), px(&img, -50.0));
+    assert!(newest[0] > 0 && newest[0] >= oldest[0] && oldest[0] > 0, "{:?} {:?}", newest, oldest);
+    assert!(newest[0] > newest[2] && px(&img, 50.0)[2] > px(&img, 50.0)[0]);
+    assert_eq!(px(&plain, -50.0), image::Rgb([0, 0, 0]));
+}
+
+#[test]
+fn gpu_trails_follow_the_sub_steps() {
+    let (device, queue) = require_gpu!();
+    let config =
+        SimConfig { stars: 2048, seed: Some(9), chunk_stars: Some(1024), steps_per_frame: 4, trails: true, ..SimConfig::default() };
+    let mut gpu = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+    let mut plain = Simulation::new(device, queue, &SimConfig { trails: false, ..config.clone() }).unwrap();
+    gpu.enable_trails();
+    for sim in [&mut gpu, &mut plain] {
+        sim.set_mode(DilationMode::Newtonian);
+    }
+    assert!(gpu.chunk_count() > 1);
+    let mut cpu = CpuSimulation::new(&config);
+    Stepper::set_mode(&mut cpu, DilationMode::Newtonian);
+
+    let (ticket_g, ticket_c) = (gpu.submit_frame(4), cpu.submit_frame(4));
+    let (g, c) = (gpu.finish_frame(ticket_g), cpu.finish_frame(ticket_c));
+    assert_eq!((g.trails.len(), c.trails.len()), (3, 3));
+    for (g, c) in g.trails.iter().zip(&c.trails) {
+        assert_eq!(g.len(), 2048);
+        let err = g.iter().zip(c).map(|(g, c)| (g.x - c.x).abs().max((g.y - c.y).abs()).max((g.z - c.z).abs())).fold(0.0, f32::max);
+        assert!(err < 1e-2, "max trail position error {}", err);
+        assert!(g.iter().zip(c).all(|(g, c)| g.data_type == c.data_type));
+    }
+    // The extra readback is what the metrics report.
+    let ticket = plain.submit_frame(4);
+    let without = plain.finish_frame(ticket);
+    assert!(without.trails.is_empty());
+    assert!(g.readback_bytes >= without.readback_bytes + 3 * 2048 * 16, "{} vs {} bytes", g.readback_bytes, without.readback_bytes);
+
+    // Stats frames read back no trails either.
+    let ticket = gpu.submit_stats(4);
+    let stats = gpu.finish_frame(ticket);
+    assert!(stats.trails.is_empty() && stats.readback_bytes < without.readback_bytes);
+}
+
+#[test]
+fn trails_need_sub_steps_and_a_cpu_drawn_frame() {
+    let limits = cpu_reference::limits();
+    let parse = |args: &[&str]| SimConfig::parse_from(["chronoturin", "--trails"].iter().chain(args)).validate(&limits);
+    assert!(parse(&[]).is_ok());
+    assert!(parse(&["--trail-decay", "1"]).is_ok());
+    assert!(parse(&["--trail-decay", "0"]).is_err());
+    assert!(parse(&["--steps-per-frame", "1"]).is_err());
+    assert!(parse(&["--raster", "gpu"]).is_err());
+    assert!(parse(&["--color-by", "speed"]).is_err());
+    assert!(parse(&["--no-images"]).is_err());
+    assert!(parse(&["bench"]).is_err());
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use clap::Parser;
+use image::RgbImage;
+
+use chronoturin::overlay::TRACKED_COLOR;
+use chronoturin::trajectory::trajectory_header;
+use chronoturin::{cpu_reference, draw_tracked, presets, Camera, SimConfig, Star, TrajectoryWriter};
+
+fn star(x: f32, z: f32) -> Star {
+    Star { x, z, vx: 0.5, time_debt: 0.25, active_flag: 1.0, ..common::star() }
+}
+
+#[test]
+fn track_ids_must_name_a_star() {
+    let parse = |args: &[&str]| SimConfig::parse_from(["chronoturin", "--stars", "100"].iter().chain(args));
+    let limits = cpu_reference::limits();
+    assert_eq!(parse(&["--track", "0,42,99"]).track, [0, 42, 99]);
+    assert!(parse(&["--track", "0,42,99"]).validate(&limits).is_ok());
+    let error = parse(&["--track", "7,100"]).validate(&limits).unwrap_err().to_string();
+    assert!(error.contains("--track 100") && error.contains("0 to 99"), "{}", error);
+    // The central mass is one more star, ID 0.
+    assert!(parse(&["--track", "100", "--central-mass", "50"]).validate(&limits).is_ok());
+    assert!(parse(&["--track", "1", "--readback", "packed"]).validate(&limits).is_err());
+    assert!(parse(&["--track", "1", "bench"]).validate(&limits).is_err());
+    assert!(parse(&["--track", "1", "simulate"]).validate(&limits).is_ok());
+    assert_eq!(presets::from_config(&parse(&["--central-mass", "50"]), 1)[0].mass, 50.0);
+}
+
+#[test]
+fn each_tracked_star_gets_its_own_trajectory() {
+    let dir = std::env::temp_dir().join(format!("chronoturin-trajectory-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |id: u32| dir.join(format!("trajectory_{}.csv", id));
+    let stars = [star(1.0, 0.0), star(2.0, 0.0), star(3.5, 0.0)];
+    let mut writer = TrajectoryWriter::open(&[2, 0], path, false).unwrap();
+    writer.write("NEWTONIAN", 0, 0.25, &stars).unwrap();
+    writer.write("CHRONOTURIN", 0, 0.25, &stars).unwrap();
+    drop(writer);
+    // Resuming appends below the rows already written, without a second header.
+    let mut writer = TrajectoryWriter::open(&[2], path, true).unwrap();
+    writer.write("NEWTONIAN", 1, 0.5, &stars).unwrap();
+    drop(writer);
+
+    let text = std::fs::read_to_string(path(2)).unwrap();
+    let rows: Vec<&str> = text.lines().collect();
+    assert_eq!(rows[0], trajectory_header());
+    assert_eq!(rows[0], "mode,frame,sim_time,x,y,z,vx,vy,vz,mass,data_type,time_debt,active_flag");
+    assert_eq!(
+        &rows[1..],
+        [
+            "NEWTONIAN,0,0.25,3.5,0,0,0.5,0,0,1,0,0.25,1",
+            "CHRONOTURIN,0,0.25,3.5,0,0,0.5,0,0,1,0,0.25,1",
+            "NEWTONIAN,1,0.5,3.5,0,0,0.5,0,0,1,0,0.25,1"
+        ]
+    );
+    assert!(std::fs::read_to_string(path(0)).unwrap().lines().nth(1).unwrap().starts_with("NEWTONIAN,0,0.25,1,"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tracked_stars_are_marked_with_crosses() {
+    let cam = Camera::look_at([0.0, 0.0, -100.0], [0.0; 3], [0.0, 1.0, 0.0], 64, 64, 100.0);
+    // One star at the image center, one behind the camera, and an ID past the end.
+    let stars = [star(0.0, 0.0), star(0.0, -200.0)];
+    let mut img = RgbImage::new(64, 64);
+    draw_tracked(&mut img, &stars, &[1, 7], &cam);
+    assert!(img.pixels().all(|p| p.0 == [0, 0, 0]));
+    draw_tracked(&mut img, &stars, &[0], &cam);
+    for (x, y) in [(32, 32), (26, 32), (38, 32), (32, 26), (32, 38)] {
+        assert_eq!(*img.get_pixel(x, y), TRACKED_COLOR, "({}, {})", x, y);
+    }
+    assert_eq!(img.pixels().filter(|p| **p == TRACKED_COLOR).count(), 25);
+
+    // Crosses at the edge are clipped.
+    let mut img = RgbImage::new(64, 64);
+    draw_tracked(&mut img, &[Star { x: -31.9, ..star(0.0, 0.0) }], &[0], &cam);
+    assert_eq!(img.pixels().filter(|p| **p == TRACKED_COLOR).count(), 13 + 6);
+}
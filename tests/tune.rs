@@ -0,0 +1,60 @@
+mod common;
+
+use clap::Parser;
+
+use chronoturin::config::Command;
+use chronoturin::{cpu_reference, tune, tune_report_json, CpuSimulation, SimConfig, Stepper, TuneArgs};
+
+use common::seeded;
+
+fn args(error_budget: f32) -> TuneArgs {
+    TuneArgs { error_budget, probe_frames: 3, probes: 5, ..TuneArgs::default() }
+}
+
+fn run(args: &TuneArgs) -> chronoturin::TuneTrace {
+    let mut newton = CpuSimulation::new(&seeded(64, 11));
+    let mut chrono = Stepper::fork(&newton);
+    tune::tune(&mut newton, &mut chrono, &seeded(64, 11), args, |_| true)
+}
+
+#[test]
+fn a_loose_budget_takes_the_whole_range() {
+    let trace = run(&args(1e3));
+    assert_eq!(trace.probes.iter().map(|p| p.threshold).collect::<Vec<_>>(), [0.05, 2.0]);
+    assert_eq!(trace.recommended, Some(2.0));
+}
+
+// Probes past the first that meets the budget bisect toward the one that does not.
+#[test]
+fn bisection_keeps_the_largest_threshold_within_budget() {
+    let loose = run(&args(1e3));
+    let budget = 0.5 * (loose.probes[0].error + loose.probes[1].error);
+    let trace = run(&args(budget));
+    assert_eq!(trace.probes.len(), 5);
+    assert_eq!(trace.probes[2].threshold, 1.025);
+    let best = trace.recommended.unwrap();
+    assert!(trace.probes.iter().all(|p| p.within(budget) == (p.threshold <= best)), "{}", trace.table());
+    // Each probe starts from the same galaxy, so the same threshold measures the same.
+    assert_eq!((trace.probes[0].divergence, trace.probes[0].error), (loose.probes[0].divergence, loose.probes[0].error));
+}
+
+#[test]
+fn a_budget_nothing_meets_recommends_nothing() {
+    let trace = run(&args(1e-9));
+    assert_eq!((trace.probes.len(), trace.recommended), (1, None));
+    let json: serde_json::Value = serde_json::from_str(&tune_report_json(&trace)).unwrap();
+    assert!(json["tuning"]["recommended_threshold"].is_null());
+    assert_eq!(json["tuning"]["probes"][0]["within_budget"].as_bool(), Some(false));
+}
+
+#[test]
+fn tune_flags_are_checked() {
+    let check = |args: &[&str]| common::parse(args).validate(&cpu_reference::limits()).map_err(|e| e.to_string());
+    assert!(check(&["--cpu", "--report", "tune.json", "tune", "--error-budget", "0.02", "--run"]).is_ok());
+    assert!(check(&["tune", "--error-budget", "0"]).unwrap_err().contains("--error-budget must be positive"));
+    assert!(check(&["tune", "--min-threshold", "1", "--max-threshold", "0.5"]).is_err());
+    assert!(check(&["tune", "--probes", "1"]).is_err());
+    assert!(check(&["--diff", "tune"]).unwrap_err().starts_with("tune probes both modes in lockstep"));
+    let parsed = SimConfig::parse_from(["chronoturin", "tune", "--probe-frames", "12"]);
+    assert!(matches!(parsed.command, Some(Command::Tune(TuneArgs { probe_frames: 12, probes: 8, .. }))));
+}
@@ -0,0 +1,28 @@
+use std::path::{Path, PathBuf};
+
+use chronoturin::video::video_path_for;
+use chronoturin::{ChronoError, DilationMode, VideoEncoder};
+use image::RgbImage;
+
+#[test]
+fn each_mode_gets_its_own_file() {
+    let path = Path::new("renders/galaxy.mp4");
+    assert_eq!(video_path_for(path, DilationMode::Newtonian), PathBuf::from("renders/galaxy_newton.mp4"));
+    assert_eq!(video_path_for(path, DilationMode::Chronoturin), PathBuf::from("renders/galaxy_chrono.mp4"));
+}
+
+#[test]
+fn missing_encoder_is_reported() {
+    let result = VideoEncoder::spawn(Path::new("no-such-ffmpeg-binary"), Path::new("out.mp4"), 8, 8, 30);
+    assert!(matches!(result, Err(ChronoError::Encoder(_))));
+}
+
+#[cfg(unix)]
+#[test]
+fn encoder_death_mid_run_aborts() {
+    // `true` ignores its arguments and exits at once, closing the pipe.
+    let mut encoder = VideoEncoder::spawn(Path::new("true"), Path::new("out.mp4"), 1024, 1024, 30).unwrap();
+    let img = RgbImage::new(1024, 1024);
+    let result = (0..10).try_for_each(|_| encoder.write_frame(&img));
+    assert!(matches!(result, Err(ChronoError::Encoder(_))));
+}
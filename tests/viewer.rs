@@ -0,0 +1,65 @@
+#![cfg(feature = "viewer")]
+
+mod common;
+
+use clap::Parser;
+
+use chronoturin::{cpu_reference, project, Camera, DilationMode, PointRenderer, SimConfig, Simulation, ViewUniform};
+
+#[test]
+fn points_land_on_the_pixels_project_picks() {
+    let (device, queue) = require_gpu!();
+    let config = SimConfig { stars: 2000, seed: Some(9), width: 64, height: 64, ..SimConfig::default() };
+    let mut sim = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+    let stars = sim.step_and_read(0);
+    let camera = Camera::from_config(&config);
+
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d { width: 64, height: 64, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 64 * 64 * 4,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let renderer = PointRenderer::new(&sim, format, &config.palette());
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    let target = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    renderer.draw(&queue, &mut encoder, &target, &ViewUniform::new(&camera, DilationMode::Newtonian));
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &readback,
+            layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(64 * 4), rows_per_image: None },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+    readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let pixels = readback.slice(..).get_mapped_range().to_vec();
+
+    let lit = |(x, y): (u32, u32)| pixels[(y * 64 + x) as usize * 4..][..3].iter().any(|&c| c > 0);
+    let projected: Vec<(u32, u32)> = stars.iter().filter_map(|s| project(s, &camera)).collect();
+    assert!(projected.len() > 100);
+    // Rasterization may round a star that sits on a pixel edge into its neighbour.
+    let hits = projected.iter().filter(|&&p| lit(p)).count();
+    assert!(hits * 100 >= projected.len() * 99, "{} of {} projected stars drawn", hits, projected.len());
+}
+
+#[test]
+fn view_refuses_the_cpu_backend_and_subcommands() {
+    let limits = cpu_reference::limits();
+    assert!(SimConfig::parse_from(["chronoturin", "--view", "--cpu"]).validate(&limits).is_err());
+    assert!(SimConfig::parse_from(["chronoturin", "--view", "sweep", "--stars", "100"]).validate(&limits).is_err());
+    assert!(SimConfig::parse_from(["chronoturin", "--view"]).validate(&limits).is_ok());
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use chronoturin::{
+    cpu_reference, presets, ChronoError, CpuSimulation, OnDivergence, SimConfig, Simulation, Star, WatchdogCounts, DEAD_FLAG,
+};
+
+use common::parse;
+
+// The default sphere with one star's x set to NaN.
+fn poisoned(config: &SimConfig) -> Vec<Star> {
+    let mut stars = presets::from_config(config, 0);
+    stars[7].x = f32::NAN;
+    stars
+}
+
+#[test]
+fn watchdog_flags_are_validated() {
+    let limits = cpu_reference::limits();
+    assert!(parse(&["--divergence-bound", "1e4", "--on-divergence", "abort"]).validate(&limits).is_ok());
+    assert!(parse(&["--divergence-bound", "0"]).validate(&limits).is_err());
+    assert!(parse(&["--divergence-bound=-1"]).validate(&limits).is_err());
+    assert!(parse(&["--divergence-bound", "NaN"]).validate(&limits).is_err());
+    assert_eq!(parse(&[]).on_divergence, OnDivergence::Warn);
+}
+
+#[test]
+fn counts_skip_the_dead_and_name_each_condition() {
+    let star = Star { x: 1.0, y: 2.0, z: 3.0, vx: 0.0, vy: 0.0, vz: 0.0, mass: 1.0, data_type: 0.0, time_debt: 0.0, active_flag: 0.0 };
+    let stars = [
+        star,
+        Star { x: f32::NAN, vx: 5e6, ..star },
+        Star { y: f32::INFINITY, ..star },
+        Star { z: -2e6, ..star },
+        Star { x: f32::NAN, active_flag: DEAD_FLAG, ..star },
+    ];
+    let counts = WatchdogCounts::measure(&stars, 1e6);
+    assert_eq!(counts, WatchdogCounts { nan_position: 2, nan_velocity: 0, far_position: 1, fast_velocity: 1 });
+    assert!(counts.fired() && !WatchdogCounts::measure(&stars[..1], 1e6).fired());
+    assert_eq!(
+        counts.describe(),
+        "2 with a non-finite position, 1 with a position past --divergence-bound, 1 with a velocity past --divergence-bound"
+    );
+    let error = ChronoError::Diverged { mode: "NEWTONIAN", frame: 0, conditions: counts.describe() };
+    assert!(error.to_string().starts_with("NEWTONIAN diverged at frame 0: 2 with"));
+}
+
+#[test]
+fn the_cpu_watchdog_fires_on_frame_zero() {
+    let config = parse(&["--stars", "200", "--cpu"]);
+    let mut cpu = CpuSimulation::with_stars(&config, poisoned(&config));
+    let data = cpu.run_frame(1);
+    assert!(data.watchdog.nan_position >= 1, "{:?}", data.watchdog);
+    assert!(!CpuSimulation::new(&config).run_frame(1).watchdog.fired());
+}
+
+#[test]
+fn the_gpu_watchdog_fires_on_frame_zero() {
+    let (device, queue) = require_gpu!();
+    let config = parse(&["--stars", "200"]);
+    let mut gpu = Simulation::with_stars(device.clone(), queue.clone(), &config, poisoned(&config)).unwrap();
+    let ticket = gpu.submit_frame(1);
+    let data = gpu.finish_frame(ticket);
+    assert!(data.watchdog.nan_position >= 1, "{:?}", data.watchdog);
+    // Stats-only frames carry the counts too.
+    let ticket = gpu.submit_stats(1);
+    assert!(gpu.finish_frame(ticket).watchdog.fired());
+
+    let mut healthy = Simulation::new(device.clone(), queue.clone(), &config).unwrap();
+    let ticket = healthy.submit_frame(1);
+    let data = healthy.finish_frame(ticket);
+    assert!(!data.watchdog.fired(), "{:?}", data.watchdog);
+    // The sphere is wider than a bound of 10, and the GPU counts it like the CPU does.
+    let tight = parse(&["--stars", "200", "--divergence-bound", "10"]);
+    let mut gpu = Simulation::new(device, queue, &tight).unwrap();
+    let ticket = gpu.submit_frame(1);
+    let data = gpu.finish_frame(ticket);
+    assert!(data.watchdog.far_position > 0);
+    assert_eq!(data.watchdog, WatchdogCounts::measure(&data.stars, 10.0));
+}